@@ -11,6 +11,28 @@ pub const INTER_REGULAR: Font = Font::with_name("Inter Regular");
 pub const INTER_BOLD: Font = Font::with_name("Inter Bold");
 pub const LUCIDE: Font = Font::with_name("lucide");
 
+/// How long sidecar writes wait for further edits, so a burst of changes
+/// such as dragging an annotation is written once.
+const SIDECAR_FLUSH_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Write `sidecars` on a blocking thread.
+pub fn write_sidecars(
+    sidecars: Vec<(std::path::PathBuf, crate::models::DocumentSidecar)>,
+) -> Task<Message> {
+    if sidecars.is_empty() {
+        return Task::none();
+    }
+    Task::future(async move {
+        tokio::task::spawn_blocking(move || {
+            for (path, sidecar) in &sidecars {
+                crate::storage::save_sidecar(path, sidecar);
+            }
+        })
+        .await
+    })
+    .discard()
+}
+
 pub mod icons {
     pub const OPEN: &str = "\u{e247}";
     pub const SIDEBAR: &str = "\u{e115}";
@@ -59,9 +81,12 @@ pub struct PdfBullApp {
     pub search_query: String,
     pub search_pending: Option<String>,
     pub page_input: String,
+    /// Set briefly after an unparseable page number is submitted.
+    pub page_input_error: bool,
     pub status_message: Option<String>,
     pub annotation_mode: Option<crate::models::PendingAnnotationKind>,
     pub annotation_drag: Option<crate::models::AnnotationDrag>,
+    pub annotation_edit: Option<crate::models::AnnotationEdit>,
     pub engine: Option<EngineState>,
     pub loaded: bool,
     pub rendering_set: std::collections::HashSet<RenderTarget>,
@@ -69,6 +94,8 @@ pub struct PdfBullApp {
     pub modifiers: iced::keyboard::Modifiers,
     pub cursor_position: Option<iced::Point>,
     pub last_session_save: Instant,
+    /// A sidecar write is waiting on [`SIDECAR_FLUSH_DELAY`].
+    pub sidecar_flush_pending: bool,
     pub sidebar_animation: animation::Animation<f32>,
     pub sidebar_mode: crate::models::SidebarMode,
     pub reading_mode: crate::models::ReadingMode,
@@ -81,6 +108,7 @@ pub struct PdfBullApp {
     pub markup_active: bool,
     pub show_watermark_prompt: bool,
     pub watermark_input: String,
+    pub watermark_options: crate::pdf_engine::WatermarkOptions,
     pub show_signature_creator: bool,
     pub signature_lines: Vec<Vec<(f32, f32)>>,
     pub signature_drag: Option<(f32, f32)>,
@@ -94,6 +122,28 @@ pub struct PdfBullApp {
     pub show_signatures_detail: bool,
     pub table_mode_active: bool,
     pub active_ribbon_tab: crate::models::RibbonTab,
+    /// Recently closed tabs as `(path, current_page, zoom)`, newest last.
+    pub recently_closed: Vec<(std::path::PathBuf, usize, f32)>,
+    /// Note being edited in the popup as `(annotation index, draft text)`.
+    pub note_editor: Option<(usize, String)>,
+    pub show_print_dialog: bool,
+    pub print_range: crate::models::PrintRange,
+    pub print_range_input: String,
+    /// Source page indices chosen in the print dialog; `None` prints all.
+    pub print_pages: Option<Vec<usize>>,
+    pub show_extract_dialog: bool,
+    pub extract_range_input: String,
+    pub show_nup_dialog: bool,
+    pub show_barcode_dialog: bool,
+    pub barcode_input: String,
+    pub barcode_kind: crate::barcode::BarcodeKind,
+    pub nup_layout: crate::impose::NupLayout,
+    pub show_poster_dialog: bool,
+    /// Sheet each poster tile is printed on.
+    pub poster_paper: crate::flow::PageSize,
+    /// Points each poster tile repeats of its neighbours, for trimming and
+    /// taping.
+    pub poster_overlap: f32,
 }
 
 impl Default for PdfBullApp {
@@ -114,9 +164,11 @@ impl Default for PdfBullApp {
             search_query: String::new(),
             search_pending: None,
             page_input: "1".to_string(),
+            page_input_error: false,
             status_message: None,
             annotation_mode: None,
             annotation_drag: None,
+            annotation_edit: None,
             engine: None,
             loaded: false,
             rendering_set: std::collections::HashSet::new(),
@@ -124,6 +176,7 @@ impl Default for PdfBullApp {
             modifiers: iced::keyboard::Modifiers::default(),
             cursor_position: None,
             last_session_save: Instant::now(),
+            sidecar_flush_pending: false,
             sidebar_animation: animation::Animation::new(0.0),
             sidebar_mode: crate::models::SidebarMode::default(),
             reading_mode: crate::models::ReadingMode::default(),
@@ -136,6 +189,7 @@ impl Default for PdfBullApp {
             markup_active: false,
             show_watermark_prompt: false,
             watermark_input: "CONFIDENTIAL".to_string(),
+            watermark_options: crate::pdf_engine::WatermarkOptions::default(),
             show_signature_creator: false,
             signature_lines: Vec::new(),
             signature_drag: None,
@@ -149,6 +203,22 @@ impl Default for PdfBullApp {
             show_signatures_detail: false,
             table_mode_active: false,
             active_ribbon_tab: crate::models::RibbonTab::default(),
+            recently_closed: Vec::new(),
+            note_editor: None,
+            show_print_dialog: false,
+            print_range: crate::models::PrintRange::default(),
+            print_range_input: String::new(),
+            print_pages: None,
+            show_extract_dialog: false,
+            extract_range_input: String::new(),
+            show_nup_dialog: false,
+            show_barcode_dialog: false,
+            barcode_input: String::new(),
+            barcode_kind: crate::barcode::BarcodeKind::Qr(Default::default()),
+            nup_layout: crate::impose::NupLayout::default(),
+            show_poster_dialog: false,
+            poster_paper: crate::flow::PageSize::LETTER,
+            poster_overlap: 36.0,
         }
     }
 }
@@ -163,6 +233,68 @@ impl PdfBullApp {
         self.tabs.get_mut(self.active_tab)
     }
 
+    /// Approximate decoded bytes held by rendered pages across all tabs.
+    #[must_use]
+    pub fn render_cache_bytes(&self) -> usize {
+        self.tabs.iter().map(DocumentTab::rendered_bytes).sum()
+    }
+
+    /// Evict least-recently-used rendered pages across all tabs until the
+    /// total fits in `settings.max_render_cache_bytes`. The active tab's
+    /// current and visible pages are pinned.
+    pub fn enforce_render_budget(&mut self) {
+        let budget = self.settings.max_render_cache_bytes;
+        let active = self.active_tab;
+        if let Some(tab) = self.tabs.get_mut(active) {
+            tab.touch_visible_pages();
+        }
+
+        let mut total = self.render_cache_bytes();
+        if total <= budget {
+            return;
+        }
+
+        let mut candidates = Vec::new();
+        for (tab_idx, tab) in self.tabs.iter().enumerate() {
+            let visible = tab.get_visible_pages();
+            for (&page_idx, usage) in &tab.view_state.rendered_usage {
+                if tab_idx == active
+                    && (page_idx == tab.current_page || visible.contains(&page_idx))
+                {
+                    continue;
+                }
+                candidates.push((usage.last_used, tab_idx, page_idx, usage.bytes));
+            }
+        }
+        candidates.sort_unstable_by_key(|&(last_used, ..)| last_used);
+
+        for (_, tab_idx, page_idx, bytes) in candidates {
+            if total <= budget {
+                break;
+            }
+            self.tabs[tab_idx].view_state.remove_rendered_page(page_idx);
+            total = total.saturating_sub(bytes);
+        }
+    }
+
+    /// Start a new render generation for the active tab so renders queued for
+    /// the previous zoom/rotation/filter are skipped by the engine and their
+    /// late results are ignored by `PageRendered`.
+    pub fn bump_render_generation(&mut self) {
+        let Some(tab) = self.current_tab_mut() else {
+            return;
+        };
+        tab.render_generation += 1;
+        let doc_id = tab.id;
+        let generation = tab.render_generation;
+
+        self.rendering_set
+            .retain(|target| !matches!(target, RenderTarget::Page(id, _) if *id == doc_id));
+        if let Some(engine) = &self.engine {
+            engine.set_render_generation(doc_id, generation);
+        }
+    }
+
     pub fn save_session(&mut self) {
         if !self.settings.restore_session {
             return;
@@ -212,6 +344,7 @@ impl PdfBullApp {
             filter,
             auto_crop,
             page_width,
+            generation,
         ) = {
             let Some(tab) = self.current_tab_mut() else {
                 return Task::none();
@@ -226,6 +359,7 @@ impl PdfBullApp {
                 tab.render_filter,
                 tab.auto_crop,
                 tab.page_width,
+                tab.render_generation,
             )
         };
 
@@ -256,12 +390,7 @@ impl PdfBullApp {
             // Translate visual page index to actual source page via page_mapping.
             let (actual_page, page_rotation) = if let Some(tab) = self.current_tab() {
                 let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-                let rot = tab
-                    .page_rotations
-                    .get(&actual_page)
-                    .copied()
-                    .unwrap_or(rotation);
-                (actual_page, rot)
+                (actual_page, tab.effective_rotation(actual_page))
             } else {
                 (page_idx, rotation)
             };
@@ -286,6 +415,7 @@ impl PdfBullApp {
                             doc_id_cloned,
                             actual_page,
                             options,
+                            Some(generation),
                             resp_tx,
                         ))
                         .await;
@@ -294,7 +424,9 @@ impl PdfBullApp {
                         .unwrap_or_else(|_| Err(crate::models::PdfError::EngineDied));
                     (page_idx, current_scale, res)
                 },
-                move |(page_idx, scale, res)| Message::PageRendered(doc_id, page_idx, scale, res),
+                move |(page_idx, scale, res)| {
+                    Message::PageRendered(doc_id, page_idx, scale, generation, res)
+                },
             ));
         }
 
@@ -313,7 +445,7 @@ impl PdfBullApp {
 
                 let (thumb_actual, thumb_rotation) = if let Some(tab) = self.current_tab() {
                     let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-                    let rot = tab.page_rotations.get(&actual_page).copied().unwrap_or(0);
+                    let rot = tab.effective_rotation(actual_page);
                     (actual_page, rot)
                 } else {
                     (page_idx, 0)
@@ -354,9 +486,48 @@ impl PdfBullApp {
         Task::batch(tasks)
     }
 
+    /// Snapshot the sidecar of every tab whose bookmarks, annotations or
+    /// rotations changed, marking them clean.
+    fn take_dirty_sidecars(&mut self) -> Vec<(std::path::PathBuf, crate::models::DocumentSidecar)> {
+        self.tabs
+            .iter_mut()
+            .filter(|t| t.sidecar_dirty)
+            .map(|tab| {
+                tab.sidecar_dirty = false;
+                (tab.path.clone(), tab.sidecar())
+            })
+            .collect()
+    }
+
+    /// Start the timer for writing changed sidecars, unless one is running.
+    /// Edits made meanwhile are picked up when it fires.
+    fn schedule_sidecar_flush(&mut self) -> Task<Message> {
+        if self.sidecar_flush_pending || !self.tabs.iter().any(|t| t.sidecar_dirty) {
+            return Task::none();
+        }
+        self.sidecar_flush_pending = true;
+        Task::perform(
+            async { tokio::time::sleep(SIDECAR_FLUSH_DELAY).await },
+            |()| Message::FlushSidecars,
+        )
+    }
+
+    /// Write changed sidecars on a blocking thread, off the UI thread.
+    pub fn flush_sidecars(&mut self) -> Task<Message> {
+        self.sidecar_flush_pending = false;
+        write_sidecars(self.take_dirty_sidecars())
+    }
+
+    /// Write changed sidecars before the app exits.
+    pub fn flush_sidecars_blocking(&mut self) {
+        for (path, sidecar) in self.take_dirty_sidecars() {
+            crate::storage::save_sidecar(&path, &sidecar);
+        }
+    }
+
     pub fn update(&mut self, message: Message) -> Task<Message> {
         let old_status = self.status_message.clone();
-        let task = handle_message(self, message);
+        let task = Task::batch([handle_message(self, message), self.schedule_sidecar_flush()]);
         if self.status_message.is_some() && self.status_message != old_status {
             let msg = self.status_message.clone().unwrap();
             let msg_clone = msg.clone();
@@ -398,10 +569,15 @@ impl PdfBullApp {
     }
 
     pub fn subscription(&self) -> iced::Subscription<Message> {
-        let events = iced::event::listen_with(|event, _status, _id| match event {
-            iced::Event::Window(
-                iced::window::Event::CloseRequested | iced::window::Event::FileDropped(_),
-            )
+        let events = iced::event::listen_with(|event, status, _id| match event {
+            // Keys a focused widget such as a text input has handled are not
+            // shortcuts; Ctrl+C there copies the input's own selection.
+            iced::Event::Keyboard(iced::keyboard::Event::KeyPressed { .. })
+                if status == iced::event::Status::Captured =>
+            {
+                None
+            }
+            iced::Event::Window(iced::window::Event::CloseRequested)
             | iced::Event::Mouse(
                 iced::mouse::Event::CursorMoved { .. } | iced::mouse::Event::WheelScrolled { .. },
             )
@@ -412,6 +588,13 @@ impl PdfBullApp {
             _ => None,
         });
 
+        let file_drops = iced::event::listen_with(|event, _status, _id| match event {
+            iced::Event::Window(iced::window::Event::FileDropped(path)) => {
+                Some(Message::FileDropped(path))
+            }
+            _ => None,
+        });
+
         let ipc_sub = iced::Subscription::run_with("ipc-stream", |_| {
             iced::stream::channel(
                 10,
@@ -462,7 +645,7 @@ impl PdfBullApp {
 
         let paths: Vec<std::path::PathBuf> = self.tabs.iter().map(|t| t.path.clone()).collect();
         if paths.is_empty() {
-            return iced::Subscription::batch(vec![events, ipc_sub]);
+            return iced::Subscription::batch(vec![events, file_drops, ipc_sub]);
         }
 
         let watch_sub = iced::Subscription::run_with(("file-watch", paths), |(_id, paths)| {
@@ -509,6 +692,6 @@ impl PdfBullApp {
             )
         });
 
-        iced::Subscription::batch(vec![events, watch_sub, ipc_sub])
+        iced::Subscription::batch(vec![events, file_drops, watch_sub, ipc_sub])
     }
 }
@@ -0,0 +1,279 @@
+//! Reading zip archives.
+//!
+//! Comic books, EPUB and XPS files are all zip containers. Only what those
+//! need is supported: the central directory, and stored or deflated
+//! entries. Zip64 and encrypted entries are rejected.
+
+use crate::models::{PdfError, PdfResult};
+use std::io::Read;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+/// The end-of-directory record is 22 bytes plus a comment of up to 64K.
+const MAX_EOCD_SEARCH: usize = 22 + 0xFFFF;
+
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    pub name: String,
+    method: u16,
+    flags: u16,
+    compressed_size: usize,
+    pub size: usize,
+    local_offset: usize,
+}
+
+#[derive(Debug)]
+pub struct ZipArchive {
+    data: Vec<u8>,
+    entries: Vec<ZipEntry>,
+}
+
+fn u16_at(data: &[u8], pos: usize) -> PdfResult<u16> {
+    data.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| PdfError::from("Zip archive is truncated"))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> PdfResult<u32> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| PdfError::from("Zip archive is truncated"))
+}
+
+/// Whether `data` starts like a zip archive.
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04")
+}
+
+impl ZipArchive {
+    /// Read the central directory of `data`; entries are only
+    /// decompressed when read.
+    pub fn new(data: Vec<u8>) -> PdfResult<Self> {
+        let search_start = data.len().saturating_sub(MAX_EOCD_SEARCH);
+        let eocd = (search_start..data.len().saturating_sub(21))
+            .rev()
+            .find(|&pos| u32_at(&data, pos).ok() == Some(END_OF_DIRECTORY))
+            .ok_or_else(|| PdfError::from("Zip archive has no central directory"))?;
+        let count = u16_at(&data, eocd + 10)?;
+        let mut pos = u32_at(&data, eocd + 16)? as usize;
+
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            if u32_at(&data, pos)? != CENTRAL_HEADER {
+                return Err(PdfError::from("Zip central directory is corrupt"));
+            }
+            let name_len = u16_at(&data, pos + 28)? as usize;
+            let extra_len = u16_at(&data, pos + 30)? as usize;
+            let comment_len = u16_at(&data, pos + 32)? as usize;
+            let name = data
+                .get(pos + 46..pos + 46 + name_len)
+                .ok_or_else(|| PdfError::from("Zip archive is truncated"))?;
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                flags: u16_at(&data, pos + 8)?,
+                method: u16_at(&data, pos + 10)?,
+                compressed_size: u32_at(&data, pos + 20)? as usize,
+                size: u32_at(&data, pos + 24)? as usize,
+                local_offset: u32_at(&data, pos + 42)? as usize,
+            });
+            pos += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(Self { data, entries })
+    }
+
+    /// Entries in central directory order, directories included.
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    pub fn find(&self, name: &str) -> Option<&ZipEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// The uncompressed contents of the named entry.
+    pub fn read(&self, name: &str) -> PdfResult<Vec<u8>> {
+        let entry = self
+            .find(name)
+            .ok_or_else(|| PdfError::from(format!("Zip archive has no entry '{name}'")))?;
+        self.read_entry(entry)
+    }
+
+    pub fn read_entry(&self, entry: &ZipEntry) -> PdfResult<Vec<u8>> {
+        if entry.flags & 1 != 0 {
+            return Err(PdfError::from(format!(
+                "Zip entry '{}' is encrypted",
+                entry.name
+            )));
+        }
+        let pos = entry.local_offset;
+        if u32_at(&self.data, pos)? != LOCAL_HEADER {
+            return Err(PdfError::from("Zip local header is corrupt"));
+        }
+        // The local header's own sizes may be zero when a data descriptor
+        // follows, so only its name and extra lengths are used.
+        let start = pos
+            + 30
+            + u16_at(&self.data, pos + 26)? as usize
+            + u16_at(&self.data, pos + 28)? as usize;
+        let raw = self
+            .data
+            .get(start..start + entry.compressed_size)
+            .ok_or_else(|| PdfError::from("Zip archive is truncated"))?;
+        match entry.method {
+            0 => Ok(raw.to_vec()),
+            8 => {
+                let mut out = Vec::with_capacity(entry.size);
+                flate2::read::DeflateDecoder::new(raw)
+                    .take(entry.size as u64)
+                    .read_to_end(&mut out)
+                    .map_err(|e| {
+                        PdfError::from(format!("Zip entry '{}' is corrupt: {e}", entry.name))
+                    })?;
+                Ok(out)
+            }
+            method => Err(PdfError::from(format!(
+                "Zip entry '{}' uses unsupported compression method {method}",
+                entry.name
+            ))),
+        }
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Resolve a URI reference from a file in directory `base` to an entry
+/// name. A leading `/` starts from the root of the archive.
+pub fn resolve_path(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let base = if href.starts_with('/') { "" } else { base };
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    let decoded = percent_decode(href);
+    for part in decoded.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// An archive of stored entries, for tests of the formats built on zip.
+#[cfg(test)]
+pub(crate) fn write_stored_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in files {
+        let mut crc = flate2::Crc::new();
+        crc.update(data);
+        let offset = out.len() as u32;
+        let mut common = Vec::new();
+        common.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        common.extend_from_slice(&crc.sum().to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&[0, 0]);
+
+        out.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        out.extend_from_slice(&common);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        directory.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+        directory.extend_from_slice(&[20, 0]);
+        directory.extend_from_slice(&common);
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let directory_offset = out.len() as u32;
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    out.extend_from_slice(&directory_offset.to_le_bytes());
+    out.extend_from_slice(&[0, 0]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_reads_stored_entries() {
+        let zip = write_stored_zip(&[("a.txt", b"hello"), ("dir/b.txt", b"")]);
+        assert!(is_zip(&zip));
+        let archive = ZipArchive::new(zip).unwrap();
+        let names: Vec<_> = archive.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["a.txt", "dir/b.txt"]);
+        assert_eq!(archive.read("a.txt").unwrap(), b"hello");
+        assert_eq!(archive.read("dir/b.txt").unwrap(), b"");
+        assert!(archive.read("missing").is_err());
+    }
+
+    #[test]
+    fn test_reads_deflated_entry() {
+        let text = b"comic comic comic comic comic".repeat(10);
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&text).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        // Turn a stored entry into a deflated one by patching its method
+        // and compressed size in both headers.
+        let mut zip = write_stored_zip(&[("t", &compressed)]);
+        let central = zip.len() - 22 - 47;
+        for header in [0, central + 2] {
+            zip[header + 8..header + 10].copy_from_slice(&8u16.to_le_bytes());
+            zip[header + 22..header + 26].copy_from_slice(&(text.len() as u32).to_le_bytes());
+        }
+        let archive = ZipArchive::new(zip).unwrap();
+        assert_eq!(archive.read("t").unwrap(), text);
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        assert_eq!(
+            resolve_path("OEBPS", "text/a%20b.xhtml#p1"),
+            "OEBPS/text/a b.xhtml"
+        );
+        assert_eq!(
+            resolve_path("OEBPS/text", "../images/c.png"),
+            "OEBPS/images/c.png"
+        );
+        assert_eq!(resolve_path("", "c.xhtml"), "c.xhtml");
+        assert_eq!(
+            resolve_path("Documents/1", "/Resources/f.odttf"),
+            "Resources/f.odttf"
+        );
+    }
+}
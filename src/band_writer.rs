@@ -0,0 +1,475 @@
+//! Writing images a band of rows at a time.
+//!
+//! A [`BandWriter`] writes the file header up front and each band as it
+//! arrives, so exporting a poster-sized page needs memory for one band
+//! rather than the whole image. PNG data is deflated as one stream and
+//! flushed out as an `IDAT` chunk per band.
+//!
+//! Photoshop files store each channel in turn rather than pixel by pixel,
+//! so [`encode_psd`] takes the whole image at once; it is the one format
+//! here that keeps CMYK and spot colour channels apart.
+
+use crate::models::{ColorSpace, PdfError, PdfResult, Pixmap};
+use flate2::Crc;
+use flate2::write::ZlibEncoder;
+use std::io::Write;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BandFormat {
+    /// Binary PGM or PPM.
+    Pnm,
+    /// Netpbm PAM, which also holds CMYK and alpha.
+    Pam,
+    Png,
+}
+
+pub struct BandWriter<W: Write> {
+    out: W,
+    row_len: usize,
+    height: u32,
+    rows_written: u32,
+    deflate: Option<ZlibEncoder<Vec<u8>>>,
+}
+
+fn io_error(e: &std::io::Error) -> PdfError {
+    PdfError::from(format!("Failed to write image: {e}"))
+}
+
+fn write_chunk(out: &mut impl Write, kind: [u8; 4], data: &[u8]) -> PdfResult<()> {
+    let len = u32::try_from(data.len()).map_err(|_| PdfError::from("PNG chunk is too large"))?;
+    let mut crc = Crc::new();
+    crc.update(&kind);
+    crc.update(data);
+    out.write_all(&len.to_be_bytes())
+        .and_then(|()| out.write_all(&kind))
+        .and_then(|()| out.write_all(data))
+        .and_then(|()| out.write_all(&crc.sum().to_be_bytes()))
+        .map_err(|e| io_error(&e))
+}
+
+impl<W: Write> BandWriter<W> {
+    /// Start an 8-bit gray, RGB or (PAM only) CMYK image, optionally with
+    /// alpha after the colour components (PNG and PAM), and write its
+    /// header.
+    pub fn new(
+        mut out: W,
+        format: BandFormat,
+        width: u32,
+        height: u32,
+        colorspace: ColorSpace,
+        alpha: bool,
+    ) -> PdfResult<Self> {
+        if width == 0 || height == 0 {
+            return Err(PdfError::from("Image has no pixels"));
+        }
+        let components = colorspace.components() + usize::from(alpha);
+        let deflate = match format {
+            BandFormat::Pnm => {
+                let magic = match (colorspace, alpha) {
+                    (ColorSpace::DeviceGray, false) => "P5",
+                    (ColorSpace::DeviceRgb, false) => "P6",
+                    (ColorSpace::DeviceCmyk, _) => {
+                        return Err(PdfError::from("CMYK images can't be written as PNM"));
+                    }
+                    (_, true) => return Err(PdfError::from("PNM images can't have alpha")),
+                };
+                write!(out, "{magic}\n{width} {height}\n255\n").map_err(|e| io_error(&e))?;
+                None
+            }
+            BandFormat::Pam => {
+                let tuple_type = match colorspace {
+                    ColorSpace::DeviceGray => "GRAYSCALE",
+                    ColorSpace::DeviceRgb => "RGB",
+                    ColorSpace::DeviceCmyk => "CMYK",
+                };
+                let alpha = if alpha { "_ALPHA" } else { "" };
+                write!(
+                    out,
+                    "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH {components}\nMAXVAL 255\n\
+                     TUPLTYPE {tuple_type}{alpha}\nENDHDR\n"
+                )
+                .map_err(|e| io_error(&e))?;
+                None
+            }
+            BandFormat::Png => {
+                let color_type = match (colorspace, alpha) {
+                    (ColorSpace::DeviceGray, false) => 0,
+                    (ColorSpace::DeviceRgb, false) => 2,
+                    (ColorSpace::DeviceGray, true) => 4,
+                    (ColorSpace::DeviceRgb, true) => 6,
+                    (ColorSpace::DeviceCmyk, _) => {
+                        return Err(PdfError::from("CMYK images can't be written as PNG"));
+                    }
+                };
+                out.write_all(PNG_SIGNATURE).map_err(|e| io_error(&e))?;
+                let mut header = Vec::with_capacity(13);
+                header.extend_from_slice(&width.to_be_bytes());
+                header.extend_from_slice(&height.to_be_bytes());
+                // 8 bits, no interlacing, standard compression and filters.
+                header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+                write_chunk(&mut out, *b"IHDR", &header)?;
+                Some(ZlibEncoder::new(Vec::new(), flate2::Compression::default()))
+            }
+        };
+        Ok(Self {
+            out,
+            row_len: width as usize * components,
+            height,
+            rows_written: 0,
+            deflate,
+        })
+    }
+
+    /// Write the next rows, which must be whole and not run past the
+    /// image's height.
+    pub fn write_band(&mut self, samples: &[u8]) -> PdfResult<()> {
+        if !samples.len().is_multiple_of(self.row_len) {
+            return Err(PdfError::from("Band doesn't hold whole rows"));
+        }
+        let rows = u32::try_from(samples.len() / self.row_len)
+            .ok()
+            .filter(|&rows| rows <= self.height - self.rows_written)
+            .ok_or_else(|| PdfError::from("Band runs past the end of the image"))?;
+        match &mut self.deflate {
+            None => self.out.write_all(samples).map_err(|e| io_error(&e))?,
+            Some(deflate) => {
+                for row in samples.chunks_exact(self.row_len) {
+                    // Each row starts with its filter type, here none.
+                    deflate
+                        .write_all(&[0])
+                        .and_then(|()| deflate.write_all(row))
+                        .map_err(|e| io_error(&e))?;
+                }
+                deflate.flush().map_err(|e| io_error(&e))?;
+                let compressed = std::mem::take(deflate.get_mut());
+                if !compressed.is_empty() {
+                    write_chunk(&mut self.out, *b"IDAT", &compressed)?;
+                }
+            }
+        }
+        self.rows_written += rows;
+        Ok(())
+    }
+
+    /// Finish the image once every row has been written, returning the
+    /// output.
+    pub fn close(mut self) -> PdfResult<W> {
+        if self.rows_written != self.height {
+            return Err(PdfError::from(format!(
+                "Only {} of {} rows were written",
+                self.rows_written, self.height
+            )));
+        }
+        if let Some(deflate) = self.deflate.take() {
+            let rest = deflate.finish().map_err(|e| io_error(&e))?;
+            if !rest.is_empty() {
+                write_chunk(&mut self.out, *b"IDAT", &rest)?;
+            }
+            write_chunk(&mut self.out, *b"IEND", &[])?;
+        }
+        self.out.flush().map_err(|e| io_error(&e))?;
+        Ok(self.out)
+    }
+}
+
+/// Encode an 8-bit pixmap in one go.
+pub fn encode_pixmap(pixmap: &Pixmap, format: BandFormat) -> PdfResult<Vec<u8>> {
+    if pixmap.bits_per_component != 8 {
+        return Err(PdfError::from("Only 8-bit images can be written"));
+    }
+    let mut writer = BandWriter::new(
+        Vec::new(),
+        format,
+        pixmap.width,
+        pixmap.height,
+        pixmap.colorspace,
+        false,
+    )?;
+    writer.write_band(&pixmap.samples)?;
+    writer.close()
+}
+
+/// A spot colour channel of a Photoshop file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotChannel {
+    pub name: String,
+    /// The colour Photoshop previews the ink in, as CMYK from 0 to 1.
+    pub cmyk: [f32; 4],
+}
+
+/// An image resource block: signature, id, empty name and padded data.
+fn psd_resource(out: &mut Vec<u8>, id: u16, data: &[u8]) {
+    out.extend_from_slice(b"8BIM");
+    out.extend_from_slice(&id.to_be_bytes());
+    out.extend_from_slice(&[0, 0]);
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Encode an 8-bit image as an uncompressed Photoshop file. `samples`
+/// interleave the colour components and then one tint per spot channel,
+/// 0 for no ink. Photoshop stores ink amounts inverted, so CMYK and spot
+/// channels are flipped on the way out.
+pub fn encode_psd(
+    width: u32,
+    height: u32,
+    colorspace: ColorSpace,
+    spots: &[SpotChannel],
+    samples: &[u8],
+) -> PdfResult<Vec<u8>> {
+    let colors = colorspace.components();
+    let channels = colors + spots.len();
+    let pixels = width as usize * height as usize;
+    if pixels == 0 {
+        return Err(PdfError::from("Image has no pixels"));
+    }
+    if samples.len() != pixels * channels {
+        return Err(PdfError::from("Image data does not match its dimensions"));
+    }
+    // Photoshop allows 56 channels and 30000 pixels a side.
+    if channels > 56 || width > 30_000 || height > 30_000 {
+        return Err(PdfError::from("Image is too large for a Photoshop file"));
+    }
+    let mode: u16 = match colorspace {
+        ColorSpace::DeviceGray => 1,
+        ColorSpace::DeviceRgb => 3,
+        ColorSpace::DeviceCmyk => 4,
+    };
+
+    let mut out = Vec::with_capacity(pixels * channels + 1024);
+    out.extend_from_slice(b"8BPS");
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&[0; 6]);
+    out.extend_from_slice(&(channels as u16).to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&8u16.to_be_bytes());
+    out.extend_from_slice(&mode.to_be_bytes());
+    // No colour mode data.
+    out.extend_from_slice(&0u32.to_be_bytes());
+
+    let mut resources = Vec::new();
+    if !spots.is_empty() {
+        // Channel names as Pascal strings (resource 1006).
+        let mut names = Vec::new();
+        for spot in spots {
+            let name: Vec<u8> = spot
+                .name
+                .chars()
+                .map(|c| u8::try_from(c).ok().filter(u8::is_ascii).unwrap_or(b'_'))
+                .take(255)
+                .collect();
+            names.push(name.len() as u8);
+            names.extend(name);
+        }
+        psd_resource(&mut resources, 1006, &names);
+        // Display info (resource 1077): each channel is a spot colour
+        // shown in its CMYK preview colour at full opacity.
+        let mut display = 1u32.to_be_bytes().to_vec();
+        for spot in spots {
+            display.extend_from_slice(&2u16.to_be_bytes());
+            for ink in spot.cmyk {
+                let inverted = (1.0 - ink.clamp(0.0, 1.0)) * 65535.0;
+                display.extend_from_slice(&(inverted.round() as u16).to_be_bytes());
+            }
+            display.extend_from_slice(&100u16.to_be_bytes());
+            display.extend_from_slice(&[2, 0]);
+        }
+        psd_resource(&mut resources, 1077, &display);
+    }
+    out.extend_from_slice(&(resources.len() as u32).to_be_bytes());
+    out.extend(resources);
+    // No layers.
+    out.extend_from_slice(&0u32.to_be_bytes());
+
+    // Raw, uncompressed channel planes.
+    out.extend_from_slice(&0u16.to_be_bytes());
+    let inks = colorspace == ColorSpace::DeviceCmyk;
+    for channel in 0..channels {
+        let invert = inks || channel >= colors;
+        out.extend(
+            samples
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .map(|&v| if invert { 255 - v } else { v }),
+        );
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    const WIDTH: u32 = 2000;
+    const HEIGHT: u32 = 3000;
+
+    fn gradient() -> Vec<u8> {
+        (0..HEIGHT)
+            .flat_map(|y| (0..WIDTH).flat_map(move |x| [x as u8, y as u8, (x ^ y) as u8]))
+            .collect()
+    }
+
+    fn write(format: BandFormat, samples: &[u8], band_rows: usize) -> Vec<u8> {
+        let mut writer = BandWriter::new(
+            Vec::new(),
+            format,
+            WIDTH,
+            HEIGHT,
+            ColorSpace::DeviceRgb,
+            false,
+        )
+        .unwrap();
+        for band in samples.chunks(band_rows * WIDTH as usize * 3) {
+            writer.write_band(band).unwrap();
+        }
+        writer.close().unwrap()
+    }
+
+    /// The rows of a PNG written by [`BandWriter`], whose rows are all
+    /// unfiltered.
+    fn decode_png(png: &[u8]) -> Vec<u8> {
+        assert!(png.starts_with(PNG_SIGNATURE));
+        let mut pos = PNG_SIGNATURE.len();
+        let mut zlib = Vec::new();
+        let mut chunks = Vec::new();
+        while pos < png.len() {
+            let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let kind = &png[pos + 4..pos + 8];
+            let data = &png[pos + 8..pos + 8 + len];
+            let mut crc = Crc::new();
+            crc.update(&png[pos + 4..pos + 8 + len]);
+            assert_eq!(crc.sum().to_be_bytes(), png[pos + 8 + len..pos + 12 + len]);
+            if kind == b"IDAT" {
+                zlib.extend_from_slice(data);
+            }
+            chunks.push(kind.to_vec());
+            pos += 12 + len;
+        }
+        assert_eq!(chunks.first().unwrap(), b"IHDR");
+        assert_eq!(chunks.last().unwrap(), b"IEND");
+
+        let mut raw = Vec::new();
+        flate2::read::ZlibDecoder::new(&zlib[..])
+            .read_to_end(&mut raw)
+            .unwrap();
+        raw.chunks_exact(WIDTH as usize * 3 + 1)
+            .flat_map(|row| {
+                assert_eq!(row[0], 0);
+                row[1..].to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_banded_png_matches_single_shot() {
+        let samples = gradient();
+        let banded = write(BandFormat::Png, &samples, 64);
+        let single = write(BandFormat::Png, &samples, HEIGHT as usize);
+        assert_eq!(decode_png(&banded), samples);
+        assert_eq!(decode_png(&single), samples);
+    }
+
+    #[test]
+    fn test_banded_pnm_matches_single_shot() {
+        let samples = gradient();
+        let banded = write(BandFormat::Pnm, &samples, 100);
+        assert_eq!(banded, write(BandFormat::Pnm, &samples, HEIGHT as usize));
+        let header = b"P6\n2000 3000\n255\n";
+        assert!(banded.starts_with(header));
+        assert_eq!(&banded[header.len()..], &samples[..]);
+    }
+
+    #[test]
+    fn test_rejects_partial_rows_and_short_images() {
+        let mut writer = BandWriter::new(
+            Vec::new(),
+            BandFormat::Pnm,
+            4,
+            2,
+            ColorSpace::DeviceGray,
+            false,
+        )
+        .unwrap();
+        assert!(writer.write_band(&[0; 6]).is_err());
+        writer.write_band(&[0; 4]).unwrap();
+        assert!(writer.write_band(&[0; 8]).is_err());
+        assert!(writer.close().is_err());
+        assert!(
+            BandWriter::new(
+                Vec::new(),
+                BandFormat::Pnm,
+                4,
+                2,
+                ColorSpace::DeviceRgb,
+                true
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_encoded_png_round_trips() {
+        let pixmap = Pixmap {
+            width: 40,
+            height: 30,
+            colorspace: ColorSpace::DeviceRgb,
+            bits_per_component: 8,
+            samples: (0..30u8)
+                .flat_map(|y| (0..40u8).flat_map(move |x| [x * 6, y * 8, 200]))
+                .collect(),
+        };
+        let png = encode_pixmap(&pixmap, BandFormat::Png).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap().to_rgb8();
+        assert_eq!(decoded.dimensions(), (40, 30));
+        assert_eq!(decoded.get_pixel(7, 11).0, [42, 88, 200]);
+        assert_eq!(pixmap.pixel(7, 11), [42, 88, 200]);
+
+        let cmyk = Pixmap {
+            colorspace: ColorSpace::DeviceCmyk,
+            samples: vec![10; 40 * 30 * 4],
+            ..pixmap
+        };
+        assert!(encode_pixmap(&cmyk, BandFormat::Png).is_err());
+        let pam = encode_pixmap(&cmyk, BandFormat::Pam).unwrap();
+        let header = b"P7\nWIDTH 40\nHEIGHT 30\nDEPTH 4\nMAXVAL 255\nTUPLTYPE CMYK\nENDHDR\n";
+        assert!(pam.starts_with(header));
+        assert_eq!(&pam[header.len()..], &cmyk.samples[..]);
+    }
+
+    #[test]
+    fn test_psd_planes_with_spot_channel() {
+        let spot = SpotChannel {
+            name: "PANTONE 185 C".to_string(),
+            cmyk: [0.0, 0.93, 0.79, 0.0],
+        };
+        // Two CMYK + spot pixels: cyan with no spot, then full spot.
+        let samples = [255, 0, 0, 0, 0, 0, 0, 0, 0, 255];
+        let psd = encode_psd(2, 1, ColorSpace::DeviceCmyk, &[spot], &samples).unwrap();
+        let u16_at = |at: usize| u16::from_be_bytes([psd[at], psd[at + 1]]);
+        let u32_at = |at: usize| u32::from_be_bytes(psd[at..at + 4].try_into().unwrap());
+        assert!(psd.starts_with(b"8BPS"));
+        assert_eq!(u16_at(12), 5);
+        assert_eq!((u32_at(14), u32_at(18)), (1, 2));
+        assert_eq!((u16_at(22), u16_at(24)), (8, 4));
+        assert_eq!(u32_at(26), 0);
+        let resources = 34 + u32_at(30) as usize;
+        let resource_data = &psd[34..resources];
+        assert!(resource_data.windows(13).any(|w| w == b"PANTONE 185 C"));
+        assert_eq!(u32_at(resources), 0);
+        assert_eq!(u16_at(resources + 4), 0);
+        // Planes of two pixels each, ink inverted.
+        assert_eq!(
+            &psd[resources + 6..],
+            [0, 255, 255, 255, 255, 255, 255, 255, 255, 0]
+        );
+        assert!(encode_psd(2, 1, ColorSpace::DeviceCmyk, &[], &samples).is_err());
+    }
+}
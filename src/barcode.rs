@@ -0,0 +1,622 @@
+//! Stamping barcodes onto pages and reading them back from renders.
+//!
+//! Code 128 (set B) and QR codes are drawn as vector rectangles, one per run
+//! of dark modules. Reading works on an 8-bit grayscale image, such as a
+//! rendered page or a region of one. QR codes are found and decoded by
+//! `rqrr`. Code 128 symbols are read row by row against the same symbol
+//! table the generator stamps with; FNC4 (Latin-1) isn't supported.
+
+use crate::models::{PdfError, PdfResult};
+use crate::pdf_engine::append_page_overlay;
+use lopdf::Document;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QrErrorCorrection {
+    Low,
+    #[default]
+    Medium,
+    Quartile,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarcodeKind {
+    /// Code 128 set B: printable ASCII.
+    Code128,
+    Qr(QrErrorCorrection),
+}
+
+/// A barcode to stamp: `x`/`y` is the lower-left corner of the symbol in
+/// PDF user space, and `scale` is the width of one module in points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarcodeSpec {
+    pub kind: BarcodeKind,
+    pub data: String,
+    pub page: usize,
+    pub x: f32,
+    pub y: f32,
+    pub scale: f32,
+}
+
+/// Stamp a Code 128 or QR barcode onto one page as vector rectangles.
+pub fn add_barcode(input_path: &str, spec: &BarcodeSpec, output_path: &str) -> PdfResult<String> {
+    /// Code 128 bar height in modules; about a quarter of the symbol
+    /// width for short payloads.
+    const BAR_HEIGHT_MODULES: f32 = 30.0;
+
+    let mut doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+    if doc.is_encrypted() {
+        return Err(PdfError::from(
+            "Cannot add a barcode to an encrypted PDF; save an unprotected copy first",
+        ));
+    }
+    let page_id = doc
+        .get_pages()
+        .into_values()
+        .nth(spec.page)
+        .ok_or(PdfError::PageNotFound(spec.page + 1))?;
+    if spec.scale <= 0.0 {
+        return Err(PdfError::from("Barcode scale must be positive"));
+    }
+
+    let mut content = pdf_writer::Content::new();
+    content.save_state();
+    content.set_fill_gray(0.0);
+    match spec.kind {
+        BarcodeKind::Code128 => {
+            let modules = code128_modules(&spec.data).map_err(PdfError::from)?;
+            draw_module_rows(
+                &mut content,
+                &[modules],
+                (spec.x, spec.y),
+                (spec.scale, spec.scale * BAR_HEIGHT_MODULES),
+            );
+        }
+        BarcodeKind::Qr(level) => {
+            let rows = qr_modules(&spec.data, level).map_err(PdfError::from)?;
+            draw_module_rows(
+                &mut content,
+                &rows,
+                (spec.x, spec.y),
+                (spec.scale, spec.scale),
+            );
+        }
+    }
+    content.restore_state();
+    append_page_overlay(&mut doc, page_id, content.finish().into_vec())?;
+
+    doc.save(output_path)
+        .map_err(|e| PdfError::IoError(e.to_string()))?;
+    tracing::info!(
+        "{:?} barcode added to page {} -> {}",
+        spec.kind,
+        spec.page + 1,
+        output_path
+    );
+    Ok(output_path.to_string())
+}
+
+/// Bar/space widths for Code 128 symbol values 0-105 (105 = Start C).
+const CODE128_WIDTHS: [&[u8; 6]; 106] = [
+    b"212222", b"222122", b"222221", b"121223", b"121322", b"131222", b"122213", b"122312",
+    b"132212", b"221213", b"221312", b"231212", b"112232", b"122132", b"122231", b"113222",
+    b"123122", b"123221", b"223211", b"221132", b"221231", b"213212", b"223112", b"312131",
+    b"311222", b"321122", b"321221", b"312212", b"322112", b"322211", b"212123", b"212321",
+    b"232121", b"111323", b"131123", b"131321", b"112313", b"132113", b"132311", b"211313",
+    b"231113", b"231311", b"112133", b"112331", b"132131", b"113123", b"113321", b"133121",
+    b"313121", b"211331", b"231131", b"213113", b"213311", b"213131", b"311123", b"311321",
+    b"331121", b"312113", b"312311", b"332111", b"314111", b"221411", b"431111", b"111224",
+    b"111422", b"121124", b"121421", b"141122", b"141221", b"112214", b"112412", b"122114",
+    b"122411", b"142112", b"142211", b"241211", b"221114", b"413111", b"241112", b"134111",
+    b"111242", b"121142", b"121241", b"114212", b"124112", b"124211", b"411212", b"421112",
+    b"421211", b"212141", b"214121", b"412121", b"111143", b"111341", b"131141", b"114113",
+    b"114311", b"411113", b"411311", b"113141", b"114131", b"311141", b"411131", b"211412",
+    b"211214", b"211232",
+];
+const CODE128_START_B: usize = 104;
+const CODE128_STOP: &[u8] = b"2331112";
+
+/// Encode `data` as Code 128 set B, returning one flag per module (true =
+/// bar), from the start character through the stop pattern. Quiet zones are
+/// not included.
+fn code128_modules(data: &str) -> Result<Vec<bool>, String> {
+    let values = data
+        .chars()
+        .map(|c| match c {
+            ' '..='\u{7f}' => Ok(c as usize - 32),
+            _ => Err(format!("Code 128 cannot encode '{c}'")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.is_empty() {
+        return Err("Nothing to encode".into());
+    }
+    let checksum = values
+        .iter()
+        .enumerate()
+        .fold(CODE128_START_B, |sum, (i, v)| sum + (i + 1) * v)
+        % 103;
+
+    let mut modules = Vec::new();
+    let symbols = std::iter::once(CODE128_START_B)
+        .chain(values)
+        .chain(std::iter::once(checksum))
+        .map(|v| CODE128_WIDTHS[v].as_slice())
+        .chain(std::iter::once(CODE128_STOP));
+    for widths in symbols {
+        for (i, w) in widths.iter().enumerate() {
+            // Widths alternate bar, space, bar, ... starting with a bar.
+            modules.extend(std::iter::repeat_n(i % 2 == 0, usize::from(w - b'0')));
+        }
+    }
+    Ok(modules)
+}
+
+/// The dark-module grid of a QR code for `data`, row by row from the top.
+pub(crate) fn qr_modules(data: &str, level: QrErrorCorrection) -> Result<Vec<Vec<bool>>, String> {
+    let level = match level {
+        QrErrorCorrection::Low => qrcode::EcLevel::L,
+        QrErrorCorrection::Medium => qrcode::EcLevel::M,
+        QrErrorCorrection::Quartile => qrcode::EcLevel::Q,
+        QrErrorCorrection::High => qrcode::EcLevel::H,
+    };
+    let code = qrcode::QrCode::with_error_correction_level(data.as_bytes(), level)
+        .map_err(|e| format!("Cannot build QR code: {e}"))?;
+    let width = code.width();
+    Ok(code
+        .to_colors()
+        .chunks(width)
+        .map(|row| row.iter().map(|&c| c == qrcode::Color::Dark).collect())
+        .collect())
+}
+
+/// Fill each horizontal run of dark modules in `rows` as one rectangle.
+/// Row 0 is the top; `(x, y)` is the bottom-left corner of the grid.
+fn draw_module_rows(
+    content: &mut pdf_writer::Content,
+    rows: &[Vec<bool>],
+    (x, y): (f32, f32),
+    module: (f32, f32),
+) -> usize {
+    let mut runs = 0;
+    for (r, row) in rows.iter().enumerate() {
+        let row_y = y + (rows.len() - 1 - r) as f32 * module.1;
+        let mut col = 0;
+        while col < row.len() {
+            if !row[col] {
+                col += 1;
+                continue;
+            }
+            let start = col;
+            while col < row.len() && row[col] {
+                col += 1;
+            }
+            content.rect(
+                x + start as f32 * module.0,
+                row_y,
+                (col - start) as f32 * module.0,
+                module.1,
+            );
+            runs += 1;
+        }
+    }
+    content.fill_nonzero();
+    runs
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarcodeResult {
+    pub kind: BarcodeKind,
+    pub data: String,
+    /// Left, top, width and height, in pixels of the scanned image.
+    pub bbox: (f32, f32, f32, f32),
+}
+
+/// A thresholded image; `true` is dark.
+struct Bitmap {
+    width: usize,
+    height: usize,
+    dark: Vec<bool>,
+}
+
+impl Bitmap {
+    /// Threshold halfway between the darkest and lightest pixels, or `None`
+    /// when the image is too flat to hold a barcode.
+    fn new(gray: &[u8], width: usize, height: usize) -> Option<Self> {
+        let (min, max) = gray
+            .iter()
+            .fold((u8::MAX, u8::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        if max.saturating_sub(min) < 32 {
+            return None;
+        }
+        let threshold = u16::midpoint(u16::from(min), u16::from(max));
+        Some(Self {
+            width,
+            height,
+            dark: gray.iter().map(|&v| u16::from(v) < threshold).collect(),
+        })
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.dark[y * self.width + x]
+    }
+}
+
+/// Alternating runs along one row, starting with a light run (possibly
+/// empty) so even indices are light and odd ones dark.
+fn row_runs(bitmap: &Bitmap, y: usize) -> Vec<usize> {
+    let mut runs = vec![0];
+    let mut dark = false;
+    for x in 0..bitmap.width {
+        if bitmap.get(x, y) != dark {
+            dark = !dark;
+            runs.push(0);
+        }
+        *runs.last_mut().expect("runs starts non-empty") += 1;
+    }
+    runs
+}
+
+/// Scan an 8-bit grayscale image, row by row from the top, for barcodes.
+pub fn scan_barcodes(gray: &[u8], width: usize, height: usize) -> Vec<BarcodeResult> {
+    if gray.len() != width * height {
+        return Vec::new();
+    }
+    let Some(bitmap) = Bitmap::new(gray, width, height) else {
+        return Vec::new();
+    };
+    let mut results = scan_qr(gray, width, height);
+    results.extend(code128::scan(&bitmap));
+    results
+}
+
+/// Find and decode QR codes with `rqrr`. Codes that are found but fail to
+/// decode are skipped.
+fn scan_qr(gray: &[u8], width: usize, height: usize) -> Vec<BarcodeResult> {
+    let mut image =
+        rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| gray[y * width + x]);
+    image
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| {
+            let (meta, data) = grid
+                .decode()
+                .map_err(|e| tracing::debug!("Skipping unreadable QR code: {e}"))
+                .ok()?;
+            // The two error-correction bits of the format information.
+            let level = match meta.ecc_level {
+                0 => QrErrorCorrection::Medium,
+                1 => QrErrorCorrection::Low,
+                2 => QrErrorCorrection::High,
+                _ => QrErrorCorrection::Quartile,
+            };
+            let (x0, y0, x1, y1) = grid.bounds.iter().fold(
+                (i32::MAX, i32::MAX, i32::MIN, i32::MIN),
+                |(x0, y0, x1, y1), p| (x0.min(p.x), y0.min(p.y), x1.max(p.x), y1.max(p.y)),
+            );
+            Some(BarcodeResult {
+                kind: BarcodeKind::Qr(level),
+                data,
+                bbox: (x0 as f32, y0 as f32, (x1 - x0) as f32, (y1 - y0) as f32),
+            })
+        })
+        .collect()
+}
+
+mod code128 {
+    use super::{BarcodeKind, BarcodeResult, Bitmap, CODE128_WIDTHS, row_runs};
+    use std::fmt::Write;
+
+    const START_A: usize = 103;
+    const START_C: usize = 105;
+    /// The stop symbol's first six elements; a 2-module bar follows.
+    const STOP: &[u8; 6] = b"233111";
+    const CODE_C: usize = 99;
+    const CODE_B: usize = 100;
+    const CODE_A: usize = 101;
+    const SHIFT: usize = 98;
+
+    /// The symbol value whose element widths best match six runs, if any
+    /// is close enough. Value 106 is the stop symbol.
+    fn match_symbol(runs: &[usize]) -> Option<usize> {
+        let total: usize = runs.iter().sum();
+        let module = total as f32 / 11.0;
+        let distance = |pattern: &[u8]| -> f32 {
+            runs.iter()
+                .zip(pattern)
+                .map(|(&r, &p)| (r as f32 / module - f32::from(p - b'0')).abs())
+                .sum()
+        };
+        let stop = distance(STOP);
+        let (value, best) = CODE128_WIDTHS
+            .iter()
+            .map(|w| distance(w.as_slice()))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        match (stop < best, stop.min(best)) {
+            (_, d) if d > 2.0 => None,
+            (true, _) => Some(106),
+            (false, _) => Some(value),
+        }
+    }
+
+    /// Turn symbol values (without start, checksum and stop) into text.
+    fn decode_values(start: usize, values: &[usize]) -> Option<String> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Set {
+            A,
+            B,
+            C,
+        }
+        let mut set = match start {
+            START_A => Set::A,
+            START_C => Set::C,
+            _ => Set::B,
+        };
+        let mut text = String::new();
+        let mut shift = false;
+        for &value in values {
+            let current = match (shift, set) {
+                (true, Set::A) => Set::B,
+                (true, Set::B) => Set::A,
+                (_, other) => other,
+            };
+            shift = false;
+            match (current, value) {
+                (Set::C, 0..=99) => {
+                    let _ = write!(text, "{value:02}");
+                }
+                // FNC1, FNC3 and FNC2 carry no text.
+                (_, 102) | (Set::A | Set::B, 96 | 97) => {}
+                (Set::A | Set::B, SHIFT) => shift = true,
+                (Set::A | Set::B, CODE_C) => set = Set::C,
+                (Set::A | Set::C, CODE_B) => set = Set::B,
+                (Set::B | Set::C, CODE_A) => set = Set::A,
+                // FNC4, for Latin-1, isn't supported; its marker is dropped.
+                (Set::A, CODE_A) | (Set::B, CODE_B) => {}
+                (Set::A, 0..=63) => text.push(char::from(value as u8 + 32)),
+                (Set::A, 64..=95) => text.push(char::from(value as u8 - 64)),
+                (Set::B, 0..=95) => text.push(char::from(value as u8 + 32)),
+                _ => return None,
+            }
+        }
+        Some(text)
+    }
+
+    /// Decode a symbol whose start character begins at run `first` (a bar),
+    /// returning its text and the run just past the stop pattern.
+    fn decode_at(runs: &[usize], first: usize) -> Option<(String, usize)> {
+        let start = match_symbol(runs.get(first..first + 6)?)?;
+        if !(START_A..=START_C).contains(&start) {
+            return None;
+        }
+        // The quiet zone before the symbol is ten modules wide; allow for
+        // a tight crop by asking for half of that.
+        let module = runs[first..first + 6].iter().sum::<usize>() as f32 / 11.0;
+        if first > 1 && (runs[first - 1] as f32) < module * 5.0 {
+            return None;
+        }
+        let mut values = Vec::new();
+        let mut pos = first + 6;
+        loop {
+            match match_symbol(runs.get(pos..pos + 6)?)? {
+                106 => {
+                    runs.get(pos + 6)?;
+                    pos += 7;
+                    break;
+                }
+                value => values.push(value),
+            }
+            pos += 6;
+        }
+        let checksum = values.pop()?;
+        let expected = values
+            .iter()
+            .enumerate()
+            .fold(start, |sum, (i, v)| sum + (i + 1) * v)
+            % 103;
+        if values.is_empty() || checksum != expected {
+            return None;
+        }
+        Some((decode_values(start, &values)?, pos))
+    }
+
+    pub(super) fn scan(bitmap: &Bitmap) -> Vec<BarcodeResult> {
+        let mut results: Vec<BarcodeResult> = Vec::new();
+        for y in 0..bitmap.height {
+            let forward = row_runs(bitmap, y);
+            let mut backward = forward.clone();
+            backward.reverse();
+            if backward.len().is_multiple_of(2) {
+                // Keep even indices light when read right to left.
+                backward.insert(0, 0);
+            }
+            for (runs, reversed) in [(&forward, false), (&backward, true)] {
+                let mut first = 1;
+                while first < runs.len() {
+                    let Some((data, end)) = decode_at(runs, first) else {
+                        first += 2;
+                        continue;
+                    };
+                    let left: usize = runs[..first].iter().sum();
+                    let right: usize = runs[..end].iter().sum();
+                    let (x0, x1) = if reversed {
+                        (bitmap.width - right, bitmap.width - left)
+                    } else {
+                        (left, right)
+                    };
+                    let (x0, x1, y) = (x0 as f32, x1 as f32, y as f32);
+                    // Rows of the same symbol extend the first match.
+                    match results
+                        .iter_mut()
+                        .find(|r| r.data == data && r.bbox.0 < x1 && x0 < r.bbox.0 + r.bbox.2)
+                    {
+                        Some(found) => found.bbox.3 = y + 1.0 - found.bbox.1,
+                        None => results.push(BarcodeResult {
+                            kind: BarcodeKind::Code128,
+                            data,
+                            bbox: (x0, y, x1 - x0, 1.0),
+                        }),
+                    }
+                    first = end + 1;
+                }
+            }
+        }
+        results
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_code_sets() {
+            // "Ab" in B, then Code C for "1234", then Code A with a NUL.
+            let values = [33, 66, CODE_C, 12, 34, CODE_A, 64];
+            assert_eq!(decode_values(104, &values).as_deref(), Some("Ab1234\u{0}"));
+            // A shift in set B reads one character from set A.
+            assert_eq!(
+                decode_values(104, &[SHIFT, 65, 65]).as_deref(),
+                Some("\u{1}a")
+            );
+            assert_eq!(decode_values(START_C, &[7, 42]).as_deref(), Some("0742"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_path() -> std::path::PathBuf {
+        std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_document.pdf")
+    }
+
+    /// A fresh temporary file, deleted when the returned path drops.
+    fn temp_path(suffix: &str) -> tempfile::TempPath {
+        tempfile::Builder::new()
+            .suffix(suffix)
+            .tempfile()
+            .unwrap()
+            .into_temp_path()
+    }
+
+    /// Deterministic noise in `-amplitude..=amplitude`.
+    fn noise(i: usize, amplitude: i32) -> i32 {
+        let hash = (i as u32).wrapping_mul(2_654_435_761) >> 16;
+        hash as i32 % (2 * amplitude + 1) - amplitude
+    }
+
+    /// Gray pixels for a dark/light pattern, with dull contrast and noise.
+    fn shade(dark: bool, i: usize) -> u8 {
+        let base = if dark { 60 } else { 200 };
+        (base + noise(i, 25)).clamp(0, 255) as u8
+    }
+
+    #[test]
+    fn test_qr_rotated_with_fractional_modules_and_noise() {
+        let data = "https://github.com/SV-stark/PDFbull";
+        let modules = qr_modules(data, QrErrorCorrection::Medium).unwrap();
+        let size = modules.len() as f32;
+        // 3.3 pixels a module, turned by 7 degrees about the image centre,
+        // with a quiet zone of six modules all round.
+        let (module, angle) = (3.3f32, 7f32.to_radians());
+        let side = ((size + 12.0) * module * 1.1) as usize;
+        let centre = side as f32 / 2.0;
+        let gray: Vec<u8> = (0..side * side)
+            .map(|i| {
+                let (x, y) = ((i % side) as f32 - centre, (i / side) as f32 - centre);
+                let u = (x * angle.cos() + y * angle.sin()) / module + size / 2.0;
+                let v = (y * angle.cos() - x * angle.sin()) / module + size / 2.0;
+                let dark = (0.0..size).contains(&u)
+                    && (0.0..size).contains(&v)
+                    && modules[v as usize][u as usize];
+                shade(dark, i)
+            })
+            .collect();
+
+        let found = scan_barcodes(&gray, side, side);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, BarcodeKind::Qr(QrErrorCorrection::Medium));
+        assert_eq!(found[0].data, data);
+    }
+
+    #[test]
+    fn test_code128_blurred_and_upside_down() {
+        let data = "PDFbull-0042";
+        let modules = code128_modules(data).unwrap();
+        // 2.3 pixels a module, drawn right to left, then smoothed so bar
+        // edges are gray.
+        let module = 2.3f32;
+        let quiet = 12.0 * module;
+        let width = (modules.len() as f32 * module + 2.0 * quiet) as usize;
+        let row: Vec<u8> = (0..width)
+            .map(|x| {
+                let m = ((width - 1 - x) as f32 - quiet) / module;
+                m >= 0.0 && modules.get(m as usize) == Some(&true)
+            })
+            .enumerate()
+            .map(|(i, dark)| shade(dark, i))
+            .collect();
+        let blurred: Vec<u8> = (0..width)
+            .map(|x| {
+                let window = &row[x.saturating_sub(1)..(x + 2).min(width)];
+                (window.iter().map(|&v| u32::from(v)).sum::<u32>() / window.len() as u32) as u8
+            })
+            .collect();
+        let height = 10;
+        let gray = blurred.repeat(height);
+
+        let found = scan_barcodes(&gray, width, height);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, BarcodeKind::Code128);
+        assert_eq!(found[0].data, data);
+        assert!((found[0].bbox.0 - quiet).abs() < 3.0, "{:?}", found[0].bbox);
+        assert!((found[0].bbox.3 - height as f32).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_code128_module_structure() {
+        let modules = code128_modules("PDFbull").unwrap();
+        // Start, 7 data symbols and checksum at 11 modules each, then a
+        // 13-module stop.
+        assert_eq!(modules.len(), 11 * 9 + 13);
+        let bits = |s: &str| s.chars().map(|c| c == '1').collect::<Vec<_>>();
+        assert_eq!(modules[..11], bits("11010010000")[..]);
+        assert_eq!(modules[modules.len() - 13..], bits("1100011101011")[..]);
+        assert!(code128_modules("").is_err());
+        assert!(code128_modules("naïve").is_err());
+    }
+
+    #[test]
+    fn test_add_qr_barcode_draws_every_run() {
+        let input = fixture_path();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+        let spec = BarcodeSpec {
+            kind: BarcodeKind::Qr(QrErrorCorrection::High),
+            data: "https://github.com/SV-stark/PDFbull".into(),
+            page: 0,
+            x: 36.0,
+            y: 36.0,
+            scale: 2.0,
+        };
+
+        let rows = qr_modules(&spec.data, QrErrorCorrection::High).unwrap();
+        assert!(rows.iter().all(|r| r.len() == rows.len()));
+        // Finder pattern: the top-left 7x7 square starts with a solid row.
+        assert!(rows[0][..7].iter().all(|&m| m));
+        let expected_runs = draw_module_rows(
+            &mut pdf_writer::Content::new(),
+            &rows,
+            (0.0, 0.0),
+            (1.0, 1.0),
+        );
+
+        add_barcode(input.to_str().unwrap(), &spec, output).unwrap();
+        let doc = Document::load(output).unwrap();
+        let page_id = *doc.get_pages().values().next().unwrap();
+        let content = doc.get_page_content(page_id);
+        let content = String::from_utf8_lossy(&content);
+        assert!(content.matches(" re").count() >= expected_runs);
+    }
+}
@@ -0,0 +1,199 @@
+//! Bidirectional text, on top of the `unicode-bidi` crate's implementation
+//! of the Unicode bidirectional algorithm (UAX #9).
+//!
+//! Text extracted from a page comes out in visual order, left to right
+//! across the page, so [`visual_to_logical`] runs the reordering backwards
+//! to recover reading order.
+
+use unicode_bidi::{BidiClass, ParagraphBidiInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// An embedding level: even is left-to-right, odd right-to-left.
+pub type Level = u8;
+
+/// A stretch of a line at one embedding level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BidiRun {
+    pub text: String,
+    pub level: Level,
+}
+
+impl BidiRun {
+    pub const fn direction(&self) -> Direction {
+        if self.level.is_multiple_of(2) {
+            Direction::Ltr
+        } else {
+            Direction::Rtl
+        }
+    }
+}
+
+/// Resolve `text` as one paragraph. With no `base`, the direction comes
+/// from the first strong character (rules P2 and P3).
+fn paragraph(text: &str, base: Option<Direction>) -> ParagraphBidiInfo<'_> {
+    let level = base.map(|direction| match direction {
+        Direction::Ltr => unicode_bidi::Level::ltr(),
+        Direction::Rtl => unicode_bidi::Level::rtl(),
+    });
+    ParagraphBidiInfo::new(text, level)
+}
+
+/// Explicit formatting characters, which take no part in display.
+fn is_formatting(c: char) -> bool {
+    use BidiClass::{BN, FSI, LRE, LRI, LRO, PDF, PDI, RLE, RLI, RLO};
+    matches!(
+        unicode_bidi::bidi_class(c),
+        LRE | LRO | RLE | RLO | PDF | BN | LRI | RLI | FSI | PDI
+    )
+}
+
+/// The character drawn in place of `c` in right-to-left text.
+fn mirror(c: char) -> char {
+    unicode_bidi_mirroring::get_mirrored(c).unwrap_or(c)
+}
+
+/// Resolved embedding levels for one line of text, one per character, and
+/// the paragraph level. With no `base`, the direction comes from the first
+/// strong character (rules P2 and P3).
+pub fn embedding_levels(text: &str, base: Option<Direction>) -> (Level, Vec<Level>) {
+    let info = paragraph(text, base);
+    let levels = info
+        .reordered_levels_per_char(0..text.len())
+        .iter()
+        .map(unicode_bidi::Level::number)
+        .collect();
+    (info.paragraph_level.number(), levels)
+}
+
+/// Lay out one line for display: its level runs left to right, with the
+/// text of right-to-left runs reversed and mirrored. Explicit formatting
+/// characters are dropped.
+pub fn bidi_reorder(line: &str, base: Option<Direction>) -> Vec<BidiRun> {
+    let info = paragraph(line, base);
+    let (levels, runs) = info.visual_runs(0..line.len());
+    let mut reordered: Vec<BidiRun> = Vec::new();
+    for range in runs {
+        let level = levels[range.start];
+        let chars = line[range].chars().filter(|&c| !is_formatting(c));
+        let text: String = if level.is_rtl() {
+            chars.rev().map(mirror).collect()
+        } else {
+            chars.collect()
+        };
+        match reordered.last_mut() {
+            _ if text.is_empty() => {}
+            Some(run) if run.level == level.number() => run.text.push_str(&text),
+            _ => reordered.push(BidiRun {
+                text,
+                level: level.number(),
+            }),
+        }
+    }
+    reordered
+}
+
+/// Put a line read off a page left to right back into reading order.
+///
+/// Lines without right-to-left characters come back unchanged. Otherwise
+/// the paragraph direction is that of most strong characters, levels are
+/// resolved on the visual text and the reordering is undone. That is exact
+/// for letters; a number between Latin and Hebrew in a left-to-right line
+/// reads the same either side of the Hebrew, and stays where it is.
+pub fn visual_to_logical(line: &str) -> String {
+    let (mut ltr, mut rtl) = (0usize, 0usize);
+    for c in line.chars() {
+        match unicode_bidi::bidi_class(c) {
+            BidiClass::L => ltr += 1,
+            BidiClass::R | BidiClass::AL => rtl += 1,
+            _ => {}
+        }
+    }
+    if rtl == 0 {
+        return line.to_string();
+    }
+    let base = if rtl > ltr {
+        Direction::Rtl
+    } else {
+        Direction::Ltr
+    };
+    let chars: Vec<char> = line.chars().collect();
+    let levels = paragraph(line, Some(base)).reordered_levels_per_char(0..line.len());
+    // Reversing the level runs again puts each run back where it was.
+    ParagraphBidiInfo::reorder_visual(&levels)
+        .into_iter()
+        .map(|i| {
+            if levels[i].is_rtl() {
+                mirror(chars[i])
+            } else {
+                chars[i]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visual(line: &str, base: Option<Direction>) -> String {
+        bidi_reorder(line, base)
+            .into_iter()
+            .map(|r| r.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_mixed_line_levels_and_visual_order() {
+        let line = "abc \u{5D0}\u{5D1}\u{5D2} 12 def";
+        let (paragraph, levels) = embedding_levels(line, None);
+        assert_eq!(paragraph, 0);
+        assert_eq!(levels, [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 0, 0, 0, 0]);
+        let runs = bidi_reorder(line, None);
+        let texts: Vec<(&str, Level)> = runs.iter().map(|r| (r.text.as_str(), r.level)).collect();
+        assert_eq!(
+            texts,
+            [
+                ("abc ", 0),
+                ("12", 2),
+                (" \u{5D2}\u{5D1}\u{5D0}", 1),
+                (" def", 0)
+            ]
+        );
+        assert_eq!(runs[2].direction(), Direction::Rtl);
+    }
+
+    #[test]
+    fn test_rtl_paragraph_with_numbers_and_brackets() {
+        // "shalom 123" in a right-to-left paragraph.
+        let line = "\u{5E9}\u{5DC}\u{5D5}\u{5DD} 123";
+        assert_eq!(visual(line, None), "123 \u{5DD}\u{5D5}\u{5DC}\u{5E9}");
+
+        // Brackets around Latin text follow the Hebrew before them, and
+        // are mirrored.
+        let line = "\u{5D0}\u{5D1}\u{5D2} (abc)";
+        assert_eq!(visual(line, None), "(abc) \u{5D2}\u{5D1}\u{5D0}");
+    }
+
+    #[test]
+    fn test_formatting_characters_are_dropped() {
+        assert_eq!(visual("a\u{202B}\u{5D0} b\u{202C}c", None), "ab \u{5D0}c");
+        assert_eq!(visual("\u{2067}ab \u{5D0}\u{2069}c", None), "\u{5D0} abc");
+    }
+
+    #[test]
+    fn test_visual_to_logical_undoes_reordering() {
+        for line in [
+            "abc \u{5D0}\u{5D1}\u{5D2} def",
+            "\u{5E9}\u{5DC}\u{5D5}\u{5DD} 123",
+            "\u{5E9}\u{5DC}\u{5D5}\u{5DD} \u{5E2}\u{5D5}\u{5DC}\u{5DD}",
+            "plain text",
+        ] {
+            assert_eq!(visual_to_logical(&visual(line, None)), line);
+        }
+    }
+}
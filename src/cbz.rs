@@ -0,0 +1,177 @@
+//! Comic book archives.
+//!
+//! A `.cbz` is a zip of page images. It is opened by building a PDF with
+//! one page per image, in natural filename order so `page2` comes before
+//! `page10`, and each page the size of its image at 72 dpi.
+
+use crate::archive::ZipArchive;
+use crate::filters::{self, FilterType};
+use crate::models::{PdfError, PdfResult};
+use lopdf::{Document, Object};
+use std::cmp::Ordering;
+
+const IMAGE_EXTENSIONS: [&str; 9] = [
+    "png", "jpg", "jpeg", "gif", "bmp", "tif", "tiff", "webp", "pnm",
+];
+
+/// Whether a zip entry is a page image, going by its extension.
+fn is_page_image(name: &str) -> bool {
+    if name.ends_with('/') || name.starts_with("__MACOSX/") {
+        return false;
+    }
+    name.rsplit_once('.').is_some_and(|(_, ext)| {
+        IMAGE_EXTENSIONS
+            .iter()
+            .any(|known| ext.eq_ignore_ascii_case(known))
+    })
+}
+
+/// Compare filenames with runs of digits compared by value, so `page2`
+/// sorts before `page10`. Other text compares case-insensitively.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_len = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let a_num = strip_zeros(&a[..a_len]);
+                let b_num = strip_zeros(&b[..b_len]);
+                let order = a_num
+                    .len()
+                    .cmp(&b_num.len())
+                    .then_with(|| a_num.cmp(b_num))
+                    .then_with(|| a_len.cmp(&b_len));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a = &a[a_len..];
+                b = &b[b_len..];
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn strip_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[zeros..]
+}
+
+/// Build a PDF with one page per image in the archive.
+pub fn cbz_to_pdf(data: Vec<u8>) -> PdfResult<Document> {
+    let archive = ZipArchive::new(data)?;
+    let mut names: Vec<&str> = archive
+        .entries()
+        .iter()
+        .map(|e| e.name.as_str())
+        .filter(|name| is_page_image(name))
+        .collect();
+    if names.is_empty() {
+        return Err(PdfError::from("Comic book archive has no images"));
+    }
+    names.sort_by(|a, b| natural_cmp(a, b));
+
+    let mut doc = Document::with_version("1.7");
+    let tree_id = doc.new_object_id();
+    let mut kids = Vec::with_capacity(names.len());
+    for name in names {
+        let bytes = archive.read(name)?;
+        let img = image::load_from_memory(&bytes)
+            .map_err(|e| PdfError::from(format!("Failed to decode '{name}': {e}")))?
+            .to_rgb8();
+        let (width, height) = img.dimensions();
+        let image_id = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"XObject".to_vec())),
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(i64::from(width))),
+                ("Height", Object::Integer(i64::from(height))),
+                ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+                ("Filter", Object::Name(FilterType::Flate.name().to_vec())),
+            ]),
+            filters::encode(img.as_raw(), FilterType::Flate)?,
+        ));
+        let content_id = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::new(),
+            format!("q {width} 0 0 {height} 0 0 cm /Im0 Do Q").into_bytes(),
+        ));
+        let page_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(tree_id)),
+            (
+                "MediaBox",
+                Object::Array(vec![
+                    Object::Integer(0),
+                    Object::Integer(0),
+                    Object::Integer(i64::from(width)),
+                    Object::Integer(i64::from(height)),
+                ]),
+            ),
+            ("Contents", Object::Reference(content_id)),
+            (
+                "Resources",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "XObject",
+                    Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                        "Im0",
+                        Object::Reference(image_id),
+                    )])),
+                )])),
+            ),
+        ])));
+        kids.push(Object::Reference(page_id));
+    }
+    doc.objects.insert(
+        tree_id,
+        Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Count", Object::Integer(kids.len() as i64)),
+            ("Kids", Object::Array(kids)),
+        ])),
+    );
+    let catalog_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Catalog".to_vec())),
+        ("Pages", Object::Reference(tree_id)),
+    ])));
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_natural_order() {
+        let mut names = vec![
+            "page10.png",
+            "Page2.png",
+            "page1.png",
+            "page02b.png",
+            "cover.jpg",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            [
+                "cover.jpg",
+                "page1.png",
+                "Page2.png",
+                "page02b.png",
+                "page10.png"
+            ]
+        );
+    }
+}
@@ -0,0 +1,492 @@
+//! CCITT Group 4 (ITU-T T.6) encoding of bilevel images.
+//!
+//! Bitmaps are packed one bit per pixel, most significant bit first, with
+//! every row padded to a whole byte and 0 meaning black. That is the sample
+//! layout of a `/DeviceGray` image with `/BitsPerComponent 1`, and what
+//! `/CCITTFaxDecode` produces with the default `/BlackIs1 false`.
+
+use crate::filters::BitWriter;
+use crate::models::{PdfError, PdfResult};
+use lopdf::{Dictionary, Object, Stream};
+
+/// `/DecodeParms` of a `/CCITTFaxDecode` stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CcittParams {
+    /// Negative for pure two-dimensional (Group 4) coding.
+    pub k: i32,
+    pub columns: u32,
+    /// 0 when the row count isn't known and data runs to end-of-block.
+    pub rows: u32,
+    pub black_is_1: bool,
+}
+
+impl CcittParams {
+    pub const fn group4(columns: u32, rows: u32) -> Self {
+        Self {
+            k: -1,
+            columns,
+            rows,
+            black_is_1: false,
+        }
+    }
+
+    /// The dictionary to store as `/DecodeParms`. Defaults are left out.
+    pub fn to_dictionary(&self) -> Dictionary {
+        let mut dict = Dictionary::new();
+        dict.set("K", i64::from(self.k));
+        dict.set("Columns", i64::from(self.columns));
+        if self.rows > 0 {
+            dict.set("Rows", i64::from(self.rows));
+        }
+        if self.black_is_1 {
+            dict.set("BlackIs1", true);
+        }
+        dict
+    }
+}
+
+/// Terminating codes for white runs of 0–63 pixels, as `(code, bits)`.
+const WHITE_TERMINATING: [(u16, u8); 64] = [
+    (0b0011_0101, 8),
+    (0b00_0111, 6),
+    (0b0111, 4),
+    (0b1000, 4),
+    (0b1011, 4),
+    (0b1100, 4),
+    (0b1110, 4),
+    (0b1111, 4),
+    (0b1_0011, 5),
+    (0b1_0100, 5),
+    (0b0_0111, 5),
+    (0b0_1000, 5),
+    (0b00_1000, 6),
+    (0b00_0011, 6),
+    (0b11_0100, 6),
+    (0b11_0101, 6),
+    (0b10_1010, 6),
+    (0b10_1011, 6),
+    (0b010_0111, 7),
+    (0b000_1100, 7),
+    (0b000_1000, 7),
+    (0b001_0111, 7),
+    (0b000_0011, 7),
+    (0b000_0100, 7),
+    (0b010_1000, 7),
+    (0b010_1011, 7),
+    (0b001_0011, 7),
+    (0b010_0100, 7),
+    (0b001_1000, 7),
+    (0b0000_0010, 8),
+    (0b0000_0011, 8),
+    (0b0001_1010, 8),
+    (0b0001_1011, 8),
+    (0b0001_0010, 8),
+    (0b0001_0011, 8),
+    (0b0001_0100, 8),
+    (0b0001_0101, 8),
+    (0b0001_0110, 8),
+    (0b0001_0111, 8),
+    (0b0010_1000, 8),
+    (0b0010_1001, 8),
+    (0b0010_1010, 8),
+    (0b0010_1011, 8),
+    (0b0010_1100, 8),
+    (0b0010_1101, 8),
+    (0b0000_0100, 8),
+    (0b0000_0101, 8),
+    (0b0000_1010, 8),
+    (0b0000_1011, 8),
+    (0b0101_0010, 8),
+    (0b0101_0011, 8),
+    (0b0101_0100, 8),
+    (0b0101_0101, 8),
+    (0b0010_0100, 8),
+    (0b0010_0101, 8),
+    (0b0101_1000, 8),
+    (0b0101_1001, 8),
+    (0b0101_1010, 8),
+    (0b0101_1011, 8),
+    (0b0100_1010, 8),
+    (0b0100_1011, 8),
+    (0b0011_0010, 8),
+    (0b0011_0011, 8),
+    (0b0011_0100, 8),
+];
+
+/// Make-up codes for white runs of 64, 128, … 1728 pixels.
+const WHITE_MAKEUP: [(u16, u8); 27] = [
+    (0b1_1011, 5),
+    (0b1_0010, 5),
+    (0b01_0111, 6),
+    (0b011_0111, 7),
+    (0b0011_0110, 8),
+    (0b0011_0111, 8),
+    (0b0110_0100, 8),
+    (0b0110_0101, 8),
+    (0b0110_1000, 8),
+    (0b0110_0111, 8),
+    (0b0_1100_1100, 9),
+    (0b0_1100_1101, 9),
+    (0b0_1101_0010, 9),
+    (0b0_1101_0011, 9),
+    (0b0_1101_0100, 9),
+    (0b0_1101_0101, 9),
+    (0b0_1101_0110, 9),
+    (0b0_1101_0111, 9),
+    (0b0_1101_1000, 9),
+    (0b0_1101_1001, 9),
+    (0b0_1101_1010, 9),
+    (0b0_1101_1011, 9),
+    (0b0_1001_1000, 9),
+    (0b0_1001_1001, 9),
+    (0b0_1001_1010, 9),
+    (0b01_1000, 6),
+    (0b0_1001_1011, 9),
+];
+
+/// Terminating codes for black runs of 0–63 pixels.
+const BLACK_TERMINATING: [(u16, u8); 64] = [
+    (0b00_0011_0111, 10),
+    (0b010, 3),
+    (0b11, 2),
+    (0b10, 2),
+    (0b011, 3),
+    (0b0011, 4),
+    (0b0010, 4),
+    (0b0_0011, 5),
+    (0b00_0101, 6),
+    (0b00_0100, 6),
+    (0b000_0100, 7),
+    (0b000_0101, 7),
+    (0b000_0111, 7),
+    (0b0000_0100, 8),
+    (0b0000_0111, 8),
+    (0b0_0001_1000, 9),
+    (0b00_0001_0111, 10),
+    (0b00_0001_1000, 10),
+    (0b00_0000_1000, 10),
+    (0b000_0110_0111, 11),
+    (0b000_0110_1000, 11),
+    (0b000_0110_1100, 11),
+    (0b000_0011_0111, 11),
+    (0b000_0010_1000, 11),
+    (0b000_0001_0111, 11),
+    (0b000_0001_1000, 11),
+    (0b0000_1100_1010, 12),
+    (0b0000_1100_1011, 12),
+    (0b0000_1100_1100, 12),
+    (0b0000_1100_1101, 12),
+    (0b0000_0110_1000, 12),
+    (0b0000_0110_1001, 12),
+    (0b0000_0110_1010, 12),
+    (0b0000_0110_1011, 12),
+    (0b0000_1101_0010, 12),
+    (0b0000_1101_0011, 12),
+    (0b0000_1101_0100, 12),
+    (0b0000_1101_0101, 12),
+    (0b0000_1101_0110, 12),
+    (0b0000_1101_0111, 12),
+    (0b0000_0110_1100, 12),
+    (0b0000_0110_1101, 12),
+    (0b0000_1101_1010, 12),
+    (0b0000_1101_1011, 12),
+    (0b0000_0101_0100, 12),
+    (0b0000_0101_0101, 12),
+    (0b0000_0101_0110, 12),
+    (0b0000_0101_0111, 12),
+    (0b0000_0110_0100, 12),
+    (0b0000_0110_0101, 12),
+    (0b0000_0101_0010, 12),
+    (0b0000_0101_0011, 12),
+    (0b0000_0010_0100, 12),
+    (0b0000_0011_0111, 12),
+    (0b0000_0011_1000, 12),
+    (0b0000_0010_0111, 12),
+    (0b0000_0010_1000, 12),
+    (0b0000_0101_1000, 12),
+    (0b0000_0101_1001, 12),
+    (0b0000_0010_1011, 12),
+    (0b0000_0010_1100, 12),
+    (0b0000_0101_1010, 12),
+    (0b0000_0110_0110, 12),
+    (0b0000_0110_0111, 12),
+];
+
+/// Make-up codes for black runs of 64, 128, … 1728 pixels.
+const BLACK_MAKEUP: [(u16, u8); 27] = [
+    (0b00_0000_1111, 10),
+    (0b0000_1100_1000, 12),
+    (0b0000_1100_1001, 12),
+    (0b0000_0101_1011, 12),
+    (0b0000_0011_0011, 12),
+    (0b0000_0011_0100, 12),
+    (0b0000_0011_0101, 12),
+    (0b0_0000_0110_1100, 13),
+    (0b0_0000_0110_1101, 13),
+    (0b0_0000_0100_1010, 13),
+    (0b0_0000_0100_1011, 13),
+    (0b0_0000_0100_1100, 13),
+    (0b0_0000_0100_1101, 13),
+    (0b0_0000_0111_0010, 13),
+    (0b0_0000_0111_0011, 13),
+    (0b0_0000_0111_0100, 13),
+    (0b0_0000_0111_0101, 13),
+    (0b0_0000_0111_0110, 13),
+    (0b0_0000_0111_0111, 13),
+    (0b0_0000_0101_0010, 13),
+    (0b0_0000_0101_0011, 13),
+    (0b0_0000_0101_0100, 13),
+    (0b0_0000_0101_0101, 13),
+    (0b0_0000_0101_1010, 13),
+    (0b0_0000_0101_1011, 13),
+    (0b0_0000_0110_0100, 13),
+    (0b0_0000_0110_0101, 13),
+];
+
+/// Make-up codes shared by both colours for runs of 1792, 1856, … 2560.
+const EXTENDED_MAKEUP: [(u16, u8); 13] = [
+    (0b000_0000_1000, 11),
+    (0b000_0000_1100, 11),
+    (0b000_0000_1101, 11),
+    (0b0000_0001_0010, 12),
+    (0b0000_0001_0011, 12),
+    (0b0000_0001_0100, 12),
+    (0b0000_0001_0101, 12),
+    (0b0000_0001_0110, 12),
+    (0b0000_0001_0111, 12),
+    (0b0000_0001_1100, 12),
+    (0b0000_0001_1101, 12),
+    (0b0000_0001_1110, 12),
+    (0b0000_0001_1111, 12),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Pass,
+    Horizontal,
+    /// `a1 - b1`, from -3 to 3.
+    Vertical(i8),
+    /// End of line; two in a row end the block.
+    Eol,
+}
+
+const MODES: [(u16, u8, Mode); 10] = [
+    (0b1, 1, Mode::Vertical(0)),
+    (0b011, 3, Mode::Vertical(1)),
+    (0b010, 3, Mode::Vertical(-1)),
+    (0b001, 3, Mode::Horizontal),
+    (0b0001, 4, Mode::Pass),
+    (0b00_0011, 6, Mode::Vertical(2)),
+    (0b00_0010, 6, Mode::Vertical(-2)),
+    (0b000_0011, 7, Mode::Vertical(3)),
+    (0b000_0010, 7, Mode::Vertical(-3)),
+    (0b0000_0000_0001, 12, Mode::Eol),
+];
+
+const fn mode_code(mode: Mode) -> (u16, u8) {
+    let mut i = 0;
+    while i < MODES.len() {
+        let (code, bits, m) = MODES[i];
+        let same = match (m, mode) {
+            (Mode::Vertical(a), Mode::Vertical(b)) => a == b,
+            (Mode::Pass, Mode::Pass)
+            | (Mode::Horizontal, Mode::Horizontal)
+            | (Mode::Eol, Mode::Eol) => true,
+            _ => false,
+        };
+        if same {
+            return (code, bits);
+        }
+        i += 1;
+    }
+    unreachable!()
+}
+
+/// Rows are handled as `true` for black, whatever the bit convention.
+fn unpack_row(row: &[u8], width: usize, black_is_1: bool) -> Vec<bool> {
+    (0..width)
+        .map(|x| (row[x / 8] >> (7 - x % 8) & 1 == 1) == black_is_1)
+        .collect()
+}
+
+/// First position at or after `from` whose pixel isn't `color`, or the
+/// line width.
+fn next_change(line: &[bool], from: usize, color: bool) -> usize {
+    line.iter()
+        .skip(from)
+        .position(|&pixel| pixel != color)
+        .map_or(line.len(), |i| from + i)
+}
+
+/// `b1`, the first changing element on the reference line at or after
+/// `from` whose colour is the opposite of `color`, and `b2`, the changing
+/// element after it. The pixel before the line counts as white.
+fn reference_changes(reference: &[bool], from: usize, color: bool) -> (usize, usize) {
+    let b1 = (from..reference.len())
+        .find(|&i| {
+            let previous = i.checked_sub(1).is_some_and(|j| reference[j]);
+            reference[i] != color && previous == color
+        })
+        .unwrap_or(reference.len());
+    (b1, next_change(reference, b1 + 1, !color))
+}
+
+fn write_run(writer: &mut BitWriter, mut run: usize, black: bool) {
+    let (terminating, makeup) = if black {
+        (&BLACK_TERMINATING, &BLACK_MAKEUP)
+    } else {
+        (&WHITE_TERMINATING, &WHITE_MAKEUP)
+    };
+    let mut write = |(code, bits): (u16, u8)| writer.write(code, u32::from(bits));
+    while run > 2560 {
+        write(EXTENDED_MAKEUP[12]);
+        run -= 2560;
+    }
+    if run >= 64 {
+        let index = run / 64 - 1;
+        write(
+            makeup
+                .get(index)
+                .copied()
+                .unwrap_or_else(|| EXTENDED_MAKEUP[index - 27]),
+        );
+        run %= 64;
+    }
+    write(terminating[run]);
+}
+
+/// Compress a bitmap with Group 4 coding. Returns the stream data and the
+/// parameters to store as its `/DecodeParms`.
+pub fn encode_g4(bitmap: &[u8], width: u32, height: u32) -> PdfResult<(Vec<u8>, CcittParams)> {
+    let columns = width as usize;
+    let stride = columns.div_ceil(8);
+    if columns == 0 || bitmap.len() < stride * height as usize {
+        return Err(PdfError::from(format!(
+            "A {width}x{height} bitmap needs {} bytes, got {}",
+            stride * height as usize,
+            bitmap.len()
+        )));
+    }
+
+    let mut writer = BitWriter::default();
+    let write_mode = |writer: &mut BitWriter, mode| {
+        let (code, bits) = mode_code(mode);
+        writer.write(code, u32::from(bits));
+    };
+    let mut reference = vec![false; columns];
+    for row in bitmap.chunks(stride).take(height as usize) {
+        let line = unpack_row(row, columns, false);
+        // a0 starts on an imaginary white pixel before the line.
+        let mut a0: Option<usize> = None;
+        let mut color = false;
+        while a0.is_none_or(|a| a < columns) {
+            let from = a0.map_or(0, |a| a + 1);
+            let a1 = next_change(&line, from, color);
+            let (b1, b2) = reference_changes(&reference, from, color);
+            if b2 < a1 {
+                write_mode(&mut writer, Mode::Pass);
+                a0 = Some(b2);
+            } else if a1.abs_diff(b1) <= 3 {
+                let delta = if a1 >= b1 {
+                    (a1 - b1) as i8
+                } else {
+                    -((b1 - a1) as i8)
+                };
+                write_mode(&mut writer, Mode::Vertical(delta));
+                a0 = Some(a1);
+                color = !color;
+            } else {
+                let a2 = next_change(&line, a1 + 1, !color);
+                write_mode(&mut writer, Mode::Horizontal);
+                write_run(&mut writer, a1 - a0.unwrap_or(0), color);
+                write_run(&mut writer, a2 - a1, !color);
+                a0 = Some(a2);
+            }
+        }
+        reference = line;
+    }
+    // End of facsimile block.
+    write_mode(&mut writer, Mode::Eol);
+    write_mode(&mut writer, Mode::Eol);
+    Ok((writer.finish(), CcittParams::group4(width, height)))
+}
+
+/// An image `XObject` holding `bitmap` as Group 4 data.
+pub fn g4_image_stream(bitmap: &[u8], width: u32, height: u32) -> PdfResult<Stream> {
+    let (data, params) = encode_g4(bitmap, width, height)?;
+    Ok(Stream::new(
+        Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"XObject".to_vec())),
+            ("Subtype", Object::Name(b"Image".to_vec())),
+            ("Width", Object::Integer(i64::from(width))),
+            ("Height", Object::Integer(i64::from(height))),
+            ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+            ("BitsPerComponent", Object::Integer(1)),
+            ("Filter", Object::Name(b"CCITTFaxDecode".to_vec())),
+            ("DecodeParms", Object::Dictionary(params.to_dictionary())),
+        ]),
+        data,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zpdf::{PdfDict, PdfName, PdfObject};
+
+    /// Decode with zpdf's `/CCITTFaxDecode` filter.
+    fn decode_g4(data: &[u8], params: &CcittParams) -> Vec<u8> {
+        let mut parms = PdfDict::new();
+        parms.insert(PdfName::new("K"), PdfObject::Integer(params.k.into()));
+        parms.insert(
+            PdfName::new("Columns"),
+            PdfObject::Integer(params.columns.into()),
+        );
+        if params.rows > 0 {
+            parms.insert(PdfName::new("Rows"), PdfObject::Integer(params.rows.into()));
+        }
+        let mut dict = PdfDict::new();
+        dict.insert(
+            PdfName::new("Filter"),
+            PdfObject::Name(PdfName::new("CCITTFaxDecode")),
+        );
+        dict.insert(PdfName::new("DecodeParms"), PdfObject::Dict(parms));
+        zpdf_parser::filters::decode_stream(data, &dict).unwrap()
+    }
+
+    fn round_trip(width: u32, height: u32, pixel: impl Fn(u32, u32) -> bool) {
+        let stride = width.div_ceil(8) as usize;
+        let mut bitmap = vec![0u8; stride * height as usize];
+        for y in 0..height {
+            for x in 0..width {
+                // `pixel` returns true for black, which is a 0 bit.
+                if !pixel(x, y) {
+                    bitmap[y as usize * stride + x as usize / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+        let (data, params) = encode_g4(&bitmap, width, height).unwrap();
+        assert_eq!(params, CcittParams::group4(width, height));
+        assert_eq!(decode_g4(&data, &params), bitmap);
+        let until_end = CcittParams { rows: 0, ..params };
+        assert_eq!(decode_g4(&data, &until_end), bitmap);
+    }
+
+    #[test]
+    fn test_all_white_rows_are_single_v0_codes() {
+        // V0 V0 then two EOLs: 11 000000000001 000000000001, zero padded.
+        let (data, _) = encode_g4(&[0xFF, 0xFF], 8, 2).unwrap();
+        assert_eq!(data, [0xC0, 0x04, 0x00, 0x40]);
+    }
+
+    #[test]
+    fn test_patterns_round_trip() {
+        for width in [8, 37, 3000] {
+            round_trip(width, 6, |_, _| false);
+            round_trip(width, 6, |_, _| true);
+            round_trip(width, 6, |_, y| y % 2 == 0);
+            round_trip(width, 6, |x, y| (x / 3 + y) % 2 == 0);
+            round_trip(width, 6, |x, y| x.abs_diff(y * 5) < 4 || x > 2700);
+        }
+    }
+}
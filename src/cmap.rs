@@ -0,0 +1,655 @@
+//! `CMap`s: the mappings from character codes in a string to CIDs and to
+//! Unicode used by composite (Type 0) fonts.
+//!
+//! [`CMap::parse`] reads embedded `CMap`s, both `/ToUnicode` streams
+//! (`bfchar`/`bfrange`) and `/Encoding` streams (`cidchar`/`cidrange`),
+//! along with their code space ranges, which decide how many bytes each
+//! code takes. [`CMap::predefined`] knows `Identity-H`/`-V` and the Adobe
+//! Unicode encodings (`UniGB-UCS2-H`, `UniJIS-UTF16-V` and so on), whose
+//! codes are Unicode themselves. The Adobe CID to Unicode tables of the
+//! character collections are not bundled, so a font with neither a
+//! `/ToUnicode` nor a Unicode encoding can't be mapped.
+
+use crate::content_stream::{TokenType, Tokenizer};
+use lopdf::{Dictionary, Document, Object};
+use std::collections::HashMap;
+
+/// How deep `usecmap` chains are followed.
+const MAX_USECMAP_DEPTH: usize = 4;
+
+/// The form the codes of a Unicode-encoded `CMap` take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnicodeCodes {
+    Utf16,
+    Utf8,
+    Utf32,
+}
+
+/// The destination of a `bfrange`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BfTarget {
+    /// The first code's text; later codes increment its last UTF-16 unit.
+    Start(Vec<u16>),
+    /// One string per code.
+    List(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CodeRange<T> {
+    /// Length of the codes in bytes.
+    len: usize,
+    low: u32,
+    high: u32,
+    target: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CMap {
+    /// The `/CMapName`, if the `CMap` declares one.
+    pub name: Option<String>,
+    /// Vertical writing (`/WMode 1`).
+    pub vertical: bool,
+    /// Code space ranges as (low, high) byte strings of equal length.
+    codespace: Vec<(Vec<u8>, Vec<u8>)>,
+    cid_chars: HashMap<(usize, u32), u32>,
+    cid_ranges: Vec<CodeRange<u32>>,
+    bf_chars: HashMap<(usize, u32), String>,
+    bf_ranges: Vec<CodeRange<BfTarget>>,
+    /// Set when codes are Unicode, as in the predefined `Uni…` `CMap`s.
+    unicode: Option<UnicodeCodes>,
+    /// Codes are CIDs, as in `Identity-H`.
+    identity: bool,
+}
+
+/// A `CMap` operand: hex strings are kept as bytes.
+#[derive(Debug, Clone)]
+enum Operand {
+    Bytes(Vec<u8>),
+    Int(u32),
+    Name(Vec<u8>),
+    Array(Vec<Self>),
+}
+
+/// The bytes of a `<…>` hex string token, a final odd digit padded with 0.
+fn hex_bytes(text: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = text
+        .iter()
+        .filter_map(|&b| char::from(b).to_digit(16))
+        .map(|d| d as u8)
+        .collect();
+    digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+/// The bytes of a `(…)` literal string token with its escapes undone.
+fn literal_bytes(text: &[u8]) -> Vec<u8> {
+    let body = &text[1..text.len().saturating_sub(1).max(1)];
+    let mut out = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        let b = body[i];
+        i += 1;
+        if b != b'\\' || i == body.len() {
+            out.push(b);
+            continue;
+        }
+        let escaped = body[i];
+        i += 1;
+        out.push(match escaped {
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'b' => 0x08,
+            b'f' => 0x0C,
+            b'0'..=b'7' => {
+                let mut value = u32::from(escaped - b'0');
+                for _ in 0..2 {
+                    match body.get(i) {
+                        Some(&d @ b'0'..=b'7') => {
+                            value = value * 8 + u32::from(d - b'0');
+                            i += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                value as u8
+            }
+            other => other,
+        });
+    }
+    out
+}
+
+fn code_value(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |code, &b| (code << 8) | u32::from(b))
+}
+
+/// Decode `bfchar`/`bfrange` destination bytes, which are UTF-16BE.
+fn utf16_units(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]))
+        .collect()
+}
+
+/// The Unicode text a code of a Unicode-encoded `CMap` stands for.
+fn unicode_code_text(form: UnicodeCodes, bytes: &[u8]) -> Option<String> {
+    match form {
+        UnicodeCodes::Utf16 => char::decode_utf16(utf16_units(bytes))
+            .collect::<Result<String, _>>()
+            .ok(),
+        UnicodeCodes::Utf8 => std::str::from_utf8(bytes).ok().map(str::to_string),
+        UnicodeCodes::Utf32 => char::from_u32(code_value(bytes)).map(String::from),
+    }
+}
+
+impl CMap {
+    /// Parse an embedded `CMap`. Unknown operators and malformed entries are
+    /// skipped; `usecmap` is followed for predefined parents only.
+    pub fn parse(data: &[u8]) -> Self {
+        let mut cmap = Self::default();
+        let mut tokens = Tokenizer::new(data);
+        let mut operands: Vec<Operand> = Vec::new();
+        let mut arrays: Vec<Vec<Operand>> = Vec::new();
+        let push = |operands: &mut Vec<Operand>, arrays: &mut Vec<Vec<Operand>>, op| match arrays
+            .last_mut()
+        {
+            Some(array) => array.push(op),
+            None => operands.push(op),
+        };
+
+        while let Some(token) = tokens.next_token() {
+            let text = token.text;
+            match token.kind {
+                TokenType::Comment | TokenType::DictOpen | TokenType::DictClose => {}
+                TokenType::ArrayOpen => arrays.push(Vec::new()),
+                TokenType::ArrayClose => {
+                    if let Some(array) = arrays.pop() {
+                        push(&mut operands, &mut arrays, Operand::Array(array));
+                    }
+                }
+                TokenType::Operand => {
+                    let op = match text.first() {
+                        Some(b'<') => Operand::Bytes(hex_bytes(&text[1..])),
+                        Some(b'(') => Operand::Bytes(literal_bytes(text)),
+                        Some(b'/') => Operand::Name(text[1..].to_vec()),
+                        _ => match std::str::from_utf8(text).ok().and_then(|t| t.parse().ok()) {
+                            Some(n) => Operand::Int(n),
+                            None => continue,
+                        },
+                    };
+                    push(&mut operands, &mut arrays, op);
+                }
+                TokenType::Operator => {
+                    cmap.apply(text, &operands);
+                    operands.clear();
+                    arrays.clear();
+                }
+            }
+        }
+        cmap
+    }
+
+    /// Run one `CMap` operator over its operands.
+    fn apply(&mut self, operator: &[u8], operands: &[Operand]) {
+        match operator {
+            b"endcodespacerange" => {
+                for pair in operands.chunks_exact(2) {
+                    if let [Operand::Bytes(low), Operand::Bytes(high)] = pair
+                        && low.len() == high.len()
+                        && (1..=4).contains(&low.len())
+                    {
+                        self.codespace.push((low.clone(), high.clone()));
+                    }
+                }
+            }
+            b"endcidchar" => {
+                for pair in operands.chunks_exact(2) {
+                    if let [Operand::Bytes(code), Operand::Int(cid)] = pair
+                        && (1..=4).contains(&code.len())
+                    {
+                        self.cid_chars.insert((code.len(), code_value(code)), *cid);
+                    }
+                }
+            }
+            b"endcidrange" => {
+                for triple in operands.chunks_exact(3) {
+                    if let [Operand::Bytes(low), Operand::Bytes(high), Operand::Int(cid)] = triple
+                        && low.len() == high.len()
+                        && (1..=4).contains(&low.len())
+                    {
+                        self.cid_ranges.push(CodeRange {
+                            len: low.len(),
+                            low: code_value(low),
+                            high: code_value(high),
+                            target: *cid,
+                        });
+                    }
+                }
+            }
+            b"endbfchar" => {
+                for pair in operands.chunks_exact(2) {
+                    let [Operand::Bytes(code), dst] = pair else {
+                        continue;
+                    };
+                    if !(1..=4).contains(&code.len()) {
+                        continue;
+                    }
+                    let text = match dst {
+                        Operand::Bytes(bytes) => String::from_utf16_lossy(&utf16_units(bytes)),
+                        // Glyph names like /uni65E5 or /space.
+                        Operand::Name(name) => match glyph_name_text(name) {
+                            Some(text) => text,
+                            None => continue,
+                        },
+                        _ => continue,
+                    };
+                    self.bf_chars.insert((code.len(), code_value(code)), text);
+                }
+            }
+            b"endbfrange" => {
+                for triple in operands.chunks_exact(3) {
+                    let [Operand::Bytes(low), Operand::Bytes(high), dst] = triple else {
+                        continue;
+                    };
+                    if low.len() != high.len() || !(1..=4).contains(&low.len()) {
+                        continue;
+                    }
+                    let target = match dst {
+                        Operand::Bytes(bytes) => BfTarget::Start(utf16_units(bytes)),
+                        Operand::Array(items) => BfTarget::List(
+                            items
+                                .iter()
+                                .map(|item| match item {
+                                    Operand::Bytes(bytes) => {
+                                        String::from_utf16_lossy(&utf16_units(bytes))
+                                    }
+                                    _ => String::new(),
+                                })
+                                .collect(),
+                        ),
+                        _ => continue,
+                    };
+                    self.bf_ranges.push(CodeRange {
+                        len: low.len(),
+                        low: code_value(low),
+                        high: code_value(high),
+                        target,
+                    });
+                }
+            }
+            b"usecmap" => {
+                if let Some(Operand::Name(name)) = operands.last() {
+                    match Self::predefined(&String::from_utf8_lossy(name)) {
+                        Some(parent) => self.inherit(parent),
+                        None => tracing::debug!(
+                            "usecmap of unknown CMap {}",
+                            String::from_utf8_lossy(name)
+                        ),
+                    }
+                }
+            }
+            b"def" => match operands {
+                [Operand::Name(key), Operand::Name(value)] if key == b"CMapName" => {
+                    self.name = Some(String::from_utf8_lossy(value).into_owned());
+                }
+                [Operand::Name(key), Operand::Int(mode)] if key == b"WMode" => {
+                    self.vertical = *mode == 1;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Take over what `parent` defines; this `CMap`'s own entries still win.
+    fn inherit(&mut self, parent: Self) {
+        self.codespace.extend(parent.codespace);
+        for (code, cid) in parent.cid_chars {
+            self.cid_chars.entry(code).or_insert(cid);
+        }
+        self.cid_ranges.splice(0..0, parent.cid_ranges);
+        for (code, text) in parent.bf_chars {
+            self.bf_chars.entry(code).or_insert(text);
+        }
+        self.bf_ranges.splice(0..0, parent.bf_ranges);
+        self.unicode = self.unicode.or(parent.unicode);
+        self.identity |= parent.identity;
+        self.vertical |= parent.vertical;
+    }
+
+    /// A predefined `CMap` by name: `Identity-H`/`-V`, or one of the Adobe
+    /// Unicode encodings (`Uni…-UCS2-`, `-UTF16-`, `-UTF8-` or `-UTF32-`
+    /// followed by `H` or `V`).
+    pub fn predefined(name: &str) -> Option<Self> {
+        let range = |low: &[u8], high: &[u8]| (low.to_vec(), high.to_vec());
+        let vertical = name.ends_with("-V");
+        let horizontal = name.ends_with("-H");
+        if !vertical && !horizontal {
+            return None;
+        }
+        let mut cmap = Self {
+            name: Some(name.to_string()),
+            vertical,
+            ..Self::default()
+        };
+        if name.starts_with("Identity-") {
+            cmap.codespace.push(range(&[0, 0], &[0xFF, 0xFF]));
+            cmap.identity = true;
+            return Some(cmap);
+        }
+        if !name.starts_with("Uni") {
+            return None;
+        }
+        let form = name[..name.len() - 2].rsplit('-').next()?;
+        match form {
+            "UCS2" | "UTF16" => {
+                cmap.codespace.push(range(&[0, 0], &[0xD7, 0xFF]));
+                cmap.codespace.push(range(&[0xE0, 0], &[0xFF, 0xFF]));
+                if form == "UTF16" {
+                    cmap.codespace
+                        .push(range(&[0xD8, 0, 0xDC, 0], &[0xDB, 0xFF, 0xDF, 0xFF]));
+                }
+                cmap.unicode = Some(UnicodeCodes::Utf16);
+            }
+            "UTF8" => {
+                cmap.codespace.push(range(&[0], &[0x7F]));
+                cmap.codespace.push(range(&[0xC2, 0x80], &[0xDF, 0xBF]));
+                cmap.codespace
+                    .push(range(&[0xE0, 0x80, 0x80], &[0xEF, 0xBF, 0xBF]));
+                cmap.codespace
+                    .push(range(&[0xF0, 0x80, 0x80, 0x80], &[0xF4, 0xBF, 0xBF, 0xBF]));
+                cmap.unicode = Some(UnicodeCodes::Utf8);
+            }
+            "UTF32" => {
+                cmap.codespace
+                    .push(range(&[0, 0, 0, 0], &[0, 0x10, 0xFF, 0xFF]));
+                cmap.unicode = Some(UnicodeCodes::Utf32);
+            }
+            _ => return None,
+        }
+        Some(cmap)
+    }
+
+    /// Length in bytes of the code at the start of `bytes`. Without a
+    /// matching code space range one byte is taken; with no code space at
+    /// all, as in some `/ToUnicode` `CMap`s, codes are two bytes.
+    fn code_len(&self, bytes: &[u8]) -> usize {
+        if self.codespace.is_empty() {
+            return bytes.len().min(2);
+        }
+        (1..=4.min(bytes.len()))
+            .find(|&len| {
+                self.codespace.iter().any(|(low, high)| {
+                    low.len() == len
+                        && bytes[..len]
+                            .iter()
+                            .zip(low.iter().zip(high))
+                            .all(|(b, (lo, hi))| (lo..=hi).contains(&b))
+                })
+            })
+            .unwrap_or(1)
+    }
+
+    /// Split `bytes` into codes as (code, length in bytes).
+    pub fn codes(&self, bytes: &[u8]) -> Vec<(u32, usize)> {
+        let mut codes = Vec::new();
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let len = self.code_len(rest);
+            codes.push((code_value(&rest[..len]), len));
+            rest = &rest[len..];
+        }
+        codes
+    }
+
+    /// The CID of a `len`-byte code.
+    pub fn cid(&self, code: u32, len: usize) -> Option<u32> {
+        if let Some(&cid) = self.cid_chars.get(&(len, code)) {
+            return Some(cid);
+        }
+        if let Some(range) = self
+            .cid_ranges
+            .iter()
+            .rev()
+            .find(|r| r.len == len && (r.low..=r.high).contains(&code))
+        {
+            return Some(range.target + (code - range.low));
+        }
+        self.identity.then_some(code)
+    }
+
+    /// The Unicode text of a `len`-byte code.
+    pub fn text(&self, code: u32, len: usize) -> Option<String> {
+        if let Some(text) = self.bf_chars.get(&(len, code)) {
+            return Some(text.clone());
+        }
+        if let Some(range) = self
+            .bf_ranges
+            .iter()
+            .rev()
+            .find(|r| r.len == len && (r.low..=r.high).contains(&code))
+        {
+            let offset = code - range.low;
+            return match &range.target {
+                BfTarget::Start(units) => {
+                    let mut units = units.clone();
+                    let last = units.last_mut()?;
+                    *last = last.checked_add(u16::try_from(offset).ok()?)?;
+                    Some(String::from_utf16_lossy(&units))
+                }
+                BfTarget::List(texts) => texts.get(offset as usize).cloned(),
+            };
+        }
+        let form = self.unicode?;
+        let bytes = code.to_be_bytes();
+        unicode_code_text(form, &bytes[4 - len.min(4)..])
+    }
+
+    /// The text of a string shown in a font with this `CMap`. Codes without
+    /// a mapping become U+FFFD.
+    pub fn to_unicode(&self, bytes: &[u8]) -> String {
+        self.codes(bytes)
+            .into_iter()
+            .map(|(code, len)| {
+                self.text(code, len)
+                    .unwrap_or_else(|| char::REPLACEMENT_CHARACTER.to_string())
+            })
+            .collect()
+    }
+}
+
+/// The Unicode value of a `code` in `cmap`, or its first character when it
+/// maps to several (a ligature). The code's length is the shortest the code
+/// space allows for it.
+pub fn cmap_lookup(cmap: &CMap, code: u32) -> Option<u32> {
+    let bytes = code.to_be_bytes();
+    let fits = |len: usize| len == 4 || code >> (8 * len) == 0;
+    let mut lengths: Vec<usize> = (1..=4).filter(|&len| fits(len)).collect();
+    // Prefer the lengths the code space allows, shortest first.
+    lengths.sort_by_key(|&len| cmap.code_len(&bytes[4 - len..]) != len);
+    lengths
+        .into_iter()
+        .find_map(|len| cmap.text(code, len))
+        .and_then(|text| text.chars().next())
+        .map(u32::from)
+}
+
+/// The text of a glyph name in a `bfchar` destination: `uniXXXX`, `uXXXX`
+/// or a single character such as `A`.
+fn glyph_name_text(name: &[u8]) -> Option<String> {
+    let name = std::str::from_utf8(name).ok()?;
+    if let Some(hex) = name.strip_prefix("uni").or_else(|| name.strip_prefix('u'))
+        && (4..=6).contains(&hex.len())
+        && let Ok(value) = u32::from_str_radix(hex, 16)
+    {
+        return char::from_u32(value).map(String::from);
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c.to_string()),
+        _ => None,
+    }
+}
+
+/// The `CMap` that maps a font's string bytes to Unicode: its `/ToUnicode`
+/// stream, or for a composite font a predefined Unicode `/Encoding`.
+pub fn font_unicode_map(doc: &Document, font: &Dictionary) -> Option<CMap> {
+    let resolve = |obj: &Object| match obj {
+        Object::Reference(id) => doc.get_object(*id).ok().cloned(),
+        other => Some(other.clone()),
+    };
+    match font.get(b"ToUnicode").ok().and_then(resolve) {
+        Some(Object::Stream(stream)) => {
+            let data = if stream.dict.has(b"Filter") {
+                stream.decompressed_content().ok()?
+            } else {
+                stream.content
+            };
+            return Some(with_parent_chain(doc, CMap::parse(&data), &stream.dict, 0));
+        }
+        Some(Object::Name(name)) if name.starts_with(b"Uni") => {
+            return CMap::predefined(&String::from_utf8_lossy(&name));
+        }
+        _ => {}
+    }
+    match font.get(b"Encoding").ok().and_then(resolve)? {
+        Object::Name(name) => {
+            CMap::predefined(&String::from_utf8_lossy(&name)).filter(|cmap| cmap.unicode.is_some())
+        }
+        _ => None,
+    }
+}
+
+/// Follow a `CMap` stream's `/UseCMap` entry, which names or embeds the
+/// `CMap` it extends.
+fn with_parent_chain(doc: &Document, mut cmap: CMap, dict: &Dictionary, depth: usize) -> CMap {
+    if depth >= MAX_USECMAP_DEPTH {
+        return cmap;
+    }
+    let parent = match dict.get(b"UseCMap") {
+        Ok(Object::Name(name)) => CMap::predefined(&String::from_utf8_lossy(name)),
+        Ok(Object::Reference(id)) => {
+            doc.get_object(*id)
+                .and_then(Object::as_stream)
+                .ok()
+                .map(|stream| {
+                    let data = if stream.dict.has(b"Filter") {
+                        stream.decompressed_content().unwrap_or_default()
+                    } else {
+                        stream.content.clone()
+                    };
+                    with_parent_chain(doc, CMap::parse(&data), &stream.dict, depth + 1)
+                })
+        }
+        _ => None,
+    };
+    if let Some(parent) = parent {
+        cmap.inherit(parent);
+    }
+    cmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TO_UNICODE: &[u8] = b"/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def
+/CMapName /Adobe-Identity-UCS def
+/CMapType 2 def
+1 begincodespacerange
+<0000> <FFFF>
+endcodespacerange
+2 beginbfchar
+<0001> <65E5>
+<0002> <672C>
+endbfchar
+2 beginbfrange
+<0003> <0004> <8A9E>
+<0010> <0011> [<FB01> <D83DDE00>]
+endbfrange
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end";
+
+    #[test]
+    fn test_parses_to_unicode() {
+        let cmap = CMap::parse(TO_UNICODE);
+        assert_eq!(cmap.name.as_deref(), Some("Adobe-Identity-UCS"));
+        assert_eq!(cmap.to_unicode(&[0, 1, 0, 2, 0, 3]), "日本語");
+        assert_eq!(cmap.text(4, 2).as_deref(), Some("\u{8A9F}"));
+        assert_eq!(cmap.text(0x11, 2).as_deref(), Some("\u{1F600}"));
+        assert_eq!(cmap.to_unicode(&[0, 9]), "\u{FFFD}");
+        assert_eq!(cmap_lookup(&cmap, 2), Some(0x672C));
+        assert_eq!(cmap_lookup(&cmap, 0x10), Some(0xFB01));
+        assert_eq!(cmap_lookup(&cmap, 0x12), None);
+    }
+
+    #[test]
+    fn test_mixed_code_lengths() {
+        let cmap = CMap::parse(
+            b"2 begincodespacerange <00> <80> <8140> <9FFC> endcodespacerange
+              1 begincidrange <8140> <817E> 633 endcidrange
+              1 begincidchar <41> 34 endcidchar",
+        );
+        assert_eq!(
+            cmap.codes(&[0x41, 0x81, 0x42, 0x20]),
+            [(0x41, 1), (0x8142, 2), (0x20, 1)]
+        );
+        assert_eq!(cmap.cid(0x8142, 2), Some(635));
+        assert_eq!(cmap.cid(0x41, 1), Some(34));
+        assert_eq!(cmap.cid(0x42, 1), None);
+    }
+
+    #[test]
+    fn test_predefined_unicode_cmaps() {
+        let ucs2 = CMap::predefined("UniJIS-UCS2-H").unwrap();
+        assert_eq!(ucs2.to_unicode(&[0x65, 0xE5, 0x67, 0x2C]), "日本");
+        let utf16 = CMap::predefined("UniGB-UTF16-V").unwrap();
+        assert!(utf16.vertical);
+        assert_eq!(
+            utf16.to_unicode(&[0xD8, 0x3D, 0xDE, 0x00, 0x4E, 0x2D]),
+            "\u{1F600}中"
+        );
+        let utf8 = CMap::predefined("UniKS-UTF8-H").unwrap();
+        assert_eq!(utf8.to_unicode("한글 ok".as_bytes()), "한글 ok");
+        let identity = CMap::predefined("Identity-H").unwrap();
+        assert_eq!(identity.cid(0x1234, 2), Some(0x1234));
+        assert_eq!(identity.text(0x1234, 2), None);
+        assert!(CMap::predefined("90ms-RKSJ-H").is_none());
+    }
+
+    #[test]
+    fn test_type0_font_with_embedded_to_unicode() {
+        let mut doc = Document::with_version("1.7");
+        let mut stream = lopdf::Stream::new(Dictionary::new(), TO_UNICODE.to_vec());
+        stream.compress().unwrap();
+        let to_unicode = doc.add_object(stream);
+        let font = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type0".to_vec())),
+            (
+                "BaseFont",
+                Object::Name(b"ABCDEF+KozMinPr6N-Regular".to_vec()),
+            ),
+            ("Encoding", Object::Name(b"Identity-H".to_vec())),
+            ("ToUnicode", Object::Reference(to_unicode)),
+        ]);
+        let cmap = font_unicode_map(&doc, &font).unwrap();
+        assert_eq!(cmap.to_unicode(&[0, 1, 0, 2, 0, 3]), "日本語");
+
+        let encoded_only =
+            Dictionary::from_iter(vec![("Encoding", Object::Name(b"UniJIS-UTF16-H".to_vec()))]);
+        let cmap = font_unicode_map(&doc, &encoded_only).unwrap();
+        assert_eq!(cmap.to_unicode(&[0x8A, 0x9E]), "語");
+        let identity_only =
+            Dictionary::from_iter(vec![("Encoding", Object::Name(b"Identity-H".to_vec()))]);
+        assert!(font_unicode_map(&doc, &identity_only).is_none());
+    }
+}
@@ -0,0 +1,442 @@
+//! PDF colour spaces and conversion to sRGB.
+//!
+//! Device spaces use the naive formulas. `/Lab` is converted through CIE
+//! XYZ with Bradford adaptation from the space's white point to D65.
+//! `/ICCBased` spaces go through their embedded profile with `qcms` while
+//! ICC handling is enabled (the default), and through their alternate space
+//! otherwise or when the profile can't be used. `/Separation` and `/DeviceN`
+//! colours are mapped into their alternate space by their tint transform.
+
+use crate::function::PdfFunction;
+use crate::models::{ColorSpace, PdfError, PdfResult};
+use lopdf::{Document, Object};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ICC_ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn enable_icc() {
+    ICC_ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Convert `/ICCBased` colours through their alternate spaces instead of
+/// their profiles, as faster and less exact renderers do.
+pub fn disable_icc() {
+    ICC_ENABLED.store(false, Ordering::Relaxed);
+}
+
+pub fn icc_enabled() -> bool {
+    ICC_ENABLED.load(Ordering::Relaxed)
+}
+
+/// CIE XYZ of the D65 white point, which sRGB is defined against.
+const D65: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+/// The D50 white point PDF uses when a profile gives none.
+pub const D50: [f32; 3] = [0.964_22, 1.0, 0.825_21];
+
+const BRADFORD: [[f32; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+const BRADFORD_INVERSE: [[f32; 3]; 3] = [
+    [0.986_993, -0.147_054, 0.159_963],
+    [0.432_305, 0.518_360, 0.049_291],
+    [-0.008_529, 0.040_043, 0.968_487],
+];
+const XYZ_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266, 1.876_010_8, 0.041_556],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+
+fn mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|i| m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2])
+}
+
+/// XYZ relative to `white` to sRGB in 0..=1.
+fn xyz_to_srgb(xyz: [f32; 3], white: [f32; 3]) -> [f32; 3] {
+    let cone = mul(&BRADFORD, xyz);
+    let from = mul(&BRADFORD, white);
+    let to = mul(&BRADFORD, D65);
+    let adapted = mul(
+        &BRADFORD_INVERSE,
+        [0, 1, 2].map(|i| cone[i] * to[i] / from[i]),
+    );
+    mul(&XYZ_TO_LINEAR_SRGB, adapted).map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+fn lab_to_xyz(lab: [f32; 3], white: [f32; 3]) -> [f32; 3] {
+    let inverse = |t: f32| {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA {
+            t * t * t
+        } else {
+            3.0 * DELTA * DELTA * (t - 4.0 / 29.0)
+        }
+    };
+    let fy = (lab[0] + 16.0) / 116.0;
+    let [x, y, z] = [fy + lab[1] / 500.0, fy, fy - lab[2] / 200.0].map(inverse);
+    [x * white[0], y * white[1], z * white[2]]
+}
+
+/// An embedded ICC profile ready to convert to sRGB.
+#[derive(Clone)]
+pub struct IccProfile {
+    components: usize,
+    transform: Arc<qcms::Transform>,
+}
+
+impl std::fmt::Debug for IccProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IccProfile")
+            .field("components", &self.components)
+            .finish_non_exhaustive()
+    }
+}
+
+impl IccProfile {
+    /// Parse a profile for `components` colour channels (1, 3 or 4).
+    pub fn new(data: &[u8], components: usize) -> PdfResult<Self> {
+        let input_type = match components {
+            1 => qcms::DataType::Gray8,
+            3 => qcms::DataType::RGB8,
+            4 => qcms::DataType::CMYK,
+            n => {
+                return Err(PdfError::from(format!(
+                    "ICC profiles with {n} components are not supported"
+                )));
+            }
+        };
+        let profile = qcms::Profile::new_from_slice(data, false)
+            .ok_or_else(|| PdfError::from("ICC profile is corrupt"))?;
+        let mut srgb = qcms::Profile::new_sRGB();
+        srgb.precache_output_transform();
+        let transform = qcms::Transform::new_to(
+            &profile,
+            &srgb,
+            input_type,
+            qcms::DataType::RGB8,
+            qcms::Intent::Perceptual,
+        )
+        .ok_or_else(|| PdfError::from("ICC profile can't be converted to sRGB"))?;
+        Ok(Self {
+            components,
+            transform: Arc::new(transform),
+        })
+    }
+
+    fn to_rgb(&self, values: &[f32]) -> [f32; 3] {
+        let src: Vec<u8> = values
+            .iter()
+            .take(self.components)
+            .map(|&v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect();
+        let mut dst = [0u8; 3];
+        self.transform.convert(&src, &mut dst);
+        dst.map(|c| f32::from(c) / 255.0)
+    }
+}
+
+/// A colour space as a PDF declares it, with whatever is needed to convert
+/// its values to RGB. `models::ColorSpace` only names the device spaces.
+#[derive(Debug, Clone)]
+pub enum PdfColorspace {
+    Device(ColorSpace),
+    /// CIE L*a*b* with the space's white point and a*/b* ranges.
+    Lab {
+        white_point: [f32; 3],
+        range: [f32; 4],
+    },
+    Icc {
+        /// `None` when the profile couldn't be parsed; the alternate is
+        /// used then.
+        profile: Option<IccProfile>,
+        alternate: Box<Self>,
+    },
+    /// A spot colour, or with several colorants `/DeviceN`, converted by
+    /// running the tints through `tint_fn` into the alternate space.
+    Separation {
+        colorants: Vec<String>,
+        alternate: Box<Self>,
+        tint_fn: Arc<PdfFunction>,
+    },
+}
+
+impl PdfColorspace {
+    pub fn components(&self) -> usize {
+        match self {
+            Self::Device(cs) => cs.components(),
+            Self::Lab { .. } => 3,
+            Self::Icc { alternate, .. } => alternate.components(),
+            Self::Separation { colorants, .. } => colorants.len(),
+        }
+    }
+
+    /// Read a colour space object: a device name, `[/Lab dict]`,
+    /// `[/ICCBased stream]`, `[/Separation name alternate tint]`,
+    /// `[/DeviceN names alternate tint ...]`, or the calibrated spaces, which
+    /// are treated as their device equivalents.
+    pub fn from_object(doc: &Document, obj: &Object) -> PdfResult<Self> {
+        let obj = match obj {
+            Object::Reference(id) => doc
+                .get_object(*id)
+                .map_err(|e| PdfError::from(e.to_string()))?,
+            other => other,
+        };
+        let unsupported = || PdfError::from("Unsupported colour space");
+        match obj {
+            Object::Name(name) => ColorSpace::from_name(name)
+                .map(Self::Device)
+                .ok_or_else(unsupported),
+            Object::Array(items) => {
+                let family = items
+                    .first()
+                    .and_then(|o| o.as_name().ok())
+                    .ok_or_else(unsupported)?;
+                let param = items.get(1).map(|o| match o {
+                    Object::Reference(id) => doc.get_object(*id).unwrap_or(o),
+                    other => other,
+                });
+                match family {
+                    b"CalGray" => Ok(Self::Device(ColorSpace::DeviceGray)),
+                    b"CalRGB" => Ok(Self::Device(ColorSpace::DeviceRgb)),
+                    b"Lab" => {
+                        let dict = param.and_then(|p| p.as_dict().ok());
+                        let numbers = |key: &[u8]| -> Option<Vec<f32>> {
+                            dict?
+                                .get(key)
+                                .ok()?
+                                .as_array()
+                                .ok()?
+                                .iter()
+                                .map(|v| v.as_float().ok())
+                                .collect()
+                        };
+                        let white_point = numbers(b"WhitePoint")
+                            .and_then(|v| v.try_into().ok())
+                            .unwrap_or(D50);
+                        let range = numbers(b"Range")
+                            .and_then(|v| v.try_into().ok())
+                            .unwrap_or([-100.0, 100.0, -100.0, 100.0]);
+                        Ok(Self::Lab { white_point, range })
+                    }
+                    b"ICCBased" => {
+                        let stream = param
+                            .and_then(|p| p.as_stream().ok())
+                            .ok_or_else(unsupported)?;
+                        let n = stream
+                            .dict
+                            .get(b"N")
+                            .and_then(Object::as_i64)
+                            .map_err(|_| PdfError::from("ICCBased stream has no /N"))?;
+                        let alternate = match stream.dict.get(b"Alternate") {
+                            Ok(alt) => Self::from_object(doc, alt)?,
+                            Err(_) => Self::Device(match n {
+                                1 => ColorSpace::DeviceGray,
+                                4 => ColorSpace::DeviceCmyk,
+                                _ => ColorSpace::DeviceRgb,
+                            }),
+                        };
+                        let data = if stream.dict.has(b"Filter") {
+                            stream.decompressed_content().ok()
+                        } else {
+                            Some(stream.content.clone())
+                        };
+                        let profile = data.and_then(|d| {
+                            IccProfile::new(&d, usize::try_from(n).unwrap_or(0)).ok()
+                        });
+                        Ok(Self::Icc {
+                            profile,
+                            alternate: Box::new(alternate),
+                        })
+                    }
+                    b"Separation" | b"DeviceN" => {
+                        let colorants: Vec<String> = match param {
+                            Some(Object::Name(name)) => {
+                                vec![String::from_utf8_lossy(name).into_owned()]
+                            }
+                            Some(Object::Array(names)) => names
+                                .iter()
+                                .filter_map(|n| n.as_name().ok())
+                                .map(|n| String::from_utf8_lossy(n).into_owned())
+                                .collect(),
+                            _ => return Err(unsupported()),
+                        };
+                        let (Some(alternate), Some(tint)) = (items.get(2), items.get(3)) else {
+                            return Err(unsupported());
+                        };
+                        let alternate = Self::from_object(doc, alternate)?;
+                        let tint_fn = PdfFunction::from_object(doc, tint)?;
+                        if colorants.is_empty() || tint_fn.outputs() != alternate.components() {
+                            return Err(PdfError::from(
+                                "Tint transform doesn't match the alternate space",
+                            ));
+                        }
+                        Ok(Self::Separation {
+                            colorants,
+                            alternate: Box::new(alternate),
+                            tint_fn: Arc::new(tint_fn),
+                        })
+                    }
+                    _ => Err(unsupported()),
+                }
+            }
+            _ => Err(unsupported()),
+        }
+    }
+}
+
+/// Convert one colour, given as the space's component values, to sRGB with
+/// components in 0..=1.
+pub fn convert_color(colorspace: &PdfColorspace, values: &[f32]) -> [f32; 3] {
+    let v = |i: usize| values.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+    match colorspace {
+        PdfColorspace::Device(ColorSpace::DeviceGray) => [v(0); 3],
+        PdfColorspace::Device(ColorSpace::DeviceRgb) => [v(0), v(1), v(2)],
+        PdfColorspace::Device(ColorSpace::DeviceCmyk) => {
+            [v(0), v(1), v(2)].map(|c| 1.0 - (c + v(3)).min(1.0))
+        }
+        PdfColorspace::Lab { white_point, range } => {
+            let get = |i: usize| values.get(i).copied().unwrap_or(0.0);
+            let lab = [
+                get(0).clamp(0.0, 100.0),
+                get(1).clamp(range[0], range[1]),
+                get(2).clamp(range[2], range[3]),
+            ];
+            xyz_to_srgb(lab_to_xyz(lab, *white_point), *white_point)
+        }
+        PdfColorspace::Icc { profile, alternate } => match profile {
+            Some(profile) if icc_enabled() => profile.to_rgb(values),
+            _ => convert_color(alternate, values),
+        },
+        PdfColorspace::Separation {
+            colorants,
+            alternate,
+            tint_fn,
+        } => {
+            let tints: Vec<f32> = (0..colorants.len()).map(v).collect();
+            convert_color(alternate, &tint_fn.eval(&tints))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.into_iter().zip(expected) {
+            assert!((a - e).abs() < 0.01, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_lab_to_srgb() {
+        // sRGB red and mid grey, in D65 and D50 Lab respectively.
+        let d65 = PdfColorspace::Lab {
+            white_point: D65,
+            range: [-128.0, 127.0, -128.0, 127.0],
+        };
+        assert_close(
+            convert_color(&d65, &[53.2408, 80.0925, 67.2032]),
+            [1.0, 0.0, 0.0],
+        );
+        let d50 = PdfColorspace::Lab {
+            white_point: D50,
+            range: [-100.0, 100.0, -100.0, 100.0],
+        };
+        assert_close(convert_color(&d50, &[50.0, 0.0, 0.0]), [0.4663; 3]);
+        assert_close(convert_color(&d50, &[100.0, 0.0, 0.0]), [1.0; 3]);
+    }
+
+    #[test]
+    fn test_parses_lab_and_falls_back_for_broken_icc() {
+        let mut doc = Document::with_version("1.7");
+        let icc = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::from_iter(vec![
+                ("N", Object::Integer(3)),
+                ("Alternate", Object::Name(b"DeviceGray".to_vec())),
+            ]),
+            b"not a profile".to_vec(),
+        ));
+        let cs = PdfColorspace::from_object(
+            &doc,
+            &Object::Array(vec![
+                Object::Name(b"ICCBased".to_vec()),
+                Object::Reference(icc),
+            ]),
+        )
+        .unwrap();
+        assert!(matches!(&cs, PdfColorspace::Icc { profile: None, .. }));
+        assert_eq!(convert_color(&cs, &[0.25]), [0.25; 3]);
+
+        let lab = PdfColorspace::from_object(
+            &doc,
+            &Object::Array(vec![
+                Object::Name(b"Lab".to_vec()),
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "WhitePoint",
+                    Object::Array(vec![
+                        Object::Real(0.9505),
+                        Object::Real(1.0),
+                        Object::Real(1.089),
+                    ]),
+                )])),
+            ]),
+        )
+        .unwrap();
+        assert!(matches!(lab, PdfColorspace::Lab { white_point, .. } if white_point[2] == 1.089));
+    }
+
+    #[test]
+    fn test_separation_maps_tint_to_alternate() {
+        let doc = Document::with_version("1.7");
+        // A pure-magenta spot colour.
+        let tint = Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("FunctionType", Object::Integer(2)),
+            (
+                "Domain",
+                Object::Array(vec![Object::Integer(0), Object::Integer(1)]),
+            ),
+            ("C0", Object::Array(vec![Object::Integer(0); 4])),
+            (
+                "C1",
+                Object::Array(vec![
+                    Object::Integer(0),
+                    Object::Integer(1),
+                    Object::Integer(0),
+                    Object::Integer(0),
+                ]),
+            ),
+            ("N", Object::Integer(1)),
+        ]));
+        let cs = PdfColorspace::from_object(
+            &doc,
+            &Object::Array(vec![
+                Object::Name(b"Separation".to_vec()),
+                Object::Name(b"Rhodamine".to_vec()),
+                Object::Name(b"DeviceCMYK".to_vec()),
+                tint,
+            ]),
+        )
+        .unwrap();
+        assert_eq!(cs.components(), 1);
+        assert_close(convert_color(&cs, &[1.0]), [1.0, 0.0, 1.0]);
+        assert_close(convert_color(&cs, &[0.0]), [1.0; 3]);
+    }
+
+    #[test]
+    fn test_cmyk_is_naive() {
+        let cmyk = PdfColorspace::Device(ColorSpace::DeviceCmyk);
+        assert_close(convert_color(&cmyk, &[1.0, 0.0, 0.0, 0.0]), [0.0, 1.0, 1.0]);
+        assert_close(convert_color(&cmyk, &[0.0, 0.0, 0.0, 0.5]), [0.5; 3]);
+    }
+}
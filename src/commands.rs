@@ -2,7 +2,7 @@ use crate::models::{
     Annotation, DetectedTable, DocumentId, DocumentMeta, FormField, OpenResult, PdfResult,
     RenderResult, SearchResultItem, TextItem,
 };
-use crate::pdf_engine::RenderOptions;
+use crate::pdf_engine::{ReflowLayout, RenderOptions};
 use tokio::sync::oneshot;
 
 #[derive(Debug)]
@@ -13,10 +13,14 @@ pub enum PdfCommand {
         DocumentId,
         oneshot::Sender<PdfResult<OpenResult>>,
     ),
+    /// The `u64` is the tab's render generation at dispatch time; workers
+    /// drop the request if a newer generation has been published. One-off
+    /// renders such as clipboard copies pass `None` and are never dropped.
     Render(
         DocumentId,
         usize,
         RenderOptions,
+        Option<u64>,
         oneshot::Sender<PdfResult<RenderResult>>,
     ),
     RenderThumbnail(
@@ -61,12 +65,21 @@ pub enum PdfCommand {
     ),
     // New features
     Merge(Vec<String>, String, oneshot::Sender<PdfResult<String>>),
+    /// Source path, pages, output directory, and a channel that receives
+    /// `(written, total)` after each page file.
     Split(
         String,
         Vec<usize>,
         String,
+        tokio::sync::mpsc::UnboundedSender<(usize, usize)>,
         oneshot::Sender<PdfResult<Vec<String>>>,
     ),
+    ExtractPages(
+        DocumentId,
+        Vec<usize>,
+        String,
+        oneshot::Sender<PdfResult<String>>,
+    ),
     GetFormFields(String, oneshot::Sender<PdfResult<Vec<FormField>>>),
     FillForm(
         String,
@@ -74,16 +87,94 @@ pub enum PdfCommand {
         String,
         oneshot::Sender<PdfResult<String>>,
     ),
-    PrintPdf(String, Option<String>, oneshot::Sender<PdfResult<()>>),
+    PrintPdf(
+        String,
+        Option<String>,
+        Option<Vec<usize>>,
+        oneshot::Sender<PdfResult<()>>,
+    ),
     ListPrinters(oneshot::Sender<PdfResult<Vec<String>>>),
-    AddWatermark(String, String, String, oneshot::Sender<PdfResult<String>>),
-    Optimize(String, String, oneshot::Sender<PdfResult<String>>),
+    AddWatermark(
+        String,
+        String,
+        crate::pdf_engine::WatermarkOptions,
+        String,
+        oneshot::Sender<PdfResult<String>>,
+    ),
+    Optimize(String, String, oneshot::Sender<PdfResult<(String, i64)>>),
+    AddChart(
+        String,
+        crate::pdf_engine::ChartSpec,
+        String,
+        oneshot::Sender<PdfResult<String>>,
+    ),
+    ExportJson(
+        String,
+        crate::pdf_json::JsonConfig,
+        String,
+        oneshot::Sender<PdfResult<String>>,
+    ),
+    HtmlToPdf(
+        String,
+        Option<std::path::PathBuf>,
+        crate::flow::PageSize,
+        String,
+        oneshot::Sender<PdfResult<String>>,
+    ),
+    AddBarcode(
+        String,
+        crate::barcode::BarcodeSpec,
+        String,
+        oneshot::Sender<PdfResult<String>>,
+    ),
+    ValidatePdf(
+        String,
+        oneshot::Sender<PdfResult<Vec<crate::models::ValidationIssue>>>,
+    ),
+    RepairPdf(
+        String,
+        String,
+        oneshot::Sender<PdfResult<crate::models::RepairResult>>,
+    ),
+    MakeBooklet(String, String, oneshot::Sender<PdfResult<String>>),
+    FlattenForms(String, String, oneshot::Sender<PdfResult<(String, usize)>>),
+    FillFieldsFromMap(
+        String,
+        String,
+        std::collections::HashMap<String, String>,
+        oneshot::Sender<PdfResult<(String, Vec<String>)>>,
+    ),
+    PrependToc(
+        String,
+        Vec<crate::pdf_engine::Bookmark>,
+        String,
+        oneshot::Sender<PdfResult<String>>,
+    ),
+    PosterPdf(
+        String,
+        String,
+        (f32, f32),
+        f32,
+        oneshot::Sender<PdfResult<String>>,
+    ),
+    NupPdf(
+        String,
+        String,
+        crate::impose::NupLayout,
+        oneshot::Sender<PdfResult<String>>,
+    ),
     ReorderPages(
         String,
         Vec<usize>,
         String,
         oneshot::Sender<PdfResult<String>>,
     ),
+    /// Lay a reflowable document out again; fixed-layout documents fail.
+    LayoutDocument(
+        DocumentId,
+        ReflowLayout,
+        oneshot::Sender<PdfResult<OpenResult>>,
+    ),
     ToggleLayer(DocumentId, (u32, u16), bool),
     GetAttachmentBytes(DocumentId, (u32, u16), oneshot::Sender<PdfResult<Vec<u8>>>),
     DetectTables(
@@ -0,0 +1,151 @@
+//! Compositing one 8-bit pixmap over another.
+//!
+//! Source coverage comes as a separate straight (unpremultiplied) alpha
+//! plane, one byte per pixel. Colours are mixed before alpha is applied, so
+//! a half-transparent pixel lightens or darkens the backdrop by exactly half
+//! and never drags its edges towards black.
+
+use crate::models::{ColorSpace, PdfError, PdfResult, Pixmap};
+
+/// The separable PDF blend modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Darken,
+    Lighten,
+    Difference,
+}
+
+impl BlendMode {
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"Normal" | b"Compatible" => Some(Self::Normal),
+            b"Multiply" => Some(Self::Multiply),
+            b"Screen" => Some(Self::Screen),
+            b"Darken" => Some(Self::Darken),
+            b"Lighten" => Some(Self::Lighten),
+            b"Difference" => Some(Self::Difference),
+            _ => None,
+        }
+    }
+
+    /// Mix backdrop `b` and source `s`, both additive and in 0..=1.
+    fn mix(self, b: f32, s: f32) -> f32 {
+        match self {
+            Self::Normal => s,
+            Self::Multiply => b * s,
+            Self::Screen => b + s - b * s,
+            Self::Darken => b.min(s),
+            Self::Lighten => b.max(s),
+            Self::Difference => (b - s).abs(),
+        }
+    }
+}
+
+/// Draw `src` over `dst` with its top-left corner at (`x`, `y`), weighting
+/// each source pixel by `alpha`. Pixels falling outside `dst` are clipped.
+pub fn blend_over(
+    dst: &mut Pixmap,
+    src: &Pixmap,
+    alpha: &[u8],
+    x: i32,
+    y: i32,
+    mode: BlendMode,
+) -> PdfResult<()> {
+    if dst.bits_per_component != 8 || src.bits_per_component != 8 {
+        return Err(PdfError::from("Only 8-bit images can be composited"));
+    }
+    if dst.colorspace != src.colorspace {
+        return Err(PdfError::from(
+            "Composited images must share a colour space",
+        ));
+    }
+    let channels = dst.colorspace.components();
+    let (src_w, src_h) = (src.width as usize, src.height as usize);
+    if src.samples.len() != src_w * src_h * channels || alpha.len() != src_w * src_h {
+        return Err(PdfError::from("Image data does not match its dimensions"));
+    }
+    // Blend modes are defined on additive colour: CMYK is mixed inverted.
+    let subtractive = dst.colorspace == ColorSpace::DeviceCmyk;
+    let additive = |v: u8| {
+        let v = f32::from(v) / 255.0;
+        if subtractive { 1.0 - v } else { v }
+    };
+
+    let stride = dst.stride();
+    for sy in 0..src_h {
+        let Ok(dy) = u32::try_from(i64::from(y) + sy as i64) else {
+            continue;
+        };
+        if dy >= dst.height {
+            continue;
+        }
+        for sx in 0..src_w {
+            let Ok(dx) = u32::try_from(i64::from(x) + sx as i64) else {
+                continue;
+            };
+            if dx >= dst.width {
+                continue;
+            }
+            let coverage = f32::from(alpha[sy * src_w + sx]) / 255.0;
+            if coverage == 0.0 {
+                continue;
+            }
+            let source = &src.samples[(sy * src_w + sx) * channels..][..channels];
+            let start = dy as usize * stride + dx as usize * channels;
+            for (out, &value) in dst.samples[start..start + channels].iter_mut().zip(source) {
+                let backdrop = additive(*out);
+                let mixed = backdrop + coverage * (mode.mix(backdrop, additive(value)) - backdrop);
+                let mixed = if subtractive { 1.0 - mixed } else { mixed };
+                *out = (mixed * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, colorspace: ColorSpace, color: &[u8]) -> Pixmap {
+        Pixmap {
+            width,
+            height,
+            colorspace,
+            bits_per_component: 8,
+            samples: color.repeat((width * height) as usize),
+        }
+    }
+
+    #[test]
+    fn test_half_transparent_red_over_white_is_pink() {
+        let mut page = solid(3, 3, ColorSpace::DeviceRgb, &[255, 255, 255]);
+        let red = solid(2, 2, ColorSpace::DeviceRgb, &[255, 0, 0]);
+        blend_over(&mut page, &red, &[128; 4], 2, -1, BlendMode::Normal).unwrap();
+        assert_eq!(page.pixel(2, 0), [255, 127, 127]);
+        assert_eq!(page.pixel(1, 0), [255, 255, 255]);
+        assert_eq!(page.pixel(2, 1), [255, 255, 255]);
+
+        let mut cmyk = solid(1, 1, ColorSpace::DeviceCmyk, &[0, 0, 0, 0]);
+        let magenta = solid(1, 1, ColorSpace::DeviceCmyk, &[0, 255, 0, 0]);
+        blend_over(&mut cmyk, &magenta, &[255], 0, 0, BlendMode::Multiply).unwrap();
+        assert_eq!(cmyk.pixel(0, 0), [0, 255, 0, 0]);
+    }
+
+    #[test]
+    fn test_blend_modes_mix_colours() {
+        let grey = solid(1, 1, ColorSpace::DeviceGray, &[128]);
+        let mut dark = solid(1, 1, ColorSpace::DeviceGray, &[64]);
+        blend_over(&mut dark, &grey, &[255], 0, 0, BlendMode::Multiply).unwrap();
+        assert_eq!(dark.samples, [32]);
+        let mut light = solid(1, 1, ColorSpace::DeviceGray, &[64]);
+        blend_over(&mut light, &grey, &[255], 0, 0, BlendMode::Lighten).unwrap();
+        assert_eq!(light.samples, [128]);
+        let rgb = solid(1, 1, ColorSpace::DeviceRgb, &[0, 0, 0]);
+        assert!(blend_over(&mut light, &rgb, &[255], 0, 0, BlendMode::Normal).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Tokenizing and pretty-printing of page content streams.
+//!
+//! [`format_content_stream`] re-emits a content stream with one operator per
+//! line, single spaces between operands and indentation for `q`/`Q`,
+//! `BT`/`ET` and marked-content nesting, so generated content can be read
+//! and diffed. [`sanitize_content_stream`] rewrites one compactly, with
+//! comments dropped and `q`/`Q`, `BT`/`ET` and marked content balanced.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenType {
+    /// Numbers, names, strings, `true`/`false`/`null`.
+    Operand,
+    ArrayOpen,
+    ArrayClose,
+    DictOpen,
+    DictClose,
+    Operator,
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Token<'a> {
+    pub(crate) kind: TokenType,
+    pub(crate) text: &'a [u8],
+}
+
+const fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+/// PDF whitespace: NUL, tab, LF, FF, CR and space.
+const fn is_whitespace(b: u8) -> bool {
+    matches!(b, 0 | b'\t' | b'\n' | 0x0C | b'\r' | b' ')
+}
+
+/// A transformation matrix `[a b c d e f]`, as operands of `cm`.
+pub(crate) type Matrix = [f32; 6];
+
+pub(crate) const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+pub(crate) fn transform(m: &Matrix, x: f32, y: f32) -> (f32, f32) {
+    (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5])
+}
+
+/// `m` followed by `n`, the CTM after `m cm` when the CTM was `n`.
+pub(crate) fn concat(m: &Matrix, n: &Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+/// The inverse of `m`, or `None` when it collapses the plane.
+pub(crate) fn invert(m: &Matrix) -> Option<Matrix> {
+    let det = m[0] * m[3] - m[1] * m[2];
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    Some([
+        m[3] / det,
+        -m[1] / det,
+        -m[2] / det,
+        m[0] / det,
+        (m[2] * m[5] - m[3] * m[4]) / det,
+        (m[1] * m[4] - m[0] * m[5]) / det,
+    ])
+}
+
+pub(crate) struct Tokenizer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub(crate) const fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn take_while(&mut self, pred: impl Fn(u8) -> bool) {
+        while self.peek().is_some_and(&pred) {
+            self.pos += 1;
+        }
+    }
+
+    pub(crate) fn next_token(&mut self) -> Option<Token<'a>> {
+        self.take_while(is_whitespace);
+        let start = self.pos;
+        let first = self.peek()?;
+        self.pos += 1;
+        let kind = match first {
+            b'%' => {
+                self.take_while(|b| b != b'\n' && b != b'\r');
+                TokenType::Comment
+            }
+            b'/' => {
+                self.take_while(|b| !is_whitespace(b) && !is_delimiter(b));
+                TokenType::Operand
+            }
+            b'(' => {
+                self.skip_literal_string();
+                TokenType::Operand
+            }
+            b'<' if self.peek() == Some(b'<') => {
+                self.pos += 1;
+                TokenType::DictOpen
+            }
+            b'<' => {
+                self.take_while(|b| b != b'>');
+                self.pos = (self.pos + 1).min(self.data.len());
+                TokenType::Operand
+            }
+            b'>' if self.peek() == Some(b'>') => {
+                self.pos += 1;
+                TokenType::DictClose
+            }
+            b'[' => TokenType::ArrayOpen,
+            b']' => TokenType::ArrayClose,
+            // Stray delimiters are passed through as operators.
+            b')' | b'>' | b'{' | b'}' => TokenType::Operator,
+            _ => {
+                self.take_while(|b| !is_whitespace(b) && !is_delimiter(b));
+                let text = &self.data[start..self.pos];
+                let numeric = text
+                    .iter()
+                    .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.'));
+                if numeric || matches!(text, b"true" | b"false" | b"null") {
+                    TokenType::Operand
+                } else {
+                    TokenType::Operator
+                }
+            }
+        };
+        Some(Token {
+            kind,
+            text: &self.data[start..self.pos],
+        })
+    }
+
+    /// Advance past a literal string body, honouring escapes and balanced
+    /// parentheses. The opening `(` has already been consumed.
+    fn skip_literal_string(&mut self) {
+        let mut depth = 1;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'\\' => self.pos = (self.pos + 1).min(self.data.len()),
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Consume inline image data after `ID` and return it untouched. The
+    /// data ends at an `EI` that is preceded by whitespace and followed by
+    /// whitespace or the end of the stream.
+    pub(crate) fn inline_image_data(&mut self) -> &'a [u8] {
+        // Exactly one whitespace byte separates ID from the data.
+        if self.peek().is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let mut i = start;
+        while i + 2 <= self.data.len() {
+            let preceded = i > start && is_whitespace(self.data[i - 1]);
+            let followed = self.data.get(i + 2).is_none_or(|&b| is_whitespace(b));
+            if preceded && followed && &self.data[i..i + 2] == b"EI" {
+                self.pos = i;
+                return &self.data[start..i - 1];
+            }
+            i += 1;
+        }
+        self.pos = self.data.len();
+        &self.data[start..]
+    }
+}
+
+/// Re-emit `data` with one operator and its operands per line. Inline
+/// images stay on one line as `dict ID data EI`, with the data copied
+/// through byte for byte; that is why the result is bytes rather than a
+/// `String`.
+pub fn format_content_stream(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 4);
+    let mut tokens = Tokenizer::new(data);
+    let mut depth: usize = 0;
+    // Operands waiting for their operator, already joined with spaces.
+    let mut line: Vec<u8> = Vec::new();
+
+    let push_line = |out: &mut Vec<u8>, depth: usize, line: &[u8]| {
+        out.extend(std::iter::repeat_n(b' ', depth * 2));
+        out.extend_from_slice(line);
+        out.push(b'\n');
+    };
+
+    while let Some(token) = tokens.next_token() {
+        match token.kind {
+            TokenType::Comment => {
+                if !line.is_empty() {
+                    push_line(&mut out, depth, &std::mem::take(&mut line));
+                }
+                push_line(&mut out, depth, token.text);
+            }
+            TokenType::Operator => {
+                let op = token.text;
+                if matches!(op, b"Q" | b"ET" | b"EMC" | b"EI") {
+                    depth = depth.saturating_sub(1);
+                }
+                if !line.is_empty() {
+                    line.push(b' ');
+                }
+                line.extend_from_slice(op);
+                match op {
+                    b"q" | b"BT" | b"BMC" | b"BDC" | b"BI" => {
+                        push_line(&mut out, depth, &std::mem::take(&mut line));
+                        depth += 1;
+                    }
+                    // The image data and EI continue the ID line, which keeps
+                    // exactly one space before EI.
+                    b"ID" => {
+                        line.push(b' ');
+                        line.extend_from_slice(tokens.inline_image_data());
+                    }
+                    _ => push_line(
+                        &mut out,
+                        depth + usize::from(op == b"EI"),
+                        &std::mem::take(&mut line),
+                    ),
+                }
+            }
+            kind => {
+                let joins =
+                    line.is_empty() || kind == TokenType::ArrayClose || line.last() == Some(&b'[');
+                if !joins {
+                    line.push(b' ');
+                }
+                line.extend_from_slice(token.text);
+            }
+        }
+    }
+    if !line.is_empty() {
+        push_line(&mut out, depth, &line);
+    }
+    out
+}
+
+/// Re-emit `data` compactly with its nesting balanced.
+///
+/// Comments are dropped, as are closing operators without an opener (`Q`,
+/// `ET`, `EMC`) and a `BT` inside a text object; whatever is still open at
+/// the end is closed. Inline images are copied through as in
+/// [`format_content_stream`].
+pub fn sanitize_content_stream(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut tokens = Tokenizer::new(data);
+    let mut saves: usize = 0;
+    let mut marked: usize = 0;
+    let mut in_text = false;
+    let mut line: Vec<u8> = Vec::new();
+
+    while let Some(token) = tokens.next_token() {
+        match token.kind {
+            TokenType::Comment => {}
+            TokenType::Operator => {
+                let op = token.text;
+                let keep = match op {
+                    b"q" => {
+                        saves += 1;
+                        true
+                    }
+                    b"Q" => saves.checked_sub(1).map(|n| saves = n).is_some(),
+                    b"BT" => !std::mem::replace(&mut in_text, true),
+                    b"ET" => std::mem::replace(&mut in_text, false),
+                    b"BMC" | b"BDC" => {
+                        marked += 1;
+                        true
+                    }
+                    b"EMC" => marked.checked_sub(1).map(|n| marked = n).is_some(),
+                    _ => true,
+                };
+                if !keep {
+                    line.clear();
+                    continue;
+                }
+                if !line.is_empty() {
+                    line.push(b' ');
+                }
+                line.extend_from_slice(op);
+                if op == b"ID" {
+                    line.push(b' ');
+                    line.extend_from_slice(tokens.inline_image_data());
+                    continue;
+                }
+                out.append(&mut line);
+                out.push(b'\n');
+            }
+            kind => {
+                let joins =
+                    line.is_empty() || kind == TokenType::ArrayClose || line.last() == Some(&b'[');
+                if !joins {
+                    line.push(b' ');
+                }
+                line.extend_from_slice(token.text);
+            }
+        }
+    }
+    // Trailing operands have no operator to apply to.
+    if in_text {
+        out.extend_from_slice(b"ET\n");
+    }
+    for _ in 0..marked {
+        out.extend_from_slice(b"EMC\n");
+    }
+    for _ in 0..saves {
+        out.extend_from_slice(b"Q\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STREAM: &[u8] = b"q 1 0 0 1 10 10 cm\n  q 0 0 1 rg 0 0 10 10 re f Q\r\nBI /W 2 /H 1 /BPC 8 /CS /G ID \x00\xffEI\x10 EI Q\nBT/F1 12 Tf[(a (nested\\) str)  )-250(b)]TJ ET % done";
+
+    #[test]
+    fn test_formats_nesting_and_inline_image() {
+        let formatted = format_content_stream(STREAM);
+        let expected: &[u8] = b"q
+  1 0 0 1 10 10 cm
+  q
+    0 0 1 rg
+    0 0 10 10 re
+    f
+  Q
+  BI
+    /W 2 /H 1 /BPC 8 /CS /G ID \x00\xffEI\x10 EI
+Q
+BT
+  /F1 12 Tf
+  [(a (nested\\) str)  ) -250 (b)] TJ
+ET
+% done
+";
+        assert_eq!(
+            String::from_utf8_lossy(&formatted),
+            String::from_utf8_lossy(expected)
+        );
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_sanitize_balances_nesting() {
+        let stream = b"Q EMC q 0 0 1 rg 0 0 10 10 re f % fill\nBT /F1 12 Tf BT [(a) -5 (b)]TJ \
+                       /P <</MCID 0>> BDC BI /W 1 /H 1 ID \x00 EI";
+        let expected: &[u8] = b"q
+0 0 1 rg
+0 0 10 10 re
+f
+BT
+/F1 12 Tf
+[(a) -5 (b)] TJ
+/P << /MCID 0 >> BDC
+BI
+/W 1 /H 1 ID \x00 EI
+ET
+EMC
+Q
+";
+        let sanitized = sanitize_content_stream(stream);
+        assert_eq!(
+            String::from_utf8_lossy(&sanitized),
+            String::from_utf8_lossy(expected)
+        );
+        assert_eq!(sanitize_content_stream(&sanitized), sanitized);
+    }
+
+    #[test]
+    fn test_formatting_is_idempotent() {
+        let once = format_content_stream(STREAM);
+        assert_eq!(format_content_stream(&once), once);
+    }
+}
@@ -0,0 +1,130 @@
+//! `DCTDecode`: JPEG decoding with `zune-jpeg`.
+//!
+//! Samples come out in the image's PDF colour space. YCbCr and YCCK data is
+//! converted, and CMYK written by Adobe applications, which store every
+//! component inverted and mark the file with an APP14 segment, is flipped
+//! back.
+
+use crate::models::{ColorSpace, PdfError, PdfResult, Pixmap};
+use zune_core::bytestream::ZCursor;
+use zune_core::colorspace::ColorSpace as JpegColorSpace;
+use zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+/// Decode a `DCTDecode` stream into samples in `colorspace`, which must have
+/// as many components as the JPEG.
+pub fn decode_dct(data: &[u8], colorspace: ColorSpace) -> PdfResult<Pixmap> {
+    let corrupt = |e: zune_jpeg::errors::DecodeErrors| PdfError::from(format!("DCT data: {e}"));
+    let mut decoder = JpegDecoder::new(ZCursor::new(data));
+    decoder.decode_headers().map_err(corrupt)?;
+    let input = decoder
+        .input_colorspace()
+        .ok_or_else(|| PdfError::from("JPEG has no frame header"))?;
+    // Four-component data is kept as decoded so the Adobe inversion and the
+    // YCCK transform below see the raw samples.
+    let output = match input {
+        JpegColorSpace::Luma => JpegColorSpace::Luma,
+        JpegColorSpace::YCbCr | JpegColorSpace::RGB => JpegColorSpace::RGB,
+        JpegColorSpace::CMYK | JpegColorSpace::YCCK => input,
+        other => {
+            return Err(PdfError::from(format!(
+                "Unsupported JPEG colour space {other:?}"
+            )));
+        }
+    };
+    if output.num_components() != colorspace.components() {
+        return Err(PdfError::from(format!(
+            "JPEG has {} components but the image colour space is {colorspace:?}",
+            output.num_components()
+        )));
+    }
+    decoder.set_options(DecoderOptions::default().jpeg_set_out_colorspace(output));
+    let mut samples = decoder.decode().map_err(corrupt)?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| PdfError::from("JPEG has no frame header"))?;
+
+    if input == JpegColorSpace::YCCK {
+        for pixel in samples.chunks_exact_mut(4) {
+            let [r, g, b] = ycc_to_rgb(pixel[0], pixel[1], pixel[2]);
+            pixel[..3].copy_from_slice(&[255 - r, 255 - g, 255 - b]);
+        }
+    }
+    if output.num_components() == 4 && has_adobe_marker(data) {
+        for v in &mut samples {
+            *v = 255 - *v;
+        }
+    }
+    Ok(Pixmap {
+        width: u32::from(info.width),
+        height: u32::from(info.height),
+        colorspace,
+        bits_per_component: 8,
+        samples,
+    })
+}
+
+/// Whether an Adobe APP14 segment comes before the first scan. The decoder
+/// reads its colour transform but doesn't say whether one was present,
+/// which is what marks CMYK as inverted.
+fn has_adobe_marker(data: &[u8]) -> bool {
+    let mut pos = 2;
+    while let [0xFF, marker, high, low, ..] = *data.get(pos..).unwrap_or_default() {
+        if marker == 0xDA {
+            break;
+        }
+        let body = pos + 4..pos + 2 + usize::from(u16::from_be_bytes([high, low]));
+        if marker == 0xEE
+            && data
+                .get(body.clone())
+                .is_some_and(|b| b.starts_with(b"Adobe"))
+        {
+            return true;
+        }
+        pos = body.end;
+    }
+    false
+}
+
+fn ycc_to_rgb(y: u8, cb: u8, cr: u8) -> [u8; 3] {
+    let (y, cb, cr) = (f32::from(y), f32::from(cb) - 128.0, f32::from(cr) - 128.0);
+    [
+        y + 1.402 * cr,
+        y - 0.344_136 * cb - 0.714_136 * cr,
+        y + 1.772 * cb,
+    ]
+    .map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/tests/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    #[test]
+    fn test_adobe_cmyk_is_inverted() {
+        // Four single-component scans of inverted CMYK behind an APP14
+        // marker with transform 0.
+        let pixmap = decode_dct(&fixture("cmyk_adobe.jpg"), ColorSpace::DeviceCmyk).unwrap();
+        assert_eq!((pixmap.width, pixmap.height), (16, 8));
+        assert_eq!(pixmap.pixel(3, 3), [200, 50, 0, 25]);
+        assert_eq!(pixmap.pixel(12, 5), [0, 0, 0, 255]);
+        assert!(decode_dct(&fixture("cmyk_adobe.jpg"), ColorSpace::DeviceRgb).is_err());
+    }
+
+    #[test]
+    fn test_subsampled_ycbcr_with_restarts() {
+        // 4:2:0 YCbCr, two MCUs with a restart marker between them.
+        let pixmap = decode_dct(&fixture("ycbcr_420.jpg"), ColorSpace::DeviceRgb).unwrap();
+        assert_eq!((pixmap.width, pixmap.height), (32, 16));
+        assert_eq!(pixmap.pixel(0, 0), [119, 40, 10]);
+        assert_eq!(pixmap.pixel(15, 0), [179, 100, 70]);
+        assert_eq!(pixmap.pixel(0, 15), [239, 160, 130]);
+        assert_eq!(pixmap.pixel(8, 8), [255, 220, 190]);
+        assert_eq!(pixmap.pixel(16, 0), [255, 220, 190]);
+        assert_eq!(pixmap.pixel(31, 15), [119, 40, 10]);
+    }
+}
@@ -0,0 +1,246 @@
+//! Skew detection and correction for scanned pages.
+//!
+//! The skew angle is the one whose projection profile is sharpest: dark
+//! pixels are binned by the row they would fall on if the page were rotated
+//! by that angle, and lines of text line up into a few tall bins exactly
+//! when the guess matches. Angles are in degrees, positive when the content
+//! is turned clockwise as displayed.
+
+use crate::models::{ColorSpace, PdfError, PdfResult, Pixmap};
+
+/// The largest skew searched for, in degrees.
+const MAX_ANGLE: f32 = 15.0;
+const COARSE_STEP: f32 = 0.5;
+const FINE_STEP: f32 = 0.05;
+
+/// Whether the pixel at (x, y) is ink: below mid-grey for gray and RGB,
+/// above half coverage of black for CMYK.
+fn is_dark(pixmap: &Pixmap, x: u32, y: u32) -> bool {
+    let row = &pixmap.samples[y as usize * pixmap.stride()..];
+    if pixmap.bits_per_component == 1 {
+        return row[x as usize / 8] & (0x80 >> (x % 8)) == 0;
+    }
+    let p = pixmap.pixel(x, y);
+    match pixmap.colorspace {
+        ColorSpace::DeviceGray => p[0] < 128,
+        ColorSpace::DeviceRgb => {
+            u32::from(p[0]) * 299 + u32::from(p[1]) * 587 + u32::from(p[2]) * 114 < 128_000
+        }
+        ColorSpace::DeviceCmyk => u32::from(p[0].max(p[1]).max(p[2])) + u32::from(p[3]) > 128,
+    }
+}
+
+fn check_supported(pixmap: &Pixmap) -> PdfResult<()> {
+    match (pixmap.bits_per_component, pixmap.colorspace) {
+        (8, _) | (1, ColorSpace::DeviceGray) => Ok(()),
+        (bpc, cs) => Err(PdfError::from(format!(
+            "Deskewing {bpc}-bit {cs:?} pixmaps is not supported"
+        ))),
+    }
+}
+
+/// Sum of squared differences between neighbouring bins of the profile at
+/// `angle`; larger means sharper.
+fn profile_score(dark: &[(f32, f32)], angle: f32, height: u32, width: u32) -> f64 {
+    let slope = angle.to_radians().tan();
+    // Rows may shift by up to width * |slope| either way.
+    let margin = (width as f32 * slope.abs()).ceil() as usize + 1;
+    let mut bins = vec![0u32; height as usize + 2 * margin];
+    for &(x, y) in dark {
+        let row = (y - x * slope).round() as isize + margin as isize;
+        if let Some(bin) = usize::try_from(row).ok().and_then(|r| bins.get_mut(r)) {
+            *bin += 1;
+        }
+    }
+    bins.windows(2)
+        .map(|w| {
+            let d = f64::from(w[1]) - f64::from(w[0]);
+            d * d
+        })
+        .sum()
+}
+
+fn best_angle(dark: &[(f32, f32)], pixmap: &Pixmap, from: f32, to: f32, step: f32) -> f32 {
+    let steps = ((to - from) / step).round() as i32;
+    (0..=steps)
+        .map(|i| from + i as f32 * step)
+        .map(|angle| {
+            (
+                angle,
+                profile_score(dark, angle, pixmap.height, pixmap.width),
+            )
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.abs().total_cmp(&a.0.abs())))
+        .map_or(0.0, |(angle, _)| angle)
+}
+
+/// The page's skew in degrees, within ±15. A page without ink has none.
+pub fn estimate_skew(pixmap: &Pixmap) -> PdfResult<f32> {
+    check_supported(pixmap)?;
+    let mut dark = Vec::new();
+    for y in 0..pixmap.height {
+        for x in 0..pixmap.width {
+            if is_dark(pixmap, x, y) {
+                dark.push((x as f32, y as f32));
+            }
+        }
+    }
+    if dark.is_empty() {
+        return Ok(0.0);
+    }
+    let coarse = best_angle(&dark, pixmap, -MAX_ANGLE, MAX_ANGLE, COARSE_STEP);
+    let fine = best_angle(
+        &dark,
+        pixmap,
+        coarse - COARSE_STEP,
+        coarse + COARSE_STEP,
+        FINE_STEP,
+    );
+    Ok(fine)
+}
+
+/// `pixmap` turned by `-angle` degrees about its centre, keeping its size.
+/// Uncovered corners are white. 8-bit pixmaps are resampled bilinearly,
+/// bilevel ones take the nearest pixel.
+pub fn rotate_pixmap(pixmap: &Pixmap, angle: f32) -> PdfResult<Pixmap> {
+    check_supported(pixmap)?;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let cx = (pixmap.width as f32 - 1.0) / 2.0;
+    let cy = (pixmap.height as f32 - 1.0) / 2.0;
+    let n = pixmap.colorspace.components();
+    let white = if pixmap.colorspace == ColorSpace::DeviceCmyk {
+        0
+    } else {
+        255
+    };
+
+    let mut out = Pixmap {
+        samples: Vec::new(),
+        ..pixmap.clone()
+    };
+    out.samples = if pixmap.bits_per_component == 1 {
+        vec![0xFF; out.stride() * pixmap.height as usize]
+    } else {
+        vec![white; out.stride() * pixmap.height as usize]
+    };
+    let stride = out.stride();
+
+    for y in 0..pixmap.height {
+        for x in 0..pixmap.width {
+            let dx = x as f32 - cx;
+            let dy = y as f32 - cy;
+            let sx = cx + dx * cos - dy * sin;
+            let sy = cy + dx * sin + dy * cos;
+            let (w, h) = (pixmap.width as f32, pixmap.height as f32);
+            if sx < -0.5 || sy < -0.5 || sx >= w - 0.5 || sy >= h - 0.5 {
+                continue;
+            }
+
+            if pixmap.bits_per_component == 1 {
+                if is_dark(pixmap, sx.round() as u32, sy.round() as u32) {
+                    out.samples[y as usize * stride + x as usize / 8] &= !(0x80 >> (x % 8));
+                }
+                continue;
+            }
+
+            let (x0, y0) = (sx.floor(), sy.floor());
+            let (fx, fy) = (sx - x0, sy - y0);
+            let sample = |px: f32, py: f32, c: usize| -> f32 {
+                if px < 0.0 || py < 0.0 || px >= w || py >= h {
+                    f32::from(white)
+                } else {
+                    f32::from(pixmap.pixel(px as u32, py as u32)[c])
+                }
+            };
+            for c in 0..n {
+                let top = sample(x0, y0, c) * (1.0 - fx) + sample(x0 + 1.0, y0, c) * fx;
+                let bottom =
+                    sample(x0, y0 + 1.0, c) * (1.0 - fx) + sample(x0 + 1.0, y0 + 1.0, c) * fx;
+                out.samples[y as usize * stride + x as usize * n + c] =
+                    (top * (1.0 - fy) + bottom * fy).round() as u8;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Estimate the skew and straighten the page, returning the angle that was
+/// removed along with the corrected pixmap.
+pub fn deskew_pixmap(pixmap: &Pixmap) -> PdfResult<(f32, Pixmap)> {
+    let angle = estimate_skew(pixmap)?;
+    Ok((angle, rotate_pixmap(pixmap, angle)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bilevel 400x300 page of dashed "text lines" turned clockwise by
+    /// `angle` degrees.
+    fn skewed_page(angle: f32) -> Pixmap {
+        let (width, height) = (400u32, 300u32);
+        let stride = width.div_ceil(8) as usize;
+        let mut samples = vec![0xFF; stride * height as usize];
+        let slope = angle.to_radians().tan();
+        for line in 0..10 {
+            let baseline = 40.0 + 24.0 * line as f32;
+            for x in 40..360u32 {
+                // Words of 30 pixels with 10-pixel spaces.
+                if x % 40 >= 30 {
+                    continue;
+                }
+                let y0 = baseline + (x as f32 - 200.0) * slope;
+                for y in (y0 - 8.0).round() as i32..y0.round() as i32 {
+                    if (0..height as i32).contains(&y) {
+                        samples[y as usize * stride + x as usize / 8] &= !(0x80 >> (x % 8));
+                    }
+                }
+            }
+        }
+        Pixmap {
+            width,
+            height,
+            colorspace: ColorSpace::DeviceGray,
+            bits_per_component: 1,
+            samples,
+        }
+    }
+
+    #[test]
+    fn test_recovers_known_angle() {
+        for angle in [3.0, -2.2, 0.0] {
+            let estimate = estimate_skew(&skewed_page(angle)).unwrap();
+            assert!(
+                (estimate - angle).abs() <= 0.15,
+                "estimated {estimate} for {angle}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_deskewed_page_is_straight() {
+        let (angle, straight) = deskew_pixmap(&skewed_page(4.0)).unwrap();
+        assert!((angle - 4.0).abs() <= 0.15);
+        assert_eq!((straight.width, straight.height), (400, 300));
+        assert!(estimate_skew(&straight).unwrap().abs() <= 0.15);
+    }
+
+    #[test]
+    fn test_rotates_gray_pixmap() {
+        let page = skewed_page(0.0);
+        let gray = Pixmap {
+            bits_per_component: 8,
+            samples: (0..page.height)
+                .flat_map(|y| (0..page.width).map(move |x| (x, y)))
+                .map(|(x, y)| if is_dark(&page, x, y) { 0 } else { 255 })
+                .collect(),
+            ..page
+        };
+        let rotated = rotate_pixmap(&gray, -3.0).unwrap();
+        assert_eq!(rotated.samples.len(), gray.samples.len());
+        // Corners come from outside the source and stay white.
+        assert_eq!(rotated.pixel(0, 0), [255]);
+        // Turning by +3 degrees undoes a skew of -3, so it introduces one of +3.
+        assert!((estimate_skew(&rotated).unwrap() - 3.0).abs() <= 0.15);
+    }
+}
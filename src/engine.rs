@@ -4,15 +4,47 @@ use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use tokio::sync::mpsc;
 
+type RenderGenerations = Arc<RwLock<HashMap<crate::models::DocumentId, u64>>>;
+type ReflowLayouts =
+    Arc<RwLock<HashMap<crate::models::DocumentId, crate::pdf_engine::ReflowLayout>>>;
+
 #[derive(Debug, Clone)]
 pub struct EngineState {
     pub cmd_tx: mpsc::Sender<PdfCommand>,
+    /// Latest render generation per document, shared with the workers so
+    /// queued renders for an outdated zoom/rotation/filter can be skipped.
+    pub render_generations: RenderGenerations,
+}
+
+impl EngineState {
+    pub fn set_render_generation(&self, doc_id: crate::models::DocumentId, generation: u64) {
+        if let Ok(mut guard) = self.render_generations.write() {
+            guard.insert(doc_id, generation);
+        }
+    }
+}
+
+fn is_stale_render(
+    generations: &RenderGenerations,
+    doc_id: crate::models::DocumentId,
+    generation: Option<u64>,
+) -> bool {
+    let Some(generation) = generation else {
+        return false;
+    };
+    generations
+        .read()
+        .ok()
+        .and_then(|guard| guard.get(&doc_id).copied())
+        .is_some_and(|latest| latest > generation)
 }
 
-/// Re-open a document from its remembered path if it isn't currently loaded.
+/// Re-open a document from its remembered path if it isn't currently loaded,
+/// and lay it out again if another worker has changed its layout.
 fn reload_if_needed(
     store: &mut DocumentStore,
     paths: &Arc<RwLock<HashMap<crate::models::DocumentId, String>>>,
+    layouts: &ReflowLayouts,
     doc_id: crate::models::DocumentId,
 ) {
     if !store.has_document(doc_id) {
@@ -26,6 +58,18 @@ fn reload_if_needed(
             }
         }
     }
+    let wanted = layouts
+        .read()
+        .ok()
+        .and_then(|guard| guard.get(&doc_id).copied());
+    if let Some(layout) = wanted
+        && store
+            .reflow_layout(doc_id)
+            .is_some_and(|current| current != layout)
+        && let Err(e) = store.layout_document(doc_id, layout)
+    {
+        tracing::error!("Failed to lay out document {doc_id:?} again: {e:?}");
+    }
 }
 
 #[must_use]
@@ -36,6 +80,8 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
 
     // Shared paths mapping between all concurrent threads
     let shared_paths = Arc::new(RwLock::new(HashMap::new()));
+    let render_generations: RenderGenerations = Arc::new(RwLock::new(HashMap::new()));
+    let shared_layouts: ReflowLayouts = Arc::new(RwLock::new(HashMap::new()));
 
     // MPMC channel for distributing tasks across the thread pool
     let (worker_tx, worker_rx) = crossbeam_channel::bounded::<PdfCommand>(256);
@@ -60,6 +106,8 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
         let rx = worker_rx.clone();
         let cache = render_cache.clone();
         let paths = shared_paths.clone();
+        let layouts = shared_layouts.clone();
+        let generations = render_generations.clone();
 
         std::thread::spawn(move || {
             let mut store = DocumentStore::new(cache);
@@ -97,14 +145,26 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
                             if let Ok(mut guard) = paths.write() {
                                 guard.insert(doc_id, path);
                             }
+                            if let Ok(mut guard) = layouts.write() {
+                                guard.remove(&doc_id);
+                            }
                         } else {
                             tracing::error!("Engine worker: open failed: {:?}", res);
                         }
                         let _ = tx.send(res);
                     }
-                    PdfCommand::Render(doc_id, page_num, options, tx) => {
+                    PdfCommand::Render(doc_id, page_num, options, generation, tx) => {
+                        if is_stale_render(&generations, doc_id, generation) {
+                            tracing::debug!(
+                                "Engine worker: skipping stale render of page {} for {:?}",
+                                page_num,
+                                doc_id
+                            );
+                            let _ = tx.send(Err(crate::models::PdfError::Cancelled));
+                            continue;
+                        }
                         tracing::debug!("Engine worker: render page {} for {:?}", page_num, doc_id);
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
 
                         let mut store_ref = std::panic::AssertUnwindSafe(&mut store);
                         let result = std::panic::catch_unwind(move || {
@@ -140,7 +200,7 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
                         let _ = tx.send(res);
                     }
                     PdfCommand::RenderThumbnail(doc_id, page_num, scale, rotation, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let options = crate::pdf_engine::RenderOptions {
                             scale,
                             rotation,
@@ -156,39 +216,45 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
                         if let Ok(mut guard) = paths.write() {
                             guard.remove(&doc_id);
                         }
+                        if let Ok(mut guard) = generations.write() {
+                            guard.remove(&doc_id);
+                        }
+                        if let Ok(mut guard) = layouts.write() {
+                            guard.remove(&doc_id);
+                        }
                     }
                     PdfCommand::ExtractText(doc_id, page_num, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.extract_text(doc_id, page_num);
                         let _ = tx.send(res);
                     }
                     PdfCommand::Search(doc_id, query, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.search(doc_id, &query);
                         let _ = tx.send(res);
                     }
                     PdfCommand::GetTextItems(doc_id, page_num, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.extract_text_items(doc_id, page_num);
                         let _ = tx.send(res);
                     }
                     PdfCommand::LoadDocumentMeta(doc_id, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.load_document_meta(doc_id);
                         let _ = tx.send(res);
                     }
                     PdfCommand::SaveAnnotations(doc_id, annotations, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.save_annotations(doc_id, &annotations, None);
                         let _ = tx.send(res);
                     }
                     PdfCommand::ExportImage(doc_id, page_num, scale, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.export_page_as_image(doc_id, page_num, scale);
                         let _ = tx.send(res);
                     }
                     PdfCommand::ExportImages(doc_id, pages, scale, out_dir, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let out_path = std::path::Path::new(&out_dir);
                         if !out_path.is_dir() {
                             let _ = tx.send(Err(crate::models::PdfError::IoError(
@@ -214,7 +280,7 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
                         let _ = tx.send(Ok(output_paths));
                     }
                     PdfCommand::ExportPdf(doc_id, path, annotations, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.save_annotations(doc_id, &annotations, Some(path));
                         let _ = tx.send(res);
                     }
@@ -222,8 +288,15 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
                         let res = store.merge_documents(paths_list, out);
                         let _ = tx.send(res);
                     }
-                    PdfCommand::Split(path, pages, out, tx) => {
-                        let res = store.split_pdf(&path, pages, out);
+                    PdfCommand::Split(path, pages, out, progress, tx) => {
+                        let res = store.split_pdf(&path, pages, out, |done, total| {
+                            let _ = progress.send((done, total));
+                        });
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::ExtractPages(doc_id, pages, out, tx) => {
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
+                        let res = store.extract_pages(doc_id, &pages, &out);
                         let _ = tx.send(res);
                     }
                     PdfCommand::GetFormFields(path, tx) => {
@@ -234,25 +307,100 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
                         let res = store.fill_form(&path, fields, out);
                         let _ = tx.send(res);
                     }
-                    PdfCommand::PrintPdf(path, printer_name, tx) => {
-                        let res = crate::pdf_engine::DocumentStore::print_document(
-                            &path,
-                            printer_name.as_deref(),
-                        );
+                    PdfCommand::PrintPdf(path, printer_name, pages, tx) => {
+                        let res = match pages {
+                            // Spool the selected pages through a temporary PDF,
+                            // unique per job; the file is removed when `tmp` drops.
+                            Some(pages) => tempfile::Builder::new()
+                                .prefix("pdfbull_print_")
+                                .suffix(".pdf")
+                                .tempfile()
+                                .map_err(|e| crate::models::PdfError::IoError(e.to_string()))
+                                .and_then(|tmp| {
+                                    let tmp_path = tmp.path().to_string_lossy().to_string();
+                                    store.reorder_pages(&path, &pages, &tmp_path)?;
+                                    crate::pdf_engine::DocumentStore::print_document(
+                                        &tmp_path,
+                                        printer_name.as_deref(),
+                                    )
+                                }),
+                            None => crate::pdf_engine::DocumentStore::print_document(
+                                &path,
+                                printer_name.as_deref(),
+                            ),
+                        };
                         let _ = tx.send(res);
                     }
                     PdfCommand::ListPrinters(tx) => {
                         let _ = tx.send(crate::pdf_engine::DocumentStore::list_printers());
                     }
-                    PdfCommand::AddWatermark(input, text, output, tx) => {
-                        let res =
-                            crate::pdf_engine::DocumentStore::add_watermark(&input, &text, &output);
+                    PdfCommand::AddWatermark(input, text, options, output, tx) => {
+                        let res = crate::pdf_engine::DocumentStore::add_watermark(
+                            &input, &text, options, &output,
+                        );
                         let _ = tx.send(res);
                     }
                     PdfCommand::Optimize(input, output, tx) => {
                         let res = store.optimize_pdf(&input, &output);
                         let _ = tx.send(res);
                     }
+                    PdfCommand::PrependToc(input, entries, output, tx) => {
+                        let res = crate::pdf_engine::DocumentStore::prepend_toc(
+                            &input, &entries, &output,
+                        );
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::FillFieldsFromMap(input, output, values, tx) => {
+                        let res = crate::pdf_engine::DocumentStore::fill_form_values(
+                            &input, values, output,
+                        );
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::FlattenForms(input, output, tx) => {
+                        let res = crate::pdf_engine::DocumentStore::flatten_forms(&input, &output);
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::MakeBooklet(input, output, tx) => {
+                        let res = crate::impose::make_booklet(&input, &output);
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::PosterPdf(input, output, (tile_w, tile_h), overlap, tx) => {
+                        let res =
+                            crate::impose::poster_pdf(&input, &output, tile_w, tile_h, overlap);
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::AddChart(input, spec, output, tx) => {
+                        let res =
+                            crate::pdf_engine::DocumentStore::add_chart(&input, &spec, &output);
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::ExportJson(input, config, output, tx) => {
+                        let _ = tx.send(crate::pdf_json::export_json(&input, config, &output));
+                    }
+                    PdfCommand::HtmlToPdf(html, base_dir, page_size, output, tx) => {
+                        let res = crate::html::html_to_pdf(
+                            &html,
+                            base_dir.as_deref(),
+                            page_size,
+                            &output,
+                        );
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::AddBarcode(input, spec, output, tx) => {
+                        let res = crate::barcode::add_barcode(&input, &spec, &output);
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::RepairPdf(input, output, tx) => {
+                        let res = crate::pdf_engine::DocumentStore::repair_pdf(&input, &output);
+                        let _ = tx.send(res);
+                    }
+                    PdfCommand::ValidatePdf(input, tx) => {
+                        let _ = tx.send(crate::pdf_engine::DocumentStore::validate_pdf(&input));
+                    }
+                    PdfCommand::NupPdf(input, output, layout, tx) => {
+                        let res = crate::impose::nup_pdf(&input, &output, layout);
+                        let _ = tx.send(res);
+                    }
                     PdfCommand::ReorderPages(input, page_order, output, tx) => {
                         let res = store.reorder_pages(&input, &page_order, &output);
                         let _ = tx.send(res);
@@ -265,17 +413,27 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
                         let res = store.load_annotations(&path);
                         let _ = tx.send(res);
                     }
+                    PdfCommand::LayoutDocument(doc_id, layout, tx) => {
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
+                        let res = store.layout_document(doc_id, layout);
+                        if res.is_ok() {
+                            if let Ok(mut guard) = layouts.write() {
+                                guard.insert(doc_id, layout);
+                            }
+                        }
+                        let _ = tx.send(res);
+                    }
                     PdfCommand::ToggleLayer(doc_id, object_id, visible) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         store.toggle_layer(doc_id, object_id, visible);
                     }
                     PdfCommand::GetAttachmentBytes(doc_id, object_id, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.get_attachment_bytes(doc_id, object_id);
                         let _ = tx.send(res);
                     }
                     PdfCommand::DetectTables(doc_id, page_num, tx) => {
-                        reload_if_needed(&mut store, &paths, doc_id);
+                        reload_if_needed(&mut store, &paths, &layouts, doc_id);
                         let res = store.detect_tables_on_page(doc_id, page_num);
                         let _ = tx.send(res);
                     }
@@ -284,5 +442,23 @@ pub fn spawn_engine_thread(cache_size: u64, max_memory_mb: u64) -> EngineState {
         });
     }
 
-    EngineState { cmd_tx }
+    EngineState {
+        cmd_tx,
+        render_generations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DocumentId;
+
+    #[test]
+    fn test_one_off_renders_are_never_stale() {
+        let generations: RenderGenerations = Arc::new(RwLock::new(HashMap::new()));
+        generations.write().unwrap().insert(DocumentId(1), 3);
+        assert!(is_stale_render(&generations, DocumentId(1), Some(2)));
+        assert!(!is_stale_render(&generations, DocumentId(1), Some(3)));
+        assert!(!is_stale_render(&generations, DocumentId(1), None));
+    }
 }
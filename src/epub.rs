@@ -0,0 +1,171 @@
+//! EPUB books.
+//!
+//! An EPUB is a zip holding XHTML chapters, listed in reading order by the
+//! spine of its package (OPF) file. The chapters have no fixed pages, so a
+//! book is laid out through [`crate::html`] at whatever page size and font
+//! size the reader picks, and laid out again when either changes. Images
+//! inside the archive aren't loaded yet; their alt text is shown instead.
+
+use crate::archive::{ZipArchive, resolve_path};
+use crate::flow::{DocumentBuilder, Flowable, PageSize};
+use crate::html::{BASE_FONT_SIZE, html_to_flowables, start_tags};
+use crate::models::{PdfError, PdfResult};
+use lopdf::Document;
+use std::collections::HashMap;
+
+/// A5, which suits reading better than the office sizes.
+pub const DEFAULT_PAGE: PageSize = PageSize {
+    width: 420.0,
+    height: 595.0,
+};
+pub const DEFAULT_EM: f32 = BASE_FONT_SIZE;
+
+#[derive(Debug, Clone)]
+pub struct EpubBook {
+    /// XHTML of each spine item, in reading order.
+    chapters: Vec<String>,
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// The tag name without any namespace prefix, e.g. `opf:item` -> `item`.
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn read_text(archive: &ZipArchive, name: &str) -> PdfResult<String> {
+    Ok(String::from_utf8_lossy(&archive.read(name)?).into_owned())
+}
+
+impl EpubBook {
+    pub fn open(data: Vec<u8>) -> PdfResult<Self> {
+        let archive = ZipArchive::new(data)?;
+        let container = read_text(&archive, "META-INF/container.xml")?;
+        let opf_path = start_tags(&container)
+            .into_iter()
+            .find(|(name, _)| local_name(name) == "rootfile")
+            .and_then(|(_, attrs)| attr(&attrs, "full-path").map(str::to_string))
+            .ok_or_else(|| PdfError::from("EPUB container names no package file"))?;
+        let base = opf_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let package = start_tags(&read_text(&archive, &opf_path)?);
+
+        let manifest: HashMap<&str, (&str, &str)> = package
+            .iter()
+            .filter(|(name, _)| local_name(name) == "item")
+            .filter_map(|(_, attrs)| {
+                Some((
+                    attr(attrs, "id")?,
+                    (
+                        attr(attrs, "href")?,
+                        attr(attrs, "media-type").unwrap_or(""),
+                    ),
+                ))
+            })
+            .collect();
+        let mut chapters = Vec::new();
+        for (_, attrs) in package
+            .iter()
+            .filter(|(name, _)| local_name(name) == "itemref")
+        {
+            if attr(attrs, "linear") == Some("no") {
+                continue;
+            }
+            let Some(&(href, media_type)) = attr(attrs, "idref").and_then(|id| manifest.get(id))
+            else {
+                continue;
+            };
+            if !matches!(media_type, "application/xhtml+xml" | "text/html") {
+                continue;
+            }
+            chapters.push(read_text(&archive, &resolve_path(base, href))?);
+        }
+        if chapters.is_empty() {
+            return Err(PdfError::from("EPUB spine has no readable chapters"));
+        }
+        Ok(Self { chapters })
+    }
+
+    pub fn chapter_count(&self) -> usize {
+        self.chapters.len()
+    }
+
+    /// Lay the book out on pages of `page` size with body text of `em`
+    /// points. Each chapter starts a new page.
+    pub fn layout(&self, page: PageSize, em: f32) -> PdfResult<Document> {
+        if !em.is_finite() || em <= 0.0 {
+            return Err(PdfError::from(format!("Invalid font size {em}")));
+        }
+        let mut builder = DocumentBuilder::new(page).margin(em * 3.0);
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            if i > 0 {
+                builder.push(Flowable::PageBreak);
+            }
+            for mut flowable in html_to_flowables(chapter, None) {
+                flowable.scale(em / BASE_FONT_SIZE);
+                builder.push(flowable);
+            }
+        }
+        builder.build()
+    }
+}
+
+/// A small book for tests: a container, a package and two chapters, the
+/// second of which is listed first in the manifest.
+#[cfg(test)]
+pub(crate) fn sample_epub() -> Vec<u8> {
+    let paragraph = "<p>It was a bright cold day in April, and the clocks were \
+        striking thirteen. The hallway smelt of boiled cabbage and old rag mats.</p>"
+        .repeat(20);
+    let chapter = |title: &str| {
+        format!(
+            "<?xml version=\"1.0\"?><html xmlns=\"http://www.w3.org/1999/xhtml\">\
+             <head><title>{title}</title></head><body><h1>{title}</h1>{paragraph}</body></html>"
+        )
+    };
+    let container = r#"<?xml version="1.0"?>
+        <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+          <rootfiles><rootfile full-path="OEBPS/content.opf"
+            media-type="application/oebps-package+xml"/></rootfiles>
+        </container>"#;
+    let package = r#"<?xml version="1.0"?>
+        <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+          <metadata><dc:title>Sample</dc:title></metadata>
+          <manifest>
+            <item id="two" href="text/chapter%202.xhtml" media-type="application/xhtml+xml"/>
+            <item id="css" href="style.css" media-type="text/css"/>
+            <item id="one" href="text/chapter1.xhtml" media-type="application/xhtml+xml"/>
+          </manifest>
+          <spine><itemref idref="one"/><itemref idref="two"/></spine>
+        </package>"#;
+    crate::archive::write_stored_zip(&[
+        ("mimetype", b"application/epub+zip"),
+        ("META-INF/container.xml", container.as_bytes()),
+        ("OEBPS/content.opf", package.as_bytes()),
+        ("OEBPS/style.css", b"p { margin: 0 }"),
+        ("OEBPS/text/chapter1.xhtml", chapter("One").as_bytes()),
+        ("OEBPS/text/chapter 2.xhtml", chapter("Two").as_bytes()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spine_order_and_relayout() {
+        let book = EpubBook::open(sample_epub()).unwrap();
+        assert_eq!(book.chapter_count(), 2);
+        assert!(book.chapters[0].contains("<h1>One</h1>"));
+        assert!(book.chapters[1].contains("<h1>Two</h1>"));
+
+        let pages = |em| book.layout(DEFAULT_PAGE, em).unwrap().get_pages().len();
+        let (small, large) = (pages(DEFAULT_EM), pages(DEFAULT_EM * 2.0));
+        assert!(small >= 2);
+        assert!(large > small, "{large} pages at 2em vs {small} at 1em");
+    }
+}
@@ -0,0 +1,642 @@
+//! PDF stream filters (ISO 32000-1 §7.4) and re-encoding between them.
+//!
+//! A `/Filter` array lists filters in decoding order, so data filtered with
+//! `[/ASCII85Decode /FlateDecode]` is undone by ASCII85 first, then Flate.
+//! Every function here takes filter lists in that same order.
+
+use crate::models::{PdfError, PdfResult};
+use lopdf::Dictionary;
+use std::collections::HashMap;
+use std::io::Write;
+use zpdf::{PdfDict, PdfName, PdfObject};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    AsciiHex,
+    Ascii85,
+    RunLength,
+    Flate,
+    Lzw,
+}
+
+impl FilterType {
+    /// Parse a `/Filter` name, including the standard inline-image
+    /// abbreviations.
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"ASCIIHexDecode" | b"AHx" => Some(Self::AsciiHex),
+            b"ASCII85Decode" | b"A85" => Some(Self::Ascii85),
+            b"RunLengthDecode" | b"RL" => Some(Self::RunLength),
+            b"FlateDecode" | b"Fl" => Some(Self::Flate),
+            b"LZWDecode" | b"LZW" => Some(Self::Lzw),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static [u8] {
+        match self {
+            Self::AsciiHex => b"ASCIIHexDecode",
+            Self::Ascii85 => b"ASCII85Decode",
+            Self::RunLength => b"RunLengthDecode",
+            Self::Flate => b"FlateDecode",
+            Self::Lzw => b"LZWDecode",
+        }
+    }
+}
+
+/// Undo one filter.
+pub fn decode(data: &[u8], filter: FilterType) -> PdfResult<Vec<u8>> {
+    decode_chain(data, &[filter], None)
+}
+
+/// Undo `filters`, in `/Filter` order, with zpdf's stream decoder.
+/// `parms` becomes the `/DecodeParms` of the first filter.
+fn decode_chain(data: &[u8], filters: &[FilterType], parms: Option<PdfDict>) -> PdfResult<Vec<u8>> {
+    if filters.is_empty() {
+        return Ok(data.to_vec());
+    }
+    let names = filters
+        .iter()
+        .map(|filter| PdfObject::Name(PdfName::new(String::from_utf8_lossy(filter.name()))))
+        .collect();
+    let mut dict = PdfDict::new();
+    dict.insert(PdfName::new("Filter"), PdfObject::Array(names));
+    if let Some(parms) = parms {
+        dict.insert(PdfName::new("DecodeParms"), PdfObject::Dict(parms));
+    }
+    zpdf_parser::filters::decode_stream(data, &dict)
+        .map_err(|e| PdfError::from(format!("Stream data is corrupt: {e}")))
+}
+
+/// Apply one filter.
+pub fn encode(data: &[u8], filter: FilterType) -> PdfResult<Vec<u8>> {
+    match filter {
+        FilterType::AsciiHex => Ok(ascii_hex_encode(data)),
+        FilterType::Ascii85 => Ok(ascii85_encode(data)),
+        FilterType::RunLength => Ok(run_length_encode(data)),
+        FilterType::Flate => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(data)
+                .and_then(|()| encoder.finish())
+                .map_err(|e| PdfError::from(e.to_string()))
+        }
+        FilterType::Lzw => Ok(lzw_encode(data, true)),
+    }
+}
+
+/// Convert stream data filtered with `from` into data filtered with `to`,
+/// e.g. ASCII85 + Flate into plain Flate. Both lists are in `/Filter`
+/// order.
+pub fn recode_stream(data: &[u8], from: &[FilterType], to: &[FilterType]) -> PdfResult<Vec<u8>> {
+    let mut data = decode_chain(data, from, None)?;
+    for &filter in to.iter().rev() {
+        data = encode(&data, filter)?;
+    }
+    Ok(data)
+}
+
+/// Undo one filter and then, for Flate and LZW, the predictor described by
+/// `params`.
+pub fn decode_with_params(
+    data: &[u8],
+    filter: FilterType,
+    params: &PredictorParams,
+) -> PdfResult<Vec<u8>> {
+    match filter {
+        FilterType::Flate | FilterType::Lzw => {
+            params.validate()?;
+            decode_chain(data, &[filter], Some(params.to_pdf_dict()))
+        }
+        _ => decode(data, filter),
+    }
+}
+
+/// Apply the predictor described by `params` (Flate and LZW only), then
+/// the filter itself.
+pub fn encode_with_params(
+    data: &[u8],
+    filter: FilterType,
+    params: &PredictorParams,
+) -> PdfResult<Vec<u8>> {
+    match filter {
+        FilterType::Flate | FilterType::Lzw => encode(&apply_predictor(data, params)?, filter),
+        _ => encode(data, filter),
+    }
+}
+
+/// The `/DecodeParms` entries that describe a Flate or LZW predictor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PredictorParams {
+    /// 1 for none, 2 for TIFF, 10–14 for PNG None/Sub/Up/Average/Paeth on
+    /// every row and 15 for a per-row choice. Decoding any PNG predictor
+    /// follows the tag byte in front of each row.
+    pub predictor: u8,
+    pub colors: u8,
+    pub bits_per_component: u8,
+    pub columns: u32,
+}
+
+impl Default for PredictorParams {
+    fn default() -> Self {
+        Self {
+            predictor: 1,
+            colors: 1,
+            bits_per_component: 8,
+            columns: 1,
+        }
+    }
+}
+
+impl PredictorParams {
+    /// Read the predictor entries of a `/DecodeParms` dictionary, using the
+    /// spec defaults for missing ones.
+    pub fn from_dictionary(dict: &Dictionary) -> Self {
+        let defaults = Self::default();
+        let get = |key: &[u8]| dict.get(key).and_then(lopdf::Object::as_i64).ok();
+        let byte = |key: &[u8], default| {
+            get(key)
+                .and_then(|v| u8::try_from(v).ok())
+                .unwrap_or(default)
+        };
+        Self {
+            predictor: byte(b"Predictor", defaults.predictor),
+            colors: byte(b"Colors", defaults.colors),
+            bits_per_component: byte(b"BitsPerComponent", defaults.bits_per_component),
+            columns: get(b"Columns")
+                .and_then(|v| u32::try_from(v).ok())
+                .unwrap_or(defaults.columns),
+        }
+    }
+
+    /// The `/DecodeParms` to store with data encoded using these
+    /// parameters.
+    pub fn to_dictionary(&self) -> Dictionary {
+        let mut dict = Dictionary::new();
+        dict.set("Predictor", i64::from(self.predictor));
+        dict.set("Colors", i64::from(self.colors));
+        dict.set("BitsPerComponent", i64::from(self.bits_per_component));
+        dict.set("Columns", i64::from(self.columns));
+        dict
+    }
+
+    /// The same entries as a zpdf dictionary, for decoding.
+    fn to_pdf_dict(self) -> PdfDict {
+        let mut dict = PdfDict::new();
+        for (key, value) in [
+            ("Predictor", i64::from(self.predictor)),
+            ("Colors", i64::from(self.colors)),
+            ("BitsPerComponent", i64::from(self.bits_per_component)),
+            ("Columns", i64::from(self.columns)),
+        ] {
+            dict.insert(PdfName::new(key), PdfObject::Integer(value));
+        }
+        dict
+    }
+
+    fn validate(self) -> PdfResult<()> {
+        if !matches!(self.predictor, 1 | 2 | 10..=15) {
+            return Err(PdfError::from(format!(
+                "Unsupported predictor {}",
+                self.predictor
+            )));
+        }
+        if self.colors == 0
+            || self.columns == 0
+            || !matches!(self.bits_per_component, 1 | 2 | 4 | 8 | 16)
+        {
+            return Err(PdfError::from(format!(
+                "Invalid predictor parameters {self:?}"
+            )));
+        }
+        Ok(())
+    }
+
+    fn row_len(self) -> usize {
+        (usize::from(self.colors) * usize::from(self.bits_per_component) * self.columns as usize)
+            .div_ceil(8)
+    }
+
+    /// Bytes per pixel, at least one: how far back PNG filters look.
+    fn pixel_len(self) -> usize {
+        (usize::from(self.colors) * usize::from(self.bits_per_component)).div_ceil(8)
+    }
+}
+
+fn ascii_hex_encode(data: &[u8]) -> Vec<u8> {
+    let mut out: Vec<u8> = data
+        .iter()
+        .flat_map(|b| format!("{b:02X}").into_bytes())
+        .collect();
+    out.push(b'>');
+    out
+}
+
+fn ascii85_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() * 5 / 4 + 2);
+    for chunk in data.chunks(4) {
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        if chunk.len() == 4 && value == 0 {
+            out.push(b'z');
+            continue;
+        }
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = (value % 85) as u8 + b'!';
+            value /= 85;
+        }
+        out.extend_from_slice(&digits[..=chunk.len()]);
+    }
+    out.extend_from_slice(b"~>");
+    out
+}
+
+fn run_length_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 128 + 1);
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(128)
+            .take_while(|&&b| b == data[i])
+            .count();
+        if run > 1 {
+            out.push((257 - run) as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+        // Literal bytes up to the next run of at least two.
+        let start = i;
+        while i < data.len() && i - start < 128 && data.get(i + 1) != Some(&data[i]) {
+            i += 1;
+        }
+        if i == start {
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+    out.push(128);
+    out
+}
+
+const LZW_CLEAR: u16 = 256;
+const LZW_EOD: u16 = 257;
+
+/// Packs variable-width codes MSB first, as LZW and CCITT data expect.
+#[derive(Default)]
+pub(crate) struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    pub(crate) fn write(&mut self, code: u16, width: u32) {
+        self.buffer = self.buffer << width | u32::from(code);
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.out.push((self.buffer >> self.bits) as u8);
+        }
+        self.buffer &= (1 << self.bits) - 1;
+    }
+
+    /// Pad the last byte with zero bits.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push((self.buffer << (8 - self.bits)) as u8);
+        }
+        self.out
+    }
+}
+
+/// Encode data as PDF LZW, starting with a clear code and ending with EOD.
+/// `early_change` must match the `/EarlyChange` value written alongside it.
+///
+/// The encoder defines each table entry one code before the decoder does,
+/// so code widths are chosen from the decoder's table size, which is one
+/// smaller. When the table fills up a clear code resets both sides.
+pub fn lzw_encode(data: &[u8], early_change: bool) -> Vec<u8> {
+    let early = u32::from(early_change);
+    let width_for = |decoder_len: u16| {
+        (9..12)
+            .find(|&w| u32::from(decoder_len) + early < 1 << w)
+            .unwrap_or(12)
+    };
+    let mut writer = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = 258;
+    let mut current: Option<u16> = None;
+    writer.write(LZW_CLEAR, 9);
+
+    for &byte in data {
+        let Some(prefix) = current else {
+            current = Some(u16::from(byte));
+            continue;
+        };
+        if let Some(&code) = table.get(&(prefix, byte)) {
+            current = Some(code);
+            continue;
+        }
+        writer.write(prefix, width_for(next_code - 1));
+        table.insert((prefix, byte), next_code);
+        next_code += 1;
+        if next_code == 4096 {
+            writer.write(LZW_CLEAR, width_for(next_code - 1));
+            table.clear();
+            next_code = 258;
+        }
+        current = Some(u16::from(byte));
+    }
+    if let Some(prefix) = current {
+        writer.write(prefix, width_for(next_code - 1));
+    }
+    // The decoder has added its entry for the last code by now.
+    writer.write(LZW_EOD, width_for(next_code));
+    writer.finish()
+}
+
+/// Apply a predictor to raw sample rows before compression.
+pub fn apply_predictor(data: &[u8], params: &PredictorParams) -> PdfResult<Vec<u8>> {
+    params.validate()?;
+    match params.predictor {
+        1 => Ok(data.to_vec()),
+        2 => Ok(tiff_predictor(data, *params)),
+        _ => {
+            let (row_len, bpp) = (params.row_len(), params.pixel_len());
+            let mut out = Vec::with_capacity(data.len() + data.len() / row_len + 1);
+            let mut prior = vec![0; row_len];
+            for row in data.chunks(row_len) {
+                let filter_row = |kind: u8| -> Vec<u8> {
+                    (0..row.len())
+                        .map(|i| row[i].wrapping_sub(png_predict(kind, row, &prior, i, bpp)))
+                        .collect()
+                };
+                let (kind, filtered) = if params.predictor == 15 {
+                    // The usual heuristic: the smallest sum of signed
+                    // differences compresses best.
+                    (0..=4)
+                        .map(|kind| (kind, filter_row(kind)))
+                        .min_by_key(|(_, row)| {
+                            row.iter()
+                                .map(|&b| u32::from(b.min(b.wrapping_neg())))
+                                .sum::<u32>()
+                        })
+                        .expect("five candidates")
+                } else {
+                    let kind = params.predictor - 10;
+                    (kind, filter_row(kind))
+                };
+                out.push(kind);
+                out.extend_from_slice(&filtered);
+                prior[..row.len()].copy_from_slice(row);
+            }
+            Ok(out)
+        }
+    }
+}
+
+/// The value PNG filter `kind` predicts for byte `i` of `row`, from the
+/// raw bytes to its left and in the `prior` row.
+fn png_predict(kind: u8, row: &[u8], prior: &[u8], i: usize, bpp: usize) -> u8 {
+    let left = i.checked_sub(bpp).map_or(0, |j| row[j]);
+    let up = prior[i];
+    let up_left = i.checked_sub(bpp).map_or(0, |j| prior[j]);
+    match kind {
+        1 => left,
+        2 => up,
+        3 => u8::try_from(u16::midpoint(u16::from(left), u16::from(up))).unwrap_or(u8::MAX),
+        4 => paeth(left, up, up_left),
+        _ => 0,
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = (
+        (p - i16::from(a)).abs(),
+        (p - i16::from(b)).abs(),
+        (p - i16::from(c)).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// TIFF predictor 2: each sample is stored as the difference from the same
+/// colour component of the pixel to its left, modulo 2^bits.
+fn tiff_predictor(data: &[u8], params: PredictorParams) -> Vec<u8> {
+    let bpc = usize::from(params.bits_per_component);
+    let colors = usize::from(params.colors);
+    let mask = (1u32 << bpc) - 1;
+    let mut out = data.to_vec();
+    for row in out.chunks_mut(params.row_len()) {
+        let samples = (row.len() * 8 / bpc).min(colors * params.columns as usize);
+        // Right to left, so every difference is taken against the original
+        // left neighbour.
+        for i in (colors..samples).rev() {
+            let left = read_sample(row, i - colors, bpc);
+            let value = read_sample(row, i, bpc);
+            write_sample(row, i, bpc, value.wrapping_sub(left) & mask);
+        }
+    }
+    out
+}
+
+fn read_sample(row: &[u8], index: usize, bpc: usize) -> u32 {
+    (index * bpc..(index + 1) * bpc).fold(0, |acc, pos| {
+        acc << 1 | u32::from(row[pos / 8] >> (7 - pos % 8) & 1)
+    })
+}
+
+fn write_sample(row: &mut [u8], index: usize, bpc: usize, value: u32) {
+    for bit in 0..bpc {
+        let pos = index * bpc + bit;
+        let mask = 0x80 >> (pos % 8);
+        if value >> (bpc - 1 - bit) & 1 == 1 {
+            row[pos / 8] |= mask;
+        } else {
+            row[pos / 8] &= !mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &[u8] = b"aaaaaaaaaabcdefffffffffffffff\x00\x00\x00\x00xyz\xff\xfe";
+
+    fn lzw_decode(data: &[u8], early_change: bool) -> Vec<u8> {
+        let mut parms = PdfDict::new();
+        parms.insert(
+            PdfName::new("EarlyChange"),
+            PdfObject::Integer(i64::from(early_change)),
+        );
+        decode_chain(data, &[FilterType::Lzw], Some(parms)).unwrap()
+    }
+
+    #[test]
+    fn test_each_filter_round_trips() {
+        for filter in [
+            FilterType::AsciiHex,
+            FilterType::Ascii85,
+            FilterType::RunLength,
+            FilterType::Flate,
+            FilterType::Lzw,
+        ] {
+            let inputs: [&[u8]; 4] = [SAMPLE, b"", b"a", &[7; 300]];
+            for data in inputs {
+                let encoded = encode(data, filter).unwrap();
+                assert_eq!(decode(&encoded, filter).unwrap(), data, "{filter:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_recode_through_hex_and_run_length() {
+        let filters = [FilterType::AsciiHex, FilterType::RunLength];
+        let encoded = recode_stream(SAMPLE, &[], &filters).unwrap();
+        assert!(encoded.iter().all(|b| b.is_ascii_hexdigit() || *b == b'>'));
+        assert_eq!(recode_stream(&encoded, &filters, &[]).unwrap(), SAMPLE);
+    }
+
+    #[test]
+    fn test_recode_ascii85_flate_to_flate() {
+        let filters = [FilterType::Ascii85, FilterType::Flate];
+        let armoured = recode_stream(SAMPLE, &[], &filters).unwrap();
+        let flate = recode_stream(&armoured, &filters, &[FilterType::Flate]).unwrap();
+        assert_eq!(decode(&flate, FilterType::Flate).unwrap(), SAMPLE);
+    }
+
+    #[test]
+    fn test_known_encodings() {
+        assert_eq!(ascii85_encode(b"Man "), b"9jqo^~>");
+        assert_eq!(decode(b"9jqo^~>", FilterType::Ascii85).unwrap(), b"Man ");
+        assert_eq!(ascii85_encode(&[0; 8]), b"zz~>");
+        assert_eq!(
+            decode(b"48 65 6C6C 6F7>", FilterType::AsciiHex).unwrap(),
+            b"Hellop"
+        );
+        assert_eq!(
+            decode(
+                &[2, b'a', b'b', b'c', 254, b'x', 128],
+                FilterType::RunLength
+            )
+            .unwrap(),
+            b"abcxxx"
+        );
+    }
+
+    fn gradient(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i * 37 % 251) as u8 ^ (i / 7) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_each_png_predictor_round_trips() {
+        // 7 RGB pixels per row, 6 rows.
+        let data = gradient(7 * 3 * 6);
+        for predictor in 10..=15 {
+            let params = PredictorParams {
+                predictor,
+                colors: 3,
+                bits_per_component: 8,
+                columns: 7,
+            };
+            let predicted = apply_predictor(&data, &params).unwrap();
+            if predictor < 15 {
+                assert!(predicted.chunks(22).all(|row| row[0] == predictor - 10));
+            }
+            let flate = encode_with_params(&data, FilterType::Flate, &params).unwrap();
+            assert_eq!(
+                decode_with_params(&flate, FilterType::Flate, &params).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_png_up_predictor_output() {
+        let params = PredictorParams {
+            predictor: 12,
+            columns: 3,
+            ..PredictorParams::default()
+        };
+        let predicted = apply_predictor(&[1, 2, 3, 4, 6, 8], &params).unwrap();
+        assert_eq!(predicted, [2, 1, 2, 3, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_tiff_predictor_output() {
+        let params = PredictorParams {
+            predictor: 2,
+            colors: 3,
+            columns: 5,
+            ..PredictorParams::default()
+        };
+        let data = gradient(params.row_len() * 4);
+        let flate = encode_with_params(&data, FilterType::Flate, &params).unwrap();
+        assert_eq!(
+            decode_with_params(&flate, FilterType::Flate, &params).unwrap(),
+            data
+        );
+        let params = PredictorParams {
+            predictor: 2,
+            bits_per_component: 4,
+            columns: 4,
+            ..PredictorParams::default()
+        };
+        assert_eq!(
+            apply_predictor(&[0x12, 0x34], &params).unwrap(),
+            [0x11, 0x11]
+        );
+        let params = PredictorParams {
+            predictor: 2,
+            colors: 2,
+            columns: 3,
+            ..PredictorParams::default()
+        };
+        let predicted = apply_predictor(&[10, 20, 15, 10, 15, 30], &params).unwrap();
+        assert_eq!(predicted, [10, 20, 5, 246, 0, 20]);
+    }
+
+    #[test]
+    fn test_lzw_round_trips_with_both_early_change_settings() {
+        // Pseudo-random bytes from a small alphabet fill the code table
+        // several times over, exercising every width and the reset.
+        let mut state = 12345u32;
+        let varied: Vec<u8> = (0..50_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                b'a' + (state >> 16) as u8 % 6
+            })
+            .collect();
+        let repetitive = b"abc".repeat(20_000);
+        let inputs: [&[u8]; 5] = [b"", b"x", SAMPLE, &repetitive, &varied];
+        for early_change in [true, false] {
+            for data in inputs {
+                let encoded = lzw_encode(data, early_change);
+                assert_eq!(lzw_decode(&encoded, early_change), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lzw_matches_spec_example() {
+        // ISO 32000-1 §7.4.4.2, codes 256 45 258 258 65 259 66 257.
+        let encoded = [0x80, 0x0B, 0x60, 0x50, 0x22, 0x0C, 0x0C, 0x85, 0x01];
+        assert_eq!(lzw_encode(b"-----A---B", true), encoded);
+        assert_eq!(lzw_decode(&encoded, true), b"-----A---B");
+    }
+}
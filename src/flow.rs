@@ -0,0 +1,1385 @@
+//! Flowable page layout for generating new PDFs from structured content.
+//!
+//! Flowables (paragraphs, images, spacers) are measured against the frame
+//! width, broken into lines and stacked top to bottom, starting a new page
+//! whenever the frame runs out. A [`Story`] instead pours them into areas
+//! the caller picks one at a time. Text is set in the standard Helvetica
+//! faces so no font files are needed.
+
+use crate::models::{PdfError, PdfResult};
+use crate::pdf_engine::win_ansi_bytes;
+use crate::stext::Rect;
+use lopdf::{Document, Object, ObjectId};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl PageSize {
+    pub const LETTER: Self = Self {
+        width: 612.0,
+        height: 792.0,
+    };
+    pub const LEGAL: Self = Self {
+        width: 612.0,
+        height: 1008.0,
+    };
+    pub const A4: Self = Self {
+        width: 595.28,
+        height: 841.89,
+    };
+
+    /// Look up a paper size by name ("letter", "legal", "a4").
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "letter" => Some(Self::LETTER),
+            "legal" => Some(Self::LEGAL),
+            "a4" => Some(Self::A4),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FontFace {
+    #[default]
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontFace {
+    const ALL: [Self; 4] = [Self::Regular, Self::Bold, Self::Italic, Self::BoldItalic];
+
+    pub const fn new(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => Self::Regular,
+            (true, false) => Self::Bold,
+            (false, true) => Self::Italic,
+            (true, true) => Self::BoldItalic,
+        }
+    }
+
+    const fn resource(self) -> &'static [u8] {
+        match self {
+            Self::Regular => b"F1",
+            Self::Bold => b"F2",
+            Self::Italic => b"F3",
+            Self::BoldItalic => b"F4",
+        }
+    }
+
+    const fn base_font(self) -> &'static [u8] {
+        match self {
+            Self::Regular => b"Helvetica",
+            Self::Bold => b"Helvetica-Bold",
+            Self::Italic => b"Helvetica-Oblique",
+            Self::BoldItalic => b"Helvetica-BoldOblique",
+        }
+    }
+
+    const fn is_bold(self) -> bool {
+        matches!(self, Self::Bold | Self::BoldItalic)
+    }
+}
+
+/// Helvetica advance widths for ' '..='~', in thousandths of an em.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+fn glyph_width(c: char) -> u16 {
+    match c {
+        ' '..='~' => HELVETICA_WIDTHS[c as usize - 32],
+        '•' => 350,
+        '‘' | '’' => 222,
+        '“' | '”' => 333,
+        '—' | '…' => 1000,
+        _ => 556,
+    }
+}
+
+/// Advance width of `text` in points. Bold faces are approximated as a few
+/// percent wider than regular Helvetica.
+pub fn text_width(text: &str, face: FontFace, size: f32) -> f32 {
+    let units: u32 = text.chars().map(|c| u32::from(glyph_width(c))).sum();
+    let bold = if face.is_bold() { 1.06 } else { 1.0 };
+    units as f32 * size / 1000.0 * bold
+}
+
+/// A run of text in one face, size and colour. A `'\n'` in the text forces
+/// a line break.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub face: FontFace,
+    pub size: f32,
+    pub color: (f32, f32, f32),
+}
+
+impl Span {
+    pub fn new(text: impl Into<String>, face: FontFace, size: f32) -> Self {
+        Self {
+            text: text.into(),
+            face,
+            size,
+            color: (0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Vertical spacing and left indent around a block, in points. Space before
+/// is dropped at the top of a page.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BlockStyle {
+    pub space_before: f32,
+    pub space_after: f32,
+    pub indent: f32,
+}
+
+#[derive(Debug, Clone)]
+pub enum Flowable {
+    Paragraph {
+        spans: Vec<Span>,
+        style: BlockStyle,
+        /// Marker drawn in the indent on the first line, e.g. "•" or "3.".
+        bullet: Option<String>,
+    },
+    /// 8-bit RGB pixels, drawn at 96 dpi and shrunk to fit the frame.
+    Image {
+        rgb: Vec<u8>,
+        width: u32,
+        height: u32,
+        style: BlockStyle,
+    },
+    Table(Table),
+    Spacer(f32),
+    PageBreak,
+}
+
+impl Flowable {
+    /// Scale text sizes, spacing and indents by `factor`, e.g. to lay the
+    /// same content out at a reader's chosen font size. Images keep their
+    /// size.
+    pub fn scale(&mut self, factor: f32) {
+        let scale_style = |style: &mut BlockStyle| {
+            style.space_before *= factor;
+            style.space_after *= factor;
+            style.indent *= factor;
+        };
+        match self {
+            Self::Paragraph { spans, style, .. } => {
+                for span in spans {
+                    span.size *= factor;
+                }
+                scale_style(style);
+            }
+            Self::Image { style, .. } => scale_style(style),
+            Self::Table(table) => {
+                for span in table.rows.iter_mut().flatten().flat_map(|c| &mut c.spans) {
+                    span.size *= factor;
+                }
+                table.padding *= factor;
+                scale_style(&mut table.style);
+            }
+            Self::Spacer(height) => *height *= factor,
+            Self::PageBreak => {}
+        }
+    }
+}
+
+/// One table cell: a run of spans wrapped to the column width.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cell {
+    pub spans: Vec<Span>,
+}
+
+impl Cell {
+    pub fn new(spans: Vec<Span>) -> Self {
+        Self { spans }
+    }
+
+    /// A cell of plain 10pt text in `face`.
+    pub fn text(text: impl Into<String>, face: FontFace) -> Self {
+        Self::new(vec![Span::new(text, face, 10.0)])
+    }
+
+    /// Widest unbreakable word; the column can't usefully be narrower.
+    fn min_width(&self) -> f32 {
+        self.spans
+            .iter()
+            .flat_map(|s| {
+                s.text
+                    .split_whitespace()
+                    .map(|w| text_width(w, s.face, s.size))
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Width of the content laid out without wrapping.
+    fn natural_width(&self) -> f32 {
+        break_lines(&self.spans, f32::INFINITY)
+            .iter()
+            .map(|l| l.width)
+            .fold(0.0, f32::max)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        Self::text(text, FontFace::Regular)
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self::text(text, FontFace::Regular)
+    }
+}
+
+/// A bordered grid whose column widths are taken from the content. Rows
+/// never split internally; when one doesn't fit, the table continues on
+/// the next page with the header rows drawn again.
+#[derive(Debug, Clone)]
+pub struct Table {
+    rows: Vec<Vec<Cell>>,
+    header_rows: usize,
+    padding: f32,
+    style: BlockStyle,
+}
+
+impl Table {
+    /// Build a table whose first row is the header.
+    pub fn with_rows(rows: Vec<Vec<Cell>>) -> Self {
+        Self {
+            header_rows: usize::from(!rows.is_empty()),
+            rows,
+            padding: 4.0,
+            style: BlockStyle {
+                space_before: 6.0,
+                space_after: 6.0,
+                indent: 0.0,
+            },
+        }
+    }
+
+    /// Number of leading rows repeated at the top of every page.
+    #[must_use]
+    pub fn header_rows(mut self, count: usize) -> Self {
+        self.header_rows = count.min(self.rows.len());
+        self
+    }
+
+    #[must_use]
+    pub fn padding(mut self, padding: f32) -> Self {
+        self.padding = padding.max(0.0);
+        self
+    }
+
+    #[must_use]
+    pub fn style(mut self, style: BlockStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    fn column_count(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Give every column its natural width if the table fits; otherwise
+    /// start from each column's longest word and share out the remaining
+    /// space in proportion to how much more each column would like.
+    fn column_widths(&self, available: f32) -> Vec<f32> {
+        let cols = self.column_count();
+        let pad = 2.0 * self.padding;
+        let mut min = vec![pad; cols];
+        let mut natural = vec![pad; cols];
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                min[i] = min[i].max(cell.min_width() + pad);
+                natural[i] = natural[i].max(cell.natural_width() + pad);
+            }
+        }
+        let natural_total: f32 = natural.iter().sum();
+        if natural_total <= available {
+            return natural;
+        }
+        let min_total: f32 = min.iter().sum();
+        if min_total >= available {
+            return min.iter().map(|w| w * available / min_total).collect();
+        }
+        let slack = available - min_total;
+        let wanted = natural_total - min_total;
+        min.iter()
+            .zip(&natural)
+            .map(|(lo, hi)| lo + (hi - lo) * slack / wanted)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Word {
+    text: String,
+    face: FontFace,
+    size: f32,
+    color: (f32, f32, f32),
+    space_before: bool,
+}
+
+impl Word {
+    fn width(&self) -> f32 {
+        text_width(&self.text, self.face, self.size)
+    }
+
+    fn space_width(&self) -> f32 {
+        if self.space_before {
+            text_width(" ", self.face, self.size)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Line {
+    words: Vec<Word>,
+    width: f32,
+}
+
+impl Line {
+    fn height(&self, fallback: f32) -> f32 {
+        self.words
+            .iter()
+            .map(|w| w.size)
+            .fold(0.0, f32::max)
+            .max(if self.words.is_empty() { fallback } else { 0.0 })
+            * 1.2
+    }
+}
+
+/// The last of `breaks` (byte offsets into `word`) that leaves the head,
+/// hyphen included, no wider than `room`.
+fn last_break_within(word: &Word, breaks: &[usize], room: f32) -> Option<usize> {
+    breaks
+        .iter()
+        .rev()
+        .copied()
+        .find(|&at| text_width(&format!("{}-", &word.text[..at]), word.face, word.size) <= room)
+}
+
+/// Cut the head off `word` at byte offset `at`, hyphenating it if asked,
+/// and shift the remaining `breaks` to match.
+fn take_head(word: &mut Word, breaks: &mut Vec<usize>, at: usize, hyphen: bool) -> Word {
+    let mut head = word.text[..at].to_string();
+    if hyphen {
+        head.push('-');
+    }
+    let head = Word {
+        text: head,
+        ..word.clone()
+    };
+    word.text.drain(..at);
+    word.space_before = false;
+    breaks.retain(|&b| b > at);
+    for b in breaks.iter_mut() {
+        *b -= at;
+    }
+    head
+}
+
+/// Greedy line breaking. Whitespace collapses to single spaces. A word
+/// wider than the line, which no whitespace break can help, is hyphenated
+/// where the English patterns allow, or else split by character, so
+/// nothing runs off the page.
+fn break_lines(spans: &[Span], max_width: f32) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line = Line::default();
+    let mut pending_space = false;
+
+    let push_word = |line: &mut Line, lines: &mut Vec<Line>, mut word: Word| {
+        if line.words.is_empty() {
+            word.space_before = false;
+        }
+        let mut breaks = if word.width() > max_width {
+            crate::hyphen::hyphenate(&word.text, "en")
+        } else {
+            Vec::new()
+        };
+        if !line.words.is_empty() && line.width + word.space_width() + word.width() > max_width {
+            // An overlong word starts in the room left on this line.
+            let room = max_width - line.width - word.space_width();
+            if let Some(at) = last_break_within(&word, &breaks, room) {
+                let head = take_head(&mut word, &mut breaks, at, true);
+                line.width += head.space_width() + head.width();
+                line.words.push(head);
+            }
+            lines.push(std::mem::take(line));
+            word.space_before = false;
+        }
+        while line.words.is_empty() && word.width() > max_width && word.text.chars().count() > 1 {
+            let head = if let Some(at) = last_break_within(&word, &breaks, max_width) {
+                take_head(&mut word, &mut breaks, at, true)
+            } else {
+                let mut at = 0;
+                for (i, c) in word.text.char_indices() {
+                    let next = i + c.len_utf8();
+                    if at > 0 && text_width(&word.text[..next], word.face, word.size) > max_width {
+                        break;
+                    }
+                    at = next;
+                }
+                take_head(&mut word, &mut breaks, at, false)
+            };
+            line.width = head.width();
+            line.words.push(head);
+            lines.push(std::mem::take(line));
+        }
+        line.width += word.space_width() + word.width();
+        line.words.push(word);
+    };
+
+    for span in spans {
+        for (i, segment) in span.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(std::mem::take(&mut line));
+                pending_space = false;
+            }
+            let mut chars = segment.char_indices().peekable();
+            while let Some(&(start, c)) = chars.peek() {
+                if c.is_whitespace() {
+                    pending_space = true;
+                    chars.next();
+                    continue;
+                }
+                let mut end = start;
+                while let Some(&(idx, c)) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    end = idx + c.len_utf8();
+                    chars.next();
+                }
+                let word = Word {
+                    text: segment[start..end].to_string(),
+                    face: span.face,
+                    size: span.size,
+                    color: span.color,
+                    space_before: pending_space,
+                };
+                pending_space = false;
+                // With no whitespace in between, the word is drawn flush
+                // against the previous span.
+                push_word(&mut line, &mut lines, word);
+            }
+        }
+    }
+    if !line.words.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+struct PageOut {
+    content: pdf_writer::Content,
+    images: Vec<(String, ObjectId)>,
+}
+
+/// Stacks flowables onto pages inside a fixed margin, in the spirit of
+/// `ReportLab`'s platypus `SimpleDocTemplate`.
+pub struct DocumentBuilder {
+    page: PageSize,
+    margin: f32,
+    flowables: Vec<Flowable>,
+}
+
+/// Drawing state while laying out: the area content goes in on every page,
+/// in PDF points from the bottom-left corner, the pages so far and the
+/// cursor height.
+struct Frame {
+    area: Rect,
+    pages: Vec<PageOut>,
+    y: f32,
+}
+
+impl Frame {
+    fn top(&self) -> f32 {
+        self.area.y1
+    }
+
+    fn bottom(&self) -> f32 {
+        self.area.y0
+    }
+
+    fn left(&self) -> f32 {
+        self.area.x0
+    }
+
+    fn width(&self) -> f32 {
+        self.area.width()
+    }
+
+    fn height(&self) -> f32 {
+        self.area.height()
+    }
+
+    fn at_top(&self) -> bool {
+        self.pages.is_empty() || (self.y - self.top()).abs() < f32::EPSILON
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(PageOut {
+            content: pdf_writer::Content::new(),
+            images: Vec::new(),
+        });
+        self.y = self.top();
+    }
+
+    /// Make room for `height` points, moving to a new page when the current
+    /// one is too full. An empty page always accepts, so oversized items
+    /// cannot loop forever.
+    fn reserve(&mut self, height: f32) {
+        if self.pages.is_empty() || (self.y - height < self.bottom() && !self.at_top()) {
+            self.new_page();
+        }
+    }
+
+    fn space(&mut self, amount: f32) {
+        if !self.at_top() {
+            self.y = (self.y - amount).max(self.bottom());
+        }
+    }
+
+    fn content(&mut self) -> &mut pdf_writer::Content {
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+        &mut self.pages.last_mut().expect("page just pushed").content
+    }
+}
+
+impl DocumentBuilder {
+    pub fn new(page: PageSize) -> Self {
+        Self {
+            page,
+            margin: 72.0,
+            flowables: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn margin(mut self, margin: f32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn push(&mut self, flowable: Flowable) {
+        self.flowables.push(flowable);
+    }
+
+    /// Lay out every flowable and return the finished document.
+    pub fn build(&self) -> PdfResult<Document> {
+        if self.page.width <= 2.0 * self.margin || self.page.height <= 2.0 * self.margin {
+            return Err(PdfError::from("Page margins leave no room for content"));
+        }
+        let mut doc = Document::with_version("1.7");
+        let mut frame = Frame {
+            area: Rect {
+                x0: self.margin,
+                y0: self.margin,
+                x1: self.page.width - self.margin,
+                y1: self.page.height - self.margin,
+            },
+            pages: Vec::new(),
+            y: 0.0,
+        };
+
+        for flowable in &self.flowables {
+            draw_flowable(&mut doc, &mut frame, flowable)?;
+        }
+        if frame.pages.is_empty() {
+            frame.new_page();
+        }
+        assemble(&mut doc, self.page, frame.pages);
+        Ok(doc)
+    }
+}
+
+fn draw_flowable(doc: &mut Document, frame: &mut Frame, flowable: &Flowable) -> PdfResult<()> {
+    match flowable {
+        Flowable::Paragraph {
+            spans,
+            style,
+            bullet,
+        } => draw_paragraph(frame, spans, style, bullet.as_deref()),
+        Flowable::Image {
+            rgb,
+            width,
+            height,
+            style,
+        } => draw_image(doc, frame, rgb, (*width, *height), style)?,
+        Flowable::Table(table) => draw_table(frame, table),
+        Flowable::Spacer(amount) => frame.space(*amount),
+        Flowable::PageBreak => frame.new_page(),
+    }
+    Ok(())
+}
+
+/// How much of a [`Story`] the last [`Story::place`] fit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StoryPlacement {
+    /// The part of the area the content fills, from its top down.
+    pub filled: Rect,
+    /// Whether content is left over for another area.
+    pub more: bool,
+}
+
+/// The outcome of fitting content into an area.
+struct Placed {
+    area: Rect,
+    fits: Vec<Flowable>,
+    rest: Vec<Flowable>,
+}
+
+/// Flowables poured into areas the caller picks one at a time, like
+/// `MuPDF`'s `fz_story`: [`place`](Self::place) fits what it can into an
+/// area, [`draw`](Self::draw) puts that on the current page, and whatever
+/// was left over goes into the next area. Paragraphs split between lines
+/// and tables between rows, repeating their header; an image or a
+/// `PageBreak` moves on to the next area.
+pub struct Story {
+    pending: Vec<Flowable>,
+    placed: Option<Placed>,
+    doc: Document,
+    pages: Vec<PageOut>,
+}
+
+impl Story {
+    pub fn new(flowables: Vec<Flowable>) -> Self {
+        Self {
+            pending: flowables,
+            placed: None,
+            doc: Document::with_version("1.7"),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Fit as much of the remaining content as `area`, in PDF points from
+    /// the page's bottom-left corner, holds. Placing again before drawing
+    /// starts over with the new area.
+    pub fn place(&mut self, area: Rect) -> StoryPlacement {
+        let (fits, rest, y) = if area.width() > 0.0 && area.height() > 0.0 {
+            split_to_fit(&self.pending, area)
+        } else {
+            (Vec::new(), self.pending.clone(), area.y1)
+        };
+        let more = !rest.is_empty();
+        self.placed = Some(Placed { area, fits, rest });
+        StoryPlacement {
+            filled: Rect {
+                y0: y.clamp(area.y0, area.y1),
+                ..area
+            },
+            more,
+        }
+    }
+
+    /// Draw the content the last [`place`](Self::place) fit onto the
+    /// current page, starting the first page if there is none yet.
+    pub fn draw(&mut self) -> PdfResult<()> {
+        let Some(placed) = self.placed.take() else {
+            return Ok(());
+        };
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+        let mut frame = Frame {
+            area: placed.area,
+            pages: std::mem::take(&mut self.pages),
+            y: placed.area.y1,
+        };
+        let drawn = placed
+            .fits
+            .iter()
+            .try_for_each(|flowable| draw_flowable(&mut self.doc, &mut frame, flowable));
+        self.pages = frame.pages;
+        drawn?;
+        self.pending = placed.rest;
+        Ok(())
+    }
+
+    /// Start a new page for the areas drawn from now on.
+    pub fn new_page(&mut self) {
+        self.pages.push(PageOut {
+            content: pdf_writer::Content::new(),
+            images: Vec::new(),
+        });
+    }
+
+    /// The drawn pages as a document of `page`-sized pages.
+    pub fn finish(mut self, page: PageSize) -> Document {
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+        assemble(&mut self.doc, page, self.pages);
+        self.doc
+    }
+}
+
+/// The cursor of a trial layout, moving exactly as [`Frame`]'s does.
+struct Cursor {
+    top: f32,
+    bottom: f32,
+    y: f32,
+}
+
+impl Cursor {
+    fn at_top(&self) -> bool {
+        (self.y - self.top).abs() < f32::EPSILON
+    }
+
+    fn space(&mut self, amount: f32) {
+        if !self.at_top() {
+            self.y = (self.y - amount).max(self.bottom);
+        }
+    }
+
+    /// Whether `height` more fits; at the top anything does, as on a page.
+    fn fits(&self, height: f32) -> bool {
+        self.y - height >= self.bottom || self.at_top()
+    }
+}
+
+/// Spans that lay out as `lines` did: each word keeps its face and spacing,
+/// and lines are joined with `'\n'` when `forced`, else with a space unless
+/// the line ends in a hyphen.
+fn line_spans(lines: &[Line], forced: bool) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        for (j, word) in line.words.iter().enumerate() {
+            let separator = if j > 0 {
+                if word.space_before { " " } else { "" }
+            } else if i == 0 {
+                ""
+            } else if forced {
+                "\n"
+            } else if spans.last().is_some_and(|s| s.text.ends_with('-')) {
+                ""
+            } else {
+                " "
+            };
+            spans.push(Span {
+                text: format!("{separator}{}", word.text),
+                face: word.face,
+                size: word.size,
+                color: word.color,
+            });
+        }
+    }
+    spans
+}
+
+/// Split `flowables` into the part that fits `area` and the rest, and say
+/// where the cursor ends up.
+fn split_to_fit(flowables: &[Flowable], area: Rect) -> (Vec<Flowable>, Vec<Flowable>, f32) {
+    let mut cursor = Cursor {
+        top: area.y1,
+        bottom: area.y0,
+        y: area.y1,
+    };
+    let mut fits = Vec::new();
+    for (i, flowable) in flowables.iter().enumerate() {
+        let before = cursor.y;
+        let later = || flowables[i + 1..].iter().cloned();
+        match flowable {
+            Flowable::Paragraph {
+                spans,
+                style,
+                bullet,
+            } => {
+                let fallback = spans.first().map_or(11.0, |s| s.size);
+                let lines = break_lines(spans, area.width() - style.indent);
+                cursor.space(style.space_before);
+                let mut taken = 0;
+                for line in &lines {
+                    let height = line.height(fallback);
+                    if !cursor.fits(height) {
+                        break;
+                    }
+                    cursor.y -= height;
+                    taken += 1;
+                }
+                if taken < lines.len() {
+                    if taken == 0 {
+                        let rest = flowables[i..].to_vec();
+                        return (fits, rest, before);
+                    }
+                    fits.push(Flowable::Paragraph {
+                        spans: line_spans(&lines[..taken], true),
+                        style: *style,
+                        bullet: bullet.clone(),
+                    });
+                    let continued = Flowable::Paragraph {
+                        spans: line_spans(&lines[taken..], false),
+                        style: BlockStyle {
+                            space_before: 0.0,
+                            ..*style
+                        },
+                        bullet: None,
+                    };
+                    let rest = std::iter::once(continued).chain(later()).collect();
+                    return (fits, rest, cursor.y);
+                }
+                cursor.space(style.space_after);
+            }
+            Flowable::Image {
+                width,
+                height,
+                style,
+                ..
+            } => {
+                let (natural_w, natural_h) = (*width as f32 * 0.75, *height as f32 * 0.75);
+                let scale = ((area.width() - style.indent) / natural_w)
+                    .min(area.height() / natural_h)
+                    .min(1.0);
+                cursor.space(style.space_before);
+                if !cursor.fits(natural_h * scale) {
+                    return (fits, flowables[i..].to_vec(), before);
+                }
+                cursor.y -= natural_h * scale;
+                cursor.space(style.space_after);
+            }
+            Flowable::Table(table) => {
+                if let Some(rest) = fit_table(&mut cursor, table, area, &mut fits) {
+                    let rest = std::iter::once(rest).chain(later()).collect();
+                    return (fits, rest, cursor.y);
+                }
+            }
+            Flowable::Spacer(amount) => cursor.space(*amount),
+            Flowable::PageBreak => return (fits, later().collect(), cursor.y),
+        }
+        fits.push(flowable.clone());
+    }
+    (fits, Vec::new(), cursor.y)
+}
+
+/// Fit `table`'s rows into the cursor's area as [`draw_table`] would draw
+/// them. When not every row fits, the rows that do are pushed onto `fits`
+/// and the rest is returned as a table of its own.
+fn fit_table(
+    cursor: &mut Cursor,
+    table: &Table,
+    area: Rect,
+    fits: &mut Vec<Flowable>,
+) -> Option<Flowable> {
+    if table.rows.is_empty() {
+        return None;
+    }
+    let widths = table.column_widths(area.width() - table.style.indent);
+    let heights: Vec<f32> = table
+        .rows
+        .iter()
+        .map(|row| lay_out_row(row, &widths, table.padding).height)
+        .collect();
+    let (header, body) = heights.split_at(table.header_rows);
+    let header_height: f32 = header.iter().sum();
+    let repeat_header = header_height <= area.height() / 2.0;
+
+    let before = cursor.y;
+    cursor.space(table.style.space_before);
+    if !cursor.fits(header_height + body.first().copied().unwrap_or(0.0)) {
+        cursor.y = before;
+        return Some(Flowable::Table(table.clone()));
+    }
+    for &height in header {
+        cursor.y -= height.min(cursor.y - cursor.bottom);
+    }
+    for (j, &height) in body.iter().enumerate() {
+        let room = cursor.y - cursor.bottom;
+        if height > room && (j > 0 || room < 1.0) {
+            let split = table.header_rows + j;
+            fits.push(Flowable::Table(Table {
+                rows: table.rows[..split].to_vec(),
+                ..table.clone()
+            }));
+            let header_rows = if repeat_header { table.header_rows } else { 0 };
+            let rows = table.rows[..header_rows]
+                .iter()
+                .chain(&table.rows[split..])
+                .cloned()
+                .collect();
+            return Some(Flowable::Table(Table {
+                rows,
+                header_rows,
+                padding: table.padding,
+                style: BlockStyle {
+                    space_before: 0.0,
+                    ..table.style
+                },
+            }));
+        }
+        cursor.y -= height.min(room);
+    }
+    cursor.space(table.style.space_after);
+    None
+}
+
+fn draw_paragraph(frame: &mut Frame, spans: &[Span], style: &BlockStyle, bullet: Option<&str>) {
+    let fallback = spans.first().map_or(11.0, |s| s.size);
+    let lines = break_lines(spans, frame.width() - style.indent);
+    frame.space(style.space_before);
+    for (i, line) in lines.iter().enumerate() {
+        let height = line.height(fallback);
+        frame.reserve(height);
+        let baseline = frame.y - height / 1.2;
+        let x = frame.left() + style.indent;
+        let content = frame.content();
+        if i == 0
+            && let Some(bullet) = bullet
+        {
+            let size = line.words.first().map_or(fallback, |w| w.size);
+            content.begin_text();
+            content.set_font(pdf_writer::Name(FontFace::Regular.resource()), size);
+            content.set_fill_gray(0.0);
+            content.next_line(
+                x - text_width(bullet, FontFace::Regular, size) - 6.0,
+                baseline,
+            );
+            content.show(pdf_writer::Str(&win_ansi_bytes(bullet)));
+            content.end_text();
+        }
+        show_line(content, line, x, baseline);
+        frame.y -= height;
+    }
+    frame.space(style.space_after);
+}
+
+/// A table row broken into lines for the final column widths.
+struct LaidOutRow {
+    cells: Vec<Vec<Line>>,
+    height: f32,
+}
+
+fn lay_out_row(row: &[Cell], widths: &[f32], padding: f32) -> LaidOutRow {
+    let mut height: f32 = 0.0;
+    let cells = widths
+        .iter()
+        .enumerate()
+        .map(|(i, width)| {
+            let cell = row.get(i).cloned().unwrap_or_default();
+            let fallback = cell.spans.first().map_or(10.0, |s| s.size);
+            let lines = break_lines(&cell.spans, (width - 2.0 * padding).max(1.0));
+            let text_height: f32 = lines.iter().map(|l| l.height(fallback)).sum();
+            height = height.max(text_height.max(fallback * 1.2));
+            lines
+        })
+        .collect();
+    LaidOutRow {
+        cells,
+        height: height + 2.0 * padding,
+    }
+}
+
+/// Draw one row with its top edge at the cursor. A row taller than the
+/// space left is clipped to it so oversized rows still terminate.
+fn draw_row(
+    frame: &mut Frame,
+    row: &LaidOutRow,
+    x0: f32,
+    widths: &[f32],
+    padding: f32,
+    header: bool,
+) {
+    let top = frame.y;
+    let height = row.height.min(frame.y - frame.bottom());
+    let total_width: f32 = widths.iter().sum();
+    let content = frame.content();
+    content.save_state();
+    content.rect(x0, top - height, total_width, height);
+    content.clip_nonzero();
+    content.end_path();
+    if header {
+        content.set_fill_gray(0.9);
+        content.rect(x0, top - height, total_width, height);
+        content.fill_nonzero();
+    }
+    let mut x = x0;
+    for (lines, width) in row.cells.iter().zip(widths) {
+        let mut y = top - padding;
+        for line in lines {
+            let line_height = line.height(10.0);
+            show_line(content, line, x + padding, y - line_height / 1.2);
+            y -= line_height;
+        }
+        x += width;
+    }
+    content.restore_state();
+
+    content.set_stroke_gray(0.0);
+    content.set_line_width(0.5);
+    let mut x = x0;
+    for width in widths {
+        content.rect(x, top - height, *width, height);
+        x += width;
+    }
+    content.stroke();
+    frame.y -= height;
+}
+
+fn draw_table(frame: &mut Frame, table: &Table) {
+    if table.rows.is_empty() {
+        return;
+    }
+    let widths = table.column_widths(frame.width() - table.style.indent);
+    let rows: Vec<LaidOutRow> = table
+        .rows
+        .iter()
+        .map(|row| lay_out_row(row, &widths, table.padding))
+        .collect();
+    let (header, body) = rows.split_at(table.header_rows);
+    let header_height: f32 = header.iter().map(|r| r.height).sum();
+    // A header that eats most of the page is drawn once, not repeated.
+    let repeat_header = header_height <= frame.height() / 2.0;
+
+    frame.space(table.style.space_before);
+    frame.reserve(header_height + body.first().map_or(0.0, |r| r.height));
+    let x = frame.left() + table.style.indent;
+    for row in header {
+        draw_row(frame, row, x, &widths, table.padding, true);
+    }
+    let mut rows_on_page = 0;
+    for row in body {
+        let room = frame.y - frame.bottom();
+        if row.height > room && (rows_on_page > 0 || room < 1.0) {
+            frame.new_page();
+            if repeat_header {
+                for row in header {
+                    draw_row(frame, row, x, &widths, table.padding, true);
+                }
+            }
+            rows_on_page = 0;
+        }
+        draw_row(frame, row, x, &widths, table.padding, false);
+        rows_on_page += 1;
+    }
+    frame.space(table.style.space_after);
+}
+
+fn show_line(content: &mut pdf_writer::Content, line: &Line, x: f32, baseline: f32) {
+    if line.words.is_empty() {
+        return;
+    }
+    content.begin_text();
+    content.next_line(x, baseline);
+    let mut font = None;
+    let mut color = None;
+    for word in &line.words {
+        if font != Some((word.face, word.size)) {
+            content.set_font(pdf_writer::Name(word.face.resource()), word.size);
+            font = Some((word.face, word.size));
+        }
+        if color != Some(word.color) {
+            let (r, g, b) = word.color;
+            content.set_fill_rgb(r, g, b);
+            color = Some(word.color);
+        }
+        let text = if word.space_before {
+            format!(" {}", word.text)
+        } else {
+            word.text.clone()
+        };
+        content.show(pdf_writer::Str(&win_ansi_bytes(&text)));
+    }
+    content.end_text();
+}
+
+fn draw_image(
+    doc: &mut Document,
+    frame: &mut Frame,
+    rgb: &[u8],
+    (px_w, px_h): (u32, u32),
+    style: &BlockStyle,
+) -> PdfResult<()> {
+    if px_w == 0 || px_h == 0 || rgb.len() != px_w as usize * px_h as usize * 3 {
+        return Err(PdfError::from("Image data does not match its dimensions"));
+    }
+    // CSS pixels are 1/96 in; PDF points are 1/72 in.
+    let (natural_w, natural_h) = (px_w as f32 * 0.75, px_h as f32 * 0.75);
+    let scale = ((frame.width() - style.indent) / natural_w)
+        .min(frame.height() / natural_h)
+        .min(1.0);
+    let (w, h) = (natural_w * scale, natural_h * scale);
+
+    frame.space(style.space_before);
+    frame.reserve(h);
+    let image_id = doc.add_object(lopdf::Stream::new(
+        lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"XObject".to_vec())),
+            ("Subtype", Object::Name(b"Image".to_vec())),
+            ("Width", Object::Integer(i64::from(px_w))),
+            ("Height", Object::Integer(i64::from(px_h))),
+            ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+            ("BitsPerComponent", Object::Integer(8)),
+        ]),
+        rgb.to_vec(),
+    ));
+    let x = frame.left() + style.indent;
+    let y = frame.y - h;
+    let content = frame.content();
+    content.save_state();
+    content.transform([w, 0.0, 0.0, h, x, y]);
+    let page = frame.pages.last_mut().expect("content() ensures a page");
+    let name = format!("Im{}", page.images.len());
+    page.content.x_object(pdf_writer::Name(name.as_bytes()));
+    page.content.restore_state();
+    page.images.push((name, image_id));
+    frame.y = y;
+    frame.space(style.space_after);
+    Ok(())
+}
+
+/// Write the page tree, shared fonts and catalog for the laid-out pages.
+fn assemble(doc: &mut Document, size: PageSize, pages: Vec<PageOut>) {
+    let fonts = lopdf::Dictionary::from_iter(FontFace::ALL.map(|face| {
+        let id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(face.base_font().to_vec())),
+            ("Encoding", Object::Name(b"WinAnsiEncoding".to_vec())),
+        ])));
+        (face.resource().to_vec(), Object::Reference(id))
+    }));
+
+    let pages_id = doc.new_object_id();
+    let mut kids = Vec::new();
+    for page in pages {
+        let content_id = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::new(),
+            page.content.finish().to_vec(),
+        ));
+        let mut resources =
+            lopdf::Dictionary::from_iter(vec![("Font", Object::Dictionary(fonts.clone()))]);
+        if !page.images.is_empty() {
+            resources.set(
+                "XObject",
+                Object::Dictionary(
+                    page.images
+                        .into_iter()
+                        .map(|(name, id)| (name.into_bytes(), Object::Reference(id)))
+                        .collect(),
+                ),
+            );
+        }
+        let page_ref = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(pages_id)),
+            (
+                "MediaBox",
+                Object::Array(vec![
+                    Object::Real(0.0),
+                    Object::Real(0.0),
+                    Object::Real(size.width),
+                    Object::Real(size.height),
+                ]),
+            ),
+            ("Contents", Object::Reference(content_id)),
+            ("Resources", Object::Dictionary(resources)),
+        ])));
+        kids.push(Object::Reference(page_ref));
+    }
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Count", Object::Integer(kids.len() as i64)),
+            ("Kids", Object::Array(kids)),
+        ])),
+    );
+    let catalog_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Catalog".to_vec())),
+        ("Pages", Object::Reference(pages_id)),
+    ])));
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &Line) -> String {
+        line.words
+            .iter()
+            .map(|w| {
+                if w.space_before {
+                    format!(" {}", w.text)
+                } else {
+                    w.text.clone()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_break_lines_wraps_and_glues_spans() {
+        let spans = vec![
+            Span::new("The quick  brown ", FontFace::Regular, 10.0),
+            Span::new("fox", FontFace::Bold, 10.0),
+            Span::new("es jump\nover", FontFace::Regular, 10.0),
+        ];
+        let lines = break_lines(&spans, 80.0);
+        let text: Vec<String> = lines.iter().map(words).collect();
+        assert_eq!(text, vec!["The quick brown", "foxes jump", "over"]);
+        assert!(lines.iter().all(|l| l.width <= 80.0));
+    }
+
+    #[test]
+    fn test_break_lines_splits_overlong_words() {
+        let lines = break_lines(&[Span::new("W".repeat(40), FontFace::Regular, 10.0)], 100.0);
+        assert!(lines.len() > 1);
+        assert_eq!(
+            lines.iter().map(|l| l.words[0].text.len()).sum::<usize>(),
+            40
+        );
+        assert!(lines.iter().all(|l| l.width <= 100.0));
+    }
+
+    #[test]
+    fn test_break_lines_hyphenates_only_overlong_words() {
+        let size = 10.0;
+        let width = |text| text_width(text, FontFace::Regular, size);
+        // "typography" fits a line of its own, so it moves down whole.
+        let spans = [Span::new("on typography", FontFace::Regular, size)];
+        let lines = break_lines(&spans, width("typography"));
+        assert_eq!(
+            lines.iter().map(words).collect::<Vec<_>>(),
+            ["on", "typography"]
+        );
+
+        // Too wide for any line, "hyphenation" breaks at its patterns,
+        // starting in the room after "an".
+        let spans = [Span::new("an hyphenation", FontFace::Regular, size)];
+        let lines = break_lines(&spans, width("an hyphen-"));
+        assert_eq!(
+            lines.iter().map(words).collect::<Vec<_>>(),
+            ["an hyphen-", "ation"]
+        );
+        let lines = break_lines(&spans, width("hyphen-"));
+        assert_eq!(
+            lines.iter().map(words).collect::<Vec<_>>(),
+            ["an hy-", "phen-", "ation"]
+        );
+    }
+
+    #[test]
+    fn test_builder_paginates() {
+        let mut builder = DocumentBuilder::new(PageSize::LETTER);
+        for i in 0..120 {
+            builder.push(Flowable::Paragraph {
+                spans: vec![Span::new(format!("Line {i}"), FontFace::Regular, 11.0)],
+                style: BlockStyle::default(),
+                bullet: None,
+            });
+        }
+        let doc = builder.build().unwrap();
+        // 648pt of frame at 13.2pt per line fits 49 lines per page.
+        assert_eq!(doc.get_pages().len(), 3);
+    }
+
+    fn table_page_texts(doc: &Document) -> Vec<String> {
+        doc.get_pages()
+            .values()
+            .map(|id| String::from_utf8_lossy(&doc.get_page_content(*id)).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_table_column_widths_follow_content() {
+        let table = Table::with_rows(vec![
+            vec!["Id".into(), "Description".into()],
+            vec!["1".into(), "A much longer description of the item".into()],
+        ]);
+        let widths = table.column_widths(468.0);
+        assert!(widths[1] > widths[0] * 4.0);
+        assert!(widths.iter().sum::<f32>() <= 468.0);
+
+        // Squeezed, every column still holds its longest word.
+        let widths = table.column_widths(150.0);
+        assert!((widths.iter().sum::<f32>() - 150.0).abs() < 0.01);
+        assert!(widths[1] >= text_width("description", FontFace::Regular, 10.0) + 8.0);
+    }
+
+    #[test]
+    fn test_table_splits_across_pages_repeating_header() {
+        let mut rows = vec![vec![
+            Cell::text("Quantity", FontFace::Bold),
+            Cell::text("Item", FontFace::Bold),
+        ]];
+        for i in 0..100 {
+            rows.push(vec![format!("{i}").into(), format!("Row {i}").into()]);
+        }
+        let mut builder = DocumentBuilder::new(PageSize::LETTER);
+        builder.push(Flowable::Table(Table::with_rows(rows)));
+        let doc = builder.build().unwrap();
+
+        let pages = table_page_texts(&doc);
+        // 20pt rows, 32 to a 648pt frame including the header.
+        assert_eq!(pages.len(), 4);
+        assert!(pages.iter().all(|p| p.contains("(Quantity)")));
+        assert!(pages[3].contains("(99)"));
+    }
+
+    #[test]
+    fn test_story_splits_table_between_areas() {
+        let mut rows = vec![vec![
+            Cell::text("Quantity", FontFace::Bold),
+            Cell::text("Item", FontFace::Bold),
+        ]];
+        for i in 0..12 {
+            rows.push(vec![format!("{i}").into(), format!("Row {i}").into()]);
+        }
+        let mut story = Story::new(vec![Flowable::Table(Table::with_rows(rows))]);
+        let area = |top: f32| Rect {
+            x0: 72.0,
+            y0: top - 120.0,
+            x1: 540.0,
+            y1: top,
+        };
+        // 20pt rows: the header and five rows to an area.
+        let first = story.place(area(700.0));
+        assert!(first.more);
+        assert_eq!(first.filled.y0, 700.0 - 120.0);
+        story.draw().unwrap();
+        story.new_page();
+        assert!(story.place(area(700.0)).more);
+        story.draw().unwrap();
+        story.new_page();
+        assert!(!story.place(area(700.0)).more);
+        story.draw().unwrap();
+
+        let pages = table_page_texts(&story.finish(PageSize::LETTER));
+        assert_eq!(pages.len(), 3);
+        assert!(pages.iter().all(|p| p.contains("(Quantity)")));
+        assert!(pages[0].contains("(Row 4)") && !pages[0].contains("(Row 5)"));
+        assert!(pages[2].contains("(Row 11)"));
+    }
+
+    #[test]
+    fn test_table_row_taller_than_page_is_clipped() {
+        let tall = Cell::text("line\n".repeat(200), FontFace::Regular);
+        let mut builder = DocumentBuilder::new(PageSize::LETTER);
+        builder.push(Flowable::Table(Table::with_rows(vec![
+            vec!["Header".into()],
+            vec![tall],
+            vec!["After".into()],
+        ])));
+        let pages = table_page_texts(&builder.build().unwrap());
+        assert_eq!(pages.len(), 2);
+        assert!(pages[1].contains("(After)"));
+    }
+}
@@ -0,0 +1,1002 @@
+//! Embedded font programs.
+//!
+//! [`extract_font`] pulls the program out of a font's descriptor
+//! (`/FontFile`, `/FontFile2` or `/FontFile3`) in a form other tools load:
+//! TrueType and OpenType data as it is, CFF as a bare CFF file, and Type 1
+//! fonts reassembled into PFB segments from the `/Length1`–`/Length3`
+//! split. TrueType programs are often embedded without the `name` and
+//! `post` tables, which a PDF reader doesn't need but font tools do; minimal
+//! ones are added back.
+//!
+//! Going the other way, [`embed_truetype`] embeds a [`TrueTypeFont`] for
+//! generated text, optionally subset to the glyphs the text uses.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::models::{ExtractedFont, FontFormat, PdfError, PdfResult};
+use crate::pdf_engine::resolve;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+/// Whether `name` starts with a subset tag: six capital letters and `+`.
+pub fn is_subset_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    bytes.len() > 7 && bytes[6] == b'+' && bytes[..6].iter().all(u8::is_ascii_uppercase)
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// An sfnt version tag and the font's tables as (tag, data).
+pub(crate) type SfntTables<'a> = ([u8; 4], Vec<([u8; 4], &'a [u8])>);
+
+/// The sfnt version tag and tables of a TrueType or OpenType font, in
+/// directory order. `None` if the table directory is damaged.
+pub(crate) fn sfnt_tables(data: &[u8]) -> Option<SfntTables<'_>> {
+    let version: [u8; 4] = data.get(..4)?.try_into().ok()?;
+    let count = usize::from(read_u16(data, 4)?);
+    let mut tables = Vec::with_capacity(count);
+    for i in 0..count {
+        let record = 12 + i * 16;
+        let tag: [u8; 4] = data.get(record..record + 4)?.try_into().ok()?;
+        let offset = read_u32(data, record + 8)? as usize;
+        let length = read_u32(data, record + 12)? as usize;
+        tables.push((tag, data.get(offset..offset.checked_add(length)?)?));
+    }
+    Some((version, tables))
+}
+
+fn table_checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// Assemble an sfnt font from its tables, sorting the directory by tag and
+/// filling in the checksums and `head.checkSumAdjustment`.
+pub(crate) fn write_sfnt(version: [u8; 4], tables: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut tables: Vec<&([u8; 4], Vec<u8>)> = tables.iter().collect();
+    tables.sort_by_key(|(tag, _)| *tag);
+    let count = tables.len() as u16;
+    let entry_selector = count.max(1).ilog2() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&version);
+    for field in [
+        count,
+        search_range,
+        entry_selector,
+        (count * 16).saturating_sub(search_range),
+    ] {
+        out.extend_from_slice(&field.to_be_bytes());
+    }
+    let mut offset = 12 + tables.len() * 16;
+    let mut head_at = None;
+    for (tag, data) in &tables {
+        let mut data = data.clone();
+        if tag == b"head" && data.len() >= 12 {
+            data[8..12].fill(0);
+            head_at = Some(offset);
+        }
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&table_checksum(&data).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    for (tag, data) in &tables {
+        let start = out.len();
+        out.extend_from_slice(data);
+        if tag == b"head" && data.len() >= 12 {
+            out[start + 8..start + 12].fill(0);
+        }
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+    if let Some(at) = head_at {
+        let adjustment = 0xB1B0_AFBA_u32.wrapping_sub(table_checksum(&out));
+        out[at + 8..at + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    out
+}
+
+/// A format 0 `name` table naming the font `name` in English for
+/// Windows.
+fn name_table(name: &str) -> Vec<u8> {
+    let family = name.split('-').next().unwrap_or(name);
+    let style = name.split_once('-').map_or("Regular", |(_, style)| style);
+    let records = [(1u16, family), (2, style), (3, name), (4, name), (6, name)];
+    let mut strings = Vec::new();
+    let mut table = Vec::new();
+    table.extend_from_slice(&0u16.to_be_bytes());
+    table.extend_from_slice(&(records.len() as u16).to_be_bytes());
+    table.extend_from_slice(&(6 + records.len() as u16 * 12).to_be_bytes());
+    for (id, text) in records {
+        let encoded: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        // Platform 3 (Windows), encoding 1 (Unicode BMP), US English.
+        for field in [3, 1, 0x409, id, encoded.len() as u16, strings.len() as u16] {
+            table.extend_from_slice(&field.to_be_bytes());
+        }
+        strings.extend(encoded);
+    }
+    table.extend(strings);
+    table
+}
+
+/// A version 3 `post` table, which lists no glyph names.
+fn post_table() -> Vec<u8> {
+    let mut table = vec![0u8; 32];
+    table[..4].copy_from_slice(&0x0003_0000_u32.to_be_bytes());
+    table
+}
+
+/// Add the `name` and `post` tables to a TrueType font that lacks them.
+/// Fonts that already have both, or whose tables can't be read, are
+/// returned unchanged.
+fn complete_truetype(data: Vec<u8>, name: &str) -> Vec<u8> {
+    let Some((version, tables)) = sfnt_tables(&data) else {
+        return data;
+    };
+    let has = |tag: &[u8; 4]| tables.iter().any(|(t, _)| t == tag);
+    let (has_name, has_post) = (has(b"name"), has(b"post"));
+    if has_name && has_post {
+        return data;
+    }
+    let mut tables: Vec<([u8; 4], Vec<u8>)> = tables
+        .into_iter()
+        .map(|(tag, table)| (tag, table.to_vec()))
+        .collect();
+    if !has_name {
+        tables.push((*b"name", name_table(name)));
+    }
+    if !has_post {
+        tables.push((*b"post", post_table()));
+    }
+    write_sfnt(version, &tables)
+}
+
+/// Wrap a Type 1 program in PFB segments: the cleartext part, the binary
+/// eexec part and the trailer, split where `/Length1` and `/Length2` say.
+/// A missing trailer is replaced by the standard zeros and `cleartomark`.
+fn type1_pfb(data: &[u8], dict: &Dictionary) -> Vec<u8> {
+    if data.starts_with(&[0x80, 0x01]) {
+        return data.to_vec();
+    }
+    let length = |key: &[u8]| {
+        dict.get(key)
+            .and_then(Object::as_i64)
+            .ok()
+            .and_then(|v| usize::try_from(v).ok())
+    };
+    let clear_len = length(b"Length1")
+        .or_else(|| {
+            let at = data.windows(5).position(|w| w == b"eexec")? + 5;
+            Some(
+                at + data[at..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_whitespace())
+                    .count(),
+            )
+        })
+        .unwrap_or(data.len())
+        .min(data.len());
+    let (clear, rest) = data.split_at(clear_len);
+    let binary_len = length(b"Length2").unwrap_or(rest.len()).min(rest.len());
+    let (binary, trailer) = rest.split_at(binary_len);
+    let trailer = if trailer.iter().all(u8::is_ascii_whitespace) {
+        let mut line = [b'0'; 65];
+        line[64] = b'\n';
+        let mut standard = line.repeat(8);
+        standard.extend_from_slice(b"cleartomark\n");
+        standard
+    } else {
+        trailer.to_vec()
+    };
+    // Some producers embed the eexec part hex encoded, which PFB keeps as
+    // text.
+    let binary_kind = if binary.iter().take(4).all(u8::is_ascii_hexdigit) {
+        1
+    } else {
+        2
+    };
+
+    let mut out = Vec::with_capacity(data.len() + trailer.len() + 20);
+    for (kind, segment) in [(1, clear), (binary_kind, binary), (1, trailer.as_slice())] {
+        out.extend_from_slice(&[0x80, kind]);
+        out.extend_from_slice(&(segment.len() as u32).to_le_bytes());
+        out.extend_from_slice(segment);
+    }
+    out.extend_from_slice(&[0x80, 0x03]);
+    out
+}
+
+/// The font dictionary holding the descriptor: a Type 0 font's descendant,
+/// otherwise the font itself.
+fn descriptor_font<'a>(doc: &'a Document, font: &'a Dictionary) -> PdfResult<&'a Dictionary> {
+    match font.get(b"Subtype").and_then(Object::as_name) {
+        Ok(b"Type3") => Err(PdfError::from("Type 3 fonts have no font program")),
+        Ok(b"Type0") => font
+            .get(b"DescendantFonts")
+            .map(|d| resolve(doc, d))
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|d| d.first())
+            .and_then(|d| resolve(doc, d).as_dict().ok())
+            .ok_or_else(|| PdfError::from("Type 0 font has no descendant font")),
+        _ => Ok(font),
+    }
+}
+
+/// A font's embedded program as its descriptor holds it.
+struct Program<'a> {
+    name: String,
+    /// `FontFile`, `FontFile2` or `FontFile3`.
+    key: &'a [u8],
+    /// The stream's object id when it is shared by reference.
+    id: Option<ObjectId>,
+    stream: &'a Stream,
+}
+
+fn font_program<'a>(doc: &'a Document, font: &'a Dictionary) -> PdfResult<Program<'a>> {
+    let font = descriptor_font(doc, font)?;
+    let descriptor = font
+        .get(b"FontDescriptor")
+        .map(|d| resolve(doc, d))
+        .and_then(Object::as_dict)
+        .map_err(|_| PdfError::from("Font has no descriptor"))?;
+    let name = descriptor
+        .get(b"FontName")
+        .or_else(|_| font.get(b"BaseFont"))
+        .and_then(Object::as_name)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .unwrap_or_default();
+    for key in [b"FontFile".as_slice(), b"FontFile2", b"FontFile3"] {
+        let Ok(entry) = descriptor.get(key) else {
+            continue;
+        };
+        let stream = resolve(doc, entry)
+            .as_stream()
+            .map_err(|_| PdfError::from(format!("Font {name} has a damaged program")))?;
+        return Ok(Program {
+            name,
+            key,
+            id: entry.as_reference().ok(),
+            stream,
+        });
+    }
+    Err(PdfError::from(format!("Font {name} is not embedded")))
+}
+
+/// The ids of the fonts with an embedded program, listing a program shared
+/// by several fonts once. Descendant fonts are reached through their Type 0
+/// parents.
+pub fn embedded_fonts(doc: &Document) -> Vec<ObjectId> {
+    let mut seen = std::collections::HashSet::new();
+    doc.objects
+        .iter()
+        .filter(|(_, obj)| {
+            obj.as_dict().is_ok_and(|dict| {
+                dict.get(b"Type").and_then(Object::as_name).ok() == Some(b"Font".as_slice())
+                    && !matches!(
+                        dict.get(b"Subtype").and_then(Object::as_name),
+                        Ok(b"CIDFontType0" | b"CIDFontType2")
+                    )
+            })
+        })
+        .filter_map(|(&id, obj)| {
+            let program = font_program(doc, obj.as_dict().ok()?).ok()?;
+            program
+                .id
+                .is_none_or(|program| seen.insert(program))
+                .then_some(id)
+        })
+        .collect()
+}
+
+/// Extract the program of the font `font_id` as a standalone font file.
+pub fn extract_font(doc: &Document, font_id: ObjectId) -> PdfResult<ExtractedFont> {
+    let font = doc
+        .get_dictionary(font_id)
+        .map_err(|e| PdfError::from(format!("Not a font: {e}")))?;
+    let Program {
+        name, key, stream, ..
+    } = font_program(doc, font)?;
+    let data = if stream.dict.has(b"Filter") {
+        stream
+            .decompressed_content()
+            .map_err(|e| PdfError::from(format!("Font {name} is corrupt: {e}")))?
+    } else {
+        stream.content.clone()
+    };
+    let sniffed = match data.get(..4) {
+        Some(b"OTTO") => Some(FontFormat::OpenType),
+        Some([0, 1, 0, 0] | b"true" | b"ttcf") => Some(FontFormat::TrueType),
+        _ => None,
+    };
+    let format = match key {
+        b"FontFile" => FontFormat::Type1,
+        b"FontFile2" => sniffed.unwrap_or(FontFormat::TrueType),
+        _ => match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"OpenType") => sniffed.unwrap_or(FontFormat::OpenType),
+            Ok(b"Type1C" | b"CIDFontType0C") => FontFormat::Cff,
+            _ => sniffed.unwrap_or(FontFormat::Cff),
+        },
+    };
+    let data = match format {
+        FontFormat::Type1 => type1_pfb(&data, &stream.dict),
+        FontFormat::TrueType => {
+            let bare = name.get(7..).filter(|_| is_subset_name(&name));
+            complete_truetype(data, bare.unwrap_or(&name))
+        }
+        FontFormat::OpenType | FontFormat::Cff => data,
+    };
+    Ok(ExtractedFont {
+        subset: is_subset_name(&name),
+        name,
+        format,
+        data,
+    })
+}
+
+/// A TrueType font program to embed in generated documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrueTypeFont {
+    data: Vec<u8>,
+    /// The PostScript name, or a stand-in when the font has none.
+    pub name: String,
+    units_per_em: u16,
+    glyphs: BTreeMap<char, u16>,
+    advances: Vec<u16>,
+    bbox: [i16; 4],
+    ascent: i16,
+    descent: i16,
+}
+
+fn table<'a>(tables: &[([u8; 4], &'a [u8])], tag: [u8; 4]) -> Option<&'a [u8]> {
+    tables
+        .iter()
+        .find(|(t, _)| *t == tag)
+        .map(|(_, data)| *data)
+}
+
+fn read_i16(data: &[u8], at: usize) -> Option<i16> {
+    read_u16(data, at).map(|v| v as i16)
+}
+
+/// The character to glyph mapping of a format 4 or format 12 `cmap`
+/// subtable; other formats yield nothing.
+fn cmap_subtable(sub: &[u8]) -> BTreeMap<char, u16> {
+    let mut glyphs = BTreeMap::new();
+    match read_u16(sub, 0) {
+        Some(4) => {
+            let segments = usize::from(read_u16(sub, 6).unwrap_or(0) / 2);
+            for seg in 0..segments {
+                let field = |array: usize| read_u16(sub, 16 + array * segments * 2 + seg * 2);
+                let (Some(end), Some(start), Some(delta), Some(range)) =
+                    (read_u16(sub, 14 + seg * 2), field(1), field(2), field(3))
+                else {
+                    break;
+                };
+                for code in start..=end.min(0xFFFE) {
+                    let glyph = if range == 0 {
+                        code.wrapping_add(delta)
+                    } else {
+                        let at = 16
+                            + segments * 6
+                            + seg * 2
+                            + usize::from(range)
+                            + usize::from(code - start) * 2;
+                        match read_u16(sub, at) {
+                            Some(0) | None => 0,
+                            Some(glyph) => glyph.wrapping_add(delta),
+                        }
+                    };
+                    if let Some(c) = char::from_u32(u32::from(code)).filter(|_| glyph != 0) {
+                        glyphs.insert(c, glyph);
+                    }
+                }
+            }
+        }
+        Some(12) => {
+            let groups = read_u32(sub, 12).unwrap_or(0) as usize;
+            for group in 0..groups {
+                let at = 16 + group * 12;
+                let (Some(start), Some(end), Some(first)) = (
+                    read_u32(sub, at),
+                    read_u32(sub, at + 4),
+                    read_u32(sub, at + 8),
+                ) else {
+                    break;
+                };
+                for code in start..=end.min(0x10_FFFF) {
+                    let glyph = first.wrapping_add(code - start);
+                    if let (Some(c), Ok(glyph)) = (char::from_u32(code), u16::try_from(glyph)) {
+                        glyphs.insert(c, glyph);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    glyphs
+}
+
+/// The Unicode mapping of a `cmap` table, preferring a full-repertoire
+/// subtable over a BMP one.
+fn unicode_cmap(cmap: &[u8]) -> BTreeMap<char, u16> {
+    let count = usize::from(read_u16(cmap, 2).unwrap_or(0));
+    let mut best: Option<(u8, &[u8])> = None;
+    for i in 0..count {
+        let record = 4 + i * 8;
+        let (Some(platform), Some(encoding), Some(offset)) = (
+            read_u16(cmap, record),
+            read_u16(cmap, record + 2),
+            read_u32(cmap, record + 4),
+        ) else {
+            break;
+        };
+        let Some(sub) = cmap.get(offset as usize..) else {
+            continue;
+        };
+        let rank = match (platform, encoding, read_u16(sub, 0)) {
+            (3, 10, Some(12)) | (0, 4 | 6, Some(12)) => 3,
+            (3, 1, Some(4)) | (0, _, Some(4)) => 2,
+            (3, 0, Some(4)) => 1,
+            _ => continue,
+        };
+        if best.is_none_or(|(r, _)| rank > r) {
+            best = Some((rank, sub));
+        }
+    }
+    best.map(|(_, sub)| cmap_subtable(sub)).unwrap_or_default()
+}
+
+/// The PostScript name (name id 6) from a `name` table, in its Windows
+/// UTF-16 or Macintosh Roman form.
+fn postscript_name(name: &[u8]) -> Option<String> {
+    let count = usize::from(read_u16(name, 2)?);
+    let strings = usize::from(read_u16(name, 4)?);
+    (0..count).find_map(|i| {
+        let record = 6 + i * 12;
+        if read_u16(name, record + 6)? != 6 {
+            return None;
+        }
+        let platform = read_u16(name, record)?;
+        let length = usize::from(read_u16(name, record + 8)?);
+        let offset = strings + usize::from(read_u16(name, record + 10)?);
+        let bytes = name.get(offset..offset + length)?;
+        let text = match platform {
+            0 | 3 => String::from_utf16(
+                &bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect::<Vec<_>>(),
+            )
+            .ok()?,
+            1 => bytes.iter().map(|&b| char::from(b)).collect(),
+            _ => return None,
+        };
+        // PDF names and subset tags want plain printable ASCII.
+        let text: String = text
+            .chars()
+            .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+            .collect();
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+/// Offsets of each glyph in `glyf` from a short or long `loca` table.
+fn glyph_offsets(loca: &[u8], long: bool, count: usize) -> Option<Vec<usize>> {
+    (0..=count)
+        .map(|i| {
+            if long {
+                read_u32(loca, i * 4).map(|v| v as usize)
+            } else {
+                read_u16(loca, i * 2).map(|v| usize::from(v) * 2)
+            }
+        })
+        .collect()
+}
+
+/// The glyphs a composite glyph is built from.
+fn glyph_components(glyph: &[u8]) -> Vec<u16> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAVE_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAVE_XY_SCALE: u16 = 0x0040;
+    const HAVE_TWO_BY_TWO: u16 = 0x0080;
+
+    let mut components = Vec::new();
+    if read_i16(glyph, 0).is_none_or(|contours| contours >= 0) {
+        return components;
+    }
+    let mut at = 10;
+    while let (Some(flags), Some(component)) = (read_u16(glyph, at), read_u16(glyph, at + 2)) {
+        components.push(component);
+        at += 4 + if flags & ARGS_ARE_WORDS != 0 { 4 } else { 2 };
+        at += if flags & HAVE_SCALE != 0 {
+            2
+        } else if flags & HAVE_XY_SCALE != 0 {
+            4
+        } else if flags & HAVE_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    components
+}
+
+/// A format 4 `cmap` table mapping the BMP characters in `glyphs`, one
+/// segment per character.
+fn format4_cmap(glyphs: &BTreeMap<char, u16>) -> Vec<u8> {
+    let mut segments: Vec<(u16, u16)> = glyphs
+        .iter()
+        .filter_map(|(&c, &glyph)| Some((u16::try_from(u32::from(c)).ok()?, glyph)))
+        .filter(|&(code, _)| code != 0xFFFF)
+        .collect();
+    segments.push((0xFFFF, 1));
+    let count = segments.len() as u16;
+    let entry_selector = count.ilog2() as u16;
+    let search_range = 2 << entry_selector;
+
+    let mut sub = Vec::new();
+    let length = 16 + segments.len() * 8;
+    for field in [
+        4,
+        length as u16,
+        0,
+        count * 2,
+        search_range,
+        entry_selector,
+        count * 2 - search_range,
+    ] {
+        sub.extend_from_slice(&field.to_be_bytes());
+    }
+    sub.extend(segments.iter().flat_map(|(code, _)| code.to_be_bytes()));
+    sub.extend_from_slice(&[0, 0]);
+    sub.extend(segments.iter().flat_map(|(code, _)| code.to_be_bytes()));
+    sub.extend(
+        segments
+            .iter()
+            .flat_map(|(code, glyph)| glyph.wrapping_sub(*code).to_be_bytes()),
+    );
+    sub.extend(segments.iter().flat_map(|_| [0, 0]));
+
+    let mut table = Vec::with_capacity(12 + sub.len());
+    // Version 0, one subtable: Windows Unicode BMP at offset 12.
+    for field in [0u16, 1, 3, 1] {
+        table.extend_from_slice(&field.to_be_bytes());
+    }
+    table.extend_from_slice(&12u32.to_be_bytes());
+    table.extend(sub);
+    table
+}
+
+impl TrueTypeFont {
+    /// Read the tables a PDF needs from a TrueType font file. OpenType
+    /// fonts with CFF outlines and font collections are rejected.
+    pub fn parse(data: Vec<u8>) -> PdfResult<Self> {
+        let invalid = || PdfError::from("Not a usable TrueType font");
+        let (version, tables) = sfnt_tables(&data).ok_or_else(invalid)?;
+        if !matches!(&version, [0, 1, 0, 0] | b"true") {
+            return Err(PdfError::from("Only TrueType outlines can be embedded"));
+        }
+        let get = |tag: [u8; 4]| table(&tables, tag).ok_or_else(invalid);
+        let (head, hhea, hmtx, maxp) = (
+            get(*b"head")?,
+            get(*b"hhea")?,
+            get(*b"hmtx")?,
+            get(*b"maxp")?,
+        );
+        get(*b"glyf")?;
+        get(*b"loca")?;
+        let units_per_em = read_u16(head, 18).filter(|&u| u > 0).ok_or_else(invalid)?;
+        let count = usize::from(read_u16(maxp, 4).ok_or_else(invalid)?);
+        let metrics = usize::from(read_u16(hhea, 34).ok_or_else(invalid)?).clamp(1, count.max(1));
+        let advances = (0..metrics)
+            .map(|i| read_u16(hmtx, i * 4))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        let bbox = [36, 38, 40, 42].map(|at| read_i16(head, at).unwrap_or(0));
+        let name = table(&tables, *b"name")
+            .and_then(postscript_name)
+            .unwrap_or_else(|| "TrueTypeFont".to_string());
+        let glyphs = table(&tables, *b"cmap")
+            .map(unicode_cmap)
+            .unwrap_or_default();
+        let ascent = read_i16(hhea, 4).unwrap_or(bbox[3]);
+        let descent = read_i16(hhea, 6).unwrap_or(bbox[1]);
+        Ok(Self {
+            data,
+            name,
+            units_per_em,
+            glyphs,
+            advances,
+            bbox,
+            ascent,
+            descent,
+        })
+    }
+
+    /// The font file as given.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The glyph for `c`, or 0 (`.notdef`) when the font lacks it.
+    pub fn glyph(&self, c: char) -> u16 {
+        self.glyphs.get(&c).copied().unwrap_or(0)
+    }
+
+    /// Scale a value in font units to thousandths of the font size.
+    fn to_pdf_units(&self, value: f32) -> f32 {
+        value * 1000.0 / f32::from(self.units_per_em)
+    }
+
+    /// The advance width of `glyph` in thousandths of the font size.
+    pub fn advance(&self, glyph: u16) -> f32 {
+        let advance = self
+            .advances
+            .get(usize::from(glyph))
+            .or_else(|| self.advances.last())
+            .copied()
+            .unwrap_or(0);
+        self.to_pdf_units(f32::from(advance))
+    }
+
+    /// The width of `text` set at `size` points.
+    pub fn text_width(&self, text: &str, size: f32) -> f32 {
+        text.chars()
+            .map(|c| self.advance(self.glyph(c)))
+            .sum::<f32>()
+            * size
+            / 1000.0
+    }
+
+    /// The descender below the baseline as a positive fraction of the font
+    /// size.
+    pub fn descent(&self) -> f32 {
+        -self.to_pdf_units(f32::from(self.descent)) / 1000.0
+    }
+
+    /// A copy of the font keeping only the outlines of `glyphs`, `.notdef`
+    /// and the components they are built from. Glyph ids stay as they are,
+    /// with every other glyph left empty, so text encoded for the full font
+    /// still shows. The `cmap` is cut down to the kept glyphs and tables a
+    /// PDF reader has no use for, such as layout features and glyph names,
+    /// are dropped.
+    pub fn subset(&self, glyphs: &BTreeSet<u16>) -> PdfResult<Vec<u8>> {
+        let invalid = || PdfError::from("Not a usable TrueType font");
+        let (version, tables) = sfnt_tables(&self.data).ok_or_else(invalid)?;
+        let get = |tag: [u8; 4]| table(&tables, tag).ok_or_else(invalid);
+        let (head, maxp, loca, glyf) = (
+            get(*b"head")?,
+            get(*b"maxp")?,
+            get(*b"loca")?,
+            get(*b"glyf")?,
+        );
+        let count = usize::from(read_u16(maxp, 4).ok_or_else(invalid)?);
+        let long = read_i16(head, 50).ok_or_else(invalid)? != 0;
+        let offsets = glyph_offsets(loca, long, count).ok_or_else(invalid)?;
+        let outline = |glyph: usize| {
+            let (start, end) = (offsets[glyph], offsets[glyph + 1]);
+            glyf.get(start..end.max(start)).unwrap_or_default()
+        };
+
+        let mut keep = vec![false; count];
+        let mut pending: Vec<u16> = glyphs.iter().copied().chain([0]).collect();
+        while let Some(glyph) = pending.pop() {
+            let glyph = usize::from(glyph);
+            if glyph < count && !keep[glyph] {
+                keep[glyph] = true;
+                pending.extend(glyph_components(outline(glyph)));
+            }
+        }
+
+        let mut new_glyf = Vec::new();
+        let mut new_loca = Vec::with_capacity((count + 1) * 4);
+        for (glyph, &kept) in keep.iter().enumerate() {
+            new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+            if kept {
+                new_glyf.extend_from_slice(outline(glyph));
+                new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+            }
+        }
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        let mut new_head = head.to_vec();
+        new_head[50..52].copy_from_slice(&1u16.to_be_bytes());
+        let kept_chars: BTreeMap<char, u16> = self
+            .glyphs
+            .iter()
+            .filter(|&(_, &glyph)| keep.get(usize::from(glyph)) == Some(&true))
+            .map(|(&c, &glyph)| (c, glyph))
+            .collect();
+
+        let mut out: Vec<([u8; 4], Vec<u8>)> = vec![
+            (*b"head", new_head),
+            (*b"loca", new_loca),
+            (*b"glyf", new_glyf),
+            (*b"cmap", format4_cmap(&kept_chars)),
+            (*b"post", post_table()),
+        ];
+        for (tag, data) in tables {
+            if matches!(
+                &tag,
+                b"hhea" | b"hmtx" | b"maxp" | b"cvt " | b"fpgm" | b"prep" | b"OS/2" | b"name"
+            ) {
+                out.push((tag, data.to_vec()));
+            }
+        }
+        Ok(write_sfnt(version, &out))
+    }
+}
+
+/// A six letter subset tag derived from the glyphs a subset keeps, so the
+/// same subset always gets the same name.
+fn subset_tag(glyphs: &BTreeSet<u16>) -> String {
+    let mut hash = glyphs
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, glyph| {
+            (hash ^ u64::from(*glyph)).wrapping_mul(0x0100_0000_01b3)
+        });
+    (0..6)
+        .map(|_| {
+            let letter = b'A' + (hash % 26) as u8;
+            hash /= 26;
+            char::from(letter)
+        })
+        .collect()
+}
+
+/// A `/ToUnicode` `CMap` for two-byte glyph codes.
+fn to_unicode_cmap(text: &BTreeMap<u16, char>) -> Vec<u8> {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    let entries: Vec<(&u16, &char)> = text.iter().collect();
+    // A `bfchar` block holds at most 100 entries.
+    for chunk in entries.chunks(100) {
+        let _ = writeln!(cmap, "{} beginbfchar", chunk.len());
+        for (glyph, c) in chunk {
+            let _ = write!(cmap, "<{glyph:04X}> <");
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                let _ = write!(cmap, "{unit:04X}");
+            }
+            cmap.push_str(">\n");
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap.into_bytes()
+}
+
+/// Embed `font` as a Type 0 font with `Identity-H` encoding, whose string
+/// codes are two-byte glyph ids. `text` maps each glyph the document shows
+/// to the character it stands for; it sets the `/W` widths and the
+/// `/ToUnicode` map. With `subset` only those glyphs are embedded and a
+/// `/CIDSet` lists them. Returns the font's id.
+pub fn embed_truetype(
+    doc: &mut Document,
+    font: &TrueTypeFont,
+    text: &BTreeMap<u16, char>,
+    subset: bool,
+) -> PdfResult<ObjectId> {
+    let used: BTreeSet<u16> = text.keys().copied().chain([0]).collect();
+    let (name, program) = if subset {
+        (
+            format!("{}+{}", subset_tag(&used), font.name),
+            font.subset(&used)?,
+        )
+    } else {
+        (font.name.clone(), font.data.clone())
+    };
+    let mut program = Stream::new(
+        Dictionary::from_iter(vec![("Length1", Object::Integer(program.len() as i64))]),
+        program,
+    );
+    program
+        .compress()
+        .map_err(|e| PdfError::from(format!("Failed to compress font: {e}")))?;
+    let program_id = doc.add_object(program);
+
+    let scaled = |value: i16| Object::Integer(font.to_pdf_units(f32::from(value)).round() as i64);
+    let mut descriptor = Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"FontDescriptor".to_vec())),
+        ("FontName", Object::Name(name.clone().into_bytes())),
+        // Symbolic: the glyphs are reached by id, not a standard encoding.
+        ("Flags", Object::Integer(4)),
+        ("FontBBox", Object::Array(font.bbox.map(scaled).to_vec())),
+        ("ItalicAngle", Object::Integer(0)),
+        ("Ascent", scaled(font.ascent)),
+        ("Descent", scaled(font.descent)),
+        ("CapHeight", scaled(font.ascent)),
+        ("StemV", Object::Integer(80)),
+        ("FontFile2", Object::Reference(program_id)),
+    ]);
+    if subset {
+        let last = used.last().copied().map_or(0, usize::from);
+        let mut bits = vec![0u8; last / 8 + 1];
+        for &glyph in &used {
+            bits[usize::from(glyph) / 8] |= 0x80 >> (glyph % 8);
+        }
+        let cid_set = doc.add_object(Stream::new(Dictionary::new(), bits));
+        descriptor.set("CIDSet", Object::Reference(cid_set));
+    }
+    let descriptor_id = doc.add_object(descriptor);
+
+    // Widths as `first [w1 w2 …]` runs of consecutive glyphs.
+    let mut runs: Vec<(u16, Vec<Object>)> = Vec::new();
+    for &glyph in &used {
+        let width = Object::Integer(font.advance(glyph).round() as i64);
+        match runs.last_mut() {
+            Some((first, run)) if usize::from(*first) + run.len() == usize::from(glyph) => {
+                run.push(width);
+            }
+            _ => runs.push((glyph, vec![width])),
+        }
+    }
+    let widths = runs
+        .into_iter()
+        .flat_map(|(first, run)| [Object::Integer(i64::from(first)), Object::Array(run)])
+        .collect();
+
+    let descendant_id = doc.add_object(Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Font".to_vec())),
+        ("Subtype", Object::Name(b"CIDFontType2".to_vec())),
+        ("BaseFont", Object::Name(name.clone().into_bytes())),
+        (
+            "CIDSystemInfo",
+            Object::Dictionary(Dictionary::from_iter(vec![
+                ("Registry", Object::string_literal("Adobe")),
+                ("Ordering", Object::string_literal("Identity")),
+                ("Supplement", Object::Integer(0)),
+            ])),
+        ),
+        ("FontDescriptor", Object::Reference(descriptor_id)),
+        ("DW", Object::Integer(font.advance(0).round() as i64)),
+        ("W", Object::Array(widths)),
+        ("CIDToGIDMap", Object::Name(b"Identity".to_vec())),
+    ]));
+    let to_unicode = doc.add_object(Stream::new(Dictionary::new(), to_unicode_cmap(text)));
+    Ok(doc.add_object(Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Font".to_vec())),
+        ("Subtype", Object::Name(b"Type0".to_vec())),
+        ("BaseFont", Object::Name(name.into_bytes())),
+        ("Encoding", Object::Name(b"Identity-H".to_vec())),
+        (
+            "DescendantFonts",
+            Object::Array(vec![Object::Reference(descendant_id)]),
+        ),
+        ("ToUnicode", Object::Reference(to_unicode)),
+    ])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LUCIDE: &[u8] = include_bytes!("assets/fonts/lucide.ttf");
+
+    /// A document with one TrueType font embedding `program`; returns the
+    /// font's id.
+    fn truetype_doc(name: &str, program: &[u8]) -> (Document, ObjectId) {
+        let mut doc = Document::with_version("1.7");
+        let mut stream = Stream::new(
+            Dictionary::from_iter(vec![("Length1", Object::Integer(program.len() as i64))]),
+            program.to_vec(),
+        );
+        stream.compress().unwrap();
+        let program_id = doc.add_object(stream);
+        let descriptor_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"FontDescriptor".to_vec())),
+            ("FontName", Object::Name(name.as_bytes().to_vec())),
+            ("FontFile2", Object::Reference(program_id)),
+        ]));
+        let font_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"TrueType".to_vec())),
+            ("BaseFont", Object::Name(name.as_bytes().to_vec())),
+            ("FontDescriptor", Object::Reference(descriptor_id)),
+        ]));
+        (doc, font_id)
+    }
+
+    #[test]
+    fn test_extract_truetype() {
+        let (doc, font_id) = truetype_doc("ABCDEF+lucide", LUCIDE);
+        assert_eq!(embedded_fonts(&doc), [font_id]);
+        let font = extract_font(&doc, font_id).unwrap();
+        assert_eq!(font.format, FontFormat::TrueType);
+        assert!(font.subset);
+        assert_eq!(font.file_name(), "lucide.ttf");
+        assert_eq!(font.data, LUCIDE);
+        assert!(ttf_parser::Face::parse(&font.data, 0).is_ok());
+    }
+
+    #[test]
+    fn test_restores_missing_tables() {
+        let (version, tables) = sfnt_tables(LUCIDE).unwrap();
+        let stripped: Vec<([u8; 4], Vec<u8>)> = tables
+            .into_iter()
+            .filter(|(tag, _)| !matches!(tag, b"name" | b"post"))
+            .map(|(tag, table)| (tag, table.to_vec()))
+            .collect();
+        let (doc, font_id) = truetype_doc("Lucide-Icons", &write_sfnt(version, &stripped));
+
+        let font = extract_font(&doc, font_id).unwrap();
+        assert!(!font.subset);
+        let (_, tables) = sfnt_tables(&font.data).unwrap();
+        assert!(tables.iter().any(|(tag, _)| tag == b"name"));
+        assert!(tables.iter().any(|(tag, _)| tag == b"post"));
+        let face = ttf_parser::Face::parse(&font.data, 0).unwrap();
+        let family = face
+            .names()
+            .into_iter()
+            .find(|name| name.name_id == ttf_parser::name_id::FAMILY)
+            .and_then(|name| name.to_string());
+        assert_eq!(family.as_deref(), Some("Lucide"));
+        assert_eq!(table_checksum(&font.data), 0xB1B0_AFBA);
+    }
+
+    #[test]
+    fn test_type1_pfb_segments() {
+        let program = b"%!PS-AdobeFont-1.0: Test\ncurrentfile eexec\n\x8f\x01\x02";
+        let dict = Dictionary::from_iter(vec![
+            ("Length1", Object::Integer(43)),
+            ("Length2", Object::Integer(3)),
+            ("Length3", Object::Integer(0)),
+        ]);
+        let pfb = type1_pfb(program, &dict);
+        assert_eq!(&pfb[..6], [0x80, 0x01, 43, 0, 0, 0]);
+        assert_eq!(&pfb[6..49], &program[..43]);
+        assert_eq!(&pfb[49..55], [0x80, 0x02, 3, 0, 0, 0]);
+        assert_eq!(&pfb[55..58], b"\x8f\x01\x02");
+        assert_eq!(&pfb[58..60], [0x80, 0x01]);
+        assert!(pfb.ends_with(b"cleartomark\n\x80\x03"));
+        assert!(is_subset_name("QWERTY+Times"));
+        assert!(!is_subset_name("Qwerty+Times"));
+    }
+
+    #[test]
+    fn test_subset_keeps_used_glyphs() {
+        let font = TrueTypeFont::parse(LUCIDE.to_vec()).unwrap();
+        assert_eq!(font.name, "lucide");
+        let (kept, dropped) = (font.glyph('\u{E038}'), font.glyph('\u{E039}'));
+        assert!(kept != 0 && dropped != 0);
+        assert_eq!(font.advance(kept), 1000.0);
+
+        let subset = font.subset(&BTreeSet::from([kept])).unwrap();
+        assert!(subset.len() * 20 < LUCIDE.len());
+        assert_eq!(table_checksum(&subset), 0xB1B0_AFBA);
+        let reparsed = TrueTypeFont::parse(subset.clone()).unwrap();
+        assert_eq!(reparsed.glyph('\u{E038}'), kept);
+        assert_eq!(reparsed.glyph('\u{E039}'), 0);
+
+        let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+        assert_eq!(
+            face.number_of_glyphs(),
+            ttf_parser::Face::parse(LUCIDE, 0)
+                .unwrap()
+                .number_of_glyphs()
+        );
+        assert!(face.glyph_bounding_box(ttf_parser::GlyphId(kept)).is_some());
+        assert!(
+            face.glyph_bounding_box(ttf_parser::GlyphId(dropped))
+                .is_none()
+        );
+    }
+}
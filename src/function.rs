@@ -0,0 +1,903 @@
+//! PDF function objects, as used by tint transforms, shadings and transfer
+//! functions.
+//!
+//! All four types are supported: sampled (0), exponential (2), stitching (3)
+//! and PostScript calculator (4). Inputs are clipped to the function's
+//! `/Domain` and outputs to its `/Range` when it has one.
+
+use crate::models::{PdfError, PdfResult};
+use lopdf::{Dictionary, Document, Object};
+
+/// How deeply stitching functions may nest, which also stops reference
+/// cycles.
+const MAX_DEPTH: usize = 8;
+/// The operand stack limit of PostScript calculator functions.
+const MAX_STACK: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionKind {
+    /// Type 0: a grid of samples, already mapped through `/Decode`, with
+    /// the first input varying fastest. Interpolation is multilinear.
+    Sampled {
+        size: Vec<usize>,
+        encode: Vec<(f32, f32)>,
+        samples: Vec<f32>,
+    },
+    /// Type 2: `C0 + x^N × (C1 − C0)` for a single input.
+    Exponential { c0: Vec<f32>, c1: Vec<f32>, n: f32 },
+    /// Type 3: one-input functions covering consecutive parts of the
+    /// domain, split at `bounds`.
+    Stitching {
+        functions: Vec<PdfFunction>,
+        bounds: Vec<f32>,
+        encode: Vec<(f32, f32)>,
+    },
+    /// Type 4: a PostScript calculator program.
+    PostScript(Vec<PsOp>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfFunction {
+    pub domain: Vec<(f32, f32)>,
+    pub range: Option<Vec<(f32, f32)>>,
+    pub kind: FunctionKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PsValue {
+    Int(i64),
+    Real(f64),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsOperator {
+    Abs,
+    Add,
+    And,
+    Atan,
+    Bitshift,
+    Ceiling,
+    Copy,
+    Cos,
+    Cvi,
+    Cvr,
+    Div,
+    Dup,
+    Eq,
+    Exch,
+    Exp,
+    False,
+    Floor,
+    Ge,
+    Gt,
+    Idiv,
+    Index,
+    Le,
+    Ln,
+    Log,
+    Lt,
+    Mod,
+    Mul,
+    Ne,
+    Neg,
+    Not,
+    Or,
+    Pop,
+    Roll,
+    Round,
+    Sin,
+    Sqrt,
+    Sub,
+    True,
+    Truncate,
+    Xor,
+}
+
+impl PsOperator {
+    fn from_name(name: &[u8]) -> Option<Self> {
+        Some(match name {
+            b"abs" => Self::Abs,
+            b"add" => Self::Add,
+            b"and" => Self::And,
+            b"atan" => Self::Atan,
+            b"bitshift" => Self::Bitshift,
+            b"ceiling" => Self::Ceiling,
+            b"copy" => Self::Copy,
+            b"cos" => Self::Cos,
+            b"cvi" => Self::Cvi,
+            b"cvr" => Self::Cvr,
+            b"div" => Self::Div,
+            b"dup" => Self::Dup,
+            b"eq" => Self::Eq,
+            b"exch" => Self::Exch,
+            b"exp" => Self::Exp,
+            b"false" => Self::False,
+            b"floor" => Self::Floor,
+            b"ge" => Self::Ge,
+            b"gt" => Self::Gt,
+            b"idiv" => Self::Idiv,
+            b"index" => Self::Index,
+            b"le" => Self::Le,
+            b"ln" => Self::Ln,
+            b"log" => Self::Log,
+            b"lt" => Self::Lt,
+            b"mod" => Self::Mod,
+            b"mul" => Self::Mul,
+            b"ne" => Self::Ne,
+            b"neg" => Self::Neg,
+            b"not" => Self::Not,
+            b"or" => Self::Or,
+            b"pop" => Self::Pop,
+            b"roll" => Self::Roll,
+            b"round" => Self::Round,
+            b"sin" => Self::Sin,
+            b"sqrt" => Self::Sqrt,
+            b"sub" => Self::Sub,
+            b"true" => Self::True,
+            b"truncate" => Self::Truncate,
+            b"xor" => Self::Xor,
+            _ => return None,
+        })
+    }
+}
+
+/// One step of a calculator program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PsOp {
+    Push(PsValue),
+    Op(PsOperator),
+    If(Vec<Self>),
+    IfElse(Vec<Self>, Vec<Self>),
+}
+
+fn pairs(values: &[f32]) -> Vec<(f32, f32)> {
+    values.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+}
+
+pub(crate) fn numbers(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<Vec<f32>> {
+    let obj = dict.get(key).ok()?;
+    let obj = match obj {
+        Object::Reference(id) => doc.get_object(*id).ok()?,
+        other => other,
+    };
+    obj.as_array()
+        .ok()?
+        .iter()
+        .map(|v| v.as_float().ok())
+        .collect()
+}
+
+/// Map `x` from `[x0, x1]` onto `[y0, y1]`.
+fn interpolate(x: f32, (x0, x1): (f32, f32), (y0, y1): (f32, f32)) -> f32 {
+    if x1 == x0 {
+        y0
+    } else {
+        y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+    }
+}
+
+/// Read `count` big-endian samples of `bits` each.
+fn read_samples(data: &[u8], bits: u32, count: usize) -> Option<Vec<u32>> {
+    if !matches!(bits, 1 | 2 | 4 | 8 | 12 | 16 | 24 | 32) {
+        return None;
+    }
+    // `count` comes from the file; check it against the data before
+    // allocating for it.
+    if count.checked_mul(bits as usize)? > data.len().saturating_mul(8) {
+        return None;
+    }
+    let mut pos = 0usize;
+    let mut samples = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut value = 0u64;
+        for _ in 0..bits {
+            let byte = data.get(pos / 8)?;
+            value = value << 1 | u64::from(byte >> (7 - pos % 8) & 1);
+            pos += 1;
+        }
+        samples.push(value as u32);
+    }
+    Some(samples)
+}
+
+impl PdfFunction {
+    /// Read a function dictionary or stream.
+    pub fn from_object(doc: &Document, obj: &Object) -> PdfResult<Self> {
+        Self::parse(doc, obj, 0)
+    }
+
+    fn parse(doc: &Document, obj: &Object, depth: usize) -> PdfResult<Self> {
+        if depth > MAX_DEPTH {
+            return Err(PdfError::from("Functions are nested too deeply"));
+        }
+        let obj = match obj {
+            Object::Reference(id) => doc
+                .get_object(*id)
+                .map_err(|e| PdfError::from(e.to_string()))?,
+            other => other,
+        };
+        let (dict, stream) = match obj {
+            Object::Dictionary(dict) => (dict, None),
+            Object::Stream(stream) => (&stream.dict, Some(stream)),
+            _ => return Err(PdfError::from("Function is not a dictionary or stream")),
+        };
+        let stream_data = || -> PdfResult<Vec<u8>> {
+            let stream = stream.ok_or_else(|| PdfError::from("Function must be a stream"))?;
+            if stream.dict.has(b"Filter") {
+                stream
+                    .decompressed_content()
+                    .map_err(|e| PdfError::from(format!("Function data is corrupt: {e}")))
+            } else {
+                Ok(stream.content.clone())
+            }
+        };
+        let domain = numbers(doc, dict, b"Domain")
+            .map(|d| pairs(&d))
+            .filter(|d| !d.is_empty())
+            .ok_or_else(|| PdfError::from("Function has no /Domain"))?;
+        let range = numbers(doc, dict, b"Range").map(|r| pairs(&r));
+        let function_type = dict
+            .get(b"FunctionType")
+            .and_then(Object::as_i64)
+            .map_err(|_| PdfError::from("Function has no /FunctionType"))?;
+
+        let kind = match function_type {
+            0 => {
+                let range = range
+                    .as_ref()
+                    .ok_or_else(|| PdfError::from("Sampled function has no /Range"))?;
+                let size: Vec<usize> = numbers(doc, dict, b"Size")
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|s| s as usize)
+                    .collect();
+                if size.len() != domain.len() || size.contains(&0) {
+                    return Err(PdfError::from("Sampled function /Size doesn't match"));
+                }
+                let bits = dict
+                    .get(b"BitsPerSample")
+                    .and_then(Object::as_i64)
+                    .map_err(|_| PdfError::from("Sampled function has no /BitsPerSample"))?;
+                let encode = numbers(doc, dict, b"Encode")
+                    .map(|e| pairs(&e))
+                    .filter(|e| e.len() == size.len())
+                    .unwrap_or_else(|| size.iter().map(|&s| (0.0, (s - 1) as f32)).collect());
+                let decode = numbers(doc, dict, b"Decode")
+                    .map(|d| pairs(&d))
+                    .filter(|d| d.len() == range.len())
+                    .unwrap_or_else(|| range.clone());
+                let count = size
+                    .iter()
+                    .try_fold(range.len(), |n, &s| n.checked_mul(s))
+                    .ok_or_else(|| PdfError::from("Sampled function is too large"))?;
+                let bits = u32::try_from(bits).unwrap_or(0);
+                let raw = read_samples(&stream_data()?, bits, count)
+                    .ok_or_else(|| PdfError::from("Sampled function data is too short"))?;
+                let max = ((1u64 << bits) - 1) as f32;
+                let samples = raw
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &s)| interpolate(s as f32, (0.0, max), decode[i % decode.len()]))
+                    .collect();
+                FunctionKind::Sampled {
+                    size,
+                    encode,
+                    samples,
+                }
+            }
+            2 => {
+                let c0 = numbers(doc, dict, b"C0").unwrap_or_else(|| vec![0.0]);
+                let c1 = numbers(doc, dict, b"C1").unwrap_or_else(|| vec![1.0]);
+                if c0.len() != c1.len() {
+                    return Err(PdfError::from("Function /C0 and /C1 differ in length"));
+                }
+                let n = dict
+                    .get(b"N")
+                    .and_then(Object::as_float)
+                    .map_err(|_| PdfError::from("Exponential function has no /N"))?;
+                FunctionKind::Exponential { c0, c1, n }
+            }
+            3 => {
+                let functions = match dict.get(b"Functions") {
+                    Ok(Object::Reference(id)) => doc.get_object(*id).ok(),
+                    Ok(other) => Some(other),
+                    Err(_) => None,
+                }
+                .and_then(|f| f.as_array().ok())
+                .ok_or_else(|| PdfError::from("Stitching function has no /Functions"))?
+                .iter()
+                .map(|f| Self::parse(doc, f, depth + 1))
+                .collect::<PdfResult<Vec<_>>>()?;
+                let bounds = numbers(doc, dict, b"Bounds").unwrap_or_default();
+                let encode = numbers(doc, dict, b"Encode")
+                    .map(|e| pairs(&e))
+                    .unwrap_or_default();
+                let outputs = functions.first().map(Self::outputs);
+                if functions.is_empty()
+                    || bounds.len() + 1 != functions.len()
+                    || encode.len() != functions.len()
+                    || functions.iter().any(|f| Some(f.outputs()) != outputs)
+                {
+                    return Err(PdfError::from("Stitching function is inconsistent"));
+                }
+                FunctionKind::Stitching {
+                    functions,
+                    bounds,
+                    encode,
+                }
+            }
+            4 => {
+                if range.is_none() {
+                    return Err(PdfError::from("PostScript function has no /Range"));
+                }
+                FunctionKind::PostScript(parse_program(&stream_data()?)?)
+            }
+            t => {
+                return Err(PdfError::from(format!(
+                    "Type {t} functions are not supported"
+                )));
+            }
+        };
+        Ok(Self {
+            domain,
+            range,
+            kind,
+        })
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.domain.len()
+    }
+
+    pub fn outputs(&self) -> usize {
+        match (&self.range, &self.kind) {
+            (Some(range), _) => range.len(),
+            (None, FunctionKind::Exponential { c0, .. }) => c0.len(),
+            (None, FunctionKind::Stitching { functions, .. }) => functions[0].outputs(),
+            // Ranges are required for the other types.
+            (None, _) => 0,
+        }
+    }
+
+    /// Evaluate the function. Missing inputs count as zero; a calculator
+    /// program that fails gives all zeros.
+    pub fn eval(&self, inputs: &[f32]) -> Vec<f32> {
+        let x: Vec<f32> = self
+            .domain
+            .iter()
+            .enumerate()
+            .map(|(i, &(lo, hi))| inputs.get(i).copied().unwrap_or(0.0).clamp(lo, hi))
+            .collect();
+        let mut out: Vec<f32> = match &self.kind {
+            FunctionKind::Sampled {
+                size,
+                encode,
+                samples,
+            } => self.eval_sampled(&x, size, encode, samples),
+            FunctionKind::Exponential { c0, c1, n } => {
+                let t = x[0].powf(*n);
+                c0.iter().zip(c1).map(|(a, b)| a + t * (b - a)).collect()
+            }
+            FunctionKind::Stitching {
+                functions,
+                bounds,
+                encode,
+            } => {
+                let (lo, hi) = self.domain[0];
+                let k = bounds.iter().take_while(|&&b| x[0] >= b).count();
+                let from = if k == 0 { lo } else { bounds[k - 1] };
+                let to = bounds.get(k).copied().unwrap_or(hi);
+                functions[k].eval(&[interpolate(x[0], (from, to), encode[k])])
+            }
+            FunctionKind::PostScript(program) => {
+                let mut stack: Vec<PsValue> = x.iter().map(|&v| PsValue::Real(v.into())).collect();
+                let n = self.outputs();
+                match run(program, &mut stack) {
+                    Some(()) if stack.len() >= n => stack[stack.len() - n..]
+                        .iter()
+                        .map(|v| v.as_real().unwrap_or(0.0) as f32)
+                        .collect(),
+                    _ => vec![0.0; n],
+                }
+            }
+        };
+        if let Some(range) = &self.range {
+            for (v, &(lo, hi)) in out.iter_mut().zip(range) {
+                *v = v.clamp(lo, hi);
+            }
+        }
+        out
+    }
+
+    fn eval_sampled(
+        &self,
+        x: &[f32],
+        size: &[usize],
+        encode: &[(f32, f32)],
+        samples: &[f32],
+    ) -> Vec<f32> {
+        let n = self.outputs();
+        // The lower grid index and the weight of the upper one, per input.
+        let cells: Vec<(usize, f32)> = x
+            .iter()
+            .zip(&self.domain)
+            .zip(encode.iter().zip(size))
+            .map(|((&x, &domain), (&encode, &size))| {
+                let e = interpolate(x, domain, encode).clamp(0.0, (size - 1) as f32);
+                let i = (e.floor() as usize).min(size.saturating_sub(2));
+                (i, if size > 1 { e - i as f32 } else { 0.0 })
+            })
+            .collect();
+        let mut out = vec![0.0; n];
+        for corner in 0..1usize << cells.len() {
+            let mut weight = 1.0;
+            let mut index = 0;
+            let mut stride = 1;
+            for (d, &(i, t)) in cells.iter().enumerate() {
+                let upper = corner >> d & 1 == 1;
+                weight *= if upper { t } else { 1.0 - t };
+                index += (i + usize::from(upper && size[d] > 1)) * stride;
+                stride *= size[d];
+            }
+            if weight == 0.0 {
+                continue;
+            }
+            for (j, o) in out.iter_mut().enumerate() {
+                *o += weight * samples[index * n + j];
+            }
+        }
+        out
+    }
+}
+
+impl PsValue {
+    fn as_real(self) -> Option<f64> {
+        match self {
+            Self::Int(i) => Some(i as f64),
+            Self::Real(r) => Some(r),
+            Self::Bool(_) => None,
+        }
+    }
+
+    const fn as_int(self) -> Option<i64> {
+        match self {
+            Self::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a calculator program, `{ ... }` with nested procedures only as the
+/// operands of `if` and `ifelse`.
+fn parse_program(code: &[u8]) -> PdfResult<Vec<PsOp>> {
+    let mut tokens = PsTokens { code, pos: 0 };
+    if tokens.next() != Some(b"{".as_slice()) {
+        return Err(PdfError::from("PostScript function doesn't start with {"));
+    }
+    let program = parse_procedure(&mut tokens)?;
+    match tokens.next() {
+        None => Ok(program),
+        Some(_) => Err(PdfError::from("PostScript function has trailing data")),
+    }
+}
+
+fn parse_procedure(tokens: &mut PsTokens) -> PdfResult<Vec<PsOp>> {
+    let mut ops = Vec::new();
+    let mut procedures: Vec<Vec<PsOp>> = Vec::new();
+    loop {
+        let token = tokens
+            .next()
+            .ok_or_else(|| PdfError::from("PostScript function is missing a }"))?;
+        if token == b"{" {
+            procedures.push(parse_procedure(tokens)?);
+            continue;
+        }
+        let op = match token {
+            b"}" if procedures.is_empty() => return Ok(ops),
+            b"if" if procedures.len() == 1 => PsOp::If(procedures.remove(0)),
+            b"ifelse" if procedures.len() == 2 => {
+                let otherwise = procedures.remove(1);
+                PsOp::IfElse(procedures.remove(0), otherwise)
+            }
+            _ if !procedures.is_empty() => {
+                return Err(PdfError::from(
+                    "PostScript procedure isn't followed by if or ifelse",
+                ));
+            }
+            _ => parse_operand(token)?,
+        };
+        ops.push(op);
+    }
+}
+
+fn parse_operand(token: &[u8]) -> PdfResult<PsOp> {
+    if let Some(op) = PsOperator::from_name(token) {
+        return Ok(PsOp::Op(op));
+    }
+    let text = std::str::from_utf8(token).unwrap_or_default();
+    if let Ok(i) = text.parse::<i64>() {
+        return Ok(PsOp::Push(PsValue::Int(i)));
+    }
+    if let Ok(r) = text.parse::<f64>() {
+        return Ok(PsOp::Push(PsValue::Real(r)));
+    }
+    Err(PdfError::from(format!(
+        "Unknown PostScript operator {}",
+        String::from_utf8_lossy(token)
+    )))
+}
+
+struct PsTokens<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PsTokens<'a> {
+    fn next(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.code.get(self.pos)? {
+                b'%' => {
+                    while self
+                        .code
+                        .get(self.pos)
+                        .is_some_and(|&c| c != b'\n' && c != b'\r')
+                    {
+                        self.pos += 1;
+                    }
+                }
+                c if c.is_ascii_whitespace() || *c == 0 => self.pos += 1,
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        if matches!(self.code[start], b'{' | b'}') {
+            self.pos += 1;
+        } else {
+            while self
+                .code
+                .get(self.pos)
+                .is_some_and(|&c| !c.is_ascii_whitespace() && !matches!(c, b'{' | b'}' | b'%' | 0))
+            {
+                self.pos += 1;
+            }
+        }
+        Some(&self.code[start..self.pos])
+    }
+}
+
+/// Run `program` on `stack`. `None` means a type error, stack underflow or
+/// overflow, or an undefined result such as division by zero.
+fn run(program: &[PsOp], stack: &mut Vec<PsValue>) -> Option<()> {
+    for op in program {
+        match op {
+            PsOp::Push(v) => stack.push(*v),
+            PsOp::If(then) => match stack.pop()? {
+                PsValue::Bool(true) => run(then, stack)?,
+                PsValue::Bool(false) => {}
+                _ => return None,
+            },
+            PsOp::IfElse(then, otherwise) => match stack.pop()? {
+                PsValue::Bool(true) => run(then, stack)?,
+                PsValue::Bool(false) => run(otherwise, stack)?,
+                _ => return None,
+            },
+            PsOp::Op(op) => apply(*op, stack)?,
+        }
+        if stack.len() > MAX_STACK {
+            return None;
+        }
+    }
+    Some(())
+}
+
+fn apply(op: PsOperator, stack: &mut Vec<PsValue>) -> Option<()> {
+    use PsOperator as O;
+    use PsValue::{Bool, Int, Real};
+
+    let result = match op {
+        O::True => Bool(true),
+        O::False => Bool(false),
+        O::Pop => {
+            stack.pop()?;
+            return Some(());
+        }
+        O::Dup => *stack.last()?,
+        O::Exch => {
+            let len = stack.len();
+            if len < 2 {
+                return None;
+            }
+            stack.swap(len - 1, len - 2);
+            return Some(());
+        }
+        O::Copy => {
+            let n = usize::try_from(stack.pop()?.as_int()?).ok()?;
+            let start = stack.len().checked_sub(n)?;
+            stack.extend_from_within(start..);
+            return Some(());
+        }
+        O::Index => {
+            let n = usize::try_from(stack.pop()?.as_int()?).ok()?;
+            stack[stack.len().checked_sub(n.checked_add(1)?)?]
+        }
+        O::Roll => {
+            let j = stack.pop()?.as_int()?;
+            let n = usize::try_from(stack.pop()?.as_int()?).ok()?;
+            let start = stack.len().checked_sub(n)?;
+            if n > 0 {
+                let shift = usize::try_from(j.rem_euclid(n as i64)).ok()?;
+                stack[start..].rotate_right(shift);
+            }
+            return Some(());
+        }
+        O::Abs | O::Neg | O::Ceiling | O::Floor | O::Round | O::Truncate => {
+            match stack.pop()? {
+                Int(i) => match op {
+                    O::Abs => i.checked_abs().map_or_else(|| Real((i as f64).abs()), Int),
+                    O::Neg => i.checked_neg().map_or_else(|| Real(-(i as f64)), Int),
+                    _ => Int(i),
+                },
+                Real(r) => Real(match op {
+                    O::Abs => r.abs(),
+                    O::Neg => -r,
+                    O::Ceiling => r.ceil(),
+                    O::Floor => r.floor(),
+                    // PostScript rounds halves up, not away from zero.
+                    O::Round => (r + 0.5).floor(),
+                    _ => r.trunc(),
+                }),
+                Bool(_) => return None,
+            }
+        }
+        O::Sqrt | O::Sin | O::Cos | O::Ln | O::Log | O::Cvr => {
+            let x = stack.pop()?.as_real()?;
+            Real(match op {
+                O::Sqrt if x >= 0.0 => x.sqrt(),
+                O::Sin => x.to_radians().sin(),
+                O::Cos => x.to_radians().cos(),
+                O::Ln if x > 0.0 => x.ln(),
+                O::Log if x > 0.0 => x.log10(),
+                O::Cvr => x,
+                _ => return None,
+            })
+        }
+        O::Cvi => {
+            let x = stack.pop()?.as_real()?.trunc();
+            if !(i64::MIN as f64..=i64::MAX as f64).contains(&x) {
+                return None;
+            }
+            Int(x as i64)
+        }
+        O::Not => match stack.pop()? {
+            Bool(b) => Bool(!b),
+            Int(i) => Int(!i),
+            Real(_) => return None,
+        },
+        _ => {
+            let b = stack.pop()?;
+            let a = stack.pop()?;
+            binary(op, a, b)?
+        }
+    };
+    stack.push(result);
+    Some(())
+}
+
+fn binary(op: PsOperator, a: PsValue, b: PsValue) -> Option<PsValue> {
+    use PsOperator as O;
+    use PsValue::{Bool, Int, Real};
+
+    Some(match (op, a, b) {
+        (O::Add, Int(x), Int(y)) if x.checked_add(y).is_some() => Int(x + y),
+        (O::Sub, Int(x), Int(y)) if x.checked_sub(y).is_some() => Int(x - y),
+        (O::Mul, Int(x), Int(y)) if x.checked_mul(y).is_some() => Int(x * y),
+        (O::Add | O::Sub | O::Mul | O::Div | O::Exp | O::Atan, _, _) => {
+            let (x, y) = (a.as_real()?, b.as_real()?);
+            Real(match op {
+                O::Add => x + y,
+                O::Sub => x - y,
+                O::Mul => x * y,
+                O::Div if y != 0.0 => x / y,
+                O::Exp => x.powf(y),
+                O::Atan if x != 0.0 || y != 0.0 => x.atan2(y).to_degrees().rem_euclid(360.0),
+                _ => return None,
+            })
+        }
+        (O::Idiv, Int(x), Int(y)) => Int(x.checked_div(y)?),
+        (O::Mod, Int(x), Int(y)) => Int(x.checked_rem(y)?),
+        (O::Bitshift, Int(x), Int(y)) => {
+            // Shifting by more than the width leaves nothing.
+            let shift = u32::try_from(y.unsigned_abs()).ok();
+            Int(if y >= 0 {
+                shift.and_then(|n| x.checked_shl(n))
+            } else {
+                shift.and_then(|n| x.checked_shr(n))
+            }
+            .unwrap_or(0))
+        }
+        (O::And, Bool(x), Bool(y)) => Bool(x && y),
+        (O::Or, Bool(x), Bool(y)) => Bool(x || y),
+        (O::Xor, Bool(x), Bool(y)) => Bool(x ^ y),
+        (O::And, Int(x), Int(y)) => Int(x & y),
+        (O::Or, Int(x), Int(y)) => Int(x | y),
+        (O::Xor, Int(x), Int(y)) => Int(x ^ y),
+        (O::Eq | O::Ne, Bool(x), Bool(y)) => Bool((x == y) == (op == O::Eq)),
+        (O::Eq | O::Ne | O::Gt | O::Ge | O::Lt | O::Le, _, _) => {
+            let (x, y) = (a.as_real()?, b.as_real()?);
+            Bool(match op {
+                O::Eq => x == y,
+                O::Ne => x != y,
+                O::Gt => x > y,
+                O::Ge => x >= y,
+                O::Lt => x < y,
+                _ => x <= y,
+            })
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::Stream;
+
+    fn array(values: &[f32]) -> Object {
+        Object::Array(values.iter().map(|&v| Object::Real(v)).collect())
+    }
+
+    fn exponential(c0: &[f32], c1: &[f32], n: f32) -> Object {
+        Object::Dictionary(Dictionary::from_iter(vec![
+            ("FunctionType", Object::Integer(2)),
+            ("Domain", array(&[0.0, 1.0])),
+            ("C0", array(c0)),
+            ("C1", array(c1)),
+            ("N", Object::Real(n)),
+        ]))
+    }
+
+    fn postscript(domain: &[f32], range: &[f32], code: &str) -> PdfFunction {
+        let stream = Stream::new(
+            Dictionary::from_iter(vec![
+                ("FunctionType", Object::Integer(4)),
+                ("Domain", array(domain)),
+                ("Range", array(range)),
+            ]),
+            code.as_bytes().to_vec(),
+        );
+        PdfFunction::from_object(&Document::with_version("1.7"), &Object::Stream(stream)).unwrap()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_exponential_function() {
+        let doc = Document::with_version("1.7");
+        let f =
+            PdfFunction::from_object(&doc, &exponential(&[0.0, 1.0], &[1.0, 0.0], 2.0)).unwrap();
+        assert_eq!(f.outputs(), 2);
+        assert_eq!(f.eval(&[0.5]), [0.25, 0.75]);
+        // Inputs outside the domain are clipped.
+        assert_eq!(f.eval(&[2.0]), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_sampled_function() {
+        let doc = Document::with_version("1.7");
+        let sampled = |size: &[f32], bits: i64, range: &[f32], data: Vec<u8>| {
+            let stream = Stream::new(
+                Dictionary::from_iter(vec![
+                    ("FunctionType", Object::Integer(0)),
+                    ("Domain", array(&[0.0, 1.0].repeat(size.len()))),
+                    ("Range", array(range)),
+                    ("Size", array(size)),
+                    ("BitsPerSample", Object::Integer(bits)),
+                ]),
+                data,
+            );
+            PdfFunction::from_object(&doc, &Object::Stream(stream)).unwrap()
+        };
+
+        // A tent: 0 at the ends, 1 in the middle.
+        let tent = sampled(&[3.0], 8, &[0.0, 1.0], vec![0, 255, 0]);
+        assert_close(&tent.eval(&[0.25]), &[0.5]);
+        assert_close(&tent.eval(&[0.5]), &[1.0]);
+        assert_close(&tent.eval(&[1.0]), &[0.0]);
+
+        // Two inputs, 4-bit samples decoded into 0..=30: the corners of a
+        // 2x2 grid are 0, 15, 15 and 0 units, first input fastest.
+        let grid = sampled(&[2.0, 2.0], 4, &[0.0, 30.0], vec![0x0F, 0xF0]);
+        assert_close(&grid.eval(&[1.0, 0.0]), &[30.0]);
+        assert_close(&grid.eval(&[0.0, 1.0]), &[30.0]);
+        assert_close(&grid.eval(&[0.5, 0.5]), &[15.0]);
+        assert_close(&grid.eval(&[0.25, 0.0]), &[7.5]);
+
+        // A /Size far beyond the data is refused without allocating for it.
+        let huge = Stream::new(
+            Dictionary::from_iter(vec![
+                ("FunctionType", Object::Integer(0)),
+                ("Domain", array(&[0.0, 1.0, 0.0, 1.0])),
+                ("Range", array(&[0.0, 1.0])),
+                ("Size", array(&[100_000.0, 100_000.0])),
+                ("BitsPerSample", Object::Integer(8)),
+            ]),
+            vec![0; 4],
+        );
+        assert!(PdfFunction::from_object(&doc, &Object::Stream(huge)).is_err());
+    }
+
+    #[test]
+    fn test_stitching_function() {
+        let doc = Document::with_version("1.7");
+        let dict = Dictionary::from_iter(vec![
+            ("FunctionType", Object::Integer(3)),
+            ("Domain", array(&[0.0, 1.0])),
+            (
+                "Functions",
+                Object::Array(vec![
+                    exponential(&[0.0], &[1.0], 1.0),
+                    exponential(&[1.0], &[0.0], 1.0),
+                ]),
+            ),
+            ("Bounds", array(&[0.5])),
+            ("Encode", array(&[0.0, 1.0, 0.0, 1.0])),
+        ]);
+        let f = PdfFunction::from_object(&doc, &Object::Dictionary(dict)).unwrap();
+        assert_close(&f.eval(&[0.25]), &[0.5]);
+        // Each bound belongs to the subdomain above it.
+        assert_close(&f.eval(&[0.5]), &[1.0]);
+        assert_close(&f.eval(&[0.75]), &[0.5]);
+        assert_close(&f.eval(&[1.0]), &[0.0]);
+    }
+
+    #[test]
+    fn test_postscript_function() {
+        // (a, b) -> (a + b, a × b), with the product clipped to 5.
+        let f = postscript(
+            &[0.0, 10.0, 0.0, 10.0],
+            &[0.0, 20.0, 0.0, 5.0],
+            "{ 2 copy add 3 1 roll mul }",
+        );
+        assert_close(&f.eval(&[2.0, 2.0]), &[4.0, 4.0]);
+        assert_close(&f.eval(&[2.0, 3.0]), &[5.0, 5.0]);
+
+        let branch = postscript(
+            &[0.0, 1.0],
+            &[0.0, 1.0],
+            "{ dup 0.5 gt { 1 exch sub } { 2 mul } ifelse } % a tent",
+        );
+        assert_close(&branch.eval(&[0.25]), &[0.5]);
+        assert_close(&branch.eval(&[0.75]), &[0.25]);
+
+        let trig = postscript(&[0.0, 1.0], &[-1.0, 1.0], "{ 360 mul sin }");
+        assert_close(&trig.eval(&[0.25]), &[1.0]);
+        let angle = postscript(&[-1.0, 1.0], &[0.0, 360.0], "{ 1 atan }");
+        assert_close(&angle.eval(&[-1.0]), &[315.0]);
+        let ints = postscript(
+            &[0.0, 100.0],
+            &[0.0, 100.0],
+            "{ cvi 7 idiv 1 bitshift 3 or }",
+        );
+        assert_close(&ints.eval(&[30.0]), &[11.0]);
+        let far = postscript(
+            &[0.0, 100.0],
+            &[-100.0, 100.0],
+            "{ cvi -9223372036854775808 bitshift 5 add }",
+        );
+        assert_close(&far.eval(&[30.0]), &[5.0]);
+
+        // Errors at run time give zeros; bad programs don't parse.
+        let divide = postscript(&[0.0, 1.0], &[-1.0, 1.0], "{ 1 exch div 0.5 mul }");
+        assert_close(&divide.eval(&[1.0]), &[0.5]);
+        assert_close(&divide.eval(&[0.0]), &[0.0]);
+        assert!(parse_program(b"{ 1 frobnicate }").is_err());
+        assert!(parse_program(b"{ { 1 } 2 }").is_err());
+        assert!(parse_program(b"{ 1 2 add").is_err());
+    }
+}
@@ -0,0 +1,806 @@
+//! Minimal HTML-to-PDF conversion on top of [`crate::flow`].
+//!
+//! Supports headings, paragraphs, divs, ordered and unordered lists, bold and
+//! italic runs, line breaks and raster images, styled by tag selectors in
+//! `<style>` blocks and inline `style` attributes (font-size, color,
+//! font-weight, font-style and vertical margins). Anything else is rendered
+//! as plain text.
+
+use crate::flow::{BlockStyle, DocumentBuilder, Flowable, FontFace, PageSize, Span, Story};
+use crate::models::{PdfError, PdfResult};
+use std::collections::HashMap;
+use std::path::Path;
+
+pub(crate) const BASE_FONT_SIZE: f32 = 11.0;
+const LIST_INDENT: f32 = 18.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Text(String),
+    Open {
+        name: String,
+        attrs: Vec<(String, String)>,
+        /// Written as `<name/>`, so no `Close` follows.
+        self_closing: bool,
+    },
+    Close(String),
+    /// Raw contents of `<style>`.
+    Style(String),
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        // Entities are short; only look for the `;` in the next few bytes.
+        let Some(semi) = rest.as_bytes().iter().take(10).position(|&b| b == b';') else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            "bull" => Some('•'),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "hellip" => Some('…'),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        if let Some(c) = decoded {
+            out.push(c);
+            rest = &rest[semi + 1..];
+        } else {
+            out.push('&');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn parse_attrs(mut s: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    loop {
+        s = s.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if s.is_empty() {
+            break;
+        }
+        let name_end = s
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(s.len());
+        let name = s[..name_end].to_ascii_lowercase();
+        s = s[name_end..].trim_start();
+        let value = if let Some(after) = s.strip_prefix('=') {
+            let after = after.trim_start();
+            if let Some(quote) = after.chars().next().filter(|c| *c == '"' || *c == '\'') {
+                let body = &after[1..];
+                let end = body.find(quote).unwrap_or(body.len());
+                s = body.get(end + 1..).unwrap_or("");
+                &body[..end]
+            } else {
+                let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                s = &after[end..];
+                &after[..end]
+            }
+        } else {
+            ""
+        };
+        attrs.push((name, decode_entities(value)));
+    }
+    attrs
+}
+
+/// Split markup into tags and text. Comments, doctypes and the contents of
+/// `<head>`-only elements such as `<script>` and `<title>` are dropped.
+pub(crate) fn tokenize(html: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            tokens.push(Token::Text(decode_entities(rest)));
+            break;
+        };
+        if lt > 0 {
+            tokens.push(Token::Text(decode_entities(&rest[..lt])));
+        }
+        rest = &rest[lt..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(gt) = rest.find('>') else {
+            tokens.push(Token::Text(decode_entities(rest)));
+            break;
+        };
+        let inner = &rest[1..gt];
+        rest = &rest[gt + 1..];
+        if inner.starts_with('!') || inner.starts_with('?') {
+            continue;
+        }
+        if let Some(name) = inner.strip_prefix('/') {
+            tokens.push(Token::Close(name.trim().to_ascii_lowercase()));
+            continue;
+        }
+        let name_end = inner
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(inner.len());
+        let name = inner[..name_end].to_ascii_lowercase();
+        if matches!(name.as_str(), "style" | "script" | "title") {
+            let close = format!("</{name}");
+            let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+            if name == "style" {
+                tokens.push(Token::Style(rest[..end].to_string()));
+            }
+            rest = &rest[end..];
+            rest = rest.find('>').map_or("", |gt| &rest[gt + 1..]);
+            continue;
+        }
+        tokens.push(Token::Open {
+            attrs: parse_attrs(&inner[name_end..]),
+            name,
+            self_closing: inner.ends_with('/'),
+        });
+    }
+    tokens
+}
+
+/// The opening tags in `markup` with their attributes, in document order.
+/// Enough to read simple XML such as EPUB package files.
+pub(crate) fn start_tags(markup: &str) -> Vec<(String, Vec<(String, String)>)> {
+    tokenize(markup)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Open { name, attrs, .. } => Some((name, attrs)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The CSS properties this converter understands. `None` means "inherit" for
+/// inherited properties and "tag default" for margins.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct CssStyle {
+    font_size: Option<f32>,
+    color: Option<(f32, f32, f32)>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    margin_top: Option<f32>,
+    margin_bottom: Option<f32>,
+}
+
+impl CssStyle {
+    fn merge(&mut self, other: &Self) {
+        self.font_size = other.font_size.or(self.font_size);
+        self.color = other.color.or(self.color);
+        self.bold = other.bold.or(self.bold);
+        self.italic = other.italic.or(self.italic);
+        self.margin_top = other.margin_top.or(self.margin_top);
+        self.margin_bottom = other.margin_bottom.or(self.margin_bottom);
+    }
+}
+
+/// Convert a CSS length to points; `em` is relative to `parent_size`.
+fn parse_length(value: &str, parent_size: f32) -> Option<f32> {
+    let value = value.trim();
+    let (number, factor) = if let Some(n) = value.strip_suffix("pt") {
+        (n, 1.0)
+    } else if let Some(n) = value.strip_suffix("px") {
+        (n, 0.75)
+    } else if let Some(n) = value.strip_suffix("rem") {
+        (n, BASE_FONT_SIZE)
+    } else if let Some(n) = value.strip_suffix("em") {
+        (n, parent_size)
+    } else if let Some(n) = value.strip_suffix('%') {
+        (n, parent_size / 100.0)
+    } else {
+        (value, 0.75)
+    };
+    number
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .map(|n| n * factor)
+}
+
+fn parse_color(value: &str) -> Option<(f32, f32, f32)> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        let (r, g, b) = match digits.as_slice() {
+            [r, g, b] => (r * 17, g * 17, b * 17),
+            [r1, r2, g1, g2, b1, b2] => (r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2),
+            _ => return None,
+        };
+        return Some((
+            f32::from(r) / 255.0,
+            f32::from(g) / 255.0,
+            f32::from(b) / 255.0,
+        ));
+    }
+    if let Some(args) = value.strip_prefix("rgb(").and_then(|v| v.strip_suffix(')')) {
+        let parts: Vec<f32> = args
+            .split(',')
+            .map(|p| p.trim().parse::<f32>().ok())
+            .collect::<Option<_>>()?;
+        if let [r, g, b] = parts.as_slice() {
+            return Some((
+                (r / 255.0).clamp(0.0, 1.0),
+                (g / 255.0).clamp(0.0, 1.0),
+                (b / 255.0).clamp(0.0, 1.0),
+            ));
+        }
+        return None;
+    }
+    match value.as_str() {
+        "black" => Some((0.0, 0.0, 0.0)),
+        "white" => Some((1.0, 1.0, 1.0)),
+        "red" => Some((1.0, 0.0, 0.0)),
+        "green" => Some((0.0, 0.5, 0.0)),
+        "blue" => Some((0.0, 0.0, 1.0)),
+        "gray" | "grey" => Some((0.5, 0.5, 0.5)),
+        "navy" => Some((0.0, 0.0, 0.5)),
+        "maroon" => Some((0.5, 0.0, 0.0)),
+        "orange" => Some((1.0, 0.65, 0.0)),
+        "purple" => Some((0.5, 0.0, 0.5)),
+        _ => None,
+    }
+}
+
+fn parse_declarations(decls: &str, parent_size: f32) -> CssStyle {
+    let mut style = CssStyle::default();
+    for decl in decls.split(';') {
+        let Some((prop, value)) = decl.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_end_matches("!important").trim();
+        match prop.trim().to_ascii_lowercase().as_str() {
+            "font-size" => style.font_size = parse_length(value, parent_size),
+            "color" => style.color = parse_color(value),
+            "font-weight" => {
+                style.bold = Some(
+                    value == "bold"
+                        || value == "bolder"
+                        || value.parse::<u16>().is_ok_and(|w| w >= 600),
+                );
+            }
+            "font-style" => style.italic = Some(value == "italic" || value == "oblique"),
+            "margin-top" => style.margin_top = parse_length(value, parent_size),
+            "margin-bottom" => style.margin_bottom = parse_length(value, parent_size),
+            "margin" => {
+                let parts: Vec<&str> = value.split_whitespace().collect();
+                let top = parts.first().and_then(|v| parse_length(v, parent_size));
+                let bottom = match parts.len() {
+                    1 | 2 => top,
+                    _ => parts.get(2).and_then(|v| parse_length(v, parent_size)),
+                };
+                style.margin_top = top;
+                style.margin_bottom = bottom;
+            }
+            _ => {}
+        }
+    }
+    style
+}
+
+/// Parse `tag, tag { ... }` rules. Only plain element selectors are kept;
+/// class, id and descendant selectors are ignored.
+fn parse_stylesheet(css: &str, sheet: &mut HashMap<String, String>) {
+    let mut rest = css;
+    while let Some(open) = rest.find('{') {
+        let selectors = &rest[..open];
+        let Some(close) = rest[open..].find('}') else {
+            break;
+        };
+        let body = &rest[open + 1..open + close];
+        for selector in selectors.split(',') {
+            let selector = selector.trim().to_ascii_lowercase();
+            if !selector.is_empty() && selector.chars().all(|c| c.is_ascii_alphanumeric()) {
+                let entry = sheet.entry(selector).or_default();
+                entry.push_str(body);
+                entry.push(';');
+            }
+        }
+        rest = &rest[open + close + 1..];
+    }
+}
+
+fn tag_defaults(tag: &str) -> CssStyle {
+    let heading = |size: f32, top: f32, bottom: f32| CssStyle {
+        font_size: Some(size),
+        bold: Some(true),
+        margin_top: Some(top),
+        margin_bottom: Some(bottom),
+        ..CssStyle::default()
+    };
+    match tag {
+        "h1" => heading(24.0, 16.0, 10.0),
+        "h2" => heading(18.0, 14.0, 8.0),
+        "h3" => heading(14.0, 12.0, 6.0),
+        "h4" => heading(12.0, 10.0, 6.0),
+        "h5" => heading(11.0, 8.0, 4.0),
+        "h6" => heading(10.0, 8.0, 4.0),
+        "p" | "ul" | "ol" | "blockquote" => CssStyle {
+            margin_bottom: Some(8.0),
+            ..CssStyle::default()
+        },
+        "li" => CssStyle {
+            margin_bottom: Some(2.0),
+            ..CssStyle::default()
+        },
+        "b" | "strong" => CssStyle {
+            bold: Some(true),
+            ..CssStyle::default()
+        },
+        "i" | "em" | "cite" => CssStyle {
+            italic: Some(true),
+            ..CssStyle::default()
+        },
+        _ => CssStyle::default(),
+    }
+}
+
+fn is_block(tag: &str) -> bool {
+    matches!(
+        tag,
+        "p" | "div"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "ul"
+            | "ol"
+            | "li"
+            | "blockquote"
+            | "section"
+            | "article"
+            | "header"
+            | "footer"
+            | "body"
+            | "html"
+    )
+}
+
+/// Inherited text state for one open element.
+#[derive(Debug, Clone)]
+struct Context {
+    tag: String,
+    size: f32,
+    color: (f32, f32, f32),
+    bold: bool,
+    italic: bool,
+    margin_bottom: f32,
+}
+
+struct Converter<'a> {
+    base_dir: Option<&'a Path>,
+    sheet: HashMap<String, String>,
+    flowables: Vec<Flowable>,
+    stack: Vec<Context>,
+    spans: Vec<Span>,
+    block: BlockStyle,
+    bullet: Option<String>,
+    /// One entry per open list: `None` for `<ul>`, the next number for `<ol>`.
+    lists: Vec<Option<usize>>,
+}
+
+impl<'a> Converter<'a> {
+    fn new(base_dir: Option<&'a Path>) -> Self {
+        Self {
+            base_dir,
+            sheet: HashMap::new(),
+            flowables: Vec::new(),
+            stack: vec![Context {
+                tag: String::new(),
+                size: BASE_FONT_SIZE,
+                color: (0.0, 0.0, 0.0),
+                bold: false,
+                italic: false,
+                margin_bottom: 0.0,
+            }],
+            spans: Vec::new(),
+            block: BlockStyle::default(),
+            bullet: None,
+            lists: Vec::new(),
+        }
+    }
+
+    fn current(&self) -> &Context {
+        self.stack.last().expect("root context is never popped")
+    }
+
+    fn indent(&self) -> f32 {
+        self.lists.len() as f32 * LIST_INDENT
+    }
+
+    /// Tag defaults, then stylesheet rules, then the inline `style` attribute.
+    fn resolve_style(&self, tag: &str, attrs: &[(String, String)]) -> CssStyle {
+        let parent_size = self.current().size;
+        let mut style = tag_defaults(tag);
+        if let Some(rules) = self.sheet.get(tag) {
+            style.merge(&parse_declarations(rules, parent_size));
+        }
+        if let Some((_, inline)) = attrs.iter().find(|(k, _)| k == "style") {
+            style.merge(&parse_declarations(inline, parent_size));
+        }
+        style
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let ctx = self.current();
+        self.spans.push(Span {
+            text: text.to_string(),
+            face: FontFace::new(ctx.bold, ctx.italic),
+            size: ctx.size,
+            color: ctx.color,
+        });
+    }
+
+    /// Emit the pending paragraph, if it has any visible text.
+    fn flush(&mut self) {
+        let spans = std::mem::take(&mut self.spans);
+        if spans.iter().any(|s| !s.text.trim().is_empty()) {
+            self.flowables.push(Flowable::Paragraph {
+                spans,
+                style: self.block,
+                bullet: self.bullet.take(),
+            });
+            self.block = BlockStyle {
+                indent: self.indent(),
+                ..BlockStyle::default()
+            };
+        }
+    }
+
+    fn open(&mut self, name: &str, attrs: &[(String, String)]) {
+        match name {
+            "br" => return self.push_text("\n"),
+            "hr" => {
+                self.flush();
+                return self.flowables.push(Flowable::Spacer(12.0));
+            }
+            "img" => return self.image(attrs),
+            "meta" | "link" | "input" | "wbr" => return,
+            _ => {}
+        }
+
+        let style = self.resolve_style(name, attrs);
+        let parent = self.current().clone();
+        if is_block(name) {
+            self.flush();
+            let margin_top = style.margin_top.unwrap_or(0.0);
+            // Adjacent vertical margins collapse to the larger one.
+            self.block.space_before = self.block.space_before.max(margin_top);
+            match name {
+                "ul" => self.lists.push(None),
+                "ol" => self.lists.push(Some(1)),
+                "li" => {
+                    self.bullet = Some(match self.lists.last_mut() {
+                        Some(Some(n)) => {
+                            *n += 1;
+                            format!("{}.", *n - 1)
+                        }
+                        _ => "•".to_string(),
+                    });
+                }
+                _ => {}
+            }
+            self.block.indent = self.indent();
+        }
+        self.stack.push(Context {
+            tag: name.to_string(),
+            size: style.font_size.unwrap_or(parent.size).max(1.0),
+            color: style.color.unwrap_or(parent.color),
+            bold: style.bold.unwrap_or(parent.bold),
+            italic: style.italic.unwrap_or(parent.italic),
+            margin_bottom: style.margin_bottom.unwrap_or(0.0),
+        });
+    }
+
+    fn close(&mut self, name: &str) {
+        // Tolerate unclosed children by popping up to the matching element.
+        let Some(pos) = self.stack.iter().rposition(|c| c.tag == name) else {
+            return;
+        };
+        if pos == 0 {
+            return;
+        }
+        let popped: Vec<Context> = self.stack.drain(pos..).collect();
+        if popped.iter().any(|c| is_block(&c.tag)) {
+            self.flush();
+            for ctx in popped.iter().rev() {
+                if matches!(ctx.tag.as_str(), "ul" | "ol") {
+                    self.lists.pop();
+                }
+            }
+            let margin = popped[0].margin_bottom;
+            match self.flowables.last_mut() {
+                Some(Flowable::Paragraph { style, .. }) => {
+                    style.space_after = style.space_after.max(margin);
+                }
+                _ if margin > 0.0 => self.block.space_before = self.block.space_before.max(margin),
+                _ => {}
+            }
+            self.block.indent = self.indent();
+        }
+    }
+
+    fn image(&mut self, attrs: &[(String, String)]) {
+        let attr = |key: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        let src = attr("src").unwrap_or_default();
+        let path = match self.base_dir {
+            Some(dir) => dir.join(src),
+            None => Path::new(src).to_path_buf(),
+        };
+        match image::open(&path) {
+            Ok(img) => {
+                self.flush();
+                let rgb = img.to_rgb8();
+                self.flowables.push(Flowable::Image {
+                    width: rgb.width(),
+                    height: rgb.height(),
+                    rgb: rgb.into_raw(),
+                    style: BlockStyle {
+                        space_before: 4.0,
+                        space_after: 4.0,
+                        indent: self.indent(),
+                    },
+                });
+            }
+            Err(e) => {
+                tracing::warn!("Skipping image {}: {e}", path.display());
+                if let Some(alt) = attr("alt").filter(|a| !a.is_empty()) {
+                    let alt = format!("[{alt}]");
+                    self.push_text(&alt);
+                }
+            }
+        }
+    }
+
+    fn run(mut self, tokens: Vec<Token>) -> Vec<Flowable> {
+        for token in tokens {
+            match token {
+                Token::Text(text) => {
+                    // Source line breaks are ordinary whitespace in HTML.
+                    let text = text.replace(['\n', '\r', '\t'], " ");
+                    self.push_text(&text);
+                }
+                Token::Open { name, attrs, .. } => self.open(&name, &attrs),
+                Token::Close(name) => self.close(&name),
+                Token::Style(css) => parse_stylesheet(&css, &mut self.sheet),
+            }
+        }
+        self.flush();
+        self.flowables
+    }
+}
+
+/// Lay out `html` as flowables. Relative image paths resolve against
+/// `base_dir`.
+pub fn html_to_flowables(html: &str, base_dir: Option<&Path>) -> Vec<Flowable> {
+    Converter::new(base_dir).run(tokenize(html))
+}
+
+/// Render `html` to a new PDF at `output_path`.
+pub fn html_to_pdf(
+    html: &str,
+    base_dir: Option<&Path>,
+    page: PageSize,
+    output_path: &str,
+) -> PdfResult<String> {
+    let mut builder = DocumentBuilder::new(page);
+    for flowable in html_to_flowables(html, base_dir) {
+        builder.push(flowable);
+    }
+    let mut doc = builder.build()?;
+    doc.compress();
+    doc.save(output_path)
+        .map_err(|e| PdfError::IoError(e.to_string()))?;
+    Ok(output_path.to_string())
+}
+
+/// A [`Story`] of `html` styled by the stylesheet `css` ahead of its own
+/// `<style>` blocks, with body text of `em` points.
+pub fn html_story(html: &str, css: &str, em: f32, base_dir: Option<&Path>) -> PdfResult<Story> {
+    if !em.is_finite() || em <= 0.0 {
+        return Err(PdfError::from(format!("Invalid font size {em}")));
+    }
+    let mut tokens = vec![Token::Style(css.to_string())];
+    tokens.extend(tokenize(html));
+    let mut flowables = Converter::new(base_dir).run(tokens);
+    for flowable in &mut flowables {
+        flowable.scale(em / BASE_FONT_SIZE);
+    }
+    Ok(Story::new(flowables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stext::Rect;
+
+    #[test]
+    fn test_decode_entities_next_to_multibyte_text() {
+        assert_eq!(decode_entities("a &amp; b &#x263A; &nope"), "a & b ☺ &nope");
+        // The `;` search window ends inside a multi-byte character here.
+        assert_eq!(decode_entities("x &ééééé; y"), "x &ééééé; y");
+        assert_eq!(decode_entities("Ω&mdash;Ω"), "Ω—Ω");
+    }
+
+    fn paragraphs(flowables: &[Flowable]) -> Vec<(&[Span], BlockStyle, Option<&str>)> {
+        flowables
+            .iter()
+            .filter_map(|f| match f {
+                Flowable::Paragraph {
+                    spans,
+                    style,
+                    bullet,
+                } => Some((spans.as_slice(), *style, bullet.as_deref())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_styles_and_inline_runs() {
+        let html = r#"<html><head><title>Ignored</title>
+            <style>h1 { font-size: 20pt; color: #ff0000 } p { margin: 4pt 0 12pt }</style>
+            </head><body>
+            <h1>Title &amp; more</h1>
+            <p style="color: blue">Plain <b>bold <i>both</i></b> <em>it</em></p>
+            </body></html>"#;
+        let flowables = html_to_flowables(html, None);
+        let paras = paragraphs(&flowables);
+        assert_eq!(paras.len(), 2);
+
+        let (title, _, _) = paras[0];
+        assert_eq!(title[0].text, "Title & more");
+        assert_eq!(title[0].size, 20.0);
+        assert_eq!(title[0].face, FontFace::Bold);
+        assert_eq!(title[0].color, (1.0, 0.0, 0.0));
+
+        let (body, style, _) = paras[1];
+        assert_eq!(style.space_before, 4.0);
+        assert_eq!(style.space_after, 12.0);
+        let faces: Vec<(&str, FontFace)> = body
+            .iter()
+            .filter(|s| !s.text.trim().is_empty())
+            .map(|s| (s.text.trim(), s.face))
+            .collect();
+        assert_eq!(
+            faces,
+            vec![
+                ("Plain", FontFace::Regular),
+                ("bold", FontFace::Bold),
+                ("both", FontFace::BoldItalic),
+                ("it", FontFace::Italic),
+            ]
+        );
+        assert!(body.iter().all(|s| s.color == (0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn test_lists_get_bullets_and_indent() {
+        let html = "<ul><li>one</li><li>two<ol><li>a</li><li>b</li></ol></li></ul><p>after";
+        let flowables = html_to_flowables(html, None);
+        let bullets: Vec<(Option<&str>, f32)> = paragraphs(&flowables)
+            .iter()
+            .map(|(_, style, bullet)| (*bullet, style.indent))
+            .collect();
+        assert_eq!(
+            bullets,
+            vec![
+                (Some("•"), 18.0),
+                (Some("•"), 18.0),
+                (Some("1."), 36.0),
+                (Some("2."), 36.0),
+                (None, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multi_paragraph_document_spans_two_pages() {
+        let words = vec!["lorem"; 60].join(" ");
+        let mut html = String::from("<h1>Report</h1>");
+        for _ in 0..15 {
+            html.push_str(&format!("<p>{words}</p>\n"));
+        }
+        let out = tempfile::Builder::new()
+            .suffix(".pdf")
+            .tempfile()
+            .unwrap()
+            .into_temp_path();
+        let out = out.to_string_lossy().to_string();
+        html_to_pdf(&html, None, PageSize::LETTER, &out).unwrap();
+
+        let doc = lopdf::Document::load(&out).unwrap();
+        // 15 words per 468pt line, so each paragraph is four 13.2pt lines.
+        assert_eq!(doc.get_pages().len(), 2);
+    }
+
+    #[test]
+    fn test_story_overflows_into_next_area() {
+        let paragraph = |word: &str| format!("<p>{}</p>", format!("{word} ").repeat(30));
+        let html = [paragraph("alpha"), paragraph("beta"), paragraph("gamma")].concat();
+        let mut story = html_story(&html, "p { margin: 0 }", 11.0, None).unwrap();
+        let upper = Rect {
+            x0: 72.0,
+            y0: 500.0,
+            x1: 272.0,
+            y1: 600.0,
+        };
+        let lower = Rect {
+            y0: 200.0,
+            y1: 400.0,
+            ..upper
+        };
+
+        let first = story.place(upper);
+        assert!(first.more);
+        assert!(first.filled.y0 >= upper.y0 && first.filled.height() > 80.0);
+        story.draw().unwrap();
+        let second = story.place(lower);
+        assert!(!second.more);
+        story.draw().unwrap();
+        let doc = story.finish(PageSize::LETTER);
+        assert_eq!(doc.get_pages().len(), 1);
+
+        let page_id = doc.get_pages()[&1];
+        let content = lopdf::content::Content::decode(&doc.get_page_content(page_id)).unwrap();
+        let mut baseline = 0.0;
+        let mut shown: Vec<(f32, String)> = Vec::new();
+        for op in &content.operations {
+            match op.operator.as_str() {
+                "Td" => baseline = op.operands[1].as_float().unwrap(),
+                "Tj" => shown.push((
+                    baseline,
+                    String::from_utf8_lossy(op.operands[0].as_str().unwrap()).into_owned(),
+                )),
+                _ => {}
+            }
+        }
+        let within = |word: &str, area: Rect| {
+            shown
+                .iter()
+                .filter(|(_, text)| text.contains(word))
+                .all(|(y, _)| *y > area.y0 && *y < area.y1)
+        };
+        let count = |word: &str| {
+            shown
+                .iter()
+                .map(|(_, text)| text.matches(word).count())
+                .sum::<usize>()
+        };
+        assert!(within("alpha", upper));
+        assert!(within("gamma", lower));
+        assert!(!within("beta", upper) && !within("beta", lower));
+        assert_eq!(
+            [count("alpha"), count("beta"), count("gamma")],
+            [30, 30, 30]
+        );
+    }
+}
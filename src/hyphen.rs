@@ -0,0 +1,210 @@
+//! Liang hyphenation, as in TeX.
+//!
+//! A pattern such as `hen5at` scores the gaps between the letters it
+//! matches; every pattern matching a word contributes, the highest score
+//! at each gap wins, and odd scores mark where the word may break. Pattern
+//! sets are read in the TeX format, so the `hyph-*.tex` files distributed
+//! with TeX can be loaded as they are. Built-in languages use hypher, which
+//! embeds the same TeX sets (`hyph-en-us.tex` for English) precompiled.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct Hyphenator {
+    /// Letters of each pattern, and the score of each gap around them.
+    patterns: HashMap<String, Vec<u8>>,
+    exceptions: HashMap<String, Vec<usize>>,
+    longest: usize,
+    /// Fewest letters kept before the first break and after the last.
+    pub left_min: usize,
+    pub right_min: usize,
+}
+
+impl Hyphenator {
+    /// Build from patterns and exceptions in TeX syntax: whitespace
+    /// separated, `%` starting a comment. The bodies of `\patterns{...}`
+    /// and `\hyphenation{...}` may be passed as they are.
+    pub fn new(patterns: &str, exceptions: &str) -> Self {
+        let mut hyphenator = Self {
+            left_min: 2,
+            right_min: 3,
+            ..Self::default()
+        };
+        for pattern in tex_words(patterns) {
+            hyphenator.add_pattern(pattern);
+        }
+        for word in tex_words(exceptions) {
+            let mut letters = String::new();
+            let mut breaks = Vec::new();
+            for c in word.chars() {
+                if c == '-' {
+                    breaks.push(letters.chars().count());
+                } else {
+                    letters.extend(c.to_lowercase());
+                }
+            }
+            hyphenator.exceptions.insert(letters, breaks);
+        }
+        hyphenator
+    }
+
+    /// Read a TeX hyphenation file, taking the `\patterns` and
+    /// `\hyphenation` groups and ignoring everything else.
+    pub fn from_tex(source: &str) -> Self {
+        let group = |name: &str| {
+            source.find(name).map_or("", |start| {
+                let body = &source[start + name.len()..];
+                let body = body.trim_start().strip_prefix('{').unwrap_or(body);
+                &body[..body.find('}').unwrap_or(body.len())]
+            })
+        };
+        Self::new(group("\\patterns"), group("\\hyphenation"))
+    }
+
+    fn add_pattern(&mut self, pattern: &str) {
+        let mut letters = String::new();
+        let mut scores = vec![0];
+        for c in pattern.chars() {
+            if let Some(digit) = c.to_digit(10) {
+                *scores.last_mut().expect("scores starts non-empty") = digit as u8;
+            } else {
+                letters.push(c);
+                scores.push(0);
+            }
+        }
+        self.longest = self.longest.max(letters.chars().count());
+        self.patterns.insert(letters, scores);
+    }
+
+    /// Byte offsets in `word` where it may be broken with a hyphen, in
+    /// increasing order. Leading and trailing punctuation is left alone,
+    /// as are words with digits or other non-letters inside.
+    pub fn hyphenate(&self, word: &str) -> Vec<usize> {
+        let Some((start, core)) = word_core(word) else {
+            return Vec::new();
+        };
+        let letters: Vec<(usize, char)> = core.char_indices().collect();
+        let count = letters.len();
+        if count < self.left_min + self.right_min {
+            return Vec::new();
+        }
+        let lower: String = core.chars().flat_map(char::to_lowercase).collect();
+        let breaks: Vec<usize> = if let Some(breaks) = self.exceptions.get(&lower) {
+            breaks.clone()
+        } else {
+            let dotted: Vec<char> = format!(".{lower}.").chars().collect();
+            let mut scores = vec![0u8; dotted.len() + 1];
+            for from in 0..dotted.len() {
+                for to in from + 1..=dotted.len().min(from + self.longest) {
+                    let key: String = dotted[from..to].iter().collect();
+                    if let Some(pattern) = self.patterns.get(&key) {
+                        for (score, &value) in scores[from..].iter_mut().zip(pattern) {
+                            *score = (*score).max(value);
+                        }
+                    }
+                }
+            }
+            // Gap k of the word is gap k + 1 of the dotted word.
+            (1..count).filter(|&k| scores[k + 1] % 2 == 1).collect()
+        };
+        // Lowercasing can change the letter count; such words keep no
+        // breaks rather than misplaced ones.
+        if lower.chars().count() != count {
+            return Vec::new();
+        }
+        breaks
+            .into_iter()
+            .filter(|&k| k >= self.left_min && k + self.right_min <= count)
+            .map(|k| start + letters[k].0)
+            .collect()
+    }
+}
+
+/// The letters of `word` without leading and trailing punctuation, and
+/// their byte offset; `None` if non-letters remain inside.
+fn word_core(word: &str) -> Option<(usize, &str)> {
+    let start = word.len() - word.trim_start_matches(|c: char| !c.is_alphabetic()).len();
+    let core = word[start..].trim_end_matches(|c: char| !c.is_alphabetic());
+    (!core.is_empty() && core.chars().all(char::is_alphabetic)).then_some((start, core))
+}
+
+/// Whitespace-separated words with `%` comments removed.
+fn tex_words(source: &str) -> impl Iterator<Item = &str> {
+    source
+        .lines()
+        .map(|line| line.split('%').next().unwrap_or(""))
+        .flat_map(str::split_whitespace)
+}
+
+/// Byte offsets where `word` may be hyphenated in language `lang`, a BCP 47
+/// tag such as `en-US`.
+///
+/// Punctuation is handled as in [`Hyphenator::hyphenate`]. Only English is
+/// built in; other languages yield no breaks.
+pub fn hyphenate(word: &str, lang: &str) -> Vec<usize> {
+    let primary = lang.split(['-', '_']).next().unwrap_or("");
+    let Some(lang) = <[u8; 2]>::try_from(primary.to_ascii_lowercase().as_bytes())
+        .ok()
+        .and_then(hypher::Lang::from_iso)
+    else {
+        return Vec::new();
+    };
+    let Some((start, core)) = word_core(word) else {
+        return Vec::new();
+    };
+    let mut at = start;
+    let mut breaks: Vec<usize> = hypher::hyphenate(core, lang)
+        .map(|syllable| {
+            at += syllable.len();
+            at
+        })
+        .collect();
+    // The last syllable ends the word rather than a break.
+    breaks.pop();
+    breaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hyphenated(word: &str) -> String {
+        let mut out = String::new();
+        let mut last = 0;
+        for at in hyphenate(word, "en-US") {
+            out.push_str(&word[last..at]);
+            out.push('-');
+            last = at;
+        }
+        out + &word[last..]
+    }
+
+    #[test]
+    fn test_english_words() {
+        assert_eq!(hyphenate("hyphenation", "en"), [2, 6]);
+        assert_eq!(hyphenated("hyphenation"), "hy-phen-ation");
+        assert_eq!(hyphenated("typography"), "ty-pog-ra-phy");
+        assert_eq!(hyphenated("\"Typography,\""), "\"Ty-pog-ra-phy,\"");
+        assert_eq!(hyphenated("the"), "the");
+        assert!(hyphenate("hyphenation", "de").is_empty());
+        assert!(hyphenate("x86", "en").is_empty());
+    }
+
+    #[test]
+    fn test_english_uses_the_full_tex_patterns() {
+        assert_eq!(hyphenated("extensive"), "ex-ten-sive");
+        assert_eq!(hyphenated("encyclopedia"), "en-cy-clo-pe-dia");
+        assert_eq!(hyphenated("documentation"), "doc-u-men-ta-tion");
+        assert_eq!(hyphenated("characteristic"), "char-ac-ter-is-tic");
+        assert_eq!(hyphenated("(rhinoceros)"), "(rhi-noc-eros)");
+    }
+
+    #[test]
+    fn test_loads_tex_patterns() {
+        let source = "% Liang's example\n\\patterns{\n.hy3p he2n hena4 hen5at\n1na n2at 1tio 2io o2n\n}\n\
+                      \\hyphenation{ ex-am-ple }";
+        let hyphenator = Hyphenator::from_tex(source);
+        assert_eq!(hyphenator.hyphenate("hyphenation"), [2, 6]);
+        assert_eq!(hyphenator.hyphenate("Example"), [2, 4]);
+    }
+}
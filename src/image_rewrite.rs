@@ -0,0 +1,481 @@
+//! Downsampling and re-encoding of image `XObject`s to shrink a document.
+//!
+//! An image's resolution is measured where it is drawn: page content and
+//! the forms it uses are walked with the CTM, and an image placed at several
+//! sizes counts at its largest, where it needs the most pixels. Images
+//! above the limit are downsampled by area averaging. Any image that is
+//! rewritten, or that is stored uncompressed or Flate-compressed, is then
+//! written as JPEG or Flate, whichever is smaller, when that beats what it
+//! had.
+//!
+//! Only 8-bit images the renderer decodes are touched. Image masks, soft
+//! masks and images never drawn are left alone; indexed and colour-keyed
+//! images are resampled by picking samples and never stored as JPEG, which
+//! would shift their values.
+
+use crate::colorspace::PdfColorspace;
+use crate::content_stream::{IDENTITY, Matrix, TokenType, Tokenizer, concat};
+use crate::filters::{self, FilterType};
+use crate::models::ColorSpace;
+use crate::pdf_engine::{page_resources, resolve};
+use crate::recolor::image_samples;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::HashMap;
+
+/// How deep forms drawing forms are followed.
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Record the resolution of every image `content` draws, in pixels per
+/// inch of default user space, keeping the lowest per image.
+fn measure_images(
+    doc: &Document,
+    content: &[u8],
+    resources: &Dictionary,
+    ctm: Matrix,
+    depth: usize,
+    dpi: &mut HashMap<ObjectId, f32>,
+) {
+    let xobjects = resources
+        .get(b"XObject")
+        .map(|obj| resolve(doc, obj))
+        .and_then(Object::as_dict)
+        .ok();
+    let mut ctm = ctm;
+    let mut saved: Vec<Matrix> = Vec::new();
+    let mut operands: Vec<&[u8]> = Vec::new();
+    let mut tokens = Tokenizer::new(content);
+    while let Some(token) = tokens.next_token() {
+        if token.kind != TokenType::Operator {
+            operands.push(token.text);
+            continue;
+        }
+        match token.text {
+            b"q" => saved.push(ctm),
+            b"Q" => ctm = saved.pop().unwrap_or(ctm),
+            b"cm" => {
+                let m: Vec<f32> = operands
+                    .iter()
+                    .filter_map(|t| std::str::from_utf8(t).ok()?.parse().ok())
+                    .collect();
+                if let [a, b, c, d, e, f] = m[..] {
+                    ctm = concat(&[a, b, c, d, e, f], &ctm);
+                }
+            }
+            b"ID" => {
+                tokens.inline_image_data();
+            }
+            b"Do" => {
+                let Some(id) = operands
+                    .last()
+                    .and_then(|name| name.strip_prefix(b"/"))
+                    .and_then(|name| xobjects?.get(name).ok())
+                    .and_then(|obj| obj.as_reference().ok())
+                else {
+                    operands.clear();
+                    continue;
+                };
+                let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else {
+                    operands.clear();
+                    continue;
+                };
+                match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+                    Ok(b"Image") => {
+                        let size = |key: &[u8]| {
+                            stream
+                                .dict
+                                .get(key)
+                                .and_then(Object::as_i64)
+                                .map_or(0.0, |v| v as f32)
+                        };
+                        // The image fills the unit square, so the CTM's axis
+                        // lengths are its placed size in points.
+                        let inches_x = ctm[0].hypot(ctm[1]) / 72.0;
+                        let inches_y = ctm[2].hypot(ctm[3]) / 72.0;
+                        if inches_x > f32::EPSILON && inches_y > f32::EPSILON {
+                            let placed =
+                                (size(b"Width") / inches_x).max(size(b"Height") / inches_y);
+                            dpi.entry(id)
+                                .and_modify(|lowest| *lowest = lowest.min(placed))
+                                .or_insert(placed);
+                        }
+                    }
+                    Ok(b"Form") if depth < MAX_FORM_DEPTH => {
+                        let matrix = stream
+                            .dict
+                            .get(b"Matrix")
+                            .and_then(Object::as_array)
+                            .ok()
+                            .map(|m| m.iter().filter_map(|v| v.as_float().ok()).collect())
+                            .and_then(|m: Vec<f32>| <Matrix>::try_from(m).ok())
+                            .unwrap_or(IDENTITY);
+                        let form_resources = stream
+                            .dict
+                            .get(b"Resources")
+                            .map(|obj| resolve(doc, obj))
+                            .and_then(Object::as_dict)
+                            .unwrap_or(resources);
+                        let form_content = if stream.dict.has(b"Filter") {
+                            stream.decompressed_content().unwrap_or_default()
+                        } else {
+                            stream.content.clone()
+                        };
+                        measure_images(
+                            doc,
+                            &form_content,
+                            form_resources,
+                            concat(&matrix, &ctm),
+                            depth + 1,
+                            dpi,
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        operands.clear();
+    }
+}
+
+/// Scale `samples` (`width` × `height` pixels of `n` components) down to
+/// `new_width` × `new_height`, averaging each pixel's source area or, with
+/// `pick`, taking its top-left sample.
+fn downsample(
+    samples: &[u8],
+    (width, height): (usize, usize),
+    n: usize,
+    (new_width, new_height): (usize, usize),
+    pick: bool,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(new_width * new_height * n);
+    let mut sums = vec![0u64; n];
+    for oy in 0..new_height {
+        let y0 = oy * height / new_height;
+        let y1 = ((oy + 1) * height / new_height).max(y0 + 1);
+        for ox in 0..new_width {
+            let x0 = ox * width / new_width;
+            let x1 = ((ox + 1) * width / new_width).max(x0 + 1);
+            if pick {
+                let at = (y0 * width + x0) * n;
+                out.extend_from_slice(&samples[at..at + n]);
+                continue;
+            }
+            sums.fill(0);
+            for y in y0..y1 {
+                let row = &samples[(y * width + x0) * n..(y * width + x1) * n];
+                for pixel in row.chunks_exact(n) {
+                    for (sum, &s) in sums.iter_mut().zip(pixel) {
+                        *sum += u64::from(s);
+                    }
+                }
+            }
+            let count = ((x1 - x0) * (y1 - y0)) as u64;
+            out.extend(sums.iter().map(|&sum| ((sum + count / 2) / count) as u8));
+        }
+    }
+    out
+}
+
+fn encode_jpeg(
+    samples: &[u8],
+    (width, height): (usize, usize),
+    n: usize,
+    quality: u8,
+) -> Option<Vec<u8>> {
+    let color = match n {
+        1 => image::ExtendedColorType::L8,
+        3 => image::ExtendedColorType::Rgb8,
+        _ => return None,
+    };
+    let mut out = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality.clamp(1, 100))
+        .encode(samples, width as u32, height as u32, color)
+        .inspect_err(|e| tracing::debug!("JPEG encoding failed: {e}"))
+        .ok()?;
+    Some(out)
+}
+
+/// The rewritten image, or `None` when it is left as it is.
+fn rewrite_image(
+    doc: &Document,
+    stream: &Stream,
+    dpi: f32,
+    max_dpi: f32,
+    jpeg_quality: Option<u8>,
+) -> Option<Stream> {
+    let dict = &stream.dict;
+    let flag = |key: &[u8]| {
+        dict.get(key)
+            .is_ok_and(|v| v.as_bool().unwrap_or(false) || v.as_i64().is_ok_and(|v| v != 0))
+    };
+    if flag(b"ImageMask")
+        || flag(b"SMaskInData")
+        || dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() != Some(8)
+    {
+        return None;
+    }
+    let width = usize::try_from(dict.get(b"Width").and_then(Object::as_i64).ok()?).ok()?;
+    let height = usize::try_from(dict.get(b"Height").and_then(Object::as_i64).ok()?).ok()?;
+
+    let scale = (max_dpi / dpi).min(1.0);
+    let new_size = (
+        ((width as f32 * scale).round() as usize).max(1),
+        ((height as f32 * scale).round() as usize).max(1),
+    );
+    let shrinks = new_size != (width, height);
+    let filter_names: Vec<&[u8]> = match dict.get(b"Filter").map(|f| resolve(doc, f)) {
+        Ok(Object::Name(name)) => vec![name.as_slice()],
+        Ok(Object::Array(names)) => names.iter().filter_map(|n| n.as_name().ok()).collect(),
+        _ => Vec::new(),
+    };
+    let is_jpx = filter_names.contains(&b"JPXDecode".as_slice());
+    // JPEG and JPEG 2000 data is already compressed about as well as it can
+    // be; re-encoding it at the same size only loses quality.
+    if !shrinks
+        && (is_jpx
+            || filter_names
+                .iter()
+                .any(|n| matches!(*n, b"DCTDecode" | b"DCT")))
+    {
+        return None;
+    }
+
+    let space_obj = dict.get(b"ColorSpace").ok().map(|cs| resolve(doc, cs));
+    let indexed = matches!(space_obj, Some(Object::Array(items))
+        if items.first().and_then(|o| o.as_name().ok()) == Some(b"Indexed".as_slice()));
+    let keyed = matches!(dict.get(b"Mask"), Ok(Object::Array(_)));
+    let declared = if indexed {
+        None
+    } else {
+        space_obj
+            .map(|space| PdfColorspace::from_object(doc, space))
+            .transpose()
+            .ok()?
+    };
+    let components = if indexed {
+        1
+    } else {
+        declared.as_ref().map_or(3, PdfColorspace::components)
+    };
+    let (samples, embedded) = image_samples(doc, stream, components)
+        .inspect_err(|e| tracing::debug!("Leaving image as it is: {e}"))
+        .ok()?;
+    // A JPEG 2000 image without /ColorSpace takes the codestream's, which
+    // has to be written out once the data is no longer JPEG 2000.
+    let inferred = if space_obj.is_none() { embedded } else { None };
+    let n = inferred.map_or(components, ColorSpace::components);
+    if samples.len() < width * height * n {
+        return None;
+    }
+    let samples = &samples[..width * height * n];
+    let resized;
+    let samples = if shrinks {
+        resized = downsample(samples, (width, height), n, new_size, indexed || keyed);
+        &resized
+    } else {
+        samples
+    };
+
+    let flate = filters::encode(samples, FilterType::Flate).ok()?;
+    let jpeg = jpeg_quality
+        .filter(|_| !indexed && !keyed)
+        .and_then(|quality| encode_jpeg(samples, new_size, n, quality))
+        .filter(|jpeg| jpeg.len() < flate.len());
+    let (filter, data): (&[u8], Vec<u8>) = match jpeg {
+        Some(jpeg) => (b"DCTDecode", jpeg),
+        None => (FilterType::Flate.name(), flate),
+    };
+    if !shrinks && data.len() >= stream.content.len() {
+        return None;
+    }
+
+    let mut dict = dict.clone();
+    dict.set("Width", Object::Integer(new_size.0 as i64));
+    dict.set("Height", Object::Integer(new_size.1 as i64));
+    dict.set("Filter", Object::Name(filter.to_vec()));
+    dict.remove(b"DecodeParms");
+    if is_jpx {
+        // /Decode is ignored for JPEG 2000 but not for what replaces it.
+        dict.remove(b"Decode");
+        dict.remove(b"SMaskInData");
+    }
+    if let Some(space) = inferred {
+        let name: &[u8] = match space {
+            ColorSpace::DeviceGray => b"DeviceGray",
+            ColorSpace::DeviceRgb => b"DeviceRGB",
+            ColorSpace::DeviceCmyk => b"DeviceCMYK",
+        };
+        dict.set("ColorSpace", Object::Name(name.to_vec()));
+    }
+    Some(Stream::new(dict, data))
+}
+
+/// Downsample every image drawn above `max_dpi` and re-encode images as
+/// JPEG at `jpeg_quality` (1–100) or Flate where that makes them smaller.
+/// With no quality, JPEG is never used. Returns how many images were
+/// rewritten.
+pub fn rewrite_images(doc: &mut Document, max_dpi: f32, jpeg_quality: Option<u8>) -> usize {
+    let mut dpi = HashMap::new();
+    for page_id in doc.get_pages().into_values() {
+        let content = doc.get_page_content(page_id);
+        let resources = page_resources(doc, page_id);
+        measure_images(doc, &content, &resources, IDENTITY, 0, &mut dpi);
+    }
+
+    let updates: Vec<(ObjectId, Stream)> = dpi
+        .into_iter()
+        .filter_map(|(id, dpi)| {
+            let stream = doc.get_object(id).and_then(Object::as_stream).ok()?;
+            let rewritten = rewrite_image(doc, stream, dpi, max_dpi, jpeg_quality)?;
+            tracing::debug!(
+                "Image {id:?} at {dpi:.0} dpi: {} -> {} bytes",
+                stream.content.len(),
+                rewritten.content.len()
+            );
+            Some((id, rewritten))
+        })
+        .collect();
+    let rewritten = updates.len();
+    for (id, stream) in updates {
+        doc.objects.insert(id, Object::Stream(stream));
+    }
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one-inch page drawing a `size`-pixel RGB gradient over its whole
+    /// area, so the image's resolution is `size` dpi.
+    fn image_doc(size: usize) -> (Document, ObjectId) {
+        let mut doc = Document::with_version("1.7");
+        let samples: Vec<u8> = (0..size * size)
+            .flat_map(|i| {
+                let (x, y) = (i % size, i / size);
+                [(x * 255 / size) as u8, (y * 255 / size) as u8, 128]
+            })
+            .collect();
+        let image_id = doc.add_object(Stream::new(
+            Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"XObject".to_vec())),
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(size as i64)),
+                ("Height", Object::Integer(size as i64)),
+                ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+            ]),
+            samples,
+        ));
+        let content_id = doc.add_object(Stream::new(
+            Dictionary::new(),
+            b"q 72 0 0 72 0 0 cm /Im0 Do Q".to_vec(),
+        ));
+        let tree_id = doc.new_object_id();
+        let page_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(tree_id)),
+            ("Contents", Object::Reference(content_id)),
+            (
+                "Resources",
+                Object::Dictionary(Dictionary::from_iter(vec![(
+                    "XObject",
+                    Object::Dictionary(Dictionary::from_iter(vec![(
+                        "Im0",
+                        Object::Reference(image_id),
+                    )])),
+                )])),
+            ),
+        ]));
+        doc.objects.insert(
+            tree_id,
+            Object::Dictionary(Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Count", Object::Integer(1)),
+                ("Kids", Object::Array(vec![Object::Reference(page_id)])),
+                (
+                    "MediaBox",
+                    Object::Array([0, 0, 72, 72].map(Object::Integer).to_vec()),
+                ),
+            ])),
+        );
+        let catalog_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(tree_id)),
+        ]));
+        doc.trailer.set("Root", Object::Reference(catalog_id));
+        (doc, image_id)
+    }
+
+    fn saved_len(doc: &mut Document) -> usize {
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes.len()
+    }
+
+    #[test]
+    fn test_downsample_averages_blocks() {
+        let samples = [0, 10, 100, 110, 20, 30, 120, 130];
+        assert_eq!(downsample(&samples, (4, 2), 1, (2, 1), false), [15, 115]);
+        assert_eq!(downsample(&samples, (4, 2), 1, (2, 1), true), [0, 100]);
+    }
+
+    #[test]
+    fn test_rewrite_600_dpi_image() {
+        let (mut doc, image_id) = image_doc(600);
+        let before = saved_len(&mut doc);
+
+        assert_eq!(rewrite_images(&mut doc, 150.0, Some(75)), 1);
+        let after = saved_len(&mut doc);
+        assert!(after < before / 10, "{before} -> {after}");
+
+        let image = doc.get_object(image_id).unwrap().as_stream().unwrap();
+        let dict = &image.dict;
+        assert_eq!(dict.get(b"Width").unwrap().as_i64().unwrap(), 150);
+        assert_eq!(dict.get(b"Height").unwrap().as_i64().unwrap(), 150);
+        assert_eq!(
+            dict.get(b"Filter").unwrap().as_name().unwrap(),
+            b"DCTDecode"
+        );
+        let pixmap = crate::dct::decode_dct(&image.content, ColorSpace::DeviceRgb).unwrap();
+        assert_eq!((pixmap.width, pixmap.height), (150, 150));
+        // The middle of the gradient keeps its colour.
+        let at = (75 * 150 + 75) * 3;
+        for (&got, want) in pixmap.samples[at..at + 3].iter().zip([128, 128, 128]) {
+            assert!(got.abs_diff(want) < 12, "{got} vs {want}");
+        }
+
+        // Already at the limit and stored as JPEG: nothing more to do.
+        assert_eq!(rewrite_images(&mut doc, 150.0, Some(75)), 0);
+    }
+
+    #[test]
+    fn test_image_drawn_twice_keeps_pixels_for_the_larger_copy() {
+        let width_after = |max_dpi: f32| {
+            let (mut doc, image_id) = image_doc(600);
+            // Once at one inch (600 dpi) and once at four inches (150 dpi).
+            let page_id = doc.get_pages()[&1];
+            let content_id = doc
+                .get_dictionary(page_id)
+                .unwrap()
+                .get(b"Contents")
+                .unwrap()
+                .as_reference()
+                .unwrap();
+            doc.objects.insert(
+                content_id,
+                Object::Stream(Stream::new(
+                    Dictionary::new(),
+                    b"q 72 0 0 72 0 0 cm /Im0 Do Q q 288 0 0 288 0 0 cm /Im0 Do Q".to_vec(),
+                )),
+            );
+            rewrite_images(&mut doc, max_dpi, None);
+            let image = doc.get_object(image_id).unwrap().as_stream().unwrap();
+            image.dict.get(b"Width").unwrap().as_i64().unwrap()
+        };
+        // The four-inch copy is under 300 dpi, so nothing is downsampled.
+        assert_eq!(width_after(300.0), 600);
+        // At 75 dpi the four-inch copy needs 300 pixels.
+        assert_eq!(width_after(75.0), 300);
+    }
+}
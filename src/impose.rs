@@ -0,0 +1,572 @@
+//! Imposition: N-up sheets, booklets and posters.
+//!
+//! Each source page is wrapped in a form `XObject` and drawn, scaled or
+//! clipped, onto new sheets that replace the document's page tree.
+
+use crate::models::{PdfError, PdfResult};
+use crate::pdf_engine::{page_media_box, page_resources, page_rotation};
+use lopdf::{Document, Object, ObjectId};
+use std::collections::HashMap;
+
+/// Grid for N-up imposition; all sizes are in PDF points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NupLayout {
+    pub cols: u32,
+    pub rows: u32,
+    pub sheet_width: f32,
+    pub sheet_height: f32,
+    pub gutter: f32,
+}
+
+impl NupLayout {
+    /// A `cols` x `rows` grid on a Letter sheet, turned landscape when the
+    /// grid is wider than it is tall.
+    pub fn letter(cols: u32, rows: u32) -> Self {
+        let (sheet_width, sheet_height) = if cols > rows {
+            (792.0, 612.0)
+        } else {
+            (612.0, 792.0)
+        };
+        Self {
+            cols,
+            rows,
+            sheet_width,
+            sheet_height,
+            gutter: 18.0,
+        }
+    }
+
+    pub fn per_sheet(&self) -> usize {
+        (self.cols * self.rows) as usize
+    }
+}
+
+impl Default for NupLayout {
+    fn default() -> Self {
+        Self::letter(2, 1)
+    }
+}
+
+/// Tile the pages of `input_path` onto larger sheets, `layout.cols` x
+/// `layout.rows` per sheet. The last sheet may be partly empty.
+pub fn nup_pdf(input_path: &str, output_path: &str, layout: NupLayout) -> PdfResult<String> {
+    let mut doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+    if doc.is_encrypted() {
+        return Err(PdfError::from(
+            "Cannot impose an encrypted PDF; save an unprotected copy first",
+        ));
+    }
+    let slots: Vec<Option<ObjectId>> = doc.get_pages().into_values().map(Some).collect();
+    if slots.is_empty() {
+        return Err(PdfError::from("Document has no pages"));
+    }
+    let sheets = impose_sheets(&mut doc, &slots, &layout)?;
+    doc.save(output_path)
+        .map_err(|e| PdfError::IoError(e.to_string()))?;
+
+    tracing::info!(
+        "Imposed {} pages {}x{} onto {} sheets -> {}",
+        slots.len(),
+        layout.cols,
+        layout.rows,
+        sheets,
+        output_path
+    );
+    Ok(output_path.to_string())
+}
+
+/// Reorder and impose `input_path` 2-up onto landscape sheets so the
+/// printed stack folds into a booklet, padding with blank pages to a
+/// multiple of four.
+pub fn make_booklet(input_path: &str, output_path: &str) -> PdfResult<String> {
+    let mut doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+    if doc.is_encrypted() {
+        return Err(PdfError::from(
+            "Cannot impose an encrypted PDF; save an unprotected copy first",
+        ));
+    }
+    let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+    if pages.is_empty() {
+        return Err(PdfError::from("Document has no pages"));
+    }
+    let slots: Vec<Option<ObjectId>> = booklet_order(pages.len())
+        .into_iter()
+        .map(|p| p.map(|p| pages[p]))
+        .collect();
+    let layout = NupLayout {
+        gutter: 0.0,
+        ..NupLayout::letter(2, 1)
+    };
+    let sheets = impose_sheets(&mut doc, &slots, &layout)?;
+    doc.save(output_path)
+        .map_err(|e| PdfError::IoError(e.to_string()))?;
+
+    tracing::info!(
+        "Booklet of {} pages onto {} sheet sides -> {}",
+        pages.len(),
+        sheets,
+        output_path
+    );
+    Ok(output_path.to_string())
+}
+
+/// Slice every page into `tile_w` x `tile_h` sheets that overlap by
+/// `overlap` points, one output page per tile.
+///
+/// Crop marks show where the next tile's content starts, so the sheets can
+/// be trimmed and taped back together.
+pub fn poster_pdf(
+    input_path: &str,
+    output_path: &str,
+    tile_w: f32,
+    tile_h: f32,
+    overlap: f32,
+) -> PdfResult<String> {
+    const MARK_LEN: f32 = 12.0;
+
+    let mut doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+    if doc.is_encrypted() {
+        return Err(PdfError::from(
+            "Cannot tile an encrypted PDF; save an unprotected copy first",
+        ));
+    }
+    let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+
+    let pages_id = doc.new_object_id();
+    let mut kids = Vec::new();
+    for page_id in pages {
+        let (form_id, [x0, y0, x1, y1]) = page_as_form_xobject(&mut doc, page_id);
+        let (left, bottom) = (x0.min(x1), y0.min(y1));
+        let (width, height) = ((x1 - x0).abs(), (y1 - y0).abs());
+        let tiles = poster_tiles(width, height, tile_w, tile_h, overlap).map_err(PdfError::from)?;
+        for (tx, ty, w, h) in tiles {
+            let mut content = pdf_writer::Content::new();
+            content.save_state();
+            content.rect(0.0, 0.0, w, h);
+            content.clip_nonzero();
+            content.end_path();
+            content.transform([1.0, 0.0, 0.0, 1.0, -(left + tx), -(bottom + ty)]);
+            content.x_object(pdf_writer::Name(b"P0"));
+            content.restore_state();
+
+            content.set_stroke_gray(0.0);
+            content.set_line_width(0.5);
+            if tx + w < width - 0.5 {
+                let x = w - overlap;
+                content.move_to(x, 0.0);
+                content.line_to(x, MARK_LEN);
+                content.move_to(x, h - MARK_LEN);
+                content.line_to(x, h);
+            }
+            if ty > 0.5 {
+                content.move_to(0.0, overlap);
+                content.line_to(MARK_LEN, overlap);
+                content.move_to(w - MARK_LEN, overlap);
+                content.line_to(w, overlap);
+            }
+            content.stroke();
+
+            let mut xobjects = lopdf::Dictionary::new();
+            xobjects.set("P0", Object::Reference(form_id));
+            kids.push(add_sheet(&mut doc, pages_id, (w, h), content, xobjects));
+        }
+    }
+    if kids.is_empty() {
+        return Err(PdfError::from("Document has no pages"));
+    }
+    let tiles = replace_page_tree(&mut doc, pages_id, kids)?;
+    doc.save(output_path)
+        .map_err(|e| PdfError::IoError(e.to_string()))?;
+
+    tracing::info!("Poster split into {} tiles -> {}", tiles, output_path);
+    Ok(output_path.to_string())
+}
+
+/// Cut a `width` x `height` page into tiles of at most `tile_w` x `tile_h`,
+/// where neighbouring tiles share `overlap` points.
+///
+/// Tiles are `(x, y, w, h)` from the page's bottom-left corner, listed row
+/// by row from the top. The last row and column are narrower when the page
+/// does not divide evenly.
+pub fn poster_tiles(
+    width: f32,
+    height: f32,
+    tile_w: f32,
+    tile_h: f32,
+    overlap: f32,
+) -> Result<Vec<(f32, f32, f32, f32)>, String> {
+    if tile_w <= 0.0 || tile_h <= 0.0 || width <= 0.0 || height <= 0.0 {
+        return Err("Tile and page sizes must be positive".into());
+    }
+    if overlap < 0.0 || overlap >= tile_w.min(tile_h) {
+        return Err("Overlap must be smaller than the tile".into());
+    }
+    let steps = |total: f32, tile: f32| {
+        let step = tile - overlap;
+        // Shave float noise so an exact fit doesn't spill a sliver tile.
+        1 + ((total - tile) / step - 1e-3).ceil().max(0.0) as usize
+    };
+    let (cols, rows) = (steps(width, tile_w), steps(height, tile_h));
+    let mut tiles = Vec::with_capacity(cols * rows);
+    for row in 0..rows {
+        let top = height - row as f32 * (tile_h - overlap);
+        let h = tile_h.min(top);
+        for col in 0..cols {
+            let x = col as f32 * (tile_w - overlap);
+            tiles.push((x, top - h, tile_w.min(width - x), h));
+        }
+    }
+    Ok(tiles)
+}
+
+/// Page order for a saddle-stitched booklet printed 2-up, duplex.
+///
+/// Each sheet side holds an outer and an inner page: last, first, second,
+/// second-last, and so on. The count is padded to a multiple of four, and
+/// padding slots are `None`.
+pub fn booklet_order(page_count: usize) -> Vec<Option<usize>> {
+    let padded = page_count.div_ceil(4) * 4;
+    let slot = |p: usize| (p < page_count).then_some(p);
+    (0..padded / 4)
+        .flat_map(|sheet| {
+            let (front, back) = (2 * sheet, padded - 1 - 2 * sheet);
+            [back, front, front + 1, back - 1]
+        })
+        .map(slot)
+        .collect()
+}
+
+/// Wrap a page's content and resources in a form `XObject` so it can be
+/// drawn onto another page. The form's `/Matrix` applies the page's
+/// `/Rotate`, so it draws upright. Returns the form and the box it covers in
+/// that upright space, which is the `/MediaBox` for unrotated pages.
+fn page_as_form_xobject(doc: &mut Document, page_id: ObjectId) -> (ObjectId, [f32; 4]) {
+    let media_box = page_media_box(doc, page_id);
+    let [x0, y0, x1, y1] = media_box;
+    let (width, height) = ((x1 - x0).abs(), (y1 - y0).abs());
+    let (left, bottom, right, top) = (x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1));
+    // Viewers turn pages clockwise by /Rotate; map the rotated page onto
+    // the positive quadrant.
+    let (matrix, upright) = match page_rotation(doc, page_id) {
+        90 => (
+            [0.0, -1.0, 1.0, 0.0, -bottom, right],
+            [0.0, 0.0, height, width],
+        ),
+        180 => (
+            [-1.0, 0.0, 0.0, -1.0, right, top],
+            [0.0, 0.0, width, height],
+        ),
+        270 => ([0.0, 1.0, -1.0, 0.0, top, -left], [0.0, 0.0, height, width]),
+        _ => ([1.0, 0.0, 0.0, 1.0, 0.0, 0.0], media_box),
+    };
+    let content = doc.get_page_content(page_id);
+    let resources = page_resources(doc, page_id);
+    let dict = lopdf::Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"XObject".to_vec())),
+        ("Subtype", Object::Name(b"Form".to_vec())),
+        (
+            "BBox",
+            Object::Array(media_box.iter().map(|&v| Object::Real(v)).collect()),
+        ),
+        (
+            "Matrix",
+            Object::Array(matrix.iter().map(|&v| Object::Real(v)).collect()),
+        ),
+        ("Resources", Object::Dictionary(resources)),
+    ]);
+    let id = doc.add_object(lopdf::Stream::new(dict, content));
+    (id, upright)
+}
+
+/// Replace the document's page tree with `layout`-sized sheets, placing the
+/// source pages in `slots` row by row. `None` leaves a cell blank. Each page
+/// is scaled to fit its cell with its aspect ratio kept and centred in it.
+/// Returns the number of sheets written.
+fn impose_sheets(
+    doc: &mut Document,
+    slots: &[Option<ObjectId>],
+    layout: &NupLayout,
+) -> PdfResult<usize> {
+    let per_sheet = layout.per_sheet();
+    if per_sheet == 0 {
+        return Err(PdfError::from(
+            "N-up layout needs at least one row and column",
+        ));
+    }
+    let cols = layout.cols as usize;
+    let cell_w = (layout.sheet_width - layout.gutter * (layout.cols + 1) as f32) / cols as f32;
+    let cell_h =
+        (layout.sheet_height - layout.gutter * (layout.rows + 1) as f32) / layout.rows as f32;
+    if cell_w <= 0.0 || cell_h <= 0.0 {
+        return Err(PdfError::from(
+            "Gutter leaves no room for pages on the sheet",
+        ));
+    }
+
+    let mut forms = HashMap::new();
+    for &page_id in slots.iter().flatten() {
+        if let std::collections::hash_map::Entry::Vacant(e) = forms.entry(page_id) {
+            e.insert(page_as_form_xobject(doc, page_id));
+        }
+    }
+
+    let pages_id = doc.new_object_id();
+    let mut kids = Vec::new();
+    for sheet in slots.chunks(per_sheet) {
+        let mut content = pdf_writer::Content::new();
+        let mut xobjects = lopdf::Dictionary::new();
+        for (cell, slot) in sheet.iter().enumerate() {
+            let Some((form_id, [x0, y0, x1, y1])) = slot.and_then(|id| forms.get(&id).copied())
+            else {
+                continue;
+            };
+            let (src_w, src_h) = ((x1 - x0).abs().max(1.0), (y1 - y0).abs().max(1.0));
+            let scale = (cell_w / src_w).min(cell_h / src_h);
+            let (col, row) = (cell % cols, cell / cols);
+            let cell_x = layout.gutter + col as f32 * (cell_w + layout.gutter);
+            let cell_y = layout.sheet_height - (row + 1) as f32 * (cell_h + layout.gutter);
+            let dx = cell_x + (cell_w - src_w * scale) / 2.0 - x0.min(x1) * scale;
+            let dy = cell_y + (cell_h - src_h * scale) / 2.0 - y0.min(y1) * scale;
+
+            let name = format!("P{cell}");
+            content.save_state();
+            content.transform([scale, 0.0, 0.0, scale, dx, dy]);
+            content.x_object(pdf_writer::Name(name.as_bytes()));
+            content.restore_state();
+            xobjects.set(name.into_bytes(), Object::Reference(form_id));
+        }
+        kids.push(add_sheet(
+            doc,
+            pages_id,
+            (layout.sheet_width, layout.sheet_height),
+            content,
+            xobjects,
+        ));
+    }
+
+    replace_page_tree(doc, pages_id, kids)
+}
+
+/// Add a page of `size` under `pages_id` drawing `content`, with `xobjects`
+/// as its only resources.
+fn add_sheet(
+    doc: &mut Document,
+    pages_id: ObjectId,
+    (width, height): (f32, f32),
+    content: pdf_writer::Content,
+    xobjects: lopdf::Dictionary,
+) -> Object {
+    let content_id = doc.add_object(lopdf::Stream::new(
+        lopdf::Dictionary::new(),
+        content.finish().to_vec(),
+    ));
+    let sheet_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Page".to_vec())),
+        ("Parent", Object::Reference(pages_id)),
+        (
+            "MediaBox",
+            Object::Array(vec![
+                Object::Real(0.0),
+                Object::Real(0.0),
+                Object::Real(width),
+                Object::Real(height),
+            ]),
+        ),
+        ("Contents", Object::Reference(content_id)),
+        (
+            "Resources",
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                "XObject",
+                Object::Dictionary(xobjects),
+            )])),
+        ),
+    ])));
+    Object::Reference(sheet_id)
+}
+
+/// Install `kids` as the document's page tree at `pages_id` and drop
+/// everything only the old pages used. Returns the new page count.
+fn replace_page_tree(
+    doc: &mut Document,
+    pages_id: ObjectId,
+    kids: Vec<Object>,
+) -> PdfResult<usize> {
+    let sheet_count = kids.len();
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Count", Object::Integer(sheet_count as i64)),
+            ("Kids", Object::Array(kids)),
+        ])),
+    );
+    let root_id = doc
+        .trailer
+        .get(b"Root")
+        .and_then(Object::as_reference)
+        .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+    let catalog = doc
+        .get_dictionary_mut(root_id)
+        .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+    catalog.set("Pages", Object::Reference(pages_id));
+    // Outlines and structure trees point at the old pages; drop them rather
+    // than leave dangling destinations.
+    catalog.remove(b"Outlines");
+    catalog.remove(b"StructTreeRoot");
+
+    doc.prune_objects();
+    doc.renumber_objects();
+    Ok(sheet_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_path() -> std::path::PathBuf {
+        std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_document.pdf")
+    }
+
+    /// A fresh temporary file, deleted when the returned path drops.
+    fn temp_path(suffix: &str) -> tempfile::TempPath {
+        tempfile::Builder::new()
+            .suffix(suffix)
+            .tempfile()
+            .unwrap()
+            .into_temp_path()
+    }
+
+    #[test]
+    fn test_booklet_order() {
+        let one_based = |order: Vec<Option<usize>>| {
+            order
+                .into_iter()
+                .map(|p| p.map(|p| p + 1))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            one_based(booklet_order(8)),
+            [8, 1, 2, 7, 6, 3, 4, 5].map(Some)
+        );
+        assert_eq!(
+            one_based(booklet_order(6)),
+            [
+                None,
+                Some(1),
+                Some(2),
+                None,
+                Some(6),
+                Some(3),
+                Some(4),
+                Some(5)
+            ]
+        );
+        assert!(booklet_order(0).is_empty());
+    }
+
+    #[test]
+    fn test_poster_tiles_cover_page() {
+        let tiles = poster_tiles(1000.0, 700.0, 400.0, 400.0, 50.0).unwrap();
+        // 3 columns (0, 350, 700) by 2 rows (top at 700, 350).
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[0], (0.0, 300.0, 400.0, 400.0));
+        assert_eq!(tiles[2], (700.0, 300.0, 300.0, 400.0));
+        assert_eq!(tiles[5], (700.0, 0.0, 300.0, 350.0));
+        let right = tiles.iter().map(|t| t.0 + t.2).fold(0.0, f32::max);
+        let top = tiles.iter().map(|t| t.1 + t.3).fold(0.0, f32::max);
+        assert_eq!((right, top), (1000.0, 700.0));
+        assert!(tiles.iter().all(|t| t.0 >= 0.0 && t.1 >= 0.0));
+
+        assert_eq!(
+            poster_tiles(400.0, 400.0, 400.0, 400.0, 50.0)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(poster_tiles(1000.0, 700.0, 400.0, 400.0, 400.0).is_err());
+    }
+
+    #[test]
+    fn test_form_xobject_turns_rotated_pages_upright() {
+        let input = fixture_path();
+        let mut doc = Document::load(input).unwrap();
+        let page_id = *doc.get_pages().values().next().unwrap();
+        let [x0, y0, x1, y1] = page_media_box(&doc, page_id);
+
+        for rotation in [0, 90, 180, 270, -90] {
+            doc.get_dictionary_mut(page_id)
+                .unwrap()
+                .set("Rotate", Object::Integer(rotation));
+            let (form_id, upright) = page_as_form_xobject(&mut doc, page_id);
+            let matrix: Vec<f32> = doc
+                .get_object(form_id)
+                .and_then(Object::as_stream)
+                .unwrap()
+                .dict
+                .get(b"Matrix")
+                .and_then(Object::as_array)
+                .unwrap()
+                .iter()
+                .map(|v| v.as_float().unwrap())
+                .collect();
+            let [a, b, c, d, e, f] = matrix[..] else {
+                panic!("Matrix needs six numbers");
+            };
+            let map = |x: f32, y: f32| (a * x + c * y + e, b * x + d * y + f);
+            let corners = [map(x0, y0), map(x1, y1)];
+            let (lo_x, hi_x) = (
+                corners[0].0.min(corners[1].0),
+                corners[0].0.max(corners[1].0),
+            );
+            let (lo_y, hi_y) = (
+                corners[0].1.min(corners[1].1),
+                corners[0].1.max(corners[1].1),
+            );
+            assert_eq!([lo_x, lo_y, hi_x, hi_y], upright, "rotation {rotation}");
+            let turned = rotation.rem_euclid(180) == 90;
+            assert_eq!(upright[2] - upright[0] == y1 - y0, turned);
+        }
+    }
+
+    #[test]
+    fn test_nup_sheet_count_rounds_up() {
+        let input = fixture_path();
+        let input = input.to_str().unwrap();
+        let source_pages = Document::load(input).unwrap().get_pages().len();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        for (cols, rows) in [(1, 1), (2, 1), (2, 2), (3, 3)] {
+            let layout = NupLayout::letter(cols, rows);
+            nup_pdf(input, output, layout).unwrap();
+            let sheets = Document::load(output).unwrap().get_pages().len();
+            assert_eq!(sheets, source_pages.div_ceil(layout.per_sheet()));
+        }
+
+        assert!(nup_pdf(input, output, NupLayout::letter(0, 2)).is_err());
+    }
+
+    #[test]
+    fn test_poster_emits_one_page_per_tile() {
+        let input = fixture_path();
+        let input = input.to_str().unwrap();
+        let source = Document::load(input).unwrap();
+        let expected: usize = source
+            .get_pages()
+            .into_values()
+            .map(|id| {
+                let [x0, y0, x1, y1] = page_media_box(&source, id);
+                poster_tiles(x1 - x0, y1 - y0, 300.0, 300.0, 20.0)
+                    .unwrap()
+                    .len()
+            })
+            .sum();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        poster_pdf(input, output, 300.0, 300.0, 20.0).unwrap();
+        let tiles = Document::load(output).unwrap().get_pages().len();
+        assert_eq!(tiles, expected);
+        assert!(expected > source.get_pages().len());
+    }
+}
@@ -0,0 +1,71 @@
+//! `JPXDecode`: JPEG 2000 images, decoded with `hayro-jpeg2000`, the same
+//! decoder zpdf renders them with.
+//!
+//! Both JP2 files and bare codestreams are accepted. Samples come out as 8
+//! bits; one, three and four colour channels map to gray, RGB and CMYK, and
+//! an alpha channel is dropped. Other channel counts are reported as
+//! unsupported.
+
+use crate::models::{ColorSpace, PdfError, PdfResult, Pixmap};
+use hayro_jpeg2000::{DecodeSettings, Image};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+pub fn decode_jpx(data: &[u8]) -> PdfResult<Pixmap> {
+    let corrupt =
+        |e: hayro_jpeg2000::DecodeError| PdfError::from(format!("JPEG 2000 data is corrupt: {e}"));
+    // The decoder can panic on malformed codestreams; treat that as corrupt
+    // data too, like zpdf does.
+    let panicked = |_| PdfError::from("JPEG 2000 data is corrupt");
+    let image = catch_unwind(|| Image::new(data, &DecodeSettings::default()))
+        .map_err(panicked)?
+        .map_err(corrupt)?;
+    let channels = usize::from(image.color_space().num_channels());
+    let colorspace = match channels {
+        1 => ColorSpace::DeviceGray,
+        3 => ColorSpace::DeviceRgb,
+        4 => ColorSpace::DeviceCmyk,
+        n => {
+            return Err(PdfError::from(format!(
+                "JPEG 2000 images with {n} components are not supported"
+            )));
+        }
+    };
+    let mut samples = catch_unwind(AssertUnwindSafe(|| image.decode()))
+        .map_err(panicked)?
+        .map_err(corrupt)?;
+    if image.has_alpha() {
+        samples = samples
+            .chunks_exact(channels + 1)
+            .flat_map(|pixel| &pixel[..channels])
+            .copied()
+            .collect();
+    }
+    Ok(Pixmap {
+        width: image.width(),
+        height: image.height(),
+        colorspace,
+        bits_per_component: 8,
+        samples,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_flat_codestream() {
+        // A 5x3 three-component codestream whose packets are all empty, so
+        // every sample is the level shift of 128.
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/flat_rgb.j2k");
+        let pixmap = decode_jpx(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!((pixmap.width, pixmap.height), (5, 3));
+        assert_eq!(pixmap.colorspace, ColorSpace::DeviceRgb);
+        assert_eq!(pixmap.pixel(4, 2), [128, 128, 128]);
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        assert!(decode_jpx(b"not a JPEG 2000 image").is_err());
+    }
+}
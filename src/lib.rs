@@ -48,13 +48,43 @@
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 pub mod app;
+pub mod archive;
+pub mod band_writer;
+pub mod barcode;
+pub mod bidi;
+pub mod cbz;
+pub mod ccitt;
+pub mod cmap;
+pub mod colorspace;
 pub mod commands;
+pub mod composite;
+pub mod content_stream;
+pub mod dct;
+pub mod deskew;
 pub mod engine;
+pub mod epub;
+pub mod filters;
+pub mod flow;
+pub mod font_program;
+pub mod function;
+pub mod html;
+pub mod hyphen;
+pub mod image_rewrite;
+pub mod impose;
+pub mod jpx;
 pub mod message;
 pub mod models;
+pub mod page_resize;
 pub mod pdf_engine;
+pub mod pdf_json;
 pub mod platform;
+pub mod recolor;
+pub mod resample;
+pub mod searchable_pdf;
+pub mod shading;
+pub mod stext;
 pub mod storage;
+pub mod tables;
 pub mod ui;
 pub mod ui_document;
 pub mod ui_keyboard_help;
@@ -62,3 +92,5 @@ pub mod ui_metadata;
 pub mod ui_settings;
 pub mod ui_welcome;
 pub mod update;
+pub mod xmp;
+pub mod xps;
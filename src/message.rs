@@ -21,6 +21,8 @@ pub enum Message {
     AddBookmark,
     RemoveBookmark(usize),
     JumpToBookmark(usize),
+    JumpToOutline(usize),
+    ToggleOutlineNode(usize),
     SetAnnotationMode(Option<crate::models::PendingAnnotationKind>),
     AnnotationDragStart {
         page: usize,
@@ -32,6 +34,7 @@ pub enum Message {
         y: f32,
     },
     AnnotationDragEnd,
+    UpdateAnnotation(usize, f32, f32, f32, f32),
     DeleteAnnotation(usize),
     Undo,
     Redo,
@@ -43,6 +46,8 @@ pub enum Message {
     OpenRecentFile(RecentFile),
     ClearRecentFiles,
     CloseTab(usize),
+    ReopenClosedTab,
+    FileDropped(PathBuf),
     SwitchTab(usize),
     TabReordered(Vec<usize>),
     NextPage,
@@ -53,6 +58,7 @@ pub enum Message {
     JumpToPage(usize),
     PageInputChanged(String),
     PageInputSubmitted,
+    ClearPageInputError,
     Search(String),
     PerformSearch(String),
     SearchResult(DocumentId, PdfResult<Vec<SearchResultItem>>),
@@ -60,7 +66,7 @@ pub enum Message {
     PrevSearchResult,
     ClearSearch,
     DocumentOpened(DocumentId, PdfResult<OpenResult>),
-    PageRendered(DocumentId, usize, f32, PdfResult<RenderResult>),
+    PageRendered(DocumentId, usize, f32, u64, PdfResult<RenderResult>),
     ThumbnailRendered(DocumentId, usize, f32, PdfResult<RenderResult>),
     TextItemsLoaded(DocumentId, usize, PdfResult<Vec<TextItem>>),
     DocumentMetaLoaded(DocumentId, PdfResult<DocumentMeta>),
@@ -71,19 +77,46 @@ pub enum Message {
     ExtractTextToClipboard,
     TextExtracted(PdfResult<String>),
     CopyToClipboard(String),
+    /// Copy the words selected on the current tab.
+    CopySelection,
     CopyImageToClipboard,
     SaveAnnotations,
     AnnotationsSaved(PdfResult<String>),
     AnnotationsLoaded(DocumentId, Vec<crate::models::Annotation>),
     MergeDocuments(Vec<PathBuf>),
     DocumentsMerged(PdfResult<String>),
+    MergeOpenTabs,
+    OpenTabsMerged(PdfResult<String>),
+    SplitDocument,
     SplitPDF(Vec<usize>),
+    SplitProgress(usize, usize),
     PDFSplit(PdfResult<Vec<String>>),
+    ToggleExtractDialog(bool),
+    ExtractRangeInputChanged(String),
+    ExtractPages,
+    PagesExtracted(PdfResult<String>),
+    ToggleNupDialog(bool),
+    SetNupGrid(u32, u32),
+    ImposeNup,
+    NupImposed(PdfResult<String>),
+    MakeBooklet,
+    BookletCreated(PdfResult<String>),
+    PrependToc,
+    TocPrepended(PdfResult<String>),
+    TogglePosterDialog(bool),
+    SetPosterPaper(crate::flow::PageSize),
+    SetPosterOverlap(f32),
+    MakePoster,
+    PosterCreated(PdfResult<String>),
     ToggleMetadata,
     LoadFormFields,
     FormFieldsLoaded(PdfResult<Vec<crate::models::FormField>>),
     FormFieldChanged(String, crate::models::FormFieldVariant),
     FillForm(Vec<crate::models::FormField>),
+    ImportFormValues,
+    FormValuesImported(PdfResult<(String, Vec<String>)>),
+    FlattenForms,
+    FormsFlattened(PdfResult<(String, usize)>),
     FormFilled(PdfResult<String>),
     ExportImage,
     ImageExported(PdfResult<String>),
@@ -93,13 +126,36 @@ pub enum Message {
     PrintersListed(PdfResult<Vec<String>>),
     PrintWithPrinter(String),
     PrintDone(PdfResult<()>),
+    SetPrintRange(crate::models::PrintRange),
+    PrintRangeInputChanged(String),
+    ConfirmPrint,
+    CancelPrint,
     AddWatermark(String),
+    SetWatermarkOpacity(f32),
+    SetWatermarkAngle(f32),
     WatermarkDone(PdfResult<String>),
     OptimizePDF,
-    PDFOptimized(PdfResult<String>),
+    PDFOptimized(PdfResult<(String, i64)>),
+    AddChart,
+    ChartAdded(PdfResult<String>),
+    ExportJson,
+    JsonExported(PdfResult<String>),
+    ImportHtml,
+    HtmlConverted(PdfResult<String>),
+    ToggleBarcodeDialog(bool),
+    BarcodeInputChanged(String),
+    SetBarcodeKind(crate::barcode::BarcodeKind),
+    AddBarcode,
+    BarcodeAdded(PdfResult<String>),
+    ValidatePdf,
+    RepairPdf,
+    PdfRepaired(PdfResult<crate::models::RepairResult>),
+    PdfValidated(PdfResult<(String, Vec<crate::models::ValidationIssue>)>),
     EngineInitialized(EngineState),
     Error(String),
     ClearStatus,
+    /// Write the sidecars of tabs changed since the last write.
+    FlushSidecars,
     IcedEvent(iced::Event),
     LinkClicked(crate::models::Hyperlink),
     ForceQuit,
@@ -128,11 +184,23 @@ pub enum Message {
     TogglePageOrganizer(bool),
     OrganizerDeletePage(usize),
     OrganizerRotatePage(usize, i32),
+    RotatePageClockwise(usize),
     OrganizerMovePage(usize, isize),
     AnnotationTextChanged(String),
     SaveOrganizedPDF,
     OrganizedPDFSaved(crate::models::PdfResult<String>),
     EditAnnotationText(usize, String),
+    /// Place a sticky note with its icon's corner at `(x, y)`, in rotated
+    /// page points, and open its editor.
+    AddTextNote {
+        page: usize,
+        x: f32,
+        y: f32,
+    },
+    OpenNoteEditor(usize),
+    NoteEditorChanged(String),
+    SaveNoteEditor,
+    CloseNoteEditor,
     PasswordInputChanged(String),
     SubmitPassword,
     CancelPasswordPrompt,
@@ -142,6 +210,9 @@ pub enum Message {
     AttachmentSaved(crate::models::PdfResult<String>),
     ToggleLayer(usize, bool),
     LayerToggled,
+    /// Lay the current reflowable tab out again with this body text size.
+    SetReflowTextSize(f32),
+    DocumentReflowed(DocumentId, PdfResult<OpenResult>),
     ToggleTableMode,
     TablesDetected(
         crate::models::DocumentId,
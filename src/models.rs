@@ -90,7 +90,7 @@ impl PartialEq<&str> for PdfError {
 
 pub type PdfResult<T> = Result<T, PdfError>;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DocumentMetadata {
     pub title: Option<String>,
     pub author: Option<String>,
@@ -136,6 +136,9 @@ pub struct DetectedTable {
     pub csv: String,
     pub tsv: String,
     pub cells: Vec<Vec<String>>,
+    /// The same cells with their row, column and bounding box.
+    #[serde(default)]
+    pub grid: crate::tables::Table,
 }
 
 #[derive(Debug, Clone)]
@@ -153,6 +156,8 @@ pub struct OpenResult {
     pub attachments: Vec<AttachmentInfo>,
     pub layers: Vec<LayerInfo>,
     pub oc_config: Option<zpdf::OcConfig>,
+    /// The format the file was in before it was opened as a PDF.
+    pub format: crate::pdf_engine::DocumentFormat,
 }
 
 #[derive(Debug, Clone)]
@@ -184,6 +189,58 @@ pub struct RenderResult {
     pub data: Arc<[u8]>,
 }
 
+/// The device colour space of decoded image samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    DeviceGray,
+    DeviceRgb,
+    DeviceCmyk,
+}
+
+impl ColorSpace {
+    pub const fn components(self) -> usize {
+        match self {
+            Self::DeviceGray => 1,
+            Self::DeviceRgb => 3,
+            Self::DeviceCmyk => 4,
+        }
+    }
+
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"DeviceGray" | b"G" => Some(Self::DeviceGray),
+            b"DeviceRGB" | b"RGB" => Some(Self::DeviceRgb),
+            b"DeviceCMYK" | b"CMYK" => Some(Self::DeviceCmyk),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded image samples: interleaved components, rows top to bottom, each
+/// row padded to a whole byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixmap {
+    pub width: u32,
+    pub height: u32,
+    pub colorspace: ColorSpace,
+    pub bits_per_component: u8,
+    pub samples: Vec<u8>,
+}
+
+impl Pixmap {
+    pub fn stride(&self) -> usize {
+        (self.width as usize * self.colorspace.components() * usize::from(self.bits_per_component))
+            .div_ceil(8)
+    }
+
+    /// The components of one pixel of an 8-bit pixmap.
+    pub fn pixel(&self, x: u32, y: u32) -> &[u8] {
+        let n = self.colorspace.components();
+        let start = y as usize * self.stride() + x as usize * n;
+        &self.samples[start..start + n]
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub page: usize,
@@ -192,10 +249,16 @@ pub struct SearchResult {
     pub x: f32,
     pub width: f32,
     pub height: f32,
+    pub rects: Vec<(f32, f32, f32, f32)>,
 }
 
 impl SearchResult {
     pub fn from_search_result_item(item: SearchResultItem) -> Self {
+        let rects = if item.rects.is_empty() {
+            vec![(item.x, item.y, item.width, item.height)]
+        } else {
+            item.rects
+        };
         Self {
             page: item.page_index,
             text: item.text,
@@ -203,6 +266,7 @@ impl SearchResult {
             x: item.x,
             width: item.width,
             height: item.height,
+            rects,
         }
     }
 }
@@ -235,6 +299,15 @@ pub enum RibbonTab {
     Tools,
 }
 
+/// Which pages the print dialog sends to the printer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrintRange {
+    #[default]
+    All,
+    Current,
+    Custom,
+}
+
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq, Default)]
 pub enum SidebarMode {
     #[default]
@@ -267,6 +340,13 @@ pub struct AppSettings {
     pub remember_last_file: bool,
     pub default_zoom: f32,
     pub auto_save: bool,
+    /// Upper bound on decoded RGBA bytes kept for rendered pages across all tabs.
+    #[serde(default = "default_max_render_cache_bytes")]
+    pub max_render_cache_bytes: usize,
+}
+
+const fn default_max_render_cache_bytes() -> usize {
+    512 * 1024 * 1024
 }
 
 impl Default for AppSettings {
@@ -282,6 +362,7 @@ impl Default for AppSettings {
             remember_last_file: true,
             default_zoom: 1.0,
             auto_save: true,
+            max_render_cache_bytes: default_max_render_cache_bytes(),
         }
     }
 }
@@ -325,6 +406,18 @@ pub struct SessionData {
     pub active_tab: usize,
 }
 
+/// Per-document state kept next to the PDF as `<file>.pdf.pdfbull.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DocumentSidecar {
+    #[serde(default)]
+    pub bookmarks: Vec<PageBookmark>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// Per-page rotation fixes keyed by source page index.
+    #[serde(default)]
+    pub page_rotations: std::collections::HashMap<usize, i32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PageBookmark {
     pub page: usize,
@@ -340,6 +433,9 @@ pub struct SearchResultItem {
     pub x: f32,
     pub width: f32,
     pub height: f32,
+    /// One `(x, y, w, h)` box per text span the match covers, top-down in PDF points.
+    #[serde(default)]
+    pub rects: Vec<(f32, f32, f32, f32)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -377,6 +473,13 @@ pub enum AnnotationStyle {
         comment: String,
         color: String,
     },
+    /// Freehand strokes. Points are offsets from the annotation's `(x, y)`
+    /// in unrotated page points, so moving the annotation moves the strokes.
+    Ink {
+        color: String,
+        thickness: f32,
+        strokes: Vec<Vec<(f32, f32)>>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -394,6 +497,10 @@ pub struct Annotation {
 pub enum UndoableAction {
     AddAnnotation(Annotation),
     DeleteAnnotation(usize, Annotation),
+    ModifyAnnotation {
+        before: Annotation,
+        after: Annotation,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -406,6 +513,7 @@ pub enum PendingAnnotationKind {
     Line,
     Arrow,
     StickyNote,
+    Ink,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -433,16 +541,361 @@ pub struct FormField {
     pub page: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    /// Breaks the file or a PDF/A requirement.
+    Error,
+    /// Worth fixing but readers cope, or only matters for archival.
+    Warning,
+}
+
+/// One finding from `DocumentStore::validate_pdf`. `page` is 1-based and
+/// `None` for document-level rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub rule: String,
+    pub severity: IssueSeverity,
+    pub page: Option<usize>,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub fn new(
+        rule: &str,
+        severity: IssueSeverity,
+        page: Option<usize>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            rule: rule.to_string(),
+            severity,
+            page,
+            message: message.into(),
+        }
+    }
+}
+
+/// A structural problem `DocumentStore::repair_pdf` found and fixed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepairIssue {
+    /// The `%PDF-` header was missing or preceded by junk.
+    BadHeader,
+    /// `startxref` was missing or didn't point at a cross-reference section.
+    BadStartXref {
+        offset: Option<usize>,
+    },
+    /// The xref listed an object at the wrong byte offset.
+    MisplacedObject {
+        id: u32,
+        generation: u16,
+        listed: usize,
+        actual: usize,
+    },
+    /// No usable `/Root` in the trailer; the catalog was found by scanning.
+    MissingRoot,
+    MissingEof,
+}
+
+impl std::fmt::Display for RepairIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadHeader => write!(f, "Missing or displaced %PDF- header"),
+            Self::BadStartXref { offset: Some(o) } => {
+                write!(f, "startxref offset {o} is not a cross-reference section")
+            }
+            Self::BadStartXref { offset: None } => write!(f, "Missing startxref"),
+            Self::MisplacedObject {
+                id,
+                generation,
+                listed,
+                actual,
+            } => write!(
+                f,
+                "Object {id} {generation} listed at offset {listed} but found at {actual}"
+            ),
+            Self::MissingRoot => write!(f, "Trailer /Root rebuilt from the document catalog"),
+            Self::MissingEof => write!(f, "Missing %%EOF marker"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepairResult {
+    pub output_path: String,
+    /// Indirect objects recovered, including those packed in object
+    /// streams.
+    pub objects_recovered: usize,
+    pub issues: Vec<RepairIssue>,
+}
+
+/// What `DocumentStore::clean_pdf` does besides rewriting the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanOptions {
+    /// Drop unreferenced objects and renumber the rest.
+    pub garbage: bool,
+    /// Leave streams uncompressed for inspection; otherwise any stream
+    /// without a filter is compressed.
+    pub decompress: bool,
+    /// Rewrite page content with comments removed and `q`/`Q`, `BT`/`ET`
+    /// and marked content balanced.
+    pub sanitize: bool,
+}
+
+/// One value of a linearization parameter dictionary compared with what
+/// the file actually holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinearizationCheck {
+    /// The dictionary key, such as `L` or `H`.
+    pub field: String,
+    pub passed: bool,
+    pub message: String,
+}
+
+/// The result of `DocumentStore::check_linearization`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinearizationCheckResult {
+    /// Whether the file starts with a linearization parameter dictionary.
+    pub linearized: bool,
+    pub file_length: usize,
+    /// Objects between the linearization dictionary and the end of the
+    /// first page (`/E`), hint stream excluded.
+    pub first_page_objects: Option<usize>,
+    pub hint_offset_ok: Option<bool>,
+    pub length_matches: Option<bool>,
+    pub checks: Vec<LinearizationCheck>,
+}
+
+impl LinearizationCheckResult {
+    /// Linearized, with every parameter matching the file.
+    pub fn is_valid(&self) -> bool {
+        self.linearized && self.checks.iter().all(|c| c.passed)
+    }
+}
+
+/// A 3D annotation found by `DocumentStore::list_3d`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThreeDInfo {
+    /// 0-based page index.
+    pub page: usize,
+    /// Position among the page's 3D annotations.
+    pub index: usize,
+    /// The model format, `U3D` or `PRC`.
+    pub format: String,
+    /// Length of the decoded model data in bytes.
+    pub size: usize,
+    /// The default view's external name (`/XN`).
+    pub view_name: Option<String>,
+    /// The default view's camera-to-world matrix (`/C2W`), column by column.
+    pub view_matrix: Option<[f32; 12]>,
+}
+
+/// The kind of font program embedded in a PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FontFormat {
+    TrueType,
+    /// An OpenType font with CFF outlines.
+    OpenType,
+    /// A bare CFF font, as `/Type1C` and `/CIDFontType0C` embed them.
+    Cff,
+    /// A Type 1 font, written out as PFB.
+    Type1,
+}
+
+impl FontFormat {
+    /// The usual file extension, without the dot.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::TrueType => "ttf",
+            Self::OpenType => "otf",
+            Self::Cff => "cff",
+            Self::Type1 => "pfb",
+        }
+    }
+}
+
+/// An embedded font program extracted by `DocumentStore::extract_fonts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractedFont {
+    /// The PostScript name, subset tag included (`ABCDEF+Arial`).
+    pub name: String,
+    /// Whether the name carries a subset tag, meaning only the glyphs the
+    /// document uses are present.
+    pub subset: bool,
+    pub format: FontFormat,
+    pub data: Vec<u8>,
+}
+
+impl ExtractedFont {
+    /// A file name for the font: its name without the subset tag, made
+    /// safe for file systems, plus the format's extension.
+    pub fn file_name(&self) -> String {
+        let name = if self.subset {
+            &self.name[7..]
+        } else {
+            self.name.as_str()
+        };
+        let stem: String = name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let stem = if stem.is_empty() { "font" } else { &stem };
+        format!("{stem}.{}", self.format.extension())
+    }
+}
+
+/// How a page is revealed when a presentation moves to it (`/Trans /S`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransitionStyle {
+    Split,
+    Blinds,
+    Box,
+    Wipe,
+    Dissolve,
+    Glitter,
+    Fly,
+    Push,
+    Cover,
+    Uncover,
+    Fade,
+    /// No effect: the new page simply replaces the old one.
+    #[default]
+    Replace,
+}
+
+impl TransitionStyle {
+    /// The style a `/S` name stands for; unknown names fall back to
+    /// [`Self::Replace`], as the spec asks.
+    pub fn from_name(name: &[u8]) -> Self {
+        match name {
+            b"Split" => Self::Split,
+            b"Blinds" => Self::Blinds,
+            b"Box" => Self::Box,
+            b"Wipe" => Self::Wipe,
+            b"Dissolve" => Self::Dissolve,
+            b"Glitter" => Self::Glitter,
+            b"Fly" => Self::Fly,
+            b"Push" => Self::Push,
+            b"Cover" => Self::Cover,
+            b"Uncover" => Self::Uncover,
+            b"Fade" => Self::Fade,
+            _ => Self::Replace,
+        }
+    }
+}
+
+/// A page's presentation transition from its `/Trans` dictionary, with the
+/// spec's defaults for missing entries.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transition {
+    pub style: TransitionStyle,
+    /// Seconds the effect takes (`/D`).
+    pub duration: f32,
+    /// Split and Blinds lines run vertically (`/Dm /V`).
+    pub vertical: bool,
+    /// Split and Box move outward from the centre (`/M /O`).
+    pub outward: bool,
+    /// Direction of travel in degrees counterclockwise from left to right
+    /// (`/Di`); `None` for `/None`, which Fly uses with a scale change.
+    pub direction: Option<u16>,
+    /// Seconds the page stays up before advancing on its own (the page's
+    /// `/Dur`), if it sets one.
+    pub page_duration: Option<f32>,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self {
+            style: TransitionStyle::Replace,
+            duration: 1.0,
+            vertical: false,
+            outward: false,
+            direction: Some(0),
+            page_duration: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AnnotationDrag {
     pub page: usize,
     pub start: (f32, f32),
     pub current: (f32, f32),
     pub kind: PendingAnnotationKind,
+    /// Pointer trail in rotated, zoomed page pixels; only filled for ink.
+    pub points: Vec<(f32, f32)>,
+}
+
+/// Part of an existing annotation grabbed by the pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationHandle {
+    Move,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// An in-progress move or resize of an existing annotation. `start` is the
+/// pointer position in rotated page units; `original` is the annotation as it
+/// was before the drag began.
+#[derive(Debug, Clone)]
+pub struct AnnotationEdit {
+    pub index: usize,
+    pub handle: AnnotationHandle,
+    pub start: (f32, f32),
+    pub original: Annotation,
+}
+
+/// Drag one corner of an `(x, y, w, h)` rect by `(dx, dy)`, keeping the
+/// opposite corner fixed and never shrinking below `min_size`.
+pub fn resize_rect(
+    rect: (f32, f32, f32, f32),
+    handle: AnnotationHandle,
+    dx: f32,
+    dy: f32,
+    min_size: f32,
+) -> (f32, f32, f32, f32) {
+    let (x, y, w, h) = rect;
+    let (mut left, mut top, mut right, mut bottom) = (x, y, x + w, y + h);
+    match handle {
+        AnnotationHandle::Move => return (x + dx, y + dy, w, h),
+        AnnotationHandle::TopLeft => {
+            left = (left + dx).min(right - min_size);
+            top = (top + dy).min(bottom - min_size);
+        }
+        AnnotationHandle::TopRight => {
+            right = (right + dx).max(left + min_size);
+            top = (top + dy).min(bottom - min_size);
+        }
+        AnnotationHandle::BottomLeft => {
+            left = (left + dx).min(right - min_size);
+            bottom = (bottom + dy).max(top + min_size);
+        }
+        AnnotationHandle::BottomRight => {
+            right = (right + dx).max(left + min_size);
+            bottom = (bottom + dy).max(top + min_size);
+        }
+    }
+    (left, top, right - left, bottom - top)
+}
+
+/// Bookkeeping for one entry of `TabViewState::rendered_pages`.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderedPageUsage {
+    pub bytes: usize,
+    pub last_used: std::time::Instant,
 }
 
 pub struct TabViewState {
     pub rendered_pages: std::collections::HashMap<usize, (f32, iced_image::Handle)>,
+    pub rendered_usage: std::collections::HashMap<usize, RenderedPageUsage>,
     pub thumbnails: std::collections::HashMap<usize, iced_image::Handle>,
     pub text_layers: std::collections::HashMap<usize, Vec<TextItem>>,
     pub detected_tables: std::collections::HashMap<usize, Vec<DetectedTable>>,
@@ -458,6 +911,7 @@ impl Default for TabViewState {
     fn default() -> Self {
         Self {
             rendered_pages: std::collections::HashMap::new(),
+            rendered_usage: std::collections::HashMap::new(),
             thumbnails: std::collections::HashMap::new(),
             text_layers: std::collections::HashMap::new(),
             detected_tables: std::collections::HashMap::new(),
@@ -473,6 +927,19 @@ impl Default for TabViewState {
     }
 }
 
+impl TabViewState {
+    /// Drop every rendered page along with its usage bookkeeping.
+    pub fn clear_rendered_pages(&mut self) {
+        self.rendered_pages.clear();
+        self.rendered_usage.clear();
+    }
+
+    pub fn remove_rendered_page(&mut self, page_idx: usize) {
+        self.rendered_pages.remove(&page_idx);
+        self.rendered_usage.remove(&page_idx);
+    }
+}
+
 pub struct DocumentTab {
     pub id: DocumentId,
     pub path: PathBuf,
@@ -490,6 +957,8 @@ pub struct DocumentTab {
     pub undo_stack: Vec<UndoableAction>,
     pub redo_stack: Vec<UndoableAction>,
     pub outline: Vec<crate::pdf_engine::Bookmark>,
+    /// Indices into `outline` whose children are hidden in the sidebar.
+    pub outline_collapsed: std::collections::HashSet<usize>,
     pub bookmarks: Vec<PageBookmark>,
     pub annotations: Vec<Annotation>,
     pub links: Vec<Hyperlink>,
@@ -497,18 +966,41 @@ pub struct DocumentTab {
     pub view_state: TabViewState,
     pub pending_session: Option<TabSession>,
     pub page_mapping: Vec<usize>,
+    /// Extra clockwise rotation per source page, added to `rotation`.
     pub page_rotations: std::collections::HashMap<usize, i32>,
     #[allow(clippy::type_complexity)]
     pub selection_drag: Option<(usize, (f32, f32), (f32, f32))>,
     pub selected_text: Option<String>,
+    /// Boxes of the selected words, unrotated, on `selected_page`.
     pub selected_boxes: Vec<(f32, f32, f32, f32)>,
+    pub selected_page: Option<usize>,
+    /// Id of the annotation picked for moving or resizing. Clicking an
+    /// unselected annotation only selects it, so text under it stays
+    /// selectable.
+    pub selected_annotation: Option<u64>,
     pub annotations_dirty: bool,
+    /// Set when bookmarks or annotations change; the sidecar is rewritten
+    /// after the current update completes.
+    pub sidecar_dirty: bool,
+    /// Sidecar annotations, already shown, to reconcile with the embedded
+    /// ones in `AnnotationsLoaded`, and whether the sidecar is newer than the
+    /// PDF.
+    pub pending_sidecar_annotations: Option<(Vec<Annotation>, bool)>,
     pub page_labels: Vec<String>,
     pub is_encrypted: bool,
     pub signatures: Vec<SignatureInfo>,
     pub attachments: Vec<AttachmentInfo>,
     pub layers: Vec<LayerInfo>,
     pub oc_config: Option<zpdf::OcConfig>,
+    /// A non-PDF format was converted on opening; operations that load
+    /// the file itself with lopdf can't run on it.
+    pub format: crate::pdf_engine::DocumentFormat,
+    /// What a reflowable document's pages are laid out at; unused for
+    /// fixed layouts.
+    pub reflow_layout: crate::pdf_engine::ReflowLayout,
+    /// Bumped whenever zoom, rotation or filter changes; render results
+    /// tagged with an older generation are discarded.
+    pub render_generation: u64,
 }
 
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -546,6 +1038,7 @@ impl DocumentTab {
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             outline: Vec::new(),
+            outline_collapsed: std::collections::HashSet::new(),
             bookmarks: Vec::new(),
             annotations: Vec::new(),
             links: Vec::new(),
@@ -557,13 +1050,43 @@ impl DocumentTab {
             selection_drag: None,
             selected_text: None,
             selected_boxes: Vec::new(),
+            selected_page: None,
+            selected_annotation: None,
             annotations_dirty: false,
+            sidecar_dirty: false,
+            pending_sidecar_annotations: None,
             page_labels: Vec::new(),
             is_encrypted: false,
             signatures: Vec::new(),
             attachments: Vec::new(),
             layers: Vec::new(),
             oc_config: None,
+            format: crate::pdf_engine::DocumentFormat::Pdf,
+            reflow_layout: crate::pdf_engine::ReflowLayout::default(),
+            render_generation: 0,
+        }
+    }
+
+    /// Rotation a source page is shown with: the document-wide rotation plus
+    /// that page's own correction.
+    #[must_use]
+    pub fn effective_rotation(&self, actual_page: usize) -> i32 {
+        let extra = self.page_rotations.get(&actual_page).copied().unwrap_or(0);
+        (self.rotation + extra).rem_euclid(360)
+    }
+
+    /// The bookmarks, annotations and page rotations kept in the sidecar.
+    #[must_use]
+    pub fn sidecar(&self) -> DocumentSidecar {
+        DocumentSidecar {
+            bookmarks: self.bookmarks.clone(),
+            annotations: self.annotations.clone(),
+            page_rotations: self
+                .page_rotations
+                .iter()
+                .filter(|&(_, &rot)| rot != 0)
+                .map(|(&page, &rot)| (page, rot))
+                .collect(),
         }
     }
 
@@ -644,6 +1167,9 @@ impl DocumentTab {
         self.view_state
             .rendered_pages
             .retain(|&p, _| p >= keep_start && p < keep_end);
+        self.view_state
+            .rendered_usage
+            .retain(|&p, _| p >= keep_start && p < keep_end);
 
         let thumb_start_idx = (self.view_state.sidebar_viewport_y
             / crate::ui::theme::THUMBNAIL_HEIGHT)
@@ -658,6 +1184,76 @@ impl DocumentTab {
         self.view_state.last_cleanup_time = std::time::Instant::now();
     }
 
+    /// Whether the outline entry at `idx` has nested children.
+    #[must_use]
+    pub fn outline_has_children(&self, idx: usize) -> bool {
+        match (self.outline.get(idx), self.outline.get(idx + 1)) {
+            (Some(node), Some(next)) => next.depth > node.depth,
+            _ => false,
+        }
+    }
+
+    /// Indices of outline entries not hidden under a collapsed ancestor.
+    #[must_use]
+    pub fn visible_outline_indices(&self) -> Vec<usize> {
+        let mut visible = Vec::new();
+        let mut hide_below: Option<usize> = None;
+        for (idx, node) in self.outline.iter().enumerate() {
+            if let Some(depth) = hide_below {
+                if node.depth > depth {
+                    continue;
+                }
+                hide_below = None;
+            }
+            visible.push(idx);
+            if self.outline_collapsed.contains(&idx) {
+                hide_below = Some(node.depth);
+            }
+        }
+        visible
+    }
+
+    /// Store a rendered page and record its approximate RGBA footprint.
+    pub fn insert_rendered_page(
+        &mut self,
+        page_idx: usize,
+        scale: f32,
+        width: u32,
+        height: u32,
+        handle: iced_image::Handle,
+    ) {
+        self.view_state
+            .rendered_pages
+            .insert(page_idx, (scale, handle));
+        self.view_state.rendered_usage.insert(
+            page_idx,
+            RenderedPageUsage {
+                bytes: width as usize * height as usize * 4,
+                last_used: std::time::Instant::now(),
+            },
+        );
+    }
+
+    /// Mark the visible pages as recently used so LRU eviction skips them.
+    pub fn touch_visible_pages(&mut self) {
+        let now = std::time::Instant::now();
+        for page_idx in self.get_visible_pages() {
+            if let Some(usage) = self.view_state.rendered_usage.get_mut(&page_idx) {
+                usage.last_used = now;
+            }
+        }
+    }
+
+    /// Approximate decoded bytes held by `rendered_pages`.
+    #[must_use]
+    pub fn rendered_bytes(&self) -> usize {
+        self.view_state
+            .rendered_usage
+            .values()
+            .map(|usage| usage.bytes)
+            .sum()
+    }
+
     pub fn needs_periodic_cleanup(&self) -> bool {
         self.view_state.last_cleanup_time.elapsed().as_secs() >= 5
     }
@@ -685,6 +1281,52 @@ pub fn rotate_coords(
     }
 }
 
+/// Parse a 1-based page list such as `"1-3,7"` into 0-based indices, in the
+/// order written. Empty parts, reversed ranges and pages outside `1..=total`
+/// are rejected with a message suitable for the status bar.
+pub fn parse_page_range(input: &str, total: usize) -> Result<Vec<usize>, String> {
+    let parse_page = |s: &str| -> Result<usize, String> {
+        let page: usize = s
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a page number", s.trim()))?;
+        if page == 0 || page > total {
+            return Err(format!("Page {page} is out of range (1-{total})"));
+        }
+        Ok(page - 1)
+    };
+
+    if input.trim().is_empty() {
+        return Err("Enter pages to print, e.g. 1-3,7".into());
+    }
+
+    let mut pages = Vec::new();
+    for part in input.split(',') {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse_page(start)?, parse_page(end)?);
+                if start > end {
+                    return Err(format!("Range {}-{} is reversed", start + 1, end + 1));
+                }
+                pages.extend(start..=end);
+            }
+            None => pages.push(parse_page(part)?),
+        }
+    }
+    Ok(pages)
+}
+
+/// Bounding box `(x, y, w, h)` of a set of points, or `None` if empty.
+pub fn ink_bounds(points: &[(f32, f32)]) -> Option<(f32, f32, f32, f32)> {
+    let (&(x0, y0), rest) = points.split_first()?;
+    let (min_x, min_y, max_x, max_y) =
+        rest.iter()
+            .fold((x0, y0, x0, y0), |(min_x, min_y, max_x, max_y), &(x, y)| {
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            });
+    Some((min_x, min_y, max_x - min_x, max_y - min_y))
+}
+
 pub fn unrotate_coords(
     x: f32,
     y: f32,
@@ -707,6 +1349,46 @@ pub fn unrotate_coords(
     }
 }
 
+/// The text items touched by a selection dragged from `start` to `end`,
+/// given in on-screen page coordinates at zoom 1, in reading order. The
+/// points are mapped back through `rotation` to the unrotated page the
+/// items are measured on.
+pub fn select_text_items(
+    items: &[TextItem],
+    start: (f32, f32),
+    end: (f32, f32),
+    page_width: f32,
+    page_height: f32,
+    rotation: i32,
+) -> Vec<TextItem> {
+    let (sx, sy, _, _) = unrotate_coords(
+        start.0,
+        start.1,
+        0.0,
+        0.0,
+        page_width,
+        page_height,
+        rotation,
+    );
+    let (ex, ey, _, _) = unrotate_coords(end.0, end.1, 0.0, 0.0, page_width, page_height, rotation);
+    let (x1, x2) = (sx.min(ex), sx.max(ex));
+    let (y1, y2) = (sy.min(ey), sy.max(ey));
+
+    let mut selected: Vec<TextItem> = items
+        .iter()
+        .filter(|w| x1 < w.x + w.width && x2 > w.x && y1 < w.y + w.height && y2 > w.y)
+        .cloned()
+        .collect();
+    selected.sort_by(|a, b| {
+        if (a.y - b.y).abs() < 5.0 {
+            a.x.total_cmp(&b.x)
+        } else {
+            a.y.total_cmp(&b.y)
+        }
+    });
+    selected
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -919,6 +1601,148 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_select_text_items_maps_through_rotation() {
+        let item = |text: &str, x: f32, y: f32| TextItem {
+            text: text.to_string(),
+            x,
+            y,
+            width: 40.0,
+            height: 10.0,
+        };
+        let items = [
+            item("world", 150.0, 100.0),
+            item("hello", 100.0, 100.0),
+            item("below", 100.0, 300.0),
+        ];
+        let (w, h) = (600.0, 800.0);
+        for rotation in [0, 90, 180, 270] {
+            // Drag around where the first line appears once rotated.
+            let (rx, ry, rw, rh) = rotate_coords(100.0, 100.0, 90.0, 10.0, w, h, rotation);
+            let picked = select_text_items(
+                &items,
+                (rx - 2.0, ry - 2.0),
+                (rx + rw + 2.0, ry + rh + 2.0),
+                w,
+                h,
+                rotation,
+            );
+            let texts: Vec<&str> = picked.iter().map(|i| i.text.as_str()).collect();
+            assert_eq!(texts, ["hello", "world"], "rotation {rotation}");
+        }
+    }
+
+    #[test]
+    fn test_effective_rotation_adds_page_rotation() {
+        let mut tab = DocumentTab::new(PathBuf::from("test.pdf"));
+        tab.rotation = 270;
+        tab.page_rotations.insert(2, 180);
+        assert_eq!(tab.effective_rotation(2), 90);
+        assert_eq!(tab.effective_rotation(0), 270);
+    }
+
+    #[test]
+    fn test_parse_page_range() {
+        assert_eq!(parse_page_range("1-3,7", 10), Ok(vec![0, 1, 2, 6]));
+        assert_eq!(parse_page_range(" 5 ", 5), Ok(vec![4]));
+        assert!(parse_page_range("", 10).is_err());
+        assert!(parse_page_range("0", 10).is_err());
+        assert!(parse_page_range("3-11", 10).is_err());
+        assert!(parse_page_range("4-2", 10).is_err());
+        assert!(parse_page_range("1,,2", 10).is_err());
+        assert!(parse_page_range("a-b", 10).is_err());
+    }
+
+    #[test]
+    fn test_ink_bounds() {
+        assert_eq!(ink_bounds(&[]), None);
+        assert_eq!(
+            ink_bounds(&[(10.0, 5.0), (4.0, 20.0), (12.0, 8.0)]),
+            Some((4.0, 5.0, 8.0, 15.0))
+        );
+    }
+
+    #[test]
+    fn test_resize_rect_keeps_opposite_corner() {
+        let rect = (10.0, 20.0, 100.0, 50.0);
+        assert_eq!(
+            resize_rect(rect, AnnotationHandle::BottomRight, 5.0, -10.0, 4.0),
+            (10.0, 20.0, 105.0, 40.0)
+        );
+        assert_eq!(
+            resize_rect(rect, AnnotationHandle::TopLeft, 5.0, 10.0, 4.0),
+            (15.0, 30.0, 95.0, 40.0)
+        );
+        assert_eq!(
+            resize_rect(rect, AnnotationHandle::Move, -5.0, 5.0, 4.0),
+            (5.0, 25.0, 100.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn test_resize_rect_enforces_min_size() {
+        let (_, _, w, h) = resize_rect(
+            (0.0, 0.0, 20.0, 20.0),
+            AnnotationHandle::TopRight,
+            -100.0,
+            100.0,
+            4.0,
+        );
+        assert_eq!((w, h), (4.0, 4.0));
+    }
+
+    #[test]
+    fn test_visible_outline_hides_collapsed_children() {
+        let node = |title: &str, depth| crate::pdf_engine::Bookmark {
+            title: title.to_string(),
+            page_index: 0,
+            depth,
+        };
+        let mut tab = DocumentTab::new(PathBuf::from("/test/doc.pdf"));
+        tab.outline = vec![
+            node("Chapter 1", 0),
+            node("Section 1.1", 1),
+            node("Section 1.1.1", 2),
+            node("Section 1.2", 1),
+            node("Chapter 2", 0),
+        ];
+
+        assert!(tab.outline_has_children(0));
+        assert!(!tab.outline_has_children(3));
+        assert!(!tab.outline_has_children(4));
+        assert_eq!(tab.visible_outline_indices(), vec![0, 1, 2, 3, 4]);
+
+        tab.outline_collapsed.insert(1);
+        assert_eq!(tab.visible_outline_indices(), vec![0, 1, 3, 4]);
+
+        tab.outline_collapsed.insert(0);
+        assert_eq!(tab.visible_outline_indices(), vec![0, 4]);
+    }
+
+    #[test]
+    fn test_rendered_bytes_ignores_evicted_pages() {
+        let mut tab = DocumentTab::new(PathBuf::from("/test/doc.pdf"));
+        tab.total_pages = 20;
+        tab.view_state.visible_range = (0, 2);
+
+        for i in 0..3 {
+            tab.insert_rendered_page(
+                i,
+                1.0,
+                100,
+                50,
+                iced::widget::image::Handle::from_bytes(vec![]),
+            );
+        }
+        assert_eq!(tab.rendered_bytes(), 3 * 100 * 50 * 4);
+
+        tab.view_state.remove_rendered_page(2);
+        assert_eq!(tab.rendered_bytes(), 2 * 100 * 50 * 4);
+
+        tab.view_state.clear_rendered_pages();
+        assert!(tab.view_state.rendered_usage.is_empty());
+    }
+
     #[test]
     fn test_cleanup_distant_pages_removes_zoom_mismatch() {
         let mut tab = DocumentTab::new(PathBuf::from("/test/doc.pdf"));
@@ -1087,6 +1911,7 @@ mod tests {
             x: 50.0,
             width: 200.0,
             height: 20.0,
+            rects: vec![(50.0, 100.0, 200.0, 20.0)],
         };
         let json = serde_json::to_string(&item).unwrap();
         let deserialized: SearchResultItem = serde_json::from_str(&json).unwrap();
@@ -1215,6 +2040,7 @@ mod tests {
             attachments: vec![],
             layers: vec![],
             oc_config: None,
+            format: crate::pdf_engine::DocumentFormat::Pdf,
         };
         let cloned = result.clone();
         assert_eq!(cloned.page_count, 10);
@@ -1230,6 +2056,7 @@ mod tests {
             x: 50.0,
             width: 200.0,
             height: 20.0,
+            rects: vec![(50.0, 100.0, 200.0, 20.0)],
         };
         let cloned = result.clone();
         assert_eq!(cloned.text, "found");
@@ -1245,6 +2072,7 @@ mod tests {
                 vec!["a".to_string(), "b".to_string()],
                 vec!["1".to_string(), "2".to_string()],
             ],
+            grid: crate::tables::Table::default(),
         };
         let json = serde_json::to_string(&table).unwrap();
         let deserialized: DetectedTable = serde_json::from_str(&json).unwrap();
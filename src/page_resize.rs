@@ -0,0 +1,428 @@
+//! Page geometry rewrites: cropping pages to their content and scaling
+//! them onto another paper size.
+//!
+//! Content bounds come from the page's content stream rather than from a
+//! rendering: painted paths, shown text and placed `XObject`s are measured
+//! in default user space, the space the page boxes live in. The measure is
+//! conservative. Curves count their control points, text is given a nominal
+//! advance of half an em per byte, and clipping paths are ignored, so the
+//! bounds may be a little larger than the ink but never miss any of it.
+
+use crate::content_stream::{IDENTITY, Matrix, TokenType, Tokenizer, concat, transform};
+use crate::flow::PageSize;
+use crate::pdf_engine::{PageBox, page_box, page_media_box};
+use lopdf::{Document, Object, ObjectId};
+
+/// Nominal glyph advance, in text space units per byte of a shown string.
+const NOMINAL_ADVANCE: f32 = 0.5;
+/// How far glyphs reach below the baseline, in text space units.
+const NOMINAL_DESCENT: f32 = 0.2;
+
+/// How [`resize_to`] maps a page onto the target size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResizeMode {
+    /// Scale uniformly until the whole page fits, centred.
+    #[default]
+    Fit,
+    /// Scale uniformly until the page covers the target, centred and
+    /// trimmed at the edges.
+    Fill,
+    /// Scale each axis separately to the target, distorting the aspect.
+    Stretch,
+}
+
+/// A growing `[x0, y0, x1, y1]` rectangle.
+#[derive(Debug, Default)]
+struct Bounds(Option<[f32; 4]>);
+
+impl Bounds {
+    fn add(&mut self, (x, y): (f32, f32)) {
+        self.0 = Some(match self.0 {
+            Some([x0, y0, x1, y1]) => [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+            None => [x, y, x, y],
+        });
+    }
+
+    fn add_rect(&mut self, m: &Matrix, [x0, y0, x1, y1]: [f32; 4]) {
+        for (x, y) in [(x0, y0), (x1, y0), (x1, y1), (x0, y1)] {
+            self.add(transform(m, x, y));
+        }
+    }
+
+    /// Add `bytes` bytes of text shown at `tm` and advance past them.
+    fn show_text(&mut self, tm: &mut Matrix, ctm: &Matrix, font_size: f32, bytes: usize) {
+        let advance = bytes as f32 * NOMINAL_ADVANCE * font_size;
+        let rect = [0.0, -NOMINAL_DESCENT * font_size, advance, font_size];
+        self.add_rect(&concat(tm, ctm), rect);
+        *tm = concat(&[1.0, 0.0, 0.0, 1.0, advance, 0.0], tm);
+    }
+}
+
+/// Byte length of the string operands of a text-showing operator.
+fn shown_bytes(text: &[u8]) -> usize {
+    match text {
+        [b'(', body @ .., b')'] => {
+            // An escape and the byte after it show a single byte.
+            let (mut n, mut bytes) = (0, body.iter());
+            while let Some(&b) = bytes.next() {
+                if b == b'\\' {
+                    bytes.next();
+                }
+                n += 1;
+            }
+            n
+        }
+        [b'<', body @ .., b'>'] => body.iter().filter(|b| b.is_ascii_hexdigit()).count() / 2,
+        _ => 0,
+    }
+}
+
+/// The bounding box of everything `content` paints, in default user space,
+/// or `None` when it paints nothing.
+pub fn content_bounds(content: &[u8]) -> Option<[f32; 4]> {
+    let mut tokens = Tokenizer::new(content);
+    let mut operands: Vec<f32> = Vec::new();
+    let mut string_bytes = 0;
+    let mut ctm = IDENTITY;
+    let mut line_width = 1.0_f32;
+    let mut saved: Vec<(Matrix, f32)> = Vec::new();
+    let mut path: Vec<(f32, f32)> = Vec::new();
+    let (mut tm, mut tlm) = (IDENTITY, IDENTITY);
+    let (mut font_size, mut leading) = (0.0_f32, 0.0_f32);
+    let mut bounds = Bounds::default();
+
+    while let Some(token) = tokens.next_token() {
+        match token.kind {
+            TokenType::Operand => {
+                if let Some(n) = std::str::from_utf8(token.text)
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                {
+                    operands.push(n);
+                }
+                string_bytes += shown_bytes(token.text);
+                continue;
+            }
+            TokenType::Operator => {}
+            _ => continue,
+        }
+        match (token.text, operands.as_slice()) {
+            (b"q", _) => saved.push((ctm, line_width)),
+            (b"Q", _) => (ctm, line_width) = saved.pop().unwrap_or((ctm, line_width)),
+            (b"cm", m) if m.len() == 6 => {
+                ctm = concat(&[m[0], m[1], m[2], m[3], m[4], m[5]], &ctm);
+            }
+            (b"w", &[w]) => line_width = w,
+            (b"m" | b"l", &[x, y]) => path.push(transform(&ctm, x, y)),
+            (b"c", &[x1, y1, x2, y2, x3, y3]) => {
+                path.extend([(x1, y1), (x2, y2), (x3, y3)].map(|(x, y)| transform(&ctm, x, y)));
+            }
+            (b"v" | b"y", &[x1, y1, x2, y2]) => {
+                path.extend([(x1, y1), (x2, y2)].map(|(x, y)| transform(&ctm, x, y)));
+            }
+            (b"re", &[x, y, w, h]) => {
+                path.extend(
+                    [(x, y), (x + w, y), (x + w, y + h), (x, y + h)]
+                        .map(|(x, y)| transform(&ctm, x, y)),
+                );
+            }
+            (b"S" | b"s" | b"B" | b"B*" | b"b" | b"b*", _) => {
+                // Strokes reach half the line width past the path.
+                let half = line_width * (ctm[0] * ctm[3] - ctm[1] * ctm[2]).abs().sqrt() / 2.0;
+                for &(x, y) in &path {
+                    bounds.add((x - half, y - half));
+                    bounds.add((x + half, y + half));
+                }
+                path.clear();
+            }
+            (b"f" | b"F" | b"f*", _) => {
+                for &point in &path {
+                    bounds.add(point);
+                }
+                path.clear();
+            }
+            (b"n", _) => path.clear(),
+            (b"Do" | b"sh", _) => bounds.add_rect(&ctm, [0.0, 0.0, 1.0, 1.0]),
+            (b"BT", _) => (tm, tlm) = (IDENTITY, IDENTITY),
+            (b"Tf", &[size]) => font_size = size,
+            (b"TL", &[l]) => leading = l,
+            (b"Td" | b"TD", &[tx, ty]) => {
+                if token.text == b"TD" {
+                    leading = -ty;
+                }
+                tlm = concat(&[1.0, 0.0, 0.0, 1.0, tx, ty], &tlm);
+                tm = tlm;
+            }
+            (b"Tm", m) if m.len() == 6 => {
+                tlm = [m[0], m[1], m[2], m[3], m[4], m[5]];
+                tm = tlm;
+            }
+            (b"T*" | b"'" | b"\"", _) => {
+                tlm = concat(&[1.0, 0.0, 0.0, 1.0, 0.0, -leading], &tlm);
+                tm = tlm;
+                if token.text != b"T*" {
+                    bounds.show_text(&mut tm, &ctm, font_size, string_bytes);
+                }
+            }
+            (b"Tj" | b"TJ", _) => bounds.show_text(&mut tm, &ctm, font_size, string_bytes),
+            (b"ID", _) => {
+                tokens.inline_image_data();
+                bounds.add_rect(&ctm, [0.0, 0.0, 1.0, 1.0]);
+            }
+            _ => {}
+        }
+        operands.clear();
+        string_bytes = 0;
+    }
+    bounds.0
+}
+
+/// Set each page's CropBox to the bounds of its content grown by `margin`
+/// points and clipped to the MediaBox. Pages that paint nothing keep their
+/// boxes. Returns how many pages were cropped.
+pub fn autocrop(doc: &mut Document, margin: f32) -> usize {
+    let mut cropped = 0;
+    for page_id in doc.get_pages().into_values() {
+        let Some([x0, y0, x1, y1]) = content_bounds(&doc.get_page_content(page_id)) else {
+            continue;
+        };
+        let media = page_media_box(doc, page_id);
+        let crop = [
+            (x0 - margin).max(media[0]),
+            (y0 - margin).max(media[1]),
+            (x1 + margin).min(media[2]),
+            (y1 + margin).min(media[3]),
+        ];
+        if crop[0] >= crop[2] || crop[1] >= crop[3] {
+            continue;
+        }
+        if let Ok(page) = doc.get_dictionary_mut(page_id) {
+            page.set(
+                "CropBox",
+                Object::Array(crop.iter().map(|&v| Object::Real(v)).collect()),
+            );
+            cropped += 1;
+        }
+    }
+    cropped
+}
+
+/// The page's `/Rotate`, inherited if need be, as 0, 90, 180 or 270.
+fn page_rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    let mut current = Some(page_id);
+    while let Some(id) = current {
+        let Ok(dict) = doc.get_dictionary(id) else {
+            break;
+        };
+        if let Ok(rotate) = dict.get(b"Rotate").and_then(Object::as_i64) {
+            return rotate.rem_euclid(360);
+        }
+        current = dict.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    0
+}
+
+/// Scale the visible area of every page onto `target`, which is taken in
+/// the page's displayed orientation, so rotated pages stay upright.
+///
+/// Each page's content is wrapped in a `cm` that maps its CropBox onto the
+/// new MediaBox, and annotation rectangles move with it. The old crop,
+/// bleed, trim and art boxes are dropped since they no longer describe
+/// the page. Returns how many pages were resized.
+pub fn resize_to(doc: &mut Document, target: PageSize, mode: ResizeMode) -> usize {
+    let mut resized = 0;
+    for page_id in doc.get_pages().into_values() {
+        let [x0, y0, x1, y1] = page_box(doc, page_id, PageBox::Crop);
+        let (width, height) = (x1 - x0, y1 - y0);
+        if width <= 0.0 || height <= 0.0 {
+            continue;
+        }
+        let (tw, th) = if page_rotation(doc, page_id) % 180 == 90 {
+            (target.height, target.width)
+        } else {
+            (target.width, target.height)
+        };
+        let (sx, sy) = match mode {
+            ResizeMode::Fit => {
+                let s = (tw / width).min(th / height);
+                (s, s)
+            }
+            ResizeMode::Fill => {
+                let s = (tw / width).max(th / height);
+                (s, s)
+            }
+            ResizeMode::Stretch => (tw / width, th / height),
+        };
+        let ex = (tw - width * sx) / 2.0 - x0 * sx;
+        let ey = (th - height * sy) / 2.0 - y0 * sy;
+
+        let head = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::new(),
+            format!("q {sx} 0 0 {sy} {ex} {ey} cm\n").into_bytes(),
+        ));
+        let tail = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::new(),
+            b"Q\n".to_vec(),
+        ));
+        let mut contents = vec![Object::Reference(head)];
+        contents.extend(
+            doc.get_page_contents(page_id)
+                .into_iter()
+                .map(Object::Reference),
+        );
+        contents.push(Object::Reference(tail));
+
+        let annots: Vec<ObjectId> = doc
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(Object::as_array)
+            .map(|annots| {
+                annots
+                    .iter()
+                    .filter_map(|a| a.as_reference().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        for annot_id in annots {
+            let Ok(annot) = doc.get_dictionary_mut(annot_id) else {
+                continue;
+            };
+            let Some([ax0, ay0, ax1, ay1]) = annot
+                .get(b"Rect")
+                .ok()
+                .and_then(|rect| rect.as_array().ok())
+                .and_then(|rect| {
+                    let nums: Vec<f32> = rect.iter().filter_map(|v| v.as_float().ok()).collect();
+                    <[f32; 4]>::try_from(nums).ok()
+                })
+            else {
+                continue;
+            };
+            let rect = [ax0 * sx + ex, ay0 * sy + ey, ax1 * sx + ex, ay1 * sy + ey];
+            annot.set(
+                "Rect",
+                Object::Array(rect.iter().map(|&v| Object::Real(v)).collect()),
+            );
+        }
+
+        if let Ok(page) = doc.get_dictionary_mut(page_id) {
+            page.set("Contents", Object::Array(contents));
+            page.set(
+                "MediaBox",
+                Object::Array([0.0, 0.0, tw, th].map(Object::Real).to_vec()),
+            );
+            for which in [PageBox::Crop, PageBox::Bleed, PageBox::Trim, PageBox::Art] {
+                page.remove(which.key().as_bytes());
+            }
+            resized += 1;
+        }
+    }
+    resized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{Dictionary, Stream};
+
+    fn page_doc(contents: &[&[u8]]) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let tree_id = doc.new_object_id();
+        let kids: Vec<Object> = contents
+            .iter()
+            .map(|content| {
+                let content_id = doc.add_object(Stream::new(Dictionary::new(), content.to_vec()));
+                Object::Reference(doc.add_object(Dictionary::from_iter(vec![
+                    ("Type", Object::Name(b"Page".to_vec())),
+                    ("Parent", Object::Reference(tree_id)),
+                    ("Contents", Object::Reference(content_id)),
+                ])))
+            })
+            .collect();
+        doc.objects.insert(
+            tree_id,
+            Object::Dictionary(Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Count", Object::Integer(kids.len() as i64)),
+                ("Kids", Object::Array(kids)),
+                (
+                    "MediaBox",
+                    Object::Array([0, 0, 612, 792].map(Object::Integer).to_vec()),
+                ),
+            ])),
+        );
+        let catalog_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(tree_id)),
+        ]));
+        doc.trailer.set("Root", Object::Reference(catalog_id));
+        doc
+    }
+
+    #[test]
+    fn test_content_bounds_follow_transforms() {
+        let content = b"q 2 0 0 2 100 100 cm 0 0 10 10 re f Q 5 5 m 6 6 l n \
+            BT /F1 10 Tf 1 0 0 1 300 400 Tm (ab) Tj ET";
+        let [x0, y0, x1, y1] = content_bounds(content).unwrap();
+        assert_eq!([x0, y0], [100.0, 100.0]);
+        assert_eq!([x1, y1], [310.0, 410.0]);
+        assert_eq!(content_bounds(b"q 1 0 0 1 5 5 cm Q"), None);
+    }
+
+    #[test]
+    fn test_autocrop_wraps_centered_rectangle() {
+        let mut doc = page_doc(&[b"0 0 1 rg 286 376 40 40 re f", b""]);
+        assert_eq!(autocrop(&mut doc, 10.0), 1);
+
+        let pages: Vec<_> = doc.get_pages().into_values().collect();
+        let crop: Vec<f32> = doc
+            .get_dictionary(pages[0])
+            .unwrap()
+            .get(b"CropBox")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_float().unwrap())
+            .collect();
+        assert_eq!(crop, [276.0, 366.0, 336.0, 426.0]);
+        // The blank page keeps showing its whole MediaBox.
+        assert!(!doc.get_dictionary(pages[1]).unwrap().has(b"CropBox"));
+    }
+
+    #[test]
+    fn test_resize_letter_to_a4() {
+        let mut doc = page_doc(&[b"0 0 1 rg 286 376 40 40 re f"]);
+        assert_eq!(resize_to(&mut doc, PageSize::A4, ResizeMode::Fit), 1);
+
+        let page_id = *doc.get_pages().values().next().unwrap();
+        let page = doc.get_dictionary(page_id).unwrap();
+        let media: Vec<f32> = page
+            .get(b"MediaBox")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_float().unwrap())
+            .collect();
+        assert_eq!(media, [0.0, 0.0, PageSize::A4.width, PageSize::A4.height]);
+
+        let content = String::from_utf8(doc.get_page_content(page_id)).unwrap();
+        let head = content.lines().next().unwrap();
+        let m: Vec<f32> = head
+            .strip_prefix("q ")
+            .and_then(|m| m.strip_suffix(" cm"))
+            .unwrap()
+            .split(' ')
+            .map(|v| v.parse().unwrap())
+            .collect();
+        let scale = PageSize::A4.width / 612.0;
+        // Letter is squatter than A4, so it is centred vertically.
+        let dy = (PageSize::A4.height - 792.0 * scale) / 2.0;
+        for (got, want) in m.iter().zip([scale, 0.0, 0.0, scale, 0.0, dy]) {
+            assert!((got - want).abs() < 1e-3, "{m:?}");
+        }
+        assert!(content.contains("286 376 40 40 re f"));
+        assert!(content.trim_end().ends_with('Q'));
+    }
+}
@@ -1,15 +1,17 @@
 use crate::models::{
-    Annotation, AnnotationStyle, DocumentId, EngineErrorKind, FormField, FormFieldVariant,
-    Hyperlink, PdfError, PdfResult, SearchResultItem,
+    Annotation, AnnotationStyle, CleanOptions, DocumentId, EngineErrorKind, ExtractedFont,
+    FormField, FormFieldVariant, Hyperlink, LinearizationCheck, LinearizationCheckResult, PdfError,
+    PdfResult, RepairIssue, RepairResult, SearchResultItem, ThreeDInfo, Transition,
+    TransitionStyle, ValidationIssue,
 };
 use lopdf::{Document, Object, ObjectId};
 use quick_cache::{Weighter, sync::Cache};
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use zpdf::{
     ContentInterpreter, FieldKind, FieldValue, FormFiller, ImageCache, IncrementalWriter,
-    PdfDocument, RenderBackend, cpu::CpuRenderer, detect_tables, spans_to_text,
+    PdfDocument, RenderBackend, cpu::CpuRenderer, detect_tables_with_rules, spans_to_text,
     struct_ordered_text,
 };
 use zune_image::codecs::ImageFormat;
@@ -23,6 +25,80 @@ const WHITE_THRESHOLD: u8 = 245;
 const BBOX_MARGIN: u32 = 10;
 const NO_SHADOW_THRESHOLD: u8 = 230;
 
+/// The formats [`DocumentStore::open_document`] can open. Anything other
+/// than PDF is converted to a PDF in memory first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DocumentFormat {
+    #[default]
+    Pdf,
+    Cbz,
+    /// Reflowable; see [`DocumentStore::layout_document`].
+    Epub,
+    Xps,
+}
+
+/// Page size and body text size a reflowable document is laid out at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReflowLayout {
+    pub page: crate::flow::PageSize,
+    pub em: f32,
+}
+
+impl Default for ReflowLayout {
+    fn default() -> Self {
+        Self {
+            page: crate::epub::DEFAULT_PAGE,
+            em: crate::epub::DEFAULT_EM,
+        }
+    }
+}
+
+impl DocumentFormat {
+    /// Detect the format from the file's leading bytes, falling back to
+    /// its extension, and to PDF when neither is recognised.
+    pub fn detect(path: &str, data: &[u8]) -> Self {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        // EPUBs start with an uncompressed `mimetype` entry naming the format.
+        let epub_magic = data.get(30..58) == Some(b"mimetypeapplication/epub+zip".as_slice());
+        if data.starts_with(b"%PDF") {
+            Self::Pdf
+        } else if epub_magic || extension.as_deref() == Some("epub") {
+            Self::Epub
+        } else if matches!(extension.as_deref(), Some("xps" | "oxps")) {
+            Self::Xps
+        } else if crate::archive::is_zip(data) || extension.as_deref() == Some("cbz") {
+            Self::Cbz
+        } else {
+            Self::Pdf
+        }
+    }
+
+    /// Whether the document was converted to PDF on opening, and so has no
+    /// PDF file on disk for operations that load one.
+    pub const fn is_converted(self) -> bool {
+        !matches!(self, Self::Pdf)
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Pdf => "PDF",
+            Self::Cbz => "CBZ",
+            Self::Epub => "EPUB",
+            Self::Xps => "XPS",
+        }
+    }
+}
+
+fn pdf_bytes(mut doc: Document) -> PdfResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes)
+        .map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+    Ok(bytes)
+}
+
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
 pub struct RenderKey {
     pub doc_id: DocumentId,
@@ -30,6 +106,8 @@ pub struct RenderKey {
     pub scale: u32,
     pub auto_crop: bool,
     pub quality: RenderQuality,
+    /// Rotation added to the page's own, normalized to 0, 90, 180 or 270.
+    pub rotation: i32,
 }
 
 #[derive(Clone)]
@@ -71,10 +149,69 @@ impl RenderCache {
     pub fn remove(&self, key: &RenderKey) {
         self.cache.remove(key);
     }
+
+    /// Drop every render of `doc_id`, including those other workers made.
+    pub fn remove_document(&self, doc_id: DocumentId) {
+        self.cache.retain(|key, _| key.doc_id != doc_id);
+    }
+
+    /// How many renders of `doc_id` the cache holds.
+    pub fn document_len(&self, doc_id: DocumentId) -> usize {
+        self.cache
+            .iter()
+            .filter(|(key, _)| key.doc_id == doc_id)
+            .count()
+    }
 }
 
 pub type SharedRenderCache = Arc<RenderCache>;
 
+/// How many pages keep their display lists; the oldest is dropped first.
+const MAX_DISPLAY_LISTS: usize = 16;
+
+/// A page's content interpreted once, together with the fonts and images it
+/// draws with, so it can be rasterized again at another zoom without
+/// reparsing. Rotation is fixed when the list is made.
+pub struct DisplayList {
+    replay: Box<dyn Fn(f32) -> PdfResult<crate::models::RenderResult>>,
+}
+
+impl DisplayList {
+    /// Rasterize the list as RGBA at `scale` pixels per point.
+    pub fn render(&self, scale: f32) -> PdfResult<crate::models::RenderResult> {
+        (self.replay)(scale)
+    }
+}
+
+impl std::fmt::Debug for DisplayList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DisplayList").finish_non_exhaustive()
+    }
+}
+
+/// An area of a page render, in pixels from the top left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Copy `rect` out of an RGBA render.
+fn crop_rgba(image: &crate::models::RenderResult, rect: TileRect) -> crate::models::RenderResult {
+    let mut data = Vec::with_capacity((rect.width * rect.height * 4) as usize);
+    for y in rect.y..rect.y + rect.height {
+        let start = ((y * image.width + rect.x) * 4) as usize;
+        data.extend_from_slice(&image.data[start..start + rect.width as usize * 4]);
+    }
+    crate::models::RenderResult {
+        width: rect.width,
+        height: rect.height,
+        data: data.into(),
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize, Hash, Eq)]
 pub enum RenderQuality {
     Low,
@@ -103,12 +240,142 @@ pub struct RenderOptions {
     pub quality: RenderQuality,
 }
 
+/// Placement and look of a text watermark. `x`/`y` anchor the text centre in
+/// PDF user space; `None` centres it on each page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatermarkOptions {
+    pub font_size: f32,
+    pub opacity: f32,
+    pub angle_degrees: f32,
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        Self {
+            font_size: 48.0,
+            opacity: 0.3,
+            angle_degrees: 45.0,
+            x: None,
+            y: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartKind {
+    #[default]
+    Bar,
+    Line,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChartSeries {
+    pub name: String,
+    #[serde(default)]
+    pub values: Vec<f64>,
+    /// `#rrggbb`; a palette colour is used when absent.
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// A chart to draw onto a page, typically read from JSON. `rect` is
+/// `[x, y, width, height]` in PDF user space and `page` is 0-based, with
+/// `None` meaning the first page.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChartSpec {
+    #[serde(default)]
+    pub kind: ChartKind,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub series: Vec<ChartSeries>,
+    #[serde(default)]
+    pub page: Option<usize>,
+    #[serde(default = "ChartSpec::default_rect")]
+    pub rect: [f32; 4],
+}
+
+impl ChartSpec {
+    const fn default_rect() -> [f32; 4] {
+        [72.0, 432.0, 468.0, 288.0]
+    }
+}
+
+/// The rectangles a page may define, from the MediaBox inwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PageBox {
+    Media,
+    Crop,
+    Bleed,
+    Trim,
+    Art,
+}
+
+impl PageBox {
+    pub const ALL: [Self; 5] = [Self::Media, Self::Crop, Self::Bleed, Self::Trim, Self::Art];
+
+    pub const fn key(self) -> &'static str {
+        match self {
+            Self::Media => "MediaBox",
+            Self::Crop => "CropBox",
+            Self::Bleed => "BleedBox",
+            Self::Trim => "TrimBox",
+            Self::Art => "ArtBox",
+        }
+    }
+
+    /// Parse a box name such as `CropBox` or `crop`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        match name.strip_suffix("box").unwrap_or(&name) {
+            "media" => Some(Self::Media),
+            "crop" => Some(Self::Crop),
+            "bleed" => Some(Self::Bleed),
+            "trim" => Some(Self::Trim),
+            "art" => Some(Self::Art),
+            _ => None,
+        }
+    }
+}
+
+/// Counts of what a [`DocumentStore`] still holds, for spotting documents
+/// whose state outlives `close_document`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LiveResources {
+    pub documents: usize,
+    pub paths: usize,
+    pub cached_renders: usize,
+    pub layer_configs: usize,
+    pub display_lists: usize,
+    pub reflowable: usize,
+}
+
+impl LiveResources {
+    pub const fn is_empty(&self) -> bool {
+        self.documents == 0
+            && self.paths == 0
+            && self.cached_renders == 0
+            && self.layer_configs == 0
+            && self.display_lists == 0
+            && self.reflowable == 0
+    }
+}
+
 pub struct DocumentStore {
     documents: HashMap<DocumentId, PdfDocument>,
     paths: HashMap<DocumentId, String>,
     render_cache: SharedRenderCache,
-    cache_keys: HashMap<DocumentId, Vec<RenderKey>>,
     oc_configs: HashMap<DocumentId, zpdf::OcConfig>,
+    /// Keyed by document, page and total rotation, oldest first.
+    display_lists: Vec<((DocumentId, usize, i32), DisplayList)>,
+    /// Sources of documents whose pages are laid out on open, such as EPUB,
+    /// with the layout their current pages were made at.
+    reflowable: HashMap<DocumentId, (crate::epub::EpubBook, ReflowLayout)>,
 }
 
 // DocumentState wrapper removed as it was a single-field struct.
@@ -119,8 +386,9 @@ impl DocumentStore {
             documents: HashMap::new(),
             paths: HashMap::new(),
             render_cache: cache,
-            cache_keys: HashMap::new(),
             oc_configs: HashMap::new(),
+            display_lists: Vec::new(),
+            reflowable: HashMap::new(),
         }
     }
 
@@ -135,6 +403,67 @@ impl DocumentStore {
         doc_id: DocumentId,
     ) -> PdfResult<crate::models::OpenResult> {
         let data = std::fs::read(path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        self.reflowable.remove(&doc_id);
+        let format = DocumentFormat::detect(path, &data);
+        let mut result = match format {
+            DocumentFormat::Pdf => self.open_pdf_data(data, path, password, doc_id),
+            DocumentFormat::Cbz => {
+                let data = pdf_bytes(crate::cbz::cbz_to_pdf(data)?)?;
+                self.open_pdf_data(data, path, password, doc_id)
+            }
+            DocumentFormat::Xps => {
+                let data = pdf_bytes(crate::xps::xps_to_pdf(data)?)?;
+                self.open_pdf_data(data, path, password, doc_id)
+            }
+            DocumentFormat::Epub => {
+                let book = crate::epub::EpubBook::open(data)?;
+                let layout = ReflowLayout::default();
+                let data = pdf_bytes(book.layout(layout.page, layout.em)?)?;
+                let result = self.open_pdf_data(data, path, password, doc_id)?;
+                self.reflowable.insert(doc_id, (book, layout));
+                Ok(result)
+            }
+        }?;
+        result.format = format;
+        Ok(result)
+    }
+
+    /// The layout a reflowable document's pages were last made at, or
+    /// `None` for fixed-layout documents.
+    pub fn reflow_layout(&self, doc_id: DocumentId) -> Option<ReflowLayout> {
+        self.reflowable.get(&doc_id).map(|(_, layout)| *layout)
+    }
+
+    /// Lay a reflowable document out again, replacing its pages.
+    pub fn layout_document(
+        &mut self,
+        doc_id: DocumentId,
+        layout: ReflowLayout,
+    ) -> PdfResult<crate::models::OpenResult> {
+        let (book, _) = self
+            .reflowable
+            .get(&doc_id)
+            .ok_or_else(|| PdfError::from("Document has a fixed layout"))?;
+        let path = self.paths.get(&doc_id).cloned().unwrap_or_default();
+        let data = pdf_bytes(book.layout(layout.page, layout.em)?)?;
+        // Renders of the old layout are keyed by page number, so they would
+        // be served for the wrong text.
+        self.render_cache.remove_document(doc_id);
+        let mut result = self.open_pdf_data(data, &path, None, doc_id)?;
+        result.format = DocumentFormat::Epub;
+        if let Some((_, current)) = self.reflowable.get_mut(&doc_id) {
+            *current = layout;
+        }
+        Ok(result)
+    }
+
+    fn open_pdf_data(
+        &mut self,
+        data: Vec<u8>,
+        path: &str,
+        password: Option<&str>,
+        doc_id: DocumentId,
+    ) -> PdfResult<crate::models::OpenResult> {
         let doc = match PdfDocument::open_with_password(data, password.unwrap_or("").as_bytes()) {
             Ok(doc) => doc,
             Err(zpdf::Error::WrongPassword) => {
@@ -278,6 +607,7 @@ impl DocumentStore {
         }
         self.documents.insert(doc_id, doc);
         self.paths.insert(doc_id, path.to_string());
+        self.forget_display_lists(doc_id);
 
         Ok(crate::models::OpenResult {
             id: doc_id,
@@ -293,6 +623,7 @@ impl DocumentStore {
             attachments,
             layers,
             oc_config,
+            format: DocumentFormat::Pdf,
         })
     }
 
@@ -451,6 +782,7 @@ impl DocumentStore {
 
         if let Some(oc) = &oc_config {
             self.oc_configs.insert(doc_id, oc.clone());
+            self.forget_display_lists(doc_id);
         }
 
         Ok(crate::models::DocumentMeta {
@@ -469,12 +801,10 @@ impl DocumentStore {
     pub fn close_document(&mut self, doc_id: DocumentId) {
         self.documents.remove(&doc_id);
         self.paths.remove(&doc_id);
+        self.reflowable.remove(&doc_id);
         self.oc_configs.remove(&doc_id);
-        if let Some(doc_keys) = self.cache_keys.remove(&doc_id) {
-            for key in doc_keys {
-                self.render_cache.remove(&key);
-            }
-        }
+        self.forget_display_lists(doc_id);
+        self.render_cache.remove_document(doc_id);
     }
 
     pub fn toggle_layer(&mut self, doc_id: DocumentId, object_id: (u32, u16), visible: bool) {
@@ -498,14 +828,31 @@ impl DocumentStore {
                     mirror.off.insert(id);
                 }
             }
-            if let Some(keys) = self.cache_keys.get(&doc_id) {
-                for key in keys {
-                    self.render_cache.remove(key);
-                }
-            }
+            self.render_cache.remove_document(doc_id);
+            self.forget_display_lists(doc_id);
+        }
+    }
+
+    /// What the store holds right now, across every open document.
+    pub fn live_resources(&self) -> LiveResources {
+        LiveResources {
+            documents: self.documents.len(),
+            paths: self.paths.len(),
+            cached_renders: self
+                .documents
+                .keys()
+                .map(|&doc_id| self.render_cache.document_len(doc_id))
+                .sum(),
+            layer_configs: self.oc_configs.len(),
+            display_lists: self.display_lists.len(),
+            reflowable: self.reflowable.len(),
         }
     }
 
+    fn forget_display_lists(&mut self, doc_id: DocumentId) {
+        self.display_lists.retain(|((id, _, _), _)| *id != doc_id);
+    }
+
     pub fn get_attachment_bytes(
         &self,
         doc_id: DocumentId,
@@ -727,10 +1074,49 @@ impl DocumentStore {
                             })
                         }
                     }
+                    "Ink" => {
+                        let to_f32 = |o: &Object| match o {
+                            Object::Real(v) => *v as f32,
+                            Object::Integer(v) => *v as f32,
+                            _ => 0.0_f32,
+                        };
+                        let strokes: Vec<Vec<(f32, f32)>> = dict
+                            .get(b"InkList")
+                            .ok()
+                            .and_then(|o| o.as_array().ok())
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|o| o.as_array().ok())
+                            .map(|coords| {
+                                coords
+                                    .chunks_exact(2)
+                                    .map(|c| (to_f32(&c[0]) - x, page_height - to_f32(&c[1]) - y))
+                                    .collect::<Vec<_>>()
+                            })
+                            .filter(|points| !points.is_empty())
+                            .collect();
+                        (!strokes.is_empty()).then_some(AnnotationStyle::Ink {
+                            color: color_str,
+                            thickness,
+                            strokes,
+                        })
+                    }
                     _ => None,
                 };
 
-                if let Some(style) = style {
+                if let Some(mut style) = style {
+                    let (mut x, mut y, mut w, mut h) = (x, y, w, h);
+                    // Fit ink to its strokes rather than the /Rect, which is
+                    // padded by the line width.
+                    if let AnnotationStyle::Ink { strokes, .. } = &mut style
+                        && let Some((bx, by, bw, bh)) = crate::models::ink_bounds(&strokes.concat())
+                    {
+                        for point in strokes.iter_mut().flatten() {
+                            point.0 -= bx;
+                            point.1 -= by;
+                        }
+                        (x, y, w, h) = (x + bx, y + by, bw, bh);
+                    }
                     annotations.push(Annotation {
                         id: crate::models::next_annotation_id(),
                         page: page_idx,
@@ -769,6 +1155,7 @@ impl DocumentStore {
             } else {
                 options.quality
             },
+            rotation: options.rotation.rem_euclid(360),
         };
 
         if let Some(base) = self.render_cache.get(&cache_key) {
@@ -784,37 +1171,9 @@ impl DocumentStore {
             });
         }
 
-        let doc = self
-            .documents
-            .get(&doc_id)
-            .ok_or(PdfError::EngineError(EngineErrorKind::DocumentNotFound))?;
-        let page = doc
-            .page(page_num)
-            .map_err(|_| PdfError::PageNotFound(page_num))?;
-
-        let mut fonts = doc.load_page_fonts(&page);
-        let mut images = ImageCache::new();
-        let content = doc
-            .page_content_bytes(&page)
-            .map_err(|e| PdfError::RenderFailed(e.to_string()))?;
-
-        // Incorporate custom option rotation into the display list rotation
-        let mut interp = ContentInterpreter::new(page.effective_box())
-            .with_page_rotation(page.rotate + options.rotation)
-            .with_fonts(&mut fonts)
-            .with_document(doc.file(), &page.resources)
-            .with_images(&mut images);
-
-        if let Some(oc) = self.oc_configs.get(&doc_id) {
-            interp = interp.with_optional_content(oc);
-        }
-
-        let display_list = interp.interpret(&content);
-
-        let mut renderer = CpuRenderer::new().with_fonts(&fonts).with_images(&images);
-        let page_img = renderer
-            .render_display_list(&display_list, options.scale)
-            .map_err(|e| PdfError::RenderFailed(e.to_string()))?;
+        let page_img = self
+            .cached_display_list(doc_id, page_num, options.rotation)?
+            .render(options.scale)?;
         let w = page_img.width;
         let h = page_img.height;
 
@@ -830,7 +1189,7 @@ impl DocumentStore {
                     let end = ((y * w + x2 + 1) * 4) as usize;
                     cropped.extend_from_slice(&result_data[start..end]);
                 }
-                (crop_w, crop_h, cropped)
+                (crop_w, crop_h, cropped.into())
             } else {
                 (w, h, result_data)
             }
@@ -841,13 +1200,9 @@ impl DocumentStore {
         let base = crate::models::RenderResult {
             width: final_w,
             height: final_h,
-            data: final_data.into(),
+            data: final_data,
         };
 
-        self.cache_keys
-            .entry(doc_id)
-            .or_default()
-            .push(cache_key.clone());
         self.render_cache.put(cache_key, base.clone());
 
         if options.filter == RenderFilter::None {
@@ -863,6 +1218,79 @@ impl DocumentStore {
         }
     }
 
+    /// Interpret a page into a [`DisplayList`], with `rotation` added to
+    /// the page's own. Layers hidden with [`Self::toggle_layer`] are left
+    /// out.
+    pub fn run_page_to_display_list(
+        &self,
+        doc_id: DocumentId,
+        page_num: usize,
+        rotation: i32,
+    ) -> PdfResult<DisplayList> {
+        let doc = self
+            .documents
+            .get(&doc_id)
+            .ok_or(PdfError::EngineError(EngineErrorKind::DocumentNotFound))?;
+        let page = doc
+            .page(page_num)
+            .map_err(|_| PdfError::PageNotFound(page_num))?;
+
+        let mut fonts = doc.load_page_fonts(&page);
+        let mut images = ImageCache::new();
+        let content = doc
+            .page_content_bytes(&page)
+            .map_err(|e| PdfError::RenderFailed(e.to_string()))?;
+
+        // Incorporate custom option rotation into the display list rotation
+        let mut interp = ContentInterpreter::new(page.effective_box())
+            .with_page_rotation(page.rotate + rotation)
+            .with_fonts(&mut fonts)
+            .with_document(doc.file(), &page.resources)
+            .with_images(&mut images);
+
+        if let Some(oc) = self.oc_configs.get(&doc_id) {
+            interp = interp.with_optional_content(oc);
+        }
+
+        let display_list = interp.interpret(&content);
+
+        Ok(DisplayList {
+            replay: Box::new(move |scale| {
+                let mut renderer = CpuRenderer::new().with_fonts(&fonts).with_images(&images);
+                let page_img = renderer
+                    .render_display_list(&display_list, scale)
+                    .map_err(|e| PdfError::RenderFailed(e.to_string()))?;
+                Ok(crate::models::RenderResult {
+                    width: page_img.width,
+                    height: page_img.height,
+                    data: page_img.data.into(),
+                })
+            }),
+        })
+    }
+
+    /// The page's display list, interpreting it only on first use.
+    fn cached_display_list(
+        &mut self,
+        doc_id: DocumentId,
+        page_num: usize,
+        rotation: i32,
+    ) -> PdfResult<&DisplayList> {
+        let key = (doc_id, page_num, rotation);
+        let index = match self.display_lists.iter().position(|(k, _)| *k == key) {
+            Some(index) => index,
+            None => {
+                let list = self.run_page_to_display_list(doc_id, page_num, rotation)?;
+                if self.display_lists.len() >= MAX_DISPLAY_LISTS {
+                    self.display_lists.remove(0);
+                }
+                self.display_lists.push((key, list));
+                self.display_lists.len() - 1
+            }
+        };
+        Ok(&self.display_lists[index].1)
+    }
+
     pub fn render_page(
         &mut self,
         doc_id: DocumentId,
@@ -872,6 +1300,65 @@ impl DocumentStore {
         self.render_page_internal(doc_id, page_num, options, false)
     }
 
+    /// Find and decode QR and Code 128 barcodes on a page, or within
+    /// `region` (x, y, width, height in points from the page's top left).
+    /// Results are in reading order by their top edge, with bounding boxes
+    /// in the same space as `region`.
+    pub fn scan_barcodes(
+        &mut self,
+        doc_id: DocumentId,
+        page_num: usize,
+        region: Option<(f32, f32, f32, f32)>,
+    ) -> PdfResult<Vec<crate::barcode::BarcodeResult>> {
+        /// Pixels per point: QR modules printed at a point or more still
+        /// come out three pixels wide.
+        const SCAN_SCALE: f32 = 3.0;
+
+        let page_img = self
+            .cached_display_list(doc_id, page_num, 0)?
+            .render(SCAN_SCALE)?;
+        let rect = match region {
+            Some((x, y, width, height)) => {
+                let to_px = |v: f32, max: u32| ((v * SCAN_SCALE).max(0.0) as u32).min(max);
+                let (x0, y0) = (to_px(x, page_img.width), to_px(y, page_img.height));
+                TileRect {
+                    x: x0,
+                    y: y0,
+                    width: to_px(x + width, page_img.width).saturating_sub(x0),
+                    height: to_px(y + height, page_img.height).saturating_sub(y0),
+                }
+            }
+            None => TileRect {
+                x: 0,
+                y: 0,
+                width: page_img.width,
+                height: page_img.height,
+            },
+        };
+        let area = crop_rgba(&page_img, rect);
+        let gray: Vec<u8> = area
+            .data
+            .chunks_exact(4)
+            .map(|p| {
+                ((u32::from(p[0]) * 299 + u32::from(p[1]) * 587 + u32::from(p[2]) * 114) / 1000)
+                    as u8
+            })
+            .collect();
+        let mut results =
+            crate::barcode::scan_barcodes(&gray, area.width as usize, area.height as usize);
+        for result in &mut results {
+            let (x, y, width, height) = result.bbox;
+            result.bbox = (
+                (x + rect.x as f32) / SCAN_SCALE,
+                (y + rect.y as f32) / SCAN_SCALE,
+                width / SCAN_SCALE,
+                height / SCAN_SCALE,
+            );
+        }
+        results.sort_by(|a, b| a.bbox.1.total_cmp(&b.bbox.1));
+        Ok(results)
+    }
+
     pub fn render_thumbnail(
         &mut self,
         doc_id: DocumentId,
@@ -961,6 +1448,74 @@ impl DocumentStore {
         Ok(text_items)
     }
 
+    /// The page's text as blocks of lines in reading order; see
+    /// [`crate::stext`].
+    pub fn extract_structured_text(
+        &self,
+        doc_id: DocumentId,
+        page_num: usize,
+    ) -> PdfResult<Vec<crate::stext::TextBlock>> {
+        Ok(crate::stext::structure_spans(
+            self.stext_spans(doc_id, page_num)?,
+        ))
+    }
+
+    fn stext_spans(
+        &self,
+        doc_id: DocumentId,
+        page_num: usize,
+    ) -> PdfResult<Vec<crate::stext::StextSpan>> {
+        Ok(self
+            .extract_text_items(doc_id, page_num)?
+            .into_iter()
+            .map(|item| crate::stext::StextSpan {
+                bbox: crate::stext::Rect {
+                    x0: item.x,
+                    y0: item.y - item.height,
+                    x1: item.x + item.width,
+                    y1: item.y,
+                },
+                text: item.text,
+                font: None,
+                size: item.height,
+            })
+            .collect())
+    }
+
+    pub fn extract_stext_page(
+        &self,
+        doc_id: DocumentId,
+        page_num: usize,
+    ) -> PdfResult<crate::stext::StextPage> {
+        let doc = self
+            .documents
+            .get(&doc_id)
+            .ok_or(PdfError::EngineError(EngineErrorKind::DocumentNotFound))?;
+        let rect = doc
+            .page(page_num)
+            .map_err(|_| PdfError::PageNotFound(page_num))?
+            .effective_box();
+        Ok(crate::stext::StextPage {
+            width: rect.width() as f32,
+            height: rect.height() as f32,
+            blocks: self.extract_structured_text(doc_id, page_num)?,
+        })
+    }
+
+    /// The whole document as positioned HTML; see
+    /// [`crate::stext::stext_document_to_html`].
+    pub fn export_stext_html(&self, doc_id: DocumentId) -> PdfResult<String> {
+        let page_count = self
+            .documents
+            .get(&doc_id)
+            .ok_or(PdfError::EngineError(EngineErrorKind::DocumentNotFound))?
+            .page_count();
+        let pages = (0..page_count)
+            .map(|i| self.extract_stext_page(doc_id, i))
+            .collect::<PdfResult<Vec<_>>>()?;
+        Ok(crate::stext::stext_document_to_html(&pages))
+    }
+
     pub fn detect_tables_on_page(
         &self,
         doc_id: DocumentId,
@@ -980,16 +1535,18 @@ impl DocumentStore {
             .map_err(|e| PdfError::SearchError(e.to_string()))?;
 
         let mut spans = Vec::new();
+        let mut rules = Vec::new();
         {
             let interp = ContentInterpreter::new(page.effective_box())
                 .with_fonts(&mut fonts)
                 .with_document(doc.file(), &page.resources)
                 .with_images(&mut images)
-                .with_text_sink(&mut spans);
+                .with_text_sink(&mut spans)
+                .with_rule_sink(&mut rules);
             let _ = interp.interpret(&content);
         }
 
-        let tables = detect_tables(&spans);
+        let tables = detect_tables_with_rules(&spans, &rules);
         let page_height = page.effective_box().height() as f32;
 
         let detected = tables
@@ -1008,6 +1565,7 @@ impl DocumentStore {
                     bbox,
                     csv: t.to_csv(),
                     tsv: t.to_tsv(),
+                    grid: crate::tables::Table::from_detected(&t, page_height),
                     cells: t.cells,
                 }
             })
@@ -1325,6 +1883,73 @@ impl DocumentStore {
                         )]);
                         annot_dict.set("BS", Object::Dictionary(border));
                     }
+                    AnnotationStyle::Ink {
+                        color,
+                        thickness,
+                        strokes,
+                    } => {
+                        let (r, g, b) = hex_to_rgb(color);
+                        annot_dict.set("Subtype", Object::Name(b"Ink".to_vec()));
+                        // Pad by half the line width so round caps aren't clipped.
+                        let pad = thickness / 2.0;
+                        let rect = [
+                            pdf_x - pad,
+                            pdf_y - pad,
+                            pdf_x + pdf_w + pad,
+                            pdf_y + pdf_h + pad,
+                        ];
+                        annot_dict.set(
+                            "Rect",
+                            Object::Array(rect.iter().map(|&v| Object::Real(v)).collect()),
+                        );
+                        let page_strokes: Vec<Vec<(f32, f32)>> = strokes
+                            .iter()
+                            .map(|points| {
+                                points
+                                    .iter()
+                                    .map(|&(px, py)| (ann.x + px, page_height - (ann.y + py)))
+                                    .collect()
+                            })
+                            .collect();
+                        let ink_list = page_strokes
+                            .iter()
+                            .map(|points| {
+                                Object::Array(
+                                    points
+                                        .iter()
+                                        .flat_map(|&(px, py)| [Object::Real(px), Object::Real(py)])
+                                        .collect(),
+                                )
+                            })
+                            .collect();
+                        annot_dict.set("InkList", Object::Array(ink_list));
+                        let appearance = doc.add_object(ink_appearance(
+                            &page_strokes,
+                            rect,
+                            (r, g, b),
+                            *thickness,
+                        ));
+                        annot_dict.set(
+                            "AP",
+                            lopdf::Dictionary::from_iter(vec![(
+                                "N",
+                                Object::Reference(appearance),
+                            )]),
+                        );
+                        annot_dict.set(
+                            "C",
+                            Object::Array(vec![
+                                Object::Real(r as f32),
+                                Object::Real(g as f32),
+                                Object::Real(b as f32),
+                            ]),
+                        );
+                        let border = lopdf::Dictionary::from_iter(vec![(
+                            "W",
+                            Object::Real(*thickness as f32),
+                        )]);
+                        annot_dict.set("BS", Object::Dictionary(border));
+                    }
                 }
 
                 let annot_id = doc.add_object(Object::Dictionary(annot_dict));
@@ -1398,6 +2023,7 @@ impl DocumentStore {
             scale: (scale * 100.0).round() as u32,
             auto_crop: false,
             quality: RenderQuality::Medium,
+            rotation: 0,
         };
 
         if let Some(cached_res) = self.render_cache.get(&cache_key) {
@@ -1463,6 +2089,7 @@ impl DocumentStore {
             out.push(Bookmark {
                 title: item.title.clone(),
                 page_index: page_idx,
+                depth,
             });
             Self::flatten_outline(&item.children, out, depth + 1);
         }
@@ -1613,19 +2240,35 @@ impl DocumentStore {
                     .unwrap_or(full_text.len());
                 let matched_text = full_text[orig_start..orig_end].to_string();
 
-                if let Some(&(_, _, span_idx)) = span_offsets
+                let rects: Vec<(f32, f32, f32, f32)> = span_offsets
                     .iter()
-                    .find(|(s, e, _)| orig_start >= *s && orig_start < *e)
-                {
-                    let first_span = &spans[span_idx];
-                    let y_top_down = page_height - first_span.y as f32 - first_span.size;
+                    .filter(|(s, e, _)| orig_start < *e && orig_end > *s)
+                    .map(|&(s, e, span_idx)| {
+                        Self::span_match_rect(
+                            &spans[span_idx],
+                            &full_text[s..e],
+                            orig_start.saturating_sub(s),
+                            orig_end.min(e) - s,
+                            page_height,
+                        )
+                    })
+                    .collect();
+
+                if let Some(&(x, y, width, height)) = rects.first() {
+                    let (x0, y0, x1, y1) = rects.iter().fold(
+                        (x, y, x + width, y + height),
+                        |(x0, y0, x1, y1), &(rx, ry, rw, rh)| {
+                            (x0.min(rx), y0.min(ry), x1.max(rx + rw), y1.max(ry + rh))
+                        },
+                    );
                     results.push(SearchResultItem {
                         page_index: page_idx,
                         text: matched_text,
-                        y: y_top_down,
-                        x: first_span.x as f32,
-                        width: first_span.advance.abs() as f32,
-                        height: first_span.size,
+                        y: y0,
+                        x: x0,
+                        width: x1 - x0,
+                        height: y1 - y0,
+                        rects,
                     });
                 }
 
@@ -1640,6 +2283,29 @@ impl DocumentStore {
         Ok(results)
     }
 
+    /// Box covering bytes `start..end` of a span's text, assuming glyphs share
+    /// the span's advance evenly. Returns top-down page coordinates.
+    fn span_match_rect(
+        span: &zpdf::TextSpan,
+        span_text: &str,
+        start: usize,
+        end: usize,
+        page_height: f32,
+    ) -> (f32, f32, f32, f32) {
+        let total_chars = span_text.chars().count().max(1) as f32;
+        let chars_before = span_text[..start].chars().count() as f32;
+        let match_chars = span_text[start..end].chars().count() as f32;
+        let advance = span.advance.abs() as f32;
+        let char_width = advance / total_chars;
+
+        (
+            span.x as f32 + char_width * chars_before,
+            page_height - span.y as f32 - span.size,
+            char_width * match_chars,
+            span.size,
+        )
+    }
+
     fn detect_content_bbox_parallel(
         data: &[u8],
         width: u32,
@@ -1781,15 +2447,53 @@ impl DocumentStore {
 
     // apply_filter_parallel removed as it was just a misleading wrapper.
 
-    pub fn optimize_pdf(&self, input_path: &str, output_path: &str) -> PdfResult<String> {
+    /// Recompress streams, merge byte-identical streams and drop unreferenced
+    /// objects. Returns the output path and how many bytes were saved
+    /// (negative if the file grew). `output_path` may equal `input_path`.
+    pub fn optimize_pdf(&self, input_path: &str, output_path: &str) -> PdfResult<(String, i64)> {
+        let before = std::fs::metadata(input_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?
+            .len();
         let mut doc =
             Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
         doc.compress();
         let _ = doc.trailer.remove(b"Info");
+        dedupe_streams(&mut doc);
         doc.prune_objects();
-        doc.save(output_path)
+
+        // Write beside the target and rename so optimizing in place never
+        // leaves a half-written file; the temporary file is deleted if
+        // anything fails. Object streams pack the many small dictionaries;
+        // encrypted files keep the classic layout.
+        let dir = match std::path::Path::new(output_path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => std::path::Path::new("."),
+        };
+        let tmp = tempfile::NamedTempFile::new_in(dir)
+            .and_then(|tmp| {
+                // Temporary files are private; give the result the
+                // original's permissions instead.
+                let permissions = std::fs::metadata(input_path)?.permissions();
+                tmp.as_file().set_permissions(permissions)?;
+                Ok(tmp)
+            })
             .map_err(|e| PdfError::IoError(e.to_string()))?;
-        Ok(output_path.to_string())
+        let mut file = std::io::BufWriter::new(tmp);
+        if doc.is_encrypted() {
+            doc.save_to(&mut file)
+        } else {
+            doc.save_modern(&mut file)
+        }
+        .map_err(|e| PdfError::IoError(e.to_string()))?;
+        file.into_inner()
+            .map_err(|e| PdfError::IoError(e.error().to_string()))?
+            .persist(output_path)
+            .map_err(|e| PdfError::IoError(e.error.to_string()))?;
+
+        let after = std::fs::metadata(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?
+            .len();
+        Ok((output_path.to_string(), before as i64 - after as i64))
     }
 
     pub fn merge_documents(&self, paths: Vec<String>, output_path: String) -> PdfResult<String> {
@@ -1923,785 +2627,6239 @@ impl DocumentStore {
         Ok(output_path.to_string())
     }
 
-    pub fn split_pdf(
+    /// Write the given 0-based pages of an open document to a new PDF, in
+    /// document order. The output is reopened to confirm its page count.
+    pub fn extract_pages(
         &self,
-        path: &str,
-        page_indices: Vec<usize>,
-        output_dir: String,
-    ) -> PdfResult<Vec<String>> {
-        let mut created_paths = Vec::new();
-        let template_doc = Document::load(path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
-        let page_count = template_doc.get_pages().len();
-
-        for &page_idx in &page_indices {
-            let mut doc = template_doc.clone();
-            let keep_page_1_based = (page_idx + 1) as u32;
+        doc_id: DocumentId,
+        pages: &[usize],
+        output_path: &str,
+    ) -> PdfResult<String> {
+        let pdf_path = self
+            .paths
+            .get(&doc_id)
+            .ok_or(PdfError::EngineError(EngineErrorKind::DocumentPathNotFound))?;
+        Self::extract_pages_from_file(pdf_path, pages, output_path)
+    }
 
-            let mut to_delete = Vec::new();
-            for p in 1..=(page_count as u32) {
-                if p != keep_page_1_based {
-                    to_delete.push(p);
-                }
-            }
+    fn extract_pages_from_file(
+        input_path: &str,
+        pages: &[usize],
+        output_path: &str,
+    ) -> PdfResult<String> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let total = doc.get_pages().len();
 
-            doc.delete_pages(&to_delete);
+        if pages.is_empty() {
+            return Err(PdfError::from("No pages selected for extraction"));
+        }
+        if let Some(&bad) = pages.iter().find(|&&p| p >= total) {
+            return Err(PdfError::PageNotFound(bad + 1));
+        }
 
-            let filename = std::path::Path::new(path)
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("document");
+        let keep: std::collections::BTreeSet<usize> = pages.iter().copied().collect();
+        let to_delete: Vec<u32> = (0..total)
+            .filter(|p| !keep.contains(p))
+            .map(|p| (p + 1) as u32)
+            .collect();
+        doc.delete_pages(&to_delete);
+        doc.prune_objects();
+        doc.renumber_objects();
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
 
-            let out_path = format!("{}/{}_page_{}.pdf", output_dir, filename, page_idx + 1);
-            doc.save(&out_path)
-                .map_err(|e| PdfError::IoError(e.to_string()))?;
-            created_paths.push(out_path);
+        let written = Document::load(output_path)
+            .map_err(|e| PdfError::IoError(format!("Extracted PDF is unreadable: {e}")))?
+            .get_pages()
+            .len();
+        if written != keep.len() {
+            return Err(PdfError::IoError(format!(
+                "Extracted PDF has {written} pages, expected {}",
+                keep.len()
+            )));
         }
 
-        Ok(created_paths)
+        Ok(output_path.to_string())
     }
 
-    pub fn get_form_fields(&mut self, path: &str) -> PdfResult<Vec<FormField>> {
-        let doc_id = self
-            .paths
-            .iter()
-            .find(|(_, p)| *p == path)
-            .map(|(id, _)| *id);
-
-        if let Some(id) = doc_id {
-            if let Some(doc) = self.documents.get(&id) {
-                return Ok(self.extract_form_fields_from_doc(doc));
+    /// Write `metadata` to both the Info dictionary and the XMP `/Metadata`
+    /// stream so the two agree.
+    ///
+    /// Fields left `None` keep their current value, taken from XMP where it
+    /// has one and from Info otherwise, the precedence readers use; a file
+    /// with only one of the two ends up with both. Dates may be `D:` strings
+    /// or ISO 8601. The XMP packet is rewritten with just the Info fields,
+    /// so other XMP properties are dropped.
+    pub fn update_metadata(
+        input_path: &str,
+        metadata: &crate::models::DocumentMetadata,
+        output_path: &str,
+    ) -> PdfResult<String> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot edit metadata of an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let root_id = doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+        let info_ref = doc.trailer.get(b"Info").and_then(Object::as_reference).ok();
+        let mut info = doc
+            .trailer
+            .get(b"Info")
+            .map(|obj| resolve(&doc, obj))
+            .and_then(Object::as_dict)
+            .cloned()
+            .unwrap_or_default();
+        let xmp_ref = doc
+            .get_dictionary(root_id)
+            .and_then(|catalog| catalog.get(b"Metadata"))
+            .and_then(Object::as_reference)
+            .ok();
+        let current_xmp = xmp_ref
+            .and_then(|id| doc.get_object(id).ok())
+            .and_then(|obj| obj.as_stream().ok())
+            .map(|stream| {
+                let packet = if stream.dict.has(b"Filter") {
+                    stream.decompressed_content().unwrap_or_default()
+                } else {
+                    stream.content.clone()
+                };
+                crate::xmp::read_xmp(&String::from_utf8_lossy(&packet))
+            })
+            .unwrap_or_default();
+        let current = metadata_or(current_xmp, info_metadata(&doc, &info));
+        let merged = metadata_or(metadata.clone(), current);
+
+        let dates = [
+            (b"CreationDate".as_slice(), &merged.creation_date),
+            (b"ModDate", &merged.modification_date),
+        ];
+        for (key, date) in dates {
+            if let Some(date) = date.as_deref().and_then(crate::xmp::pdf_date) {
+                info.set(key.to_vec(), pdf_text_string(&date));
             }
         }
+        let texts = [
+            (b"Title".as_slice(), &merged.title),
+            (b"Author", &merged.author),
+            (b"Subject", &merged.subject),
+            (b"Keywords", &merged.keywords),
+            (b"Creator", &merged.creator),
+            (b"Producer", &merged.producer),
+        ];
+        for (key, value) in texts {
+            if let Some(value) = value {
+                info.set(key.to_vec(), pdf_text_string(value));
+            }
+        }
+        if let Some(id) = info_ref {
+            doc.objects.insert(id, Object::Dictionary(info));
+        } else {
+            let id = doc.add_object(info);
+            doc.trailer.set("Info", Object::Reference(id));
+        }
 
-        let data = std::fs::read(path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
-        let doc = PdfDocument::open(data).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
-        Ok(self.extract_form_fields_from_doc(&doc))
-    }
-
-    fn extract_form_fields_from_doc(&self, doc: &PdfDocument) -> Vec<FormField> {
-        let mut fields = Vec::new();
-        if let Some(acro) = doc.acro_form() {
-            for f in &acro.fields {
-                let name = f.name.clone();
-                let variant = match f.kind {
-                    FieldKind::Text => {
-                        let val = match &f.value {
-                            Some(FieldValue::Text(s)) => s.clone(),
-                            _ => String::new(),
-                        };
-                        FormFieldVariant::Text { value: val }
-                    }
-                    FieldKind::Button => {
-                        let is_checked = match &f.value {
-                            Some(FieldValue::Name(n)) => n != "Off",
-                            _ => false,
-                        };
-                        if f.flags & FF_RADIO != 0 {
-                            FormFieldVariant::RadioButton {
-                                is_selected: is_checked,
-                                group_name: Some(name.clone()),
-                            }
-                        } else {
-                            FormFieldVariant::Checkbox { is_checked }
-                        }
-                    }
-                    FieldKind::Choice => {
-                        let opts: Vec<String> =
-                            f.options.iter().map(|(_, label)| label.clone()).collect();
-                        let selected_val = match &f.value {
-                            Some(FieldValue::Text(s)) => Some(s.clone()),
-                            _ => None,
-                        };
-                        let selected_index = selected_val.and_then(|val| {
-                            f.options.iter().position(|(export, _)| *export == val)
-                        });
-                        FormFieldVariant::ComboBox {
-                            options: opts,
-                            selected_index,
-                        }
-                    }
-                    _ => FormFieldVariant::Text {
-                        value: String::new(),
-                    },
-                };
+        // Left uncompressed, as PDF/A asks, so tools can find the packet.
+        let xmp = lopdf::Stream::new(
+            lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Metadata".to_vec())),
+                ("Subtype", Object::Name(b"XML".to_vec())),
+            ]),
+            crate::xmp::write_xmp(&merged).into_bytes(),
+        );
+        if let Some(id) = xmp_ref {
+            doc.objects.insert(id, Object::Stream(xmp));
+        } else {
+            let id = doc.add_object(xmp);
+            doc.get_dictionary_mut(root_id)
+                .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?
+                .set("Metadata", Object::Reference(id));
+        }
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
 
-                let mut page_idx = 0;
-                if let Some(&widget_id) = f.widgets.first() {
-                    for i in 0..doc.page_count() {
-                        if let Ok(page) = doc.page(i) {
-                            if page.annots.contains(&widget_id) {
-                                page_idx = i;
-                                break;
-                            }
-                        }
-                    }
-                }
+        tracing::info!("Updated metadata -> {}", output_path);
+        Ok(output_path.to_string())
+    }
 
-                fields.push(FormField {
-                    name,
-                    variant,
-                    page: page_idx,
-                });
-            }
+    /// Attach `bytes` to the document as `name`: an `/EmbeddedFile` stream
+    /// behind a `/Filespec` in the catalog's `/EmbeddedFiles` name tree. An
+    /// attachment already named `name` is replaced.
+    pub fn embed_file(
+        input_path: &str,
+        name: &str,
+        bytes: &[u8],
+        output_path: &str,
+    ) -> PdfResult<String> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot attach files to an encrypted PDF; save an unprotected copy first",
+            ));
         }
-        fields
+        let root_id = doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+        attach_file(&mut doc, root_id, name, bytes)?;
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+
+        tracing::info!(
+            "Attached {} ({} bytes) -> {}",
+            name,
+            bytes.len(),
+            output_path
+        );
+        Ok(output_path.to_string())
     }
 
-    pub fn fill_form(
-        &mut self,
-        path: &str,
-        updates: Vec<FormField>,
-        output_path: String,
-    ) -> PdfResult<String> {
-        let data = std::fs::read(path).map_err(|e| PdfError::IoError(e.to_string()))?;
-        let mut writer =
-            IncrementalWriter::new(data).map_err(|e| PdfError::IoError(e.to_string()))?;
+    /// Every attachment in the `/EmbeddedFiles` name tree as its file name
+    /// and decoded contents, in name order. Entries whose file stream is
+    /// missing or can't be decoded are skipped.
+    pub fn extract_files(input_path: &str) -> PdfResult<Vec<(String, Vec<u8>)>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let Ok(tree) = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Names"))
+            .map(|obj| resolve(&doc, obj))
+            .and_then(Object::as_dict)
+            .and_then(|names| names.get(b"EmbeddedFiles"))
+        else {
+            return Ok(Vec::new());
+        };
 
-        {
-            let mut filler =
-                FormFiller::new(&mut writer).map_err(|e| PdfError::IoError(e.to_string()))?;
-            for update in updates {
-                let val_str = match &update.variant {
-                    FormFieldVariant::Text { value } => value.clone(),
-                    FormFieldVariant::Checkbox { is_checked } => {
-                        if *is_checked {
-                            "Yes".to_string()
-                        } else {
-                            "Off".to_string()
-                        }
-                    }
-                    FormFieldVariant::RadioButton { is_selected, .. } => {
-                        if *is_selected {
-                            "Yes".to_string()
-                        } else {
-                            "Off".to_string()
-                        }
-                    }
-                    FormFieldVariant::ComboBox {
-                        selected_index,
-                        options,
-                    } => {
-                        if let Some(idx) = selected_index {
-                            options.get(*idx).cloned().unwrap_or_default()
-                        } else {
-                            String::new()
-                        }
+        let mut files = Vec::new();
+        for (key, filespec) in name_tree_entries(&doc, tree) {
+            let Ok(filespec) = resolve(&doc, &filespec).as_dict() else {
+                continue;
+            };
+            let stream = filespec
+                .get(b"EF")
+                .map(|obj| resolve(&doc, obj))
+                .and_then(Object::as_dict)
+                .and_then(|ef| ef.get(b"UF").or_else(|_| ef.get(b"F")))
+                .map(|obj| resolve(&doc, obj))
+                .and_then(Object::as_stream);
+            let Ok(stream) = stream else {
+                tracing::warn!("Attachment {} has no file stream", decode_pdf_string(&key));
+                continue;
+            };
+            let data = if stream.dict.has(b"Filter") {
+                match stream.decompressed_content() {
+                    Ok(data) => data,
+                    Err(e) => {
+                        tracing::warn!("Attachment {} is corrupt: {e}", decode_pdf_string(&key));
+                        continue;
                     }
-                };
-                if let Err(e) = filler.set(&update.name, &val_str) {
-                    tracing::warn!("Failed to set field {}: {}", update.name, e);
                 }
-            }
-            filler
-                .finish()
-                .map_err(|e| PdfError::IoError(e.to_string()))?;
+            } else {
+                stream.content.clone()
+            };
+            let name = filespec
+                .get(b"UF")
+                .or_else(|_| filespec.get(b"F"))
+                .and_then(Object::as_str)
+                .map_or_else(|_| decode_pdf_string(&key), decode_pdf_string);
+            files.push((name, data));
         }
-
-        let mut file =
-            std::fs::File::create(&output_path).map_err(|e| PdfError::IoError(e.to_string()))?;
-        writer
-            .write(&mut file)
-            .map_err(|e| PdfError::IoError(e.to_string()))?;
-
-        Ok(output_path)
+        Ok(files)
     }
 
-    #[cfg(windows)]
-    pub fn print_document(path: &str, printer_name: Option<&str>) -> PdfResult<()> {
-        use winprint::printer::{FilePrinter, PrinterDevice, WinPdfPrinter};
-
-        let all_devices = PrinterDevice::all()
-            .map_err(|e| PdfError::IoError(format!("Failed to list printers: {e}")))?;
+    /// Embed a ZUGFeRD / Factur-X invoice: `xml` is attached as
+    /// `factur-x.xml`, listed in the catalog's `/AF` associated files with
+    /// `/AFRelationship /Data`, and the document's XMP gains the Factur-X
+    /// properties for `profile` (`MINIMUM`, `BASIC WL`, `BASIC`,
+    /// `EN 16931` or `EXTENDED`). The rest of PDF/A-3 conformance is left
+    /// to the input.
+    pub fn embed_zugferd(
+        input_path: &str,
+        xml: &[u8],
+        profile: &str,
+        output_path: &str,
+    ) -> PdfResult<String> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot embed an invoice in an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let profile = ZUGFERD_PROFILES
+            .into_iter()
+            .find(|known| known.eq_ignore_ascii_case(profile.trim()))
+            .ok_or_else(|| PdfError::from(format!("Unknown Factur-X profile {profile:?}")))?;
+        let root_id = doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+        let name = ZUGFERD_NAMES[0];
+        let (stream_id, filespec_id) = attach_file(&mut doc, root_id, name, xml)?;
+        if let Ok(Object::Stream(stream)) = doc.get_object_mut(stream_id) {
+            stream
+                .dict
+                .set("Subtype", Object::Name(b"text/xml".to_vec()));
+        }
+        if let Ok(filespec) = doc.get_dictionary_mut(filespec_id) {
+            filespec.set("AFRelationship", Object::Name(b"Data".to_vec()));
+            filespec.set("Desc", Object::string_literal("Factur-X invoice"));
+        }
 
-        let device = if let Some(name) = printer_name {
-            all_devices
-                .into_iter()
-                .find(|d| d.name() == name)
-                .ok_or_else(|| PdfError::IoError(format!("Printer '{name}' not found")))?
+        // Drop any earlier invoice from /AF so only the new one is listed.
+        let mut associated: Vec<Object> = doc
+            .get_dictionary(root_id)
+            .and_then(|catalog| catalog.get(b"AF"))
+            .map(|obj| resolve(&doc, obj))
+            .and_then(Object::as_array)
+            .cloned()
+            .unwrap_or_default();
+        associated.retain(|entry| {
+            !resolve(&doc, entry)
+                .as_dict()
+                .is_ok_and(|spec| filespec_name(spec).as_deref() == Some(name))
+        });
+        associated.push(Object::Reference(filespec_id));
+
+        let xmp_ref = doc
+            .get_dictionary(root_id)
+            .and_then(|catalog| catalog.get(b"Metadata"))
+            .and_then(Object::as_reference)
+            .ok();
+        let info = doc
+            .trailer
+            .get(b"Info")
+            .map(|obj| resolve(&doc, obj))
+            .and_then(Object::as_dict)
+            .cloned()
+            .unwrap_or_default();
+        let current_xmp = xmp_ref
+            .and_then(|id| doc.get_object(id).ok())
+            .and_then(|obj| obj.as_stream().ok())
+            .and_then(|stream| stream_data(stream).ok())
+            .map(|packet| crate::xmp::read_xmp(&String::from_utf8_lossy(&packet)))
+            .unwrap_or_default();
+        let metadata = metadata_or(current_xmp, info_metadata(&doc, &info));
+        let packet = crate::xmp::with_factur_x(&crate::xmp::write_xmp(&metadata), name, profile);
+        let xmp = lopdf::Stream::new(
+            lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Metadata".to_vec())),
+                ("Subtype", Object::Name(b"XML".to_vec())),
+            ]),
+            packet.into_bytes(),
+        );
+        let xmp_id = if let Some(id) = xmp_ref {
+            doc.objects.insert(id, Object::Stream(xmp));
+            id
         } else {
-            all_devices
-                .into_iter()
-                .next()
-                .ok_or_else(|| PdfError::IoError("No printers found".into()))?
+            doc.add_object(xmp)
         };
+        let catalog = doc
+            .get_dictionary_mut(root_id)
+            .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+        catalog.set("AF", Object::Array(associated));
+        catalog.set("Metadata", Object::Reference(xmp_id));
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
 
-        let printer = WinPdfPrinter::new(device);
-        printer
-            .print(std::path::Path::new(path), Default::default())
-            .map_err(|e| PdfError::IoError(format!("Print failed: {e}")))?;
-        Ok(())
+        tracing::info!(
+            "Embedded {} invoice ({} bytes) -> {}",
+            profile,
+            xml.len(),
+            output_path
+        );
+        Ok(output_path.to_string())
     }
 
-    #[cfg(not(windows))]
-    pub fn print_document(_path: &str, _printer_name: Option<&str>) -> PdfResult<()> {
-        Err(PdfError::IoError(
-            "Printing is only supported on Windows".into(),
-        ))
+    /// The ZUGFeRD / Factur-X invoice XML among the catalog's `/AF`
+    /// associated files, or `None` when the document carries none. An
+    /// invoice whose `/AFRelationship` isn't `/Data` is an error, as is one
+    /// that isn't UTF-8.
+    pub fn extract_zugferd(input_path: &str) -> PdfResult<Option<String>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let Ok(associated) = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"AF"))
+            .map(|obj| resolve(&doc, obj))
+            .and_then(Object::as_array)
+        else {
+            return Ok(None);
+        };
+        let invoice = associated
+            .iter()
+            .filter_map(|entry| resolve(&doc, entry).as_dict().ok())
+            .find(|spec| {
+                filespec_name(spec).is_some_and(|name| {
+                    ZUGFERD_NAMES
+                        .iter()
+                        .any(|known| known.eq_ignore_ascii_case(&name))
+                })
+            });
+        let Some(spec) = invoice else {
+            return Ok(None);
+        };
+        let relationship = spec
+            .get(b"AFRelationship")
+            .and_then(Object::as_name)
+            .unwrap_or_default();
+        if relationship != b"Data" {
+            return Err(PdfError::from(format!(
+                "Invoice has AFRelationship /{}, expected /Data",
+                String::from_utf8_lossy(relationship)
+            )));
+        }
+        let stream = spec
+            .get(b"EF")
+            .map(|obj| resolve(&doc, obj))
+            .and_then(Object::as_dict)
+            .and_then(|ef| ef.get(b"UF").or_else(|_| ef.get(b"F")))
+            .map(|obj| resolve(&doc, obj))
+            .and_then(Object::as_stream)
+            .map_err(|_| PdfError::from("Invoice has no embedded file stream"))?;
+        let xml = String::from_utf8(stream_data(stream)?)
+            .map_err(|_| PdfError::from("Invoice XML is not UTF-8"))?;
+        Ok(Some(xml))
     }
 
-    /// Returns a sorted list of all available printer names on this system.
-    #[cfg(windows)]
-    pub fn list_printers() -> PdfResult<Vec<String>> {
-        use winprint::printer::PrinterDevice;
-        PrinterDevice::all()
-            .map(|devices| {
-                let mut names: Vec<String> =
-                    devices.into_iter().map(|d| d.name().to_string()).collect();
-                names.sort_unstable();
-                names
+    /// Build a PDF Portfolio: the pages of `cover_path` with each member
+    /// attached under its name and a `/Collection` dictionary so viewers
+    /// show the members as a file list, sorted by name. Readers without
+    /// portfolio support show the cover, which should say as much.
+    pub fn create_portfolio(
+        cover_path: &str,
+        members: &[(String, Vec<u8>)],
+        output_path: &str,
+    ) -> PdfResult<String> {
+        let mut doc =
+            Document::load(cover_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot build a portfolio on an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        if members.is_empty() {
+            return Err(PdfError::from("A portfolio needs at least one member"));
+        }
+        let root_id = doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+        for (name, bytes) in members {
+            Document::load_mem(bytes)
+                .map_err(|e| PdfError::from(format!("Member {name} is not a PDF: {e}")))?;
+            let (stream_id, _) = attach_file(&mut doc, root_id, name, bytes)?;
+            if let Ok(Object::Stream(stream)) = doc.get_object_mut(stream_id) {
+                stream
+                    .dict
+                    .set("Subtype", Object::Name(b"application/pdf".to_vec()));
+            }
+        }
+
+        let field = |name: &str, subtype: &[u8], order: i64| {
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"CollectionField".to_vec())),
+                ("Subtype", Object::Name(subtype.to_vec())),
+                ("N", Object::string_literal(name)),
+                ("O", Object::Integer(order)),
+            ]))
+        };
+        let first = members
+            .iter()
+            .map(|(name, _)| name)
+            .min()
+            .map(|name| pdf_text_string(name));
+        let mut collection = lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Collection".to_vec())),
+            // Details view: one row per member with the schema's columns.
+            ("View", Object::Name(b"D".to_vec())),
+            (
+                "Schema",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                    ("FileName", field("Name", b"F", 0)),
+                    ("Size", field("Size", b"Size", 1)),
+                ])),
+            ),
+            (
+                "Sort",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "S",
+                    Object::Name(b"FileName".to_vec()),
+                )])),
+            ),
+        ]);
+        if let Some(first) = first {
+            collection.set("D", first);
+        }
+        let collection_id = doc.add_object(collection);
+        let catalog = doc
+            .get_dictionary_mut(root_id)
+            .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+        catalog.set("Collection", Object::Reference(collection_id));
+        catalog.set("PageMode", Object::Name(b"UseAttachments".to_vec()));
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+
+        tracing::info!(
+            "Built portfolio of {} documents -> {}",
+            members.len(),
+            output_path
+        );
+        Ok(output_path.to_string())
+    }
+
+    /// The member names of a PDF Portfolio in name order, or `None` when
+    /// the document has no `/Collection` and isn't a portfolio. Use
+    /// [`Self::extract_files`] to get their contents.
+    pub fn list_portfolio(input_path: &str) -> PdfResult<Option<Vec<String>>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let catalog = doc
+            .catalog()
+            .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+        if !catalog.has(b"Collection") {
+            return Ok(None);
+        }
+        let Ok(tree) = catalog
+            .get(b"Names")
+            .map(|obj| resolve(&doc, obj))
+            .and_then(Object::as_dict)
+            .and_then(|names| names.get(b"EmbeddedFiles"))
+        else {
+            return Ok(Some(Vec::new()));
+        };
+        let mut members: Vec<String> = name_tree_entries(&doc, tree)
+            .into_iter()
+            .map(|(key, filespec)| {
+                resolve(&doc, &filespec)
+                    .as_dict()
+                    .ok()
+                    .and_then(filespec_name)
+                    .unwrap_or_else(|| decode_pdf_string(&key))
             })
-            .map_err(|e| PdfError::IoError(format!("Failed to list printers: {e}")))
+            .collect();
+        members.sort();
+        Ok(Some(members))
     }
 
-    #[cfg(not(windows))]
-    pub fn list_printers() -> PdfResult<Vec<String>> {
-        Ok(Vec::new())
+    /// Every embedded font program as a standalone font file; see
+    /// [`crate::font_program`]. Fonts whose program can't be read are
+    /// skipped.
+    pub fn extract_fonts(input_path: &str) -> PdfResult<Vec<ExtractedFont>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        Ok(crate::font_program::embedded_fonts(&doc)
+            .into_iter()
+            .filter_map(|id| {
+                crate::font_program::extract_font(&doc, id)
+                    .inspect_err(|e| tracing::warn!("Skipping font {id:?}: {e}"))
+                    .ok()
+            })
+            .collect())
+    }
+
+    /// The `/Link` annotations on the zero-based `page` with their targets:
+    /// a URL for URI actions or the zero-based page a `GoTo` action or
+    /// `/Dest` jumps to, named destinations included. Links to anything
+    /// else are left out.
+    pub fn load_links(input_path: &str, page: usize) -> PdfResult<Vec<Hyperlink>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let page_ids: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        let page_id = *page_ids.get(page).ok_or(PdfError::PageNotFound(page))?;
+        Ok(page_links(&doc, page_id, page, &page_ids))
+    }
+
+    /// The presentation transition of the zero-based `page`, or `None` when
+    /// it has neither a `/Trans` effect nor a `/Dur` display time.
+    pub fn page_transition(input_path: &str, page: usize) -> PdfResult<Option<Transition>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let page_id = doc
+            .get_pages()
+            .into_values()
+            .nth(page)
+            .ok_or(PdfError::PageNotFound(page))?;
+        Ok(page_transition(&doc, page_id))
+    }
+
+    /// Every JavaScript action in the document as (where it runs, script):
+    /// document-level scripts, the open action, document, page, field and
+    /// annotation trigger events, and bookmarks.
+    pub fn list_javascript(input_path: &str) -> PdfResult<Vec<(String, String)>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        Ok(javascript_actions(&doc))
     }
 
-    pub fn add_watermark(input_path: &str, text: &str, output_path: &str) -> PdfResult<String> {
+    /// Remove every script: the document-level `/JavaScript` name tree, all
+    /// `/AA` trigger events, and JavaScript actions wherever they are used,
+    /// including the `/OpenAction`. Returns the output path and how many
+    /// entries were removed.
+    pub fn strip_javascript(input_path: &str, output_path: &str) -> PdfResult<(String, usize)> {
         let mut doc =
             Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot strip scripts from an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let scripts: HashSet<ObjectId> = doc
+            .objects
+            .iter()
+            .filter(|(_, obj)| obj.as_dict().is_ok_and(is_javascript_action))
+            .map(|(&id, _)| id)
+            .collect();
+        let mut removed = 0;
+        for obj in doc.objects.values_mut() {
+            removed += strip_scripts(obj, &scripts);
+        }
+        doc.prune_objects();
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!("Removed {} script entries -> {}", removed, output_path);
+        Ok((output_path.to_string(), removed))
+    }
 
-        let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
-        let page_count = pages.len();
+    /// Every 3D annotation with its model format, size and default view.
+    /// Annotations whose model stream is missing are skipped.
+    pub fn list_3d(input_path: &str) -> PdfResult<Vec<ThreeDInfo>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let mut found = Vec::new();
+        for (page, index, annot) in three_d_annotations(&doc) {
+            let Some(stream) = three_d_stream(&doc, annot) else {
+                tracing::warn!("3D annotation {index} on page {} has no model", page + 1);
+                continue;
+            };
+            let view = three_d_default_view(&doc, annot, stream);
+            found.push(ThreeDInfo {
+                page,
+                index,
+                format: stream
+                    .dict
+                    .get(b"Subtype")
+                    .and_then(Object::as_name)
+                    .map_or_else(
+                        |_| "U3D".into(),
+                        |s| String::from_utf8_lossy(s).into_owned(),
+                    ),
+                size: stream_data(stream).map_or(0, |data| data.len()),
+                view_name: view
+                    .and_then(|v| v.get(b"XN").ok())
+                    .and_then(|name| name.as_str().ok())
+                    .map(decode_pdf_string),
+                view_matrix: view
+                    .and_then(|v| v.get(b"C2W").ok())
+                    .and_then(|m| m.as_array().ok())
+                    .and_then(|m| {
+                        let values: Vec<f32> = m.iter().filter_map(|v| v.as_float().ok()).collect();
+                        values.try_into().ok()
+                    }),
+            });
+        }
+        Ok(found)
+    }
 
-        let font_ref_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
-            ("Type", Object::Name(b"Font".to_vec())),
-            ("Subtype", Object::Name(b"Type1".to_vec())),
-            ("BaseFont", Object::Name(b"Helvetica".to_vec())),
-        ])));
+    /// The decoded U3D or PRC data of 3D annotation `index` on page `page`
+    /// (0-based), numbered as [`Self::list_3d`] numbers them.
+    pub fn extract_3d_stream(input_path: &str, page: usize, index: usize) -> PdfResult<Vec<u8>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let (_, _, annot) = three_d_annotations(&doc)
+            .into_iter()
+            .find(|&(p, i, _)| p == page && i == index)
+            .ok_or_else(|| {
+                PdfError::from(format!("Page {} has no 3D annotation {index}", page + 1))
+            })?;
+        let stream = three_d_stream(&doc, annot)
+            .ok_or_else(|| PdfError::from("3D annotation has no model stream"))?;
+        stream_data(stream)
+    }
 
-        let resources_id =
-            doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
-                "Font",
-                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
-                    "F1",
-                    Object::Reference(font_ref_id),
-                )])),
-            )])));
+    /// Every box of page `page` (0-based), resolved the way viewers resolve
+    /// them, with defaults filled in.
+    pub fn page_boxes(input_path: &str, page: usize) -> PdfResult<Vec<(PageBox, [f32; 4])>> {
+        let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let page_id = doc
+            .get_pages()
+            .into_values()
+            .nth(page)
+            .ok_or(PdfError::PageNotFound(page + 1))?;
+        Ok(PageBox::ALL
+            .iter()
+            .map(|&which| (which, page_box(&doc, page_id, which)))
+            .collect())
+    }
 
-        let mut content = pdf_writer::Content::new();
-        content.begin_text();
-        content.set_font(pdf_writer::Name(b"F1"), 48.0);
-        content.set_fill_rgb(0.7, 0.7, 0.7);
-        content.set_text_matrix([1.0, 0.0, 0.0, 1.0, 200.0, 400.0]);
-        content.show(pdf_writer::Str(text.as_bytes()));
-        content.end_text();
-        let watermark_stream =
-            lopdf::Stream::new(lopdf::Dictionary::new(), content.finish().to_vec());
-        let watermark_id = doc.add_object(watermark_stream);
+    /// Set one box of page `page` (0-based) to `rect` (`[x0, y0, x1, y1]`).
+    /// A box other than the MediaBox must lie within the MediaBox.
+    ///
+    /// Returns warnings for boxes left out of order: a TrimBox outside the
+    /// BleedBox, or boxes that a smaller MediaBox now clips.
+    pub fn set_page_box(
+        input_path: &str,
+        output_path: &str,
+        page: usize,
+        which: PageBox,
+        rect: [f32; 4],
+    ) -> PdfResult<Vec<ValidationIssue>> {
+        use crate::models::IssueSeverity::Warning;
 
-        for &page_id in &pages {
-            let existing_contents = doc
-                .get_page_contents(page_id)
-                .into_iter()
-                .map(Object::Reference)
-                .collect::<Vec<_>>();
-            let mut all_contents = existing_contents;
-            all_contents.push(Object::Reference(watermark_id));
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot change page boxes of an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let page_id = doc
+            .get_pages()
+            .into_values()
+            .nth(page)
+            .ok_or(PdfError::PageNotFound(page + 1))?;
+        let [x0, y0, x1, y1] = rect;
+        let rect = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+        if rect[0] == rect[2] || rect[1] == rect[3] {
+            return Err(PdfError::from(format!("{} has no area", which.key())));
+        }
+        let media = page_media_box(&doc, page_id);
+        if which != PageBox::Media && !rect_contains(media, rect) {
+            return Err(PdfError::from(format!(
+                "{} {rect:?} does not lie within the MediaBox {media:?}",
+                which.key()
+            )));
+        }
 
-            let existing_res = doc
-                .objects
-                .get(&page_id)
-                .and_then(|o| o.as_dict().ok())
-                .and_then(|d| d.get(b"Resources").ok())
-                .cloned();
+        doc.get_dictionary_mut(page_id)
+            .map_err(|e| PdfError::from(e.to_string()))?
+            .set(
+                which.key(),
+                Object::Array(rect.iter().map(|&v| Object::Real(v)).collect()),
+            );
 
-            let mut merged_res = None;
-            if let Some(res) = existing_res {
-                let mut res_dict = match res {
-                    Object::Reference(r) => {
-                        doc.objects.get(&r).and_then(|o| o.as_dict().ok()).cloned()
-                    }
-                    Object::Dictionary(d) => Some(d.clone()),
-                    _ => None,
-                };
-                if let Some(ref mut d) = res_dict {
-                    if let Ok(existing_fonts) = d.get_mut(b"Font") {
-                        let mut fonts_dict = match existing_fonts {
-                            Object::Reference(r) => {
-                                doc.objects.get(r).and_then(|o| o.as_dict().ok()).cloned()
-                            }
-                            Object::Dictionary(fd) => Some(fd.clone()),
-                            _ => None,
-                        };
-                        if let Some(ref mut fd) = fonts_dict {
-                            fd.set("F1", Object::Reference(font_ref_id));
-                            d.set("Font", Object::Dictionary(fd.clone()));
-                        }
-                    } else {
-                        d.set(
+        let mut issues = Vec::new();
+        let media = page_media_box(&doc, page_id);
+        for other in [PageBox::Crop, PageBox::Bleed, PageBox::Trim, PageBox::Art] {
+            if let Some(own) = own_page_box(&doc, page_id, other)
+                && !rect_contains(media, own)
+            {
+                issues.push(ValidationIssue::new(
+                    "page-box-order",
+                    Warning,
+                    Some(page + 1),
+                    format!(
+                        "{} extends outside the MediaBox and is clipped",
+                        other.key()
+                    ),
+                ));
+            }
+        }
+        let bleed = page_box(&doc, page_id, PageBox::Bleed);
+        if !rect_contains(bleed, page_box(&doc, page_id, PageBox::Trim)) {
+            issues.push(ValidationIssue::new(
+                "page-box-order",
+                Warning,
+                Some(page + 1),
+                "TrimBox extends outside the BleedBox",
+            ));
+        }
+        for issue in &issues {
+            tracing::warn!("Page {}: {}", page + 1, issue.message);
+        }
+
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!(
+            "Set {} of page {} to {rect:?} in {}",
+            which.key(),
+            page + 1,
+            output_path
+        );
+        Ok(issues)
+    }
+
+    /// Crop every page to its content plus `margin` points by setting its
+    /// CropBox. Returns the output path and how many pages were cropped.
+    pub fn autocrop(
+        input_path: &str,
+        output_path: &str,
+        margin: f32,
+    ) -> PdfResult<(String, usize)> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot crop an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let cropped = crate::page_resize::autocrop(&mut doc, margin);
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!("Cropped {} pages to content -> {}", cropped, output_path);
+        Ok((output_path.to_string(), cropped))
+    }
+
+    /// Scale every page onto `target` paper, fitting, filling or stretching
+    /// as `mode` says.
+    pub fn resize_to(
+        input_path: &str,
+        output_path: &str,
+        target: crate::flow::PageSize,
+        mode: crate::page_resize::ResizeMode,
+    ) -> PdfResult<String> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot resize an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let resized = crate::page_resize::resize_to(&mut doc, target, mode);
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!(
+            "Resized {} pages to {}x{} ({:?}) -> {}",
+            resized,
+            target.width,
+            target.height,
+            mode,
+            output_path
+        );
+        Ok(output_path.to_string())
+    }
+
+    /// Convert every colour in the document to gray for black-and-white
+    /// printing: content colours, images and gradients.
+    pub fn recolor_to_gray(input_path: &str, output_path: &str) -> PdfResult<String> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot recolour an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let rewritten = crate::recolor::recolor_to_gray(&mut doc)?;
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!("Converted {} objects to gray -> {}", rewritten, output_path);
+        Ok(output_path.to_string())
+    }
+
+    /// Shrink the document's images: downsample those drawn above
+    /// `max_dpi` and store them as JPEG at `jpeg_quality` or Flate,
+    /// whichever is smaller. Returns the output path and how many images
+    /// were rewritten.
+    pub fn rewrite_images(
+        input_path: &str,
+        output_path: &str,
+        max_dpi: f32,
+        jpeg_quality: Option<u8>,
+    ) -> PdfResult<(String, usize)> {
+        if max_dpi.is_nan() || max_dpi <= 0.0 {
+            return Err(PdfError::from("Image resolution limit must be positive"));
+        }
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot rewrite images of an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let rewritten = crate::image_rewrite::rewrite_images(&mut doc, max_dpi, jpeg_quality);
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!("Rewrote {} images -> {}", rewritten, output_path);
+        Ok((output_path.to_string(), rewritten))
+    }
+
+    /// Prepend a "Contents" page listing `entries` with dotted leaders and
+    /// page numbers. Every line links to its target page. Long titles wrap,
+    /// and the list continues onto more pages when it overflows. Page
+    /// numbers count the TOC pages, so they match the viewer.
+    pub fn prepend_toc(
+        input_path: &str,
+        entries: &[Bookmark],
+        output_path: &str,
+    ) -> PdfResult<String> {
+        const PAGE: (f32, f32) = (612.0, 792.0);
+        const MARGIN: f32 = 72.0;
+        const SIZE: f32 = 11.0;
+        const LEADING: f32 = 16.0;
+        const INDENT: f32 = 18.0;
+        const HEADING_LINES: usize = 3;
+        // Helvetica metrics: average glyph, period and digit widths in ems.
+        const CHAR_EM: f32 = 0.5;
+        const DOT_EM: f32 = 0.278;
+        const DIGIT_EM: f32 = 0.556;
+
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot add a table of contents to an encrypted PDF",
+            ));
+        }
+        if entries.is_empty() {
+            return Err(PdfError::from("Document has no bookmarks to list"));
+        }
+        let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        let Some(&last_page) = pages.last() else {
+            return Err(PdfError::from("Document has no pages"));
+        };
+
+        // (text, depth, target, last line of its entry)
+        let number_w = DIGIT_EM * SIZE * (pages.len() + 10).to_string().len() as f32;
+        let mut lines = Vec::new();
+        for entry in entries {
+            let depth = entry.depth.min(4);
+            let width = PAGE.0 - 2.0 * MARGIN - depth as f32 * INDENT - number_w - 24.0;
+            let wrapped = wrap_words(&entry.title, (width / (CHAR_EM * SIZE)) as usize);
+            let count = wrapped.len();
+            let target = pages.get(entry.page_index).copied().unwrap_or(last_page);
+            for (i, text) in wrapped.into_iter().enumerate() {
+                lines.push((text, depth, target, i + 1 == count));
+            }
+        }
+        let per_page = ((PAGE.1 - 2.0 * MARGIN) / LEADING) as usize;
+        let mut toc_pages = vec![
+            lines
+                .drain(..lines.len().min(per_page - HEADING_LINES))
+                .collect::<Vec<_>>(),
+        ];
+        while !lines.is_empty() {
+            toc_pages.push(lines.drain(..lines.len().min(per_page)).collect());
+        }
+        let toc_count = toc_pages.len();
+        let number_of =
+            |target: ObjectId| pages.iter().position(|&p| p == target).unwrap_or(0) + 1 + toc_count;
+
+        let pages_id = doc
+            .catalog()
+            .and_then(|c| c.get(b"Pages"))
+            .and_then(Object::as_reference)
+            .map_err(|e| PdfError::from(format!("Missing page tree: {e}")))?;
+        let font_id = helvetica_font(&mut doc);
+
+        let mut new_kids = Vec::new();
+        for (page_no, page_lines) in toc_pages.into_iter().enumerate() {
+            let mut content = pdf_writer::Content::new();
+            let mut annots = Vec::new();
+            let mut y = PAGE.1 - MARGIN - SIZE;
+            if page_no == 0 {
+                content.begin_text();
+                content.set_font(pdf_writer::Name(b"F1"), 18.0);
+                content.next_line(MARGIN, y);
+                content.show(pdf_writer::Str(b"Contents"));
+                content.end_text();
+                y -= HEADING_LINES as f32 * LEADING;
+            }
+            for (text, depth, target, last) in page_lines {
+                let x = MARGIN + depth as f32 * INDENT;
+                let text_end = x + text.chars().count() as f32 * CHAR_EM * SIZE;
+                content.begin_text();
+                content.set_font(pdf_writer::Name(b"F1"), SIZE);
+                content.next_line(x, y);
+                content.show(pdf_writer::Str(&win_ansi_bytes(&text)));
+                content.end_text();
+                if last {
+                    let number = number_of(target).to_string();
+                    let number_x = PAGE.0 - MARGIN - number.len() as f32 * DIGIT_EM * SIZE;
+                    let dots = ((number_x - text_end - 8.0) / (DOT_EM * SIZE)).max(0.0) as usize;
+                    content.begin_text();
+                    content.set_font(pdf_writer::Name(b"F1"), SIZE);
+                    content.next_line(number_x - 4.0 - dots as f32 * DOT_EM * SIZE, y);
+                    content.show(pdf_writer::Str(
+                        format!("{} {number}", ".".repeat(dots)).as_bytes(),
+                    ));
+                    content.end_text();
+                }
+                annots.push(
+                    doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                        ("Type", Object::Name(b"Annot".to_vec())),
+                        ("Subtype", Object::Name(b"Link".to_vec())),
+                        (
+                            "Rect",
+                            Object::Array(vec![
+                                Object::Real(x),
+                                Object::Real(y - 4.0),
+                                Object::Real(PAGE.0 - MARGIN),
+                                Object::Real(y + SIZE),
+                            ]),
+                        ),
+                        ("Border", Object::Array(vec![Object::Integer(0); 3])),
+                        (
+                            "Dest",
+                            Object::Array(vec![
+                                Object::Reference(target),
+                                Object::Name(b"Fit".to_vec()),
+                            ]),
+                        ),
+                    ]))),
+                );
+                y -= LEADING;
+            }
+
+            let content_id = doc.add_object(lopdf::Stream::new(
+                lopdf::Dictionary::new(),
+                content.finish().to_vec(),
+            ));
+            let toc_page_id =
+                doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                    ("Type", Object::Name(b"Page".to_vec())),
+                    ("Parent", Object::Reference(pages_id)),
+                    (
+                        "MediaBox",
+                        Object::Array(vec![
+                            Object::Real(0.0),
+                            Object::Real(0.0),
+                            Object::Real(PAGE.0),
+                            Object::Real(PAGE.1),
+                        ]),
+                    ),
+                    ("Contents", Object::Reference(content_id)),
+                    (
+                        "Resources",
+                        Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
                             "Font",
                             Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
                                 "F1",
-                                Object::Reference(font_ref_id),
+                                Object::Reference(font_id),
                             )])),
-                        );
-                    }
-                    merged_res = Some(doc.add_object(Object::Dictionary(d.clone())));
-                }
-            }
-
-            let final_res_id = merged_res.unwrap_or(resources_id);
+                        )])),
+                    ),
+                    (
+                        "Annots",
+                        Object::Array(annots.into_iter().map(Object::Reference).collect()),
+                    ),
+                ])));
+            new_kids.push(Object::Reference(toc_page_id));
+        }
 
-            let page_dict = doc
-                .objects
-                .get_mut(&page_id)
-                .and_then(|o| o.as_dict_mut().ok())
-                .ok_or_else(|| PdfError::EngineError("Invalid page object".into()))?;
-            page_dict.set("Contents", Object::Array(all_contents));
-            page_dict.set("Resources", Object::Reference(final_res_id));
+        let pages_dict = doc
+            .get_dictionary_mut(pages_id)
+            .map_err(|e| PdfError::from(format!("Missing page tree: {e}")))?;
+        let mut kids = new_kids;
+        if let Ok(Object::Array(existing)) = pages_dict.get(b"Kids") {
+            kids.extend(existing.iter().cloned());
         }
+        let count = pages_dict
+            .get(b"Count")
+            .and_then(Object::as_i64)
+            .unwrap_or(pages.len() as i64);
+        pages_dict.set("Kids", Object::Array(kids));
+        pages_dict.set("Count", Object::Integer(count + toc_count as i64));
 
         doc.save(output_path)
             .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!(
+            "Prepended {} TOC pages for {} entries -> {}",
+            toc_count,
+            entries.len(),
+            output_path
+        );
+        Ok(output_path.to_string())
+    }
+
+    /// Draw a bar or line chart described by `spec` onto one page.
+    pub fn add_chart(input_path: &str, spec: &ChartSpec, output_path: &str) -> PdfResult<String> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot add a chart to an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let page = spec.page.unwrap_or(0);
+        let page_id = doc
+            .get_pages()
+            .into_values()
+            .nth(page)
+            .ok_or(PdfError::PageNotFound(page + 1))?;
+        let chart = chart_content(spec)?;
+
+        let font_id = helvetica_font(&mut doc);
+        let mut resources = page_resources(&doc, page_id);
+        add_named_resource(&doc, &mut resources, b"Font", b"FChart", font_id);
+        let resources_id = doc.add_object(Object::Dictionary(resources));
+
+        append_page_overlay(&mut doc, page_id, chart)?;
+        doc.get_dictionary_mut(page_id)
+            .map_err(|e| PdfError::from(format!("Invalid page object: {e}")))?
+            .set("Resources", Object::Reference(resources_id));
 
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
         tracing::info!(
-            "Watermark '{}' applied to {} pages -> {}",
-            text,
-            page_count,
+            "{:?} chart with {} series added to page {} -> {}",
+            spec.kind,
+            spec.series.len(),
+            page + 1,
             output_path
         );
         Ok(output_path.to_string())
     }
-}
 
-pub fn create_render_cache(cache_size: u64, max_memory_mb: u64) -> SharedRenderCache {
-    let mb = (max_memory_mb * 1024 * 1024) as usize;
-    Arc::new(RenderCache::new(
-        cache_size as usize,
-        if mb == 0 { 512 * 1024 * 1024 } else { mb },
-    ))
-}
+    /// Encrypt a PDF with AES-256 (`/V 5`, `/R 6`). Opening the result
+    /// needs `user_password`, which may be empty to let anyone open it
+    /// under `permissions`; `owner_password` lifts the restrictions.
+    pub fn encrypt_pdf(
+        input_path: &str,
+        output_path: &str,
+        user_password: &str,
+        owner_password: &str,
+        permissions: lopdf::Permissions,
+    ) -> PdfResult<String> {
+        use lopdf::encryption::crypt_filters::{Aes256CryptFilter, CryptFilter};
+        use rand::RngCore;
 
-#[derive(Clone, Debug)]
-pub struct Bookmark {
-    pub title: String,
-    pub page_index: usize,
-}
+        if owner_password.is_empty() {
+            return Err(PdfError::from("An owner password is required"));
+        }
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "The PDF is already encrypted; save an unprotected copy first",
+            ));
+        }
+        let mut file_key = [0u8; 32];
+        rand::rng().fill_bytes(&mut file_key);
+        let filter: std::sync::Arc<dyn CryptFilter> = std::sync::Arc::new(Aes256CryptFilter);
+        let state = lopdf::EncryptionState::try_from(lopdf::EncryptionVersion::V5 {
+            encrypt_metadata: true,
+            crypt_filters: std::collections::BTreeMap::from([(b"StdCF".to_vec(), filter)]),
+            file_encryption_key: &file_key,
+            stream_filter: b"StdCF".to_vec(),
+            string_filter: b"StdCF".to_vec(),
+            owner_password,
+            user_password,
+            permissions,
+        })
+        .map_err(|e| PdfError::from(format!("Cannot set up encryption: {e}")))?;
+        doc.encrypt(&state)
+            .map_err(|e| PdfError::from(format!("Encryption failed: {e}")))?;
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!("Encrypted {} -> {}", input_path, output_path);
+        Ok(output_path.to_string())
+    }
+
+    /// Check a file for structural damage (header, EOF marker, xref offsets,
+    /// trailer) and for common PDF/A violations (encryption, non-embedded
+    /// fonts, transparency, missing metadata). An unparsable file yields an
+    /// issue rather than an error, so scripts always get a report.
+    pub fn validate_pdf(path: &str) -> PdfResult<Vec<ValidationIssue>> {
+        use crate::models::IssueSeverity::Error;
+
+        let bytes = std::fs::read(path).map_err(|e| PdfError::IoError(e.to_string()))?;
+        let mut issues = Vec::new();
+        if !bytes.starts_with(b"%PDF-") {
+            issues.push(ValidationIssue::new(
+                "header",
+                Error,
+                None,
+                "File does not start with a %PDF- header",
+            ));
+        }
+        let tail = &bytes[bytes.len().saturating_sub(1024)..];
+        if !tail.windows(5).any(|w| w == b"%%EOF") {
+            issues.push(ValidationIssue::new(
+                "eof-marker",
+                Error,
+                None,
+                "No %%EOF marker near the end of the file",
+            ));
+        }
+
+        let doc = match Document::load_mem(&bytes) {
+            Ok(doc) => doc,
+            Err(e) => {
+                issues.push(ValidationIssue::new(
+                    "parse",
+                    Error,
+                    None,
+                    format!("File could not be parsed: {e}"),
+                ));
+                return Ok(issues);
+            }
+        };
+        // lopdf quietly repairs bad offsets on load, so compare each entry
+        // against the raw bytes ourselves.
+        for (&id, entry) in &doc.reference_table.entries {
+            if let lopdf::xref::XrefEntry::Normal { offset, generation } = *entry {
+                let expected = format!("{id} {generation} obj");
+                let found = bytes
+                    .get(offset as usize..)
+                    .is_some_and(|rest| rest.starts_with(expected.as_bytes()));
+                if !found {
+                    issues.push(ValidationIssue::new(
+                        "xref-offset",
+                        Error,
+                        None,
+                        format!("Xref entry for object {id} points to offset {offset}, which is not that object"),
+                    ));
+                }
+            }
+        }
+        issues.extend(validate_document(&doc));
+        Ok(issues)
+    }
+
+    /// Compare a linearized ("fast web view") file's parameter dictionary
+    /// with the file itself: `/L` against its length, `/H` against the
+    /// primary hint stream, `/O` and `/N` against the page tree, and `/E`
+    /// against the first page's objects. A file that isn't linearized
+    /// yields a result with `linearized` false and no checks.
+    pub fn check_linearization(path: &str) -> PdfResult<LinearizationCheckResult> {
+        let bytes = std::fs::read(path).map_err(|e| PdfError::IoError(e.to_string()))?;
+        let mut result = LinearizationCheckResult {
+            linearized: false,
+            file_length: bytes.len(),
+            first_page_objects: None,
+            hint_offset_ok: None,
+            length_matches: None,
+            checks: Vec::new(),
+        };
+        // The parameter dictionary must be the first object in the file.
+        let objects = scan_object_offsets(&bytes);
+        let Some((&params_id, &params_offset)) = objects.iter().min_by_key(|&(_, &offset)| offset)
+        else {
+            return Ok(result);
+        };
+        if params_offset > 1024 {
+            return Ok(result);
+        }
+        let doc = Document::load_mem(&bytes).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let Some(params) = doc
+            .get_dictionary(params_id)
+            .ok()
+            .filter(|d| d.get(b"Linearized").is_ok())
+        else {
+            return Ok(result);
+        };
+        result.linearized = true;
+        let offset = |obj: Option<&Object>| {
+            obj.and_then(|o| o.as_i64().ok())
+                .and_then(|v| usize::try_from(v).ok())
+        };
+        let check = |field: &str, passed: bool, message: String| LinearizationCheck {
+            field: field.to_string(),
+            passed,
+            message,
+        };
+
+        let length_ok = match offset(params.get(b"L").ok()) {
+            Some(length) if length == bytes.len() => {
+                result
+                    .checks
+                    .push(check("L", true, format!("File length is {length} bytes")));
+                true
+            }
+            Some(length) => {
+                result.checks.push(check(
+                    "L",
+                    false,
+                    format!(
+                        "/L is {length} but the file is {} bytes; it was changed after linearizing",
+                        bytes.len()
+                    ),
+                ));
+                false
+            }
+            None => {
+                result
+                    .checks
+                    .push(check("L", false, "/L is missing".into()));
+                false
+            }
+        };
+        result.length_matches = Some(length_ok);
+
+        let hint = params.get(b"H").and_then(Object::as_array).ok();
+        let hint_offset = offset(hint.and_then(|h| h.first()));
+        let hint_id = hint_offset
+            .and_then(|at| objects.iter().find(|&(_, &o)| o == at))
+            .map(|(&id, _)| id)
+            .filter(|&id| matches!(doc.get_object(id), Ok(Object::Stream(_))));
+        let hint_ok = match (hint_offset, hint_id) {
+            (Some(at), Some(id)) => {
+                result.checks.push(check(
+                    "H",
+                    true,
+                    format!("Hint stream {} {} is at offset {at}", id.0, id.1),
+                ));
+                // The length covers the whole object, up to `endobj` and
+                // perhaps its end-of-line marker.
+                let end = find_from(&bytes, b"endstream", at)
+                    .and_then(|pos| find_from(&bytes, b"endobj", pos))
+                    .map(|pos| pos + 6);
+                let eol = end.map_or(0, |end| {
+                    bytes[end..]
+                        .iter()
+                        .take(2)
+                        .take_while(|b| matches!(b, b'\r' | b'\n'))
+                        .count()
+                });
+                let listed = offset(hint.and_then(|h| h.get(1)));
+                let spans = end.map(|end| end - at);
+                result.checks.push(match (listed, spans) {
+                    (Some(listed), Some(span)) if (span..=span + eol).contains(&listed) => {
+                        check("H", true, format!("Hint stream is {listed} bytes"))
+                    }
+                    (listed, span) => check(
+                        "H",
+                        false,
+                        format!(
+                            "Hint stream length is listed as {} but the object spans {}",
+                            listed.map_or_else(|| "nothing".into(), |l| l.to_string()),
+                            span.map_or_else(
+                                || "an unterminated range".into(),
+                                |s| format!("{s} bytes")
+                            ),
+                        ),
+                    ),
+                });
+                true
+            }
+            (Some(at), None) => {
+                result.checks.push(check(
+                    "H",
+                    false,
+                    format!("No hint stream starts at offset {at}"),
+                ));
+                false
+            }
+            (None, _) => {
+                result
+                    .checks
+                    .push(check("H", false, "/H is missing".into()));
+                false
+            }
+        };
+        result.hint_offset_ok = Some(hint_ok);
+
+        let pages = doc.get_pages();
+        let first_page = pages.values().next().copied();
+        let listed_first = params.get(b"O").and_then(Object::as_i64).ok();
+        result.checks.push(match (listed_first, first_page) {
+            (Some(listed), Some(page)) if listed == i64::from(page.0) => {
+                check("O", true, format!("First page is object {listed}"))
+            }
+            (listed, page) => check(
+                "O",
+                false,
+                format!(
+                    "/O is {} but the first page is object {}",
+                    listed.map_or_else(|| "missing".into(), |l| l.to_string()),
+                    page.map_or_else(|| "missing".into(), |p| p.0.to_string()),
+                ),
+            ),
+        });
+
+        let listed_pages = offset(params.get(b"N").ok());
+        result.checks.push(if listed_pages == Some(pages.len()) {
+            check("N", true, format!("{} pages", pages.len()))
+        } else {
+            check(
+                "N",
+                false,
+                format!(
+                    "/N is {} but the document has {} pages",
+                    listed_pages.map_or_else(|| "missing".into(), |n| n.to_string()),
+                    pages.len()
+                ),
+            )
+        });
+
+        let first_page_end = offset(params.get(b"E").ok());
+        let first_page_offset = first_page.and_then(|id| objects.get(&id).copied());
+        result
+            .checks
+            .push(match (first_page_end, first_page_offset) {
+                (Some(end), Some(page)) if page < end && end <= bytes.len() => {
+                    check("E", true, format!("First page ends at offset {end}"))
+                }
+                (Some(end), _) if end > bytes.len() => {
+                    check("E", false, format!("/E {end} is past the end of the file"))
+                }
+                (Some(end), _) => check(
+                    "E",
+                    false,
+                    format!("The first page object is not before /E {end}"),
+                ),
+                (None, _) => check("E", false, "/E is missing".into()),
+            });
+        result.first_page_objects = first_page_end.map(|end| {
+            objects
+                .iter()
+                .filter(|&(&id, &at)| at > params_offset && at < end && Some(id) != hint_id)
+                .count()
+        });
+        Ok(result)
+    }
+
+    /// Recover a file whose cross-reference data is missing or wrong by
+    /// scanning for `N G obj` headers, rebuilding the xref from the actual
+    /// offsets and the trailer from the last `/Root` (or the catalog), then
+    /// writing a clean copy.
+    pub fn repair_pdf(input_path: &str, output_path: &str) -> PdfResult<RepairResult> {
+        let original = std::fs::read(input_path).map_err(|e| PdfError::IoError(e.to_string()))?;
+        let mut issues = Vec::new();
+
+        let mut bytes = match original.windows(5).take(1024).position(|w| w == b"%PDF-") {
+            Some(0) => original,
+            Some(start) => {
+                issues.push(RepairIssue::BadHeader);
+                original[start..].to_vec()
+            }
+            None => {
+                issues.push(RepairIssue::BadHeader);
+                [b"%PDF-1.7\n".as_slice(), original.as_slice()].concat()
+            }
+        };
+        if !bytes[bytes.len().saturating_sub(1024)..]
+            .windows(5)
+            .any(|w| w == b"%%EOF")
+        {
+            issues.push(RepairIssue::MissingEof);
+        }
+
+        let objects = scan_object_offsets(&bytes);
+        if objects.is_empty() {
+            return Err(PdfError::OpenFailed("No objects found in file".into()));
+        }
+
+        let startxref = last_startxref(&bytes);
+        let points_at_xref = startxref.is_some_and(|offset| {
+            bytes
+                .get(offset..)
+                .is_some_and(|rest| rest.starts_with(b"xref"))
+                || objects.values().any(|&o| o == offset)
+        });
+        if !points_at_xref {
+            issues.push(RepairIssue::BadStartXref { offset: startxref });
+        } else if let Some(table) = startxref.and_then(|o| classic_xref_offsets(&bytes, o)) {
+            for ((id, generation), listed) in table {
+                if let Some(&actual) = objects.get(&(id, generation))
+                    && actual != listed
+                {
+                    issues.push(RepairIssue::MisplacedObject {
+                        id,
+                        generation,
+                        listed,
+                        actual,
+                    });
+                }
+            }
+        }
+
+        // Append a fresh classic xref covering every recovered object so the
+        // damaged sections are never consulted. Object streams listed there
+        // are unpacked on load, so their members are recovered too.
+        if !bytes.ends_with(b"\n") {
+            bytes.push(b'\n');
+        }
+        let xref_offset = bytes.len();
+        let size = objects.keys().map(|(id, _)| id + 1).max().unwrap_or(1);
+        let tail = format!(
+            "{}trailer\n<< /Size {size} >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            xref_sections(&objects)
+        );
+        bytes.extend_from_slice(tail.as_bytes());
+
+        let mut doc = Document::load_mem(&bytes)
+            .map_err(|e| PdfError::OpenFailed(format!("Still unreadable after repair: {e}")))?;
+        let is_catalog = |id: &ObjectId| {
+            doc.get_dictionary(*id)
+                .is_ok_and(|dict| dict.has_type(b"Catalog"))
+        };
+        let root = find_last(&bytes[..xref_offset], b"/Root")
+            .and_then(|pos| parse_reference_at(&bytes[pos + 5..]))
+            .filter(is_catalog);
+        let root = match root {
+            Some(root) => root,
+            None => {
+                issues.push(RepairIssue::MissingRoot);
+                find_catalog(&bytes, &objects)
+                    .or_else(|| doc.objects.keys().rev().copied().find(is_catalog))
+                    .ok_or_else(|| PdfError::OpenFailed("No document catalog found".into()))?
+            }
+        };
+        let info = find_last(&bytes[..xref_offset], b"/Info")
+            .and_then(|pos| parse_reference_at(&bytes[pos + 5..]))
+            .filter(|id| doc.objects.contains_key(id));
+        doc.trailer.set("Root", Object::Reference(root));
+        if let Some(info) = info {
+            doc.trailer.set("Info", Object::Reference(info));
+        }
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        Ok(RepairResult {
+            output_path: output_path.to_string(),
+            objects_recovered: doc.objects.len(),
+            issues,
+        })
+    }
+
+    /// Rewrite the document like `mutool clean`, fixing minor structural
+    /// problems on the way: page tree `/Count`s and `/Type`s, and `null`
+    /// dictionary entries. `options` adds garbage collection, stream
+    /// decompression and content sanitizing. Returns the output path and
+    /// how many unused objects were dropped.
+    pub fn clean_pdf(
+        input_path: &str,
+        output_path: &str,
+        options: CleanOptions,
+    ) -> PdfResult<(String, usize)> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot clean an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        if options.sanitize {
+            for page_id in doc.get_pages().into_values() {
+                let content = doc.get_page_content(page_id);
+                let content = crate::content_stream::sanitize_content_stream(&content);
+                let content_id =
+                    doc.add_object(lopdf::Stream::new(lopdf::Dictionary::new(), content));
+                doc.get_dictionary_mut(page_id)
+                    .map_err(|e| PdfError::from(format!("Invalid page object: {e}")))?
+                    .set("Contents", Object::Reference(content_id));
+            }
+        }
+        let fixed = fix_structure(&mut doc);
+
+        let mut removed = 0;
+        if options.garbage {
+            let before = doc.objects.len();
+            doc.prune_objects();
+            removed = before - doc.objects.len();
+            doc.renumber_objects();
+        }
+        if options.decompress {
+            doc.decompress();
+        } else {
+            doc.compress();
+        }
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+
+        tracing::info!(
+            "Cleaned {} (fixed {}, removed {} objects) -> {}",
+            input_path,
+            fixed,
+            removed,
+            output_path
+        );
+        Ok((output_path.to_string(), removed))
+    }
+
+    /// Draw every form field's current appearance into the page content and
+    /// remove the widgets and `/AcroForm`, leaving a static PDF. Returns the
+    /// output path and how many widgets were flattened.
+    pub fn flatten_forms(input_path: &str, output_path: &str) -> PdfResult<(String, usize)> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        if doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot flatten an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let flattened = flatten_form_fields(&mut doc)?;
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+        tracing::info!("Flattened {} form widgets -> {}", flattened, output_path);
+        Ok((output_path.to_string(), flattened))
+    }
+
+    /// Write each of `page_indices` to its own `page_NNN.pdf` in `output_dir`.
+    /// `on_page` is called with `(written, total)` after every file.
+    pub fn split_pdf(
+        &self,
+        path: &str,
+        page_indices: Vec<usize>,
+        output_dir: String,
+        mut on_page: impl FnMut(usize, usize),
+    ) -> PdfResult<Vec<String>> {
+        let mut created_paths = Vec::new();
+        let template_doc = Document::load(path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        // lopdf would copy the encrypted streams verbatim into files that no
+        // longer carry the key, so refuse instead of writing unreadable pages.
+        if template_doc.is_encrypted() {
+            return Err(PdfError::from(
+                "Cannot split an encrypted PDF; save an unprotected copy first",
+            ));
+        }
+        let page_count = template_doc.get_pages().len();
+        if let Some(&bad) = page_indices.iter().find(|&&p| p >= page_count) {
+            return Err(PdfError::PageNotFound(bad + 1));
+        }
+        let digits = page_count.to_string().len().max(3);
+
+        for &page_idx in &page_indices {
+            let mut doc = template_doc.clone();
+            let keep_page_1_based = (page_idx + 1) as u32;
+
+            let mut to_delete = Vec::new();
+            for p in 1..=(page_count as u32) {
+                if p != keep_page_1_based {
+                    to_delete.push(p);
+                }
+            }
+
+            doc.delete_pages(&to_delete);
+            doc.prune_objects();
+
+            let out_path = std::path::Path::new(&output_dir)
+                .join(format!("page_{:0digits$}.pdf", page_idx + 1))
+                .to_string_lossy()
+                .to_string();
+            doc.save(&out_path)
+                .map_err(|e| PdfError::IoError(e.to_string()))?;
+            created_paths.push(out_path);
+            on_page(created_paths.len(), page_indices.len());
+        }
+
+        Ok(created_paths)
+    }
+
+    pub fn get_form_fields(&mut self, path: &str) -> PdfResult<Vec<FormField>> {
+        let doc_id = self
+            .paths
+            .iter()
+            .find(|(_, p)| *p == path)
+            .map(|(id, _)| *id);
+
+        if let Some(id) = doc_id {
+            if let Some(doc) = self.documents.get(&id) {
+                return Ok(self.extract_form_fields_from_doc(doc));
+            }
+        }
+
+        let data = std::fs::read(path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        let doc = PdfDocument::open(data).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+        Ok(self.extract_form_fields_from_doc(&doc))
+    }
+
+    fn extract_form_fields_from_doc(&self, doc: &PdfDocument) -> Vec<FormField> {
+        let mut fields = Vec::new();
+        if let Some(acro) = doc.acro_form() {
+            for f in &acro.fields {
+                let name = f.name.clone();
+                let variant = match f.kind {
+                    FieldKind::Text => {
+                        let val = match &f.value {
+                            Some(FieldValue::Text(s)) => s.clone(),
+                            _ => String::new(),
+                        };
+                        FormFieldVariant::Text { value: val }
+                    }
+                    FieldKind::Button => {
+                        let is_checked = match &f.value {
+                            Some(FieldValue::Name(n)) => n != "Off",
+                            _ => false,
+                        };
+                        if f.flags & FF_RADIO != 0 {
+                            FormFieldVariant::RadioButton {
+                                is_selected: is_checked,
+                                group_name: Some(name.clone()),
+                            }
+                        } else {
+                            FormFieldVariant::Checkbox { is_checked }
+                        }
+                    }
+                    FieldKind::Choice => {
+                        let opts: Vec<String> =
+                            f.options.iter().map(|(_, label)| label.clone()).collect();
+                        let selected_val = match &f.value {
+                            Some(FieldValue::Text(s)) => Some(s.clone()),
+                            _ => None,
+                        };
+                        let selected_index = selected_val.and_then(|val| {
+                            f.options.iter().position(|(export, _)| *export == val)
+                        });
+                        FormFieldVariant::ComboBox {
+                            options: opts,
+                            selected_index,
+                        }
+                    }
+                    _ => FormFieldVariant::Text {
+                        value: String::new(),
+                    },
+                };
+
+                let mut page_idx = 0;
+                if let Some(&widget_id) = f.widgets.first() {
+                    for i in 0..doc.page_count() {
+                        if let Ok(page) = doc.page(i) {
+                            if page.annots.contains(&widget_id) {
+                                page_idx = i;
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                fields.push(FormField {
+                    name,
+                    variant,
+                    page: page_idx,
+                });
+            }
+        }
+        fields
+    }
+
+    pub fn fill_form(
+        &mut self,
+        path: &str,
+        updates: Vec<FormField>,
+        output_path: String,
+    ) -> PdfResult<String> {
+        let values = updates.into_iter().map(|update| {
+            let val_str = match &update.variant {
+                FormFieldVariant::Text { value } => value.clone(),
+                FormFieldVariant::Checkbox { is_checked } => {
+                    if *is_checked {
+                        "Yes".to_string()
+                    } else {
+                        "Off".to_string()
+                    }
+                }
+                FormFieldVariant::RadioButton { is_selected, .. } => {
+                    if *is_selected {
+                        "Yes".to_string()
+                    } else {
+                        "Off".to_string()
+                    }
+                }
+                FormFieldVariant::ComboBox {
+                    selected_index,
+                    options,
+                } => {
+                    if let Some(idx) = selected_index {
+                        options.get(*idx).cloned().unwrap_or_default()
+                    } else {
+                        String::new()
+                    }
+                }
+            };
+            (update.name, val_str)
+        });
+        let (output_path, missing) = Self::fill_form_values(path, values, output_path)?;
+        for name in missing {
+            tracing::warn!("Failed to set field {}: not found", name);
+        }
+        Ok(output_path)
+    }
+
+    /// Set fields by name and append the change as an incremental update.
+    /// A name matches a fully qualified field name or, failing that, the
+    /// last `/T` component of any field. Text goes through zpdf's
+    /// `FormFiller`, which writes Unicode values and rebuilds appearances.
+    /// Returns the output path and the names that matched no field, sorted.
+    pub fn fill_form_values(
+        path: &str,
+        values: impl IntoIterator<Item = (String, String)>,
+        output_path: String,
+    ) -> PdfResult<(String, Vec<String>)> {
+        let data = std::fs::read(path).map_err(|e| PdfError::IoError(e.to_string()))?;
+        let mut writer =
+            IncrementalWriter::new(data).map_err(|e| PdfError::IoError(e.to_string()))?;
+
+        let field_names: Vec<String> = writer
+            .document()
+            .acro_form()
+            .map(|acro| acro.fields.iter().map(|f| f.name.clone()).collect())
+            .unwrap_or_default();
+        let mut missing = Vec::new();
+        let mut resolved = Vec::new();
+        for (key, value) in values {
+            let matches: Vec<&String> = if field_names.contains(&key) {
+                vec![&key]
+            } else {
+                field_names
+                    .iter()
+                    .filter(|name| name.rsplit('.').next() == Some(key.as_str()))
+                    .collect()
+            };
+            if matches.is_empty() {
+                missing.push(key.clone());
+            }
+            resolved.extend(
+                matches
+                    .into_iter()
+                    .map(|name| (name.clone(), value.clone())),
+            );
+        }
+        missing.sort();
+
+        {
+            let mut filler =
+                FormFiller::new(&mut writer).map_err(|e| PdfError::IoError(e.to_string()))?;
+            for (name, value) in &resolved {
+                if let Err(e) = filler.set(name, value) {
+                    tracing::warn!("Failed to set field {}: {}", name, e);
+                }
+            }
+            filler
+                .finish()
+                .map_err(|e| PdfError::IoError(e.to_string()))?;
+        }
+
+        let mut file =
+            std::fs::File::create(&output_path).map_err(|e| PdfError::IoError(e.to_string()))?;
+        writer
+            .write(&mut file)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+
+        if !missing.is_empty() {
+            tracing::warn!("Form fields not found: {}", missing.join(", "));
+        }
+        Ok((output_path, missing))
+    }
+
+    #[cfg(windows)]
+    pub fn print_document(path: &str, printer_name: Option<&str>) -> PdfResult<()> {
+        use winprint::printer::{FilePrinter, PrinterDevice, WinPdfPrinter};
+
+        let all_devices = PrinterDevice::all()
+            .map_err(|e| PdfError::IoError(format!("Failed to list printers: {e}")))?;
+
+        let device = if let Some(name) = printer_name {
+            all_devices
+                .into_iter()
+                .find(|d| d.name() == name)
+                .ok_or_else(|| PdfError::IoError(format!("Printer '{name}' not found")))?
+        } else {
+            all_devices
+                .into_iter()
+                .next()
+                .ok_or_else(|| PdfError::IoError("No printers found".into()))?
+        };
+
+        let printer = WinPdfPrinter::new(device);
+        printer
+            .print(std::path::Path::new(path), Default::default())
+            .map_err(|e| PdfError::IoError(format!("Print failed: {e}")))?;
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    pub fn print_document(_path: &str, _printer_name: Option<&str>) -> PdfResult<()> {
+        Err(PdfError::IoError(
+            "Printing is only supported on Windows".into(),
+        ))
+    }
+
+    /// Returns a sorted list of all available printer names on this system.
+    #[cfg(windows)]
+    pub fn list_printers() -> PdfResult<Vec<String>> {
+        use winprint::printer::PrinterDevice;
+        PrinterDevice::all()
+            .map(|devices| {
+                let mut names: Vec<String> =
+                    devices.into_iter().map(|d| d.name().to_string()).collect();
+                names.sort_unstable();
+                names
+            })
+            .map_err(|e| PdfError::IoError(format!("Failed to list printers: {e}")))
+    }
+
+    #[cfg(not(windows))]
+    pub fn list_printers() -> PdfResult<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    pub fn add_watermark(
+        input_path: &str,
+        text: &str,
+        options: WatermarkOptions,
+        output_path: &str,
+    ) -> PdfResult<String> {
+        let mut doc =
+            Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+
+        let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        let page_count = pages.len();
+
+        let font_ref_id = helvetica_font(&mut doc);
+        let opacity = options.opacity.clamp(0.0, 1.0);
+        let gs_ref_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"ExtGState".to_vec())),
+            ("ca", Object::Real(opacity)),
+            ("CA", Object::Real(opacity)),
+        ])));
+
+        // Helvetica averages roughly half an em per glyph; close enough to
+        // centre the text on its anchor point.
+        let text_width = text.chars().count() as f32 * options.font_size * 0.5;
+        let (sin, cos) = options.angle_degrees.to_radians().sin_cos();
+
+        for &page_id in &pages {
+            let [x0, y0, x1, y1] = page_media_box(&doc, page_id);
+            let anchor_x = options.x.unwrap_or((x0 + x1) / 2.0);
+            let anchor_y = options.y.unwrap_or((y0 + y1) / 2.0);
+
+            let mut content = pdf_writer::Content::new();
+            content.save_state();
+            content.set_parameters(pdf_writer::Name(b"GSWm"));
+            content.transform([cos, sin, -sin, cos, anchor_x, anchor_y]);
+            content.begin_text();
+            content.set_font(pdf_writer::Name(b"FWm"), options.font_size);
+            content.set_fill_rgb(0.7, 0.7, 0.7);
+            content.set_text_matrix([
+                1.0,
+                0.0,
+                0.0,
+                1.0,
+                -text_width / 2.0,
+                -options.font_size / 3.0,
+            ]);
+            content.show(pdf_writer::Str(&win_ansi_bytes(text)));
+            content.end_text();
+            content.restore_state();
+            append_page_overlay(&mut doc, page_id, content.finish().into_vec())?;
+
+            let mut res_dict = page_resources(&doc, page_id);
+            add_named_resource(&doc, &mut res_dict, b"Font", b"FWm", font_ref_id);
+            add_named_resource(&doc, &mut res_dict, b"ExtGState", b"GSWm", gs_ref_id);
+            let res_id = doc.add_object(Object::Dictionary(res_dict));
+
+            let page_dict = doc
+                .objects
+                .get_mut(&page_id)
+                .and_then(|o| o.as_dict_mut().ok())
+                .ok_or_else(|| PdfError::EngineError("Invalid page object".into()))?;
+            page_dict.set("Resources", Object::Reference(res_id));
+        }
+
+        doc.save(output_path)
+            .map_err(|e| PdfError::IoError(e.to_string()))?;
+
+        tracing::info!(
+            "Watermark '{}' applied to {} pages -> {}",
+            text,
+            page_count,
+            output_path
+        );
+        Ok(output_path.to_string())
+    }
+}
+
+pub fn create_render_cache(cache_size: u64, max_memory_mb: u64) -> SharedRenderCache {
+    let mb = (max_memory_mb * 1024 * 1024) as usize;
+    Arc::new(RenderCache::new(
+        cache_size as usize,
+        if mb == 0 { 512 * 1024 * 1024 } else { mb },
+    ))
+}
+
+/// Source object id to destination object id.
+pub type ObjectMap = HashMap<ObjectId, ObjectId>;
+
+/// Page attributes a page may inherit from its ancestors in the page tree.
+const INHERITABLE_PAGE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Copies objects between documents together with everything they
+/// reference. Use one context per source/destination pair: objects it has
+/// already copied are reused, so pages sharing a font or image copy it once.
+#[derive(Debug, Default)]
+pub struct CopyContext {
+    map: ObjectMap,
+}
+
+impl CopyContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Where each copied source object ended up.
+    pub const fn object_map(&self) -> &ObjectMap {
+        &self.map
+    }
+
+    /// Copy `id` and its transitive closure from `from` into `to`, returning
+    /// the new id. References are remapped, and cycles (such as an
+    /// annotation's `/P` pointing back at its page) are followed once. A
+    /// page is copied without `/Parent`, with any inherited attributes made
+    /// explicit, so the rest of the source page tree isn't pulled along.
+    /// References to missing objects become `null`.
+    pub fn copy_object(&mut self, from: &Document, id: ObjectId, to: &mut Document) -> ObjectId {
+        let mut pending = Vec::new();
+        let new_id = self.remap(id, to, &mut pending);
+        while let Some(source_id) = pending.pop() {
+            let mut obj = from.get_object(source_id).cloned().unwrap_or(Object::Null);
+            if let Object::Dictionary(dict) = &mut obj
+                && matches!(dict.get(b"Type"), Ok(Object::Name(t)) if t == b"Page")
+            {
+                detach_page(from, dict);
+            }
+            self.remap_references(&mut obj, to, &mut pending);
+            to.objects.insert(self.map[&source_id], obj);
+        }
+        new_id
+    }
+
+    fn remap(&mut self, id: ObjectId, to: &mut Document, pending: &mut Vec<ObjectId>) -> ObjectId {
+        if let Some(&mapped) = self.map.get(&id) {
+            return mapped;
+        }
+        let mapped = to.new_object_id();
+        self.map.insert(id, mapped);
+        pending.push(id);
+        mapped
+    }
+
+    fn remap_references(
+        &mut self,
+        obj: &mut Object,
+        to: &mut Document,
+        pending: &mut Vec<ObjectId>,
+    ) {
+        match obj {
+            Object::Reference(id) => *id = self.remap(*id, to, pending),
+            Object::Array(items) => {
+                for item in items {
+                    self.remap_references(item, to, pending);
+                }
+            }
+            Object::Dictionary(dict) => {
+                for (_, value) in dict.iter_mut() {
+                    self.remap_references(value, to, pending);
+                }
+            }
+            Object::Stream(stream) => {
+                for (_, value) in stream.dict.iter_mut() {
+                    self.remap_references(value, to, pending);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replace a page's `/Parent` with the attributes it would have inherited.
+fn detach_page(doc: &Document, page: &mut lopdf::Dictionary) {
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+    page.remove(b"Parent");
+    while let Some(id) = parent {
+        let Ok(node) = doc.get_dictionary(id) else {
+            break;
+        };
+        for key in INHERITABLE_PAGE_KEYS {
+            if !page.has(key)
+                && let Ok(value) = node.get(key)
+            {
+                page.set(key.to_vec(), value.clone());
+            }
+        }
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+}
+
+/// One outline entry in pre-order; `depth` is its nesting level (0 = top).
+#[derive(Clone, Debug)]
+pub struct Bookmark {
+    pub title: String,
+    pub page_index: usize,
+    pub depth: usize,
+}
+
+/// Follow an indirect reference, or return `obj` unchanged.
+pub(crate) fn resolve<'a>(doc: &'a Document, obj: &'a Object) -> &'a Object {
+    match obj {
+        Object::Reference(id) => doc.get_object(*id).unwrap_or(obj),
+        _ => obj,
+    }
+}
+
+/// Look up a field attribute on a widget, walking `/Parent` up the field
+/// tree for inherited values such as `/FT` and `/V`.
+fn inherited_field_attr<'a>(
+    doc: &'a Document,
+    mut dict: &'a lopdf::Dictionary,
+    key: &[u8],
+) -> Option<&'a Object> {
+    for _ in 0..32 {
+        if let Ok(value) = dict.get(key) {
+            return Some(resolve(doc, value));
+        }
+        dict = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .ok()?;
+    }
+    None
+}
+
+/// The metadata fields of an Info dictionary.
+fn info_metadata(doc: &Document, info: &lopdf::Dictionary) -> crate::models::DocumentMetadata {
+    let text = |key: &[u8]| {
+        info.get(key)
+            .map(|obj| resolve(doc, obj))
+            .and_then(Object::as_str)
+            .ok()
+            .map(decode_pdf_string)
+    };
+    crate::models::DocumentMetadata {
+        title: text(b"Title"),
+        author: text(b"Author"),
+        subject: text(b"Subject"),
+        keywords: text(b"Keywords"),
+        creator: text(b"Creator"),
+        producer: text(b"Producer"),
+        creation_date: text(b"CreationDate"),
+        modification_date: text(b"ModDate"),
+    }
+}
+
+/// Each field of `first`, or of `second` where `first` has none.
+fn metadata_or(
+    first: crate::models::DocumentMetadata,
+    second: crate::models::DocumentMetadata,
+) -> crate::models::DocumentMetadata {
+    crate::models::DocumentMetadata {
+        title: first.title.or(second.title),
+        author: first.author.or(second.author),
+        subject: first.subject.or(second.subject),
+        keywords: first.keywords.or(second.keywords),
+        creator: first.creator.or(second.creator),
+        producer: first.producer.or(second.producer),
+        creation_date: first.creation_date.or(second.creation_date),
+        modification_date: first.modification_date.or(second.modification_date),
+    }
+}
+
+/// Decode a PDF text string: UTF-16BE with a BOM, otherwise Latin-1.
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFE, 0xFF, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+fn rect_of(obj: &Object) -> Option<[f32; 4]> {
+    let arr = obj.as_array().ok()?;
+    let [a, b, c, d] = arr.as_slice() else {
+        return None;
+    };
+    let (a, b, c, d) = (
+        a.as_float().ok()?,
+        b.as_float().ok()?,
+        c.as_float().ok()?,
+        d.as_float().ok()?,
+    );
+    Some([a.min(c), b.min(d), a.max(c), b.max(d)])
+}
+
+/// What a widget contributes once flattened.
+enum FlatItem {
+    /// Draw the appearance stream with this placement matrix.
+    Appearance(ObjectId, [f32; 6]),
+    /// No usable appearance; draw the value as plain text in the rect.
+    Text(String, [f32; 4]),
+}
+
+/// The flattened form of one widget, or `None` when it draws nothing (hidden,
+/// or an appearance state such as `/Off` with no matching stream).
+fn flatten_widget(doc: &Document, widget: &lopdf::Dictionary) -> Option<FlatItem> {
+    const HIDDEN: i64 = 1 << 1;
+    if widget.get(b"F").and_then(Object::as_i64).unwrap_or(0) & HIDDEN != 0 {
+        return None;
+    }
+    let rect = rect_of(widget.get(b"Rect").ok()?)?;
+
+    let normal = widget
+        .get(b"AP")
+        .ok()
+        .and_then(|ap| resolve(doc, ap).as_dict().ok())
+        .and_then(|ap| ap.get(b"N").ok());
+    let state = widget.get(b"AS").and_then(Object::as_name).ok();
+    if let Some(normal) = normal {
+        let stream_id = match normal {
+            Object::Reference(id) if matches!(doc.get_object(*id), Ok(Object::Stream(_))) => *id,
+            other => {
+                // A state dictionary: pick the stream for `/AS`, if any.
+                let states = resolve(doc, other).as_dict().ok()?;
+                states.get(state?).and_then(Object::as_reference).ok()?
+            }
+        };
+        let stream = doc.get_object(stream_id).and_then(Object::as_stream).ok()?;
+        let bbox = stream
+            .dict
+            .get(b"BBox")
+            .ok()
+            .and_then(|b| rect_of(resolve(doc, b)))
+            .unwrap_or(rect);
+        let m: Vec<f32> = stream
+            .dict
+            .get(b"Matrix")
+            .and_then(Object::as_array)
+            .map(|a| a.iter().filter_map(|v| v.as_float().ok()).collect())
+            .unwrap_or_default();
+        let m = if m.len() == 6 {
+            m
+        } else {
+            vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0]
+        };
+        // Map the matrix-transformed BBox onto the widget rect (PDF 32000
+        // 12.5.5), which is what a viewer does when it draws the widget.
+        let corners = [
+            (bbox[0], bbox[1]),
+            (bbox[2], bbox[1]),
+            (bbox[0], bbox[3]),
+            (bbox[2], bbox[3]),
+        ]
+        .map(|(x, y)| (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5]));
+        let (bx0, bx1) = corners.iter().fold((f32::MAX, f32::MIN), |(lo, hi), c| {
+            (lo.min(c.0), hi.max(c.0))
+        });
+        let (by0, by1) = corners.iter().fold((f32::MAX, f32::MIN), |(lo, hi), c| {
+            (lo.min(c.1), hi.max(c.1))
+        });
+        let sx = if bx1 > bx0 {
+            (rect[2] - rect[0]) / (bx1 - bx0)
+        } else {
+            1.0
+        };
+        let sy = if by1 > by0 {
+            (rect[3] - rect[1]) / (by1 - by0)
+        } else {
+            1.0
+        };
+        return Some(FlatItem::Appearance(
+            stream_id,
+            [sx, 0.0, 0.0, sy, rect[0] - bx0 * sx, rect[1] - by0 * sy],
+        ));
+    }
+
+    let is_button = inherited_field_attr(doc, widget, b"FT").and_then(|ft| ft.as_name().ok())
+        == Some(b"Btn".as_slice());
+    let text = if is_button {
+        let on = state
+            .or_else(|| inherited_field_attr(doc, widget, b"V").and_then(|v| v.as_name().ok()))
+            .is_some_and(|s| s != b"Off");
+        on.then(|| "X".to_string())?
+    } else {
+        match inherited_field_attr(doc, widget, b"V")? {
+            Object::String(bytes, _) => decode_pdf_string(bytes),
+            Object::Array(values) => match values.first().map(|v| resolve(doc, v)) {
+                Some(Object::String(bytes, _)) => decode_pdf_string(bytes),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    };
+    (!text.is_empty()).then_some(FlatItem::Text(text, rect))
+}
+
+/// Burn every form widget into its page's content and drop the AcroForm.
+/// Returns how many widgets were removed.
+fn flatten_form_fields(doc: &mut Document) -> PdfResult<usize> {
+    let mut plans = Vec::new();
+    let mut removed = 0;
+    for page_id in doc.get_pages().into_values() {
+        let Some(annots) = doc
+            .get_dictionary(page_id)
+            .ok()
+            .and_then(|page| page.get(b"Annots").ok())
+            .and_then(|a| resolve(doc, a).as_array().ok())
+        else {
+            continue;
+        };
+        let mut keep = Vec::new();
+        let mut items = Vec::new();
+        for annot in annots {
+            let widget = resolve(doc, annot).as_dict().ok().filter(|d| {
+                d.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Widget".as_slice())
+            });
+            match widget {
+                Some(widget) => {
+                    removed += 1;
+                    items.extend(flatten_widget(doc, widget));
+                }
+                None => keep.push(annot.clone()),
+            }
+        }
+        if keep.len() != annots.len() {
+            plans.push((page_id, keep, items));
+        }
+    }
+
+    let mut font_id = None;
+    for (page_id, keep, items) in plans {
+        let mut content = pdf_writer::Content::new();
+        let mut resources = page_resources(doc, page_id);
+        for (i, item) in items.into_iter().enumerate() {
+            match item {
+                FlatItem::Appearance(stream_id, matrix) => {
+                    if let Ok(Object::Stream(stream)) = doc.get_object_mut(stream_id) {
+                        stream.dict.set("Type", Object::Name(b"XObject".to_vec()));
+                        stream.dict.set("Subtype", Object::Name(b"Form".to_vec()));
+                    }
+                    let name = format!("Flat{i}");
+                    content.save_state();
+                    content.transform(matrix);
+                    content.x_object(pdf_writer::Name(name.as_bytes()));
+                    content.restore_state();
+                    add_named_resource(doc, &mut resources, b"XObject", name.as_bytes(), stream_id);
+                }
+                FlatItem::Text(text, [x0, y0, x1, y1]) => {
+                    let font = *font_id.get_or_insert_with(|| helvetica_font(doc));
+                    let size = ((y1 - y0) * 0.7).clamp(4.0, 12.0);
+                    content.save_state();
+                    content.rect(x0, y0, x1 - x0, y1 - y0);
+                    content.clip_nonzero();
+                    content.end_path();
+                    content.begin_text();
+                    content.set_font(pdf_writer::Name(b"FlatF"), size);
+                    content.set_fill_gray(0.0);
+                    content.next_line(x0 + 2.0, y0 + (y1 - y0 - size) / 2.0 + size * 0.2);
+                    content.show(pdf_writer::Str(&win_ansi_bytes(&text)));
+                    content.end_text();
+                    content.restore_state();
+                    add_named_resource(doc, &mut resources, b"Font", b"FlatF", font);
+                }
+            }
+        }
+
+        append_page_overlay(doc, page_id, content.finish().into_vec())?;
+        let resources_id = doc.add_object(Object::Dictionary(resources));
+
+        let page = doc
+            .get_dictionary_mut(page_id)
+            .map_err(|e| PdfError::from(format!("Invalid page object: {e}")))?;
+        page.set("Resources", Object::Reference(resources_id));
+        if keep.is_empty() {
+            page.remove(b"Annots");
+        } else {
+            page.set("Annots", Object::Array(keep));
+        }
+    }
+
+    if let Ok(root_id) = doc.trailer.get(b"Root").and_then(Object::as_reference)
+        && let Ok(catalog) = doc.get_dictionary_mut(root_id)
+    {
+        catalog.remove(b"AcroForm");
+    }
+    doc.prune_objects();
+    Ok(removed)
+}
+
+/// Encode a PDF text string: Latin-1 when every character fits, otherwise
+/// UTF-16BE with a byte order mark.
+fn pdf_text_string(text: &str) -> Object {
+    let latin1: Option<Vec<u8>> = text
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).ok())
+        .collect();
+    let bytes = latin1.unwrap_or_else(|| {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        bytes
+    });
+    Object::String(bytes, lopdf::StringFormat::Literal)
+}
+
+fn is_javascript_action(dict: &lopdf::Dictionary) -> bool {
+    dict.get(b"S").and_then(Object::as_name).ok() == Some(b"JavaScript".as_slice())
+}
+
+/// The scripts of `action` and of the actions chained after it by `/Next`.
+fn action_scripts(doc: &Document, action: &Object, depth: usize, out: &mut Vec<String>) {
+    if depth > 32 {
+        return;
+    }
+    match resolve(doc, action) {
+        Object::Array(actions) => {
+            for action in actions {
+                action_scripts(doc, action, depth + 1, out);
+            }
+        }
+        Object::Dictionary(dict) => {
+            if is_javascript_action(dict) {
+                let code = match dict.get(b"JS").map(|js| resolve(doc, js)) {
+                    Ok(Object::String(bytes, _)) => Some(decode_pdf_string(bytes)),
+                    Ok(Object::Stream(stream)) => stream_data(stream)
+                        .ok()
+                        .map(|data| decode_pdf_string(&data)),
+                    _ => None,
+                };
+                out.extend(code);
+            }
+            if let Ok(next) = dict.get(b"Next") {
+                action_scripts(doc, next, depth + 1, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The trigger events of an `/AA` dictionary and their actions.
+fn additional_actions<'a>(
+    doc: &'a Document,
+    dict: &'a lopdf::Dictionary,
+) -> Vec<(String, &'a Object)> {
+    dict.get(b"AA")
+        .map(|aa| resolve(doc, aa))
+        .and_then(Object::as_dict)
+        .map(|aa| {
+            aa.iter()
+                .map(|(event, action)| (String::from_utf8_lossy(event).into_owned(), action))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn javascript_actions(doc: &Document) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    let mut add = |location: String, action: &Object| {
+        let mut scripts = Vec::new();
+        action_scripts(doc, action, 0, &mut scripts);
+        found.extend(scripts.into_iter().map(|code| (location.clone(), code)));
+    };
+
+    if let Ok(catalog) = doc.catalog() {
+        if let Ok(tree) = catalog
+            .get(b"Names")
+            .map(|names| resolve(doc, names))
+            .and_then(Object::as_dict)
+            .and_then(|names| names.get(b"JavaScript"))
+        {
+            for (name, action) in name_tree_entries(doc, tree) {
+                add(
+                    format!("Document script \"{}\"", decode_pdf_string(&name)),
+                    &action,
+                );
+            }
+        }
+        if let Ok(action) = catalog.get(b"OpenAction") {
+            add("Document open action".into(), action);
+        }
+        for (event, action) in additional_actions(doc, catalog) {
+            add(format!("Document action {event}"), action);
+        }
+
+        let mut item = catalog
+            .get(b"Outlines")
+            .map(|o| resolve(doc, o))
+            .and_then(Object::as_dict)
+            .and_then(|o| o.get(b"First"))
+            .ok();
+        let mut seen = HashSet::new();
+        while let Some(Object::Reference(id)) = item {
+            let Ok(outline) = doc.get_dictionary(*id) else {
+                break;
+            };
+            if !seen.insert(*id) {
+                break;
+            }
+            if let Ok(action) = outline.get(b"A") {
+                let title = outline
+                    .get(b"Title")
+                    .and_then(Object::as_str)
+                    .map(decode_pdf_string)
+                    .unwrap_or_default();
+                add(format!("Bookmark \"{title}\" action"), action);
+            }
+            // Children before siblings, as the outline reads.
+            item = outline
+                .get(b"First")
+                .or_else(|_| outline.get(b"Next"))
+                .ok()
+                .or_else(|| {
+                    let mut parent = outline.get(b"Parent").ok();
+                    while let Some(Object::Reference(pid)) = parent {
+                        let dict = doc.get_dictionary(*pid).ok()?;
+                        if let Ok(next) = dict.get(b"Next") {
+                            return Some(next);
+                        }
+                        parent = dict.get(b"Parent").ok();
+                    }
+                    None
+                });
+        }
+    }
+
+    let mut widgets = HashSet::new();
+    for (name, field_id) in terminal_fields(doc) {
+        let Ok(field) = doc.get_dictionary(field_id) else {
+            continue;
+        };
+        widgets.insert(field_id);
+        let kids: Vec<ObjectId> = field
+            .get(b"Kids")
+            .and_then(Object::as_array)
+            .map(|kids| kids.iter().filter_map(|k| k.as_reference().ok()).collect())
+            .unwrap_or_default();
+        widgets.extend(kids.iter().copied());
+        let dicts =
+            std::iter::once(field).chain(kids.iter().filter_map(|&k| doc.get_dictionary(k).ok()));
+        for dict in dicts {
+            if let Ok(action) = dict.get(b"A") {
+                add(format!("Field \"{name}\" action"), action);
+            }
+            for (event, action) in additional_actions(doc, dict) {
+                add(format!("Field \"{name}\" action {event}"), action);
+            }
+        }
+    }
+
+    for (page, page_id) in doc.get_pages().into_values().enumerate() {
+        let Ok(page_dict) = doc.get_dictionary(page_id) else {
+            continue;
+        };
+        for (event, action) in additional_actions(doc, page_dict) {
+            add(format!("Page {} action {event}", page + 1), action);
+        }
+        let annots = page_dict
+            .get(b"Annots")
+            .map(|a| resolve(doc, a))
+            .and_then(Object::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for (i, annot) in annots.iter().enumerate() {
+            if annot.as_reference().is_ok_and(|id| widgets.contains(&id)) {
+                continue;
+            }
+            let Ok(annot) = resolve(doc, annot).as_dict() else {
+                continue;
+            };
+            let location = format!("Page {} annotation {}", page + 1, i + 1);
+            if let Ok(action) = annot.get(b"A") {
+                add(format!("{location} action"), action);
+            }
+            for (event, action) in additional_actions(doc, annot) {
+                add(format!("{location} action {event}"), action);
+            }
+        }
+    }
+    found
+}
+
+/// Whether `obj` is, or starts a chain containing, a JavaScript action.
+/// `scripts` holds the JavaScript actions stored as objects of their own.
+fn runs_script(obj: &Object, scripts: &HashSet<ObjectId>) -> bool {
+    match obj {
+        Object::Reference(id) => scripts.contains(id),
+        Object::Dictionary(dict) => is_javascript_action(dict),
+        Object::Array(actions) => actions.iter().any(|a| runs_script(a, scripts)),
+        _ => false,
+    }
+}
+
+/// Remove scripts from `obj` and everything nested in it, returning how
+/// many entries went. Actions chained after a script by `/Next` are cut
+/// off with it.
+fn strip_scripts(obj: &mut Object, scripts: &HashSet<ObjectId>) -> usize {
+    match obj {
+        Object::Dictionary(dict) => strip_dict_scripts(dict, scripts),
+        Object::Stream(stream) => strip_dict_scripts(&mut stream.dict, scripts),
+        Object::Array(items) => items.iter_mut().map(|i| strip_scripts(i, scripts)).sum(),
+        _ => 0,
+    }
+}
+
+fn strip_dict_scripts(dict: &mut lopdf::Dictionary, scripts: &HashSet<ObjectId>) -> usize {
+    let mut removed = 0;
+    for key in [b"AA".as_slice(), b"JavaScript"] {
+        if dict.remove(key).is_some() {
+            removed += 1;
+        }
+    }
+    // A rendition action may carry a script alongside its other work.
+    if !is_javascript_action(dict) && dict.remove(b"JS").is_some() {
+        removed += 1;
+    }
+    for key in [b"A".as_slice(), b"OpenAction", b"Next"] {
+        if dict
+            .get(key)
+            .is_ok_and(|action| runs_script(action, scripts))
+        {
+            dict.remove(key);
+            removed += 1;
+        }
+    }
+    for (_, value) in dict.iter_mut() {
+        removed += strip_scripts(value, scripts);
+    }
+    removed
+}
+
+/// A stream's data with its filters undone.
+fn stream_data(stream: &lopdf::Stream) -> PdfResult<Vec<u8>> {
+    if stream.dict.has(b"Filter") {
+        stream
+            .decompressed_content()
+            .map_err(|e| PdfError::from(format!("Stream data is corrupt: {e}")))
+    } else {
+        Ok(stream.content.clone())
+    }
+}
+
+/// The `/3D` annotations of every page as (page, index on the page,
+/// annotation).
+fn three_d_annotations(doc: &Document) -> Vec<(usize, usize, &lopdf::Dictionary)> {
+    let mut found = Vec::new();
+    for (page, page_id) in doc.get_pages().into_values().enumerate() {
+        let Ok(annots) = doc
+            .get_dictionary(page_id)
+            .and_then(|p| p.get(b"Annots"))
+            .map(|a| resolve(doc, a))
+            .and_then(Object::as_array)
+        else {
+            continue;
+        };
+        let three_d = annots
+            .iter()
+            .filter_map(|a| resolve(doc, a).as_dict().ok())
+            .filter(|a| a.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"3D".as_slice()));
+        found.extend(
+            three_d
+                .enumerate()
+                .map(|(index, annot)| (page, index, annot)),
+        );
+    }
+    found
+}
+
+/// The 3D stream of an annotation, following a `/3DRef` if it uses one.
+fn three_d_stream<'a>(
+    doc: &'a Document,
+    annot: &'a lopdf::Dictionary,
+) -> Option<&'a lopdf::Stream> {
+    match resolve(doc, annot.get(b"3DD").ok()?) {
+        Object::Stream(stream) => Some(stream),
+        Object::Dictionary(reference) => resolve(doc, reference.get(b"3DD").ok()?).as_stream().ok(),
+        _ => None,
+    }
+}
+
+/// The view an annotation opens with: its `/3DV`, or else the stream's
+/// `/DV`, either of which may pick from the stream's `/VA` views by index
+/// or as `/F`irst, `/L`ast or the stream `/D`efault.
+fn three_d_default_view<'a>(
+    doc: &'a Document,
+    annot: &'a lopdf::Dictionary,
+    stream: &'a lopdf::Stream,
+) -> Option<&'a lopdf::Dictionary> {
+    let views = stream
+        .dict
+        .get(b"VA")
+        .ok()
+        .and_then(|va| resolve(doc, va).as_array().ok());
+    let stream_default = stream.dict.get(b"DV").ok();
+    let mut selector = annot.get(b"3DV").ok().or(stream_default)?;
+    // `/D` in the stream's own `/DV` would point back at itself.
+    for _ in 0..2 {
+        match resolve(doc, selector) {
+            Object::Dictionary(view) => return Some(view),
+            Object::Integer(i) => {
+                let view = views?.get(usize::try_from(*i).ok()?)?;
+                return resolve(doc, view).as_dict().ok();
+            }
+            Object::Name(name) if name == b"F" => {
+                return resolve(doc, views?.first()?).as_dict().ok();
+            }
+            Object::Name(name) if name == b"L" => {
+                return resolve(doc, views?.last()?).as_dict().ok();
+            }
+            Object::Name(name) if name == b"D" => selector = stream_default?,
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// File names the ZUGFeRD / Factur-X versions give the invoice XML; the
+/// first is the one written.
+const ZUGFERD_NAMES: [&str; 3] = ["factur-x.xml", "zugferd-invoice.xml", "xrechnung.xml"];
+
+/// Factur-X conformance levels, as written to `fx:ConformanceLevel`.
+const ZUGFERD_PROFILES: [&str; 6] = [
+    "MINIMUM",
+    "BASIC WL",
+    "BASIC",
+    "EN 16931",
+    "EXTENDED",
+    "XRECHNUNG",
+];
+
+/// The file name of a file specification, preferring the Unicode `/UF`.
+fn filespec_name(spec: &lopdf::Dictionary) -> Option<String> {
+    spec.get(b"UF")
+        .or_else(|_| spec.get(b"F"))
+        .and_then(Object::as_str)
+        .ok()
+        .map(decode_pdf_string)
+}
+
+/// Add `bytes` to the `/EmbeddedFiles` name tree under `name`, replacing
+/// any attachment of that name. Returns the ids of the file stream and its
+/// file specification.
+fn attach_file(
+    doc: &mut Document,
+    root_id: ObjectId,
+    name: &str,
+    bytes: &[u8],
+) -> PdfResult<(ObjectId, ObjectId)> {
+    let catalog = doc
+        .get_dictionary(root_id)
+        .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?;
+    let names_ref = catalog.get(b"Names").and_then(Object::as_reference).ok();
+    let mut names = catalog
+        .get(b"Names")
+        .map(|obj| resolve(doc, obj))
+        .and_then(Object::as_dict)
+        .cloned()
+        .unwrap_or_default();
+    let mut entries = names
+        .get(b"EmbeddedFiles")
+        .map(|tree| name_tree_entries(doc, tree))
+        .unwrap_or_default();
+
+    let mut stream = lopdf::Stream::new(
+        lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"EmbeddedFile".to_vec())),
+            (
+                "Params",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "Size",
+                    Object::Integer(bytes.len() as i64),
+                )])),
+            ),
+        ]),
+        bytes.to_vec(),
+    );
+    stream
+        .compress()
+        .map_err(|e| PdfError::from(e.to_string()))?;
+    let stream_id = doc.add_object(stream);
+    let filespec_id = doc.add_object(lopdf::Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Filespec".to_vec())),
+        ("F", pdf_text_string(name)),
+        ("UF", pdf_text_string(name)),
+        (
+            "EF",
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("F", Object::Reference(stream_id)),
+                ("UF", Object::Reference(stream_id)),
+            ])),
+        ),
+    ]));
+
+    // The tree is rewritten flat, which any reader accepts; keys must
+    // stay sorted.
+    let key = pdf_text_string(name);
+    let key = key.as_str().unwrap_or_default().to_vec();
+    entries.retain(|(existing, _)| *existing != key);
+    entries.push((key, Object::Reference(filespec_id)));
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let flat = entries
+        .into_iter()
+        .flat_map(|(key, value)| [Object::String(key, lopdf::StringFormat::Literal), value])
+        .collect();
+    names.set(
+        "EmbeddedFiles",
+        Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+            "Names",
+            Object::Array(flat),
+        )])),
+    );
+    if let Some(id) = names_ref {
+        doc.objects.insert(id, Object::Dictionary(names));
+    } else {
+        doc.get_dictionary_mut(root_id)
+            .map_err(|e| PdfError::from(format!("Missing document catalog: {e}")))?
+            .set("Names", Object::Dictionary(names));
+    }
+    Ok((stream_id, filespec_id))
+}
+
+/// The key/value pairs of a name tree, walking `/Kids` down to the
+/// `/Names` arrays of its leaves.
+fn name_tree_entries(doc: &Document, tree: &Object) -> Vec<(Vec<u8>, Object)> {
+    fn walk(doc: &Document, node: &Object, depth: usize, out: &mut Vec<(Vec<u8>, Object)>) {
+        let Ok(node) = resolve(doc, node).as_dict() else {
+            return;
+        };
+        if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+            for pair in names.chunks_exact(2) {
+                if let Ok(key) = resolve(doc, &pair[0]).as_str() {
+                    out.push((key.to_vec(), pair[1].clone()));
+                }
+            }
+        }
+        // Depth-limited so a cyclic tree can't recurse forever.
+        if depth < 32
+            && let Ok(kids) = node.get(b"Kids").and_then(Object::as_array)
+        {
+            for kid in kids {
+                walk(doc, kid, depth + 1, out);
+            }
+        }
+    }
+    let mut entries = Vec::new();
+    walk(doc, tree, 0, &mut entries);
+    entries
+}
+
+/// The zero-based page a destination points at: an explicit
+/// `[page /XYZ …]` array, a dictionary holding one under `/D`, or a name
+/// looked up in the catalog's `/Dests` dictionary or `/Names` tree.
+fn destination_page(doc: &Document, dest: &Object, page_ids: &[ObjectId]) -> Option<usize> {
+    let named = |key: &[u8]| {
+        let catalog = doc.catalog().ok()?;
+        let in_dests = catalog
+            .get(b"Dests")
+            .map(|dests| resolve(doc, dests))
+            .and_then(Object::as_dict)
+            .and_then(|dests| dests.get(key))
+            .ok()
+            .cloned();
+        in_dests.or_else(|| {
+            let tree = catalog
+                .get(b"Names")
+                .map(|names| resolve(doc, names))
+                .and_then(Object::as_dict)
+                .and_then(|names| names.get(b"Dests"))
+                .ok()?;
+            name_tree_entries(doc, tree)
+                .into_iter()
+                .find_map(|(name, dest)| (name == key).then_some(dest))
+        })
+    };
+    let target = match resolve(doc, dest) {
+        Object::Name(key) | Object::String(key, _) => named(key)?,
+        other => other.clone(),
+    };
+    let target = match resolve(doc, &target) {
+        Object::Dictionary(dict) => dict.get(b"D").ok()?.clone(),
+        other => other.clone(),
+    };
+    match resolve(doc, &target).as_array().ok()?.first()? {
+        Object::Reference(id) => page_ids.iter().position(|page_id| page_id == id),
+        // Remote-style destinations give the page number itself.
+        Object::Integer(page) => usize::try_from(*page).ok().filter(|&p| p < page_ids.len()),
+        _ => None,
+    }
+}
+
+/// The URI and `GoTo` links among a page's annotations.
+fn page_links(
+    doc: &Document,
+    page_id: ObjectId,
+    page: usize,
+    page_ids: &[ObjectId],
+) -> Vec<Hyperlink> {
+    let annots = doc
+        .get_dictionary(page_id)
+        .ok()
+        .and_then(|page| page.get(b"Annots").ok())
+        .and_then(|a| resolve(doc, a).as_array().ok())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    annots
+        .iter()
+        .filter_map(|annot| resolve(doc, annot).as_dict().ok())
+        .filter(|annot| {
+            annot.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Link".as_slice())
+        })
+        .filter_map(|annot| {
+            let [x0, y0, x1, y1] = rect_of(resolve(doc, annot.get(b"Rect").ok()?))?;
+            let action = annot
+                .get(b"A")
+                .ok()
+                .and_then(|a| resolve(doc, a).as_dict().ok());
+            let (url, destination_page) = match action {
+                Some(action) => match action.get(b"S").and_then(Object::as_name).ok()? {
+                    b"URI" => {
+                        let uri = resolve(doc, action.get(b"URI").ok()?).as_str().ok()?;
+                        (Some(decode_pdf_string(uri)), None)
+                    }
+                    b"GoTo" => (
+                        None,
+                        destination_page(doc, action.get(b"D").ok()?, page_ids),
+                    ),
+                    _ => return None,
+                },
+                None => (
+                    None,
+                    destination_page(doc, annot.get(b"Dest").ok()?, page_ids),
+                ),
+            };
+            (url.is_some() || destination_page.is_some()).then_some(Hyperlink {
+                page,
+                bounds: (x0, y0, x1 - x0, y1 - y0),
+                url,
+                destination_page,
+            })
+        })
+        .collect()
+}
+
+/// A page's `/Trans` dictionary and `/Dur` entry as a [`Transition`].
+fn page_transition(doc: &Document, page_id: ObjectId) -> Option<Transition> {
+    let page = doc.get_dictionary(page_id).ok()?;
+    let trans = page
+        .get(b"Trans")
+        .map(|t| resolve(doc, t))
+        .and_then(Object::as_dict)
+        .ok();
+    let page_duration = page
+        .get(b"Dur")
+        .map(|d| resolve(doc, d))
+        .and_then(Object::as_float)
+        .ok()
+        .filter(|d| *d >= 0.0);
+    if trans.is_none() && page_duration.is_none() {
+        return None;
+    }
+    let mut transition = Transition {
+        page_duration,
+        ..Transition::default()
+    };
+    let Some(trans) = trans else {
+        return Some(transition);
+    };
+    let name = |key: &[u8]| trans.get(key).and_then(Object::as_name).ok();
+    if let Some(style) = name(b"S") {
+        transition.style = TransitionStyle::from_name(style);
+    }
+    if let Ok(duration) = trans.get(b"D").and_then(Object::as_float)
+        && duration >= 0.0
+    {
+        transition.duration = duration;
+    }
+    transition.vertical = name(b"Dm") == Some(b"V".as_slice());
+    transition.outward = name(b"M") == Some(b"O".as_slice());
+    match trans.get(b"Di") {
+        Ok(Object::Name(_)) => transition.direction = None,
+        Ok(direction) => {
+            if let Ok(degrees) = direction.as_float() {
+                transition.direction = Some(degrees.rem_euclid(360.0) as u16);
+            }
+        }
+        Err(_) => {}
+    }
+    Some(transition)
+}
+
+/// Every terminal field in the AcroForm with its fully qualified name
+/// (`parent.child`).
+fn terminal_fields(doc: &Document) -> Vec<(String, ObjectId)> {
+    fn walk(doc: &Document, id: ObjectId, prefix: &str, out: &mut Vec<(String, ObjectId)>) {
+        let Ok(dict) = doc.get_dictionary(id) else {
+            return;
+        };
+        let name = match dict.get(b"T") {
+            Ok(Object::String(bytes, _)) if prefix.is_empty() => decode_pdf_string(bytes),
+            Ok(Object::String(bytes, _)) => format!("{prefix}.{}", decode_pdf_string(bytes)),
+            _ => prefix.to_string(),
+        };
+        // Kids without /T are widgets of this field rather than sub-fields.
+        let sub_fields: Vec<ObjectId> = dict
+            .get(b"Kids")
+            .and_then(Object::as_array)
+            .map(|kids| {
+                kids.iter()
+                    .filter_map(|k| k.as_reference().ok())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&k| doc.get_dictionary(k).is_ok_and(|d| d.has(b"T")))
+            .collect();
+        if sub_fields.is_empty() {
+            out.push((name, id));
+        } else if out.len() < 10_000 {
+            for kid in sub_fields {
+                walk(doc, kid, &name, out);
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    let roots = doc
+        .catalog()
+        .ok()
+        .and_then(|c| c.get(b"AcroForm").ok())
+        .and_then(|f| resolve(doc, f).as_dict().ok())
+        .and_then(|f| f.get(b"Fields").ok())
+        .and_then(|f| resolve(doc, f).as_array().ok());
+    for root in roots.into_iter().flatten() {
+        if let Ok(id) = root.as_reference() {
+            walk(doc, id, "", &mut out);
+        }
+    }
+    out
+}
+
+/// Whether a font dictionary carries its glyph program. Type 3 fonts are
+/// defined inline; Type 0 fonts are checked through their descendant.
+fn font_is_embedded(doc: &Document, font: &lopdf::Dictionary) -> bool {
+    let font = match font.get(b"Subtype").and_then(Object::as_name) {
+        Ok(b"Type3") => return true,
+        Ok(b"Type0") => match font
+            .get(b"DescendantFonts")
+            .map(|d| resolve(doc, d))
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|d| d.first())
+            .and_then(|d| resolve(doc, d).as_dict().ok())
+        {
+            Some(descendant) => descendant,
+            None => return false,
+        },
+        _ => font,
+    };
+    font.get(b"FontDescriptor")
+        .ok()
+        .and_then(|d| resolve(doc, d).as_dict().ok())
+        .is_some_and(|d| d.has(b"FontFile") || d.has(b"FontFile2") || d.has(b"FontFile3"))
+}
+
+fn find_last(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+/// The offset named by the last `startxref` in the file.
+fn last_startxref(bytes: &[u8]) -> Option<usize> {
+    let pos = find_last(bytes, b"startxref")?;
+    let digits: String = bytes[pos + 9..]
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take_while(|b| b.is_ascii_digit())
+        .map(|&b| char::from(b))
+        .collect();
+    digits.parse().ok()
+}
+
+fn find_from(haystack: &[u8], needle: &[u8], start: usize) -> Option<usize> {
+    haystack
+        .get(start..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + start)
+}
+
+/// Parse `N G R` after optional whitespace.
+fn parse_reference_at(bytes: &[u8]) -> Option<ObjectId> {
+    let text = String::from_utf8_lossy(&bytes[..bytes.len().min(32)]);
+    let mut parts = text.split_ascii_whitespace();
+    let id = parts.next()?.parse().ok()?;
+    let generation = parts.next()?.parse().ok()?;
+    parts.next()?.starts_with('R').then_some((id, generation))
+}
+
+/// Largest object number a conforming file may use (ISO 32000-1, Annex C).
+const MAX_OBJECT_NUMBER: u32 = 8_388_607;
+
+/// Byte offset of every `N G obj` header. Stream bodies are skipped so
+/// binary data can't produce false matches, and a later definition of the
+/// same object (an incremental update) wins.
+fn scan_object_offsets(bytes: &[u8]) -> std::collections::BTreeMap<ObjectId, usize> {
+    let is_delim = |b: u8| b.is_ascii_whitespace() || b"<[/(%".contains(&b);
+    let mut objects = std::collections::BTreeMap::new();
+    let mut pos = 0;
+    while let Some(at) = find_from(bytes, b"obj", pos) {
+        pos = at + 3;
+        if bytes.get(at + 3).is_some_and(|&b| !is_delim(b)) {
+            continue;
+        }
+        // Walk back over "<id> <gen> ".
+        let mut i = at;
+        let mut fields = [(0, 0); 2];
+        for field in &mut fields {
+            let end = i;
+            while i > 0 && bytes[i - 1].is_ascii_whitespace() {
+                i -= 1;
+            }
+            if i == end {
+                break;
+            }
+            let digits_end = i;
+            while i > 0 && bytes[i - 1].is_ascii_digit() {
+                i -= 1;
+            }
+            *field = (i, digits_end);
+        }
+        let [(gen_start, gen_end), (id_start, id_end)] = fields;
+        if gen_end <= gen_start
+            || id_end <= id_start
+            || (id_start > 0 && !is_delim(bytes[id_start - 1]) && bytes[id_start - 1] != b'>')
+        {
+            continue;
+        }
+        let number = |range: std::ops::Range<usize>| {
+            std::str::from_utf8(&bytes[range])
+                .ok()
+                .and_then(|s| s.parse::<u32>().ok())
+        };
+        // Object numbers past the spec's implementation limit are junk.
+        let (Some(id), Some(generation)) = (
+            number(id_start..id_end).filter(|id| (1..=MAX_OBJECT_NUMBER).contains(id)),
+            number(gen_start..gen_end).and_then(|g| u16::try_from(g).ok()),
+        ) else {
+            continue;
+        };
+        objects.insert((id, generation), id_start);
+
+        let end = find_from(bytes, b"endobj", pos);
+        let stream = find_from(bytes, b"stream", pos);
+        if let (Some(stream), Some(end)) = (stream, end)
+            && stream < end
+            && let Some(end_stream) = find_from(bytes, b"endstream", stream + 6)
+        {
+            pos = end_stream + 9;
+        }
+    }
+    objects
+}
+
+/// Entries of the classic xref table at `offset`, or `None` if there isn't
+/// one there. Only this section is read, not `/Prev` ones.
+fn classic_xref_offsets(bytes: &[u8], offset: usize) -> Option<Vec<(ObjectId, usize)>> {
+    let text = String::from_utf8_lossy(bytes.get(offset..)?.strip_prefix(b"xref")?);
+    let mut entries = Vec::new();
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    while let Some(header) = lines.next() {
+        let mut parts = header.split_ascii_whitespace();
+        let (Some(Ok(start)), Some(Ok(count)), None) = (
+            parts.next().map(str::parse::<u32>),
+            parts.next().map(str::parse::<u32>),
+            parts.next(),
+        ) else {
+            break;
+        };
+        for id in start..start + count {
+            let mut fields = lines.next()?.split_ascii_whitespace();
+            let offset = fields.next()?.parse().ok()?;
+            let generation = fields.next()?.parse().ok()?;
+            if fields.next()? == "n" {
+                entries.push(((id, generation), offset));
+            }
+        }
+    }
+    Some(entries)
+}
+
+/// A classic xref section listing `objects`, one subsection per run of
+/// consecutive object numbers so sparse numbering stays small.
+fn xref_sections(objects: &std::collections::BTreeMap<ObjectId, usize>) -> String {
+    // The highest generation of each number wins.
+    let latest: std::collections::BTreeMap<u32, (u16, usize)> = objects
+        .iter()
+        .map(|(&(id, generation), &offset)| (id, (generation, offset)))
+        .collect();
+    let mut runs: Vec<(u32, Vec<String>)> = vec![(0, vec!["0000000000 65535 f \n".into()])];
+    for (id, (generation, offset)) in latest {
+        let entry = format!("{offset:010} {generation:05} n \n");
+        match runs.last_mut() {
+            Some((start, entries)) if *start as usize + entries.len() == id as usize => {
+                entries.push(entry);
+            }
+            _ => runs.push((id, vec![entry])),
+        }
+    }
+    let mut section = String::from("xref\n");
+    for (start, entries) in runs {
+        section.push_str(&format!("{start} {}\n{}", entries.len(), entries.concat()));
+    }
+    section
+}
+
+/// The last object whose dictionary declares `/Type /Catalog`.
+fn find_catalog(
+    bytes: &[u8],
+    objects: &std::collections::BTreeMap<ObjectId, usize>,
+) -> Option<ObjectId> {
+    let mut by_offset: Vec<(usize, ObjectId)> = objects.iter().map(|(&id, &o)| (o, id)).collect();
+    by_offset.sort_unstable();
+    by_offset.into_iter().rev().find_map(|(offset, id)| {
+        let end = find_from(bytes, b"endobj", offset).unwrap_or(bytes.len());
+        let body = &bytes[offset..end];
+        let body = &body[..find_from(body, b"stream", 0).unwrap_or(body.len())];
+        let body: Vec<u8> = body
+            .iter()
+            .copied()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect();
+        find_from(&body, b"/Type/Catalog", 0).map(|_| id)
+    })
+}
+
+/// Repair what [`DocumentStore::clean_pdf`] fixes in place: `/Count` and
+/// `/Type` of the page tree nodes, and dictionary entries whose value is
+/// `null`, which the spec treats as absent. Returns how many were fixed.
+fn fix_structure(doc: &mut Document) -> usize {
+    // Returns the number of pages under `id`.
+    fn walk(doc: &mut Document, id: ObjectId, depth: usize, fixed: &mut usize) -> i64 {
+        let Ok(node) = doc.get_dictionary(id) else {
+            return 0;
+        };
+        let kids: Option<Vec<ObjectId>> =
+            node.get(b"Kids")
+                .and_then(Object::as_array)
+                .ok()
+                .map(|kids| {
+                    kids.iter()
+                        .filter_map(|kid| kid.as_reference().ok())
+                        .collect()
+                });
+        let (kind, count) = match kids {
+            // Depth-limited so a cyclic tree can't recurse forever.
+            Some(kids) if depth < 64 => {
+                let count = kids
+                    .into_iter()
+                    .map(|kid| walk(doc, kid, depth + 1, fixed))
+                    .sum();
+                (b"Pages".as_slice(), Some(count))
+            }
+            Some(_) => (b"Pages".as_slice(), None),
+            None => (b"Page".as_slice(), None),
+        };
+        let Ok(node) = doc.get_dictionary_mut(id) else {
+            return 0;
+        };
+        if node.get(b"Type").and_then(Object::as_name).ok() != Some(kind) {
+            node.set("Type", Object::Name(kind.to_vec()));
+            *fixed += 1;
+        }
+        match count {
+            Some(count) => {
+                if node.get(b"Count").and_then(Object::as_i64).ok() != Some(count) {
+                    node.set("Count", Object::Integer(count));
+                    *fixed += 1;
+                }
+                count
+            }
+            None if kind == b"Page" => 1,
+            None => node.get(b"Count").and_then(Object::as_i64).unwrap_or(0),
+        }
+    }
+
+    let mut fixed = 0;
+    for obj in doc.objects.values_mut() {
+        let dict = match obj {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &mut stream.dict,
+            _ => continue,
+        };
+        let nulls: Vec<Vec<u8>> = dict
+            .iter()
+            .filter(|(_, value)| matches!(value, Object::Null))
+            .map(|(key, _)| key.clone())
+            .collect();
+        fixed += nulls.len();
+        for key in nulls {
+            dict.remove(&key);
+        }
+    }
+    if let Ok(pages_id) = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+    {
+        walk(doc, pages_id, 0, &mut fixed);
+    }
+    fixed
+}
+
+/// Structural and PDF/A checks on a parsed document.
+fn validate_document(doc: &Document) -> Vec<ValidationIssue> {
+    use crate::models::IssueSeverity::{Error, Warning};
+
+    let mut issues = Vec::new();
+    let catalog = doc
+        .trailer
+        .get(b"Root")
+        .and_then(Object::as_reference)
+        .and_then(|id| doc.get_dictionary(id));
+    let Ok(catalog) = catalog else {
+        issues.push(ValidationIssue::new(
+            "trailer-root",
+            Error,
+            None,
+            "Trailer has no usable /Root catalog",
+        ));
+        return issues;
+    };
+    if doc.is_encrypted() {
+        issues.push(ValidationIssue::new(
+            "encryption",
+            Error,
+            None,
+            "Document is encrypted, which PDF/A forbids",
+        ));
+    }
+    if !catalog.has(b"Metadata") {
+        issues.push(ValidationIssue::new(
+            "xmp-metadata",
+            Warning,
+            None,
+            "Catalog has no XMP /Metadata stream",
+        ));
+    }
+    if !catalog.has(b"OutputIntents") {
+        issues.push(ValidationIssue::new(
+            "output-intent",
+            Warning,
+            None,
+            "Catalog has no /OutputIntents colour profile",
+        ));
+    }
+
+    for (page_no, page_id) in doc.get_pages() {
+        let page = Some(page_no as usize);
+        let resources = page_resources(doc, page_id);
+        let category = |name: &[u8]| {
+            resources
+                .get(name)
+                .ok()
+                .and_then(|c| resolve(doc, c).as_dict().ok())
+                .into_iter()
+                .flat_map(|c| c.iter())
+                .filter_map(|(key, value)| {
+                    let dict = resolve(doc, value).as_dict().ok()?;
+                    Some((String::from_utf8_lossy(key).to_string(), dict))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (key, font) in category(b"Font") {
+            if !font_is_embedded(doc, font) {
+                let base = font
+                    .get(b"BaseFont")
+                    .and_then(Object::as_name)
+                    .map(|n| String::from_utf8_lossy(n).to_string())
+                    .unwrap_or(key);
+                issues.push(ValidationIssue::new(
+                    "font-embedded",
+                    Error,
+                    page,
+                    format!("Font '{base}' is not embedded"),
+                ));
+            }
+        }
+        let transparent = category(b"ExtGState").iter().any(|(_, gs)| {
+            let below_one = |k: &[u8]| gs.get(k).and_then(Object::as_float).is_ok_and(|v| v < 1.0);
+            let soft_mask = gs
+                .get(b"SMask")
+                .is_ok_and(|m| m.as_name().ok() != Some(b"None".as_slice()));
+            below_one(b"ca") || below_one(b"CA") || soft_mask
+        });
+        if transparent {
+            issues.push(ValidationIssue::new(
+                "transparency",
+                Warning,
+                page,
+                "Page uses transparency, which PDF/A-1 forbids",
+            ));
+        }
+    }
+    issues
+}
+
+/// A y-axis range that includes zero and the data, widened to round tick
+/// steps of 1, 2 or 5 times a power of ten. Returns `(low, high, step)`.
+fn nice_axis(min: f64, max: f64) -> (f64, f64, f64) {
+    const TARGET_TICKS: f64 = 5.0;
+    let (mut lo, mut hi) = (min.min(0.0), max.max(0.0));
+    if !lo.is_finite() || !hi.is_finite() || hi - lo < f64::EPSILON {
+        (lo, hi) = (lo.min(0.0), lo.min(0.0) + 1.0);
+    }
+    let raw = (hi - lo) / TARGET_TICKS;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|&s| s >= raw)
+        .unwrap_or(10.0 * magnitude);
+    ((lo / step).floor() * step, (hi / step).ceil() * step, step)
+}
+
+/// Trim a tick value for display: no trailing zeros, no "-0".
+fn format_tick(value: f64) -> String {
+    let text = format!("{value:.4}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".into()
+    } else {
+        text.into()
+    }
+}
+
+/// Normal appearance for ink strokes given in page space, so viewers that
+/// don't synthesize appearances still draw them.
+fn ink_appearance(
+    strokes: &[Vec<(f32, f32)>],
+    bbox: [f32; 4],
+    (red, green, blue): (f32, f32, f32),
+    thickness: f32,
+) -> lopdf::Stream {
+    let mut content = pdf_writer::Content::new();
+    content.save_state();
+    content.set_stroke_rgb(red, green, blue);
+    content.set_line_width(thickness);
+    content.set_line_cap(pdf_writer::types::LineCapStyle::RoundCap);
+    content.set_line_join(pdf_writer::types::LineJoinStyle::RoundJoin);
+    for points in strokes {
+        let Some((&(x, y), rest)) = points.split_first() else {
+            continue;
+        };
+        content.move_to(x, y);
+        for &(x, y) in rest {
+            content.line_to(x, y);
+        }
+        content.stroke();
+    }
+    content.restore_state();
+    let dict = lopdf::Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"XObject".to_vec())),
+        ("Subtype", Object::Name(b"Form".to_vec())),
+        (
+            "BBox",
+            Object::Array(bbox.iter().map(|&v| Object::Real(v)).collect()),
+        ),
+    ]);
+    lopdf::Stream::new(dict, content.finish().into_vec())
+}
+
+/// Content stream for `spec`: title, y-axis with gridlines and labels, the
+/// zero baseline, bars or lines per series, category labels and a legend.
+/// Text uses the font resource `/FChart`. Empty series give bare axes.
+fn chart_content(spec: &ChartSpec) -> PdfResult<Vec<u8>> {
+    const PALETTE: [(f32, f32, f32); 6] = [
+        (0.22, 0.47, 0.85),
+        (0.90, 0.45, 0.13),
+        (0.20, 0.63, 0.33),
+        (0.80, 0.20, 0.25),
+        (0.55, 0.35, 0.75),
+        (0.45, 0.45, 0.45),
+    ];
+    const LABEL_SIZE: f32 = 8.0;
+    const TITLE_SIZE: f32 = 12.0;
+
+    let [left, bottom, width, height] = spec.rect;
+    let title_h = if spec.title.is_empty() { 0.0 } else { 22.0 };
+    let legend_h = if spec.series.is_empty() { 0.0 } else { 16.0 };
+    let (px, py) = (left + 44.0, bottom + legend_h + 14.0);
+    let (pw, ph) = (width - 52.0, height - title_h - legend_h - 22.0);
+    if pw <= 0.0 || ph <= 0.0 {
+        return Err(PdfError::from("Chart area is too small"));
+    }
+
+    let values = spec.series.iter().flat_map(|s| s.values.iter().copied());
+    let (min, max) = values
+        .filter(|v| v.is_finite())
+        .fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    let (lo, hi, step) = if min <= max {
+        nice_axis(min, max)
+    } else {
+        nice_axis(0.0, 1.0)
+    };
+    let map_y = |v: f64| py + ((v - lo) / (hi - lo)) as f32 * ph;
+    let baseline = map_y(0.0);
+    let categories = spec
+        .series
+        .iter()
+        .map(|s| s.values.len())
+        .chain(std::iter::once(spec.labels.len()))
+        .max()
+        .unwrap_or(0);
+    let colour = |i: usize, series: &ChartSeries| {
+        series
+            .color
+            .as_deref()
+            .map_or(PALETTE[i % PALETTE.len()], hex_to_rgb)
+    };
+
+    let mut content = pdf_writer::Content::new();
+    let label = |content: &mut pdf_writer::Content, text: &str, x: f32, y: f32, size: f32| {
+        content.begin_text();
+        content.set_font(pdf_writer::Name(b"FChart"), size);
+        content.next_line(x, y);
+        content.show(pdf_writer::Str(&win_ansi_bytes(text)));
+        content.end_text();
+    };
+    let text_width = |text: &str, size: f32| text.chars().count() as f32 * size * 0.5;
+    content.save_state();
+    content.set_fill_gray(0.15);
+
+    if !spec.title.is_empty() {
+        let tx = left + (width - text_width(&spec.title, TITLE_SIZE)) / 2.0;
+        label(
+            &mut content,
+            &spec.title,
+            tx,
+            bottom + height - TITLE_SIZE - 4.0,
+            TITLE_SIZE,
+        );
+    }
+
+    // Gridlines and y labels.
+    content.set_line_width(0.5);
+    content.set_stroke_gray(0.85);
+    let ticks = ((hi - lo) / step).round() as usize;
+    for i in 0..=ticks {
+        let value = lo + i as f64 * step;
+        let ty = map_y(value);
+        content.move_to(px, ty);
+        content.line_to(px + pw, ty);
+        let text = format_tick(value);
+        label(
+            &mut content,
+            &text,
+            px - 4.0 - text_width(&text, LABEL_SIZE),
+            ty - LABEL_SIZE / 3.0,
+            LABEL_SIZE,
+        );
+    }
+    content.stroke();
+
+    // Y axis and the zero baseline, which sits inside the plot when values
+    // go negative.
+    content.set_line_width(1.0);
+    content.set_stroke_gray(0.3);
+    content.move_to(px, py);
+    content.line_to(px, py + ph);
+    content.move_to(px, baseline);
+    content.line_to(px + pw, baseline);
+    content.stroke();
+
+    if categories > 0 {
+        let group = pw / categories as f32;
+        for (i, text) in spec.labels.iter().enumerate().take(categories) {
+            let cx = px + group * (i as f32 + 0.5);
+            label(
+                &mut content,
+                text,
+                cx - text_width(text, LABEL_SIZE) / 2.0,
+                py - LABEL_SIZE - 4.0,
+                LABEL_SIZE,
+            );
+        }
+
+        let series_count = spec.series.len().max(1) as f32;
+        for (s, series) in spec.series.iter().enumerate() {
+            let (r, g, b) = colour(s, series);
+            let points = series
+                .values
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.is_finite())
+                .map(|(i, &v)| (i, map_y(v)));
+            match spec.kind {
+                ChartKind::Bar => {
+                    content.set_fill_rgb(r, g, b);
+                    let bar_w = group * 0.8 / series_count;
+                    for (i, vy) in points {
+                        let bx = px + group * (i as f32 + 0.1) + bar_w * s as f32;
+                        content.rect(bx, baseline.min(vy), bar_w, (vy - baseline).abs());
+                    }
+                    content.fill_nonzero();
+                }
+                ChartKind::Line => {
+                    content.set_stroke_rgb(r, g, b);
+                    content.set_fill_rgb(r, g, b);
+                    content.set_line_width(1.5);
+                    let points: Vec<(f32, f32)> = points
+                        .map(|(i, vy)| (px + group * (i as f32 + 0.5), vy))
+                        .collect();
+                    for (n, &(lx, ly)) in points.iter().enumerate() {
+                        if n == 0 {
+                            content.move_to(lx, ly);
+                        } else {
+                            content.line_to(lx, ly);
+                        }
+                    }
+                    if !points.is_empty() {
+                        content.stroke();
+                    }
+                    for &(lx, ly) in &points {
+                        content.rect(lx - 1.5, ly - 1.5, 3.0, 3.0);
+                    }
+                    content.fill_nonzero();
+                }
+            }
+        }
+    }
+
+    // Legend along the bottom edge.
+    let mut lx = px;
+    for (s, series) in spec.series.iter().enumerate() {
+        let (r, g, b) = colour(s, series);
+        content.set_fill_rgb(r, g, b);
+        content.rect(lx, bottom + 2.0, 8.0, 8.0);
+        content.fill_nonzero();
+        content.set_fill_gray(0.15);
+        label(
+            &mut content,
+            &series.name,
+            lx + 11.0,
+            bottom + 3.0,
+            LABEL_SIZE,
+        );
+        lx += 24.0 + text_width(&series.name, LABEL_SIZE);
+    }
+    content.restore_state();
+    Ok(content.finish().to_vec())
+}
+
+/// Greedy word wrap to at most `max_chars` per line, hard-splitting words that
+/// are longer than a line on their own.
+fn wrap_words(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > max_chars {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..max_chars).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if word.is_empty() {
+            continue;
+        }
+        let line_len = line.chars().count();
+        if line_len > 0 && line_len + 1 + word.chars().count() > max_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Encode text for a WinAnsi-encoded standard font, replacing characters it
+/// cannot represent.
+pub(crate) fn win_ansi_bytes(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => u8::try_from(u32::from(c)).unwrap_or(b'?'),
+        })
+        .collect()
+}
+
+/// A rectangle set on the page itself or, for the inheritable MediaBox and
+/// CropBox, on one of its ancestors.
+fn own_page_box(doc: &Document, page_id: ObjectId, which: PageBox) -> Option<[f32; 4]> {
+    let mut current = Some(page_id);
+    while let Some(id) = current {
+        let dict = doc.get_dictionary(id).ok()?;
+        if let Ok(rect) = dict.get(which.key().as_bytes()) {
+            return rect_of(resolve(doc, rect));
+        }
+        if !matches!(which, PageBox::Media | PageBox::Crop) {
+            break;
+        }
+        current = dict.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    None
+}
+
+/// Add a Helvetica font object using `WinAnsiEncoding`, the encoding
+/// `win_ansi_bytes` produces.
+pub(crate) fn helvetica_font(doc: &mut Document) -> ObjectId {
+    doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Font".to_vec())),
+        ("Subtype", Object::Name(b"Type1".to_vec())),
+        ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+        ("Encoding", Object::Name(b"WinAnsiEncoding".to_vec())),
+    ])))
+}
+
+/// Draw `overlay` on top of the page's existing content. The old content is
+/// wrapped in `q`/`Q` so an unbalanced graphics state in it cannot skew the
+/// overlay.
+pub(crate) fn append_page_overlay(
+    doc: &mut Document,
+    page_id: ObjectId,
+    overlay: Vec<u8>,
+) -> PdfResult<()> {
+    let save_state = doc.add_object(lopdf::Stream::new(
+        lopdf::Dictionary::new(),
+        b"q\n".to_vec(),
+    ));
+    let mut restored = b"Q\n".to_vec();
+    restored.extend(overlay);
+    let overlay_id = doc.add_object(lopdf::Stream::new(lopdf::Dictionary::new(), restored));
+    let mut contents = vec![Object::Reference(save_state)];
+    contents.extend(
+        doc.get_page_contents(page_id)
+            .into_iter()
+            .map(Object::Reference),
+    );
+    contents.push(Object::Reference(overlay_id));
+    doc.get_dictionary_mut(page_id)
+        .map_err(|e| PdfError::from(format!("Invalid page object: {e}")))?
+        .set("Contents", Object::Array(contents));
+    Ok(())
+}
+
+/// The page's MediaBox, following `/Parent` for inherited values and falling
+/// back to US Letter.
+pub(crate) fn page_media_box(doc: &Document, page_id: ObjectId) -> [f32; 4] {
+    own_page_box(doc, page_id, PageBox::Media).unwrap_or([0.0, 0.0, 612.0, 792.0])
+}
+
+/// A page box as viewers resolve it: the CropBox defaults to the MediaBox
+/// and the other boxes to the CropBox, and all are clipped to the MediaBox.
+pub(crate) fn page_box(doc: &Document, page_id: ObjectId, which: PageBox) -> [f32; 4] {
+    let media = page_media_box(doc, page_id);
+    let clip = |[x0, y0, x1, y1]: [f32; 4]| {
+        [
+            x0.max(media[0]),
+            y0.max(media[1]),
+            x1.min(media[2]),
+            y1.min(media[3]),
+        ]
+    };
+    let crop = own_page_box(doc, page_id, PageBox::Crop).map_or(media, clip);
+    match which {
+        PageBox::Media => media,
+        PageBox::Crop => crop,
+        _ => own_page_box(doc, page_id, which).map_or(crop, clip),
+    }
+}
+
+fn rect_contains(outer: [f32; 4], inner: [f32; 4]) -> bool {
+    inner[0] >= outer[0] && inner[1] >= outer[1] && inner[2] <= outer[2] && inner[3] <= outer[3]
+}
+
+/// A copy of the page's resource dictionary, inherited from `/Parent` when the
+/// page has none of its own.
+pub(crate) fn page_resources(doc: &Document, page_id: ObjectId) -> lopdf::Dictionary {
+    doc.get_page_resources(page_id)
+        .ok()
+        .and_then(|(inline, ids)| {
+            inline.cloned().or_else(|| {
+                ids.first()
+                    .and_then(|id| doc.get_dictionary(*id).ok().cloned())
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// The page's `/Rotate`, inherited from its ancestors, normalized to 0, 90,
+/// 180 or 270.
+pub(crate) fn page_rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    let mut current = Some(page_id);
+    while let Some(id) = current {
+        let Ok(dict) = doc.get_dictionary(id) else {
+            break;
+        };
+        if let Ok(rotate) = dict.get(b"Rotate") {
+            let degrees = resolve(doc, rotate).as_i64().unwrap_or(0);
+            return (degrees.rem_euclid(360) / 90) * 90;
+        }
+        current = dict.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    0
+}
+
+/// Set `/category /name id` in a resource dictionary, resolving an indirect
+/// category dictionary into an inline copy first.
+fn add_named_resource(
+    doc: &Document,
+    resources: &mut lopdf::Dictionary,
+    category: &[u8],
+    name: &[u8],
+    id: ObjectId,
+) {
+    let mut entries = match resources.get(category) {
+        Ok(Object::Reference(r)) => doc.get_dictionary(*r).cloned().unwrap_or_default(),
+        Ok(Object::Dictionary(d)) => d.clone(),
+        _ => lopdf::Dictionary::new(),
+    };
+    entries.set(name.to_vec(), Object::Reference(id));
+    resources.set(category.to_vec(), Object::Dictionary(entries));
+}
+
+/// Write `original` to `output` followed by an incremental update holding
+/// `changed_objects`, new or replacing earlier versions.
+///
+/// The update's cross-reference section lists only those objects and its
+/// trailer's `/Prev` points at the previous section, so the original bytes
+/// are left untouched and existing signatures stay valid. The section is an
+/// xref stream if the file's last one was, and a table otherwise.
+pub fn write_incremental(
+    original: &[u8],
+    changed_objects: &std::collections::BTreeMap<ObjectId, Object>,
+    output: &mut impl std::io::Write,
+) -> PdfResult<()> {
+    let doc = Document::load_mem(original).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+    if doc.trailer.get(b"Encrypt").is_ok() {
+        return Err(PdfError::from(
+            "Incremental updates aren't supported for encrypted files",
+        ));
+    }
+    if last_startxref(original).is_none_or(|offset| offset >= original.len()) {
+        return Err(PdfError::from(
+            "File has no startxref to chain the update to",
+        ));
+    }
+
+    let mut update = lopdf::IncrementalDocument::create_from(original.to_vec(), doc);
+    let new_document = &mut update.new_document;
+    let mut trailer = lopdf::Dictionary::new();
+    for key in [b"Root".as_slice(), b"Info", b"ID", b"Prev"] {
+        if let Ok(value) = new_document.trailer.get(key) {
+            trailer.set(key.to_vec(), value.clone());
+        }
+    }
+    new_document.trailer = trailer;
+    for (&id, obj) in changed_objects {
+        new_document.max_id = new_document.max_id.max(id.0);
+        new_document.set_object(id, obj.clone());
+    }
+    update
+        .save_to(output)
+        .map_err(|e| PdfError::IoError(e.to_string()))
+}
+
+/// A dictionary's entries sorted by key, so dictionaries that differ only
+/// in key order compare equal.
+fn sorted_entries(dict: &lopdf::Dictionary) -> Vec<(&[u8], &Object)> {
+    let mut entries: Vec<_> = dict.iter().map(|(k, v)| (k.as_slice(), v)).collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// Point every reference to a byte-identical stream at one copy so that
+/// `prune_objects` can drop the rest. Returns how many streams were merged.
+fn dedupe_streams(doc: &mut Document) -> usize {
+    use std::hash::{Hash, Hasher};
+
+    let mut seen: HashMap<u64, Vec<ObjectId>> = HashMap::new();
+    let mut remap: HashMap<ObjectId, ObjectId> = HashMap::new();
+    for (&id, obj) in &doc.objects {
+        let Object::Stream(stream) = obj else {
+            continue;
+        };
+        // Bucket by content alone; dictionaries are compared within a bucket.
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        stream.content.hash(&mut hasher);
+        let candidates = seen.entry(hasher.finish()).or_default();
+        let entries = sorted_entries(&stream.dict);
+        let original = candidates.iter().copied().find(|other| {
+            matches!(
+                doc.objects.get(other),
+                Some(Object::Stream(s))
+                    if s.content == stream.content && sorted_entries(&s.dict) == entries
+            )
+        });
+        match original {
+            Some(original) => {
+                remap.insert(id, original);
+            }
+            None => candidates.push(id),
+        }
+    }
+
+    if !remap.is_empty() {
+        for obj in doc.objects.values_mut() {
+            remap_references(obj, &remap);
+        }
+        for (_, obj) in doc.trailer.iter_mut() {
+            remap_references(obj, &remap);
+        }
+    }
+    remap.len()
+}
+
+fn remap_references(obj: &mut Object, remap: &HashMap<ObjectId, ObjectId>) {
+    match obj {
+        Object::Reference(id) => {
+            if let Some(&new_id) = remap.get(id) {
+                *id = new_id;
+            }
+        }
+        Object::Array(items) => {
+            for item in items {
+                remap_references(item, remap);
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                remap_references(value, remap);
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                remap_references(value, remap);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::barcode::{BarcodeKind, BarcodeSpec, QrErrorCorrection, add_barcode, qr_modules};
+
+    fn fixture_path() -> std::path::PathBuf {
+        std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_document.pdf")
+    }
+
+    /// A fresh temporary file, deleted when the returned path drops.
+    fn temp_path(suffix: &str) -> tempfile::TempPath {
+        tempfile::Builder::new()
+            .suffix(suffix)
+            .tempfile()
+            .unwrap()
+            .into_temp_path()
+    }
+
+    #[test]
+    fn test_render_key_equality() {
+        let doc_id = DocumentId(1);
+        let key1 = RenderKey {
+            doc_id,
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let key2 = RenderKey {
+            doc_id,
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        assert_eq!(key1, key2);
+    }
+
+    #[test]
+    fn test_render_key_different_pages() {
+        let doc_id = DocumentId(1);
+        let key1 = RenderKey {
+            doc_id,
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let key2 = RenderKey {
+            doc_id,
+            page_num: 1,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        assert_ne!(key1, key2);
+    }
+
+    #[test]
+    fn test_render_key_different_scales() {
+        let doc_id = DocumentId(1);
+        let key1 = RenderKey {
+            doc_id,
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let key2 = RenderKey {
+            doc_id,
+            page_num: 0,
+            scale: 200,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        assert_ne!(key1, key2);
+    }
+
+    #[test]
+    fn test_render_key_different_documents() {
+        let key1 = RenderKey {
+            doc_id: DocumentId(1),
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let key2 = RenderKey {
+            doc_id: DocumentId(2),
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        assert_ne!(key1, key2);
+    }
+
+    #[test]
+    fn test_render_key_distinguishes_scale() {
+        let doc_id = DocumentId(1);
+        let key_low = RenderKey {
+            doc_id,
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let key_high = RenderKey {
+            doc_id,
+            page_num: 0,
+            scale: 200,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        assert_ne!(key_low, key_high);
+    }
+
+    #[test]
+    fn test_render_cache_creation() {
+        let cache = RenderCache::new(10, 100);
+        assert_eq!(
+            cache.get(&RenderKey {
+                doc_id: DocumentId(1),
+                page_num: 0,
+                scale: 100,
+                auto_crop: false,
+                quality: RenderQuality::Medium,
+                rotation: 0,
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_render_cache_insert_and_get() {
+        let cache = RenderCache::new(10, 1024);
+        let key = RenderKey {
+            doc_id: DocumentId(1),
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let result = crate::models::RenderResult {
+            width: 100,
+            height: 100,
+            data: vec![0u8; 100].into(),
+        };
+        cache.put(key.clone(), result.clone());
+        let cached = cache.get(&key);
+        assert!(cached.is_some());
+        assert_eq!(cached.unwrap().width, 100);
+    }
+
+    #[test]
+    fn test_render_cache_overwrite() {
+        let cache = RenderCache::new(10, 1024);
+        let key = RenderKey {
+            doc_id: DocumentId(1),
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let result1 = crate::models::RenderResult {
+            width: 100,
+            height: 100,
+            data: vec![0u8; 100].into(),
+        };
+        let result2 = crate::models::RenderResult {
+            width: 200,
+            height: 200,
+            data: vec![0u8; 200].into(),
+        };
+        cache.put(key.clone(), result1);
+        cache.put(key.clone(), result2);
+        let cached = cache.get(&key);
+        assert_eq!(cached.unwrap().width, 200);
+    }
+
+    #[test]
+    fn test_render_cache_different_keys() {
+        let cache = RenderCache::new(10, 1024);
+        let key1 = RenderKey {
+            doc_id: DocumentId(1),
+            page_num: 0,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let key2 = RenderKey {
+            doc_id: DocumentId(1),
+            page_num: 1,
+            scale: 100,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+            rotation: 0,
+        };
+        let result1 = crate::models::RenderResult {
+            width: 100,
+            height: 100,
+            data: vec![0u8; 100].into(),
+        };
+        let result2 = crate::models::RenderResult {
+            width: 200,
+            height: 200,
+            data: vec![0u8; 200].into(),
+        };
+        cache.put(key1.clone(), result1);
+        cache.put(key2.clone(), result2);
+        assert_eq!(cache.get(&key1).unwrap().width, 100);
+        assert_eq!(cache.get(&key2).unwrap().width, 200);
+    }
+
+    #[test]
+    fn test_apply_filter_inverted() {
+        let mut data = vec![100, 150, 200, 255, 50, 75, 100, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::Inverted);
+        assert_eq!(data[0], 155);
+        assert_eq!(data[1], 105);
+        assert_eq!(data[2], 55);
+    }
+
+    #[test]
+    fn test_apply_filter_eco_bright() {
+        let mut data = vec![250, 250, 250, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::Eco);
+        assert_eq!(data[0], 255);
+        assert_eq!(data[1], 255);
+        assert_eq!(data[2], 255);
+    }
+
+    #[test]
+    fn test_apply_filter_eco_dark() {
+        let mut data = vec![100, 100, 100, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::Eco);
+        assert_eq!(data[0], 100);
+        assert_eq!(data[1], 100);
+        assert_eq!(data[2], 100);
+    }
+
+    #[test]
+    fn test_apply_filter_black_white_high() {
+        let mut data = vec![200, 200, 200, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::BlackWhite);
+        assert_eq!(data[0], 255);
+        assert_eq!(data[1], 255);
+        assert_eq!(data[2], 255);
+    }
+
+    #[test]
+    fn test_apply_filter_black_white_low() {
+        let mut data = vec![50, 50, 50, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::BlackWhite);
+        assert_eq!(data[0], 0);
+        assert_eq!(data[1], 0);
+        assert_eq!(data[2], 0);
+    }
+
+    #[test]
+    fn test_apply_filter_lighten() {
+        let mut data = vec![100, 100, 100, 255, 230, 230, 230, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::Lighten);
+        assert_eq!(data[0], 120);
+        assert_eq!(data[1], 120);
+        assert_eq!(data[2], 120);
+        assert_eq!(data[4], 250);
+    }
+
+    #[test]
+    fn test_apply_filter_lighten_saturation() {
+        let mut data = vec![245, 245, 245, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::Lighten);
+        assert_eq!(data[0], 255);
+        assert_eq!(data[1], 255);
+        assert_eq!(data[2], 255);
+    }
+
+    #[test]
+    fn test_apply_filter_no_shadow_bright() {
+        let mut data = vec![235, 235, 235, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::NoShadow);
+        assert_eq!(data[0], 255);
+        assert_eq!(data[1], 255);
+        assert_eq!(data[2], 255);
+    }
+
+    #[test]
+    fn test_apply_filter_no_shadow_dark() {
+        let mut data = vec![100, 100, 100, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::NoShadow);
+        assert_eq!(data[0], 100);
+        assert_eq!(data[1], 100);
+        assert_eq!(data[2], 100);
+    }
+
+    #[test]
+    fn test_apply_filter_grayscale() {
+        let mut data = vec![100, 150, 200, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::Grayscale);
+        let luma = ((100 * 299 + 150 * 587 + 200 * 114) / 1000) as u8;
+        assert_eq!(data[0], luma);
+        assert_eq!(data[1], luma);
+        assert_eq!(data[2], luma);
+    }
+
+    #[test]
+    fn test_apply_filter_none_does_nothing() {
+        let mut data = vec![100, 150, 200, 255];
+        DocumentStore::apply_filter(&mut data, RenderFilter::None);
+        assert_eq!(data[0], 100);
+        assert_eq!(data[1], 150);
+        assert_eq!(data[2], 200);
+    }
+
+    #[test]
+    fn test_apply_filter_large_buffer() {
+        let mut data = vec![0u8; 10000];
+        for i in 0..2500 {
+            data[i * 4] = 100;
+            data[i * 4 + 1] = 150;
+            data[i * 4 + 2] = 200;
+            data[i * 4 + 3] = 255;
+        }
+        DocumentStore::apply_filter(&mut data, RenderFilter::Inverted);
+        for i in 0..2500 {
+            assert_eq!(data[i * 4], 155);
+            assert_eq!(data[i * 4 + 1], 105);
+            assert_eq!(data[i * 4 + 2], 55);
+        }
+    }
+
+    #[test]
+    fn test_detect_content_bbox_parallel_empty() {
+        let data = vec![255u8; 400];
+        let result = DocumentStore::detect_content_bbox_parallel(&data, 10, 10);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_detect_content_bbox_parallel_full() {
+        let mut data = vec![255u8; 400];
+        data[0] = 100;
+        data[4] = 100;
+        let result = DocumentStore::detect_content_bbox_parallel(&data, 10, 10);
+        assert!(result.is_some());
+        let (min_x, min_y, max_x, max_y) = result.unwrap();
+        assert!(min_x <= max_x);
+        assert!(min_y <= max_y);
+    }
+
+    #[test]
+    fn test_detect_content_bbox_parallel_with_margin() {
+        let mut data = vec![255u8; 40];
+        data[0] = 100;
+        let result = DocumentStore::detect_content_bbox_parallel(&data, 10, 10);
+        assert!(result.is_some());
+        let (min_x, min_y, _max_x, _max_y) = result.unwrap();
+        assert!(min_x <= 10);
+        assert!(min_y <= 10);
+    }
+
+    #[test]
+    fn test_create_render_cache_defaults() {
+        let cache = create_render_cache(10, 0);
+        assert!(
+            cache
+                .get(&RenderKey {
+                    doc_id: DocumentId(1),
+                    page_num: 0,
+                    scale: 100,
+                    auto_crop: false,
+                    quality: RenderQuality::Medium,
+                    rotation: 0,
+                })
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_render_options_default() {
+        let options = RenderOptions {
+            scale: 1.0,
+            rotation: 0,
+            filter: RenderFilter::None,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+        };
+        assert_eq!(options.scale, 1.0);
+        assert_eq!(options.rotation, 0);
+    }
+
+    #[test]
+    fn test_render_quality_serialization() {
+        let json_low = serde_json::to_string(&RenderQuality::Low).unwrap();
+        let json_medium = serde_json::to_string(&RenderQuality::Medium).unwrap();
+        let json_high = serde_json::to_string(&RenderQuality::High).unwrap();
+        assert_eq!(json_low, "\"Low\"");
+        assert_eq!(json_medium, "\"Medium\"");
+        assert_eq!(json_high, "\"High\"");
+    }
+
+    #[test]
+    fn test_render_filter_serialization() {
+        let json_none = serde_json::to_string(&RenderFilter::None).unwrap();
+        let json_grayscale = serde_json::to_string(&RenderFilter::Grayscale).unwrap();
+        let json_inverted = serde_json::to_string(&RenderFilter::Inverted).unwrap();
+        assert_eq!(json_none, "\"None\"");
+        assert_eq!(json_grayscale, "\"Grayscale\"");
+        assert_eq!(json_inverted, "\"Inverted\"");
+    }
+
+    #[test]
+    fn test_render_quality_deserialization() {
+        let low: RenderQuality = serde_json::from_str("\"Low\"").unwrap();
+        let medium: RenderQuality = serde_json::from_str("\"Medium\"").unwrap();
+        let high: RenderQuality = serde_json::from_str("\"High\"").unwrap();
+        assert_eq!(low, RenderQuality::Low);
+        assert_eq!(medium, RenderQuality::Medium);
+        assert_eq!(high, RenderQuality::High);
+    }
+
+    #[test]
+    fn test_render_filter_deserialization() {
+        let none: RenderFilter = serde_json::from_str("\"None\"").unwrap();
+        let grayscale: RenderFilter = serde_json::from_str("\"Grayscale\"").unwrap();
+        let inverted: RenderFilter = serde_json::from_str("\"Inverted\"").unwrap();
+        assert_eq!(none, RenderFilter::None);
+        assert_eq!(grayscale, RenderFilter::Grayscale);
+        assert_eq!(inverted, RenderFilter::Inverted);
+    }
+
+    #[test]
+    fn test_dedupe_streams_merges_identical_streams() {
+        let mut doc = Document::with_version("1.5");
+        let make_stream =
+            || lopdf::Stream::new(lopdf::Dictionary::new(), b"0 0 m 10 10 l S".to_vec());
+        let first = doc.add_object(make_stream());
+        let second = doc.add_object(make_stream());
+        let other = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::new(),
+            b"1 0 0 RG".to_vec(),
+        ));
+        let holder = doc.add_object(Object::Array(vec![
+            Object::Reference(first),
+            Object::Reference(second),
+            Object::Reference(other),
+        ]));
+
+        assert_eq!(dedupe_streams(&mut doc), 1);
+        let refs: Vec<ObjectId> = doc
+            .get_object(holder)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o.as_reference().unwrap())
+            .collect();
+        assert_eq!(refs[0], refs[1]);
+        assert_ne!(refs[0], refs[2]);
+    }
+
+    #[test]
+    fn test_dedupe_streams_compares_dictionaries_by_entry() {
+        let mut doc = Document::with_version("1.5");
+        let image = |entries: Vec<(&str, Object)>| {
+            lopdf::Stream::new(lopdf::Dictionary::from_iter(entries), vec![0x80; 16])
+        };
+        let gray = Object::Name(b"DeviceGray".to_vec());
+        let first = doc.add_object(image(vec![
+            ("Width", Object::Integer(4)),
+            ("Height", Object::Integer(4)),
+            ("ColorSpace", gray.clone()),
+        ]));
+        // Same entries, written in another order.
+        let reordered = doc.add_object(image(vec![
+            ("ColorSpace", gray),
+            ("Height", Object::Integer(4)),
+            ("Width", Object::Integer(4)),
+        ]));
+        // Same bytes, but a different image.
+        let wider = doc.add_object(image(vec![
+            ("Width", Object::Integer(8)),
+            ("Height", Object::Integer(2)),
+            ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+        ]));
+        let holder = doc.add_object(Object::Array(vec![
+            Object::Reference(first),
+            Object::Reference(reordered),
+            Object::Reference(wider),
+        ]));
+
+        assert_eq!(dedupe_streams(&mut doc), 1);
+        let refs: Vec<ObjectId> = doc
+            .get_object(holder)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o.as_reference().unwrap())
+            .collect();
+        assert_eq!(refs[0], refs[1]);
+        assert_ne!(refs[0], refs[2]);
+    }
+
+    #[test]
+    fn test_add_watermark_stamps_every_page() {
+        let input = fixture_path();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        DocumentStore::add_watermark(
+            input.to_str().unwrap(),
+            "PDFBULLMARK",
+            WatermarkOptions::default(),
+            output,
+        )
+        .unwrap();
+
+        let doc = Document::load(output).unwrap();
+        for page_id in doc.get_pages().into_values() {
+            let content = doc.get_page_content(page_id);
+            let content = String::from_utf8_lossy(&content);
+            assert!(content.contains("(PDFBULLMARK) Tj"));
+            assert!(content.contains("/GSWm gs"));
+        }
+    }
+
+    /// A one-page form: text fields `name` ("Hello", no appearance) and
+    /// `email` (empty), and checkboxes `agree` (on) and `news` (off) with
+    /// on/off appearance streams.
+    fn form_fixture() -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let check_ap = |doc: &mut Document, ops: &[u8]| {
+            doc.add_object(lopdf::Stream::new(
+                lopdf::Dictionary::from_iter(vec![(
+                    "BBox",
+                    Object::Array(vec![
+                        Object::Integer(0),
+                        Object::Integer(0),
+                        Object::Integer(12),
+                        Object::Integer(12),
+                    ]),
+                )]),
+                ops.to_vec(),
+            ))
+        };
+        let yes = check_ap(&mut doc, b"0 0 12 12 re S 2 2 8 8 re f");
+        let off = check_ap(&mut doc, b"0 0 12 12 re S");
+        let mut fields = Vec::new();
+        let mut widget = |doc: &mut Document, entries: Vec<(&str, Object)>| {
+            let mut dict = lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Annot".to_vec())),
+                ("Subtype", Object::Name(b"Widget".to_vec())),
+                ("P", Object::Reference((1, 0))),
+            ]);
+            for (k, v) in entries {
+                dict.set(k, v);
+            }
+            let id = doc.add_object(Object::Dictionary(dict));
+            fields.push(Object::Reference(id));
+        };
+        let text_field = |name: &str, value: &str, y: i64| {
+            vec![
+                ("FT", Object::Name(b"Tx".to_vec())),
+                ("T", Object::string_literal(name)),
+                ("V", Object::string_literal(value)),
+                (
+                    "Rect",
+                    Object::Array(vec![
+                        Object::Integer(72),
+                        Object::Integer(y),
+                        Object::Integer(272),
+                        Object::Integer(y + 20),
+                    ]),
+                ),
+            ]
+        };
+        let checkbox = |name: &str, on: bool, y: i64| {
+            let state = if on { b"Yes".to_vec() } else { b"Off".to_vec() };
+            vec![
+                ("FT", Object::Name(b"Btn".to_vec())),
+                ("T", Object::string_literal(name)),
+                ("V", Object::Name(state.clone())),
+                ("AS", Object::Name(state)),
+                (
+                    "Rect",
+                    Object::Array(vec![
+                        Object::Integer(72),
+                        Object::Integer(y),
+                        Object::Integer(84),
+                        Object::Integer(y + 12),
+                    ]),
+                ),
+                (
+                    "AP",
+                    Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                        "N",
+                        Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                            ("Yes", Object::Reference(yes)),
+                            ("Off", Object::Reference(off)),
+                        ])),
+                    )])),
+                ),
+            ]
+        };
+        widget(&mut doc, text_field("name", "Hello", 700));
+        widget(&mut doc, text_field("email", "", 660));
+        widget(&mut doc, checkbox("agree", true, 620));
+        widget(&mut doc, checkbox("news", false, 590));
+
+        let content = doc.add_object(lopdf::Stream::new(lopdf::Dictionary::new(), Vec::new()));
+        let page_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(pages_id)),
+            (
+                "MediaBox",
+                Object::Array(vec![
+                    Object::Integer(0),
+                    Object::Integer(0),
+                    Object::Integer(612),
+                    Object::Integer(792),
+                ]),
+            ),
+            ("Contents", Object::Reference(content)),
+            ("Annots", Object::Array(fields.clone())),
+        ])));
+        for field in &fields {
+            let id = field.as_reference().unwrap();
+            doc.get_dictionary_mut(id)
+                .unwrap()
+                .set("P", Object::Reference(page_id));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Kids", Object::Array(vec![Object::Reference(page_id)])),
+                ("Count", Object::Integer(1)),
+            ])),
+        );
+        let catalog = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+            (
+                "AcroForm",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "Fields",
+                    Object::Array(fields),
+                )])),
+            ),
+        ])));
+        doc.trailer.set("Root", Object::Reference(catalog));
+        doc
+    }
+
+    #[test]
+    fn test_flatten_forms_keeps_values_as_content() {
+        let mut doc = form_fixture();
+        assert_eq!(flatten_form_fields(&mut doc).unwrap(), 4);
+
+        assert!(doc.catalog().unwrap().get(b"AcroForm").is_err());
+        let page_id = *doc.get_pages().values().next().unwrap();
+        assert!(doc.get_dictionary(page_id).unwrap().get(b"Annots").is_err());
+        let content = doc.get_page_content(page_id);
+        let content = String::from_utf8_lossy(&content);
+        // The text field had no appearance stream, so its value is synthesized.
+        assert!(content.contains("(Hello) Tj"));
+        // Both checkboxes keep their state's appearance: on for agree, off for news.
+        assert_eq!(content.matches(" Do").count(), 2);
+    }
+
+    #[test]
+    fn test_fill_form_values_sets_values_and_reports_missing() {
+        let input = temp_path(".pdf");
+        form_fixture().save(&input).unwrap();
+        let output = temp_path(".pdf");
+        let values = [
+            ("name", "Ada Lovelace – Ωμέγα"),
+            ("email", "ada@example.com"),
+            ("agree", "Off"),
+            ("news", "Yes"),
+            ("nickname", "x"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+
+        let (_, missing) = DocumentStore::fill_form_values(
+            input.to_str().unwrap(),
+            values,
+            output.to_string_lossy().to_string(),
+        )
+        .unwrap();
+        assert_eq!(missing, vec!["nickname"]);
+
+        let doc = Document::load(&output).unwrap();
+        let field = |name: &str| {
+            terminal_fields(&doc)
+                .into_iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, id)| doc.get_dictionary(id).unwrap())
+                .unwrap()
+        };
+        let value = match field("name").get(b"V") {
+            Ok(Object::String(bytes, _)) => decode_pdf_string(bytes),
+            other => panic!("unexpected /V: {other:?}"),
+        };
+        assert_eq!(value, "Ada Lovelace – Ωμέγα");
+        assert_eq!(
+            field("agree").get(b"AS").unwrap().as_name().unwrap(),
+            b"Off"
+        );
+        assert_eq!(field("news").get(b"AS").unwrap().as_name().unwrap(), b"Yes");
+        assert_eq!(field("news").get(b"V").unwrap().as_name().unwrap(), b"Yes");
+    }
+
+    #[test]
+    fn test_validate_flags_non_embedded_font() {
+        let mut doc = form_fixture();
+        let font = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+        ])));
+        let page_id = *doc.get_pages().values().next().unwrap();
+        doc.get_dictionary_mut(page_id).unwrap().set(
+            "Resources",
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                "Font",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "F1",
+                    Object::Reference(font),
+                )])),
+            )])),
+        );
+        let path = temp_path(".pdf");
+        doc.save(&path).unwrap();
+
+        let issues = DocumentStore::validate_pdf(path.to_str().unwrap()).unwrap();
+        let font_issue = issues
+            .iter()
+            .find(|i| i.rule == "font-embedded")
+            .expect("non-embedded font should be reported");
+        assert_eq!(font_issue.page, Some(1));
+        assert_eq!(font_issue.severity, crate::models::IssueSeverity::Error);
+        assert!(font_issue.message.contains("Helvetica"));
+        // A file lopdf just wrote is structurally sound.
+        assert!(
+            !issues
+                .iter()
+                .any(|i| i.rule == "xref-offset" || i.rule == "parse")
+        );
+    }
+
+    #[test]
+    fn test_repair_rebuilds_xref_after_bad_startxref() {
+        let input = fixture_path();
+        let mut bytes = std::fs::read(&input).unwrap();
+        let mut pos = find_last(&bytes, b"startxref").unwrap() + b"startxref".len();
+        while bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let digits = bytes[pos..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        bytes.splice(pos..pos + digits, b"99999".iter().copied());
+
+        let broken = temp_path(".pdf");
+        let fixed = temp_path(".pdf");
+        std::fs::write(&broken, &bytes).unwrap();
+        let result =
+            DocumentStore::repair_pdf(broken.to_str().unwrap(), fixed.to_str().unwrap()).unwrap();
+        let doc = Document::load(&fixed);
+
+        assert!(result.issues.contains(&RepairIssue::BadStartXref {
+            offset: Some(99999)
+        }));
+        assert!(result.objects_recovered > 0);
+        let original = Document::load(&input).unwrap();
+        assert_eq!(doc.unwrap().get_pages().len(), original.get_pages().len());
+    }
+
+    #[test]
+    fn test_repair_recovers_objects_inside_object_streams() {
+        let mut doc = Document::load(fixture_path()).unwrap();
+        let mut bytes = Vec::new();
+        doc.save_modern(&mut bytes).unwrap();
+        // Drop the xref stream and startxref so only a scan can find objects.
+        let cut = find_last(&bytes, b"startxref").unwrap();
+        bytes.truncate(cut);
+
+        let broken = temp_path(".pdf");
+        let fixed = temp_path(".pdf");
+        std::fs::write(&broken, &bytes).unwrap();
+        let result =
+            DocumentStore::repair_pdf(broken.to_str().unwrap(), fixed.to_str().unwrap()).unwrap();
+
+        let repaired = Document::load(&fixed).unwrap();
+        assert_eq!(repaired.get_pages().len(), doc.get_pages().len());
+        assert!(result.objects_recovered > scan_object_offsets(&bytes).len());
+    }
+
+    #[test]
+    fn test_xref_sections_split_sparse_numbers() {
+        let objects =
+            std::collections::BTreeMap::from([((1, 0), 10), ((2, 0), 20), ((4_000_000, 0), 30)]);
+        let section = xref_sections(&objects);
+        assert!(section.starts_with("xref\n0 3\n0000000000 65535 f \n"));
+        assert!(section.ends_with("4000000 1\n0000000030 00000 n \n"));
+        assert_eq!(section.lines().count(), 7);
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_scan_object_offsets_skips_stream_data() {
+        let pdf = b"%PDF-1.4\n1 0 obj\n<< /Length 9 >>\nstream\n9 9 obj x\nendstream\nendobj\n2 0 obj\n(a)\nendobj\n2 0 obj\n(b)\nendobj\n";
+        let objects = scan_object_offsets(pdf);
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[&(1, 0)], 9);
+        // The later definition of object 2 wins.
+        assert_eq!(objects[&(2, 0)], find_last(pdf, b"2 0 obj").unwrap());
+    }
 
     #[test]
-    fn test_render_key_equality() {
-        let doc_id = DocumentId(1);
-        let key1 = RenderKey {
-            doc_id,
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let key2 = RenderKey {
-            doc_id,
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        assert_eq!(key1, key2);
+    fn test_optimize_packs_objects_into_object_streams() {
+        let input = fixture_path();
+        let output = temp_path(".pdf");
+        let store = DocumentStore::new(create_render_cache(10, 0));
+        store
+            .optimize_pdf(input.to_str().unwrap(), output.to_str().unwrap())
+            .unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        assert!(find_from(&bytes, b"/ObjStm", 0).is_some());
+        assert!(find_from(&bytes, b"/XRef", 0).is_some());
+        let original = Document::load(&input).unwrap();
+        let optimized = Document::load_mem(&bytes).unwrap();
+        assert_eq!(optimized.get_pages().len(), original.get_pages().len());
     }
 
     #[test]
-    fn test_render_key_different_pages() {
-        let doc_id = DocumentId(1);
-        let key1 = RenderKey {
-            doc_id,
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let key2 = RenderKey {
-            doc_id,
-            page_num: 1,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
+    fn test_optimize_merges_duplicate_images_and_reports_savings() {
+        // Three pages, each with its own copy of one noisy image, so the
+        // copies survive compression and only deduplication removes them.
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let pixels: Vec<u8> = (0u32..64 * 64)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        let mut kids = Vec::new();
+        for _ in 0..3 {
+            let image = doc.add_object(lopdf::Stream::new(
+                lopdf::Dictionary::from_iter(vec![
+                    ("Type", Object::Name(b"XObject".to_vec())),
+                    ("Subtype", Object::Name(b"Image".to_vec())),
+                    ("Width", Object::Integer(64)),
+                    ("Height", Object::Integer(64)),
+                    ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+                    ("BitsPerComponent", Object::Integer(8)),
+                ]),
+                pixels.clone(),
+            ));
+            let content = doc.add_object(lopdf::Stream::new(
+                lopdf::Dictionary::new(),
+                b"q 64 0 0 64 0 0 cm /Im0 Do Q".to_vec(),
+            ));
+            kids.push(Object::Reference(doc.add_object(
+                lopdf::Dictionary::from_iter(vec![
+                    ("Type", Object::Name(b"Page".to_vec())),
+                    ("Parent", Object::Reference(pages_id)),
+                    (
+                        "MediaBox",
+                        Object::Array(vec![0.into(), 0.into(), 64.into(), 64.into()]),
+                    ),
+                    ("Contents", Object::Reference(content)),
+                    (
+                        "Resources",
+                        Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                            "XObject",
+                            Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                                "Im0",
+                                Object::Reference(image),
+                            )])),
+                        )])),
+                    ),
+                ]),
+            )));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Count", Object::Integer(3)),
+                ("Kids", Object::Array(kids)),
+            ])),
+        );
+        let catalog = doc.add_object(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+        ]));
+        doc.trailer.set("Root", Object::Reference(catalog));
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.pdf");
+        doc.save(&input).unwrap();
+        let input = input.to_str().unwrap();
+        let before = std::fs::metadata(input).unwrap().len();
+        let store = DocumentStore::new(create_render_cache(10, 0));
+        let image_count = |path: &str| {
+            let doc = Document::load(path).unwrap();
+            assert_eq!(doc.get_pages().len(), 3);
+            doc.objects
+                .values()
+                .filter(|o| o.as_stream().is_ok_and(|s| s.dict.has(b"Width")))
+                .count()
         };
-        assert_ne!(key1, key2);
+
+        let output = dir.path().join("output.pdf");
+        let output = output.to_str().unwrap();
+        let (written, saved) = store.optimize_pdf(input, output).unwrap();
+        assert_eq!(written, output);
+        let after = std::fs::metadata(output).unwrap().len();
+        assert!(after < before / 2, "{before} -> {after}");
+        assert_eq!(saved, before as i64 - after as i64);
+        assert_eq!(image_count(output), 1);
+
+        // In place: the file is replaced, and nothing is left beside it.
+        let (_, saved) = store.optimize_pdf(input, input).unwrap();
+        let in_place = std::fs::metadata(input).unwrap().len();
+        assert_eq!(saved, before as i64 - in_place as i64);
+        assert!(in_place < before / 2);
+        assert_eq!(image_count(input), 1);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
     }
 
     #[test]
-    fn test_render_key_different_scales() {
-        let doc_id = DocumentId(1);
-        let key1 = RenderKey {
-            doc_id,
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let key2 = RenderKey {
-            doc_id,
-            page_num: 0,
-            scale: 200,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        assert_ne!(key1, key2);
+    fn test_copy_page_brings_fonts_and_images_once() {
+        let mut src = Document::with_version("1.7");
+        let pages_id = src.new_object_id();
+        let font = src.add_object(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+        ]));
+        let image = src.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::from_iter(vec![
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(1)),
+                ("Height", Object::Integer(1)),
+            ]),
+            vec![0, 0, 0],
+        ));
+        let resources = src.add_object(lopdf::Dictionary::from_iter(vec![
+            (
+                "Font",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "F1",
+                    Object::Reference(font),
+                )])),
+            ),
+            (
+                "XObject",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "Im0",
+                    Object::Reference(image),
+                )])),
+            ),
+        ]));
+        let mut kids = Vec::new();
+        for _ in 0..2 {
+            let page = src.new_object_id();
+            // The annotation points back at its page, forming a cycle.
+            let annot = src.add_object(lopdf::Dictionary::from_iter(vec![
+                ("Subtype", Object::Name(b"Text".to_vec())),
+                ("P", Object::Reference(page)),
+            ]));
+            src.objects.insert(
+                page,
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                    ("Type", Object::Name(b"Page".to_vec())),
+                    ("Parent", Object::Reference(pages_id)),
+                    ("Annots", Object::Array(vec![Object::Reference(annot)])),
+                ])),
+            );
+            kids.push(Object::Reference(page));
+        }
+        src.objects.insert(
+            pages_id,
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Kids", Object::Array(kids.clone())),
+                ("Count", Object::Integer(2)),
+                ("Resources", Object::Reference(resources)),
+            ])),
+        );
+
+        let mut dest = Document::with_version("1.7");
+        let mut ctx = CopyContext::new();
+        let first = ctx.copy_object(&src, kids[0].as_reference().unwrap(), &mut dest);
+        // Page, annotation, resources, font and image; not the page tree.
+        assert_eq!(dest.objects.len(), 5);
+        assert!(!ctx.object_map().contains_key(&pages_id));
+
+        let page = dest.get_dictionary(first).unwrap();
+        assert!(page.get(b"Parent").is_err());
+        let resources = dest
+            .get_dictionary(page.get(b"Resources").unwrap().as_reference().unwrap())
+            .unwrap();
+        let new_font = resources
+            .get(b"Font")
+            .and_then(Object::as_dict)
+            .and_then(|f| f.get(b"F1"))
+            .and_then(Object::as_reference)
+            .unwrap();
+        assert_eq!(
+            dest.get_dictionary(new_font)
+                .unwrap()
+                .get(b"BaseFont")
+                .unwrap(),
+            &Object::Name(b"Helvetica".to_vec())
+        );
+        let annot = page.get(b"Annots").unwrap().as_array().unwrap()[0]
+            .as_reference()
+            .unwrap();
+        assert_eq!(
+            dest.get_dictionary(annot).unwrap().get(b"P").unwrap(),
+            &Object::Reference(first)
+        );
+
+        // The second page shares resources, so only it and its annotation
+        // are new.
+        ctx.copy_object(&src, kids[1].as_reference().unwrap(), &mut dest);
+        assert_eq!(dest.objects.len(), 7);
+    }
+
+    /// A one-page file laid out as a linearized one: the parameter
+    /// dictionary, the first page's objects, the hint stream, then the
+    /// rest. The parameters are zero-padded so the file can be rebuilt with
+    /// the real offsets without moving anything.
+    fn linearized_fixture() -> Vec<u8> {
+        let objects = [
+            (2, "<</Type/Catalog/Pages 3 0 R>>"),
+            (
+                4,
+                "<</Type/Page/Parent 3 0 R/MediaBox[0 0 200 200]/Contents 5 0 R>>",
+            ),
+            (5, "<</Length 13>>\nstream\n0 0 m 9 9 l S\nendstream"),
+            (6, "<</Length 4>>\nstream\nhint\nendstream"),
+            (3, "<</Type/Pages/Kids[4 0 R]/Count 1>>"),
+        ];
+        let mut params = [0usize; 4];
+        loop {
+            let [length, hint_offset, hint_length, first_page_end] = params;
+            let mut out = b"%PDF-1.7\n".to_vec();
+            let mut offsets = [out.len(); 7];
+            out.extend_from_slice(
+                format!(
+                    "1 0 obj\n<</Linearized 1/L {length:08}/H [{hint_offset:08} {hint_length:08}]\
+                     /O 4/E {first_page_end:08}/N 1/T 0>>\nendobj\n"
+                )
+                .as_bytes(),
+            );
+            for (id, body) in objects {
+                offsets[id] = out.len();
+                out.extend_from_slice(format!("{id} 0 obj\n{body}\nendobj\n").as_bytes());
+            }
+            let xref_offset = out.len();
+            out.extend_from_slice(b"xref\n0 7\n0000000000 65535 f \n");
+            for offset in &offsets[1..] {
+                out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+            }
+            out.extend_from_slice(
+                format!("trailer\n<</Size 7/Root 2 0 R>>\nstartxref\n{xref_offset}\n%%EOF\n")
+                    .as_bytes(),
+            );
+            let actual = [out.len(), offsets[6], offsets[3] - offsets[6], offsets[6]];
+            if actual == params {
+                return out;
+            }
+            params = actual;
+        }
     }
 
     #[test]
-    fn test_render_key_different_documents() {
-        let key1 = RenderKey {
-            doc_id: DocumentId(1),
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let key2 = RenderKey {
-            doc_id: DocumentId(2),
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        assert_ne!(key1, key2);
+    fn test_check_linearization() {
+        let path = temp_path(".pdf");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, linearized_fixture()).unwrap();
+        let linear = DocumentStore::check_linearization(path).unwrap();
+        assert!(linear.is_valid(), "{:?}", linear.checks);
+        assert_eq!(linear.length_matches, Some(true));
+        assert_eq!(linear.hint_offset_ok, Some(true));
+        // Catalog, page and content stream.
+        assert_eq!(linear.first_page_objects, Some(3));
+
+        // Anything appended afterwards leaves /L stale.
+        let mut updated = linearized_fixture();
+        updated.extend_from_slice(b"% appended\n");
+        std::fs::write(path, updated).unwrap();
+        let updated = DocumentStore::check_linearization(path).unwrap();
+        assert!(updated.linearized);
+        assert_eq!(updated.length_matches, Some(false));
+        assert!(!updated.is_valid());
+        let failed: Vec<_> = updated.checks.iter().filter(|c| !c.passed).collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].field, "L");
+
+        let input = fixture_path();
+        let plain = DocumentStore::check_linearization(input.to_str().unwrap()).unwrap();
+        assert!(!plain.linearized);
+        assert_eq!(plain.first_page_objects, None);
+        assert_eq!(plain.length_matches, None);
+        assert!(plain.checks.is_empty());
     }
 
     #[test]
-    fn test_render_key_distinguishes_scale() {
-        let doc_id = DocumentId(1);
-        let key_low = RenderKey {
-            doc_id,
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let key_high = RenderKey {
-            doc_id,
-            page_num: 0,
-            scale: 200,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
+    fn test_update_metadata_writes_info_and_xmp() {
+        let input = fixture_path();
+        let bare = temp_path(".pdf");
+        let bare = bare.to_str().unwrap();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        // Start from Info alone: an author and no XMP packet.
+        let mut doc = Document::load(&input).unwrap();
+        let root_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.get_dictionary_mut(root_id).unwrap().remove(b"Metadata");
+        let info_id = doc.add_object(lopdf::Dictionary::from_iter(vec![(
+            "Author",
+            Object::string_literal("Ada Lovelace"),
+        )]));
+        doc.trailer.set("Info", Object::Reference(info_id));
+        doc.save(bare).unwrap();
+
+        let update = crate::models::DocumentMetadata {
+            title: Some("Notes on the Analytical Engine".into()),
+            creation_date: Some("1843-09-01T12:00:00Z".into()),
+            ..Default::default()
         };
-        assert_ne!(key_low, key_high);
+        DocumentStore::update_metadata(bare, &update, output).unwrap();
+        let doc = Document::load(output).unwrap();
+
+        let info = doc
+            .trailer
+            .get(b"Info")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .unwrap();
+        let info = info_metadata(&doc, info);
+        let xmp_id = doc
+            .catalog()
+            .unwrap()
+            .get(b"Metadata")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let packet = &doc.get_object(xmp_id).unwrap().as_stream().unwrap().content;
+        let xmp = crate::xmp::read_xmp(&String::from_utf8_lossy(packet));
+
+        for meta in [&info, &xmp] {
+            assert_eq!(
+                meta.title.as_deref(),
+                Some("Notes on the Analytical Engine")
+            );
+            assert_eq!(meta.author.as_deref(), Some("Ada Lovelace"));
+        }
+        assert_eq!(info.creation_date.as_deref(), Some("D:18430901120000Z"));
+        assert_eq!(xmp.creation_date.as_deref(), Some("1843-09-01T12:00:00Z"));
     }
 
     #[test]
-    fn test_render_cache_creation() {
-        let cache = RenderCache::new(10, 100);
+    fn test_set_page_box_round_trip() {
+        let input = fixture_path();
+        let input = input.to_str().unwrap();
+        let cropped = temp_path(".pdf");
+        let cropped = cropped.to_str().unwrap();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        let issues = DocumentStore::set_page_box(
+            input,
+            cropped,
+            0,
+            PageBox::Crop,
+            [50.0, 60.0, 300.0, 400.0],
+        )
+        .unwrap();
+        assert!(issues.is_empty());
+        let boxes: HashMap<_, _> = DocumentStore::page_boxes(cropped, 0)
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(boxes[&PageBox::Crop], [50.0, 60.0, 300.0, 400.0]);
+        // Bleed, trim and art boxes default to the crop box.
+        assert_eq!(boxes[&PageBox::Trim], [50.0, 60.0, 300.0, 400.0]);
+        assert_eq!(PageBox::from_name("cropbox"), Some(PageBox::Crop));
+
+        DocumentStore::set_page_box(
+            cropped,
+            output,
+            0,
+            PageBox::Bleed,
+            [60.0, 70.0, 200.0, 200.0],
+        )
+        .unwrap();
+        let issues = DocumentStore::set_page_box(
+            output,
+            output,
+            0,
+            PageBox::Trim,
+            [55.0, 70.0, 200.0, 200.0],
+        )
+        .unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].rule, "page-box-order");
+
+        let media = DocumentStore::page_boxes(input, 0).unwrap()[0].1;
+        let outside = [media[0] - 10.0, media[1], media[2], media[3]];
+        assert!(DocumentStore::set_page_box(input, output, 0, PageBox::Art, outside).is_err());
+    }
+
+    #[test]
+    fn test_strip_javascript() {
+        let input = fixture_path();
+        let scripted = temp_path(".pdf");
+        let scripted = scripted.to_str().unwrap();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        let js = |code: &str| {
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("S", Object::Name(b"JavaScript".to_vec())),
+                ("JS", Object::string_literal(code)),
+            ]))
+        };
+        let mut doc = Document::load(&input).unwrap();
+        let named = doc.add_object(js("app.alert('named')"));
+        let names = doc.add_object(lopdf::Dictionary::from_iter(vec![(
+            "JavaScript",
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                "Names",
+                Object::Array(vec![
+                    Object::string_literal("init"),
+                    Object::Reference(named),
+                ]),
+            )])),
+        )]));
+        let page_id = *doc.get_pages().values().next().unwrap();
+        doc.get_dictionary_mut(page_id).unwrap().set(
+            "AA",
+            lopdf::Dictionary::from_iter(vec![("O", js("this.print()"))]),
+        );
+        let root_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let catalog = doc.get_dictionary_mut(root_id).unwrap();
+        catalog.set("Names", Object::Reference(names));
+        catalog.set("OpenAction", js("app.alert('open')"));
+        doc.save(scripted).unwrap();
+
+        let listed = DocumentStore::list_javascript(scripted).unwrap();
         assert_eq!(
-            cache.get(&RenderKey {
-                doc_id: DocumentId(1),
-                page_num: 0,
-                scale: 100,
-                auto_crop: false,
-                quality: RenderQuality::Medium,
-            }),
-            None
+            listed,
+            [
+                (
+                    "Document script \"init\"".to_string(),
+                    "app.alert('named')".to_string()
+                ),
+                (
+                    "Document open action".to_string(),
+                    "app.alert('open')".to_string()
+                ),
+                ("Page 1 action O".to_string(), "this.print()".to_string()),
+            ]
         );
+
+        let (_, removed) = DocumentStore::strip_javascript(scripted, output).unwrap();
+        let doc = Document::load(output).unwrap();
+        let listed = DocumentStore::list_javascript(output).unwrap();
+        assert_eq!(removed, 3);
+        assert!(listed.is_empty());
+        let catalog = doc.catalog().unwrap();
+        assert!(!catalog.has(b"OpenAction"));
+        let names = resolve(&doc, catalog.get(b"Names").unwrap())
+            .as_dict()
+            .unwrap();
+        assert!(!names.has(b"JavaScript"));
     }
 
     #[test]
-    fn test_render_cache_insert_and_get() {
-        let cache = RenderCache::new(10, 1024);
-        let key = RenderKey {
-            doc_id: DocumentId(1),
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let result = crate::models::RenderResult {
-            width: 100,
-            height: 100,
-            data: vec![0u8; 100].into(),
-        };
-        cache.put(key.clone(), result.clone());
-        let cached = cache.get(&key);
-        assert!(cached.is_some());
-        assert_eq!(cached.unwrap().width, 100);
+    fn test_list_and_extract_3d() {
+        let input = fixture_path();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        let model = b"U3D\0 a small model standing in for a CAD part".repeat(4);
+        let c2w = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, -10.0];
+        let view = lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"3DView".to_vec())),
+            ("XN", Object::string_literal("Front")),
+            ("MS", Object::Name(b"M".to_vec())),
+            ("C2W", Object::Array(c2w.map(Object::Real).to_vec())),
+        ]);
+        let mut doc = Document::load(&input).unwrap();
+        let mut stream = lopdf::Stream::new(
+            lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"3D".to_vec())),
+                ("Subtype", Object::Name(b"U3D".to_vec())),
+                ("VA", Object::Array(vec![Object::Dictionary(view)])),
+                ("DV", Object::Integer(0)),
+            ]),
+            model.clone(),
+        );
+        stream.compress().unwrap();
+        let stream_id = doc.add_object(stream);
+        let annot_id = doc.add_object(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Annot".to_vec())),
+            ("Subtype", Object::Name(b"3D".to_vec())),
+            (
+                "Rect",
+                Object::Array(vec![100.into(), 100.into(), 300.into(), 300.into()]),
+            ),
+            ("3DD", Object::Reference(stream_id)),
+        ]));
+        let page_id = *doc.get_pages().values().next().unwrap();
+        doc.get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Annots", Object::Array(vec![Object::Reference(annot_id)]));
+        doc.save(output).unwrap();
+
+        let found = DocumentStore::list_3d(output).unwrap();
+        let extracted = DocumentStore::extract_3d_stream(output, 0, 0);
+        let missing = DocumentStore::extract_3d_stream(output, 0, 1);
+        assert_eq!(
+            found,
+            [ThreeDInfo {
+                page: 0,
+                index: 0,
+                format: "U3D".into(),
+                size: model.len(),
+                view_name: Some("Front".into()),
+                view_matrix: Some(c2w),
+            }]
+        );
+        assert_eq!(extracted.unwrap(), model);
+        assert!(missing.is_err());
+        assert!(
+            DocumentStore::list_3d(input.to_str().unwrap())
+                .unwrap()
+                .is_empty()
+        );
     }
 
     #[test]
-    fn test_render_cache_overwrite() {
-        let cache = RenderCache::new(10, 1024);
-        let key = RenderKey {
-            doc_id: DocumentId(1),
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let result1 = crate::models::RenderResult {
-            width: 100,
-            height: 100,
-            data: vec![0u8; 100].into(),
-        };
-        let result2 = crate::models::RenderResult {
-            width: 200,
-            height: 200,
-            data: vec![0u8; 200].into(),
-        };
-        cache.put(key.clone(), result1);
-        cache.put(key.clone(), result2);
-        let cached = cache.get(&key);
-        assert_eq!(cached.unwrap().width, 200);
+    fn test_embedded_files_round_trip() {
+        let input = fixture_path();
+        let first = temp_path(".pdf");
+        let first = first.to_str().unwrap();
+        let second = temp_path(".pdf");
+        let second = second.to_str().unwrap();
+        assert!(
+            DocumentStore::extract_files(input.to_str().unwrap())
+                .unwrap()
+                .is_empty()
+        );
+
+        let notes = b"Line one\nLine two (with parentheses)\n".repeat(50);
+        DocumentStore::embed_file(input.to_str().unwrap(), "notes.txt", &notes, first).unwrap();
+        DocumentStore::embed_file(first, "r\u{e9}sum\u{e9} \u{2116}1.txt", b"CV", second).unwrap();
+        // Attaching under an existing name replaces the file.
+        DocumentStore::embed_file(second, "notes.txt", &notes[..9], second).unwrap();
+
+        let files = DocumentStore::extract_files(second).unwrap();
+        assert_eq!(
+            files,
+            [
+                ("notes.txt".to_string(), b"Line one\n".to_vec()),
+                ("r\u{e9}sum\u{e9} \u{2116}1.txt".to_string(), b"CV".to_vec()),
+            ]
+        );
     }
 
     #[test]
-    fn test_render_cache_different_keys() {
-        let cache = RenderCache::new(10, 1024);
-        let key1 = RenderKey {
-            doc_id: DocumentId(1),
-            page_num: 0,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let key2 = RenderKey {
-            doc_id: DocumentId(1),
-            page_num: 1,
-            scale: 100,
-            auto_crop: false,
-            quality: RenderQuality::Medium,
-        };
-        let result1 = crate::models::RenderResult {
-            width: 100,
-            height: 100,
-            data: vec![0u8; 100].into(),
-        };
-        let result2 = crate::models::RenderResult {
-            width: 200,
-            height: 200,
-            data: vec![0u8; 200].into(),
-        };
-        cache.put(key1.clone(), result1);
-        cache.put(key2.clone(), result2);
-        assert_eq!(cache.get(&key1).unwrap().width, 100);
-        assert_eq!(cache.get(&key2).unwrap().width, 200);
+    fn test_zugferd_round_trip() {
+        let input = fixture_path();
+        let input = input.to_str().unwrap();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                   <rsm:CrossIndustryInvoice xmlns:rsm=\"urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100\">\n\
+                   <!-- Gr\u{f6}\u{df}e: 42 \u{20ac} -->\n\
+                   </rsm:CrossIndustryInvoice>\n";
+
+        assert_eq!(DocumentStore::extract_zugferd(input).unwrap(), None);
+        assert!(DocumentStore::embed_zugferd(input, xml.as_bytes(), "gold", output).is_err());
+        DocumentStore::embed_zugferd(input, xml.as_bytes(), "en 16931", output).unwrap();
+        let extracted = DocumentStore::extract_zugferd(output).unwrap();
+
+        let doc = Document::load(output).unwrap();
+        assert_eq!(extracted.as_deref(), Some(xml));
+        let catalog = doc.catalog().unwrap();
+        let associated = catalog.get(b"AF").unwrap().as_array().unwrap();
+        assert_eq!(associated.len(), 1);
+        let spec = resolve(&doc, &associated[0]).as_dict().unwrap();
+        assert_eq!(
+            spec.get(b"AFRelationship").unwrap().as_name().unwrap(),
+            b"Data"
+        );
+        let xmp_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let packet = &doc.get_object(xmp_id).unwrap().as_stream().unwrap().content;
+        let packet = String::from_utf8_lossy(packet);
+        assert!(packet.contains("<fx:ConformanceLevel>EN 16931</fx:ConformanceLevel>"));
+        assert!(packet.contains("<fx:DocumentFileName>factur-x.xml</fx:DocumentFileName>"));
     }
 
     #[test]
-    fn test_apply_filter_inverted() {
-        let mut data = vec![100, 150, 200, 255, 50, 75, 100, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::Inverted);
-        assert_eq!(data[0], 155);
-        assert_eq!(data[1], 105);
-        assert_eq!(data[2], 55);
+    fn test_create_portfolio() {
+        let input = fixture_path();
+        let input = input.to_str().unwrap();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+        let member = std::fs::read(input).unwrap();
+        let members = vec![
+            ("report.pdf".to_string(), member.clone()),
+            ("appendix.pdf".to_string(), member),
+        ];
+
+        assert_eq!(DocumentStore::list_portfolio(input).unwrap(), None);
+        let not_pdf = [("notes.txt".to_string(), b"plain text".to_vec())];
+        assert!(DocumentStore::create_portfolio(input, &not_pdf, output).is_err());
+        DocumentStore::create_portfolio(input, &members, output).unwrap();
+        let listed = DocumentStore::list_portfolio(output).unwrap();
+        let files = DocumentStore::extract_files(output).unwrap();
+
+        let doc = Document::load(output).unwrap();
+        assert_eq!(
+            listed,
+            Some(vec!["appendix.pdf".to_string(), "report.pdf".to_string()])
+        );
+        assert_eq!(files.len(), 2);
+        let catalog = doc.catalog().unwrap();
+        let collection = resolve(&doc, catalog.get(b"Collection").unwrap())
+            .as_dict()
+            .unwrap();
+        assert_eq!(
+            collection.get(b"D").unwrap().as_str().unwrap(),
+            b"appendix.pdf"
+        );
     }
 
     #[test]
-    fn test_apply_filter_eco_bright() {
-        let mut data = vec![250, 250, 250, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::Eco);
-        assert_eq!(data[0], 255);
-        assert_eq!(data[1], 255);
-        assert_eq!(data[2], 255);
+    fn test_load_links() {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let rect = |x0: i64, y0: i64, x1: i64, y1: i64| {
+            Object::Array(vec![
+                Object::Integer(x0),
+                Object::Integer(y0),
+                Object::Integer(x1),
+                Object::Integer(y1),
+            ])
+        };
+        let second = doc.add_object(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(pages_id)),
+        ]));
+        let link = |action: Vec<(&str, Object)>, area: Object| {
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Annot".to_vec())),
+                ("Subtype", Object::Name(b"Link".to_vec())),
+                ("Rect", area),
+                (
+                    "A",
+                    Object::Dictionary(lopdf::Dictionary::from_iter(action)),
+                ),
+            ]))
+        };
+        let first = doc.add_object(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(pages_id)),
+            (
+                "Annots",
+                Object::Array(vec![
+                    link(
+                        vec![
+                            ("S", Object::Name(b"GoTo".to_vec())),
+                            ("D", Object::string_literal("chapter2")),
+                        ],
+                        rect(72, 700, 200, 714),
+                    ),
+                    link(
+                        vec![
+                            ("S", Object::Name(b"URI".to_vec())),
+                            ("URI", Object::string_literal("https://example.com/")),
+                        ],
+                        rect(300, 100, 72, 120),
+                    ),
+                    link(
+                        vec![("S", Object::Name(b"Launch".to_vec()))],
+                        rect(0, 0, 10, 10),
+                    ),
+                ]),
+            ),
+        ]));
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                (
+                    "Kids",
+                    Object::Array(vec![Object::Reference(first), Object::Reference(second)]),
+                ),
+                ("Count", Object::Integer(2)),
+                ("MediaBox", rect(0, 0, 612, 792)),
+            ])),
+        );
+        let names = lopdf::Dictionary::from_iter(vec![(
+            "Dests",
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                "Names",
+                Object::Array(vec![
+                    Object::string_literal("chapter2"),
+                    Object::Array(vec![
+                        Object::Reference(second),
+                        Object::Name(b"Fit".to_vec()),
+                    ]),
+                ]),
+            )])),
+        )]);
+        let catalog = doc.add_object(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+            ("Names", Object::Dictionary(names)),
+        ]));
+        doc.trailer.set("Root", Object::Reference(catalog));
+        let path = temp_path(".pdf");
+        let path = path.to_str().unwrap();
+        doc.save(path).unwrap();
+
+        let links = DocumentStore::load_links(path, 0);
+        let none = DocumentStore::load_links(path, 1);
+        let missing = DocumentStore::load_links(path, 2);
+        let links = links.unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].bounds, (72.0, 700.0, 128.0, 14.0));
+        assert_eq!(links[0].destination_page, Some(1));
+        assert_eq!(links[0].url, None);
+        assert_eq!(links[1].bounds, (72.0, 100.0, 228.0, 20.0));
+        assert_eq!(links[1].url.as_deref(), Some("https://example.com/"));
+        assert!(links.iter().all(|link| link.page == 0));
+        assert!(none.unwrap().is_empty());
+        assert!(matches!(missing, Err(PdfError::PageNotFound(2))));
     }
 
     #[test]
-    fn test_apply_filter_eco_dark() {
-        let mut data = vec![100, 100, 100, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::Eco);
-        assert_eq!(data[0], 100);
-        assert_eq!(data[1], 100);
-        assert_eq!(data[2], 100);
+    fn test_page_transition() {
+        let input = fixture_path();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        let mut doc = Document::load(&input).unwrap();
+        let page_id = *doc.get_pages().values().next().unwrap();
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        page.set(
+            "Trans",
+            lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Trans".to_vec())),
+                ("S", Object::Name(b"Wipe".to_vec())),
+                ("D", Object::Real(2.5)),
+                ("Di", Object::Integer(270)),
+            ]),
+        );
+        page.set("Dur", Object::Integer(8));
+        doc.save(output).unwrap();
+
+        let transition = DocumentStore::page_transition(output, 0);
+        let transition = transition.unwrap().unwrap();
+        assert_eq!(transition.style, TransitionStyle::Wipe);
+        assert_eq!(transition.duration, 2.5);
+        assert_eq!(transition.direction, Some(270));
+        assert!(!transition.vertical);
+        assert_eq!(transition.page_duration, Some(8.0));
+        let input = input.to_str().unwrap();
+        assert_eq!(DocumentStore::page_transition(input, 0).unwrap(), None);
     }
 
     #[test]
-    fn test_apply_filter_black_white_high() {
-        let mut data = vec![200, 200, 200, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::BlackWhite);
-        assert_eq!(data[0], 255);
-        assert_eq!(data[1], 255);
-        assert_eq!(data[2], 255);
+    fn test_clean_collects_garbage() {
+        let input = fixture_path();
+        let orphaned = temp_path(".pdf");
+        let orphaned = orphaned.to_str().unwrap();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        let mut doc = Document::load(&input).unwrap();
+        let pages = doc.get_pages().len();
+        doc.add_object(lopdf::Dictionary::from_iter(vec![(
+            "Orphan",
+            Object::Boolean(true),
+        )]));
+        doc.save(orphaned).unwrap();
+        let has_orphan = |doc: &Document| {
+            doc.objects
+                .values()
+                .any(|obj| obj.as_dict().is_ok_and(|dict| dict.has(b"Orphan")))
+        };
+
+        let kept = CleanOptions::default();
+        DocumentStore::clean_pdf(orphaned, output, kept).unwrap();
+        assert!(has_orphan(&Document::load(output).unwrap()));
+
+        let options = CleanOptions {
+            garbage: true,
+            decompress: true,
+            sanitize: true,
+        };
+        let (_, removed) = DocumentStore::clean_pdf(orphaned, output, options).unwrap();
+        let cleaned = Document::load(output).unwrap();
+        assert!(removed >= 1);
+        assert!(!has_orphan(&cleaned));
+        assert_eq!(cleaned.get_pages().len(), pages);
+        let page_id = *cleaned.get_pages().values().next().unwrap();
+        let contents = cleaned
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Contents")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        assert!(
+            !cleaned
+                .get_object(contents)
+                .unwrap()
+                .as_stream()
+                .unwrap()
+                .dict
+                .has(b"Filter")
+        );
     }
 
     #[test]
-    fn test_apply_filter_black_white_low() {
-        let mut data = vec![50, 50, 50, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::BlackWhite);
-        assert_eq!(data[0], 0);
-        assert_eq!(data[1], 0);
-        assert_eq!(data[2], 0);
+    fn test_incremental_update_keeps_original_bytes() {
+        let input = fixture_path();
+        let original = std::fs::read(input).unwrap();
+        let doc = Document::load_mem(&original).unwrap();
+        let page_id = *doc.get_pages().values().next().unwrap();
+
+        let annot_id = (doc.max_id + 1, 0);
+        let annot = lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Annot".to_vec())),
+            ("Subtype", Object::Name(b"Square".to_vec())),
+            (
+                "Rect",
+                Object::Array(vec![10.into(), 10.into(), 60.into(), 40.into()]),
+            ),
+        ]);
+        let mut page = doc.get_dictionary(page_id).unwrap().clone();
+        page.set("Annots", Object::Array(vec![Object::Reference(annot_id)]));
+        let changed = std::collections::BTreeMap::from([
+            (page_id, Object::Dictionary(page)),
+            (annot_id, Object::Dictionary(annot)),
+        ]);
+
+        let mut updated = Vec::new();
+        write_incremental(&original, &changed, &mut updated).unwrap();
+        assert!(updated.starts_with(&original));
+        let update = String::from_utf8_lossy(&updated[original.len()..]);
+        assert!(update.contains(&format!("/Prev {}", last_startxref(&original).unwrap())));
+
+        let reopened = Document::load_mem(&updated).unwrap();
+        let page_id = *reopened.get_pages().values().next().unwrap();
+        let annots = reopened
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Annots")
+            .unwrap()
+            .as_array()
+            .unwrap();
+        let annot = reopened
+            .get_dictionary(annots[0].as_reference().unwrap())
+            .unwrap();
+        assert_eq!(annot.get(b"Subtype").unwrap().as_name().unwrap(), b"Square");
+        assert_eq!(reopened.get_pages().len(), doc.get_pages().len());
     }
 
     #[test]
-    fn test_apply_filter_lighten() {
-        let mut data = vec![100, 100, 100, 255, 230, 230, 230, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::Lighten);
-        assert_eq!(data[0], 120);
-        assert_eq!(data[1], 120);
-        assert_eq!(data[2], 120);
-        assert_eq!(data[4], 250);
+    fn test_incremental_update_chains_xref_streams() {
+        let mut doc = Document::load(fixture_path()).unwrap();
+        let mut original = Vec::new();
+        doc.save_modern(&mut original).unwrap();
+        let doc = Document::load_mem(&original).unwrap();
+        let info_id = (doc.max_id + 1, 0);
+        let mut catalog = doc.catalog().unwrap().clone();
+        catalog.set("Lang", Object::string_literal("en"));
+        let root_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let changed = std::collections::BTreeMap::from([
+            (root_id, Object::Dictionary(catalog)),
+            (
+                info_id,
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "Title",
+                    Object::string_literal("Chained"),
+                )])),
+            ),
+        ]);
+
+        let mut updated = Vec::new();
+        write_incremental(&original, &changed, &mut updated).unwrap();
+        assert!(updated.starts_with(&original));
+        assert!(
+            !updated[original.len()..]
+                .windows(6)
+                .any(|w| w == b"\nxref\n")
+        );
+        let reopened = Document::load_mem(&updated).unwrap();
+        assert_eq!(
+            reopened
+                .catalog()
+                .unwrap()
+                .get(b"Lang")
+                .unwrap()
+                .as_str()
+                .unwrap(),
+            b"en"
+        );
+        assert_eq!(reopened.get_pages().len(), doc.get_pages().len());
     }
 
     #[test]
-    fn test_apply_filter_lighten_saturation() {
-        let mut data = vec![245, 245, 245, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::Lighten);
-        assert_eq!(data[0], 255);
-        assert_eq!(data[1], 255);
-        assert_eq!(data[2], 255);
+    fn test_encrypt_pdf_needs_the_user_password() {
+        let input = fixture_path();
+        let output = temp_path(".pdf");
+        DocumentStore::encrypt_pdf(
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "user",
+            "owner",
+            lopdf::Permissions::PRINTABLE,
+        )
+        .unwrap();
+
+        let encrypted = Document::load(&output).unwrap();
+        assert!(encrypted.is_encrypted());
+        let dict = encrypted
+            .trailer
+            .get(b"Encrypt")
+            .and_then(Object::as_reference)
+            .and_then(|id| encrypted.get_dictionary(id))
+            .unwrap();
+        assert_eq!(dict.get(b"V").and_then(Object::as_i64).unwrap(), 5);
+        assert_eq!(dict.get(b"R").and_then(Object::as_i64).unwrap(), 6);
+        assert!(encrypted.authenticate_user_password("").is_err());
+        assert!(encrypted.authenticate_user_password("user").is_ok());
+        assert!(encrypted.authenticate_owner_password("owner").is_ok());
+
+        let original = Document::load(&input).unwrap();
+        let decrypted = Document::load_with_password(&output, "user").unwrap();
+        let first_page = |doc: &Document| *doc.get_pages().values().next().unwrap();
+        assert_eq!(
+            decrypted.get_page_content(first_page(&decrypted)),
+            original.get_page_content(first_page(&original))
+        );
+
+        // Encrypting twice, or without an owner password, is refused.
+        let again = temp_path(".pdf");
+        let twice = DocumentStore::encrypt_pdf(
+            output.to_str().unwrap(),
+            again.to_str().unwrap(),
+            "",
+            "owner",
+            lopdf::Permissions::all(),
+        );
+        assert!(twice.is_err());
+        let no_owner = DocumentStore::encrypt_pdf(
+            input.to_str().unwrap(),
+            again.to_str().unwrap(),
+            "user",
+            "",
+            lopdf::Permissions::all(),
+        );
+        assert!(no_owner.is_err());
     }
 
     #[test]
-    fn test_apply_filter_no_shadow_bright() {
-        let mut data = vec![235, 235, 235, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::NoShadow);
-        assert_eq!(data[0], 255);
-        assert_eq!(data[1], 255);
-        assert_eq!(data[2], 255);
+    fn test_scan_barcodes_reads_back_stamped_codes() {
+        let input = fixture_path();
+        let qr_path = temp_path(".pdf");
+        let both_path = temp_path(".pdf");
+        let (qr_path, both_path) = (qr_path.to_str().unwrap(), both_path.to_str().unwrap());
+        let qr = BarcodeSpec {
+            kind: BarcodeKind::Qr(QrErrorCorrection::Quartile),
+            data: "https://github.com/SV-stark/PDFbull".into(),
+            page: 0,
+            x: 36.0,
+            y: 36.0,
+            scale: 2.0,
+        };
+        let code128 = BarcodeSpec {
+            kind: BarcodeKind::Code128,
+            data: "PDFbull-0042".into(),
+            x: 300.0,
+            scale: 1.5,
+            ..qr.clone()
+        };
+        add_barcode(input.to_str().unwrap(), &qr, qr_path).unwrap();
+        add_barcode(qr_path, &code128, both_path).unwrap();
+
+        let mut store = DocumentStore::new(create_render_cache(10, 0));
+        let doc_id = DocumentId(1);
+        let opened = store.open_document(both_path, None, doc_id).unwrap();
+        let page_height = opened.page_heights[0];
+
+        let found = store.scan_barcodes(doc_id, 0, None).unwrap();
+        let mut payloads: Vec<_> = found.iter().map(|r| (r.kind, r.data.as_str())).collect();
+        payloads.sort_by_key(|(kind, _)| *kind == BarcodeKind::Code128);
+        assert_eq!(
+            payloads,
+            [
+                (qr.kind, qr.data.as_str()),
+                (code128.kind, code128.data.as_str())
+            ]
+        );
+
+        // Only the QR code lies in the bottom-left corner; its box comes
+        // back in points from the top left.
+        let corner = (0.0, page_height - 250.0, 250.0, 250.0);
+        let found = store.scan_barcodes(doc_id, 0, Some(corner)).unwrap();
+        assert_eq!(found.len(), 1);
+        let size = qr_modules(&qr.data, QrErrorCorrection::Quartile)
+            .unwrap()
+            .len() as f32
+            * 2.0;
+        let (x, y, width, height) = found[0].bbox;
+        assert!((x - 36.0).abs() < 1.0, "{:?}", found[0].bbox);
+        assert!((y - (page_height - 36.0 - size)).abs() < 1.0);
+        assert!((width - size).abs() < 1.0 && (height - size).abs() < 1.0);
     }
 
     #[test]
-    fn test_apply_filter_no_shadow_dark() {
-        let mut data = vec![100, 100, 100, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::NoShadow);
-        assert_eq!(data[0], 100);
-        assert_eq!(data[1], 100);
-        assert_eq!(data[2], 100);
+    fn test_nice_axis_spans_zero_and_data() {
+        assert_eq!(nice_axis(0.0, 97.0), (0.0, 100.0, 20.0));
+        let (lo, hi, step) = nice_axis(-3.2, 7.5);
+        assert!(lo <= -3.2 && hi >= 7.5 && lo < 0.0);
+        assert_eq!((lo / step).fract(), 0.0, "zero must fall on a tick");
+        assert_eq!(nice_axis(5.0, 5.0).0, 0.0);
+        assert_eq!(format_tick(-0.0), "0");
+        assert_eq!(format_tick(2.5), "2.5");
     }
 
     #[test]
-    fn test_apply_filter_grayscale() {
-        let mut data = vec![100, 150, 200, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::Grayscale);
-        let luma = ((100 * 299 + 150 * 587 + 200 * 114) / 1000) as u8;
-        assert_eq!(data[0], luma);
-        assert_eq!(data[1], luma);
-        assert_eq!(data[2], luma);
+    fn test_chart_content_handles_negative_and_empty_series() {
+        let spec: ChartSpec = serde_json::from_str(
+            r#"{"kind": "bar", "title": "Profit", "labels": ["Q1", "Q2", "Q3"],
+                "series": [{"name": "2025", "values": [4, -2, 6]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(spec.rect, ChartSpec::default_rect());
+        let content = String::from_utf8(chart_content(&spec).unwrap()).unwrap();
+        assert!(content.contains("(Profit) Tj"));
+        assert!(content.contains("(-2) Tj"), "axis should extend below zero");
+
+        let line = ChartSpec {
+            kind: ChartKind::Line,
+            ..spec.clone()
+        };
+        assert!(chart_content(&line).unwrap().windows(2).any(|w| w == b" l"));
+
+        let empty = ChartSpec {
+            series: vec![ChartSeries {
+                name: "none".into(),
+                values: Vec::new(),
+                color: None,
+            }],
+            labels: Vec::new(),
+            ..spec
+        };
+        let content = String::from_utf8(chart_content(&empty).unwrap()).unwrap();
+        assert!(content.contains("(0) Tj"));
     }
 
     #[test]
-    fn test_apply_filter_none_does_nothing() {
-        let mut data = vec![100, 150, 200, 255];
-        DocumentStore::apply_filter(&mut data, RenderFilter::None);
-        assert_eq!(data[0], 100);
-        assert_eq!(data[1], 150);
-        assert_eq!(data[2], 200);
+    fn test_wrap_words() {
+        assert_eq!(wrap_words("one two three", 7), vec!["one two", "three"]);
+        assert_eq!(wrap_words("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap_words("", 10), vec![""]);
     }
 
     #[test]
-    fn test_apply_filter_large_buffer() {
-        let mut data = vec![0u8; 10000];
-        for i in 0..2500 {
-            data[i * 4] = 100;
-            data[i * 4 + 1] = 150;
-            data[i * 4 + 2] = 200;
-            data[i * 4 + 3] = 255;
-        }
-        DocumentStore::apply_filter(&mut data, RenderFilter::Inverted);
-        for i in 0..2500 {
-            assert_eq!(data[i * 4], 155);
-            assert_eq!(data[i * 4 + 1], 105);
-            assert_eq!(data[i * 4 + 2], 55);
-        }
+    fn test_prepend_toc_paginates_and_links() {
+        let input = fixture_path();
+        let input = input.to_str().unwrap();
+        let source_pages = Document::load(input).unwrap().get_pages().len();
+        let entries: Vec<Bookmark> = (0..60)
+            .map(|i| Bookmark {
+                title: format!("Chapter {i} with a reasonably long descriptive title"),
+                page_index: i % source_pages,
+                depth: i % 3,
+            })
+            .collect();
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+
+        DocumentStore::prepend_toc(input, &entries, output).unwrap();
+        let doc = Document::load(output).unwrap();
+        let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        let toc_pages = pages.len() - source_pages;
+        assert!(toc_pages > 1, "60 entries should overflow one TOC page");
+
+        let links: usize = pages[..toc_pages]
+            .iter()
+            .filter_map(|&id| doc.get_dictionary(id).ok())
+            .filter_map(|page| page.get(b"Annots").and_then(Object::as_array).ok())
+            .map(Vec::len)
+            .sum();
+        assert!(links >= entries.len());
+        let first = String::from_utf8_lossy(&doc.get_page_content(pages[0])).to_string();
+        assert!(first.contains("(Contents) Tj"));
+        assert!(first.contains("(Chapter 0 with a"));
     }
 
     #[test]
-    fn test_detect_content_bbox_parallel_empty() {
-        let data = vec![255u8; 400];
-        let result = DocumentStore::detect_content_bbox_parallel(&data, 10, 10);
-        assert!(result.is_none());
+    fn test_extract_pages_writes_selected_pages() {
+        let input = fixture_path();
+        let input = input.to_str().unwrap();
+        let total = Document::load(input).unwrap().get_pages().len();
+
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap();
+        DocumentStore::extract_pages_from_file(input, &[total - 1, 0], output).unwrap();
+        let extracted = Document::load(output).unwrap().get_pages().len();
+        assert_eq!(extracted, if total > 1 { 2 } else { 1 });
+
+        assert!(DocumentStore::extract_pages_from_file(input, &[total], output).is_err());
+        assert!(DocumentStore::extract_pages_from_file(input, &[], output).is_err());
     }
 
     #[test]
-    fn test_detect_content_bbox_parallel_full() {
-        let mut data = vec![255u8; 400];
-        data[0] = 100;
-        data[4] = 100;
-        let result = DocumentStore::detect_content_bbox_parallel(&data, 10, 10);
-        assert!(result.is_some());
-        let (min_x, min_y, max_x, max_y) = result.unwrap();
-        assert!(min_x <= max_x);
-        assert!(min_y <= max_y);
+    fn test_split_writes_numbered_pages_and_reports_progress() {
+        let store = DocumentStore::new(create_render_cache(10, 0));
+        let input = fixture_path();
+        let input = input.to_str().unwrap();
+        let total = Document::load(input).unwrap().get_pages().len();
+        let out_dir = tempfile::tempdir().unwrap();
+
+        let mut progress = Vec::new();
+        let paths = store
+            .split_pdf(
+                input,
+                (0..total).collect(),
+                out_dir.path().to_string_lossy().to_string(),
+                |done, of| progress.push((done, of)),
+            )
+            .unwrap();
+        let pages: Vec<usize> = paths
+            .iter()
+            .map(|p| Document::load(p).unwrap().get_pages().len())
+            .collect();
+
+        assert_eq!(paths.len(), total);
+        assert!(paths[0].ends_with("page_001.pdf"));
+        assert!(pages.iter().all(|&n| n == 1));
+        assert_eq!(
+            progress,
+            (1..=total).map(|done| (done, total)).collect::<Vec<_>>()
+        );
+        assert!(matches!(
+            store.split_pdf(input, vec![total], String::new(), |_, _| {}),
+            Err(PdfError::PageNotFound(_))
+        ));
     }
 
     #[test]
-    fn test_detect_content_bbox_parallel_with_margin() {
-        let mut data = vec![255u8; 40];
-        data[0] = 100;
-        let result = DocumentStore::detect_content_bbox_parallel(&data, 10, 10);
-        assert!(result.is_some());
-        let (min_x, min_y, _max_x, _max_y) = result.unwrap();
-        assert!(min_x <= 10);
-        assert!(min_y <= 10);
+    fn test_ink_round_trip_keeps_every_stroke_and_writes_appearance() {
+        let mut store = DocumentStore::new(create_render_cache(10, 0));
+        let path = fixture_path();
+        let doc_id = DocumentId(1);
+        store
+            .open_document(path.to_str().unwrap(), None, doc_id)
+            .unwrap();
+        let strokes = vec![
+            vec![(0.0, 0.0), (40.0, 10.0)],
+            vec![(5.0, 30.0), (20.0, 50.0)],
+        ];
+        let ink = Annotation {
+            id: crate::models::next_annotation_id(),
+            page: 0,
+            x: 100.0,
+            y: 120.0,
+            width: 40.0,
+            height: 50.0,
+            style: AnnotationStyle::Ink {
+                color: "#FF0000".to_string(),
+                thickness: 2.0,
+                strokes: strokes.clone(),
+            },
+        };
+        let output = temp_path(".pdf");
+        let output = output.to_str().unwrap().to_string();
+        store
+            .save_annotations(doc_id, &[ink], Some(output.clone()))
+            .unwrap();
+
+        let doc = Document::load(&output).unwrap();
+        let has_appearance = doc.objects.values().any(|o| {
+            o.as_dict().is_ok_and(|d| {
+                d.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Ink".as_slice())
+                    && d.get(b"AP").and_then(Object::as_dict).is_ok()
+            })
+        });
+        assert!(has_appearance);
+
+        let loaded = store.load_annotations(&output).unwrap();
+        let ink = loaded
+            .iter()
+            .find(|a| matches!(a.style, AnnotationStyle::Ink { .. }))
+            .unwrap();
+        assert!((ink.x - 100.0).abs() < 0.01 && (ink.y - 120.0).abs() < 0.01);
+        let AnnotationStyle::Ink {
+            strokes: loaded_strokes,
+            ..
+        } = &ink.style
+        else {
+            unreachable!()
+        };
+        assert_eq!(loaded_strokes.len(), strokes.len());
+        for (loaded, expected) in loaded_strokes
+            .iter()
+            .flatten()
+            .zip(strokes.iter().flatten())
+        {
+            assert!((loaded.0 - expected.0).abs() < 0.01);
+            assert!((loaded.1 - expected.1).abs() < 0.01);
+        }
     }
 
     #[test]
-    fn test_create_render_cache_defaults() {
-        let cache = create_render_cache(10, 0);
-        assert!(
-            cache
-                .get(&RenderKey {
-                    doc_id: DocumentId(1),
-                    page_num: 0,
-                    scale: 100,
-                    auto_crop: false,
-                    quality: RenderQuality::Medium,
-                })
-                .is_none()
+    fn test_render_cache_keys_on_rotation() {
+        let mut store = DocumentStore::new(create_render_cache(10, 0));
+        let path = fixture_path();
+        let doc_id = DocumentId(1);
+        store
+            .open_document(path.to_str().unwrap(), None, doc_id)
+            .unwrap();
+        let options = |rotation| RenderOptions {
+            scale: 1.0,
+            rotation,
+            filter: RenderFilter::None,
+            auto_crop: false,
+            quality: RenderQuality::Medium,
+        };
+        let upright = store.render_page(doc_id, 0, options(0)).unwrap();
+        assert_ne!(upright.width, upright.height);
+        let turned = store.render_page(doc_id, 0, options(90)).unwrap();
+        assert_eq!(
+            (turned.width, turned.height),
+            (upright.height, upright.width)
+        );
+        let thumb = store.render_thumbnail(doc_id, 0, options(0)).unwrap();
+        let thumb_turned = store.render_thumbnail(doc_id, 0, options(90)).unwrap();
+        assert_eq!(
+            (thumb_turned.width, thumb_turned.height),
+            (thumb.height, thumb.width)
         );
     }
 
     #[test]
-    fn test_render_options_default() {
+    fn test_display_list_replay_matches_direct_render() {
+        let mut store = DocumentStore::new(create_render_cache(10, 0));
+        let path = fixture_path();
+        let doc_id = DocumentId(1);
+        store
+            .open_document(path.to_str().unwrap(), None, doc_id)
+            .unwrap();
         let options = RenderOptions {
             scale: 1.0,
             rotation: 0,
             filter: RenderFilter::None,
             auto_crop: false,
-            quality: RenderQuality::Medium,
+            quality: RenderQuality::High,
         };
-        assert_eq!(options.scale, 1.0);
-        assert_eq!(options.rotation, 0);
+        let direct = store.render_page(doc_id, 0, options).unwrap();
+
+        let list = store.run_page_to_display_list(doc_id, 0, 0).unwrap();
+        let zoomed = list.render(2.0).unwrap();
+        assert!(zoomed.width.abs_diff(direct.width * 2) <= 1);
+        assert!(zoomed.height.abs_diff(direct.height * 2) <= 1);
+
+        let replayed = list.render(1.0).unwrap();
+        assert_eq!(
+            (replayed.width, replayed.height),
+            (direct.width, direct.height)
+        );
+        let differing = direct
+            .data
+            .iter()
+            .zip(replayed.data.iter())
+            .filter(|(a, b)| a.abs_diff(**b) > 2)
+            .count();
+        assert_eq!(differing, 0);
+
+        store.close_document(doc_id);
+        assert!(store.display_lists.is_empty());
     }
 
     #[test]
-    fn test_render_quality_serialization() {
-        let json_low = serde_json::to_string(&RenderQuality::Low).unwrap();
-        let json_medium = serde_json::to_string(&RenderQuality::Medium).unwrap();
-        let json_high = serde_json::to_string(&RenderQuality::High).unwrap();
-        assert_eq!(json_low, "\"Low\"");
-        assert_eq!(json_medium, "\"Medium\"");
-        assert_eq!(json_high, "\"High\"");
+    fn test_open_cbz_pages_in_natural_order() {
+        let png = |width, height| {
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            image::RgbImage::new(width, height)
+                .write_to(&mut bytes, image::ImageFormat::Png)
+                .unwrap();
+            bytes.into_inner()
+        };
+        let (first, second, third) = (png(100, 150), png(200, 120), png(80, 60));
+        let zip = crate::archive::write_stored_zip(&[
+            ("page10.png", &third),
+            ("page2.png", &second),
+            ("page1.png", &first),
+            ("notes.txt", b"not a page"),
+        ]);
+        let path = temp_path(".cbz");
+        std::fs::write(&path, zip).unwrap();
+
+        let mut store = DocumentStore::new(create_render_cache(10, 0));
+        let result = store
+            .open_document(path.to_str().unwrap(), None, DocumentId(1))
+            .unwrap();
+        assert_eq!(result.page_count, 3);
+        assert_eq!(result.page_heights, [150.0, 120.0, 60.0]);
+        assert!((result.max_width - 200.0).abs() < f32::EPSILON);
     }
 
     #[test]
-    fn test_render_filter_serialization() {
-        let json_none = serde_json::to_string(&RenderFilter::None).unwrap();
-        let json_grayscale = serde_json::to_string(&RenderFilter::Grayscale).unwrap();
-        let json_inverted = serde_json::to_string(&RenderFilter::Inverted).unwrap();
-        assert_eq!(json_none, "\"None\"");
-        assert_eq!(json_grayscale, "\"Grayscale\"");
-        assert_eq!(json_inverted, "\"Inverted\"");
+    fn test_epub_page_count_follows_font_size() {
+        let path = temp_path(".epub");
+        std::fs::write(&path, crate::epub::sample_epub()).unwrap();
+        let mut store = DocumentStore::new(create_render_cache(10, 0));
+        let doc_id = DocumentId(1);
+        let opened = store
+            .open_document(path.to_str().unwrap(), None, doc_id)
+            .unwrap();
+        assert!(opened.page_count >= 2);
+
+        assert_eq!(store.reflow_layout(doc_id), Some(ReflowLayout::default()));
+        let layout = ReflowLayout {
+            em: crate::epub::DEFAULT_EM * 2.0,
+            ..ReflowLayout::default()
+        };
+        let larger = store.layout_document(doc_id, layout).unwrap();
+        assert!(larger.page_count > opened.page_count);
+        assert_eq!(store.reflow_layout(doc_id), Some(layout));
+
+        let pdf = fixture_path();
+        store
+            .open_document(pdf.to_str().unwrap(), None, doc_id)
+            .unwrap();
+        assert!(store.layout_document(doc_id, layout).is_err());
+        assert_eq!(store.reflow_layout(doc_id), None);
     }
 
     #[test]
-    fn test_render_quality_deserialization() {
-        let low: RenderQuality = serde_json::from_str("\"Low\"").unwrap();
-        let medium: RenderQuality = serde_json::from_str("\"Medium\"").unwrap();
-        let high: RenderQuality = serde_json::from_str("\"High\"").unwrap();
-        assert_eq!(low, RenderQuality::Low);
-        assert_eq!(medium, RenderQuality::Medium);
-        assert_eq!(high, RenderQuality::High);
+    fn test_open_and_render_xps() {
+        let path = temp_path(".xps");
+        std::fs::write(&path, crate::xps::sample_xps()).unwrap();
+        let mut store = DocumentStore::new(create_render_cache(10, 0));
+        let doc_id = DocumentId(1);
+        let opened = store
+            .open_document(path.to_str().unwrap(), None, doc_id)
+            .unwrap();
+        assert_eq!(opened.page_count, 1);
+        assert_eq!(opened.page_heights, [792.0]);
+
+        let options = RenderOptions {
+            scale: 1.0,
+            rotation: 0,
+            filter: RenderFilter::None,
+            auto_crop: false,
+            quality: RenderQuality::High,
+        };
+        let page = store.render_page(doc_id, 0, options).unwrap();
+        let pixel = |x: u32, y: u32| {
+            let i = ((y * page.width + x) * 4) as usize;
+            &page.data[i..i + 3]
+        };
+        // The square spans 96..296 XPS units, i.e. 72..222 pt from the
+        // top left.
+        assert_eq!(pixel(150, 150), [255, 0, 0]);
+        assert_eq!(pixel(30, 30), [255, 255, 255]);
     }
 
     #[test]
-    fn test_render_filter_deserialization() {
-        let none: RenderFilter = serde_json::from_str("\"None\"").unwrap();
-        let grayscale: RenderFilter = serde_json::from_str("\"Grayscale\"").unwrap();
-        let inverted: RenderFilter = serde_json::from_str("\"Inverted\"").unwrap();
-        assert_eq!(none, RenderFilter::None);
-        assert_eq!(grayscale, RenderFilter::Grayscale);
-        assert_eq!(inverted, RenderFilter::Inverted);
+    fn test_render_jbig2_generic_region_with_globals() {
+        // The fixture is an arithmetic coded, template 0 generic region
+        // with typical prediction: a ring, and two identical striped rows
+        // at the bottom.
+        let black = |x: u32, y: u32| {
+            let (dx, dy) = (x as i32 - 18, y as i32 - 9);
+            (30..=80).contains(&(dx * dx + dy * dy)) || (y >= 19 && x % 4 < 2)
+        };
+        let region = std::fs::read(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/generic_region.jb2"),
+        )
+        .unwrap();
+        // An ASCII comment extension segment with no entries, which a
+        // decoder must read and skip before the image's own segments.
+        let globals = [0, 0, 0, 0, 62, 0, 0, 0, 0, 0, 6, 0x20, 0, 0, 0, 0, 0];
+
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let globals_id = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::new(),
+            globals.to_vec(),
+        ));
+        let image_id = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"XObject".to_vec())),
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(37)),
+                ("Height", Object::Integer(21)),
+                ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+                ("BitsPerComponent", Object::Integer(1)),
+                ("Filter", Object::Name(b"JBIG2Decode".to_vec())),
+                (
+                    "DecodeParms",
+                    Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                        "JBIG2Globals",
+                        Object::Reference(globals_id),
+                    )])),
+                ),
+            ]),
+            region,
+        ));
+        let content_id = doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::new(),
+            b"q 37 0 0 21 0 0 cm /Im0 Do Q".to_vec(),
+        ));
+        let page_id = doc.add_object(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(pages_id)),
+            (
+                "MediaBox",
+                Object::Array(vec![0.into(), 0.into(), 37.into(), 21.into()]),
+            ),
+            ("Contents", Object::Reference(content_id)),
+            (
+                "Resources",
+                Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                    "XObject",
+                    Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                        "Im0",
+                        Object::Reference(image_id),
+                    )])),
+                )])),
+            ),
+        ]));
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Kids", Object::Array(vec![Object::Reference(page_id)])),
+                ("Count", Object::Integer(1)),
+            ])),
+        );
+        let catalog_id = doc.add_object(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+        ]));
+        doc.trailer.set("Root", catalog_id);
+        let path = temp_path(".pdf");
+        doc.save(&path).unwrap();
+
+        let mut store = DocumentStore::new(create_render_cache(10, 0));
+        let doc_id = DocumentId(1);
+        store
+            .open_document(path.to_str().unwrap(), None, doc_id)
+            .unwrap();
+        let options = RenderOptions {
+            scale: 4.0,
+            rotation: 0,
+            filter: RenderFilter::None,
+            auto_crop: false,
+            quality: RenderQuality::High,
+        };
+        let page = store.render_page(doc_id, 0, options).unwrap();
+        assert_eq!((page.width, page.height), (148, 84));
+        // Sample the centre of each image pixel, clear of smoothing at
+        // the pixel edges.
+        for y in 0..21 {
+            for x in 0..37 {
+                let i = (((y * 4 + 2) * page.width + x * 4 + 2) * 4) as usize;
+                assert_eq!(page.data[i] < 128, black(x, y), "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn test_closing_releases_live_resources() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_document.pdf");
+        let cache = create_render_cache(10, 0);
+        let mut store = DocumentStore::new(cache.clone());
+        let doc_id = DocumentId(1);
+        store.open_document(path, None, doc_id).unwrap();
+        let options = RenderOptions {
+            scale: 0.5,
+            rotation: 0,
+            filter: RenderFilter::None,
+            auto_crop: false,
+            quality: RenderQuality::High,
+        };
+        store.render_page(doc_id, 0, options).unwrap();
+        let live = store.live_resources();
+        assert_eq!((live.documents, live.paths, live.cached_renders), (1, 1, 1));
+        assert!(!live.is_empty());
+
+        store.close_document(doc_id);
+        assert_eq!(cache.document_len(doc_id), 0);
+        assert_eq!(store.live_resources(), LiveResources::default());
+        assert!(store.live_resources().is_empty());
     }
 
     #[test]
     fn test_crash_investigation() {
         let handle = std::thread::spawn(move || {
             let mut store = DocumentStore::new(create_render_cache(10, 0));
-            let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-            path.push("tests");
-            path.push("test_document.pdf");
+            let path = fixture_path();
             let path_str = path.to_str().unwrap();
             let doc_id = DocumentId(1);
             let open_res = store.open_document(path_str, None, doc_id).unwrap();
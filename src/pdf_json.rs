@@ -0,0 +1,419 @@
+//! QPDF-compatible JSON (version 2) view of a PDF's object graph.
+//!
+//! Every indirect object becomes an `"obj:N G R"` entry holding either a
+//! `"value"` or a `"stream"`, alongside the `"trailer"`. Values use QPDF's
+//! encoding: names as `"/Name"`, references as `"N G R"`, text strings as
+//! `"u:..."` and binary strings as `"b:<hex>"`. Stream data is base64 and
+//! can be kept raw, decoded, or left out entirely.
+
+use crate::models::{PdfError, PdfResult};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use std::collections::BTreeMap;
+
+/// How stream data is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonDecodeLevel {
+    /// Stream bytes exactly as stored, with their `/Filter` kept.
+    Raw,
+    /// Filters lopdf can undo are removed and the plain bytes written.
+    /// Streams it can't decode (e.g. images) stay raw.
+    #[default]
+    Decoded,
+    /// Only stream dictionaries are written. Such JSON can't be turned
+    /// back into a PDF.
+    Omitted,
+}
+
+impl JsonDecodeLevel {
+    /// The name QPDF uses for the equivalent `--decode-level`.
+    const fn qpdf_name(self) -> &'static str {
+        match self {
+            Self::Raw | Self::Omitted => "none",
+            Self::Decoded => "generalized",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonConfig {
+    pub decode_level: JsonDecodeLevel,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonParameters {
+    pub decodelevel: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonHeader {
+    pub jsonversion: u32,
+    pub pdfversion: String,
+    pub maxobjectid: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonStream {
+    pub dict: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonObject {
+    Value(Value),
+    Stream(JsonStream),
+}
+
+/// Top-level QPDF JSON document. `qpdf` is the two-element array QPDF
+/// writes: a header, then the object table keyed by `"obj:N G R"` plus
+/// `"trailer"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonPdf {
+    pub version: u32,
+    pub parameters: JsonParameters,
+    pub qpdf: (JsonHeader, BTreeMap<String, JsonObject>),
+}
+
+/// Trailer keys that describe document state rather than the old xref.
+const TRAILER_KEYS: [&[u8]; 4] = [b"Root", b"Info", b"ID", b"Encrypt"];
+
+fn is_text(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .all(|&b| (0x20..0x7f).contains(&b) || matches!(b, b'\n' | b'\r' | b'\t'))
+}
+
+fn string_to_json(bytes: &[u8]) -> String {
+    if let [0xFE, 0xFF, rest @ ..] = bytes
+        && rest.len() % 2 == 0
+    {
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        if let Ok(text) = String::from_utf16(&units) {
+            // Only claim text if writing it back gives the same bytes.
+            if !text.is_ascii() {
+                return format!("u:{text}");
+            }
+        }
+    } else if is_text(bytes) {
+        return format!("u:{}", String::from_utf8_lossy(bytes));
+    }
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!("b:{hex}")
+}
+
+fn json_to_string(text: &str) -> Vec<u8> {
+    if text.is_ascii() {
+        text.as_bytes().to_vec()
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        bytes
+    }
+}
+
+fn object_to_json(obj: &Object) -> Value {
+    match obj {
+        Object::Null => Value::Null,
+        Object::Boolean(b) => Value::Bool(*b),
+        Object::Integer(i) => Value::from(*i),
+        // Go through the shortest f32 representation so 0.1 stays 0.1.
+        Object::Real(r) => r
+            .to_string()
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map_or(Value::from(0), Value::Number),
+        Object::Name(name) => Value::String(format!("/{}", String::from_utf8_lossy(name))),
+        Object::String(bytes, _) => Value::String(string_to_json(bytes)),
+        Object::Array(items) => Value::Array(items.iter().map(object_to_json).collect()),
+        Object::Dictionary(dict) => dict_to_json(dict),
+        // Streams are only valid as indirect objects; inline they are
+        // reduced to their dictionary.
+        Object::Stream(stream) => dict_to_json(&stream.dict),
+        Object::Reference((id, generation)) => Value::String(format!("{id} {generation} R")),
+    }
+}
+
+fn dict_to_json(dict: &Dictionary) -> Value {
+    Value::Object(
+        dict.iter()
+            .map(|(k, v)| {
+                (
+                    format!("/{}", String::from_utf8_lossy(k)),
+                    object_to_json(v),
+                )
+            })
+            .collect(),
+    )
+}
+
+fn parse_reference(text: &str) -> Option<ObjectId> {
+    let mut parts = text.split(' ');
+    let id = parts.next()?.parse().ok()?;
+    let generation = parts.next()?.parse().ok()?;
+    (parts.next()? == "R" && parts.next().is_none()).then_some((id, generation))
+}
+
+fn json_to_object(value: &Value) -> PdfResult<Object> {
+    Ok(match value {
+        Value::Null => Object::Null,
+        Value::Bool(b) => Object::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Object::Integer(i),
+            None => Object::Real(n.as_f64().unwrap_or_default() as f32),
+        },
+        Value::String(s) => {
+            if let Some(name) = s.strip_prefix('/') {
+                Object::Name(name.as_bytes().to_vec())
+            } else if let Some(text) = s.strip_prefix("u:") {
+                Object::String(json_to_string(text), StringFormat::Literal)
+            } else if let Some(hex) = s.strip_prefix("b:") {
+                let bytes = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| {
+                        hex.get(i..i + 2)
+                            .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    })
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(|| PdfError::from(format!("Invalid binary string: {s}")))?;
+                Object::String(bytes, StringFormat::Hexadecimal)
+            } else if let Some(id) = parse_reference(s) {
+                Object::Reference(id)
+            } else {
+                return Err(PdfError::from(format!("Unrecognised JSON value: {s}")));
+            }
+        }
+        Value::Array(items) => {
+            Object::Array(items.iter().map(json_to_object).collect::<PdfResult<_>>()?)
+        }
+        Value::Object(_) => Object::Dictionary(json_to_dict(value)?),
+    })
+}
+
+fn json_to_dict(value: &Value) -> PdfResult<Dictionary> {
+    let Value::Object(map) = value else {
+        return Err(PdfError::from("Expected a JSON object for a dictionary"));
+    };
+    let mut dict = Dictionary::new();
+    for (key, value) in map {
+        let key = key
+            .strip_prefix('/')
+            .ok_or_else(|| PdfError::from(format!("Dictionary key {key} is not a name")))?;
+        dict.set(key.as_bytes().to_vec(), json_to_object(value)?);
+    }
+    Ok(dict)
+}
+
+fn stream_to_json(stream: &Stream, level: JsonDecodeLevel) -> JsonStream {
+    let mut dict = stream.dict.clone();
+    let data = match level {
+        JsonDecodeLevel::Omitted => None,
+        JsonDecodeLevel::Raw => Some(stream.content.clone()),
+        JsonDecodeLevel::Decoded => match stream.decompressed_content() {
+            Ok(plain) if dict.has(b"Filter") => {
+                dict.remove(b"Filter");
+                dict.remove(b"DecodeParms");
+                Some(plain)
+            }
+            _ => Some(stream.content.clone()),
+        },
+    };
+    // Length is implied by the data and recomputed on write.
+    dict.remove(b"Length");
+    JsonStream {
+        dict: dict_to_json(&dict),
+        data: data.map(|d| BASE64.encode(d)),
+    }
+}
+
+/// Serialize every indirect object and the trailer of `doc`.
+pub fn pdf_to_json(doc: &Document, config: JsonConfig) -> JsonPdf {
+    let mut objects = BTreeMap::new();
+    for (&(id, generation), obj) in &doc.objects {
+        let entry = match obj {
+            Object::Stream(stream) => {
+                // Cross-reference and object streams are file layout, which
+                // the writer regenerates.
+                if matches!(
+                    stream.dict.get(b"Type"),
+                    Ok(Object::Name(t)) if t == b"XRef" || t == b"ObjStm"
+                ) {
+                    continue;
+                }
+                JsonObject::Stream(stream_to_json(stream, config.decode_level))
+            }
+            other => JsonObject::Value(object_to_json(other)),
+        };
+        objects.insert(format!("obj:{id} {generation} R"), entry);
+    }
+    let trailer = Dictionary::from_iter(
+        doc.trailer
+            .iter()
+            .filter(|(k, _)| TRAILER_KEYS.contains(&k.as_slice()))
+            .map(|(k, v)| (k.clone(), v.clone())),
+    );
+    objects.insert(
+        "trailer".to_string(),
+        JsonObject::Value(dict_to_json(&trailer)),
+    );
+
+    JsonPdf {
+        version: 2,
+        parameters: JsonParameters {
+            decodelevel: config.decode_level.qpdf_name().to_string(),
+        },
+        qpdf: (
+            JsonHeader {
+                jsonversion: 2,
+                pdfversion: doc.version.clone(),
+                maxobjectid: doc.objects.keys().map(|(id, _)| *id).max().unwrap_or(0),
+            },
+            objects,
+        ),
+    }
+}
+
+/// Rebuild a document from QPDF JSON. Fails if any stream's data was
+/// omitted.
+pub fn json_to_pdf(json: &JsonPdf) -> PdfResult<Document> {
+    let (header, objects) = &json.qpdf;
+    let mut doc = Document::with_version(header.pdfversion.as_str());
+    for (key, entry) in objects {
+        if key == "trailer" {
+            let JsonObject::Value(value) = entry else {
+                return Err(PdfError::from("Trailer must be a value"));
+            };
+            doc.trailer = json_to_dict(value)?;
+            continue;
+        }
+        let id = key
+            .strip_prefix("obj:")
+            .and_then(parse_reference)
+            .ok_or_else(|| PdfError::from(format!("Invalid object key: {key}")))?;
+        let obj = match entry {
+            JsonObject::Value(value) => json_to_object(value)?,
+            JsonObject::Stream(stream) => {
+                let data = stream
+                    .data
+                    .as_deref()
+                    .ok_or_else(|| PdfError::from(format!("Stream data for {key} was omitted")))?;
+                let data = BASE64
+                    .decode(data)
+                    .map_err(|e| PdfError::from(format!("Invalid stream data for {key}: {e}")))?;
+                Object::Stream(Stream::new(json_to_dict(&stream.dict)?, data))
+            }
+        };
+        doc.max_id = doc.max_id.max(id.0);
+        doc.objects.insert(id, obj);
+    }
+    if doc.trailer.get(b"Root").is_err() {
+        return Err(PdfError::from("Trailer has no /Root"));
+    }
+    Ok(doc)
+}
+
+/// Write the QPDF JSON for the PDF at `input_path` to `output_path`.
+pub fn export_json(input_path: &str, config: JsonConfig, output_path: &str) -> PdfResult<String> {
+    let doc = Document::load(input_path).map_err(|e| PdfError::OpenFailed(e.to_string()))?;
+    let json = serde_json::to_vec_pretty(&pdf_to_json(&doc, config))
+        .map_err(|e| PdfError::from(e.to_string()))?;
+    std::fs::write(output_path, json).map_err(|e| PdfError::IoError(e.to_string()))?;
+    Ok(output_path.to_string())
+}
+
+/// Rebuild a PDF from the QPDF JSON at `input_path`.
+pub fn import_json(input_path: &str, output_path: &str) -> PdfResult<String> {
+    let bytes = std::fs::read(input_path).map_err(|e| PdfError::IoError(e.to_string()))?;
+    let json: JsonPdf = serde_json::from_slice(&bytes)
+        .map_err(|e| PdfError::from(format!("Not QPDF JSON: {e}")))?;
+    let mut doc = json_to_pdf(&json)?;
+    doc.save(output_path)
+        .map_err(|e| PdfError::IoError(e.to_string()))?;
+    Ok(output_path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Document {
+        Document::load(
+            std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_document.pdf"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_value_encoding_round_trips() {
+        let obj = Object::Array(vec![
+            Object::Name(b"Type".to_vec()),
+            Object::Reference((12, 0)),
+            Object::Real(0.1),
+            Object::Integer(-3),
+            Object::String(b"Hello".to_vec(), StringFormat::Literal),
+            Object::String(vec![0xFE, 0xFF, 0x00, 0xE9], StringFormat::Hexadecimal),
+            Object::String(vec![0x00, 0x9F], StringFormat::Hexadecimal),
+        ]);
+        let json = object_to_json(&obj);
+        assert_eq!(
+            json,
+            serde_json::json!(["/Type", "12 0 R", 0.1, -3, "u:Hello", "u:é", "b:009f"])
+        );
+        let Object::Array(back) = json_to_object(&json).unwrap() else {
+            panic!("expected an array");
+        };
+        assert_eq!(back[1], Object::Reference((12, 0)));
+        assert_eq!(back[2], Object::Real(0.1));
+        assert_eq!(back[5].as_str().unwrap(), [0xFE, 0xFF, 0x00, 0xE9]);
+        assert_eq!(back[6].as_str().unwrap(), [0x00, 0x9F]);
+    }
+
+    #[test]
+    fn test_round_trip_preserves_pages() {
+        let doc = fixture();
+        for level in [JsonDecodeLevel::Raw, JsonDecodeLevel::Decoded] {
+            let json = pdf_to_json(
+                &doc,
+                JsonConfig {
+                    decode_level: level,
+                },
+            );
+            let text = serde_json::to_string(&json).unwrap();
+            let parsed: JsonPdf = serde_json::from_str(&text).unwrap();
+            assert_eq!(parsed.qpdf.0.pdfversion, doc.version);
+            assert!(parsed.qpdf.1.contains_key("trailer"));
+
+            let mut rebuilt = json_to_pdf(&parsed).unwrap();
+            let mut bytes = Vec::new();
+            rebuilt.save_to(&mut bytes).unwrap();
+            let reopened = Document::load_mem(&bytes).unwrap();
+            assert_eq!(reopened.get_pages().len(), doc.get_pages().len());
+        }
+    }
+
+    #[test]
+    fn test_omitted_stream_data_cannot_rebuild() {
+        let json = pdf_to_json(
+            &fixture(),
+            JsonConfig {
+                decode_level: JsonDecodeLevel::Omitted,
+            },
+        );
+        assert!(
+            json.qpdf
+                .1
+                .values()
+                .any(|o| matches!(o, JsonObject::Stream(s) if s.data.is_none()))
+        );
+        assert!(json_to_pdf(&json).is_err());
+    }
+}
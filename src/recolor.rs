@@ -0,0 +1,766 @@
+//! Conversion of a whole document to grayscale, for black-and-white
+//! printing.
+//!
+//! Colours are taken to sRGB with [`convert_color`] and then to gray with
+//! the NTSC weights, so every colour space the renderer understands is
+//! handled the same way. Three kinds of content are rewritten:
+//!
+//! - colour operators in page, form and tiling pattern content streams,
+//!   which become `g`/`G`;
+//! - images, whose samples are converted and stored as 8-bit `/DeviceGray`
+//!   (indexed images only have their palette converted);
+//! - axial and radial shadings, whose colour function is replaced by a
+//!   gray one sampled from it.
+//!
+//! Inline images, uncoloured tiling patterns, function-based and mesh
+//! shadings, and images with a colour-key `/Mask` are left as they are.
+
+use crate::colorspace::{PdfColorspace, convert_color};
+use crate::content_stream::{TokenType, Tokenizer};
+use crate::filters::{self, FilterType, PredictorParams};
+use crate::function::PdfFunction;
+use crate::models::{ColorSpace, PdfError, PdfResult};
+use crate::pdf_engine::{page_resources, resolve};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::HashMap;
+
+/// Linear pieces in the gray function that replaces a shading's, when the
+/// gray ramp isn't a straight line.
+const SHADING_SEGMENTS: usize = 64;
+
+fn luma([r, g, b]: [f32; 3]) -> f32 {
+    (0.3 * r + 0.59 * g + 0.11 * b).clamp(0.0, 1.0)
+}
+
+fn gray_of(colorspace: &PdfColorspace, values: &[f32]) -> f32 {
+    luma(convert_color(colorspace, values))
+}
+
+/// A gray level as a content stream operand, without trailing zeros.
+fn gray_operand(gray: f32) -> String {
+    let text = format!("{gray:.3}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// The colour a space starts out with after `cs`: black for the device
+/// spaces, full tint for spot colours.
+fn initial_values(colorspace: &PdfColorspace) -> Vec<f32> {
+    match colorspace {
+        PdfColorspace::Device(ColorSpace::DeviceCmyk) => vec![0.0, 0.0, 0.0, 1.0],
+        PdfColorspace::Separation { colorants, .. } => vec![1.0; colorants.len()],
+        other => vec![0.0; other.components()],
+    }
+}
+
+/// What the colour operands of `sc`/`scn` mean under the current space.
+#[derive(Debug, Clone)]
+enum Paint {
+    Color(PdfColorspace),
+    /// Patterns are recoloured where they are defined.
+    Pattern,
+    /// A space we can't convert; its operators are kept.
+    Unknown,
+}
+
+fn is_pattern_space(obj: &Object) -> bool {
+    match obj {
+        Object::Name(name) => name == b"Pattern",
+        Object::Array(items) => items
+            .first()
+            .and_then(|o| o.as_name().ok())
+            .is_some_and(|name| name == b"Pattern"),
+        _ => false,
+    }
+}
+
+/// The space named by a `cs`/`CS` operand, looked up in `resources`.
+fn lookup_space(doc: &Document, resources: &Dictionary, operand: &[u8]) -> Paint {
+    let Some(name) = operand.strip_prefix(b"/") else {
+        return Paint::Unknown;
+    };
+    if name == b"Pattern" {
+        return Paint::Pattern;
+    }
+    if let Some(device) = ColorSpace::from_name(name) {
+        return Paint::Color(PdfColorspace::Device(device));
+    }
+    let Some(space) = resources
+        .get(b"ColorSpace")
+        .ok()
+        .and_then(|spaces| resolve(doc, spaces).as_dict().ok())
+        .and_then(|spaces| spaces.get(name).ok())
+        .map(|space| resolve(doc, space))
+    else {
+        return Paint::Unknown;
+    };
+    if is_pattern_space(space) {
+        Paint::Pattern
+    } else {
+        PdfColorspace::from_object(doc, space).map_or(Paint::Unknown, Paint::Color)
+    }
+}
+
+/// Rewrite the colour operators of a content stream to gray. Comments are
+/// dropped; everything else is re-emitted one operator per line.
+fn recolor_content(doc: &Document, content: &[u8], resources: &Dictionary) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len());
+    let mut tokens = Tokenizer::new(content);
+    let mut operands: Vec<&[u8]> = Vec::new();
+    let gray = || Paint::Color(PdfColorspace::Device(ColorSpace::DeviceGray));
+    let (mut fill, mut stroke) = (gray(), gray());
+    let mut saved: Vec<(Paint, Paint)> = Vec::new();
+
+    while let Some(token) = tokens.next_token() {
+        match token.kind {
+            TokenType::Comment => continue,
+            TokenType::Operator => {}
+            _ => {
+                operands.push(token.text);
+                continue;
+            }
+        }
+        let op = token.text;
+        let numbers: Vec<f32> = operands
+            .iter()
+            .filter_map(|o| std::str::from_utf8(o).ok()?.parse().ok())
+            .collect();
+        let stroking = op.first().is_some_and(u8::is_ascii_uppercase);
+        let replacement = match op {
+            b"q" => {
+                saved.push((fill.clone(), stroke.clone()));
+                None
+            }
+            b"Q" => {
+                if let Some((f, s)) = saved.pop() {
+                    (fill, stroke) = (f, s);
+                }
+                None
+            }
+            b"rg" | b"RG" | b"k" | b"K" => {
+                let device = if op.eq_ignore_ascii_case(b"rg") {
+                    ColorSpace::DeviceRgb
+                } else {
+                    ColorSpace::DeviceCmyk
+                };
+                *if stroking { &mut stroke } else { &mut fill } = gray();
+                Some(gray_of(&PdfColorspace::Device(device), &numbers))
+            }
+            b"g" | b"G" => {
+                *if stroking { &mut stroke } else { &mut fill } = gray();
+                None
+            }
+            b"cs" | b"CS" => {
+                let paint = operands
+                    .last()
+                    .map_or(Paint::Unknown, |name| lookup_space(doc, resources, name));
+                let level = match &paint {
+                    Paint::Color(space) => Some(gray_of(space, &initial_values(space))),
+                    Paint::Pattern | Paint::Unknown => None,
+                };
+                // The output is gray from here on, but later `sc` operands
+                // are still in the original space.
+                *if stroking { &mut stroke } else { &mut fill } = paint;
+                level
+            }
+            b"sc" | b"scn" | b"SC" | b"SCN" => match if stroking { &stroke } else { &fill } {
+                Paint::Color(space) => Some(gray_of(space, &numbers)),
+                Paint::Pattern | Paint::Unknown => None,
+            },
+            _ => None,
+        };
+
+        if let Some(level) = replacement {
+            out.extend_from_slice(gray_operand(level).as_bytes());
+            out.extend_from_slice(if stroking { b" G\n" } else { b" g\n" });
+        } else {
+            for operand in &operands {
+                out.extend_from_slice(operand);
+                out.push(b' ');
+            }
+            out.extend_from_slice(op);
+            if op == b"ID" {
+                out.push(b' ');
+                out.extend_from_slice(tokens.inline_image_data());
+            }
+            out.push(b'\n');
+        }
+        operands.clear();
+    }
+    out
+}
+
+/// The `/Filter` names of a stream paired with their `/DecodeParms`.
+fn stream_filters(doc: &Document, dict: &Dictionary) -> Vec<(Vec<u8>, Dictionary)> {
+    let names: Vec<Vec<u8>> = match dict.get(b"Filter").map(|f| resolve(doc, f)) {
+        Ok(Object::Name(name)) => vec![name.clone()],
+        Ok(Object::Array(names)) => names
+            .iter()
+            .filter_map(|n| n.as_name().ok())
+            .map(<[u8]>::to_vec)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let params: Vec<Dictionary> = match dict.get(b"DecodeParms").map(|p| resolve(doc, p)) {
+        Ok(Object::Dictionary(params)) => vec![params.clone()],
+        Ok(Object::Array(params)) => params
+            .iter()
+            .map(|p| resolve(doc, p).as_dict().cloned().unwrap_or_default())
+            .collect(),
+        _ => Vec::new(),
+    };
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| (name, params.get(i).cloned().unwrap_or_default()))
+        .collect()
+}
+
+/// Decode an image's samples as 8-bit components in a device space with
+/// `components` channels. Returns the samples and, for JPEG 2000 images,
+/// the space the codestream declared.
+pub(crate) fn image_samples(
+    doc: &Document,
+    stream: &Stream,
+    components: usize,
+) -> PdfResult<(Vec<u8>, Option<ColorSpace>)> {
+    let mut data = stream.content.clone();
+    for (name, params) in stream_filters(doc, &stream.dict) {
+        if let Some(filter) = FilterType::from_name(&name) {
+            data = filters::decode_with_params(
+                &data,
+                filter,
+                &PredictorParams::from_dictionary(&params),
+            )?;
+            continue;
+        }
+        return match name.as_slice() {
+            b"DCTDecode" | b"DCT" => {
+                let space = match components {
+                    1 => ColorSpace::DeviceGray,
+                    4 => ColorSpace::DeviceCmyk,
+                    _ => ColorSpace::DeviceRgb,
+                };
+                Ok((crate::dct::decode_dct(&data, space)?.samples, None))
+            }
+            b"JPXDecode" => {
+                let pixmap = crate::jpx::decode_jpx(&data)?;
+                Ok((pixmap.samples, Some(pixmap.colorspace)))
+            }
+            other => Err(PdfError::from(format!(
+                "Can't decode {} images",
+                String::from_utf8_lossy(other)
+            ))),
+        };
+    }
+    Ok((data, None))
+}
+
+/// An `/Indexed` space with its palette converted to gray.
+fn gray_indexed(doc: &Document, items: &[Object]) -> Option<Object> {
+    let base = PdfColorspace::from_object(doc, items.get(1)?).ok()?;
+    let hival = items.get(2)?.as_i64().ok()?;
+    let lookup = match resolve(doc, items.get(3)?) {
+        Object::String(bytes, _) => bytes.clone(),
+        Object::Stream(stream) if stream.dict.has(b"Filter") => {
+            stream.decompressed_content().ok()?
+        }
+        Object::Stream(stream) => stream.content.clone(),
+        _ => return None,
+    };
+    let n = base.components();
+    let palette: Vec<u8> = lookup
+        .chunks_exact(n)
+        .map(|entry| {
+            let values: Vec<f32> = entry.iter().map(|&b| f32::from(b) / 255.0).collect();
+            (gray_of(&base, &values) * 255.0).round() as u8
+        })
+        .collect();
+    Some(Object::Array(vec![
+        Object::Name(b"Indexed".to_vec()),
+        Object::Name(b"DeviceGray".to_vec()),
+        Object::Integer(hival),
+        Object::String(palette, lopdf::StringFormat::Hexadecimal),
+    ]))
+}
+
+/// The image converted to 8-bit gray, or `None` if it is gray already or
+/// can't be converted.
+fn recolor_image(doc: &Document, stream: &Stream) -> Option<Stream> {
+    let dict = &stream.dict;
+    if dict
+        .get(b"ImageMask")
+        .and_then(Object::as_bool)
+        .unwrap_or(false)
+    {
+        return None;
+    }
+    let space_obj = dict.get(b"ColorSpace").ok().map(|cs| resolve(doc, cs));
+    if let Some(Object::Array(items)) = space_obj
+        && items.first().and_then(|o| o.as_name().ok()) == Some(b"Indexed".as_slice())
+    {
+        let mut gray = stream.clone();
+        gray.dict.set("ColorSpace", gray_indexed(doc, items)?);
+        return Some(gray);
+    }
+    if matches!(dict.get(b"Mask"), Ok(Object::Array(_))) {
+        tracing::debug!("Leaving colour-keyed image in colour");
+        return None;
+    }
+    if dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() != Some(8) {
+        return None;
+    }
+    let width = usize::try_from(dict.get(b"Width").and_then(Object::as_i64).ok()?).ok()?;
+    let height = usize::try_from(dict.get(b"Height").and_then(Object::as_i64).ok()?).ok()?;
+    let declared = space_obj
+        .map(|space| PdfColorspace::from_object(doc, space))
+        .transpose()
+        .ok()?;
+    let components = declared.as_ref().map_or(3, PdfColorspace::components);
+    let (samples, embedded) = image_samples(doc, stream, components)
+        .inspect_err(|e| tracing::debug!("Leaving image in colour: {e}"))
+        .ok()?;
+    let space = declared.or_else(|| embedded.map(PdfColorspace::Device))?;
+    let n = space.components();
+    let already_gray = n == 1 && !matches!(space, PdfColorspace::Separation { .. });
+    if already_gray || samples.len() < width * height * n {
+        return None;
+    }
+
+    let decode: Vec<f32> = dict
+        .get(b"Decode")
+        .and_then(Object::as_array)
+        .map(|d| d.iter().filter_map(|v| v.as_float().ok()).collect())
+        .ok()
+        .filter(|d: &Vec<f32>| d.len() == 2 * n)
+        .unwrap_or_else(|| match &space {
+            PdfColorspace::Lab { range, .. } => {
+                vec![0.0, 100.0, range[0], range[1], range[2], range[3]]
+            }
+            _ => [0.0, 1.0].repeat(n),
+        });
+    let mut cache: HashMap<&[u8], u8> = HashMap::new();
+    let gray: Vec<u8> = samples[..width * height * n]
+        .chunks_exact(n)
+        .map(|pixel| {
+            *cache.entry(pixel).or_insert_with(|| {
+                let values: Vec<f32> = pixel
+                    .iter()
+                    .zip(decode.chunks_exact(2))
+                    .map(|(&s, d)| d[0] + f32::from(s) / 255.0 * (d[1] - d[0]))
+                    .collect();
+                (gray_of(&space, &values) * 255.0).round() as u8
+            })
+        })
+        .collect();
+
+    let mut dict = dict.clone();
+    dict.set("ColorSpace", Object::Name(b"DeviceGray".to_vec()));
+    dict.set("Filter", Object::Name(FilterType::Flate.name().to_vec()));
+    dict.remove(b"DecodeParms");
+    dict.remove(b"Decode");
+    Some(Stream::new(
+        dict,
+        filters::encode(&gray, FilterType::Flate).ok()?,
+    ))
+}
+
+fn linear_function(domain: (f32, f32), c0: f32, c1: f32) -> Object {
+    Object::Dictionary(Dictionary::from_iter(vec![
+        ("FunctionType", Object::Integer(2)),
+        (
+            "Domain",
+            Object::Array(vec![Object::Real(domain.0), Object::Real(domain.1)]),
+        ),
+        ("C0", Object::Array(vec![Object::Real(c0)])),
+        ("C1", Object::Array(vec![Object::Real(c1)])),
+        ("N", Object::Integer(1)),
+    ]))
+}
+
+/// Convert an axial or radial shading to `/DeviceGray` in place. Returns
+/// whether anything changed.
+fn recolor_shading(doc: &Document, shading: &mut Dictionary) -> bool {
+    let shading_type = shading.get(b"ShadingType").and_then(Object::as_i64);
+    if !matches!(shading_type, Ok(2 | 3)) {
+        return false;
+    }
+    let Some(space) = shading
+        .get(b"ColorSpace")
+        .ok()
+        .and_then(|cs| PdfColorspace::from_object(doc, cs).ok())
+    else {
+        return false;
+    };
+    if space.components() == 1 && !matches!(space, PdfColorspace::Separation { .. }) {
+        return false;
+    }
+    let functions = match shading.get(b"Function").map(|f| resolve(doc, f)) {
+        Ok(Object::Array(items)) => items
+            .iter()
+            .map(|f| PdfFunction::from_object(doc, f))
+            .collect::<PdfResult<Vec<_>>>(),
+        Ok(f) => PdfFunction::from_object(doc, f).map(|f| vec![f]),
+        Err(e) => Err(PdfError::from(e.to_string())),
+    };
+    let Ok(functions) = functions else {
+        return false;
+    };
+    let (t0, t1) = match shading.get(b"Domain").and_then(Object::as_array) {
+        Ok(d) if d.len() == 2 => (
+            d[0].as_float().unwrap_or(0.0),
+            d[1].as_float().unwrap_or(1.0),
+        ),
+        _ => (0.0, 1.0),
+    };
+    let gray_at = |t: f32| {
+        let values: Vec<f32> = functions.iter().flat_map(|f| f.eval(&[t])).collect();
+        gray_of(&space, &values)
+    };
+    let samples: Vec<f32> = (0..=SHADING_SEGMENTS)
+        .map(|i| gray_at(t0 + (t1 - t0) * i as f32 / SHADING_SEGMENTS as f32))
+        .collect();
+    let (first, last) = (samples[0], samples[SHADING_SEGMENTS]);
+    let straight = samples.iter().enumerate().all(|(i, &g)| {
+        let expected = first + (last - first) * i as f32 / SHADING_SEGMENTS as f32;
+        (g - expected).abs() < 1e-3
+    });
+    let function = if straight {
+        linear_function((t0, t1), first, last)
+    } else {
+        let step = (t1 - t0) / SHADING_SEGMENTS as f32;
+        Object::Dictionary(Dictionary::from_iter(vec![
+            ("FunctionType", Object::Integer(3)),
+            (
+                "Domain",
+                Object::Array(vec![Object::Real(t0), Object::Real(t1)]),
+            ),
+            (
+                "Functions",
+                Object::Array(
+                    samples
+                        .windows(2)
+                        .map(|pair| linear_function((0.0, 1.0), pair[0], pair[1]))
+                        .collect(),
+                ),
+            ),
+            (
+                "Bounds",
+                Object::Array(
+                    (1..SHADING_SEGMENTS)
+                        .map(|i| Object::Real(t0 + step * i as f32))
+                        .collect(),
+                ),
+            ),
+            (
+                "Encode",
+                Object::Array(
+                    [0.0, 1.0]
+                        .repeat(SHADING_SEGMENTS)
+                        .into_iter()
+                        .map(Object::Real)
+                        .collect(),
+                ),
+            ),
+        ]))
+    };
+
+    if let Ok(background) = shading.get(b"Background").and_then(Object::as_array) {
+        let values: Vec<f32> = background
+            .iter()
+            .filter_map(|v| v.as_float().ok())
+            .collect();
+        let gray = gray_of(&space, &values);
+        shading.set("Background", Object::Array(vec![Object::Real(gray)]));
+    }
+    shading.set("ColorSpace", Object::Name(b"DeviceGray".to_vec()));
+    shading.set("Function", function);
+    true
+}
+
+fn has_shading(obj: &Object) -> bool {
+    match obj {
+        Object::Dictionary(dict) => dict_has_shading(dict),
+        Object::Array(items) => items.iter().any(has_shading),
+        Object::Stream(stream) => dict_has_shading(&stream.dict),
+        _ => false,
+    }
+}
+
+fn dict_has_shading(dict: &Dictionary) -> bool {
+    dict.has(b"ShadingType") || dict.iter().any(|(_, v)| has_shading(v))
+}
+
+/// Recolour every shading dictionary nested in `obj`.
+fn recolor_shadings(doc: &Document, obj: &mut Object) -> bool {
+    match obj {
+        Object::Dictionary(dict) => recolor_dict_shadings(doc, dict),
+        Object::Array(items) => items
+            .iter_mut()
+            .fold(false, |changed, item| recolor_shadings(doc, item) | changed),
+        Object::Stream(stream) => recolor_dict_shadings(doc, &mut stream.dict),
+        _ => false,
+    }
+}
+
+fn recolor_dict_shadings(doc: &Document, dict: &mut Dictionary) -> bool {
+    let mut changed = recolor_shading(doc, dict);
+    for (_, value) in dict.iter_mut() {
+        changed |= recolor_shadings(doc, value);
+    }
+    changed
+}
+
+/// The gray version of one object, or `None` when it has no colour to
+/// convert. Page content is handled separately, since it has no object
+/// of its own to say what it is.
+fn recolor_object(doc: &Document, obj: &Object) -> PdfResult<Option<Object>> {
+    let mut updated = None;
+    if let Object::Stream(stream) = obj {
+        let dict = &stream.dict;
+        let subtype = dict.get(b"Subtype").and_then(Object::as_name).ok();
+        let colored_tiling = dict.get(b"PatternType").and_then(Object::as_i64).ok() == Some(1)
+            && dict.get(b"PaintType").and_then(Object::as_i64).ok() == Some(1);
+        if subtype == Some(b"Image".as_slice()) {
+            updated = recolor_image(doc, stream).map(Object::Stream);
+        } else if subtype == Some(b"Form".as_slice()) || colored_tiling {
+            let content = if dict.has(b"Filter") {
+                stream.decompressed_content().ok()
+            } else {
+                Some(stream.content.clone())
+            };
+            let Some(content) = content else {
+                tracing::warn!("Skipping form with unreadable content");
+                return Ok(None);
+            };
+            let resources = dict
+                .get(b"Resources")
+                .ok()
+                .and_then(|r| resolve(doc, r).as_dict().ok())
+                .cloned()
+                .unwrap_or_default();
+            let mut dict = dict.clone();
+            dict.remove(b"Filter");
+            dict.remove(b"DecodeParms");
+            let mut form = Stream::new(dict, recolor_content(doc, &content, &resources));
+            form.compress().map_err(|e| PdfError::from(e.to_string()))?;
+            updated = Some(Object::Stream(form));
+        }
+    }
+    if has_shading(updated.as_ref().unwrap_or(obj)) {
+        let mut copy = updated.take().unwrap_or_else(|| obj.clone());
+        if recolor_shadings(doc, &mut copy) || updated.is_some() {
+            updated = Some(copy);
+        }
+    }
+    Ok(updated)
+}
+
+/// Convert all colour in `doc` to gray: content stream colours, images and
+/// shadings. Returns how many objects and pages were rewritten.
+pub fn recolor_to_gray(doc: &mut Document) -> PdfResult<usize> {
+    let mut pages: Vec<(ObjectId, Vec<u8>)> = Vec::new();
+    for page_id in doc.get_pages().into_values() {
+        let content = doc.get_page_content(page_id);
+        let resources = page_resources(doc, page_id);
+        pages.push((page_id, recolor_content(doc, &content, &resources)));
+    }
+    let mut updates: Vec<(ObjectId, Object)> = Vec::new();
+    for (&id, obj) in &doc.objects {
+        if let Some(updated) = recolor_object(doc, obj)? {
+            updates.push((id, updated));
+        }
+    }
+
+    let rewritten = pages.len() + updates.len();
+    doc.objects.extend(updates);
+    for (page_id, content) in pages {
+        let mut stream = Stream::new(Dictionary::new(), content);
+        stream
+            .compress()
+            .map_err(|e| PdfError::from(e.to_string()))?;
+        let content_id = doc.add_object(stream);
+        doc.get_dictionary_mut(page_id)
+            .map_err(|e| PdfError::from(format!("Invalid page object: {e}")))?
+            .set("Contents", Object::Reference(content_id));
+    }
+    doc.prune_objects();
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Letter page painting coloured paths, one through a named RGB
+    /// space, an RGB image and a red-to-blue axial shading.
+    fn colour_doc() -> Document {
+        let mut doc = Document::with_version("1.7");
+        let tree_id = doc.new_object_id();
+        let image_id = doc.add_object(Stream::new(
+            Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"XObject".to_vec())),
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(2)),
+                ("Height", Object::Integer(1)),
+                ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+                ("Filter", Object::Name(b"FlateDecode".to_vec())),
+            ]),
+            filters::encode(&[255, 0, 0, 0, 0, 255], FilterType::Flate).unwrap(),
+        ));
+        let shading_id = doc.add_object(Dictionary::from_iter(vec![
+            ("ShadingType", Object::Integer(2)),
+            ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+            (
+                "Coords",
+                Object::Array([0, 0, 100, 0].map(Object::Integer).to_vec()),
+            ),
+            (
+                "Function",
+                Object::Dictionary(Dictionary::from_iter(vec![
+                    ("FunctionType", Object::Integer(2)),
+                    (
+                        "Domain",
+                        Object::Array([0, 1].map(Object::Integer).to_vec()),
+                    ),
+                    ("C0", Object::Array([1, 0, 0].map(Object::Integer).to_vec())),
+                    ("C1", Object::Array([0, 0, 1].map(Object::Integer).to_vec())),
+                    ("N", Object::Integer(1)),
+                ])),
+            ),
+        ]));
+        let resources = Dictionary::from_iter(vec![
+            (
+                "XObject",
+                Object::Dictionary(Dictionary::from_iter(vec![(
+                    "Im0",
+                    Object::Reference(image_id),
+                )])),
+            ),
+            (
+                "Shading",
+                Object::Dictionary(Dictionary::from_iter(vec![(
+                    "Sh0",
+                    Object::Reference(shading_id),
+                )])),
+            ),
+            (
+                "ColorSpace",
+                Object::Dictionary(Dictionary::from_iter(vec![(
+                    "CS0",
+                    Object::Name(b"DeviceRGB".to_vec()),
+                )])),
+            ),
+        ]);
+        let content = b"1 0 0 rg 0 0 1 RG 10 10 100 100 re B % red box\n\
+            /CS0 cs 0 1 0 sc 0 0 50 50 re f 0 0 0 1 k 5 5 5 5 re f\n\
+            q 100 0 0 50 200 200 cm /Im0 Do Q /Sh0 sh";
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content.to_vec()));
+        let page_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(tree_id)),
+            (
+                "MediaBox",
+                Object::Array([0, 0, 612, 792].map(Object::Integer).to_vec()),
+            ),
+            ("Resources", Object::Dictionary(resources)),
+            ("Contents", Object::Reference(content_id)),
+        ]));
+        doc.objects.insert(
+            tree_id,
+            Object::Dictionary(Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Count", Object::Integer(1)),
+                ("Kids", Object::Array(vec![Object::Reference(page_id)])),
+            ])),
+        );
+        let catalog_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(tree_id)),
+        ]));
+        doc.trailer.set("Root", Object::Reference(catalog_id));
+        doc
+    }
+
+    #[test]
+    fn test_gray_content_operators() {
+        let resources = Dictionary::new();
+        let gray = recolor_content(
+            &Document::with_version("1.7"),
+            b"1 0 0 rg q 0 0 1 RG Q /DeviceCMYK cs 0 0 0 1 sc /Pattern cs /P0 scn",
+            &resources,
+        );
+        assert_eq!(
+            String::from_utf8(gray).unwrap(),
+            "0.3 g\nq\n0.11 G\nQ\n0 g\n0 g\n/Pattern cs\n/P0 scn\n"
+        );
+    }
+
+    #[test]
+    fn test_recolor_to_gray() {
+        let mut doc = colour_doc();
+        recolor_to_gray(&mut doc).unwrap();
+
+        let page_id = *doc.get_pages().values().next().unwrap();
+        let content = doc.get_page_content(page_id);
+        let mut tokens = Tokenizer::new(&content);
+        let mut operands: Vec<f32> = Vec::new();
+        while let Some(token) = tokens.next_token() {
+            if token.kind != TokenType::Operator {
+                if let Some(n) = std::str::from_utf8(token.text)
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                {
+                    operands.push(n);
+                }
+                continue;
+            }
+            if matches!(token.text, b"rg" | b"RG" | b"sc" | b"SC" | b"scn" | b"SCN") {
+                let spread = operands.iter().copied().fold(f32::MIN, f32::max)
+                    - operands.iter().copied().fold(f32::MAX, f32::min);
+                assert!(
+                    spread <= 0.0,
+                    "{} keeps its colour",
+                    String::from_utf8_lossy(token.text)
+                );
+            }
+            assert!(!matches!(token.text, b"k" | b"K"));
+            operands.clear();
+        }
+
+        let (_, image) = doc
+            .objects
+            .iter()
+            .find(|(_, o)| {
+                o.as_stream().is_ok_and(|s| {
+                    s.dict.get(b"Subtype").and_then(Object::as_name).ok()
+                        == Some(b"Image".as_slice())
+                })
+            })
+            .unwrap();
+        let image = image.as_stream().unwrap();
+        assert_eq!(
+            image.dict.get(b"ColorSpace").unwrap().as_name().unwrap(),
+            b"DeviceGray"
+        );
+        // Red and blue through the NTSC weights.
+        assert_eq!(image.decompressed_content().unwrap(), [77, 28]);
+
+        let shading = doc
+            .objects
+            .values()
+            .find_map(|o| o.as_dict().ok().filter(|d| d.has(b"ShadingType")))
+            .unwrap();
+        assert_eq!(
+            shading.get(b"ColorSpace").unwrap().as_name().unwrap(),
+            b"DeviceGray"
+        );
+        let function = shading.get(b"Function").unwrap().as_dict().unwrap();
+        assert_eq!(
+            function.get(b"C0").unwrap().as_array().unwrap()[0]
+                .as_float()
+                .unwrap(),
+            0.3
+        );
+    }
+}
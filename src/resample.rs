@@ -0,0 +1,205 @@
+//! Resampling 8-bit pixmaps to a new size.
+//!
+//! Scaling is separable: rows are resampled first, then columns. When
+//! shrinking, the filter is stretched to cover every source pixel that
+//! falls under a destination pixel, so fine detail averages out instead of
+//! aliasing; [`ScaleFilter::Nearest`] alone picks single samples.
+
+use crate::models::{PdfError, PdfResult, Pixmap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleFilter {
+    /// The source pixel under each destination pixel's centre.
+    Nearest,
+    /// A triangle filter: linear interpolation when enlarging, a weighted
+    /// average when shrinking.
+    #[default]
+    Bilinear,
+    /// A three-lobed Lanczos filter, sharper than bilinear at the cost of
+    /// slight ringing at hard edges.
+    Lanczos,
+}
+
+impl ScaleFilter {
+    /// How far the kernel reaches, in source pixels at scale 1.
+    const fn radius(self) -> f32 {
+        match self {
+            Self::Nearest => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Lanczos => 3.0,
+        }
+    }
+
+    fn kernel(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Self::Nearest => f32::from(u8::from(x < 0.5)),
+            Self::Bilinear => (1.0 - x).max(0.0),
+            Self::Lanczos => {
+                if x < 1e-6 {
+                    1.0
+                } else if x < 3.0 {
+                    let pi_x = std::f32::consts::PI * x;
+                    3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// The source pixels a destination pixel draws on, from `start`, and
+/// their normalized weights.
+struct Taps {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// The taps of each of `to` destination pixels along an axis of `from`
+/// source pixels.
+fn taps(from: usize, to: usize, filter: ScaleFilter) -> Vec<Taps> {
+    let ratio = from as f32 / to as f32;
+    (0..to)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * ratio;
+            if filter == ScaleFilter::Nearest {
+                let start = (center as usize).min(from - 1);
+                return Taps {
+                    start,
+                    weights: vec![1.0],
+                };
+            }
+            let stretch = ratio.max(1.0);
+            let radius = filter.radius() * stretch;
+            let start = (center - radius).floor().max(0.0) as usize;
+            let end = ((center + radius).ceil() as usize).clamp(start + 1, from);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.kernel((j as f32 + 0.5 - center) / stretch))
+                .collect();
+            let total: f32 = weights.iter().sum();
+            if total.abs() > f32::EPSILON {
+                for weight in &mut weights {
+                    *weight /= total;
+                }
+            } else {
+                // Too narrow to catch any sample: take the nearest.
+                let nearest = ((center as usize).min(from - 1) - start).min(weights.len() - 1);
+                weights.fill(0.0);
+                weights[nearest] = 1.0;
+            }
+            Taps { start, weights }
+        })
+        .collect()
+}
+
+/// Scale `pixmap` to exactly `new_width` × `new_height` pixels.
+pub fn scale(
+    pixmap: &Pixmap,
+    new_width: u32,
+    new_height: u32,
+    filter: ScaleFilter,
+) -> PdfResult<Pixmap> {
+    if pixmap.bits_per_component != 8 {
+        return Err(PdfError::from("Only 8-bit images can be scaled"));
+    }
+    let (width, height) = (pixmap.width as usize, pixmap.height as usize);
+    let n = pixmap.colorspace.components();
+    if width == 0 || height == 0 || pixmap.samples.len() != width * height * n {
+        return Err(PdfError::from("Image data does not match its dimensions"));
+    }
+    if new_width == 0 || new_height == 0 {
+        return Err(PdfError::from("Scaled image would have no pixels"));
+    }
+    let (new_w, new_h) = (new_width as usize, new_height as usize);
+
+    // Rows first, into floats so the column pass doesn't round twice.
+    let columns = taps(width, new_w, filter);
+    let mut rows = vec![0.0f32; height * new_w * n];
+    for (src, dst) in pixmap
+        .samples
+        .chunks_exact(width * n)
+        .zip(rows.chunks_exact_mut(new_w * n))
+    {
+        for (column, out) in columns.iter().zip(dst.chunks_exact_mut(n)) {
+            for (k, &weight) in column.weights.iter().enumerate() {
+                let pixel = &src[(column.start + k) * n..][..n];
+                for (value, &sample) in out.iter_mut().zip(pixel) {
+                    *value += weight * f32::from(sample);
+                }
+            }
+        }
+    }
+
+    let mut samples = Vec::with_capacity(new_w * new_h * n);
+    let mut line = vec![0.0f32; new_w * n];
+    for row in taps(height, new_h, filter) {
+        line.fill(0.0);
+        for (k, &weight) in row.weights.iter().enumerate() {
+            let src = &rows[(row.start + k) * new_w * n..][..new_w * n];
+            for (value, &sample) in line.iter_mut().zip(src) {
+                *value += weight * sample;
+            }
+        }
+        samples.extend(line.iter().map(|v| v.round().clamp(0.0, 255.0) as u8));
+    }
+    Ok(Pixmap {
+        width: new_width,
+        height: new_height,
+        colorspace: pixmap.colorspace,
+        bits_per_component: 8,
+        samples,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ColorSpace;
+
+    fn checkerboard(size: u32) -> Pixmap {
+        Pixmap {
+            width: size,
+            height: size,
+            colorspace: ColorSpace::DeviceGray,
+            bits_per_component: 8,
+            samples: (0..size)
+                .flat_map(|y| (0..size).map(move |x| if (x + y) % 2 == 0 { 0 } else { 255 }))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_downscaled_checkerboard_averages() {
+        let board = checkerboard(64);
+        for filter in [ScaleFilter::Bilinear, ScaleFilter::Lanczos] {
+            let small = scale(&board, 20, 13, filter).unwrap();
+            assert_eq!((small.width, small.height), (20, 13));
+            assert_eq!(small.samples.len(), 20 * 13);
+            assert!(
+                small.samples.iter().all(|&v| (96..=160).contains(&v)),
+                "{filter:?} left extremes: {:?}",
+                small.samples
+            );
+        }
+        let nearest = scale(&board, 32, 32, ScaleFilter::Nearest).unwrap();
+        assert!(nearest.samples.iter().all(|&v| v == 0 || v == 255));
+    }
+
+    #[test]
+    fn test_bilinear_enlarging_interpolates() {
+        let ramp = Pixmap {
+            width: 2,
+            height: 1,
+            colorspace: ColorSpace::DeviceRgb,
+            bits_per_component: 8,
+            samples: vec![0, 100, 200, 200, 100, 0],
+        };
+        let wide = scale(&ramp, 4, 2, ScaleFilter::Bilinear).unwrap();
+        assert_eq!((wide.width, wide.height), (4, 2));
+        assert_eq!(wide.pixel(0, 0), [0, 100, 200]);
+        assert_eq!(wide.pixel(1, 1), [50, 100, 150]);
+        assert_eq!(wide.pixel(3, 0), [200, 100, 0]);
+        assert!(scale(&ramp, 0, 2, ScaleFilter::Bilinear).is_err());
+    }
+}
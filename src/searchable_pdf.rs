@@ -0,0 +1,430 @@
+//! Searchable PDFs from page images and recognized text.
+//!
+//! Each page is the scanned or rendered image drawn full-page, with every
+//! recognized block overlaid as invisible text (rendering mode 3) scaled to
+//! the block's box, so the text can be searched and selected while only
+//! the image shows. Block boxes are in image pixels from the top-left
+//! corner; the page size in points follows from the image's resolution.
+//!
+//! The text is set in Helvetica unless [`SearchablePdfWriter`] is given a
+//! TrueType font, which is embedded whole or subset to the glyphs the
+//! recognized text uses. Blocks the recognizer was unsure of can be left
+//! out with [`SearchablePdfWriter::min_confidence`].
+
+use crate::filters::{FilterType, encode};
+use crate::flow::{FontFace, text_width};
+use crate::font_program::{TrueTypeFont, embed_truetype};
+use crate::models::{ColorSpace, PdfError, PdfResult, Pixmap};
+use crate::pdf_engine::{helvetica_font, win_ansi_bytes};
+use lopdf::{Dictionary, Document, Object, Stream};
+use std::collections::BTreeMap;
+
+/// Helvetica's descender, as a fraction of the font size. Baselines sit
+/// this far above the bottom of a block so descenders stay inside it.
+const DESCENT: f32 = 0.21;
+
+/// Recognized text and its box in image pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcrTextBlock {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// How sure the recognizer is of the text, from 0 to 1. `None` when it
+    /// doesn't say; such blocks pass any confidence threshold and don't
+    /// count towards [`OcrPage::average_confidence`].
+    pub confidence: Option<f32>,
+}
+
+impl OcrTextBlock {
+    /// Whether the block clears `min_confidence`. Blocks without a
+    /// confidence always do.
+    pub fn meets(&self, min_confidence: f32) -> bool {
+        self.confidence.is_none_or(|c| c >= min_confidence)
+    }
+}
+
+/// One page image, the resolution it was rendered or scanned at, and the
+/// text found on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcrPage {
+    pub image: Pixmap,
+    pub dpi: f32,
+    pub blocks: Vec<OcrTextBlock>,
+}
+
+impl OcrPage {
+    /// The mean confidence of the blocks that have one, or `None` if no
+    /// block does.
+    pub fn average_confidence(&self) -> Option<f32> {
+        let scores: Vec<f32> = self.blocks.iter().filter_map(|b| b.confidence).collect();
+        (!scores.is_empty()).then(|| scores.iter().sum::<f32>() / scores.len() as f32)
+    }
+}
+
+const fn colorspace_name(colorspace: ColorSpace) -> &'static [u8] {
+    match colorspace {
+        ColorSpace::DeviceGray => b"DeviceGray",
+        ColorSpace::DeviceRgb => b"DeviceRGB",
+        ColorSpace::DeviceCmyk => b"DeviceCMYK",
+    }
+}
+
+fn image_stream(image: &Pixmap) -> PdfResult<Stream> {
+    if image.width == 0
+        || image.height == 0
+        || image.samples.len() != image.stride() * image.height as usize
+    {
+        return Err(PdfError::from("Image data does not match its dimensions"));
+    }
+    Ok(Stream::new(
+        Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"XObject".to_vec())),
+            ("Subtype", Object::Name(b"Image".to_vec())),
+            ("Width", Object::Integer(i64::from(image.width))),
+            ("Height", Object::Integer(i64::from(image.height))),
+            (
+                "ColorSpace",
+                Object::Name(colorspace_name(image.colorspace).to_vec()),
+            ),
+            (
+                "BitsPerComponent",
+                Object::Integer(i64::from(image.bits_per_component)),
+            ),
+            ("Filter", Object::Name(b"FlateDecode".to_vec())),
+        ]),
+        encode(&image.samples, FilterType::Flate)?,
+    ))
+}
+
+/// The page's content: the image scaled to the page, then the text of the
+/// blocks meeting `min_confidence`, in `font` or else Helvetica.
+fn page_content(
+    page: &OcrPage,
+    width: f32,
+    height: f32,
+    font: Option<&TrueTypeFont>,
+    min_confidence: f32,
+) -> Vec<u8> {
+    let scale = 72.0 / page.dpi;
+    let mut content = pdf_writer::Content::new();
+    content.save_state();
+    content.transform([width, 0.0, 0.0, height, 0.0, 0.0]);
+    content.x_object(pdf_writer::Name(b"Im0"));
+    content.restore_state();
+
+    let descent = font.map_or(DESCENT, TrueTypeFont::descent);
+    content.begin_text();
+    content.set_text_rendering_mode(pdf_writer::types::TextRenderingMode::Invisible);
+    for block in page.blocks.iter().filter(|b| b.meets(min_confidence)) {
+        let text = block.text.trim();
+        if text.is_empty() || block.height <= 0.0 || block.width <= 0.0 {
+            continue;
+        }
+        let size = block.height * scale;
+        let natural = font.map_or_else(
+            || text_width(text, FontFace::Regular, size),
+            |font| font.text_width(text, size),
+        );
+        if natural <= 0.0 {
+            continue;
+        }
+        let x = block.x * scale;
+        let baseline = height - (block.y + block.height) * scale + descent * size;
+        content.set_font(pdf_writer::Name(b"F1"), size);
+        // Stretch or squeeze the run to the width the recognizer measured.
+        content.set_horizontal_scaling(100.0 * block.width * scale / natural);
+        content.set_text_matrix([1.0, 0.0, 0.0, 1.0, x, baseline]);
+        let encoded = font.map_or_else(
+            || win_ansi_bytes(text),
+            |font| {
+                text.chars()
+                    .flat_map(|c| font.glyph(c).to_be_bytes())
+                    .collect()
+            },
+        );
+        content.show(pdf_writer::Str(&encoded));
+    }
+    content.end_text();
+    content.finish().to_vec()
+}
+
+/// Builds searchable PDFs, by default with the text in Helvetica.
+#[derive(Debug, Clone, Default)]
+pub struct SearchablePdfWriter {
+    font: Option<TrueTypeFont>,
+    subset_fonts: bool,
+    min_confidence: f32,
+}
+
+impl SearchablePdfWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the text in the TrueType font `data`, for scripts Helvetica
+    /// lacks.
+    pub fn add_ttf_font(&mut self, data: Vec<u8>) -> PdfResult<()> {
+        self.font = Some(TrueTypeFont::parse(data)?);
+        Ok(())
+    }
+
+    /// Embed only the glyphs the text uses rather than the whole font.
+    #[must_use]
+    pub fn subset_fonts(mut self, subset: bool) -> Self {
+        self.subset_fonts = subset;
+        self
+    }
+
+    /// Leave out text blocks whose confidence is below `min_confidence`.
+    /// Blocks without a confidence are kept.
+    #[must_use]
+    pub const fn min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    /// Build a PDF with one page per [`OcrPage`].
+    pub fn build(&self, pages: &[OcrPage]) -> PdfResult<Document> {
+        if pages.is_empty() {
+            return Err(PdfError::from("No pages to write"));
+        }
+        let mut doc = Document::with_version("1.7");
+        let font_id = match &self.font {
+            Some(font) => {
+                let mut used = BTreeMap::new();
+                for c in pages
+                    .iter()
+                    .flat_map(|page| &page.blocks)
+                    .filter(|block| block.meets(self.min_confidence))
+                    .flat_map(|block| block.text.trim().chars())
+                {
+                    let glyph = font.glyph(c);
+                    if glyph != 0 {
+                        used.entry(glyph).or_insert(c);
+                    }
+                }
+                embed_truetype(&mut doc, font, &used, self.subset_fonts)?
+            }
+            None => helvetica_font(&mut doc),
+        };
+
+        let pages_id = doc.new_object_id();
+        let mut kids = Vec::new();
+        for page in pages {
+            if page.dpi <= 0.0 {
+                return Err(PdfError::from("Page resolution must be positive"));
+            }
+            let width = page.image.width as f32 * 72.0 / page.dpi;
+            let height = page.image.height as f32 * 72.0 / page.dpi;
+            let image_id = doc.add_object(image_stream(&page.image)?);
+            let content_id = doc.add_object(Stream::new(
+                Dictionary::new(),
+                page_content(page, width, height, self.font.as_ref(), self.min_confidence),
+            ));
+            let resources = Dictionary::from_iter(vec![
+                (
+                    "Font",
+                    Object::Dictionary(Dictionary::from_iter(vec![(
+                        "F1",
+                        Object::Reference(font_id),
+                    )])),
+                ),
+                (
+                    "XObject",
+                    Object::Dictionary(Dictionary::from_iter(vec![(
+                        "Im0",
+                        Object::Reference(image_id),
+                    )])),
+                ),
+            ]);
+            let new_page_id = doc.add_object(Object::Dictionary(Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Page".to_vec())),
+                ("Parent", Object::Reference(pages_id)),
+                (
+                    "MediaBox",
+                    Object::Array(vec![
+                        Object::Real(0.0),
+                        Object::Real(0.0),
+                        Object::Real(width),
+                        Object::Real(height),
+                    ]),
+                ),
+                ("Contents", Object::Reference(content_id)),
+                ("Resources", Object::Dictionary(resources)),
+            ])));
+            kids.push(Object::Reference(new_page_id));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Count", Object::Integer(kids.len() as i64)),
+                ("Kids", Object::Array(kids)),
+            ])),
+        );
+        let catalog_id = doc.add_object(Object::Dictionary(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+        ])));
+        doc.trailer.set("Root", Object::Reference(catalog_id));
+        Ok(doc)
+    }
+}
+
+/// Build a PDF with one page per [`OcrPage`], the text in Helvetica.
+pub fn build_searchable_pdf(pages: &[OcrPage]) -> PdfResult<Document> {
+    SearchablePdfWriter::new().build(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> OcrPage {
+        OcrPage {
+            image: Pixmap {
+                width: 300,
+                height: 200,
+                colorspace: ColorSpace::DeviceGray,
+                bits_per_component: 8,
+                samples: vec![255; 300 * 200],
+            },
+            dpi: 144.0,
+            blocks: vec![OcrTextBlock {
+                text: "Invoice".to_string(),
+                x: 40.0,
+                y: 60.0,
+                width: 120.0,
+                height: 20.0,
+                confidence: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_page_size_and_hidden_text_follow_dpi() {
+        let doc = build_searchable_pdf(&[page(), page()]).unwrap();
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 2);
+
+        let page_id = pages[&1];
+        let media_box = doc
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"MediaBox")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_float().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(media_box, [0.0, 0.0, 150.0, 100.0]);
+
+        let content = lopdf::content::Content::decode(&doc.get_page_content(page_id)).unwrap();
+        let op = |name: &str| {
+            content
+                .operations
+                .iter()
+                .find(|o| o.operator == name)
+                .unwrap()
+                .operands
+                .iter()
+                .filter_map(|v| v.as_float().ok())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(op("Tr"), [3.0]);
+        assert_eq!(op("Tf"), [10.0]);
+        // 40px at 144dpi is 20pt from the left; the box's bottom edge, 80px
+        // down, is 60pt above the page bottom, plus the descent.
+        let tm = op("Tm");
+        assert_eq!(tm[4], 20.0);
+        assert!((tm[5] - (60.0 + DESCENT * 10.0)).abs() < 1e-4);
+        let tz = op("Tz")[0];
+        let natural = text_width("Invoice", FontFace::Regular, 10.0);
+        assert!((natural * tz / 100.0 - 60.0).abs() < 1e-3);
+
+        assert!(doc.extract_text(&[1]).unwrap().contains("Invoice"));
+    }
+
+    #[test]
+    fn test_rejects_mismatched_image() {
+        let mut bad = page();
+        bad.image.samples.pop();
+        assert!(build_searchable_pdf(&[bad]).is_err());
+        assert!(build_searchable_pdf(&[]).is_err());
+    }
+
+    #[test]
+    fn test_min_confidence_drops_unsure_blocks() {
+        let mut scanned = page();
+        let block = |text: &str, y: f32, confidence| OcrTextBlock {
+            text: text.to_string(),
+            x: 40.0,
+            y,
+            width: 120.0,
+            height: 20.0,
+            confidence,
+        };
+        scanned.blocks = vec![
+            block("Total", 100.0, Some(0.9)),
+            block("Smudge", 140.0, Some(0.2)),
+            block("Invoice", 60.0, None),
+        ];
+        assert!((scanned.average_confidence().unwrap() - 0.55).abs() < 1e-6);
+        assert_eq!(page().average_confidence(), None);
+
+        let text = |min: f32| {
+            let doc = SearchablePdfWriter::new()
+                .min_confidence(min)
+                .build(&[scanned.clone()])
+                .unwrap();
+            doc.extract_text(&[1]).unwrap()
+        };
+        let all = text(0.0);
+        assert!(all.contains("Smudge") && all.contains("Total"));
+        let confident = text(0.5);
+        assert!(!confident.contains("Smudge"));
+        assert!(confident.contains("Total") && confident.contains("Invoice"));
+    }
+
+    #[test]
+    fn test_subset_font_embeds_fewer_bytes() {
+        let lucide = include_bytes!("assets/fonts/lucide.ttf").to_vec();
+        let mut icons = page();
+        icons.blocks[0].text = "\u{E038}\u{E039}\u{E03A}".to_string();
+        let build = |subset: bool| {
+            let mut writer = SearchablePdfWriter::new().subset_fonts(subset);
+            writer.add_ttf_font(lucide.clone()).unwrap();
+            writer.build(&[icons.clone()]).unwrap()
+        };
+        let embedded = |doc: &Document| {
+            let fonts = crate::font_program::embedded_fonts(doc);
+            assert_eq!(fonts.len(), 1);
+            crate::font_program::extract_font(doc, fonts[0]).unwrap()
+        };
+        let (full, subset) = (build(false), build(true));
+        let (full_font, subset_font) = (embedded(&full), embedded(&subset));
+        assert!(!full_font.subset);
+        assert!(subset_font.subset);
+        assert!(subset_font.data.len() * 20 < full_font.data.len());
+
+        let page_id = subset.get_pages()[&1];
+        let content = lopdf::content::Content::decode(&subset.get_page_content(page_id)).unwrap();
+        let shown = content
+            .operations
+            .iter()
+            .find(|o| o.operator == "Tj")
+            .and_then(|o| o.operands[0].as_str().ok())
+            .unwrap();
+        let font = subset
+            .get_page_fonts(page_id)
+            .unwrap()
+            .into_values()
+            .next()
+            .unwrap();
+        let to_unicode = crate::cmap::font_unicode_map(&subset, font).unwrap();
+        assert_eq!(to_unicode.to_unicode(shown), icons.blocks[0].text);
+    }
+}
@@ -0,0 +1,312 @@
+//! Axial (type 2) and radial (type 3) shadings.
+//!
+//! Colours are sampled from the shading's function into a 256-entry table
+//! across its domain and converted to sRGB once, so drawing costs one
+//! parameter calculation per pixel. Pixels the shading doesn't cover,
+//! including those beyond an end that isn't extended, are left as they
+//! were.
+
+use crate::colorspace::{PdfColorspace, convert_color};
+use crate::content_stream::{Matrix, invert, transform};
+use crate::function::PdfFunction;
+use crate::models::{ColorSpace, PdfError, PdfResult, Pixmap};
+use lopdf::{Document, Object};
+
+const LUT_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingKind {
+    /// The axis from (x0, y0) to (x1, y1).
+    Axial([f32; 4]),
+    /// Circles blending from (x0, y0, r0) to (x1, y1, r1).
+    Radial([f32; 6]),
+}
+
+#[derive(Debug, Clone)]
+pub struct Shading {
+    pub kind: ShadingKind,
+    pub colorspace: PdfColorspace,
+    pub domain: (f32, f32),
+    /// A single function giving every colour component, or one function
+    /// per component.
+    pub functions: Vec<PdfFunction>,
+    /// Whether to continue past the start and the end.
+    pub extend: [bool; 2],
+}
+
+impl Shading {
+    /// Read a shading dictionary (or the dictionary of a shading stream).
+    pub fn from_object(doc: &Document, obj: &Object) -> PdfResult<Self> {
+        let obj = match obj {
+            Object::Reference(id) => doc
+                .get_object(*id)
+                .map_err(|e| PdfError::from(e.to_string()))?,
+            other => other,
+        };
+        let dict = match obj {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &stream.dict,
+            _ => return Err(PdfError::from("Shading is not a dictionary")),
+        };
+        let numbers = |key: &[u8]| crate::function::numbers(doc, dict, key);
+        let shading_type = dict
+            .get(b"ShadingType")
+            .and_then(Object::as_i64)
+            .map_err(|_| PdfError::from("Shading has no /ShadingType"))?;
+        let coords = numbers(b"Coords").unwrap_or_default();
+        let kind = match shading_type {
+            2 => coords.try_into().map(ShadingKind::Axial).ok(),
+            3 => coords.try_into().map(ShadingKind::Radial).ok(),
+            t => {
+                return Err(PdfError::from(format!(
+                    "Type {t} shadings are not supported"
+                )));
+            }
+        }
+        .ok_or_else(|| PdfError::from("Shading /Coords are malformed"))?;
+
+        let colorspace = PdfColorspace::from_object(
+            doc,
+            dict.get(b"ColorSpace")
+                .map_err(|_| PdfError::from("Shading has no /ColorSpace"))?,
+        )?;
+        let functions = match dict.get(b"Function") {
+            Ok(Object::Array(items)) => items
+                .iter()
+                .map(|f| PdfFunction::from_object(doc, f))
+                .collect::<PdfResult<Vec<_>>>()?,
+            Ok(f) => vec![PdfFunction::from_object(doc, f)?],
+            Err(_) => return Err(PdfError::from("Shading has no /Function")),
+        };
+        let outputs: usize = functions.iter().map(PdfFunction::outputs).sum();
+        if outputs != colorspace.components() {
+            return Err(PdfError::from(
+                "Shading functions don't match the colour space",
+            ));
+        }
+        let domain = match numbers(b"Domain").as_deref() {
+            Some(&[t0, t1]) => (t0, t1),
+            _ => (0.0, 1.0),
+        };
+        let extend = match dict.get(b"Extend").and_then(Object::as_array) {
+            Ok(flags) => {
+                [0, 1].map(|i| flags.get(i).and_then(|f| f.as_bool().ok()).unwrap_or(false))
+            }
+            Err(_) => [false; 2],
+        };
+        Ok(Self {
+            kind,
+            colorspace,
+            domain,
+            functions,
+            extend,
+        })
+    }
+
+    /// The sRGB colour at parameter `t` of the domain.
+    pub fn color_at(&self, t: f32) -> [f32; 3] {
+        let values: Vec<f32> = self.functions.iter().flat_map(|f| f.eval(&[t])).collect();
+        convert_color(&self.colorspace, &values)
+    }
+
+    /// Where the point falls along the shading, from 0 at the start to 1 at
+    /// the end, or `None` if it isn't painted.
+    fn position(&self, x: f32, y: f32) -> Option<f32> {
+        let [before, after] = self.extend;
+        let s = match self.kind {
+            ShadingKind::Axial([x0, y0, x1, y1]) => {
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let length = dx * dx + dy * dy;
+                if length == 0.0 {
+                    return None;
+                }
+                ((x - x0) * dx + (y - y0) * dy) / length
+            }
+            ShadingKind::Radial(coords) => radial_position(coords, x, y, before, after)?,
+        };
+        match s {
+            s if s < 0.0 => before.then_some(0.0),
+            s if s > 1.0 => after.then_some(1.0),
+            s => Some(s),
+        }
+    }
+}
+
+/// The largest `s` whose circle, centre `c0 + s(c1 − c0)` and radius
+/// `r0 + s(r1 − r0)`, passes through the point, among those with a
+/// non-negative radius that are in 0..=1 or on an extended side.
+fn radial_position(
+    [x0, y0, r0, x1, y1, r1]: [f32; 6],
+    x: f32,
+    y: f32,
+    before: bool,
+    after: bool,
+) -> Option<f32> {
+    let (cdx, cdy, dr) = (x1 - x0, y1 - y0, r1 - r0);
+    let (pdx, pdy) = (x - x0, y - y0);
+    // |p − c(s)|² = r(s)², as qa·s² − 2qb·s + qc = 0.
+    let qa = cdx * cdx + cdy * cdy - dr * dr;
+    let qb = pdx * cdx + pdy * cdy + r0 * dr;
+    let qc = pdx * pdx + pdy * pdy - r0 * r0;
+    let usable =
+        |s: f32| s.is_finite() && r0 + s * dr >= 0.0 && (before || s >= 0.0) && (after || s <= 1.0);
+    if qa.abs() < f32::EPSILON {
+        // One root, or none when the circles neither move nor grow.
+        if qb.abs() < f32::EPSILON {
+            return None;
+        }
+        let s = qc / (2.0 * qb);
+        return usable(s).then_some(s);
+    }
+    let discriminant = qb.powi(2) - qa * qc;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (s0, s1) = ((qb + root) / qa, (qb - root) / qa);
+    let roots = if s0 >= s1 { [s0, s1] } else { [s1, s0] };
+    roots.into_iter().find(|&s| usable(s))
+}
+
+/// Paint `shading` into an 8-bit gray or RGB pixmap, with `ctm` mapping
+/// shading space to pixel coordinates.
+pub fn render_shade(shading: &Shading, ctm: &Matrix, pixmap: &mut Pixmap) -> PdfResult<()> {
+    if pixmap.bits_per_component != 8 || pixmap.colorspace == ColorSpace::DeviceCmyk {
+        return Err(PdfError::from(
+            "Shadings can only be drawn into 8-bit gray or RGB pixmaps",
+        ));
+    }
+    // A singular matrix squashes the shading to nothing.
+    let Some(inverse) = invert(ctm) else {
+        return Ok(());
+    };
+    let (t0, t1) = shading.domain;
+    let lut: Vec<[u8; 3]> = (0..LUT_SIZE)
+        .map(|i| {
+            let t = t0 + (t1 - t0) * i as f32 / (LUT_SIZE - 1) as f32;
+            shading
+                .color_at(t)
+                .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+        })
+        .collect();
+
+    let n = pixmap.colorspace.components();
+    let stride = pixmap.stride();
+    for py in 0..pixmap.height {
+        for px in 0..pixmap.width {
+            let (x, y) = transform(&inverse, px as f32 + 0.5, py as f32 + 0.5);
+            let Some(s) = shading.position(x, y) else {
+                continue;
+            };
+            let rgb = lut[(s * (LUT_SIZE - 1) as f32).round() as usize];
+            let start = py as usize * stride + px as usize * n;
+            if n == 1 {
+                pixmap.samples[start] = ((u32::from(rgb[0]) * 299
+                    + u32::from(rgb[1]) * 587
+                    + u32::from(rgb[2]) * 114
+                    + 500)
+                    / 1000) as u8;
+            } else {
+                pixmap.samples[start..start + 3].copy_from_slice(&rgb);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content_stream::IDENTITY;
+    use lopdf::Dictionary;
+
+    fn array(values: &[f32]) -> Object {
+        Object::Array(values.iter().map(|&v| Object::Real(v)).collect())
+    }
+
+    fn black_to_white(shading_type: i64, coords: &[f32], extend: bool) -> Shading {
+        let dict = Dictionary::from_iter(vec![
+            ("ShadingType", Object::Integer(shading_type)),
+            ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+            ("Coords", array(coords)),
+            (
+                "Function",
+                Object::Dictionary(Dictionary::from_iter(vec![
+                    ("FunctionType", Object::Integer(2)),
+                    ("Domain", array(&[0.0, 1.0])),
+                    ("C0", array(&[0.0])),
+                    ("C1", array(&[1.0])),
+                    ("N", Object::Real(1.0)),
+                ])),
+            ),
+            (
+                "Extend",
+                Object::Array(vec![Object::Boolean(extend), Object::Boolean(extend)]),
+            ),
+        ]);
+        Shading::from_object(&Document::with_version("1.7"), &Object::Dictionary(dict)).unwrap()
+    }
+
+    fn canvas(width: u32, height: u32) -> Pixmap {
+        Pixmap {
+            width,
+            height,
+            colorspace: ColorSpace::DeviceRgb,
+            bits_per_component: 8,
+            samples: vec![128; (width * height * 3) as usize],
+        }
+    }
+
+    #[test]
+    fn test_axial_black_to_white() {
+        let shading = black_to_white(2, &[0.0, 0.0, 100.0, 0.0], false);
+        let mut pixmap = canvas(100, 4);
+        render_shade(&shading, &IDENTITY, &mut pixmap).unwrap();
+        assert!(pixmap.pixel(0, 2).iter().all(|&c| c <= 2));
+        assert!(pixmap.pixel(99, 2).iter().all(|&c| c >= 253));
+        assert!(pixmap.pixel(50, 0).iter().all(|&c| c.abs_diff(128) <= 2));
+
+        // Scaled up 2x, the same gradient spans 200 pixels.
+        let mut wide = canvas(200, 1);
+        render_shade(&shading, &[2.0, 0.0, 0.0, 2.0, 0.0, 0.0], &mut wide).unwrap();
+        assert!(wide.pixel(100, 0)[0].abs_diff(128) <= 2);
+    }
+
+    #[test]
+    fn test_extend() {
+        let inner = black_to_white(2, &[25.0, 0.0, 75.0, 0.0], false);
+        let mut pixmap = canvas(100, 1);
+        render_shade(&inner, &IDENTITY, &mut pixmap).unwrap();
+        assert_eq!(pixmap.pixel(10, 0), [128; 3]);
+        assert_eq!(pixmap.pixel(90, 0), [128; 3]);
+
+        let extended = black_to_white(2, &[25.0, 0.0, 75.0, 0.0], true);
+        render_shade(&extended, &IDENTITY, &mut pixmap).unwrap();
+        assert_eq!(pixmap.pixel(10, 0), [0; 3]);
+        assert_eq!(pixmap.pixel(90, 0), [255; 3]);
+    }
+
+    #[test]
+    fn test_radial() {
+        // From a point at the centre out to a radius of 50.
+        let shading = black_to_white(3, &[50.0, 50.0, 0.0, 50.0, 50.0, 50.0], false);
+        let mut pixmap = canvas(100, 100);
+        render_shade(&shading, &IDENTITY, &mut pixmap).unwrap();
+        assert!(pixmap.pixel(49, 49)[0] <= 5);
+        // The pixel's centre is 39.5 units out, 79% of the way.
+        assert!(pixmap.pixel(89, 49)[0].abs_diff(201) <= 2);
+        assert_eq!(pixmap.pixel(0, 0), [128; 3]);
+    }
+
+    #[test]
+    fn test_degenerate_shadings_paint_nothing() {
+        for shading in [
+            black_to_white(2, &[50.0, 50.0, 50.0, 50.0], true),
+            black_to_white(3, &[50.0, 50.0, 20.0, 50.0, 50.0, 20.0], true),
+        ] {
+            let mut pixmap = canvas(100, 100);
+            render_shade(&shading, &IDENTITY, &mut pixmap).unwrap();
+            assert!(pixmap.samples.iter().all(|&c| c == 128));
+        }
+    }
+}
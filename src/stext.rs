@@ -0,0 +1,492 @@
+//! Structured text: a page's text grouped into blocks, lines and spans.
+//!
+//! Coordinates are in page space with the origin at the top-left corner, as
+//! for [`crate::models::TextItem`]. Reading order is column by column: the
+//! page is split at vertical gutters that no text crosses, and within a
+//! column blocks run top to bottom.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct Rect {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+impl Rect {
+    #[must_use]
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y1 - self.y0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StextSpan {
+    pub text: String,
+    /// Font name, when the extractor knows it.
+    pub font: Option<String>,
+    pub size: f32,
+    /// The span's box; `y1` is the baseline.
+    pub bbox: Rect,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextLine {
+    pub bbox: Rect,
+    pub spans: Vec<StextSpan>,
+}
+
+impl TextLine {
+    /// The line's text in reading order. Spans are kept left to right as
+    /// they sit on the page, so right-to-left script is put back into
+    /// logical order with [`crate::bidi::visual_to_logical`].
+    pub fn text(&self) -> String {
+        let visual = self
+            .spans
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        crate::bidi::visual_to_logical(&visual)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextBlock {
+    pub bbox: Rect,
+    pub lines: Vec<TextLine>,
+}
+
+impl TextBlock {
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(TextLine::text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A page's structured text together with the page size, in points.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StextPage {
+    pub width: f32,
+    pub height: f32,
+    pub blocks: Vec<TextBlock>,
+}
+
+/// Group spans into blocks in reading order.
+pub fn structure_spans(mut spans: Vec<StextSpan>) -> Vec<TextBlock> {
+    spans.retain(|s| !s.text.trim().is_empty());
+    if spans.is_empty() {
+        return Vec::new();
+    }
+
+    let columns = column_bounds(&spans);
+    let mut by_column: Vec<Vec<StextSpan>> = vec![Vec::new(); columns.len()];
+    for span in spans {
+        // A span belongs to the column it starts in, so a heading that
+        // crosses a gutter stays with the column on its left.
+        let index = columns
+            .iter()
+            .rposition(|&(start, _)| span.bbox.x0 >= start)
+            .unwrap_or(0);
+        by_column[index].push(span);
+    }
+
+    by_column
+        .into_iter()
+        .flat_map(|spans| group_blocks(group_lines(spans)))
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// One page as a `<div>` of absolutely positioned spans, one per text run.
+///
+/// Span boxes are already measured from the top-left corner, so `top` is
+/// the top of the box (the baseline less the font size) rather than the
+/// baseline, which is where CSS places an element.
+pub fn stext_to_html(page: &StextPage) -> String {
+    let mut out = format!(
+        "<div class=\"page\" style=\"position:relative;width:{:.2}pt;height:{:.2}pt\">\n",
+        page.width, page.height
+    );
+    for span in page
+        .blocks
+        .iter()
+        .flat_map(|b| &b.lines)
+        .flat_map(|l| &l.spans)
+    {
+        let family = span
+            .font
+            .as_deref()
+            .map(|f| format!(";font-family:'{}'", escape_html(f)))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "<span style=\"left:{:.2}pt;top:{:.2}pt;font-size:{:.2}pt{family}\">{}</span>\n",
+            span.bbox.x0,
+            span.bbox.y0,
+            span.size,
+            escape_html(&span.text)
+        ));
+    }
+    out.push_str("</div>\n");
+    out
+}
+
+/// A standalone HTML document with every page, one after another.
+pub fn stext_document_to_html(pages: &[StextPage]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>\n\
+         .page { margin: 1em auto; border: 1px solid #ccc; overflow: hidden; }\n\
+         .page span { position: absolute; white-space: pre; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    for page in pages {
+        out.push_str(&stext_to_html(page));
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn json_bbox(rect: Rect) -> Value {
+    json!({ "x": rect.x0, "y": rect.y0, "w": rect.width(), "h": rect.height() })
+}
+
+fn json_font(span: &StextSpan) -> Value {
+    json!({
+        "name": span.font.as_deref().unwrap_or(""),
+        "size": span.size,
+    })
+}
+
+/// One page in the shape of MuPDF's stext JSON: `blocks` of `type` "text",
+/// each with `lines` carrying `wmode`, `bbox`, the first span's `font`, the
+/// line origin as `x`/`y` and the line's `text`. Boxes are `{x, y, w, h}`.
+/// Lines also list their `spans`, which MuPDF doesn't.
+pub fn stext_to_json(page: &StextPage) -> Value {
+    let blocks: Vec<Value> = page
+        .blocks
+        .iter()
+        .map(|block| {
+            let lines: Vec<Value> = block
+                .lines
+                .iter()
+                .map(|line| {
+                    let first = &line.spans[0];
+                    let spans: Vec<Value> = line
+                        .spans
+                        .iter()
+                        .map(|span| {
+                            json!({
+                                "font": json_font(span),
+                                "bbox": json_bbox(span.bbox),
+                                "text": span.text,
+                            })
+                        })
+                        .collect();
+                    json!({
+                        "wmode": 0,
+                        "bbox": json_bbox(line.bbox),
+                        "font": json_font(first),
+                        "x": first.bbox.x0,
+                        "y": first.bbox.y1,
+                        "text": line.text(),
+                        "spans": spans,
+                    })
+                })
+                .collect();
+            json!({ "type": "text", "bbox": json_bbox(block.bbox), "lines": lines })
+        })
+        .collect();
+    json!({ "width": page.width, "height": page.height, "blocks": blocks })
+}
+
+/// Horizontal extents of the page's columns, left to right.
+///
+/// A gutter is a run of x positions covered by (almost) no span and at least
+/// as wide as the median font size, so that word gaps, which other lines
+/// cover, don't split the page. A tenth of the baselines may cross a gutter
+/// to allow for headings spanning several columns, and each side of a
+/// gutter needs text on at least two lines.
+fn column_bounds(spans: &[StextSpan]) -> Vec<(f32, f32)> {
+    let left = spans
+        .iter()
+        .map(|s| s.bbox.x0)
+        .fold(f32::INFINITY, f32::min);
+    let right = spans
+        .iter()
+        .map(|s| s.bbox.x1)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let bins = (right - left).ceil().max(1.0) as usize;
+
+    let mut coverage = vec![0usize; bins];
+    for span in spans {
+        let start = ((span.bbox.x0 - left).floor() as usize).min(bins - 1);
+        let end = ((span.bbox.x1 - left).ceil() as usize).clamp(start + 1, bins);
+        for count in &mut coverage[start..end] {
+            *count += 1;
+        }
+    }
+
+    let allowance = lines_where(spans, |_| true) / 10;
+
+    let mut sizes: Vec<f32> = spans.iter().map(|s| s.size).collect();
+    sizes.sort_by(f32::total_cmp);
+    let min_gutter = sizes[sizes.len() / 2].max(1.0) as usize;
+
+    let mut columns = Vec::new();
+    let mut column_start = 0;
+    let mut bin = 0;
+    while bin < bins {
+        if coverage[bin] > allowance {
+            bin += 1;
+            continue;
+        }
+        let gap_start = bin;
+        while bin < bins && coverage[bin] <= allowance {
+            bin += 1;
+        }
+        let (gap_x0, gap_x1) = (left + gap_start as f32, left + bin as f32);
+        let wide = bin - gap_start >= min_gutter && gap_start > 0 && bin < bins;
+        if wide
+            && lines_where(spans, |s| s.bbox.x1 <= gap_x0) >= 2
+            && lines_where(spans, |s| s.bbox.x0 >= gap_x1) >= 2
+        {
+            columns.push((column_start, gap_start));
+            column_start = bin;
+        }
+    }
+    columns.push((column_start, bins));
+    columns
+        .into_iter()
+        .map(|(start, end)| (left + start as f32, left + end as f32))
+        .collect()
+}
+
+/// Number of distinct baselines among the spans matching `pred`.
+fn lines_where(spans: &[StextSpan], pred: impl Fn(&StextSpan) -> bool) -> usize {
+    let mut baselines: Vec<f32> = spans
+        .iter()
+        .filter(|s| pred(s))
+        .map(|s| s.bbox.y1.round())
+        .collect();
+    baselines.sort_by(f32::total_cmp);
+    baselines.dedup();
+    baselines.len()
+}
+
+/// Join spans sharing a baseline into lines, top to bottom.
+pub(crate) fn group_lines(mut spans: Vec<StextSpan>) -> Vec<TextLine> {
+    spans.sort_by(|a, b| {
+        a.bbox
+            .y1
+            .total_cmp(&b.bbox.y1)
+            .then(a.bbox.x0.total_cmp(&b.bbox.x0))
+    });
+    let mut lines: Vec<TextLine> = Vec::new();
+    for span in spans {
+        if let Some(line) = lines.last_mut() {
+            let baseline = line.spans[0].bbox.y1;
+            let tolerance = line.spans[0].size.min(span.size) * 0.5;
+            if (span.bbox.y1 - baseline).abs() <= tolerance {
+                line.bbox = line.bbox.union(span.bbox);
+                line.spans.push(span);
+                continue;
+            }
+        }
+        lines.push(TextLine {
+            bbox: span.bbox,
+            spans: vec![span],
+        });
+    }
+    for line in &mut lines {
+        line.spans.sort_by(|a, b| a.bbox.x0.total_cmp(&b.bbox.x0));
+    }
+    lines
+}
+
+/// Split a column's lines into blocks wherever the gap between two lines
+/// is larger than the upper line's height.
+fn group_blocks(lines: Vec<TextLine>) -> Vec<TextBlock> {
+    let mut blocks: Vec<TextBlock> = Vec::new();
+    for line in lines {
+        if let Some(block) = blocks.last_mut() {
+            let previous = &block.lines[block.lines.len() - 1].bbox;
+            if line.bbox.y0 - previous.y1 <= previous.height() {
+                block.bbox = block.bbox.union(line.bbox);
+                block.lines.push(line);
+                continue;
+            }
+        }
+        blocks.push(TextBlock {
+            bbox: line.bbox,
+            lines: vec![line],
+        });
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str, x: f32, baseline: f32) -> StextSpan {
+        StextSpan {
+            text: text.to_string(),
+            font: None,
+            size: 10.0,
+            bbox: Rect {
+                x0: x,
+                y0: baseline - 10.0,
+                x1: x + 6.0 * text.len() as f32,
+                y1: baseline,
+            },
+        }
+    }
+
+    /// Two columns of two paragraphs each, emitted row by row across the
+    /// page the way a naive extractor sees them.
+    fn two_columns() -> Vec<StextSpan> {
+        let mut spans = Vec::new();
+        for (row, baseline) in [100.0, 112.0, 150.0, 162.0].into_iter().enumerate() {
+            spans.push(span(&format!("left{row}"), 72.0, baseline));
+            spans.push(span("word", 110.0, baseline));
+            spans.push(span(&format!("right{row}"), 320.0, baseline));
+        }
+        spans
+    }
+
+    #[test]
+    fn test_left_column_precedes_right_column() {
+        let blocks = structure_spans(two_columns());
+        let texts: Vec<String> = blocks.iter().map(TextBlock::text).collect();
+        assert_eq!(
+            texts,
+            [
+                "left0 word\nleft1 word",
+                "left2 word\nleft3 word",
+                "right0\nright1",
+                "right2\nright3",
+            ]
+        );
+        assert_eq!(
+            blocks[0].bbox,
+            Rect {
+                x0: 72.0,
+                y0: 90.0,
+                x1: 134.0,
+                y1: 112.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_single_column_keeps_lines_together() {
+        let spans = vec![span("b", 90.0, 100.0), span("a", 72.0, 101.0)];
+        let blocks = structure_spans(spans);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].lines.len(), 1);
+        assert_eq!(blocks[0].text(), "a b");
+    }
+
+    #[test]
+    fn test_rtl_line_text_in_reading_order() {
+        // "shalom olam" drawn right to left: the second word is leftmost and
+        // each word's letters run right to left.
+        let spans = vec![
+            span("\u{5DD}\u{5DC}\u{5D5}\u{5E2}", 72.0, 100.0),
+            span("\u{5DD}\u{5D5}\u{5DC}\u{5E9}", 110.0, 100.0),
+        ];
+        let blocks = structure_spans(spans);
+        assert_eq!(
+            blocks[0].text(),
+            "\u{5E9}\u{5DC}\u{5D5}\u{5DD} \u{5E2}\u{5D5}\u{5DC}\u{5DD}"
+        );
+    }
+
+    #[test]
+    fn test_html_positions_each_run() {
+        let page = StextPage {
+            width: 612.0,
+            height: 792.0,
+            blocks: structure_spans(two_columns()),
+        };
+        let html = stext_to_html(&page);
+        assert_eq!(html.matches("<span ").count(), 12);
+        assert!(
+            html.contains(
+                "<span style=\"left:72.00pt;top:90.00pt;font-size:10.00pt\">left0</span>"
+            )
+        );
+        assert!(html.contains(
+            "<span style=\"left:320.00pt;top:152.00pt;font-size:10.00pt\">right3</span>"
+        ));
+
+        let document = stext_document_to_html(&[page.clone(), page]);
+        assert_eq!(document.matches("<div class=\"page\"").count(), 2);
+        assert!(document.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_html_escapes_text() {
+        assert_eq!(
+            escape_html("a<b & \"c\">"),
+            "a&lt;b &amp; &quot;c&quot;&gt;"
+        );
+    }
+
+    #[test]
+    fn test_json_matches_block_structure() {
+        let page = StextPage {
+            width: 612.0,
+            height: 792.0,
+            blocks: structure_spans(two_columns()),
+        };
+        let text = serde_json::to_string(&stext_to_json(&page)).unwrap();
+        let parsed: Value = serde_json::from_str(&text).unwrap();
+        let blocks = parsed["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0]["type"], "text");
+        assert_eq!(
+            blocks[0]["bbox"],
+            json!({ "x": 72.0, "y": 90.0, "w": 62.0, "h": 22.0 })
+        );
+        let line = &blocks[2]["lines"][0];
+        assert_eq!(line["text"], "right0");
+        assert_eq!(line["font"]["size"], 10.0);
+        assert_eq!(
+            (line["x"].as_f64(), line["y"].as_f64()),
+            (Some(320.0), Some(100.0))
+        );
+    }
+}
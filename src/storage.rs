@@ -1,4 +1,4 @@
-use crate::models::{AppSettings, AppTheme, RecentFile, SessionData};
+use crate::models::{Annotation, AppSettings, AppTheme, DocumentSidecar, RecentFile, SessionData};
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
@@ -191,6 +191,71 @@ pub fn save_session(session: &SessionData) {
     }
 }
 
+pub fn sidecar_path(pdf_path: &Path) -> PathBuf {
+    let mut name = pdf_path.as_os_str().to_owned();
+    name.push(".pdfbull.json");
+    PathBuf::from(name)
+}
+
+pub fn load_sidecar(pdf_path: &Path) -> Option<DocumentSidecar> {
+    let path = sidecar_path(pdf_path);
+    let data = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str::<DocumentSidecar>(&data) {
+        Ok(sidecar) => Some(sidecar),
+        Err(e) => {
+            tracing::warn!("Corrupted sidecar {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Whether the sidecar was modified after the PDF itself.
+pub fn sidecar_is_newer(pdf_path: &Path) -> bool {
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (modified(&sidecar_path(pdf_path)), modified(pdf_path)) {
+        (Some(sidecar), Some(pdf)) => sidecar > pdf,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Write the sidecar, or remove it once there is nothing left to remember.
+pub fn save_sidecar(pdf_path: &Path, sidecar: &DocumentSidecar) {
+    let path = sidecar_path(pdf_path);
+    if sidecar.bookmarks.is_empty()
+        && sidecar.annotations.is_empty()
+        && sidecar.page_rotations.is_empty()
+    {
+        if path.exists()
+            && let Err(e) = fs::remove_file(&path)
+        {
+            tracing::error!("Failed to remove sidecar {:?}: {}", path, e);
+        }
+        return;
+    }
+    if let Ok(data) = serde_json::to_string_pretty(sidecar)
+        && let Err(e) = atomic_write(&path, &data)
+    {
+        tracing::error!("Failed to save sidecar {:?}: {}", path, e);
+    }
+}
+
+/// Pick the annotation set to show when both the sidecar and the PDF carry
+/// annotations. A sidecar written after the PDF reflects the latest edits and
+/// wins; otherwise the PDF was saved (or edited elsewhere) more recently, so
+/// its embedded annotations win unless it has none.
+pub fn resolve_annotations(
+    embedded: Vec<Annotation>,
+    sidecar: Vec<Annotation>,
+    sidecar_newer: bool,
+) -> Vec<Annotation> {
+    if sidecar_newer || embedded.is_empty() {
+        sidecar
+    } else {
+        embedded
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -372,4 +437,66 @@ mod tests {
 
         assert!(files.len() <= 20);
     }
+
+    fn sample_annotation(page: usize) -> Annotation {
+        Annotation {
+            id: page as u64,
+            page,
+            style: crate::models::AnnotationStyle::Highlight {
+                color: "#FFFF00".to_string(),
+            },
+            x: 10.0,
+            y: 20.0,
+            width: 100.0,
+            height: 15.0,
+        }
+    }
+
+    #[test]
+    fn test_sidecar_path_appends_suffix() {
+        let path = sidecar_path(Path::new("/docs/report.pdf"));
+        assert_eq!(path, PathBuf::from("/docs/report.pdf.pdfbull.json"));
+    }
+
+    #[test]
+    fn test_sidecar_round_trip_and_cleanup() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf_path = dir.path().join("test_sidecar.pdf");
+
+        let sidecar = DocumentSidecar {
+            bookmarks: vec![crate::models::PageBookmark {
+                page: 2,
+                label: "Page 3".to_string(),
+                created_at: 0,
+            }],
+            annotations: vec![sample_annotation(1)],
+            page_rotations: [(0, 90)].into(),
+        };
+        save_sidecar(&pdf_path, &sidecar);
+        assert!(sidecar_is_newer(&pdf_path));
+
+        let loaded = load_sidecar(&pdf_path).unwrap();
+        assert_eq!(loaded.bookmarks.len(), 1);
+        assert_eq!(loaded.annotations[0].page, 1);
+        assert_eq!(loaded.page_rotations.get(&0), Some(&90));
+
+        save_sidecar(&pdf_path, &DocumentSidecar::default());
+        assert!(!sidecar_path(&pdf_path).exists());
+    }
+
+    #[test]
+    fn test_resolve_annotations_prefers_newer_source() {
+        let embedded = vec![sample_annotation(0)];
+        let sidecar = vec![sample_annotation(4), sample_annotation(5)];
+
+        assert_eq!(
+            resolve_annotations(embedded.clone(), sidecar.clone(), true).len(),
+            2
+        );
+        assert_eq!(
+            resolve_annotations(embedded, sidecar.clone(), false)[0].page,
+            0
+        );
+        assert_eq!(resolve_annotations(Vec::new(), sidecar, false).len(), 2);
+    }
 }
@@ -0,0 +1,154 @@
+//! Table detection with cell grid coordinates.
+//!
+//! Detection is zpdf's: the page's text spans and the ruling lines captured
+//! while interpreting its content go to [`zpdf::detect_tables_with_rules`],
+//! which takes drawn vertical rules as column separators and falls back to
+//! whitespace gutters for borderless tables. This module turns the separator
+//! positions it reports into cells with bounding boxes.
+//!
+//! Coordinates are in page space from the top-left corner, as in
+//! [`crate::stext`].
+
+use crate::stext::Rect;
+use serde::{Deserialize, Serialize};
+use zpdf::{RuleLine, TextSpan};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Cell {
+    pub r: usize,
+    pub c: usize,
+    pub bbox: Rect,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Table {
+    pub rows: usize,
+    pub cols: usize,
+    pub bbox: Rect,
+    /// Row-major, one per grid position.
+    pub cells: Vec<Cell>,
+}
+
+impl Table {
+    /// The cell grid of a table found by zpdf on a page `page_height` tall.
+    pub fn from_detected(table: &zpdf::Table, page_height: f32) -> Self {
+        let rect = |x0: f64, x1: f64, top: f64, bottom: f64| Rect {
+            x0: x0 as f32,
+            y0: page_height - top as f32,
+            x1: x1 as f32,
+            y1: page_height - bottom as f32,
+        };
+        let (x0, y0, x1, y1) = table.bbox();
+        let cells = table
+            .cells
+            .iter()
+            .zip(table.row_y.windows(2))
+            .enumerate()
+            .flat_map(|(r, (row, ys))| {
+                row.iter()
+                    .zip(table.col_x.windows(2))
+                    .enumerate()
+                    .map(move |(c, (text, xs))| Cell {
+                        r,
+                        c,
+                        bbox: rect(xs[0], xs[1], ys[0], ys[1]),
+                        text: text.clone(),
+                    })
+            })
+            .collect();
+        Self {
+            rows: table.rows(),
+            cols: table.cols(),
+            bbox: rect(x0, x1, y1, y0),
+            cells,
+        }
+    }
+}
+
+/// Tables among `spans`, with `rules` as drawn grid evidence, top to bottom.
+pub fn detect_tables(spans: &[TextSpan], rules: &[RuleLine], page_height: f32) -> Vec<Table> {
+    zpdf::detect_tables_with_rules(spans, rules)
+        .iter()
+        .map(|t| Table::from_detected(t, page_height))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_HEIGHT: f32 = 792.0;
+
+    fn span(text: &str, x: f64, baseline: f64) -> TextSpan {
+        TextSpan {
+            text: text.to_string(),
+            x,
+            y: f64::from(PAGE_HEIGHT) - baseline,
+            size: 10.0,
+            advance: 6.0 * text.len() as f64,
+            mcid: None,
+        }
+    }
+
+    fn rule(vertical: bool, pos: f64, start: f64, end: f64) -> RuleLine {
+        RuleLine {
+            vertical,
+            pos,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_bordered_table_grid() {
+        // Three rows and two columns, 100 from the top of the page, boxed by
+        // four horizontal and three vertical rules.
+        let mut rules: Vec<RuleLine> = [692.0, 672.0, 652.0, 632.0]
+            .into_iter()
+            .map(|y| rule(false, y, 72.0, 272.0))
+            .collect();
+        rules.extend([72.0, 172.0, 272.0].map(|x| rule(true, x, 632.0, 692.0)));
+        let spans = [
+            span("Name", 76.0, 115.0),
+            span("Qty", 176.0, 115.0),
+            span("Apples", 76.0, 135.0),
+            span("3", 176.0, 135.0),
+            span("Pears", 76.0, 155.0),
+            span("12", 176.0, 155.0),
+        ];
+        let tables = detect_tables(&spans, &rules, PAGE_HEIGHT);
+        assert_eq!(tables.len(), 1);
+        let table = &tables[0];
+        assert_eq!((table.rows, table.cols), (3, 2));
+        assert_eq!(table.cells.len(), 6);
+        let texts: Vec<&str> = table.cells.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["Name", "Qty", "Apples", "3", "Pears", "12"]);
+        assert_eq!((table.cells[3].r, table.cells[3].c), (1, 1));
+        let cell = &table.cells[3].bbox;
+        assert!(cell.x0 < 176.0 && cell.x1 > 182.0 && cell.y0 < 126.0 && cell.y1 > 135.0);
+        assert!(table.bbox.y0 < table.bbox.y1);
+    }
+
+    #[test]
+    fn test_borderless_table_from_gaps() {
+        let mut spans = Vec::new();
+        for (row, baseline) in [100.0, 112.0, 124.0, 136.0].into_iter().enumerate() {
+            spans.push(span(&format!("item {row}"), 72.0, baseline));
+            spans.push(span("x", 160.0, baseline));
+            spans.push(span(&format!("{row}.00"), 240.0, baseline));
+        }
+        let tables = detect_tables(&spans, &[], PAGE_HEIGHT);
+        assert_eq!(tables.len(), 1);
+        assert_eq!((tables[0].rows, tables[0].cols), (4, 3));
+        assert_eq!(tables[0].cells[11].text, "3.00");
+    }
+
+    #[test]
+    fn test_plain_text_is_not_a_table() {
+        let spans: Vec<TextSpan> = (0..5)
+            .map(|i| span("Just a line of prose", 72.0, 100.0 + 12.0 * f64::from(i)))
+            .collect();
+        assert!(detect_tables(&spans, &[], PAGE_HEIGHT).is_empty());
+    }
+}
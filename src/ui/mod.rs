@@ -115,9 +115,46 @@ impl<'a> canvas::Program<crate::message::Message> for SignatureCanvasProgram<'a>
     }
 }
 
+/// Wrap dialog content in the shared modal card and dim the window behind it.
+fn modal_container<'a>(
+    content: impl Into<Element<'a, crate::message::Message>>,
+    width: f32,
+) -> Element<'a, crate::message::Message> {
+    let card = container(content)
+        .padding(25)
+        .width(Length::Fixed(width))
+        .style(|_| container::Style {
+            background: Some(Color::from_rgb8(30, 32, 36).into()),
+            border: Border {
+                radius: theme::BORDER_RADIUS_LG.into(),
+                width: 1.0,
+                color: Color::from_rgb8(54, 56, 62),
+            },
+            shadow: Shadow {
+                color: Color::from_rgba(0.0, 0.0, 0.0, 0.45),
+                offset: Vector::new(0.0, 8.0),
+                blur_radius: 18.0,
+            },
+            ..Default::default()
+        });
+
+    container(card)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_x(Length::Fill)
+        .center_y(Length::Fill)
+        .style(|_| container::Style {
+            background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.65).into()),
+            ..Default::default()
+        })
+        .into()
+}
+
 // ── Overlay Modal: Watermark Prompt ──────────────────────────────────────────
 fn watermark_prompt_view(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
-    let modal_content = container(
+    let opacity = app.watermark_options.opacity;
+    let angle = app.watermark_options.angle_degrees;
+    modal_container(
         column![
             text("🏷️ Add Document Watermark")
                 .size(18)
@@ -138,6 +175,29 @@ fn watermark_prompt_view(app: &PdfBullApp) -> Element<'_, crate::message::Messag
                 .on_submit(crate::message::Message::SubmitWatermark)
                 .padding(10)
                 .size(14),
+            row![
+                text(format!("Opacity: {:.0}%", opacity * 100.0))
+                    .size(12)
+                    .font(INTER_BOLD),
+                button("-")
+                    .on_press(crate::message::Message::SetWatermarkOpacity(opacity - 0.05))
+                    .padding([2, 6]),
+                button("+")
+                    .on_press(crate::message::Message::SetWatermarkOpacity(opacity + 0.05))
+                    .padding([2, 6]),
+                Space::new().width(Length::Fixed(16.0)),
+                text(format!("Angle: {angle:.0}°"))
+                    .size(12)
+                    .font(INTER_BOLD),
+                button("-")
+                    .on_press(crate::message::Message::SetWatermarkAngle(angle - 15.0))
+                    .padding([2, 6]),
+                button("+")
+                    .on_press(crate::message::Message::SetWatermarkAngle(angle + 15.0))
+                    .padding([2, 6]),
+            ]
+            .spacing(4)
+            .align_y(Alignment::Center),
             Space::new().height(16),
             row![
                 button(text("Cancel").size(13).font(INTER_REGULAR))
@@ -161,39 +221,13 @@ fn watermark_prompt_view(app: &PdfBullApp) -> Element<'_, crate::message::Messag
             .align_y(Alignment::Center)
         ]
         .spacing(10),
+        440.0,
     )
-    .padding(25)
-    .width(Length::Fixed(440.0))
-    .style(|_| container::Style {
-        background: Some(Color::from_rgb8(30, 32, 36).into()),
-        border: Border {
-            radius: theme::BORDER_RADIUS_LG.into(),
-            width: 1.0,
-            color: Color::from_rgb8(54, 56, 62),
-        },
-        shadow: Shadow {
-            color: Color::from_rgba(0.0, 0.0, 0.0, 0.45),
-            offset: Vector::new(0.0, 8.0),
-            blur_radius: 18.0,
-        },
-        ..Default::default()
-    });
-
-    container(modal_content)
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .center_x(Length::Fill)
-        .center_y(Length::Fill)
-        .style(|_| container::Style {
-            background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.65).into()),
-            ..Default::default()
-        })
-        .into()
 }
 
 // ── Overlay Modal: Password Prompt ───────────────────────────────────────────
 fn password_prompt_view(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
-    let modal_content = container(
+    modal_container(
         column![
             text("🔑 Enter Password")
                 .size(18)
@@ -238,34 +272,408 @@ fn password_prompt_view(app: &PdfBullApp) -> Element<'_, crate::message::Message
             .align_y(Alignment::Center)
         ]
         .spacing(10),
+        440.0,
     )
-    .padding(25)
-    .width(Length::Fixed(440.0))
-    .style(|_| container::Style {
-        background: Some(Color::from_rgb8(30, 32, 36).into()),
-        border: Border {
-            radius: theme::BORDER_RADIUS_LG.into(),
-            width: 1.0,
-            color: Color::from_rgb8(54, 56, 62),
-        },
-        shadow: Shadow {
-            color: Color::from_rgba(0.0, 0.0, 0.0, 0.45),
-            offset: Vector::new(0.0, 8.0),
-            blur_radius: 18.0,
-        },
-        ..Default::default()
+}
+
+// ── Overlay Modal: Note Editor ───────────────────────────────────────────────
+fn note_editor_view(draft: &str) -> Element<'_, crate::message::Message> {
+    modal_container(
+        column![
+            text("📝 Edit Note")
+                .size(18)
+                .font(INTER_BOLD)
+                .style(|_| text::Style {
+                    color: Some(Color::WHITE)
+                }),
+            Space::new().height(12),
+            text_input("Note content...", draft)
+                .on_input(crate::message::Message::NoteEditorChanged)
+                .on_submit(crate::message::Message::SaveNoteEditor)
+                .padding(10)
+                .size(14),
+            Space::new().height(16),
+            row![
+                button(text("Cancel").size(13).font(INTER_REGULAR))
+                    .on_press(crate::message::Message::CloseNoteEditor)
+                    .style(theme::button_ghost)
+                    .padding([8, 16]),
+                Space::new().width(Length::Fill),
+                button(text("Save").size(13).font(INTER_BOLD))
+                    .on_press(crate::message::Message::SaveNoteEditor)
+                    .padding([8, 16])
+                    .style(|_theme, _status| button::Style {
+                        background: Some(theme::COLOR_ACCENT.into()),
+                        text_color: Color::WHITE,
+                        border: Border {
+                            radius: theme::BORDER_RADIUS_MD.into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+            ]
+            .align_y(Alignment::Center)
+        ]
+        .spacing(10),
+        440.0,
+    )
+}
+
+// ── Overlay Modal: Print Range ───────────────────────────────────────────────
+fn print_dialog_view(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
+    use crate::models::PrintRange;
+
+    let range_button = |label: &'static str, range: PrintRange| {
+        button(text(label).size(13).font(INTER_REGULAR))
+            .on_press(crate::message::Message::SetPrintRange(range))
+            .style(theme::button_ribbon_tab(app.print_range == range))
+            .padding([6, 14])
+    };
+
+    let custom_input = text_input("e.g. 1-3,7", &app.print_range_input)
+        .on_input_maybe(
+            (app.print_range == PrintRange::Custom)
+                .then_some(crate::message::Message::PrintRangeInputChanged),
+        )
+        .on_submit(crate::message::Message::ConfirmPrint)
+        .padding(10)
+        .size(14);
+
+    modal_container(
+        column![
+            text("🖨️ Print")
+                .size(18)
+                .font(INTER_BOLD)
+                .style(|_| text::Style {
+                    color: Some(Color::WHITE)
+                }),
+            Space::new().height(6),
+            text("Choose which pages to print:")
+                .size(13)
+                .font(INTER_REGULAR)
+                .style(|_| text::Style {
+                    color: Some(theme::COLOR_TEXT_DIM)
+                }),
+            row![
+                range_button("All pages", PrintRange::All),
+                range_button("Current page", PrintRange::Current),
+                range_button("Custom", PrintRange::Custom),
+            ]
+            .spacing(6),
+            custom_input,
+            Space::new().height(16),
+            row![
+                button(text("Cancel").size(13).font(INTER_REGULAR))
+                    .on_press(crate::message::Message::CancelPrint)
+                    .style(theme::button_ghost)
+                    .padding([8, 16]),
+                Space::new().width(Length::Fill),
+                button(text("Print").size(13).font(INTER_BOLD))
+                    .on_press(crate::message::Message::ConfirmPrint)
+                    .padding([8, 16])
+                    .style(|_theme, _status| button::Style {
+                        background: Some(theme::COLOR_ACCENT.into()),
+                        text_color: Color::WHITE,
+                        border: Border {
+                            radius: theme::BORDER_RADIUS_MD.into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+            ]
+            .align_y(Alignment::Center)
+        ]
+        .spacing(10),
+        440.0,
+    )
+}
+
+// ── Overlay Modal: Extract Pages ─────────────────────────────────────────────
+fn extract_dialog_view(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
+    let total = app.current_tab().map_or(0, |t| t.total_pages);
+    modal_container(
+        column![
+            text("📄 Extract Pages")
+                .size(18)
+                .font(INTER_BOLD)
+                .style(|_| text::Style {
+                    color: Some(Color::WHITE)
+                }),
+            Space::new().height(6),
+            text(format!(
+                "Pages to copy into a new PDF (1-{total}), e.g. 1-3,7:"
+            ))
+            .size(13)
+            .font(INTER_REGULAR)
+            .style(|_| text::Style {
+                color: Some(theme::COLOR_TEXT_DIM)
+            }),
+            Space::new().height(12),
+            text_input("e.g. 1-3,7", &app.extract_range_input)
+                .on_input(crate::message::Message::ExtractRangeInputChanged)
+                .on_submit(crate::message::Message::ExtractPages)
+                .padding(10)
+                .size(14),
+            Space::new().height(16),
+            row![
+                button(text("Cancel").size(13).font(INTER_REGULAR))
+                    .on_press(crate::message::Message::ToggleExtractDialog(false))
+                    .style(theme::button_ghost)
+                    .padding([8, 16]),
+                Space::new().width(Length::Fill),
+                button(text("Extract").size(13).font(INTER_BOLD))
+                    .on_press(crate::message::Message::ExtractPages)
+                    .padding([8, 16])
+                    .style(|_theme, _status| button::Style {
+                        background: Some(theme::COLOR_ACCENT.into()),
+                        text_color: Color::WHITE,
+                        border: Border {
+                            radius: theme::BORDER_RADIUS_MD.into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+            ]
+            .align_y(Alignment::Center)
+        ]
+        .spacing(10),
+        440.0,
+    )
+}
+
+// ── Overlay Modal: N-up ──────────────────────────────────────────────────────
+fn nup_dialog_view(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
+    let total = app.current_tab().map_or(0, |t| t.total_pages);
+    let layout = app.nup_layout;
+    let sheets = total.div_ceil(layout.per_sheet().max(1));
+    let grid_buttons =
+        [(2, 1), (2, 2), (3, 3), (4, 4)]
+            .into_iter()
+            .fold(row![].spacing(8), |r, (cols, rows)| {
+                let selected = layout.cols == cols && layout.rows == rows;
+                r.push(
+                    button(
+                        text(format!("{}-up", cols * rows))
+                            .size(13)
+                            .font(INTER_BOLD),
+                    )
+                    .on_press(crate::message::Message::SetNupGrid(cols, rows))
+                    .padding([6, 12])
+                    .style(theme::button_ribbon_tab(selected)),
+                )
+            });
+    modal_container(
+        column![
+            text("🔲 Pages per Sheet")
+                .size(18)
+                .font(INTER_BOLD)
+                .style(|_| text::Style {
+                    color: Some(Color::WHITE)
+                }),
+            Space::new().height(6),
+            text(format!(
+                "{} x {} grid on Letter: {total} pages onto {sheets} sheets",
+                layout.cols, layout.rows
+            ))
+            .size(13)
+            .font(INTER_REGULAR)
+            .style(|_| text::Style {
+                color: Some(theme::COLOR_TEXT_DIM)
+            }),
+            Space::new().height(12),
+            grid_buttons,
+            Space::new().height(16),
+            row![
+                button(text("Cancel").size(13).font(INTER_REGULAR))
+                    .on_press(crate::message::Message::ToggleNupDialog(false))
+                    .style(theme::button_ghost)
+                    .padding([8, 16]),
+                Space::new().width(Length::Fill),
+                button(text("Save").size(13).font(INTER_BOLD))
+                    .on_press(crate::message::Message::ImposeNup)
+                    .padding([8, 16])
+                    .style(|_theme, _status| button::Style {
+                        background: Some(theme::COLOR_ACCENT.into()),
+                        text_color: Color::WHITE,
+                        border: Border {
+                            radius: theme::BORDER_RADIUS_MD.into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+            ]
+            .align_y(Alignment::Center)
+        ]
+        .spacing(10),
+        440.0,
+    )
+}
+
+// ── Overlay Modal: Poster ────────────────────────────────────────────────────
+fn poster_dialog_view(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
+    use crate::flow::PageSize;
+
+    let paper_buttons = [
+        ("Letter", PageSize::LETTER),
+        ("Legal", PageSize::LEGAL),
+        ("A4", PageSize::A4),
+    ]
+    .into_iter()
+    .fold(row![].spacing(8), |r, (label, paper)| {
+        r.push(
+            button(text(label).size(13).font(INTER_BOLD))
+                .on_press(crate::message::Message::SetPosterPaper(paper))
+                .padding([6, 12])
+                .style(theme::button_ribbon_tab(app.poster_paper == paper)),
+        )
+    });
+    let overlap_buttons = [
+        ("None", 0.0),
+        ("¼ in", 18.0),
+        ("½ in", 36.0),
+        ("1 in", 72.0),
+    ]
+    .into_iter()
+    .fold(row![].spacing(8), |r, (label, overlap)| {
+        r.push(
+            button(text(label).size(13).font(INTER_BOLD))
+                .on_press(crate::message::Message::SetPosterOverlap(overlap))
+                .padding([6, 12])
+                .style(theme::button_ribbon_tab(app.poster_overlap == overlap)),
+        )
     });
+    let dim = |label: &'static str| {
+        text(label)
+            .size(13)
+            .font(INTER_REGULAR)
+            .style(|_| text::Style {
+                color: Some(theme::COLOR_TEXT_DIM),
+            })
+    };
+    modal_container(
+        column![
+            text("🧩 Poster")
+                .size(18)
+                .font(INTER_BOLD)
+                .style(|_| text::Style {
+                    color: Some(Color::WHITE)
+                }),
+            Space::new().height(6),
+            dim("Paper for each tile:"),
+            paper_buttons,
+            dim("Overlap between neighbouring tiles:"),
+            overlap_buttons,
+            Space::new().height(16),
+            row![
+                button(text("Cancel").size(13).font(INTER_REGULAR))
+                    .on_press(crate::message::Message::TogglePosterDialog(false))
+                    .style(theme::button_ghost)
+                    .padding([8, 16]),
+                Space::new().width(Length::Fill),
+                button(text("Save").size(13).font(INTER_BOLD))
+                    .on_press(crate::message::Message::MakePoster)
+                    .padding([8, 16])
+                    .style(|_theme, _status| button::Style {
+                        background: Some(theme::COLOR_ACCENT.into()),
+                        text_color: Color::WHITE,
+                        border: Border {
+                            radius: theme::BORDER_RADIUS_MD.into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+            ]
+            .align_y(Alignment::Center)
+        ]
+        .spacing(10),
+        440.0,
+    )
+}
 
-    container(modal_content)
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .center_x(Length::Fill)
-        .center_y(Length::Fill)
-        .style(|_| container::Style {
-            background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.65).into()),
-            ..Default::default()
-        })
-        .into()
+// ── Overlay Modal: Barcode ───────────────────────────────────────────────────
+fn barcode_dialog_view(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
+    use crate::barcode::{BarcodeKind, QrErrorCorrection};
+
+    let kind = app.barcode_kind;
+    let choice = |label: &'static str, value: BarcodeKind| {
+        button(text(label).size(13).font(INTER_BOLD))
+            .on_press(crate::message::Message::SetBarcodeKind(value))
+            .padding([6, 12])
+            .style(theme::button_ribbon_tab(kind == value))
+    };
+    let mut kinds = row![
+        choice("Code 128", BarcodeKind::Code128),
+        choice(
+            "QR",
+            BarcodeKind::Qr(match kind {
+                BarcodeKind::Qr(level) => level,
+                BarcodeKind::Code128 => QrErrorCorrection::default(),
+            })
+        ),
+    ]
+    .spacing(8)
+    .align_y(Alignment::Center);
+    if matches!(kind, BarcodeKind::Qr(_)) {
+        kinds = kinds
+            .push(Space::new().width(Length::Fixed(12.0)))
+            .push(text("Recovery:").size(12).font(INTER_REGULAR));
+        for (label, level) in [
+            ("L", QrErrorCorrection::Low),
+            ("M", QrErrorCorrection::Medium),
+            ("Q", QrErrorCorrection::Quartile),
+            ("H", QrErrorCorrection::High),
+        ] {
+            kinds = kinds.push(choice(label, BarcodeKind::Qr(level)));
+        }
+    }
+
+    modal_container(
+        column![
+            text("▦ Add Barcode")
+                .size(18)
+                .font(INTER_BOLD)
+                .style(|_| text::Style {
+                    color: Some(Color::WHITE)
+                }),
+            Space::new().height(6),
+            text("Stamped at the bottom-left of the current page.")
+                .size(13)
+                .font(INTER_REGULAR)
+                .style(|_| text::Style {
+                    color: Some(theme::COLOR_TEXT_DIM)
+                }),
+            Space::new().height(12),
+            kinds,
+            text_input("Text or URL to encode", &app.barcode_input)
+                .on_input(crate::message::Message::BarcodeInputChanged)
+                .on_submit(crate::message::Message::AddBarcode)
+                .padding(10)
+                .size(14),
+            Space::new().height(16),
+            row![
+                button(text("Cancel").size(13).font(INTER_REGULAR))
+                    .on_press(crate::message::Message::ToggleBarcodeDialog(false))
+                    .style(theme::button_ghost)
+                    .padding([8, 16]),
+                Space::new().width(Length::Fill),
+                button(text("Add").size(13).font(INTER_BOLD))
+                    .on_press(crate::message::Message::AddBarcode)
+                    .padding([8, 16])
+                    .style(|_theme, _status| button::Style {
+                        background: Some(theme::COLOR_ACCENT.into()),
+                        text_color: Color::WHITE,
+                        border: Border {
+                            radius: theme::BORDER_RADIUS_MD.into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+            ]
+            .align_y(Alignment::Center)
+        ]
+        .spacing(10),
+        440.0,
+    )
 }
 
 // ── Overlay Modal: Signature Creator ─────────────────────────────────────────
@@ -277,7 +685,7 @@ fn signature_creator_view(app: &PdfBullApp) -> Element<'_, crate::message::Messa
     .width(Length::Fill)
     .height(Length::Fixed(220.0));
 
-    let modal_content = container(
+    modal_container(
         column![
             text("✍️ Create Digital Signature")
                 .size(18)
@@ -328,34 +736,8 @@ fn signature_creator_view(app: &PdfBullApp) -> Element<'_, crate::message::Messa
             .align_y(Alignment::Center)
         ]
         .spacing(10),
+        500.0,
     )
-    .padding(25)
-    .width(Length::Fixed(500.0))
-    .style(|_| container::Style {
-        background: Some(Color::from_rgb8(30, 32, 36).into()),
-        border: Border {
-            radius: theme::BORDER_RADIUS_LG.into(),
-            width: 1.0,
-            color: Color::from_rgb8(54, 56, 62),
-        },
-        shadow: Shadow {
-            color: Color::from_rgba(0.0, 0.0, 0.0, 0.45),
-            offset: Vector::new(0.0, 8.0),
-            blur_radius: 18.0,
-        },
-        ..Default::default()
-    });
-
-    container(modal_content)
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .center_x(Length::Fill)
-        .center_y(Length::Fill)
-        .style(|_| container::Style {
-            background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.65).into()),
-            ..Default::default()
-        })
-        .into()
 }
 
 // ── View: Page Organizer Grid ────────────────────────────────────────────────
@@ -671,7 +1053,7 @@ fn signatures_detail_view(app: &PdfBullApp) -> Element<'_, crate::message::Messa
         }
     }
 
-    let modal_content = container(
+    modal_container(
         column![
             text("✍️ Digital Signatures")
                 .size(18)
@@ -707,34 +1089,8 @@ fn signatures_detail_view(app: &PdfBullApp) -> Element<'_, crate::message::Messa
             .align_y(Alignment::Center)
         ]
         .spacing(10),
+        500.0,
     )
-    .padding(25)
-    .width(Length::Fixed(500.0))
-    .style(|_| container::Style {
-        background: Some(Color::from_rgb8(30, 32, 36).into()),
-        border: Border {
-            radius: theme::BORDER_RADIUS_LG.into(),
-            width: 1.0,
-            color: Color::from_rgb8(54, 56, 62),
-        },
-        shadow: Shadow {
-            color: Color::from_rgba(0.0, 0.0, 0.0, 0.45),
-            offset: Vector::new(0.0, 8.0),
-            blur_radius: 18.0,
-        },
-        ..Default::default()
-    });
-
-    container(modal_content)
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .center_x(Length::Fill)
-        .center_y(Length::Fill)
-        .style(|_| container::Style {
-            background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.65).into()),
-            ..Default::default()
-        })
-        .into()
 }
 
 // ── Central UI View Coordinator ──────────────────────────────────────────────
@@ -777,5 +1133,29 @@ pub fn view(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
         base_stack = base_stack.push(signatures_detail_view(app));
     }
 
+    if app.show_print_dialog {
+        base_stack = base_stack.push(print_dialog_view(app));
+    }
+
+    if app.show_extract_dialog {
+        base_stack = base_stack.push(extract_dialog_view(app));
+    }
+
+    if app.show_nup_dialog {
+        base_stack = base_stack.push(nup_dialog_view(app));
+    }
+
+    if app.show_barcode_dialog {
+        base_stack = base_stack.push(barcode_dialog_view(app));
+    }
+
+    if app.show_poster_dialog {
+        base_stack = base_stack.push(poster_dialog_view(app));
+    }
+
+    if let Some((_, draft)) = &app.note_editor {
+        base_stack = base_stack.push(note_editor_view(draft));
+    }
+
     base_stack.into()
 }
@@ -274,27 +274,46 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                     Some(format!("{}", tab.outline.len())),
                 ));
                 let mut list_col = column![].spacing(4);
-                for bookmark in &tab.outline {
+                for idx in tab.visible_outline_indices() {
+                    let bookmark = &tab.outline[idx];
+                    let toggle: Element<'_, crate::message::Message> =
+                        if tab.outline_has_children(idx) {
+                            let glyph = if tab.outline_collapsed.contains(&idx) {
+                                "▸"
+                            } else {
+                                "▾"
+                            };
+                            button(text(glyph).size(12))
+                                .on_press(crate::message::Message::ToggleOutlineNode(idx))
+                                .style(theme::button_ghost)
+                                .padding([6, 4])
+                                .into()
+                        } else {
+                            Space::new().width(20).into()
+                        };
+
                     list_col = list_col.push(
-                        button(
-                            row![
-                                text("🔖").size(12),
-                                text(&bookmark.title)
-                                    .size(12)
-                                    .font(INTER_REGULAR)
-                                    .style(|_| text::Style {
-                                        color: Some(theme::COLOR_TEXT_PRIMARY)
-                                    }),
-                            ]
-                            .spacing(6)
-                            .align_y(Alignment::Center),
-                        )
-                        .on_press(crate::message::Message::JumpToPage(
-                            bookmark.page_index as usize,
-                        ))
-                        .style(theme::button_ghost)
-                        .padding([6, 8])
-                        .width(Length::Fill),
+                        row![
+                            Space::new().width(bookmark.depth.min(8) as f32 * 12.0),
+                            toggle,
+                            button(
+                                row![
+                                    text("🔖").size(12),
+                                    text(&bookmark.title).size(12).font(INTER_REGULAR).style(
+                                        |_| text::Style {
+                                            color: Some(theme::COLOR_TEXT_PRIMARY)
+                                        }
+                                    ),
+                                ]
+                                .spacing(6)
+                                .align_y(Alignment::Center),
+                            )
+                            .on_press(crate::message::Message::JumpToOutline(idx))
+                            .style(theme::button_ghost)
+                            .padding([6, 8])
+                            .width(Length::Fill),
+                        ]
+                        .align_y(Alignment::Center),
                     );
                 }
                 outline_col = outline_col.push(list_col);
@@ -371,6 +390,7 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                         AnnotationStyle::Circle { .. } => format!("Circle Page {}", ann.page + 1),
                         AnnotationStyle::Line { .. } => format!("Line Page {}", ann.page + 1),
                         AnnotationStyle::Arrow { .. } => format!("Arrow Page {}", ann.page + 1),
+                        AnnotationStyle::Ink { .. } => format!("Ink Page {}", ann.page + 1),
                         AnnotationStyle::StickyNote { comment, .. } => {
                             format!("Sticky: {}", &comment[..comment.len().min(18)])
                         }
@@ -384,6 +404,7 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                         AnnotationStyle::Circle { .. } => "⭕",
                         AnnotationStyle::Line { .. } => "📏",
                         AnnotationStyle::Arrow { .. } => "➡️",
+                        AnnotationStyle::Ink { .. } => "✍️",
                         AnnotationStyle::StickyNote { .. } => "📌",
                     };
 
@@ -752,6 +773,20 @@ pub fn render_forms(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                     ..Default::default()
                 }),
         );
+        fields_col = fields_col.push(
+            button(text("📥 Fill from JSON…").font(INTER_BOLD).size(12))
+                .on_press(crate::message::Message::ImportFormValues)
+                .width(Length::Fill)
+                .padding(10)
+                .style(theme::button_ghost),
+        );
+        fields_col = fields_col.push(
+            button(text("📌 Flatten Form").font(INTER_BOLD).size(12))
+                .on_press(crate::message::Message::FlattenForms)
+                .width(Length::Fill)
+                .padding(10)
+                .style(theme::button_ghost),
+        );
     }
 
     scrollable(fields_col)
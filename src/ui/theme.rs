@@ -36,6 +36,7 @@ pub const COLOR_TEXT_SECONDARY: Color = Color::from_rgb(0.42, 0.45, 0.50); // #6
 // Colors - Status Badges
 pub const COLOR_SUCCESS: Color = Color::from_rgb(0.06, 0.73, 0.49); // #10B981 Emerald Green
 pub const COLOR_WARNING: Color = Color::from_rgb(0.96, 0.62, 0.04); // #F59E0B Amber Warning
+pub const COLOR_DANGER: Color = Color::from_rgb(0.94, 0.27, 0.27); // #EF4444 Red Error
 
 // Reusable Styles
 pub fn button_ribbon_tab(
@@ -161,6 +162,13 @@ pub fn input_field(_theme: &iced::Theme) -> iced::widget::container::Style {
     }
 }
 
+pub fn input_field_error(theme: &iced::Theme) -> iced::widget::container::Style {
+    let mut style = input_field(theme);
+    style.border.color = COLOR_DANGER;
+    style.border.width = 1.5;
+    style
+}
+
 pub fn hex_to_rgb(hex: &str) -> (f32, f32, f32) {
     let hex = hex.trim();
     let hex_digits = hex.strip_prefix('#').unwrap_or(hex);
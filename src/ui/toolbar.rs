@@ -243,7 +243,11 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                     .padding([2, 6])
                     .align_y(Alignment::Center)
                 )
-                .style(theme::input_field),
+                .style(if app.page_input_error {
+                    theme::input_field_error
+                } else {
+                    theme::input_field
+                }),
                 button(text(icons::NEXT).size(14).font(LUCIDE))
                     .on_press(crate::message::Message::NextPage)
                     .style(theme::button_ghost)
@@ -322,16 +326,48 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                 "Toggle Midnight Mode (Color Inversion)",
             );
 
+            let reflow_controls =
+                (tab.format == crate::pdf_engine::DocumentFormat::Epub).then(|| {
+                    let em = tab.reflow_layout.em;
+                    row![
+                        tool_button_emoji(
+                            "🔡",
+                            "Smaller Text",
+                            crate::message::Message::SetReflowTextSize(em - 2.0),
+                            false,
+                            "Lay the book out again with smaller text"
+                        ),
+                        tool_button_emoji(
+                            "🔠",
+                            "Larger Text",
+                            crate::message::Message::SetReflowTextSize(em + 2.0),
+                            false,
+                            "Lay the book out again with larger text"
+                        ),
+                        v_sep(),
+                    ]
+                    .spacing(12)
+                    .align_y(Alignment::Center)
+                });
+
             container(
                 row![
                     zoom_control(tab.zoom),
                     v_sep(),
+                    reflow_controls,
                     tool_button(
                         icons::ROTATE,
                         "Rotate 90°",
                         crate::message::Message::RotateClockwise,
                         false,
-                        "Rotate page 90° clockwise"
+                        "Rotate all pages 90° clockwise"
+                    ),
+                    tool_button(
+                        icons::ROTATE,
+                        "Rotate Page",
+                        crate::message::Message::RotatePageClockwise(tab.current_page),
+                        false,
+                        "Rotate only the current page 90° clockwise"
                     ),
                     v_sep(),
                     midnight_btn,
@@ -404,6 +440,13 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                     app.annotation_mode == Some(PendingAnnotationKind::Arrow),
                     "Draw directional arrow"
                 ),
+                tool_button_emoji(
+                    "✍️",
+                    "Ink",
+                    crate::message::Message::SetAnnotationMode(Some(PendingAnnotationKind::Ink)),
+                    app.annotation_mode == Some(PendingAnnotationKind::Ink),
+                    "Draw freehand ink"
+                ),
                 tool_button_emoji(
                     "📌",
                     "Note",
@@ -596,6 +639,20 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                     false,
                     "Overlay custom text watermark across document pages"
                 ),
+                tool_button_emoji(
+                    "📊",
+                    "Chart",
+                    crate::message::Message::AddChart,
+                    false,
+                    "Draw a bar or line chart from a JSON spec onto this page"
+                ),
+                tool_button_emoji(
+                    "▦",
+                    "Barcode",
+                    crate::message::Message::ToggleBarcodeDialog(true),
+                    app.show_barcode_dialog,
+                    "Stamp a QR code or Code 128 barcode on this page"
+                ),
                 tool_button_emoji(
                     "✍️",
                     "Signature",
@@ -610,12 +667,68 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                     false,
                     "Combine multiple PDF files into one"
                 ),
+                tool_button(
+                    icons::MERGE,
+                    "Merge Tabs",
+                    crate::message::Message::MergeOpenTabs,
+                    false,
+                    "Combine all open tabs, in tab order, into one PDF"
+                ),
+                tool_button_emoji(
+                    "{ }",
+                    "JSON",
+                    crate::message::Message::ExportJson,
+                    false,
+                    "Export every object and the trailer as QPDF-style JSON"
+                ),
+                tool_button_emoji(
+                    "🌐",
+                    "From HTML",
+                    crate::message::Message::ImportHtml,
+                    false,
+                    "Convert an HTML file with headings, lists and images to PDF"
+                ),
+                tool_button_emoji(
+                    "📄",
+                    "Extract",
+                    crate::message::Message::ToggleExtractDialog(true),
+                    app.show_extract_dialog,
+                    "Save a range of pages as a new PDF"
+                ),
+                tool_button_emoji(
+                    "🔲",
+                    "N-up",
+                    crate::message::Message::ToggleNupDialog(true),
+                    app.show_nup_dialog,
+                    "Print several pages per sheet"
+                ),
+                tool_button_emoji(
+                    "📑",
+                    "TOC",
+                    crate::message::Message::PrependToc,
+                    false,
+                    "Add a linked table of contents page built from the bookmarks"
+                ),
+                tool_button_emoji(
+                    "📖",
+                    "Booklet",
+                    crate::message::Message::MakeBooklet,
+                    false,
+                    "Reorder pages 2-up for folding into a booklet"
+                ),
+                tool_button_emoji(
+                    "🧩",
+                    "Poster",
+                    crate::message::Message::TogglePosterDialog(true),
+                    false,
+                    "Slice large pages into overlapping printable tiles"
+                ),
                 tool_button_emoji(
                     "✂️",
                     "Split",
-                    crate::message::Message::SplitPDF(vec![]),
+                    crate::message::Message::SplitDocument,
                     false,
-                    "Extract or split pages into separate PDF files"
+                    "Split every page into its own PDF file"
                 ),
                 tool_button_emoji(
                     "⚡",
@@ -624,6 +737,20 @@ pub fn render(app: &PdfBullApp) -> Element<'_, crate::message::Message> {
                     false,
                     "Compress streams & sanitize document metadata"
                 ),
+                tool_button_emoji(
+                    "✅",
+                    "Validate",
+                    crate::message::Message::ValidatePdf,
+                    false,
+                    "Check structure and PDF/A rules, save a JSON report"
+                ),
+                tool_button_emoji(
+                    "🩹",
+                    "Repair",
+                    crate::message::Message::RepairPdf,
+                    false,
+                    "Rebuild a damaged file's cross-reference table by scanning its objects"
+                ),
             ]
             .spacing(8)
             .align_y(Alignment::Center);
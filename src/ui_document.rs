@@ -80,7 +80,7 @@ impl<'a> canvas::Program<crate::message::Message> for AnnotationCanvas<'a> {
     ) -> Vec<canvas::Geometry> {
         let mut frame = canvas::Frame::new(renderer, bounds.size());
 
-        // 1. Draw existing Line & Arrow annotations for this page
+        // 1. Draw existing Line, Arrow & Ink annotations for this page
         for ann in self.annotations.iter().filter(|a| a.page == self.page_idx) {
             match &ann.style {
                 AnnotationStyle::Line { color, thickness } => {
@@ -187,11 +187,47 @@ impl<'a> canvas::Program<crate::message::Message> for AnnotationCanvas<'a> {
                         );
                     }
                 }
+                AnnotationStyle::Ink {
+                    color,
+                    thickness,
+                    strokes,
+                } => {
+                    let (r, g, b) = hex_to_rgb(color);
+                    let path = canvas::Path::new(|builder| {
+                        for points in strokes {
+                            for (i, &(px, py)) in points.iter().enumerate() {
+                                let (rx, ry, _, _) = crate::models::rotate_coords(
+                                    ann.x + px,
+                                    ann.y + py,
+                                    0.0,
+                                    0.0,
+                                    self.page_width,
+                                    self.page_height,
+                                    self.rotation,
+                                );
+                                let point = iced::Point::new(rx * self.zoom, ry * self.zoom);
+                                if i == 0 {
+                                    builder.move_to(point);
+                                } else {
+                                    builder.line_to(point);
+                                }
+                            }
+                        }
+                    });
+                    frame.stroke(
+                        &path,
+                        canvas::Stroke::default()
+                            .with_color(Color::from_rgb(r, g, b))
+                            .with_width(*thickness * self.zoom)
+                            .with_line_cap(canvas::LineCap::Round)
+                            .with_line_join(canvas::LineJoin::Round),
+                    );
+                }
                 _ => {}
             }
         }
 
-        // 2. Draw active dragging preview if it is a Line or Arrow
+        // 2. Draw active dragging preview if it is a Line, Arrow or Ink
         if let Some(drag) = &self.drag
             && drag.page == self.page_idx
         {
@@ -260,6 +296,24 @@ impl<'a> canvas::Program<crate::message::Message> for AnnotationCanvas<'a> {
                         );
                     }
                 }
+                PendingAnnotationKind::Ink => {
+                    let path = canvas::Path::new(|builder| {
+                        if let Some((&(x0, y0), rest)) = drag.points.split_first() {
+                            builder.move_to(iced::Point::new(x0, y0));
+                            for &(x, y) in rest {
+                                builder.line_to(iced::Point::new(x, y));
+                            }
+                        }
+                    });
+                    frame.stroke(
+                        &path,
+                        canvas::Stroke::default()
+                            .with_color(Color::from_rgb(1.0, 0.0, 0.0))
+                            .with_width(2.0 * self.zoom)
+                            .with_line_cap(canvas::LineCap::Round)
+                            .with_line_join(canvas::LineJoin::Round),
+                    );
+                }
                 _ => {}
             }
         }
@@ -278,11 +332,7 @@ fn render_annotations<'a>(
         .filter(|ann| ann.page == page_idx)
         .map(|ann| {
             let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-            let page_rotation = tab
-                .page_rotations
-                .get(&actual_page)
-                .copied()
-                .unwrap_or(tab.rotation);
+            let page_rotation = tab.effective_rotation(actual_page);
             let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
 
             let (ann_x, ann_y, ann_width, ann_height) = crate::models::rotate_coords(
@@ -304,6 +354,8 @@ fn render_annotations<'a>(
                 _ => ann_height,
             };
 
+            let ann_idx = tab.annotations.iter().position(|a| a.id == ann.id);
+
             let ann_overlay: Element<'a, crate::message::Message> = match &ann.style {
                 AnnotationStyle::Highlight { color } => {
                     let (r, g, b) = hex_to_rgb(color);
@@ -346,13 +398,18 @@ fn render_annotations<'a>(
                     font_size,
                 } => {
                     let (r, g, b) = hex_to_rgb(color);
-                    container(
+                    let label = container(
                         iced::widget::text(text.clone())
                             .size(*font_size as f32 * zoom)
                             .font(INTER_REGULAR)
                             .color(Color::from_rgb(r, g, b)),
-                    )
-                    .into()
+                    );
+                    match ann_idx {
+                        Some(idx) => mouse_area(label)
+                            .on_double_click(crate::message::Message::OpenNoteEditor(idx))
+                            .into(),
+                        None => label.into(),
+                    }
                 }
                 AnnotationStyle::Redact { color } => {
                     let (r, g, b) = hex_to_rgb(color);
@@ -395,9 +452,15 @@ fn render_annotations<'a>(
                         .height(Length::Fixed(0.0))
                         .into()
                 }
+                // The stroke itself is drawn by `AnnotationCanvas`; this box
+                // only anchors the delete button.
+                AnnotationStyle::Ink { .. } => container(Space::new())
+                    .width(Length::Fixed(display_width * zoom))
+                    .height(Length::Fixed(display_height * zoom))
+                    .into(),
                 AnnotationStyle::StickyNote { comment, color } => {
                     let (r, g, b) = hex_to_rgb(color);
-                    iced::widget::tooltip(
+                    let note = iced::widget::tooltip(
                         container(
                             iced::widget::text("📝")
                                 .size(14.0 * zoom)
@@ -418,13 +481,17 @@ fn render_annotations<'a>(
                         }),
                         iced::widget::text(comment.clone()),
                         iced::widget::tooltip::Position::Top,
-                    )
-                    .into()
+                    );
+                    match ann_idx {
+                        Some(idx) => mouse_area(note)
+                            .on_double_click(crate::message::Message::OpenNoteEditor(idx))
+                            .interaction(iced::mouse::Interaction::Pointer)
+                            .into(),
+                        None => note.into(),
+                    }
                 }
             };
 
-            let ann_idx = tab.annotations.iter().position(|a| a.id == ann.id);
-
             let mut delete_btn = button(
                 iced::widget::text("×")
                     .size(9)
@@ -451,13 +518,26 @@ fn render_annotations<'a>(
                 .align_x(Alignment::End)
                 .align_y(Alignment::Start);
 
-            let ann_stack: Element<'a, crate::message::Message> = match &ann.style {
+            let mut ann_stack: Element<'a, crate::message::Message> = match &ann.style {
                 AnnotationStyle::Line { .. } | AnnotationStyle::Arrow { .. } => ann_overlay,
                 _ => Stack::new()
                     .push(ann_overlay)
                     .push(delete_btn_overlay)
                     .into(),
             };
+            // Outline the annotation that a drag would move or resize.
+            if tab.selected_annotation == Some(ann.id) {
+                ann_stack = container(ann_stack)
+                    .style(|_| iced::widget::container::Style {
+                        border: iced::Border {
+                            color: theme::COLOR_ACCENT,
+                            width: 1.0,
+                            radius: 0.0.into(),
+                        },
+                        ..Default::default()
+                    })
+                    .into();
+            }
 
             container(ann_stack)
                 .padding(Padding {
@@ -480,11 +560,7 @@ fn render_hyperlinks<'a>(
         .filter(|link| link.page == page_idx)
         .map(|link| {
             let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-            let page_rotation = tab
-                .page_rotations
-                .get(&actual_page)
-                .copied()
-                .unwrap_or(tab.rotation);
+            let page_rotation = tab.effective_rotation(actual_page);
             let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
 
             let (lx, ly, lw, lh) = crate::models::rotate_coords(
@@ -526,11 +602,17 @@ fn render_search_highlights<'a>(
         return vec![];
     }
 
+    let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
+    let page_rotation = tab.effective_rotation(actual_page);
+    let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
+
+    // Boxes are stored in unrotated page points and projected here on every
+    // view, so zoom and rotation changes never require a new search.
     tab.search_results
         .iter()
         .enumerate()
         .filter(|(_, result)| result.page == page_idx)
-        .map(|(result_idx, result)| {
+        .flat_map(|(result_idx, result)| {
             let is_active = result_idx == tab.current_search_index;
             let highlight_color = if is_active {
                 Color::from_rgba(1.0, 0.6, 0.0, 0.6)
@@ -538,39 +620,33 @@ fn render_search_highlights<'a>(
                 Color::from_rgba(1.0, 1.0, 0.0, 0.4)
             };
 
-            let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-            let page_rotation = tab
-                .page_rotations
-                .get(&actual_page)
-                .copied()
-                .unwrap_or(tab.rotation);
-            let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
-
-            let (rx, ry, rw, rh) = crate::models::rotate_coords(
-                result.x,
-                result.y_position,
-                result.width,
-                result.height,
-                tab.page_width,
-                original_height,
-                page_rotation,
-            );
-
-            container(
-                Space::new()
-                    .width(Length::Fixed(rw * zoom))
-                    .height(Length::Fixed(rh * zoom)),
-            )
-            .style(move |_| iced::widget::container::Style {
-                background: Some(iced::Background::Color(highlight_color)),
-                ..Default::default()
-            })
-            .padding(Padding {
-                top: ry * zoom,
-                left: rx * zoom,
-                ..Default::default()
+            result.rects.iter().map(move |&(x, y, w, h)| {
+                let (rx, ry, rw, rh) = crate::models::rotate_coords(
+                    x,
+                    y,
+                    w,
+                    h,
+                    tab.page_width,
+                    original_height,
+                    page_rotation,
+                );
+
+                container(
+                    Space::new()
+                        .width(Length::Fixed(rw * zoom))
+                        .height(Length::Fixed(rh * zoom)),
+                )
+                .style(move |_| iced::widget::container::Style {
+                    background: Some(iced::Background::Color(highlight_color)),
+                    ..Default::default()
+                })
+                .padding(Padding {
+                    top: ry * zoom,
+                    left: rx * zoom,
+                    ..Default::default()
+                })
+                .into()
             })
-            .into()
         })
         .collect()
 }
@@ -614,13 +690,14 @@ fn render_selection_overlay<'a>(
     }
 
     // 2. Draw permanent selection highlight boxes for selected words
-    for &(bx, by, bw, bh) in &tab.selected_boxes {
+    let selected_boxes = if tab.selected_page == Some(page_idx) {
+        tab.selected_boxes.as_slice()
+    } else {
+        &[]
+    };
+    for &(bx, by, bw, bh) in selected_boxes {
         let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-        let page_rotation = tab
-            .page_rotations
-            .get(&actual_page)
-            .copied()
-            .unwrap_or(tab.rotation);
+        let page_rotation = tab.effective_rotation(actual_page);
         let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
 
         let (rx, ry, rw, rh) = crate::models::rotate_coords(
@@ -661,7 +738,10 @@ fn render_active_drag<'a>(
     if let Some(drag) = &app.annotation_drag
         && drag.page == page_idx
     {
-        if drag.kind == PendingAnnotationKind::Line || drag.kind == PendingAnnotationKind::Arrow {
+        if matches!(
+            drag.kind,
+            PendingAnnotationKind::Line | PendingAnnotationKind::Arrow | PendingAnnotationKind::Ink
+        ) {
             return vec![];
         }
 
@@ -677,7 +757,9 @@ fn render_active_drag<'a>(
             PendingAnnotationKind::Text => Color::from_rgba(0.0, 0.0, 1.0, 0.1),
             PendingAnnotationKind::Circle => Color::from_rgba(1.0, 0.0, 0.0, 0.2),
             PendingAnnotationKind::StickyNote => Color::from_rgba(1.0, 0.9, 0.3, 0.6),
-            PendingAnnotationKind::Line | PendingAnnotationKind::Arrow => Color::TRANSPARENT,
+            PendingAnnotationKind::Line
+            | PendingAnnotationKind::Arrow
+            | PendingAnnotationKind::Ink => Color::TRANSPARENT,
         };
 
         let preview_border = match drag.kind {
@@ -695,7 +777,9 @@ fn render_active_drag<'a>(
                 width: 2.0 * zoom,
                 radius: (w.min(h) * zoom / 2.0).into(),
             },
-            PendingAnnotationKind::Line | PendingAnnotationKind::Arrow => iced::Border::default(),
+            PendingAnnotationKind::Line
+            | PendingAnnotationKind::Arrow
+            | PendingAnnotationKind::Ink => iced::Border::default(),
         };
 
         return vec![
@@ -730,11 +814,7 @@ fn render_accessibility_layer<'a>(
         .get(&page_idx)
         .map(|items| {
             let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-            let page_rotation = tab
-                .page_rotations
-                .get(&actual_page)
-                .copied()
-                .unwrap_or(tab.rotation);
+            let page_rotation = tab.effective_rotation(actual_page);
             let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
 
             items
@@ -783,11 +863,7 @@ fn render_table_overlays<'a>(
         .get(&page_idx)
         .map(|tables| {
             let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-            let page_rotation = tab
-                .page_rotations
-                .get(&actual_page)
-                .copied()
-                .unwrap_or(tab.rotation);
+            let page_rotation = tab.effective_rotation(actual_page);
             let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
 
             tables
@@ -881,11 +957,7 @@ fn render_page_canvas<'a>(
 ) -> Element<'a, crate::message::Message> {
     let zoom = tab.zoom;
     let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-    let page_rotation = tab
-        .page_rotations
-        .get(&actual_page)
-        .copied()
-        .unwrap_or(tab.rotation);
+    let page_rotation = tab.effective_rotation(actual_page);
     let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
     let is_landscape = page_rotation % 180 != 0;
 
@@ -1185,6 +1257,14 @@ pub fn document_view<'a>(app: &'a PdfBullApp) -> Element<'a, crate::message::Mes
                     .on_press(crate::message::Message::ResetZoom)
                     .style(theme::button_ghost)
                     .padding([2, 6]),
+                text(format!(
+                    "{:.0} MB",
+                    app.render_cache_bytes() as f64 / (1024.0 * 1024.0)
+                ))
+                .size(10)
+                .style(|_| text::Style {
+                    color: Some(theme::COLOR_TEXT_SECONDARY),
+                }),
             ]
             .spacing(8)
             .padding([6, 14])
@@ -37,8 +37,10 @@ pub fn keyboard_help_view(_app: &crate::app::PdfBullApp) -> Element<'_, crate::m
                 ("Ctrl + S", "Save/Export"),
                 ("Ctrl + D", "Add Bookmark"),
                 ("Ctrl + F", "Search"),
+                ("Ctrl + C", "Copy Selected Text"),
                 ("Ctrl + B", "Toggle Sidebar"),
                 ("Ctrl + W", "Close Tab"),
+                ("Ctrl + Shift + T", "Reopen Closed Tab"),
             ]
         ),
         Space::new().height(20),
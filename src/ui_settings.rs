@@ -253,6 +253,36 @@ pub fn settings_view(app: &crate::app::PdfBullApp) -> Element<'_, crate::message
     ]
     .align_y(Alignment::Center);
 
+    let render_budget_row = row![
+        text(format!(
+            "Page memory: {} MB",
+            app.settings.max_render_cache_bytes / (1024 * 1024)
+        ))
+        .font(INTER_REGULAR)
+        .style(|_theme| {
+            iced::widget::text::Style {
+                color: Some(Color::WHITE),
+            }
+        }),
+        Space::new().width(Length::Fill),
+        action_btn("-", {
+            let mut s = app.settings.clone();
+            s.max_render_cache_bytes = s
+                .max_render_cache_bytes
+                .saturating_sub(64 * 1024 * 1024)
+                .max(128 * 1024 * 1024);
+            crate::message::Message::SaveSettings(s)
+        }),
+        Space::new().width(10),
+        action_btn("+", {
+            let mut s = app.settings.clone();
+            s.max_render_cache_bytes =
+                (s.max_render_cache_bytes + 64 * 1024 * 1024).min(2048 * 1024 * 1024);
+            crate::message::Message::SaveSettings(s)
+        }),
+    ]
+    .align_y(Alignment::Center);
+
     let appearance_card = custom_card(
         text("Appearance")
             .size(18)
@@ -302,7 +332,7 @@ pub fn settings_view(app: &crate::app::PdfBullApp) -> Element<'_, crate::message
             .style(|_theme| iced::widget::text::Style {
                 color: Some(Color::WHITE),
             }),
-        column![quality_buttons, cache_row].spacing(16),
+        column![quality_buttons, cache_row, render_budget_row].spacing(16),
     );
 
     let defaults_card = custom_card(
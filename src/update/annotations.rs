@@ -1,7 +1,195 @@
 use crate::app::PdfBullApp;
 use crate::message::Message;
+use crate::models::{
+    Annotation, AnnotationEdit, AnnotationHandle, AnnotationStyle, DocumentTab, rotate_coords,
+    unrotate_coords,
+};
 use iced::Task;
 
+/// Grab distance for corner handles, in screen pixels.
+const HANDLE_GRAB_PX: f32 = 8.0;
+/// Smallest width/height a resize may produce, in page points.
+const MIN_ANNOTATION_SIZE: f32 = 4.0;
+/// Side of a sticky note's icon, in page points.
+const NOTE_ICON_SIZE: f32 = 24.0;
+
+/// Effective rotation and unrotated height of a visual page.
+fn page_geometry(tab: &DocumentTab, page_idx: usize) -> (i32, f32) {
+    let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
+    let rotation = tab.effective_rotation(actual_page);
+    let height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
+    (rotation, height)
+}
+
+/// Bounds of an annotation as drawn, in rotated page units.
+fn visual_bounds(tab: &DocumentTab, ann: &Annotation) -> (f32, f32, f32, f32) {
+    let (rotation, page_height) = page_geometry(tab, ann.page);
+    match ann.style {
+        AnnotationStyle::Line { .. } | AnnotationStyle::Arrow { .. } => {
+            let (x1, y1, _, _) = rotate_coords(
+                ann.x,
+                ann.y,
+                0.0,
+                0.0,
+                tab.page_width,
+                page_height,
+                rotation,
+            );
+            let (x2, y2, _, _) = rotate_coords(
+                ann.x + ann.width,
+                ann.y + ann.height,
+                0.0,
+                0.0,
+                tab.page_width,
+                page_height,
+                rotation,
+            );
+            (x1.min(x2), y1.min(y2), (x1 - x2).abs(), (y1 - y2).abs())
+        }
+        _ => {
+            let (x, y, w, h) = rotate_coords(
+                ann.x,
+                ann.y,
+                ann.width,
+                ann.height,
+                tab.page_width,
+                page_height,
+                rotation,
+            );
+            if matches!(ann.style, AnnotationStyle::StickyNote { .. }) {
+                (x, y, NOTE_ICON_SIZE, NOTE_ICON_SIZE)
+            } else {
+                (x, y, w, h)
+            }
+        }
+    }
+}
+
+/// Find the topmost annotation under `(x, y)` (rotated page units) and the
+/// part of it that was grabbed.
+fn hit_test_annotation(
+    tab: &DocumentTab,
+    page_idx: usize,
+    x: f32,
+    y: f32,
+) -> Option<(usize, AnnotationHandle)> {
+    let tolerance = HANDLE_GRAB_PX / tab.zoom.max(0.01);
+    tab.annotations
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, ann)| ann.page == page_idx)
+        .find_map(|(idx, ann)| {
+            let (bx, by, bw, bh) = visual_bounds(tab, ann);
+            let resizable = matches!(
+                ann.style,
+                AnnotationStyle::Highlight { .. }
+                    | AnnotationStyle::Rectangle { .. }
+                    | AnnotationStyle::Redact { .. }
+                    | AnnotationStyle::Circle { .. }
+            );
+            if resizable {
+                let corners = [
+                    (bx, by, AnnotationHandle::TopLeft),
+                    (bx + bw, by, AnnotationHandle::TopRight),
+                    (bx, by + bh, AnnotationHandle::BottomLeft),
+                    (bx + bw, by + bh, AnnotationHandle::BottomRight),
+                ];
+                if let Some(&(_, _, handle)) = corners
+                    .iter()
+                    .find(|(cx, cy, _)| (x - cx).abs() <= tolerance && (y - cy).abs() <= tolerance)
+                {
+                    return Some((idx, handle));
+                }
+            }
+            let inside = x >= bx - tolerance
+                && x <= bx + bw + tolerance
+                && y >= by - tolerance
+                && y <= by + bh + tolerance;
+            inside.then_some((idx, AnnotationHandle::Move))
+        })
+}
+
+/// Page-space rect of `edit.original` after dragging the pointer to `current`.
+fn edited_rect(
+    tab: &DocumentTab,
+    edit: &AnnotationEdit,
+    current: (f32, f32),
+) -> (f32, f32, f32, f32) {
+    let ann = &edit.original;
+    let (rotation, page_height) = page_geometry(tab, ann.page);
+    if edit.handle == AnnotationHandle::Move {
+        let (sx, sy, _, _) = unrotate_coords(
+            edit.start.0,
+            edit.start.1,
+            0.0,
+            0.0,
+            tab.page_width,
+            page_height,
+            rotation,
+        );
+        let (cx, cy, _, _) = unrotate_coords(
+            current.0,
+            current.1,
+            0.0,
+            0.0,
+            tab.page_width,
+            page_height,
+            rotation,
+        );
+        return (ann.x + cx - sx, ann.y + cy - sy, ann.width, ann.height);
+    }
+
+    let visual = rotate_coords(
+        ann.x,
+        ann.y,
+        ann.width,
+        ann.height,
+        tab.page_width,
+        page_height,
+        rotation,
+    );
+    let (vx, vy, vw, vh) = crate::models::resize_rect(
+        visual,
+        edit.handle,
+        current.0 - edit.start.0,
+        current.1 - edit.start.1,
+        MIN_ANNOTATION_SIZE,
+    );
+    unrotate_coords(vx, vy, vw, vh, tab.page_width, page_height, rotation)
+}
+
+/// A stroke's page-space bounding box and its points relative to the box's
+/// top-left corner.
+type InkStroke = ((f32, f32, f32, f32), Vec<(f32, f32)>);
+
+/// Convert an ink pointer trail (rotated, zoomed pixels) into page space.
+fn ink_stroke_in_page(tab: &DocumentTab, page_idx: usize, trail: &[(f32, f32)]) -> InkStroke {
+    let (rotation, page_height) = page_geometry(tab, page_idx);
+    let zoom = tab.zoom.max(0.01);
+    let absolute: Vec<(f32, f32)> = trail
+        .iter()
+        .map(|&(x, y)| {
+            let (px, py, _, _) = unrotate_coords(
+                x / zoom,
+                y / zoom,
+                0.0,
+                0.0,
+                tab.page_width,
+                page_height,
+                rotation,
+            );
+            (px, py)
+        })
+        .collect();
+    let bounds = crate::models::ink_bounds(&absolute).unwrap_or_default();
+    let relative = absolute
+        .iter()
+        .map(|&(x, y)| (x - bounds.0, y - bounds.1))
+        .collect();
+    (bounds, relative)
+}
+
 #[allow(clippy::suboptimal_flops, clippy::similar_names)]
 pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
     match message {
@@ -12,6 +200,59 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
             }
             Task::none()
         }
+        Message::AnnotationDragStart { page, x, y }
+            if app.annotation_mode == Some(crate::models::PendingAnnotationKind::StickyNote)
+                && !app.signature_stamp_active =>
+        {
+            let zoom = app.current_tab().map_or(1.0, |tab| tab.zoom.max(0.01));
+            app.update(Message::AddTextNote {
+                page,
+                x: x / zoom,
+                y: y / zoom,
+            })
+        }
+        Message::AddTextNote { page, x, y } => {
+            let comment = if app.annotation_text.is_empty() {
+                "Sticky Note".to_string()
+            } else {
+                app.annotation_text.clone()
+            };
+            let Some(tab) = app.current_tab_mut() else {
+                return Task::none();
+            };
+            let (rotation, page_height) = page_geometry(tab, page);
+            let (x, y, width, height) = unrotate_coords(
+                x,
+                y,
+                NOTE_ICON_SIZE,
+                NOTE_ICON_SIZE,
+                tab.page_width,
+                page_height,
+                rotation,
+            );
+            let ann = Annotation {
+                id: crate::models::next_annotation_id(),
+                page,
+                style: AnnotationStyle::StickyNote {
+                    comment: comment.clone(),
+                    color: "#ffeb3b".to_string(),
+                },
+                x,
+                y,
+                width,
+                height,
+            };
+            tab.undo_stack
+                .push(crate::models::UndoableAction::AddAnnotation(ann.clone()));
+            tab.redo_stack.clear();
+            tab.annotations.push(ann);
+            tab.annotations_dirty = true;
+            tab.sidecar_dirty = true;
+            let index = tab.annotations.len() - 1;
+            app.annotation_edit = None;
+            app.note_editor = Some((index, comment));
+            Task::none()
+        }
         Message::AnnotationDragStart { page, x, y } => {
             if let Some(kind) = &app.annotation_mode {
                 app.annotation_drag = Some(crate::models::AnnotationDrag {
@@ -19,11 +260,32 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                     start: (x, y),
                     current: (x, y),
                     kind: *kind,
+                    points: if *kind == crate::models::PendingAnnotationKind::Ink {
+                        vec![(x, y)]
+                    } else {
+                        Vec::new()
+                    },
+                });
+            } else if let Some(tab) = app.current_tab()
+                && let Some((index, handle)) =
+                    hit_test_annotation(tab, page, x / tab.zoom, y / tab.zoom)
+                && tab.selected_annotation == Some(tab.annotations[index].id)
+            {
+                app.annotation_edit = Some(AnnotationEdit {
+                    index,
+                    handle,
+                    start: (x / tab.zoom, y / tab.zoom),
+                    original: tab.annotations[index].clone(),
                 });
             } else if let Some(tab) = app.current_tab_mut() {
+                // A press on an unselected annotation selects it and, like a
+                // press anywhere else, starts a text selection.
+                let zoom = tab.zoom;
+                tab.selected_annotation = hit_test_annotation(tab, page, x / zoom, y / zoom)
+                    .map(|(index, _)| tab.annotations[index].id);
                 tab.selected_text = None;
                 tab.selected_boxes.clear();
-                let zoom = tab.zoom;
+                tab.selected_page = None;
                 tab.selection_drag = Some((page, (x / zoom, y / zoom), (x / zoom, y / zoom)));
             }
             Task::none()
@@ -31,6 +293,23 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
         Message::AnnotationDragUpdate { x, y } => {
             if let Some(drag) = &mut app.annotation_drag {
                 drag.current = (x, y);
+                if drag.kind == crate::models::PendingAnnotationKind::Ink {
+                    drag.points.push((x, y));
+                }
+            } else if let Some(edit) = &app.annotation_edit {
+                // Preview the edit live; `AnnotationDragEnd` commits it.
+                if let Some(tab) = app.tabs.get_mut(app.active_tab) {
+                    let zoom = tab.zoom;
+                    let (nx, ny, nw, nh) = edited_rect(tab, edit, (x / zoom, y / zoom));
+                    if let Some(ann) = tab.annotations.get_mut(edit.index)
+                        && ann.id == edit.original.id
+                    {
+                        ann.x = nx;
+                        ann.y = ny;
+                        ann.width = nw;
+                        ann.height = nh;
+                    }
+                }
             } else if let Some(tab) = app.current_tab_mut() {
                 if let Some((_, _, current)) = &mut tab.selection_drag {
                     let zoom = tab.zoom;
@@ -39,6 +318,48 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
             }
             Task::none()
         }
+        Message::AnnotationDragEnd if app.annotation_edit.is_some() => {
+            let Some(edit) = app.annotation_edit.take() else {
+                return Task::none();
+            };
+            let Some(tab) = app.current_tab_mut() else {
+                return Task::none();
+            };
+            let Some(ann) = tab.annotations.get_mut(edit.index) else {
+                return Task::none();
+            };
+            if ann.id != edit.original.id {
+                return Task::none();
+            }
+            let (x, y, w, h) = (ann.x, ann.y, ann.width, ann.height);
+            *ann = edit.original.clone();
+            let unchanged = (x - ann.x).abs() < f32::EPSILON
+                && (y - ann.y).abs() < f32::EPSILON
+                && (w - ann.width).abs() < f32::EPSILON
+                && (h - ann.height).abs() < f32::EPSILON;
+            if unchanged {
+                return Task::none();
+            }
+            app.update(Message::UpdateAnnotation(edit.index, x, y, w, h))
+        }
+        Message::UpdateAnnotation(idx, x, y, width, height) => {
+            if let Some(tab) = app.current_tab_mut()
+                && let Some(ann) = tab.annotations.get_mut(idx)
+            {
+                let before = ann.clone();
+                ann.x = x;
+                ann.y = y;
+                ann.width = width;
+                ann.height = height;
+                let after = ann.clone();
+                tab.undo_stack
+                    .push(crate::models::UndoableAction::ModifyAnnotation { before, after });
+                tab.redo_stack.clear();
+                tab.annotations_dirty = true;
+                tab.sidecar_dirty = true;
+            }
+            Task::none()
+        }
         Message::AnnotationDragEnd => {
             let ann_color = app.annotation_color.clone();
             let ann_thickness = app.annotation_thickness;
@@ -117,6 +438,7 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                                     };
                                     tab.annotations.push(line_ann);
                                     tab.annotations_dirty = true;
+                                    tab.sidecar_dirty = true;
                                 }
                             }
                         }
@@ -141,10 +463,16 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                 let dy = curr_y - start_y;
                 let dist = dx.hypot(dy);
 
-                let is_sticky = drag.kind == crate::models::PendingAnnotationKind::StickyNote;
-                let is_valid = dist > 5.0 || is_sticky;
+                let is_ink = drag.kind == crate::models::PendingAnnotationKind::Ink;
+                let is_valid = if is_ink {
+                    drag.points.len() > 1
+                } else {
+                    dist > 5.0
+                };
 
                 if is_valid {
+                    let ink_stroke =
+                        is_ink.then(|| ink_stroke_in_page(tab, drag.page, &drag.points));
                     let id = crate::models::next_annotation_id();
                     let style = match drag.kind {
                         crate::models::PendingAnnotationKind::Highlight => {
@@ -194,14 +522,16 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                                 thickness: ann_thickness,
                             }
                         }
-                        crate::models::PendingAnnotationKind::StickyNote => {
-                            crate::models::AnnotationStyle::StickyNote {
-                                comment: if ann_text.is_empty() {
-                                    "Sticky Note".to_string()
-                                } else {
-                                    ann_text.clone()
-                                },
-                                color: "#ffeb3b".to_string(),
+                        // Notes are placed on press, by `AddTextNote`.
+                        crate::models::PendingAnnotationKind::StickyNote => return Task::none(),
+                        crate::models::PendingAnnotationKind::Ink => {
+                            crate::models::AnnotationStyle::Ink {
+                                color: ann_color.clone(),
+                                thickness: ann_thickness,
+                                strokes: ink_stroke
+                                    .as_ref()
+                                    .map(|(_, points)| vec![points.clone()])
+                                    .unwrap_or_default(),
                             }
                         }
                     };
@@ -211,8 +541,7 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                         | crate::models::PendingAnnotationKind::Arrow => {
                             (start_x / zoom, start_y / zoom, dx / zoom, dy / zoom)
                         }
-                        crate::models::PendingAnnotationKind::Text
-                        | crate::models::PendingAnnotationKind::StickyNote => {
+                        crate::models::PendingAnnotationKind::Text => {
                             let click_x = start_x / zoom;
                             let click_y = start_y / zoom;
                             (click_x, click_y, 120.0, 24.0)
@@ -231,22 +560,21 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                         .get(drag.page)
                         .copied()
                         .unwrap_or(drag.page);
-                    let page_rotation = tab
-                        .page_rotations
-                        .get(&actual_page)
-                        .copied()
-                        .unwrap_or(tab.rotation);
+                    let page_rotation = tab.effective_rotation(actual_page);
                     let original_height = tab.page_heights.get(drag.page).copied().unwrap_or(800.0);
 
-                    let (ann_x, ann_y, ann_w, ann_h) = crate::models::unrotate_coords(
-                        ann_x_vis,
-                        ann_y_vis,
-                        ann_w_vis,
-                        ann_h_vis,
-                        tab.page_width,
-                        original_height,
-                        page_rotation,
-                    );
+                    let (ann_x, ann_y, ann_w, ann_h) = match &ink_stroke {
+                        Some((bounds, _)) => *bounds,
+                        None => crate::models::unrotate_coords(
+                            ann_x_vis,
+                            ann_y_vis,
+                            ann_w_vis,
+                            ann_h_vis,
+                            tab.page_width,
+                            original_height,
+                            page_rotation,
+                        ),
+                    };
 
                     let ann = crate::models::Annotation {
                         id,
@@ -263,66 +591,29 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                     tab.redo_stack.clear();
                     tab.annotations.push(ann);
                     tab.annotations_dirty = true;
+                    tab.sidecar_dirty = true;
                 }
             } else if let Some(tab) = app.current_tab_mut() {
                 if let Some((page_idx, start, current)) = tab.selection_drag.take() {
                     let actual_page = tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx);
-                    let page_rotation = tab
-                        .page_rotations
-                        .get(&actual_page)
-                        .copied()
-                        .unwrap_or(tab.rotation);
+                    let page_rotation = tab.effective_rotation(actual_page);
                     let original_height = tab.page_heights.get(page_idx).copied().unwrap_or(800.0);
-
-                    let (sx, sy, _, _) = crate::models::unrotate_coords(
-                        start.0,
-                        start.1,
-                        0.0,
-                        0.0,
-                        tab.page_width,
-                        original_height,
-                        page_rotation,
-                    );
-                    let (cx, cy, _, _) = crate::models::unrotate_coords(
-                        current.0,
-                        current.1,
-                        0.0,
-                        0.0,
-                        tab.page_width,
-                        original_height,
-                        page_rotation,
-                    );
-
-                    let mut selected_words = Vec::new();
-                    if let Some(words) = tab.view_state.text_layers.get(&page_idx) {
-                        let x1 = sx.min(cx);
-                        let x2 = sx.max(cx);
-                        let y1 = sy.min(cy);
-                        let y2 = sy.max(cy);
-
-                        for word in words {
-                            let wx1 = word.x;
-                            let wx2 = word.x + word.width;
-                            let wy1 = word.y;
-                            let wy2 = word.y + word.height;
-
-                            let overlap_x = x1 < wx2 && x2 > wx1;
-                            let overlap_y = y1 < wy2 && y2 > wy1;
-
-                            if overlap_x && overlap_y {
-                                selected_words.push(word.clone());
-                            }
-                        }
-                    }
+                    let selected_words = tab
+                        .view_state
+                        .text_layers
+                        .get(&page_idx)
+                        .map(|words| {
+                            crate::models::select_text_items(
+                                words,
+                                start,
+                                current,
+                                tab.page_width,
+                                original_height,
+                                page_rotation,
+                            )
+                        })
+                        .unwrap_or_default();
                     if !selected_words.is_empty() {
-                        selected_words.sort_by(|a, b| {
-                            let y_diff = (a.y - b.y).abs();
-                            if y_diff < 5.0 {
-                                a.x.partial_cmp(&b.x).unwrap_or(std::cmp::Ordering::Equal)
-                            } else {
-                                a.y.partial_cmp(&b.y).unwrap_or(std::cmp::Ordering::Equal)
-                            }
-                        });
                         let text: String = selected_words
                             .iter()
                             .map(|w| w.text.as_str())
@@ -332,13 +623,9 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                             .iter()
                             .map(|w| (w.x, w.y, w.width, w.height))
                             .collect();
-                        tab.selected_text = Some(text.clone());
-
-                        let mut clipboard = arboard::Clipboard::new().ok();
-                        if let Some(cb) = &mut clipboard {
-                            let _ = cb.set_text(text);
-                        }
-                        app.status_message = Some("Text copied to clipboard!".to_string());
+                        tab.selected_page = Some(page_idx);
+                        tab.selected_text = Some(text);
+                        return app.update(Message::CopySelection);
                     }
                 }
             }
@@ -353,6 +640,7 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                     .push(crate::models::UndoableAction::DeleteAnnotation(idx, ann));
                 tab.redo_stack.clear();
                 tab.annotations_dirty = true;
+                tab.sidecar_dirty = true;
             }
             Task::none()
         }
@@ -360,6 +648,7 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
             if let Some(tab) = app.current_tab_mut()
                 && let Some(action) = tab.undo_stack.pop()
             {
+                tab.sidecar_dirty = true;
                 match action {
                     crate::models::UndoableAction::AddAnnotation(ann) => {
                         tab.redo_stack
@@ -374,6 +663,16 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                             ));
                         tab.annotations.insert(idx.min(tab.annotations.len()), ann);
                     }
+                    crate::models::UndoableAction::ModifyAnnotation { before, after } => {
+                        if let Some(ann) = tab.annotations.iter_mut().find(|a| a.id == before.id) {
+                            *ann = before.clone();
+                        }
+                        tab.redo_stack
+                            .push(crate::models::UndoableAction::ModifyAnnotation {
+                                before,
+                                after,
+                            });
+                    }
                 }
             }
             Task::none()
@@ -382,6 +681,7 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
             if let Some(tab) = app.current_tab_mut()
                 && let Some(action) = tab.redo_stack.pop()
             {
+                tab.sidecar_dirty = true;
                 match action {
                     crate::models::UndoableAction::AddAnnotation(ann) => {
                         tab.undo_stack
@@ -396,6 +696,16 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
                             ));
                         tab.annotations.retain(|a| a.id != ann.id);
                     }
+                    crate::models::UndoableAction::ModifyAnnotation { before, after } => {
+                        if let Some(ann) = tab.annotations.iter_mut().find(|a| a.id == after.id) {
+                            *ann = after.clone();
+                        }
+                        tab.undo_stack
+                            .push(crate::models::UndoableAction::ModifyAnnotation {
+                                before,
+                                after,
+                            });
+                    }
                 }
             }
             Task::none()
@@ -461,28 +771,159 @@ pub fn handle_annotation_message(app: &mut PdfBullApp, message: Message) -> Task
         }
         Message::AnnotationsLoaded(doc_id, annotations) => {
             if let Some(tab) = app.tabs.iter_mut().find(|t| t.id == doc_id) {
-                tab.annotations = annotations;
+                tab.annotations = match tab.pending_sidecar_annotations.take() {
+                    Some((sidecar, sidecar_newer)) => {
+                        crate::storage::resolve_annotations(annotations, sidecar, sidecar_newer)
+                    }
+                    None => annotations,
+                };
             }
             app.render_visible_pages()
         }
         Message::EditAnnotationText(idx, new_text) => {
-            if let Some(tab) = app.current_tab_mut() {
-                if let Some(ann) = tab.annotations.get_mut(idx) {
-                    match &mut ann.style {
-                        crate::models::AnnotationStyle::Text { text, .. } => {
-                            *text = new_text;
-                            tab.annotations_dirty = true;
-                        }
-                        crate::models::AnnotationStyle::StickyNote { comment, .. } => {
-                            *comment = new_text;
-                            tab.annotations_dirty = true;
-                        }
-                        _ => {}
-                    }
+            if let Some(tab) = app.current_tab_mut()
+                && let Some(ann) = tab.annotations.get_mut(idx)
+            {
+                let before = ann.clone();
+                let target = match &mut ann.style {
+                    AnnotationStyle::Text { text, .. } => text,
+                    AnnotationStyle::StickyNote { comment, .. } => comment,
+                    _ => return Task::none(),
+                };
+                if *target == new_text {
+                    return Task::none();
                 }
+                *target = new_text;
+                let after = ann.clone();
+                tab.undo_stack
+                    .push(crate::models::UndoableAction::ModifyAnnotation { before, after });
+                tab.redo_stack.clear();
+                tab.annotations_dirty = true;
+                tab.sidecar_dirty = true;
+            }
+            Task::none()
+        }
+        Message::OpenNoteEditor(idx) => {
+            let draft = app
+                .current_tab()
+                .and_then(|tab| tab.annotations.get(idx))
+                .and_then(|ann| match &ann.style {
+                    AnnotationStyle::Text { text, .. } => Some(text.clone()),
+                    AnnotationStyle::StickyNote { comment, .. } => Some(comment.clone()),
+                    _ => None,
+                });
+            if let Some(draft) = draft {
+                app.annotation_edit = None;
+                app.note_editor = Some((idx, draft));
+            }
+            Task::none()
+        }
+        Message::NoteEditorChanged(text) => {
+            if let Some((_, draft)) = &mut app.note_editor {
+                *draft = text;
             }
             Task::none()
         }
+        Message::SaveNoteEditor => match app.note_editor.take() {
+            Some((idx, draft)) => app.update(Message::EditAnnotationText(idx, draft)),
+            None => Task::none(),
+        },
+        Message::CloseNoteEditor => {
+            app.note_editor = None;
+            Task::none()
+        }
         _ => Task::none(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with_highlight() -> PdfBullApp {
+        let mut app = PdfBullApp::default();
+        app.loaded = true;
+        let mut tab = DocumentTab::new(std::path::PathBuf::from("test.pdf"));
+        tab.total_pages = 1;
+        tab.page_heights = vec![800.0];
+        tab.page_width = 600.0;
+        tab.annotations.push(Annotation {
+            id: 7,
+            page: 0,
+            style: AnnotationStyle::Highlight {
+                color: "#FFFF00".to_string(),
+            },
+            x: 100.0,
+            y: 100.0,
+            width: 200.0,
+            height: 20.0,
+        });
+        app.tabs.push(tab);
+        app
+    }
+
+    #[test]
+    fn test_first_press_on_annotation_selects_text_instead_of_moving() {
+        let mut app = app_with_highlight();
+        let press = Message::AnnotationDragStart {
+            page: 0,
+            x: 150.0,
+            y: 110.0,
+        };
+        let _ = handle_annotation_message(&mut app, press.clone());
+        assert!(app.annotation_edit.is_none());
+        assert!(app.tabs[0].selection_drag.is_some());
+        assert_eq!(app.tabs[0].selected_annotation, Some(7));
+
+        let _ = handle_annotation_message(&mut app, Message::AnnotationDragEnd);
+        let _ = handle_annotation_message(&mut app, press);
+        let edit = app.annotation_edit.as_ref().unwrap();
+        assert_eq!((edit.index, edit.handle), (0, AnnotationHandle::Move));
+
+        // Pressing outside any annotation drops the selection.
+        app.annotation_edit = None;
+        let away = Message::AnnotationDragStart {
+            page: 0,
+            x: 500.0,
+            y: 500.0,
+        };
+        let _ = handle_annotation_message(&mut app, away);
+        assert_eq!(app.tabs[0].selected_annotation, None);
+    }
+
+    #[test]
+    fn test_sticky_note_placed_at_click_and_editor_opened() {
+        let mut app = app_with_highlight();
+        app.tabs[0].zoom = 2.0;
+        app.annotation_mode = Some(crate::models::PendingAnnotationKind::StickyNote);
+        let click = Message::AnnotationDragStart {
+            page: 0,
+            x: 300.0,
+            y: 500.0,
+        };
+        let _ = handle_annotation_message(&mut app, click);
+        assert!(app.annotation_drag.is_none());
+
+        let tab = &app.tabs[0];
+        let note = &tab.annotations[1];
+        assert!(matches!(note.style, AnnotationStyle::StickyNote { .. }));
+        assert_eq!((note.x, note.y), (150.0, 250.0));
+        assert_eq!(app.note_editor.as_ref().map(|(idx, _)| *idx), Some(1));
+        assert!(tab.sidecar_dirty);
+
+        // On a rotated page the icon is still drawn at the click.
+        app.tabs[0].rotation = 90;
+        let _ = handle_annotation_message(
+            &mut app,
+            Message::AddTextNote {
+                page: 0,
+                x: 40.0,
+                y: 60.0,
+            },
+        );
+        let tab = &app.tabs[0];
+        let (x, y, width, height) = visual_bounds(tab, &tab.annotations[2]);
+        assert!((x - 40.0).abs() < 0.01 && (y - 60.0).abs() < 0.01);
+        assert_eq!((width, height), (NOTE_ICON_SIZE, NOTE_ICON_SIZE));
+    }
+}
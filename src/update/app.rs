@@ -9,6 +9,8 @@ pub fn handle_app_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
             if let Some(tab) = app.current_tab_mut() {
                 tab.zoom = 1.0;
             }
+            app.bump_render_generation();
+            app.enforce_render_budget();
             app.render_visible_pages()
         }
         Message::OpenSettings => {
@@ -49,15 +51,19 @@ pub fn handle_app_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
         Message::RotateClockwise => {
             if let Some(tab) = app.current_tab_mut() {
                 tab.rotation = (tab.rotation + 90) % 360;
-                tab.view_state.rendered_pages.clear();
+                tab.view_state.clear_rendered_pages();
+                tab.view_state.thumbnails.clear();
             }
+            app.bump_render_generation();
             app.render_visible_pages()
         }
         Message::RotateCounterClockwise => {
             if let Some(tab) = app.current_tab_mut() {
                 tab.rotation = (tab.rotation - 90 + 360) % 360;
-                tab.view_state.rendered_pages.clear();
+                tab.view_state.clear_rendered_pages();
+                tab.view_state.thumbnails.clear();
             }
+            app.bump_render_generation();
             app.render_visible_pages()
         }
         Message::ClearRecentFiles => {
@@ -83,8 +89,9 @@ pub fn handle_app_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                     crate::models::ReadingMode::Sepia => RenderFilter::Sepia,
                     crate::models::ReadingMode::Grayscale => RenderFilter::Grayscale,
                 };
-                tab.view_state.rendered_pages.clear();
+                tab.view_state.clear_rendered_pages();
             }
+            app.bump_render_generation();
             app.render_visible_pages()
         }
         Message::SetAnnotationColor(color) => {
@@ -16,6 +16,7 @@ pub fn handle_bookmark_message(app: &mut PdfBullApp, message: Message) -> Task<M
                 };
                 if !tab.bookmarks.iter().any(|b| b.page == page) {
                     tab.bookmarks.push(bookmark);
+                    tab.sidecar_dirty = true;
                 }
             }
             Task::none()
@@ -25,6 +26,7 @@ pub fn handle_bookmark_message(app: &mut PdfBullApp, message: Message) -> Task<M
                 && idx < tab.bookmarks.len()
             {
                 tab.bookmarks.remove(idx);
+                tab.sidecar_dirty = true;
             }
             Task::none()
         }
@@ -56,6 +58,96 @@ pub fn handle_bookmark_message(app: &mut PdfBullApp, message: Message) -> Task<M
             }
             Task::none()
         }
+        Message::JumpToOutline(idx) => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            let Some(node) = tab.outline.get(idx) else {
+                return Task::none();
+            };
+            if tab.total_pages == 0 {
+                return Task::none();
+            }
+
+            // Outline destinations refer to source pages; map them through the
+            // organizer's reordering when one is active.
+            let source_page = node.page_index;
+            let visual_page = tab
+                .page_mapping
+                .iter()
+                .position(|&p| p == source_page)
+                .unwrap_or(source_page);
+
+            if visual_page < tab.total_pages {
+                return app.update(Message::JumpToPage(visual_page));
+            }
+
+            let last_page = tab.total_pages - 1;
+            let status = format!(
+                "Outline entry \"{}\" points to missing page {}",
+                node.title,
+                source_page + 1
+            );
+            app.status_message = Some(status);
+            app.update(Message::JumpToPage(last_page))
+        }
+        Message::ToggleOutlineNode(idx) => {
+            if let Some(tab) = app.current_tab_mut()
+                && !tab.outline_collapsed.remove(&idx)
+            {
+                tab.outline_collapsed.insert(idx);
+            }
+            Task::none()
+        }
         _ => Task::none(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DocumentTab;
+    use crate::pdf_engine::Bookmark;
+
+    fn setup_test_app() -> PdfBullApp {
+        let mut app = PdfBullApp::default();
+        app.loaded = true;
+        let mut tab = DocumentTab::new(std::path::PathBuf::from("test.pdf"));
+        tab.total_pages = 5;
+        tab.page_heights = vec![800.0; 5];
+        tab.outline = vec![
+            Bookmark {
+                title: "Intro".to_string(),
+                page_index: 2,
+                depth: 0,
+            },
+            Bookmark {
+                title: "Appendix".to_string(),
+                page_index: 40,
+                depth: 0,
+            },
+        ];
+        app.tabs.push(tab);
+        app.active_tab = 0;
+        app
+    }
+
+    #[test]
+    fn test_outline_jump_to_valid_page() {
+        let mut app = setup_test_app();
+        let _ = handle_bookmark_message(&mut app, Message::JumpToOutline(0));
+        assert_eq!(app.tabs[0].current_page, 2);
+        assert!(app.status_message.is_none());
+    }
+
+    #[test]
+    fn test_outline_jump_past_end_clamps_to_last_page() {
+        let mut app = setup_test_app();
+        let _ = handle_bookmark_message(&mut app, Message::JumpToOutline(1));
+        assert_eq!(app.tabs[0].current_page, 4);
+        assert_eq!(app.page_input, "5");
+        let status = app.status_message.as_deref().unwrap();
+        assert!(status.contains("\"Appendix\""));
+        assert!(status.contains("missing page 41"));
+    }
+}
@@ -102,6 +102,12 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
             app.status_message = Some("Copied to clipboard".into());
             Task::none()
         }
+        Message::CopySelection => {
+            match app.current_tab().and_then(|tab| tab.selected_text.clone()) {
+                Some(text) => app.update(Message::CopyToClipboard(text)),
+                None => Task::none(),
+            }
+        }
         Message::CopyImageToClipboard => {
             let Some(tab) = app.current_tab() else {
                 return Task::none();
@@ -110,7 +116,8 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
             let page = tab.current_page;
             let doc_id = tab.id;
             let zoom = tab.zoom;
-            let rotation = tab.rotation;
+            let rotation =
+                tab.effective_rotation(tab.page_mapping.get(page).copied().unwrap_or(page));
             let filter = tab.render_filter;
             let auto_crop = tab.auto_crop;
             let quality = app.settings.render_quality;
@@ -132,7 +139,7 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                     };
                     if let Err(_e) = cmd_tx
                         .send(crate::commands::PdfCommand::Render(
-                            doc_id, page, options, resp_tx,
+                            doc_id, page, options, None, resp_tx,
                         ))
                         .await
                     {
@@ -388,6 +395,85 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
             }
             Task::none()
         }
+        Message::MergeOpenTabs => {
+            let (paths, missing): (Vec<_>, Vec<_>) = app
+                .tabs
+                .iter()
+                .filter(|t| !t.format.is_converted())
+                .map(|t| t.path.clone())
+                .partition(|p| p.exists());
+            if paths.len() < 2 {
+                app.status_message = Some(format!(
+                    "Merge needs at least 2 open documents on disk ({} available)",
+                    paths.len()
+                ));
+                return Task::none();
+            }
+            if !missing.is_empty() {
+                app.status_message = Some(format!(
+                    "Skipping {} tab(s) whose file no longer exists",
+                    missing.len()
+                ));
+            }
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let out = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name("merged.pdf")
+                        .set_title("Save Merged PDF")
+                        .save_file()
+                        .await;
+                    let Some(f) = out else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let path_strs = paths
+                        .iter()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .collect();
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::Merge(
+                            path_strs,
+                            f.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::OpenTabsMerged,
+            )
+        }
+        Message::OpenTabsMerged(res) => match res {
+            Ok(p) => {
+                app.status_message = Some(format!("Merged PDF saved to: {p}"));
+                app.update(Message::OpenFile(std::path::PathBuf::from(p)))
+            }
+            Err(crate::models::PdfError::Cancelled) => Task::none(),
+            Err(e) => {
+                app.status_message = Some(format!("Merge failed: {e}"));
+                Task::none()
+            }
+        },
+        Message::SplitDocument => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            if tab.is_encrypted {
+                app.status_message =
+                    Some("Split failed: encrypted PDFs can't be split into pages".into());
+                return Task::none();
+            }
+            let total = tab.total_pages;
+            app.status_message = Some(format!("Splitting {total} pages..."));
+            app.update(Message::SplitPDF((0..total).collect()))
+        }
         Message::SplitPDF(pages) => {
             let Some(tab) = app.current_tab() else {
                 return Task::none();
@@ -397,32 +483,57 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                 return Task::none();
             };
             let cmd_tx = engine.cmd_tx.clone();
-            Task::perform(
-                async move {
-                    let folder = rfd::AsyncFileDialog::new()
-                        .set_title("Select Output Folder for Split Pages")
-                        .pick_folder()
-                        .await;
-                    if let Some(f) = folder {
+            // Stream one SplitProgress per written page, then the result.
+            Task::run(
+                iced::stream::channel(
+                    4,
+                    move |mut output: iced::futures::channel::mpsc::Sender<Message>| async move {
+                        use iced::futures::SinkExt;
+
+                        let folder = rfd::AsyncFileDialog::new()
+                            .set_title("Select Output Folder for Split Pages")
+                            .pick_folder()
+                            .await;
+                        let Some(f) = folder else {
+                            let _ = output
+                                .send(Message::PDFSplit(Err(crate::models::PdfError::from(
+                                    "Cancelled",
+                                ))))
+                                .await;
+                            return;
+                        };
                         let out_dir = f.path().to_string_lossy().to_string();
+                        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
                         let (tx, rx) = tokio::sync::oneshot::channel();
                         let _ = cmd_tx
-                            .send(PdfCommand::Split(path, pages, out_dir, tx))
+                            .send(PdfCommand::Split(path, pages, out_dir, progress_tx, tx))
                             .await;
-                        match rx.await {
-                            Ok(res) => res,
-                            Err(_) => Err(crate::models::PdfError::EngineDied),
+                        // The engine drops the sender once the split finishes.
+                        while let Some((done, total)) = progress_rx.recv().await {
+                            let _ = output.send(Message::SplitProgress(done, total)).await;
                         }
-                    } else {
-                        Err(crate::models::PdfError::from("Cancelled"))
-                    }
-                },
-                Message::PDFSplit,
+                        let res = rx.await.unwrap_or(Err(crate::models::PdfError::EngineDied));
+                        let _ = output.send(Message::PDFSplit(res)).await;
+                    },
+                ),
+                std::convert::identity,
             )
         }
+        Message::SplitProgress(done, total) => {
+            app.status_message = Some(format!("Splitting page {done} of {total}..."));
+            Task::none()
+        }
         Message::PDFSplit(res) => {
             match res {
-                Ok(paths) => app.status_message = Some(format!("Split into {} files", paths.len())),
+                Ok(paths) => {
+                    let folder = paths
+                        .first()
+                        .and_then(|p| std::path::Path::new(p).parent())
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default();
+                    app.status_message =
+                        Some(format!("Split into {} files in {folder}", paths.len()));
+                }
                 Err(e) => {
                     if e != "Cancelled" {
                         app.status_message = Some(format!("Split failed: {e}"));
@@ -431,6 +542,322 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
             }
             Task::none()
         }
+        Message::ToggleExtractDialog(show) => {
+            app.show_extract_dialog = show && app.current_tab().is_some();
+            Task::none()
+        }
+        Message::ExtractRangeInputChanged(input) => {
+            app.extract_range_input = input;
+            Task::none()
+        }
+        Message::ExtractPages => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            let pages =
+                match crate::models::parse_page_range(&app.extract_range_input, tab.total_pages) {
+                    Ok(pages) => pages
+                        .into_iter()
+                        .map(|p| tab.page_mapping.get(p).copied().unwrap_or(p))
+                        .collect::<Vec<_>>(),
+                    Err(e) => {
+                        app.status_message = Some(format!("Invalid page range: {e}"));
+                        return Task::none();
+                    }
+                };
+            let doc_id = tab.id;
+            let file_name = format!(
+                "{}_extract.pdf",
+                tab.path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or_default()
+            );
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            app.show_extract_dialog = false;
+            Task::perform(
+                async move {
+                    let out = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name(file_name)
+                        .set_title("Save Extracted Pages")
+                        .save_file()
+                        .await;
+                    let Some(f) = out else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::ExtractPages(
+                            doc_id,
+                            pages,
+                            f.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::PagesExtracted,
+            )
+        }
+        Message::PagesExtracted(res) => {
+            match res {
+                Ok(p) => app.status_message = Some(format!("Pages extracted to: {p}")),
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Extract failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::ToggleNupDialog(show) => {
+            app.show_nup_dialog = show && app.current_tab().is_some();
+            Task::none()
+        }
+        Message::SetNupGrid(cols, rows) => {
+            app.nup_layout = crate::impose::NupLayout::letter(cols, rows);
+            Task::none()
+        }
+        Message::ImposeNup => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            let path = tab.path.to_string_lossy().to_string();
+            let layout = app.nup_layout;
+            let file_name = format!(
+                "{}_{}x{}.pdf",
+                tab.path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or_default(),
+                layout.cols,
+                layout.rows
+            );
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            app.show_nup_dialog = false;
+            Task::perform(
+                async move {
+                    let out = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name(file_name)
+                        .set_title("Save N-up PDF")
+                        .save_file()
+                        .await;
+                    let Some(f) = out else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::NupPdf(
+                            path,
+                            f.path().to_string_lossy().to_string(),
+                            layout,
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::NupImposed,
+            )
+        }
+        Message::NupImposed(res) => {
+            match res {
+                Ok(p) => app.status_message = Some(format!("N-up PDF saved to: {p}")),
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("N-up failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::MakeBooklet => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            let path = tab.path.to_string_lossy().to_string();
+            let file_name = format!(
+                "{}_booklet.pdf",
+                tab.path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or_default()
+            );
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let out = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name(file_name)
+                        .set_title("Save Booklet")
+                        .save_file()
+                        .await;
+                    let Some(f) = out else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::MakeBooklet(
+                            path,
+                            f.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::BookletCreated,
+            )
+        }
+        Message::BookletCreated(res) => {
+            match res {
+                Ok(p) => {
+                    app.status_message = Some(format!(
+                        "Booklet saved to: {p} (print double-sided, flip on short edge)"
+                    ));
+                }
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Booklet failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::PrependToc => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            if tab.outline.is_empty() {
+                app.status_message = Some("This document has no bookmarks to list".into());
+                return Task::none();
+            }
+            let path = tab.path.to_string_lossy().to_string();
+            let entries = tab.outline.clone();
+            let file_name = format!(
+                "{}_toc.pdf",
+                tab.path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or_default()
+            );
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let out = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name(file_name)
+                        .set_title("Save PDF with Table of Contents")
+                        .save_file()
+                        .await;
+                    let Some(f) = out else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::PrependToc(
+                            path,
+                            entries,
+                            f.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::TocPrepended,
+            )
+        }
+        Message::TocPrepended(res) => {
+            match res {
+                Ok(p) => app.status_message = Some(format!("Table of contents added: {p}")),
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Table of contents failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::TogglePosterDialog(show) => {
+            app.show_poster_dialog = show && app.current_tab().is_some();
+            Task::none()
+        }
+        Message::SetPosterPaper(paper) => {
+            app.poster_paper = paper;
+            Task::none()
+        }
+        Message::SetPosterOverlap(overlap) => {
+            app.poster_overlap = overlap.max(0.0);
+            Task::none()
+        }
+        Message::MakePoster => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            let tile = (app.poster_paper.width, app.poster_paper.height);
+            let overlap = app.poster_overlap;
+            let path = tab.path.to_string_lossy().to_string();
+            let file_name = format!(
+                "{}_poster.pdf",
+                tab.path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or_default()
+            );
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            app.show_poster_dialog = false;
+            Task::perform(
+                async move {
+                    let out = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name(file_name)
+                        .set_title("Save Poster Tiles")
+                        .save_file()
+                        .await;
+                    let Some(f) = out else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::PosterPdf(
+                            path,
+                            f.path().to_string_lossy().to_string(),
+                            tile,
+                            overlap,
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::PosterCreated,
+            )
+        }
+        Message::PosterCreated(res) => {
+            match res {
+                Ok(p) => app.status_message = Some(format!("Poster tiles saved to: {p}")),
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Poster failed: {e}")),
+            }
+            Task::none()
+        }
         Message::LoadFormFields => {
             let (Some(tab), Some(engine)) = (app.current_tab(), &app.engine) else {
                 return Task::none();
@@ -503,13 +930,161 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
             }
             Task::none()
         }
-        // Step 1: user pressed Print → fetch list of printers from the engine
+        Message::ImportFormValues => {
+            let (Some(tab), Some(engine)) = (app.current_tab(), &app.engine) else {
+                return Task::none();
+            };
+            let path = tab.path.to_string_lossy().to_string();
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let Some(json_file) = rfd::AsyncFileDialog::new()
+                        .add_filter("JSON", &["json"])
+                        .set_title("Choose Field Values (name → value)")
+                        .pick_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let raw: std::collections::HashMap<String, serde_json::Value> =
+                        serde_json::from_slice(&json_file.read().await).map_err(|e| {
+                            crate::models::PdfError::from(format!("Invalid field JSON: {e}"))
+                        })?;
+                    let values = raw
+                        .into_iter()
+                        .map(|(name, value)| {
+                            let value = match value {
+                                serde_json::Value::String(s) => s,
+                                other => other.to_string(),
+                            };
+                            (name, value)
+                        })
+                        .collect();
+
+                    let Some(out) = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name("filled_form.pdf")
+                        .save_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::FillFieldsFromMap(
+                            path,
+                            out.path().to_string_lossy().to_string(),
+                            values,
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::FormValuesImported,
+            )
+        }
+        Message::FormValuesImported(res) => {
+            match res {
+                Ok((p, missing)) if missing.is_empty() => {
+                    app.status_message = Some(format!("Form saved to: {p}"));
+                }
+                Ok((p, missing)) => {
+                    app.status_message = Some(format!(
+                        "Form saved to: {p} (no field named: {})",
+                        missing.join(", ")
+                    ));
+                }
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Form filling failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::FlattenForms => {
+            let (Some(tab), Some(engine)) = (app.current_tab(), &app.engine) else {
+                return Task::none();
+            };
+            let path = tab.path.to_string_lossy().to_string();
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let out = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name("flattened_form.pdf")
+                        .set_title("Save Flattened Form")
+                        .save_file()
+                        .await;
+                    let Some(f) = out else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::FlattenForms(
+                            path,
+                            f.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::FormsFlattened,
+            )
+        }
+        Message::FormsFlattened(res) => {
+            match res {
+                Ok((p, count)) => {
+                    app.status_message = Some(format!("Flattened {count} form fields: {p}"));
+                }
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Flattening failed: {e}")),
+            }
+            Task::none()
+        }
+        // Step 1: user pressed Print → ask which pages to print
         Message::Print => {
+            if app.current_tab().is_some() {
+                app.show_print_dialog = true;
+            }
+            Task::none()
+        }
+        Message::SetPrintRange(range) => {
+            app.print_range = range;
+            Task::none()
+        }
+        Message::PrintRangeInputChanged(input) => {
+            app.print_range_input = input;
+            Task::none()
+        }
+        Message::CancelPrint => {
+            app.show_print_dialog = false;
+            Task::none()
+        }
+        // Step 2: range confirmed → fetch list of printers from the engine
+        Message::ConfirmPrint => {
             let Some(tab) = app.current_tab() else {
                 return Task::none();
             };
-            // stash the path so subsequent steps can use it
-            let _path = tab.path.to_string_lossy().to_string();
+            let to_source = |visual: usize| tab.page_mapping.get(visual).copied().unwrap_or(visual);
+            let pages = match app.print_range {
+                crate::models::PrintRange::All => None,
+                crate::models::PrintRange::Current => Some(vec![to_source(tab.current_page)]),
+                crate::models::PrintRange::Custom => {
+                    match crate::models::parse_page_range(&app.print_range_input, tab.total_pages) {
+                        Ok(pages) => Some(pages.into_iter().map(to_source).collect()),
+                        Err(e) => {
+                            app.status_message = Some(format!("Invalid page range: {e}"));
+                            return Task::none();
+                        }
+                    }
+                }
+            };
+            app.print_pages = pages;
+            app.show_print_dialog = false;
             let Some(engine) = &app.engine else {
                 return Task::none();
             };
@@ -526,7 +1101,7 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                 Message::PrintersListed,
             )
         }
-        // Step 2: have the list → show an rfd selection dialog
+        // Step 3: have the list → show an rfd selection dialog
         Message::PrintersListed(res) => {
             let Some(tab) = app.current_tab() else {
                 return Task::none();
@@ -584,11 +1159,12 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                 ),
             }
         }
-        // Step 3: send the actual print command with the chosen printer
+        // Step 4: send the actual print command with the chosen printer
         Message::PrintWithPrinter(payload) => {
             let mut parts = payload.splitn(2, '|');
             let path = parts.next().unwrap_or("").to_string();
             let printer = parts.next().unwrap_or("").to_string();
+            let pages = app.print_pages.take();
 
             let Some(engine) = &app.engine else {
                 return Task::none();
@@ -603,7 +1179,7 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                         Some(printer)
                     };
                     let _ = cmd_tx
-                        .send(PdfCommand::PrintPdf(path, printer_opt, tx))
+                        .send(PdfCommand::PrintPdf(path, printer_opt, pages, tx))
                         .await;
                     match rx.await {
                         Ok(res) => res,
@@ -625,6 +1201,7 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                 return Task::none();
             };
             let path = tab.path.to_string_lossy().to_string();
+            let options = app.watermark_options;
             let Some(engine) = &app.engine else {
                 return Task::none();
             };
@@ -642,7 +1219,13 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                             let out = f.path().to_string_lossy().to_string();
                             let (tx, rx) = tokio::sync::oneshot::channel();
                             let _ = cmd_tx
-                                .send(PdfCommand::AddWatermark(path, text, out.clone(), tx))
+                                .send(PdfCommand::AddWatermark(
+                                    path,
+                                    text,
+                                    options,
+                                    out.clone(),
+                                    tx,
+                                ))
                                 .await;
                             match rx.await {
                                 Ok(Ok(path)) => Ok(path),
@@ -694,8 +1277,7 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                                 .send(PdfCommand::Optimize(path, out.clone(), tx))
                                 .await;
                             match rx.await {
-                                Ok(Ok(path)) => Ok(path),
-                                Ok(Err(e)) => Err(e),
+                                Ok(res) => res,
                                 Err(_) => Err(crate::models::PdfError::EngineDied),
                             }
                         }
@@ -707,8 +1289,13 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
         }
         Message::PDFOptimized(res) => {
             match res {
-                Ok(path) => {
-                    app.status_message = Some(format!("Optimized PDF saved to: {path}"));
+                Ok((path, saved)) => {
+                    let change = if saved >= 0 {
+                        format!("{} KB smaller", saved / 1024)
+                    } else {
+                        format!("{} KB larger", -saved / 1024)
+                    };
+                    app.status_message = Some(format!("Optimized PDF saved to: {path} ({change})"));
                 }
                 Err(e) => {
                     if e != "Cancelled" {
@@ -718,6 +1305,378 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
             }
             Task::none()
         }
+        Message::AddChart => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            let current_page = tab
+                .page_mapping
+                .get(tab.current_page)
+                .copied()
+                .unwrap_or(tab.current_page);
+            let path = tab.path.to_string_lossy().to_string();
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let Some(spec_file) = rfd::AsyncFileDialog::new()
+                        .add_filter("Chart spec", &["json"])
+                        .set_title("Choose Chart Spec")
+                        .pick_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let mut spec: crate::pdf_engine::ChartSpec =
+                        serde_json::from_slice(&spec_file.read().await).map_err(|e| {
+                            crate::models::PdfError::from(format!("Invalid chart spec: {e}"))
+                        })?;
+                    spec.page = spec.page.or(Some(current_page));
+
+                    let Some(out) = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name("with_chart.pdf")
+                        .set_title("Save PDF with Chart")
+                        .save_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::AddChart(
+                            path,
+                            spec,
+                            out.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::ChartAdded,
+            )
+        }
+        Message::ChartAdded(res) => {
+            match res {
+                Ok(p) => app.status_message = Some(format!("Chart added: {p}")),
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Chart failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::ExportJson => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            let path = tab.path.to_string_lossy().to_string();
+            let stem = tab
+                .path
+                .file_stem()
+                .map_or_else(|| "document".into(), |s| s.to_string_lossy().to_string());
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let Some(out) = rfd::AsyncFileDialog::new()
+                        .add_filter("JSON", &["json"])
+                        .set_file_name(format!("{stem}.json"))
+                        .set_title("Export PDF Structure as JSON")
+                        .save_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::ExportJson(
+                            path,
+                            crate::pdf_json::JsonConfig::default(),
+                            out.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::JsonExported,
+            )
+        }
+        Message::JsonExported(res) => {
+            match res {
+                Ok(p) => app.status_message = Some(format!("JSON exported: {p}")),
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("JSON export failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::ImportHtml => {
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let Some(html_file) = rfd::AsyncFileDialog::new()
+                        .add_filter("HTML", &["html", "htm"])
+                        .set_title("Choose HTML File")
+                        .pick_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let html = String::from_utf8_lossy(&html_file.read().await).into_owned();
+                    let base_dir = html_file.path().parent().map(std::path::Path::to_path_buf);
+                    let stem = html_file
+                        .path()
+                        .file_stem()
+                        .map_or_else(|| "document".into(), |s| s.to_string_lossy());
+
+                    let Some(out) = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name(format!("{stem}.pdf"))
+                        .set_title("Save Converted PDF")
+                        .save_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::HtmlToPdf(
+                            html,
+                            base_dir,
+                            crate::flow::PageSize::LETTER,
+                            out.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::HtmlConverted,
+            )
+        }
+        Message::HtmlConverted(res) => match res {
+            Ok(p) => {
+                app.status_message = Some(format!("HTML converted: {p}"));
+                app.update(Message::OpenFile(std::path::PathBuf::from(p)))
+            }
+            Err(crate::models::PdfError::Cancelled) => Task::none(),
+            Err(e) => {
+                app.status_message = Some(format!("HTML conversion failed: {e}"));
+                Task::none()
+            }
+        },
+        Message::ToggleBarcodeDialog(show) => {
+            app.show_barcode_dialog = show && app.current_tab().is_some();
+            Task::none()
+        }
+        Message::BarcodeInputChanged(input) => {
+            app.barcode_input = input;
+            Task::none()
+        }
+        Message::SetBarcodeKind(kind) => {
+            app.barcode_kind = kind;
+            Task::none()
+        }
+        Message::AddBarcode => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            if app.barcode_input.is_empty() {
+                return Task::none();
+            }
+            let page = tab
+                .page_mapping
+                .get(tab.current_page)
+                .copied()
+                .unwrap_or(tab.current_page);
+            // Bottom-left corner, half an inch in from both edges.
+            let spec = crate::barcode::BarcodeSpec {
+                kind: app.barcode_kind,
+                data: app.barcode_input.clone(),
+                page,
+                x: 36.0,
+                y: 36.0,
+                scale: match app.barcode_kind {
+                    crate::barcode::BarcodeKind::Code128 => 1.0,
+                    crate::barcode::BarcodeKind::Qr(_) => 2.0,
+                },
+            };
+            let path = tab.path.to_string_lossy().to_string();
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            app.show_barcode_dialog = false;
+            Task::perform(
+                async move {
+                    let out = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name("barcoded.pdf")
+                        .set_title("Save PDF with Barcode")
+                        .save_file()
+                        .await;
+                    let Some(f) = out else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::AddBarcode(
+                            path,
+                            spec,
+                            f.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::BarcodeAdded,
+            )
+        }
+        Message::BarcodeAdded(res) => {
+            match res {
+                Ok(p) => app.status_message = Some(format!("Barcode added: {p}")),
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Barcode failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::ValidatePdf => {
+            let Some(tab) = app.current_tab() else {
+                return Task::none();
+            };
+            let path = tab.path.to_string_lossy().to_string();
+            let file_name = format!(
+                "{}_validation.json",
+                tab.path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or_default()
+            );
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx.send(PdfCommand::ValidatePdf(path, tx)).await;
+                    let issues = rx
+                        .await
+                        .map_err(|_| crate::models::PdfError::EngineDied)??;
+                    let Some(out) = rfd::AsyncFileDialog::new()
+                        .add_filter("JSON", &["json"])
+                        .set_file_name(file_name)
+                        .set_title("Save Validation Report")
+                        .save_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let json = serde_json::to_string_pretty(&issues)
+                        .map_err(|e| crate::models::PdfError::IoError(e.to_string()))?;
+                    out.write(json.as_bytes())
+                        .await
+                        .map_err(|e| crate::models::PdfError::IoError(e.to_string()))?;
+                    Ok((out.path().to_string_lossy().to_string(), issues))
+                },
+                Message::PdfValidated,
+            )
+        }
+        Message::PdfValidated(res) => {
+            match res {
+                Ok((report, issues)) => {
+                    let errors = issues
+                        .iter()
+                        .filter(|i| i.severity == crate::models::IssueSeverity::Error)
+                        .count();
+                    app.status_message = Some(format!(
+                        "Validation: {errors} errors, {} warnings. Report saved to: {report}",
+                        issues.len() - errors
+                    ));
+                }
+                Err(crate::models::PdfError::Cancelled) => {}
+                Err(e) => app.status_message = Some(format!("Validation failed: {e}")),
+            }
+            Task::none()
+        }
+        Message::RepairPdf => {
+            let Some(engine) = &app.engine else {
+                return Task::none();
+            };
+            let cmd_tx = engine.cmd_tx.clone();
+            Task::perform(
+                async move {
+                    // Damaged files often won't open in a tab, so pick from disk.
+                    let Some(input) = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_title("Choose Damaged PDF")
+                        .pick_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let Some(out) = rfd::AsyncFileDialog::new()
+                        .add_filter("PDF", &["pdf"])
+                        .set_file_name("repaired.pdf")
+                        .set_title("Save Repaired PDF")
+                        .save_file()
+                        .await
+                    else {
+                        return Err(crate::models::PdfError::Cancelled);
+                    };
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    let _ = cmd_tx
+                        .send(PdfCommand::RepairPdf(
+                            input.path().to_string_lossy().to_string(),
+                            out.path().to_string_lossy().to_string(),
+                            tx,
+                        ))
+                        .await;
+                    match rx.await {
+                        Ok(res) => res,
+                        Err(_) => Err(crate::models::PdfError::EngineDied),
+                    }
+                },
+                Message::PdfRepaired,
+            )
+        }
+        Message::PdfRepaired(res) => match res {
+            Ok(result) => {
+                for issue in &result.issues {
+                    tracing::info!("Repaired: {issue}");
+                }
+                app.status_message = Some(format!(
+                    "Repaired {} issue(s), {} objects recovered: {}",
+                    result.issues.len(),
+                    result.objects_recovered,
+                    result.output_path
+                ));
+                app.update(Message::OpenFile(std::path::PathBuf::from(
+                    result.output_path,
+                )))
+            }
+            Err(crate::models::PdfError::Cancelled) => Task::none(),
+            Err(e) => {
+                app.status_message = Some(format!("Repair failed: {e}"));
+                Task::none()
+            }
+        },
         Message::SaveOrganizedPDF => {
             let Some(tab) = app.current_tab() else {
                 return Task::none();
@@ -779,3 +1738,82 @@ pub fn handle_export_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
         _ => Task::none(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DocumentTab;
+    use std::path::PathBuf;
+
+    fn fixture_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_document.pdf")
+    }
+
+    #[test]
+    fn test_merge_open_tabs_skips_missing_files() {
+        let mut app = PdfBullApp::default();
+        app.tabs.push(DocumentTab::new(fixture_path()));
+        app.tabs
+            .push(DocumentTab::new(PathBuf::from("/missing/gone.pdf")));
+
+        let _ = handle_export_message(&mut app, Message::MergeOpenTabs);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Merge needs at least 2 open documents on disk (1 available)")
+        );
+    }
+
+    #[test]
+    fn test_merging_open_tabs_keeps_tab_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let store =
+            crate::pdf_engine::DocumentStore::new(crate::pdf_engine::create_render_cache(10, 0));
+        let fixture = fixture_path().to_string_lossy().to_string();
+        let fixture_doc = lopdf::Document::load(&fixture).unwrap();
+        let last = fixture_doc.get_pages().len() - 1;
+        // A one-page second tab holding the fixture's last page.
+        let second = store
+            .split_pdf(
+                &fixture,
+                vec![last],
+                dir.path().to_string_lossy().to_string(),
+                |_, _| {},
+            )
+            .unwrap()
+            .remove(0);
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        store
+            .merge_documents(vec![fixture, second.clone()], output.clone())
+            .unwrap();
+        let merged = lopdf::Document::load(&output).unwrap();
+        let second_doc = lopdf::Document::load(&second).unwrap();
+
+        let content = |doc: &lopdf::Document, index: usize| {
+            let page_id = doc.get_pages().into_values().nth(index).unwrap();
+            doc.get_page_content(page_id)
+        };
+        assert_eq!(merged.get_pages().len(), last + 2);
+        assert_eq!(content(&merged, 0), content(&fixture_doc, 0));
+        assert_eq!(content(&merged, last + 1), content(&second_doc, 0));
+    }
+
+    #[test]
+    fn test_poster_dialog_sets_paper_and_overlap() {
+        let mut app = PdfBullApp::default();
+        let _ = handle_export_message(&mut app, Message::TogglePosterDialog(true));
+        assert!(!app.show_poster_dialog, "no document to slice");
+
+        app.tabs.push(DocumentTab::new(fixture_path()));
+        let _ = handle_export_message(&mut app, Message::TogglePosterDialog(true));
+        assert!(app.show_poster_dialog);
+        assert_eq!(app.poster_paper, crate::flow::PageSize::LETTER);
+
+        let _ = handle_export_message(&mut app, Message::SetPosterPaper(crate::flow::PageSize::A4));
+        let _ = handle_export_message(&mut app, Message::SetPosterOverlap(72.0));
+        assert_eq!(app.poster_paper, crate::flow::PageSize::A4);
+        assert_eq!(app.poster_overlap, 72.0);
+        let _ = handle_export_message(&mut app, Message::SetPosterOverlap(-5.0));
+        assert_eq!(app.poster_overlap, 0.0);
+    }
+}
@@ -17,6 +17,7 @@ pub fn handle_misc_message(app: &mut PdfBullApp, message: Message) -> Task<Messa
             app.status_message = None;
             Task::none()
         }
+        Message::FlushSidecars => app.flush_sidecars(),
         Message::IcedEvent(event) => {
             match event {
                 iced::Event::Window(iced::window::Event::CloseRequested) => {
@@ -41,12 +42,10 @@ pub fn handle_misc_message(app: &mut PdfBullApp, message: Message) -> Task<Messa
                             },
                         );
                     }
+                    app.flush_sidecars_blocking();
                     app.save_session_and_recent();
                     return iced::exit();
                 }
-                iced::Event::Window(iced::window::Event::FileDropped(path)) => {
-                    return app.update(Message::OpenFile(path));
-                }
                 iced::Event::Mouse(iced::mouse::Event::CursorMoved { position }) => {
                     app.cursor_position = Some(position);
                 }
@@ -73,6 +72,32 @@ pub fn handle_misc_message(app: &mut PdfBullApp, message: Message) -> Task<Messa
                 }) => {
                     use iced::keyboard::Key;
 
+                    // Keys typed into a popup must not trigger shortcuts.
+                    if app.note_editor.is_some()
+                        || app.show_print_dialog
+                        || app.show_extract_dialog
+                        || app.show_nup_dialog
+                        || app.show_barcode_dialog
+                        || app.show_poster_dialog
+                    {
+                        if key == Key::Named(iced::keyboard::key::Named::Escape) {
+                            return app.update(if app.show_print_dialog {
+                                Message::CancelPrint
+                            } else if app.show_extract_dialog {
+                                Message::ToggleExtractDialog(false)
+                            } else if app.show_nup_dialog {
+                                Message::ToggleNupDialog(false)
+                            } else if app.show_barcode_dialog {
+                                Message::ToggleBarcodeDialog(false)
+                            } else if app.show_poster_dialog {
+                                Message::TogglePosterDialog(false)
+                            } else {
+                                Message::CloseNoteEditor
+                            });
+                        }
+                        return Task::none();
+                    }
+
                     match key {
                         Key::Named(iced::keyboard::key::Named::F11) => {
                             return app.update(Message::ToggleFullscreen);
@@ -82,6 +107,9 @@ pub fn handle_misc_message(app: &mut PdfBullApp, message: Message) -> Task<Messa
                         }
                         Key::Character(c) => match c.as_str() {
                             "o" if modifiers.command() => return app.update(Message::OpenDocument),
+                            "c" if modifiers.command() => {
+                                return app.update(Message::CopySelection);
+                            }
                             "e" if modifiers.command() => return app.update(Message::ExportImage),
                             "p" if modifiers.command() && !app.tabs.is_empty() => {
                                 return app.update(Message::Print);
@@ -98,6 +126,9 @@ pub fn handle_misc_message(app: &mut PdfBullApp, message: Message) -> Task<Messa
                             "0" if modifiers.command() => return app.update(Message::ResetZoom),
                             "=" | "+" if modifiers.command() => return app.update(Message::ZoomIn),
                             "-" if modifiers.command() => return app.update(Message::ZoomOut),
+                            "t" | "T" if modifiers.command() && modifiers.shift() => {
+                                return app.update(Message::ReopenClosedTab);
+                            }
                             "w" if modifiers.command() && !app.tabs.is_empty() => {
                                 return app.update(Message::CloseTab(app.active_tab));
                             }
@@ -142,6 +173,7 @@ pub fn handle_misc_message(app: &mut PdfBullApp, message: Message) -> Task<Messa
             Task::none()
         }
         Message::ForceQuit => {
+            app.flush_sidecars_blocking();
             app.save_session_and_recent();
             iced::exit()
         }
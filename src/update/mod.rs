@@ -44,11 +44,7 @@ pub fn scroll_to_search_result(
             .sum();
 
         let actual_page = tab.page_mapping.get(page).copied().unwrap_or(page);
-        let page_rotation = tab
-            .page_rotations
-            .get(&actual_page)
-            .copied()
-            .unwrap_or(tab.rotation);
+        let page_rotation = tab.effective_rotation(actual_page);
         let original_height = tab.page_heights.get(page).copied().unwrap_or(800.0);
 
         let (_, ry, _, _) = crate::models::rotate_coords(
@@ -135,6 +131,38 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
     }
 
     match message {
+        // These load the document's file with lopdf, which a converted
+        // document doesn't have.
+        Message::SaveAnnotations
+        | Message::SaveOrganizedPDF
+        | Message::SplitDocument
+        | Message::ToggleExtractDialog(true)
+        | Message::ToggleNupDialog(true)
+        | Message::MakeBooklet
+        | Message::PrependToc
+        | Message::TogglePosterDialog(true)
+        | Message::LoadFormFields
+        | Message::FillForm(_)
+        | Message::ImportFormValues
+        | Message::FlattenForms
+        | Message::Print
+        | Message::ToggleWatermarkPrompt(true)
+        | Message::OptimizePDF
+        | Message::AddChart
+        | Message::ExportJson
+        | Message::ToggleBarcodeDialog(true)
+        | Message::ValidatePdf
+            if app.current_tab().is_some_and(|t| t.format.is_converted()) =>
+        {
+            let format = app
+                .current_tab()
+                .map(|t| t.format.name())
+                .unwrap_or_default();
+            app.status_message = Some(format!(
+                "This document was converted from {format}; only PDF files can be edited or exported"
+            ));
+            Task::none()
+        }
         Message::ResetZoom
         | Message::OpenSettings
         | Message::CloseSettings
@@ -155,9 +183,11 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
         | Message::ToggleMarkupBar
         | Message::ToggleTableMode
         | Message::ClearRecentFiles => app::handle_app_message(app, message),
-        Message::AddBookmark | Message::RemoveBookmark(_) | Message::JumpToBookmark(_) => {
-            bookmarks::handle_bookmark_message(app, message)
-        }
+        Message::AddBookmark
+        | Message::RemoveBookmark(_)
+        | Message::JumpToBookmark(_)
+        | Message::JumpToOutline(_)
+        | Message::ToggleOutlineNode(_) => bookmarks::handle_bookmark_message(app, message),
         Message::AnnotationTextChanged(text) => {
             app.annotation_text = text;
             Task::none()
@@ -166,19 +196,25 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
         | Message::AnnotationDragStart { .. }
         | Message::AnnotationDragUpdate { .. }
         | Message::AnnotationDragEnd
+        | Message::UpdateAnnotation(_, _, _, _, _)
         | Message::DeleteAnnotation(_)
         | Message::Undo
         | Message::Redo
         | Message::SaveAnnotations
         | Message::AnnotationsSaved(_)
         | Message::AnnotationsLoaded(_, _)
-        | Message::EditAnnotationText(_, _) => annotations::handle_annotation_message(app, message),
+        | Message::EditAnnotationText(_, _)
+        | Message::AddTextNote { .. }
+        | Message::OpenNoteEditor(_)
+        | Message::NoteEditorChanged(_)
+        | Message::SaveNoteEditor
+        | Message::CloseNoteEditor => annotations::handle_annotation_message(app, message),
         Message::SetFilter(_)
         | Message::ToggleAutoCrop
         | Message::ViewportChanged(_, _)
         | Message::SidebarViewportChanged(_)
         | Message::RequestRender(_)
-        | Message::PageRendered(_, _, _, _)
+        | Message::PageRendered(_, _, _, _, _)
         | Message::ThumbnailRendered(_, _, _, _)
         | Message::TextItemsLoaded(_, _, _)
         | Message::TablesDetected(_, _, _) => render::handle_render_message(app, message),
@@ -189,6 +225,8 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
         | Message::OpenFile(_)
         | Message::OpenRecentFile(_)
         | Message::CloseTab(_)
+        | Message::ReopenClosedTab
+        | Message::FileDropped(_)
         | Message::SwitchTab(_)
         | Message::TabReordered(_)
         | Message::DocumentModifiedExternally(_)
@@ -199,7 +237,9 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
         | Message::SaveAttachment(_)
         | Message::AttachmentSaved(_)
         | Message::ToggleLayer(_, _)
-        | Message::LayerToggled => tabs::handle_tab_message(app, message),
+        | Message::LayerToggled
+        | Message::SetReflowTextSize(_)
+        | Message::DocumentReflowed(_, _) => tabs::handle_tab_message(app, message),
         Message::NextPage
         | Message::PrevPage
         | Message::ZoomIn
@@ -207,7 +247,8 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
         | Message::SetZoom(_)
         | Message::JumpToPage(_)
         | Message::PageInputChanged(_)
-        | Message::PageInputSubmitted => navigation::handle_nav_message(app, message),
+        | Message::PageInputSubmitted
+        | Message::ClearPageInputError => navigation::handle_nav_message(app, message),
         Message::Search(_)
         | Message::PerformSearch(_)
         | Message::SearchResult(_, _)
@@ -218,6 +259,7 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
         | Message::ExtractTextToClipboard
         | Message::TextExtracted(_)
         | Message::CopyToClipboard(_)
+        | Message::CopySelection
         | Message::CopyImageToClipboard
         | Message::ExportImage
         | Message::ImageExported(_)
@@ -229,18 +271,62 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
         | Message::PrintersListed(_)
         | Message::PrintWithPrinter(_)
         | Message::PrintDone(_)
+        | Message::SetPrintRange(_)
+        | Message::PrintRangeInputChanged(_)
+        | Message::ConfirmPrint
+        | Message::CancelPrint
         | Message::AddWatermark(_)
         | Message::WatermarkDone(_)
         | Message::OptimizePDF
         | Message::PDFOptimized(_)
+        | Message::AddChart
+        | Message::ChartAdded(_)
+        | Message::ExportJson
+        | Message::JsonExported(_)
+        | Message::ImportHtml
+        | Message::HtmlConverted(_)
+        | Message::ToggleBarcodeDialog(_)
+        | Message::BarcodeInputChanged(_)
+        | Message::SetBarcodeKind(_)
+        | Message::AddBarcode
+        | Message::BarcodeAdded(_)
+        | Message::ValidatePdf
+        | Message::PdfValidated(_)
+        | Message::RepairPdf
+        | Message::PdfRepaired(_)
         | Message::MergeDocuments(_)
         | Message::DocumentsMerged(_)
+        | Message::MergeOpenTabs
+        | Message::OpenTabsMerged(_)
+        | Message::SplitDocument
         | Message::SplitPDF(_)
+        | Message::SplitProgress(_, _)
         | Message::PDFSplit(_)
+        | Message::ToggleExtractDialog(_)
+        | Message::ExtractRangeInputChanged(_)
+        | Message::ExtractPages
+        | Message::PagesExtracted(_)
+        | Message::ToggleNupDialog(_)
+        | Message::SetNupGrid(_, _)
+        | Message::ImposeNup
+        | Message::NupImposed(_)
+        | Message::MakeBooklet
+        | Message::BookletCreated(_)
+        | Message::PrependToc
+        | Message::TocPrepended(_)
+        | Message::TogglePosterDialog(_)
+        | Message::SetPosterPaper(_)
+        | Message::SetPosterOverlap(_)
+        | Message::MakePoster
+        | Message::PosterCreated(_)
         | Message::LoadFormFields
         | Message::FormFieldsLoaded(_)
         | Message::FormFieldChanged(_, _)
         | Message::FillForm(_)
+        | Message::ImportFormValues
+        | Message::FormValuesImported(_)
+        | Message::FlattenForms
+        | Message::FormsFlattened(_)
         | Message::FormFilled(_) => export::handle_export_message(app, message),
         Message::ToggleWatermarkPrompt(show) => {
             app.show_watermark_prompt = show;
@@ -258,6 +344,14 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
             app.watermark_input = input;
             Task::none()
         }
+        Message::SetWatermarkOpacity(opacity) => {
+            app.watermark_options.opacity = opacity.clamp(0.05, 1.0);
+            Task::none()
+        }
+        Message::SetWatermarkAngle(angle) => {
+            app.watermark_options.angle_degrees = angle.rem_euclid(360.0);
+            Task::none()
+        }
         Message::SubmitWatermark => {
             app.show_watermark_prompt = false;
             let text = app.watermark_input.clone();
@@ -334,7 +428,7 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
                     }
                     tab.total_pages = tab.page_mapping.len();
                     tab.current_page = tab.current_page.min(tab.total_pages.saturating_sub(1));
-                    tab.view_state.rendered_pages.clear();
+                    tab.view_state.clear_rendered_pages();
                     tab.view_state.thumbnails.clear();
                 }
             }
@@ -349,12 +443,17 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
                     if *current_rot < 0 {
                         *current_rot += 360;
                     }
-                    tab.view_state.rendered_pages.clear();
+                    tab.view_state.clear_rendered_pages();
                     tab.view_state.thumbnails.clear();
+                    tab.sidecar_dirty = true;
                 }
             }
+            app.bump_render_generation();
             app.render_visible_pages()
         }
+        Message::RotatePageClockwise(page_idx) => {
+            app.update(Message::OrganizerRotatePage(page_idx, 90))
+        }
         Message::OrganizerMovePage(page_idx, direction) => {
             if let Some(tab) = app.current_tab_mut() {
                 if let Ok(target_idx) = usize::try_from(page_idx as isize + direction) {
@@ -372,7 +471,7 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
                                 ann.page = page_idx;
                             }
                         }
-                        tab.view_state.rendered_pages.clear();
+                        tab.view_state.clear_rendered_pages();
                         tab.view_state.thumbnails.clear();
                     }
                 }
@@ -382,6 +481,7 @@ pub fn handle_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
         Message::EngineInitialized(_)
         | Message::Error(_)
         | Message::ClearStatus
+        | Message::FlushSidecars
         | Message::IcedEvent(_)
         | Message::LinkClicked(_)
         | Message::ForceQuit => misc::handle_misc_message(app, message),
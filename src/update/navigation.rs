@@ -64,11 +64,13 @@ pub fn handle_nav_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
         Message::ZoomIn => {
             let cursor_pos = app.cursor_position;
             let mut tasks = Vec::new();
+            let mut zoom_changed = false;
             if let Some(tab) = app.current_tab_mut() {
                 let old_zoom = tab.zoom;
                 let new_zoom = (old_zoom * 1.1).min(5.0);
                 if (new_zoom - old_zoom).abs() > 0.001 {
                     tab.zoom = new_zoom;
+                    zoom_changed = true;
 
                     let relative_y = if let Some(pos) = cursor_pos {
                         let toolbar_height = 50.0;
@@ -84,17 +86,23 @@ pub fn handle_nav_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                     tasks.push(crate::update::scroll_to_y(clamped_scroll_y));
                 }
             }
+            if zoom_changed {
+                app.bump_render_generation();
+                app.enforce_render_budget();
+            }
             tasks.push(app.render_visible_pages());
             Task::batch(tasks)
         }
         Message::ZoomOut => {
             let cursor_pos = app.cursor_position;
             let mut tasks = Vec::new();
+            let mut zoom_changed = false;
             if let Some(tab) = app.current_tab_mut() {
                 let old_zoom = tab.zoom;
                 let new_zoom = (old_zoom / 1.1).max(0.25);
                 if (new_zoom - old_zoom).abs() > 0.001 {
                     tab.zoom = new_zoom;
+                    zoom_changed = true;
 
                     let relative_y = if let Some(pos) = cursor_pos {
                         let toolbar_height = 50.0;
@@ -110,17 +118,23 @@ pub fn handle_nav_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                     tasks.push(crate::update::scroll_to_y(clamped_scroll_y));
                 }
             }
+            if zoom_changed {
+                app.bump_render_generation();
+                app.enforce_render_budget();
+            }
             tasks.push(app.render_visible_pages());
             Task::batch(tasks)
         }
         Message::SetZoom(zoom) => {
             let cursor_pos = app.cursor_position;
             let mut tasks = Vec::new();
+            let mut zoom_changed = false;
             if let Some(tab) = app.current_tab_mut() {
                 let old_zoom = tab.zoom;
                 let new_zoom = zoom.clamp(0.25, 5.0);
                 if (new_zoom - old_zoom).abs() > 0.001 {
                     tab.zoom = new_zoom;
+                    zoom_changed = true;
 
                     let relative_y = if let Some(pos) = cursor_pos {
                         let toolbar_height = 50.0;
@@ -136,6 +150,10 @@ pub fn handle_nav_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                     tasks.push(crate::update::scroll_to_y(clamped_scroll_y));
                 }
             }
+            if zoom_changed {
+                app.bump_render_generation();
+                app.enforce_render_budget();
+            }
             tasks.push(app.render_visible_pages());
             Task::batch(tasks)
         }
@@ -169,6 +187,7 @@ pub fn handle_nav_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
         }
         Message::PageInputChanged(s) => {
             app.page_input = s;
+            app.page_input_error = false;
             Task::none()
         }
         Message::PageInputSubmitted => {
@@ -181,10 +200,15 @@ pub fn handle_nav_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                 {
                     Some(idx)
                 } else {
-                    cleaned.parse::<usize>().ok().map(|p| p.saturating_sub(1))
+                    // 1-based input, clamped into the document's page range.
+                    cleaned
+                        .parse::<usize>()
+                        .ok()
+                        .map(|p| p.clamp(1, tab.total_pages.max(1)) - 1)
                 };
 
                 if let Some(page) = target_page {
+                    app.page_input_error = false;
                     return app.update(Message::JumpToPage(page));
                 }
 
@@ -194,9 +218,18 @@ pub fn handle_nav_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                     .cloned()
                     .unwrap_or_else(|| (tab.current_page + 1).to_string());
                 app.page_input = current_label;
+                app.page_input_error = true;
+                return Task::perform(
+                    tokio::time::sleep(std::time::Duration::from_millis(1200)),
+                    |()| Message::ClearPageInputError,
+                );
             }
             Task::none()
         }
+        Message::ClearPageInputError => {
+            app.page_input_error = false;
+            Task::none()
+        }
         _ => Task::none(),
     }
 }
@@ -255,6 +288,58 @@ mod tests {
         assert_eq!(app.tabs[0].current_page, 0);
     }
 
+    #[test]
+    fn test_render_budget_evicts_lru_pages_but_not_the_current_one() {
+        let mut app = setup_test_app();
+        let page_bytes = 100 * 50 * 4;
+        app.settings.max_render_cache_bytes = 3 * page_bytes;
+        app.tabs[0].current_page = 0;
+        app.tabs[0].view_state.visible_range = (0, 1);
+
+        // Page 0 is the oldest entry, but it is the page being read.
+        let start = std::time::Instant::now() - std::time::Duration::from_secs(60);
+        let insert = |app: &mut PdfBullApp, page_idx: usize| {
+            let tab = &mut app.tabs[0];
+            tab.insert_rendered_page(
+                page_idx,
+                1.0,
+                100,
+                50,
+                iced::widget::image::Handle::from_bytes(vec![]),
+            );
+            tab.view_state
+                .rendered_usage
+                .get_mut(&page_idx)
+                .unwrap()
+                .last_used = start + std::time::Duration::from_secs(page_idx as u64);
+        };
+        for page_idx in 0..5 {
+            insert(&mut app, page_idx);
+        }
+        app.enforce_render_budget();
+        let mut cached: Vec<usize> = app.tabs[0]
+            .view_state
+            .rendered_pages
+            .keys()
+            .copied()
+            .collect();
+        cached.sort_unstable();
+        assert_eq!(cached, [0, 3, 4]);
+        assert_eq!(app.render_cache_bytes(), 3 * page_bytes);
+
+        // A zoom change enforces the budget too.
+        insert(&mut app, 5);
+        let _ = handle_nav_message(&mut app, Message::ZoomIn);
+        let mut cached: Vec<usize> = app.tabs[0]
+            .view_state
+            .rendered_pages
+            .keys()
+            .copied()
+            .collect();
+        cached.sort_unstable();
+        assert_eq!(cached, [0, 4, 5]);
+    }
+
     #[test]
     fn test_zoom_calculation_zoom_in() {
         let mut app = setup_test_app();
@@ -308,6 +393,20 @@ mod tests {
         assert_eq!(app.tabs[0].zoom, 5.0);
     }
 
+    #[test]
+    fn test_zoom_bumps_render_generation() {
+        let mut app = setup_test_app();
+        app.rendering_set
+            .insert(crate::app::RenderTarget::Page(app.tabs[0].id, 0));
+        let _ = handle_nav_message(&mut app, Message::ZoomIn);
+        assert_eq!(app.tabs[0].render_generation, 1);
+        assert!(app.rendering_set.is_empty());
+
+        let zoom = app.tabs[0].zoom;
+        let _ = handle_nav_message(&mut app, Message::SetZoom(zoom));
+        assert_eq!(app.tabs[0].render_generation, 1);
+    }
+
     #[test]
     fn test_jump_to_valid_page() {
         let mut app = setup_test_app();
@@ -342,6 +441,26 @@ mod tests {
         assert_eq!(app.page_input, "3");
     }
 
+    #[test]
+    fn test_page_input_flags_invalid_input() {
+        let mut app = setup_test_app();
+        app.page_input = "abc".to_string();
+        let _ = handle_nav_message(&mut app, Message::PageInputSubmitted);
+        assert!(app.page_input_error);
+
+        let _ = handle_nav_message(&mut app, Message::PageInputChanged("4".to_string()));
+        assert!(!app.page_input_error);
+    }
+
+    #[test]
+    fn test_page_input_clamps_to_last_page() {
+        let mut app = setup_test_app();
+        app.page_input = "42".to_string();
+        let _ = handle_nav_message(&mut app, Message::PageInputSubmitted);
+        assert_eq!(app.tabs[0].current_page, 9);
+        assert!(!app.page_input_error);
+    }
+
     #[test]
     fn test_page_input_parsing_with_whitespace() {
         let mut app = setup_test_app();
@@ -11,15 +11,17 @@ pub fn handle_render_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                 && tab.render_filter != filter
             {
                 tab.render_filter = filter;
-                tab.view_state.rendered_pages.clear();
+                tab.view_state.clear_rendered_pages();
+                app.bump_render_generation();
             }
             app.render_visible_pages()
         }
         Message::ToggleAutoCrop => {
             if let Some(tab) = app.current_tab_mut() {
                 tab.auto_crop = !tab.auto_crop;
-                tab.view_state.rendered_pages.clear();
+                tab.view_state.clear_rendered_pages();
             }
+            app.bump_render_generation();
             app.render_visible_pages()
         }
         Message::ViewportChanged(y, height) => {
@@ -34,6 +36,7 @@ pub fn handle_render_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                     tab.cleanup_distant_pages();
                 }
             }
+            app.enforce_render_budget();
             app.render_visible_pages()
         }
         Message::SidebarViewportChanged(y) => {
@@ -43,7 +46,7 @@ pub fn handle_render_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
             Task::none()
         }
         Message::RequestRender(page_idx) => {
-            let (doc_id, zoom, rotation, filter, auto_crop, quality) = {
+            let (doc_id, zoom, rotation, filter, auto_crop, quality, generation) = {
                 let Some(tab) = app.current_tab() else {
                     return Task::none();
                 };
@@ -66,10 +69,13 @@ pub fn handle_render_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                 (
                     tab.id,
                     tab.zoom,
-                    tab.rotation,
+                    tab.effective_rotation(
+                        tab.page_mapping.get(page_idx).copied().unwrap_or(page_idx),
+                    ),
                     tab.render_filter,
                     tab.auto_crop,
                     app.settings.render_quality,
+                    tab.render_generation,
                 )
             };
 
@@ -92,7 +98,11 @@ pub fn handle_render_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                         quality,
                     };
                     if let Err(e) = cmd_tx.try_send(crate::commands::PdfCommand::Render(
-                        doc_id, page_idx, options, resp_tx,
+                        doc_id,
+                        page_idx,
+                        options,
+                        Some(generation),
+                        resp_tx,
                     )) {
                         tracing::warn!("Failed to send Render command: {e}");
                         return Err(crate::models::PdfError::Cancelled);
@@ -101,12 +111,26 @@ pub fn handle_render_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                         .await
                         .unwrap_or(Err(crate::models::PdfError::ChannelClosed))
                 },
-                move |res| Message::PageRendered(doc_id, page_idx, zoom, res),
+                move |res| Message::PageRendered(doc_id, page_idx, zoom, generation, res),
             )
         }
-        Message::PageRendered(doc_id, page_idx, scale, result) => {
+        Message::PageRendered(doc_id, page_idx, scale, generation, result) => {
+            // A newer generation already cleared this page from the rendering
+            // set and may have re-dispatched it, so leave that entry alone.
+            let is_stale = app
+                .tabs
+                .iter()
+                .find(|t| t.id == doc_id)
+                .is_some_and(|t| t.render_generation != generation);
+            if is_stale {
+                return Task::none();
+            }
+
             app.rendering_set
                 .remove(&crate::app::RenderTarget::Page(doc_id, page_idx));
+            if matches!(result, Err(PdfError::Cancelled)) {
+                return Task::none();
+            }
 
             let mut text_tasks: Vec<Task<Message>> = Vec::new();
 
@@ -116,12 +140,12 @@ pub fn handle_render_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                         let width = res.width;
                         let height = res.height;
                         let pixel_data = res.data.to_vec();
-                        tab.view_state.rendered_pages.insert(
+                        tab.insert_rendered_page(
                             page_idx,
-                            (
-                                scale,
-                                iced_image::Handle::from_rgba(width, height, pixel_data),
-                            ),
+                            scale,
+                            width,
+                            height,
+                            iced_image::Handle::from_rgba(width, height, pixel_data),
                         );
 
                         // Lazily fetch text (selection / accessibility) so the
@@ -202,6 +226,7 @@ pub fn handle_render_message(app: &mut PdfBullApp, message: Message) -> Task<Mes
                 }
             }
 
+            app.enforce_render_budget();
             let render_task = app.render_visible_pages();
             if text_tasks.is_empty() {
                 render_task
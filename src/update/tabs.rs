@@ -5,6 +5,37 @@ use crate::storage;
 use iced::Task;
 use std::path::PathBuf;
 
+const MAX_RECENTLY_CLOSED: usize = 10;
+
+/// Extensions the viewer opens. Anything but PDF is converted on opening;
+/// see [`crate::pdf_engine::DocumentFormat`].
+const OPENABLE_EXTENSIONS: &[&str] = &["pdf", "cbz", "epub", "xps", "oxps"];
+
+/// Body text sizes, in points, a reflowable document can be laid out at.
+const MIN_REFLOW_EM: f32 = 8.0;
+const MAX_REFLOW_EM: f32 = 32.0;
+
+fn is_openable(path: &std::path::Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        OPENABLE_EXTENSIONS
+            .iter()
+            .any(|known| ext.eq_ignore_ascii_case(known))
+    })
+}
+
+/// The most recently closed tab whose file still exists, dropping any
+/// closed after it whose files have since gone.
+fn pop_reopenable(
+    recently_closed: &mut Vec<(PathBuf, usize, f32)>,
+) -> Option<(PathBuf, usize, f32)> {
+    while let Some(entry) = recently_closed.pop() {
+        if entry.0.exists() {
+            return Some(entry);
+        }
+    }
+    None
+}
+
 pub fn handle_tab_message(app: &mut PdfBullApp, message: Message) -> Task<Message> {
     match message {
         Message::OpenDocument => {
@@ -19,7 +50,7 @@ pub fn handle_tab_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                 return Task::perform(
                     async move {
                         let file = rfd::AsyncFileDialog::new()
-                            .add_filter("PDF", &["pdf"])
+                            .add_filter("Documents", OPENABLE_EXTENSIONS)
                             .pick_file()
                             .await;
 
@@ -98,16 +129,36 @@ pub fn handle_tab_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                     tab.attachments = res.attachments.clone();
                     tab.layers = res.layers.clone();
                     tab.oc_config = res.oc_config.clone();
+                    tab.format = res.format;
                     tab.view_state.is_loading = false;
                     tab.page_mapping = (0..count).collect();
 
+                    if let Some(sidecar) = crate::storage::load_sidecar(&tab.path) {
+                        tab.bookmarks = sidecar.bookmarks;
+                        tab.bookmarks.retain(|b| b.page < count);
+                        tab.page_rotations = sidecar.page_rotations;
+                        tab.page_rotations.retain(|&page, _| page < count);
+                        // Show the sidecar's annotations right away; embedded
+                        // ones, if the engine loads any, are reconciled in
+                        // `AnnotationsLoaded`.
+                        tab.annotations.clone_from(&sidecar.annotations);
+                        tab.pending_sidecar_annotations = Some((
+                            sidecar.annotations,
+                            crate::storage::sidecar_is_newer(&tab.path),
+                        ));
+                    }
+
                     if let Some(session) = tab.pending_session.take() {
                         tab.current_page = session.current_page.min(count.saturating_sub(1));
                         tab.zoom = session.zoom;
                         tab.rotation = session.rotation;
                         tab.auto_crop = session.auto_crop;
                         tab.view_state.viewport_y = session.viewport_y;
-                        scroll_task = crate::update::scroll_to_y(session.viewport_y);
+                        scroll_task = if session.viewport_y <= 0.0 && tab.current_page > 0 {
+                            crate::update::scroll_to_page(tab, tab.current_page)
+                        } else {
+                            crate::update::scroll_to_y(session.viewport_y)
+                        };
                     } else {
                         tab.zoom = default_zoom;
                         tab.render_filter = default_filter;
@@ -270,6 +321,19 @@ pub fn handle_tab_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
             }
 
             let tab = app.tabs.remove(idx);
+            // The pending flush only sees open tabs, so write this one now.
+            let sidecar = if tab.sidecar_dirty {
+                vec![(tab.path.clone(), tab.sidecar())]
+            } else {
+                Vec::new()
+            };
+            if tab.total_pages > 0 {
+                app.recently_closed
+                    .push((tab.path.clone(), tab.current_page, tab.zoom));
+                if app.recently_closed.len() > MAX_RECENTLY_CLOSED {
+                    app.recently_closed.remove(0);
+                }
+            }
             if let Some(engine) = &app.engine {
                 let cmd_tx = engine.cmd_tx.clone();
                 let doc_id = tab.id;
@@ -289,7 +353,42 @@ pub fn handle_tab_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
             } else if app.active_tab >= app.tabs.len() {
                 app.active_tab = app.tabs.len() - 1;
             }
+            app.enforce_render_budget();
             app.save_session();
+            crate::app::write_sidecars(sidecar)
+        }
+        Message::FileDropped(path) => {
+            if is_openable(&path) {
+                // Each dropped file arrives as its own event, so a multi-file
+                // drop opens one tab per file and leaves the last one active.
+                return app.update(Message::OpenFile(path));
+            }
+            let name = path.file_name().map_or_else(
+                || path.display().to_string(),
+                |n| n.to_string_lossy().to_string(),
+            );
+            app.status_message = Some(format!(
+                "Ignored {name}: only {} files can be opened",
+                OPENABLE_EXTENSIONS.join(", ").to_uppercase()
+            ));
+            Task::none()
+        }
+        Message::ReopenClosedTab => {
+            if let Some((path, current_page, zoom)) = pop_reopenable(&mut app.recently_closed) {
+                let task = app.update(Message::OpenFile(path.clone()));
+                if let Some(tab) = app.tabs.last_mut() {
+                    tab.pending_session = Some(crate::models::TabSession {
+                        path: path.to_string_lossy().to_string(),
+                        current_page,
+                        zoom,
+                        viewport_y: 0.0,
+                        rotation: 0,
+                        auto_crop: false,
+                    });
+                }
+                return task;
+            }
+            app.status_message = Some("No recently closed tabs to reopen".into());
             Task::none()
         }
         Message::SwitchTab(idx) => {
@@ -545,7 +644,7 @@ pub fn handle_tab_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
                 let obj_id = layer.object_id;
                 let doc_id = tab.id;
 
-                tab.view_state.rendered_pages.clear();
+                tab.view_state.clear_rendered_pages();
                 tab.view_state.thumbnails.clear();
 
                 if let Some(engine) = &app.engine {
@@ -565,6 +664,177 @@ pub fn handle_tab_message(app: &mut PdfBullApp, message: Message) -> Task<Messag
             Task::none()
         }
         Message::LayerToggled => app.render_visible_pages(),
+        Message::SetReflowTextSize(em) => {
+            let Some(tab) = app.current_tab_mut() else {
+                return Task::none();
+            };
+            let layout = crate::pdf_engine::ReflowLayout {
+                em: em.clamp(MIN_REFLOW_EM, MAX_REFLOW_EM),
+                ..tab.reflow_layout
+            };
+            if tab.format != crate::pdf_engine::DocumentFormat::Epub || layout == tab.reflow_layout
+            {
+                return Task::none();
+            }
+            tab.reflow_layout = layout;
+            let doc_id = tab.id;
+
+            if let Some(engine) = &app.engine {
+                let cmd_tx = engine.cmd_tx.clone();
+                return Task::perform(
+                    async move {
+                        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+                        let _ = cmd_tx
+                            .send(crate::commands::PdfCommand::LayoutDocument(
+                                doc_id, layout, resp_tx,
+                            ))
+                            .await;
+                        resp_rx.await.unwrap_or(Err(PdfError::ChannelClosed))
+                    },
+                    move |res| Message::DocumentReflowed(doc_id, res),
+                );
+            }
+            Task::none()
+        }
+        Message::DocumentReflowed(doc_id, result) => {
+            let res = match result {
+                Ok(res) => res,
+                Err(e) => {
+                    app.status_message = Some(format!("Failed to lay the document out: {e}"));
+                    return Task::none();
+                }
+            };
+            let Some(tab) = app.tabs.iter_mut().find(|t| t.id == doc_id) else {
+                return Task::none();
+            };
+            let count = res.page_count;
+            tab.total_pages = count;
+            tab.page_heights = res.page_heights;
+            tab.page_width = res.max_width;
+            tab.outline = res.outline;
+            tab.links = res.links;
+            tab.page_labels = res.page_labels;
+            tab.page_mapping = (0..count).collect();
+            tab.current_page = tab.current_page.min(count.saturating_sub(1));
+            // Page numbers now point at different text.
+            tab.search_results.clear();
+            tab.selected_text = None;
+            tab.selected_boxes.clear();
+            tab.selected_page = None;
+            tab.view_state.clear_rendered_pages();
+            tab.view_state.thumbnails.clear();
+
+            if app.current_tab().is_some_and(|t| t.id == doc_id) {
+                app.bump_render_generation();
+                let scroll = app
+                    .current_tab()
+                    .map(|tab| crate::update::scroll_to_page(tab, tab.current_page))
+                    .unwrap_or_else(Task::none);
+                return Task::batch(vec![scroll, app.render_visible_pages()]);
+            }
+            Task::none()
+        }
         _ => Task::none(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_document.pdf")
+    }
+
+    fn open_tab(app: &mut PdfBullApp, path: PathBuf, total_pages: usize) {
+        let mut tab = DocumentTab::new(path);
+        tab.total_pages = total_pages;
+        tab.page_heights = vec![800.0; total_pages];
+        app.tabs.push(tab);
+    }
+
+    #[test]
+    fn test_closed_tabs_reopen_newest_existing_first() {
+        let mut app = PdfBullApp::default();
+        open_tab(&mut app, fixture_path(), 3);
+        open_tab(&mut app, PathBuf::from("/missing/gone.pdf"), 2);
+        open_tab(&mut app, PathBuf::from("/missing/never_loaded.pdf"), 0);
+        app.tabs[0].current_page = 2;
+        app.tabs[0].zoom = 1.5;
+
+        for _ in 0..3 {
+            let _ = handle_tab_message(&mut app, Message::CloseTab(0));
+        }
+        assert!(app.tabs.is_empty());
+        // Tabs that never finished loading aren't remembered.
+        assert_eq!(app.recently_closed.len(), 2);
+
+        let reopened = pop_reopenable(&mut app.recently_closed);
+        assert_eq!(reopened, Some((fixture_path(), 2, 1.5)));
+        assert!(app.recently_closed.is_empty());
+
+        let _ = handle_tab_message(&mut app, Message::ReopenClosedTab);
+        assert!(app.tabs.is_empty());
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("No recently closed tabs to reopen")
+        );
+    }
+
+    #[test]
+    fn test_converted_tabs_refuse_file_edits() {
+        assert!(is_openable(std::path::Path::new("/comics/issue.CBZ")));
+        assert!(is_openable(std::path::Path::new("/forms/invoice.oxps")));
+        assert!(is_openable(std::path::Path::new("/books/novel.epub")));
+        assert!(!is_openable(std::path::Path::new("/notes/readme.txt")));
+
+        let mut app = PdfBullApp::default();
+        app.loaded = true;
+        open_tab(&mut app, PathBuf::from("/comics/issue.cbz"), 2);
+        app.tabs[0].format = crate::pdf_engine::DocumentFormat::Cbz;
+
+        let _ = crate::update::handle_message(&mut app, Message::OptimizePDF);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("This document was converted from CBZ; only PDF files can be edited or exported")
+        );
+    }
+
+    #[test]
+    fn test_reflow_text_size_only_applies_to_reflowable_tabs() {
+        let mut app = PdfBullApp::default();
+        open_tab(&mut app, fixture_path(), 3);
+        let _ = handle_tab_message(&mut app, Message::SetReflowTextSize(20.0));
+        assert_eq!(
+            app.tabs[0].reflow_layout,
+            crate::pdf_engine::ReflowLayout::default()
+        );
+
+        app.tabs[0].format = crate::pdf_engine::DocumentFormat::Epub;
+        let _ = handle_tab_message(&mut app, Message::SetReflowTextSize(100.0));
+        assert_eq!(app.tabs[0].reflow_layout.em, MAX_REFLOW_EM);
+
+        let doc_id = app.tabs[0].id;
+        let reflowed = crate::models::OpenResult {
+            id: doc_id,
+            page_count: 5,
+            page_heights: vec![595.0; 5],
+            max_width: 420.0,
+            outline: vec![],
+            links: vec![],
+            metadata: crate::models::DocumentMetadata::default(),
+            page_labels: vec![],
+            is_encrypted: false,
+            signatures: vec![],
+            attachments: vec![],
+            layers: vec![],
+            oc_config: None,
+            format: crate::pdf_engine::DocumentFormat::Epub,
+        };
+        app.tabs[0].current_page = 2;
+        let _ = handle_tab_message(&mut app, Message::DocumentReflowed(doc_id, Ok(reflowed)));
+        assert_eq!(app.tabs[0].total_pages, 5);
+        assert_eq!(app.tabs[0].page_mapping, [0, 1, 2, 3, 4]);
+        assert_eq!(app.tabs[0].current_page, 2);
+    }
+}
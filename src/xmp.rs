@@ -0,0 +1,388 @@
+//! XMP metadata packets.
+//!
+//! Only the properties that mirror the document Info dictionary are read
+//! and written: `dc:title`, `dc:creator`, `dc:description`, `pdf:Keywords`,
+//! `xmp:CreatorTool`, `pdf:Producer`, `xmp:CreateDate` and
+//! `xmp:ModifyDate`. XMP dates are ISO 8601 where Info uses `D:` strings;
+//! both forms are converted here.
+
+use crate::html::{Token, tokenize};
+use crate::models::DocumentMetadata;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy)]
+enum Property {
+    Title,
+    Creator,
+    Description,
+    Keywords,
+    CreatorTool,
+    Producer,
+    CreateDate,
+    ModifyDate,
+}
+
+impl Property {
+    /// Match a lowercased element or attribute name. `xap:` is the prefix
+    /// older writers used for the XMP basic schema.
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "dc:title" => Self::Title,
+            "dc:creator" => Self::Creator,
+            "dc:description" => Self::Description,
+            "pdf:keywords" => Self::Keywords,
+            "xmp:creatortool" | "xap:creatortool" => Self::CreatorTool,
+            "pdf:producer" => Self::Producer,
+            "xmp:createdate" | "xap:createdate" => Self::CreateDate,
+            "xmp:modifydate" | "xap:modifydate" => Self::ModifyDate,
+            _ => return None,
+        })
+    }
+}
+
+/// Read the Info-equivalent properties of an XMP packet.
+///
+/// Properties may be elements or attributes of `rdf:Description`. Language
+/// alternatives yield their first entry and `dc:creator` its entries joined
+/// with ", ". Dates are returned as written, in ISO 8601.
+pub fn read_xmp(packet: &str) -> DocumentMetadata {
+    let mut values: [Vec<String>; 8] = Default::default();
+    let mut open: Option<(Property, String)> = None;
+    let mut text = String::new();
+    for token in tokenize(packet) {
+        match token {
+            Token::Open {
+                name,
+                attrs,
+                self_closing,
+            } => {
+                if name == "rdf:description" {
+                    for (key, value) in attrs {
+                        if let Some(property) = Property::from_name(&key) {
+                            values[property as usize].push(value);
+                        }
+                    }
+                } else if let Some(property) = Property::from_name(&name)
+                    && !self_closing
+                {
+                    open = Some((property, name));
+                }
+                text.clear();
+            }
+            Token::Text(t) => text.push_str(&t),
+            Token::Close(name) => {
+                let Some((property, element)) = &open else {
+                    continue;
+                };
+                // Items of an `rdf:Alt`, `rdf:Seq` or `rdf:Bag`, or the
+                // text of a simple property when its element closes.
+                if name == "rdf:li" || name == *element {
+                    let value = text.trim();
+                    if !value.is_empty() {
+                        values[*property as usize].push(value.to_string());
+                    }
+                }
+                if name == *element {
+                    open = None;
+                }
+                text.clear();
+            }
+            Token::Style(_) => {}
+        }
+    }
+
+    let first = |property: Property| values[property as usize].first().cloned();
+    let creators = &values[Property::Creator as usize];
+    DocumentMetadata {
+        title: first(Property::Title),
+        author: (!creators.is_empty()).then(|| creators.join(", ")),
+        subject: first(Property::Description),
+        keywords: first(Property::Keywords),
+        creator: first(Property::CreatorTool),
+        producer: first(Property::Producer),
+        creation_date: first(Property::CreateDate),
+        modification_date: first(Property::ModifyDate),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A complete XMP packet holding the set fields of `metadata`. Dates may
+/// be given in either form and are written in ISO 8601; ones that don't
+/// parse are left out.
+pub fn write_xmp(metadata: &DocumentMetadata) -> String {
+    let mut body = String::from("   <dc:format>application/pdf</dc:format>\n");
+    let alt = |body: &mut String, element: &str, value: &str| {
+        let _ = writeln!(
+            body,
+            "   <{element}><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></{element}>",
+            escape(value)
+        );
+    };
+    let simple = |body: &mut String, element: &str, value: &str| {
+        let _ = writeln!(body, "   <{element}>{}</{element}>", escape(value));
+    };
+    if let Some(title) = &metadata.title {
+        alt(&mut body, "dc:title", title);
+    }
+    // Info has a single author string, which PDF/A maps to one entry.
+    if let Some(author) = &metadata.author {
+        let _ = writeln!(
+            body,
+            "   <dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>",
+            escape(author)
+        );
+    }
+    if let Some(subject) = &metadata.subject {
+        alt(&mut body, "dc:description", subject);
+    }
+    if let Some(keywords) = &metadata.keywords {
+        simple(&mut body, "pdf:Keywords", keywords);
+    }
+    if let Some(creator) = &metadata.creator {
+        simple(&mut body, "xmp:CreatorTool", creator);
+    }
+    if let Some(producer) = &metadata.producer {
+        simple(&mut body, "pdf:Producer", producer);
+    }
+    if let Some(date) = metadata.creation_date.as_deref().and_then(xmp_date) {
+        simple(&mut body, "xmp:CreateDate", &date);
+    }
+    if let Some(date) = metadata.modification_date.as_deref().and_then(xmp_date) {
+        simple(&mut body, "xmp:ModifyDate", &date);
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         \x20<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         \x20 <rdf:Description rdf:about=\"\"\n\
+         \x20   xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n\
+         \x20   xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n\
+         \x20   xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\">\n\
+         {body}\
+         \x20 </rdf:Description>\n\
+         \x20</rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>"
+    )
+}
+
+/// Add the Factur-X invoice properties to a packet from [`write_xmp`],
+/// along with the PDF/A extension schema that declares them. `profile` is
+/// the conformance level, such as `EN 16931` or `BASIC`.
+pub fn with_factur_x(packet: &str, file_name: &str, profile: &str) -> String {
+    let description = format!(
+        "  <rdf:Description rdf:about=\"\"\n\
+         \x20   xmlns:fx=\"urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#\">\n\
+         \x20  <fx:DocumentType>INVOICE</fx:DocumentType>\n\
+         \x20  <fx:DocumentFileName>{}</fx:DocumentFileName>\n\
+         \x20  <fx:Version>1.0</fx:Version>\n\
+         \x20  <fx:ConformanceLevel>{}</fx:ConformanceLevel>\n\
+         \x20 </rdf:Description>\n\
+         \x20 <rdf:Description rdf:about=\"\"\n\
+         \x20   xmlns:pdfaExtension=\"http://www.aiim.org/pdfa/ns/extension/\"\n\
+         \x20   xmlns:pdfaSchema=\"http://www.aiim.org/pdfa/ns/schema#\"\n\
+         \x20   xmlns:pdfaProperty=\"http://www.aiim.org/pdfa/ns/property#\">\n\
+         \x20  <pdfaExtension:schemas><rdf:Bag><rdf:li rdf:parseType=\"Resource\">\n\
+         \x20   <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>\n\
+         \x20   <pdfaSchema:namespaceURI>urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#</pdfaSchema:namespaceURI>\n\
+         \x20   <pdfaSchema:prefix>fx</pdfaSchema:prefix>\n\
+         \x20   <pdfaSchema:property><rdf:Seq>\n\
+         {}\
+         \x20   </rdf:Seq></pdfaSchema:property>\n\
+         \x20  </rdf:li></rdf:Bag></pdfaExtension:schemas>\n\
+         \x20 </rdf:Description>\n",
+        escape(file_name),
+        escape(profile),
+        [
+            ("DocumentFileName", "The name of the embedded XML invoice file"),
+            ("DocumentType", "INVOICE"),
+            ("Version", "The version of the Factur-X XML schema"),
+            ("ConformanceLevel", "The conformance level of the embedded data"),
+        ]
+        .iter()
+        .fold(String::new(), |mut out, (name, description)| {
+            let _ = writeln!(
+                out,
+                "    <rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>{name}</pdfaProperty:name>\
+                 <pdfaProperty:valueType>Text</pdfaProperty:valueType>\
+                 <pdfaProperty:category>external</pdfaProperty:category>\
+                 <pdfaProperty:description>{description}</pdfaProperty:description></rdf:li>"
+            );
+            out
+        })
+    );
+    packet.rfind(" </rdf:RDF>").map_or_else(
+        || packet.to_string(),
+        |at| format!("{}{description}{}", &packet[..at], &packet[at..]),
+    )
+}
+
+/// Split off the first `n` ASCII digits of `s`.
+fn digits(s: &str, n: usize) -> Option<(&str, &str)> {
+    let head = s.get(..n)?;
+    head.bytes()
+        .all(|b| b.is_ascii_digit())
+        .then_some((head, &s[n..]))
+}
+
+/// Convert a date to XMP's ISO 8601 form. A `D:YYYYMMDDHHmmSSOHH'mm'`
+/// string may stop after any field; an ISO date is returned as it is.
+pub fn xmp_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    let Some(rest) = date.strip_prefix("D:") else {
+        return digits(date, 4).map(|_| date.to_string());
+    };
+    let (year, mut rest) = digits(rest, 4)?;
+    let mut out = year.to_string();
+    for (separator, index) in ["-", "-", "T", ":", ":"].into_iter().zip(0..) {
+        let Some((field, tail)) = digits(rest, 2) else {
+            break;
+        };
+        // Hours without minutes aren't valid ISO 8601.
+        if index == 2 && digits(tail, 2).is_none() {
+            break;
+        }
+        out.push_str(separator);
+        out.push_str(field);
+        rest = tail;
+    }
+    if out.len() > 10 {
+        match rest.chars().next() {
+            Some('Z') => out.push('Z'),
+            Some(sign @ ('+' | '-')) => {
+                let (hours, tail) = digits(&rest[1..], 2)?;
+                let minutes = digits(tail.trim_start_matches('\''), 2).map_or("00", |(m, _)| m);
+                let _ = write!(out, "{sign}{hours}:{minutes}");
+            }
+            _ => {}
+        }
+    }
+    Some(out)
+}
+
+/// Convert a date to the Info dictionary's `D:` form. A `D:` string is
+/// returned as it is; an ISO 8601 date may stop after any field and its
+/// fractional seconds are dropped.
+pub fn pdf_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    if date.starts_with("D:") {
+        return Some(date.to_string());
+    }
+    let (year, mut rest) = digits(date, 4)?;
+    let mut out = format!("D:{year}");
+    for separator in ['-', '-', 'T', ':', ':'] {
+        let Some((field, tail)) = rest.strip_prefix(separator).and_then(|r| digits(r, 2)) else {
+            break;
+        };
+        out.push_str(field);
+        rest = tail;
+    }
+    if let Some(fraction) = rest.strip_prefix('.') {
+        rest = fraction.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    match rest.chars().next() {
+        Some('Z') => out.push('Z'),
+        Some(sign @ ('+' | '-')) => {
+            let (hours, tail) = digits(&rest[1..], 2)?;
+            let minutes = digits(tail.trim_start_matches(':'), 2).map_or("00", |(m, _)| m);
+            let _ = write!(out, "{sign}{hours}'{minutes}'");
+        }
+        _ => {}
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_elements_and_attributes() {
+        let packet = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+            <x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF>
+            <rdf:Description rdf:about="" pdf:Producer="Typesetter 2.1"
+                xap:CreateDate="2024-03-01T09:30:00+01:00"/>
+            <rdf:Description rdf:about="">
+              <dc:title><rdf:Alt>
+                <rdf:li xml:lang="x-default">Fish &amp; Chips</rdf:li>
+                <rdf:li xml:lang="fr">Poisson-frites</rdf:li>
+              </rdf:Alt></dc:title>
+              <dc:creator><rdf:Seq><rdf:li>Ada</rdf:li><rdf:li>Grace</rdf:li></rdf:Seq></dc:creator>
+              <pdf:Keywords>food, recipes</pdf:Keywords>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let meta = read_xmp(packet);
+        assert_eq!(meta.title.as_deref(), Some("Fish & Chips"));
+        assert_eq!(meta.author.as_deref(), Some("Ada, Grace"));
+        assert_eq!(meta.keywords.as_deref(), Some("food, recipes"));
+        assert_eq!(meta.producer.as_deref(), Some("Typesetter 2.1"));
+        assert_eq!(
+            meta.creation_date.as_deref(),
+            Some("2024-03-01T09:30:00+01:00")
+        );
+        assert_eq!(meta.subject, None);
+    }
+
+    #[test]
+    fn test_write_then_read() {
+        let meta = DocumentMetadata {
+            title: Some("Q3 <draft>".into()),
+            author: Some("A. Writer".into()),
+            subject: Some("Quarterly \"numbers\"".into()),
+            keywords: None,
+            creator: Some("PDFbull".into()),
+            producer: None,
+            creation_date: Some("D:20240301093000+01'00'".into()),
+            modification_date: Some("2024-03-02T10:00:00Z".into()),
+        };
+        let packet = write_xmp(&meta);
+        assert!(packet.contains("<xmp:CreateDate>2024-03-01T09:30:00+01:00</xmp:CreateDate>"));
+        let back = read_xmp(&packet);
+        assert_eq!(
+            back,
+            DocumentMetadata {
+                creation_date: Some("2024-03-01T09:30:00+01:00".into()),
+                ..meta
+            }
+        );
+    }
+
+    #[test]
+    fn test_factur_x_keeps_packet_readable() {
+        let meta = DocumentMetadata {
+            title: Some("Invoice 17".into()),
+            ..DocumentMetadata::default()
+        };
+        let packet = with_factur_x(&write_xmp(&meta), "factur-x.xml", "BASIC WL");
+        assert!(packet.contains("<fx:ConformanceLevel>BASIC WL</fx:ConformanceLevel>"));
+        assert!(packet.contains("<pdfaSchema:prefix>fx</pdfaSchema:prefix>"));
+        assert!(packet.ends_with("</x:xmpmeta>\n<?xpacket end=\"w\"?>"));
+        assert_eq!(read_xmp(&packet), meta);
+    }
+
+    #[test]
+    fn test_date_conversion() {
+        assert_eq!(
+            xmp_date("D:20240301093005-05'30'").as_deref(),
+            Some("2024-03-01T09:30:05-05:30")
+        );
+        assert_eq!(xmp_date("D:202403").as_deref(), Some("2024-03"));
+        assert_eq!(xmp_date("D:2024030109").as_deref(), Some("2024-03-01"));
+        assert_eq!(xmp_date("yesterday"), None);
+        assert_eq!(
+            pdf_date("2024-03-01T09:30:05.250Z").as_deref(),
+            Some("D:20240301093005Z")
+        );
+        assert_eq!(
+            pdf_date("2024-03-01T09:30+01:00").as_deref(),
+            Some("D:202403010930+01'00'")
+        );
+        assert_eq!(pdf_date("2024").as_deref(), Some("D:2024"));
+    }
+}
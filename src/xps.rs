@@ -0,0 +1,765 @@
+//! XPS and OpenXPS documents.
+//!
+//! An XPS file is a zip whose fixed document sequence lists documents,
+//! each listing `FixedPage` parts. Pages are converted to PDF so they open
+//! like any other document. Supported markup is `Canvas` with render
+//! transforms, `Path` with abbreviated geometry and solid or image fills,
+//! solid strokes, and `Glyphs`, whose text is drawn in Helvetica rather
+//! than the embedded font. Gradients, visual brushes, opacity masks, clips
+//! and path geometry given as elements are skipped with a warning.
+
+use crate::archive::{ZipArchive, resolve_path};
+use crate::content_stream::Matrix;
+use crate::filters::{self, FilterType};
+use crate::html::{Token, tokenize};
+use crate::models::{PdfError, PdfResult};
+use crate::pdf_engine::{helvetica_font, win_ansi_bytes};
+use lopdf::{Document, Object, ObjectId};
+use pdf_writer::{Content, Name, Str};
+use std::collections::HashMap;
+
+/// XPS units are 1/96 inch.
+const UNIT: f32 = 0.75;
+const FIXED_REPRESENTATION: &str = "http://schemas.microsoft.com/xps/2005/06/fixedrepresentation";
+const OXPS_FIXED_REPRESENTATION: &str = "http://schemas.openxps.org/oxps/v1.0/fixedrepresentation";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PathOp {
+    Move(f32, f32),
+    Line(f32, f32),
+    Cubic(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PathToken {
+    Command(char),
+    Number(f32),
+}
+
+fn lex_path(data: &str) -> Option<Vec<PathToken>> {
+    let mut tokens = Vec::new();
+    let mut rest = data;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() || c == ',' {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_alphabetic() {
+            tokens.push(PathToken::Command(c));
+            rest = &rest[1..];
+        } else {
+            let mut end = 0;
+            for (i, ch) in rest.char_indices() {
+                let exponent_sign = (ch == '-' || ch == '+')
+                    && i > 0
+                    && matches!(rest.as_bytes()[i - 1], b'e' | b'E');
+                let sign = (ch == '-' || ch == '+') && i == 0;
+                if ch.is_ascii_digit() || matches!(ch, '.' | 'e' | 'E') || sign || exponent_sign {
+                    end = i + 1;
+                } else {
+                    break;
+                }
+            }
+            tokens.push(PathToken::Number(rest[..end].parse().ok()?));
+            rest = &rest[end..];
+        }
+    }
+    Some(tokens)
+}
+
+/// Parse abbreviated path geometry, e.g. `F1 M 10,10 L 50,10 50,40 Z`.
+/// Returns the path and whether it fills even-odd, or `None` for syntax
+/// this reader doesn't handle (arcs and smooth curves).
+fn parse_path_data(data: &str) -> Option<(Vec<PathOp>, bool)> {
+    let tokens = lex_path(data)?;
+    let mut ops = Vec::new();
+    let mut even_odd = true;
+    let (mut x, mut y) = (0.0, 0.0);
+    let mut start = (0.0, 0.0);
+    let mut pos = 0;
+    let mut command = None;
+    while pos < tokens.len() {
+        if let PathToken::Command(letter) = tokens[pos] {
+            command = Some(letter);
+            pos += 1;
+        }
+        let letter = command?;
+        let mut take = |n: usize| -> Option<Vec<f32>> {
+            let values = tokens.get(pos..pos + n)?;
+            let numbers = values
+                .iter()
+                .map(|t| match t {
+                    PathToken::Number(v) => Some(*v),
+                    PathToken::Command(_) => None,
+                })
+                .collect::<Option<Vec<f32>>>()?;
+            pos += n;
+            Some(numbers)
+        };
+        let relative = letter.is_ascii_lowercase();
+        let (dx, dy) = if relative { (x, y) } else { (0.0, 0.0) };
+        match letter.to_ascii_uppercase() {
+            'F' => {
+                even_odd = take(1)?[0] == 0.0;
+                command = None;
+            }
+            'M' => {
+                let args = take(2)?;
+                (x, y) = (args[0] + dx, args[1] + dy);
+                start = (x, y);
+                ops.push(PathOp::Move(x, y));
+                // Further pairs after a move are implicit lines.
+                command = Some(if relative { 'l' } else { 'L' });
+            }
+            'L' => {
+                let args = take(2)?;
+                (x, y) = (args[0] + dx, args[1] + dy);
+                ops.push(PathOp::Line(x, y));
+            }
+            'H' => {
+                x = take(1)?[0] + dx;
+                ops.push(PathOp::Line(x, y));
+            }
+            'V' => {
+                y = take(1)?[0] + dy;
+                ops.push(PathOp::Line(x, y));
+            }
+            'C' => {
+                let args = take(6)?;
+                ops.push(PathOp::Cubic(
+                    args[0] + dx,
+                    args[1] + dy,
+                    args[2] + dx,
+                    args[3] + dy,
+                    args[4] + dx,
+                    args[5] + dy,
+                ));
+                (x, y) = (args[4] + dx, args[5] + dy);
+            }
+            'Q' => {
+                let args = take(4)?;
+                let (qx, qy) = (args[0] + dx, args[1] + dy);
+                let (ex, ey) = (args[2] + dx, args[3] + dy);
+                // Raise the quadratic to a cubic with the same shape.
+                ops.push(PathOp::Cubic(
+                    x + (qx - x) * 2.0 / 3.0,
+                    y + (qy - y) * 2.0 / 3.0,
+                    ex + (qx - ex) * 2.0 / 3.0,
+                    ey + (qy - ey) * 2.0 / 3.0,
+                    ex,
+                    ey,
+                ));
+                (x, y) = (ex, ey);
+            }
+            'Z' => {
+                ops.push(PathOp::Close);
+                (x, y) = start;
+                command = None;
+            }
+            _ => return None,
+        }
+    }
+    Some((ops, even_odd))
+}
+
+/// A colour in `#RRGGBB`, `#AARRGGBB` or `sc#[A,]R,G,B` form. Fully
+/// transparent colours are `None`, as there is nothing to draw.
+fn parse_color(value: &str) -> Option<[f32; 3]> {
+    let value = value.trim();
+    if let Some(sc) = value.strip_prefix("sc#") {
+        let parts: Vec<f32> = sc
+            .split(',')
+            .map(|p| p.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        return match parts[..] {
+            [a, ..] if parts.len() == 4 && a <= 0.0 => None,
+            [_, r, g, b] | [r, g, b] => Some([r, g, b].map(|c| c.clamp(0.0, 1.0))),
+            _ => None,
+        };
+    }
+    let hex = value.strip_prefix('#')?;
+    let channels: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        })
+        .collect::<Option<_>>()?;
+    let rgb = match channels[..] {
+        [0, ..] if channels.len() == 4 => return None,
+        [_, r, g, b] | [r, g, b] => [r, g, b],
+        _ => return None,
+    };
+    Some(rgb.map(|c| f32::from(c) / 255.0))
+}
+
+/// Parse `N` comma-separated numbers, e.g. a matrix or a viewport.
+fn parse_numbers<const N: usize>(value: &str) -> Option<[f32; N]> {
+    let values: Vec<f32> = value
+        .split(',')
+        .map(|v| v.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    values.try_into().ok()
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+fn number(attrs: &[(String, String)], key: &str) -> Option<f32> {
+    attr(attrs, key)?.trim().parse().ok()
+}
+
+#[derive(Debug, Clone)]
+enum Fill {
+    Solid([f32; 3]),
+    Image { source: String, viewport: [f32; 4] },
+}
+
+/// A `Path` or `Glyphs` element, drawn once its property children have
+/// been read.
+#[derive(Debug, Clone)]
+struct Element {
+    glyphs: bool,
+    attrs: Vec<(String, String)>,
+    fill: Option<Fill>,
+    stroke: Option<[f32; 3]>,
+    transform: Option<Matrix>,
+}
+
+impl Element {
+    fn new(name: &str, attrs: Vec<(String, String)>) -> Self {
+        let fill = attr(&attrs, "fill").and_then(parse_color).map(Fill::Solid);
+        let stroke = attr(&attrs, "stroke").and_then(parse_color);
+        let transform = attr(&attrs, "rendertransform").and_then(parse_numbers::<6>);
+        Self {
+            glyphs: name == "glyphs",
+            attrs,
+            fill,
+            stroke,
+            transform,
+        }
+    }
+}
+
+/// Converts one fixed page's markup into a content stream.
+struct PageWriter<'a> {
+    archive: &'a ZipArchive,
+    doc: &'a mut Document,
+    /// Directory of the page part, for resolving relative URIs.
+    base: String,
+    content: Content,
+    images: &'a mut HashMap<String, ObjectId>,
+    /// `XObject`s drawn on this page, as resource name and object.
+    used: Vec<(String, ObjectId)>,
+    /// Width and height from the `FixedPage` element.
+    size: Option<(f32, f32)>,
+    /// The `Path` or `Glyphs` whose property elements are being read.
+    pending: Option<Element>,
+    /// Open property elements, e.g. `path.fill`, innermost last.
+    properties: Vec<String>,
+}
+
+impl PageWriter<'_> {
+    fn draw_path(&mut self, ops: &[PathOp]) {
+        for op in ops {
+            match *op {
+                PathOp::Move(x, y) => {
+                    self.content.move_to(x, y);
+                }
+                PathOp::Line(x, y) => {
+                    self.content.line_to(x, y);
+                }
+                PathOp::Cubic(x1, y1, x2, y2, x3, y3) => {
+                    self.content.cubic_to(x1, y1, x2, y2, x3, y3);
+                }
+                PathOp::Close => {
+                    self.content.close_path();
+                }
+            }
+        }
+    }
+
+    fn image(&mut self, source: &str) -> PdfResult<ObjectId> {
+        let path = resolve_path(&self.base, source);
+        if let Some(&id) = self.images.get(&path) {
+            return Ok(id);
+        }
+        let bytes = self.archive.read(&path)?;
+        let img = image::load_from_memory(&bytes)
+            .map_err(|e| PdfError::from(format!("Failed to decode '{path}': {e}")))?
+            .to_rgb8();
+        let id = self.doc.add_object(lopdf::Stream::new(
+            lopdf::Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"XObject".to_vec())),
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(i64::from(img.width()))),
+                ("Height", Object::Integer(i64::from(img.height()))),
+                ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+                ("Filter", Object::Name(FilterType::Flate.name().to_vec())),
+            ]),
+            filters::encode(img.as_raw(), FilterType::Flate)?,
+        ));
+        self.images.insert(path, id);
+        Ok(id)
+    }
+
+    fn draw_element(&mut self, element: &Element) -> PdfResult<()> {
+        self.content.save_state();
+        if let Some(matrix) = element.transform {
+            self.content.transform(matrix);
+        }
+        if element.glyphs {
+            self.draw_glyphs(element);
+        } else {
+            self.draw_path_element(element)?;
+        }
+        self.content.restore_state();
+        Ok(())
+    }
+
+    fn draw_glyphs(&mut self, element: &Element) {
+        let Some(Fill::Solid(color)) = element.fill else {
+            return;
+        };
+        let text = attr(&element.attrs, "unicodestring").unwrap_or_default();
+        // A leading "{}" escapes text that would otherwise start with "{".
+        let text = text.strip_prefix("{}").unwrap_or(text);
+        let size = number(&element.attrs, "fontrenderingemsize").unwrap_or(0.0);
+        if text.is_empty() || size <= 0.0 {
+            return;
+        }
+        let origin_x = number(&element.attrs, "originx").unwrap_or(0.0);
+        let origin_y = number(&element.attrs, "originy").unwrap_or(0.0);
+        self.content.set_fill_rgb(color[0], color[1], color[2]);
+        self.content.begin_text();
+        self.content.set_font(Name(b"F0"), size);
+        // The page space is y-down, so flip the text back upright.
+        self.content
+            .set_text_matrix([1.0, 0.0, 0.0, -1.0, origin_x, origin_y]);
+        self.content.show(Str(&win_ansi_bytes(text)));
+        self.content.end_text();
+    }
+
+    fn draw_path_element(&mut self, element: &Element) -> PdfResult<()> {
+        let Some(data) = attr(&element.attrs, "data") else {
+            tracing::warn!("Skipping XPS path without abbreviated geometry");
+            return Ok(());
+        };
+        let Some((ops, even_odd)) = parse_path_data(data) else {
+            tracing::warn!("Skipping XPS path with unsupported geometry '{data}'");
+            return Ok(());
+        };
+        match &element.fill {
+            Some(Fill::Image { source, viewport }) => {
+                let id = self.image(source)?;
+                let name = format!("Im{}", self.used.len());
+                self.used.push((name.clone(), id));
+                let [x, y, w, h] = *viewport;
+                self.content.save_state();
+                self.draw_path(&ops);
+                if even_odd {
+                    self.content.clip_even_odd();
+                } else {
+                    self.content.clip_nonzero();
+                }
+                self.content.end_path();
+                self.content.transform([w, 0.0, 0.0, -h, x, y + h]);
+                self.content.x_object(Name(name.as_bytes()));
+                self.content.restore_state();
+            }
+            Some(Fill::Solid([r, g, b])) => {
+                self.content.set_fill_rgb(*r, *g, *b);
+            }
+            None => {}
+        }
+        let solid_fill = matches!(element.fill, Some(Fill::Solid(_)));
+        if let Some([r, g, b]) = element.stroke {
+            self.content.set_stroke_rgb(r, g, b);
+            self.content
+                .set_line_width(number(&element.attrs, "strokethickness").unwrap_or(1.0));
+        }
+        if !solid_fill && element.stroke.is_none() {
+            return Ok(());
+        }
+        self.draw_path(&ops);
+        match (solid_fill, element.stroke.is_some(), even_odd) {
+            (true, true, true) => self.content.fill_even_odd_and_stroke(),
+            (true, true, false) => self.content.fill_nonzero_and_stroke(),
+            (true, false, true) => self.content.fill_even_odd(),
+            (true, false, false) => self.content.fill_nonzero(),
+            (false, _, _) => self.content.stroke(),
+        };
+        Ok(())
+    }
+
+    /// The innermost open property element, e.g. `path.fill`.
+    fn property(&self) -> Option<&str> {
+        self.properties.last().map(String::as_str)
+    }
+
+    fn open_element(
+        &mut self,
+        name: &str,
+        attrs: Vec<(String, String)>,
+        self_closing: bool,
+    ) -> PdfResult<()> {
+        match name {
+            "fixedpage" => {
+                self.size = number(&attrs, "width").zip(number(&attrs, "height"));
+            }
+            "canvas" if !self_closing => {
+                self.content.save_state();
+                if let Some(m) = attr(&attrs, "rendertransform").and_then(parse_numbers::<6>) {
+                    self.content.transform(m);
+                }
+            }
+            "path" | "glyphs" => {
+                let element = Element::new(name, attrs);
+                if self_closing {
+                    self.draw_element(&element)?;
+                } else {
+                    self.pending = Some(element);
+                }
+            }
+            "matrixtransform" => {
+                let Some(m) = attr(&attrs, "matrix").and_then(parse_numbers::<6>) else {
+                    return Ok(());
+                };
+                match self.property() {
+                    Some("canvas.rendertransform") => {
+                        self.content.transform(m);
+                    }
+                    Some("path.rendertransform" | "glyphs.rendertransform") => {
+                        if let Some(element) = &mut self.pending {
+                            element.transform = Some(m);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "solidcolorbrush" => {
+                let color = attr(&attrs, "color").and_then(parse_color);
+                let target = self
+                    .property()
+                    .and_then(|p| p.rsplit_once('.'))
+                    .map(|(_, target)| target.to_string());
+                if let Some(element) = &mut self.pending {
+                    match target.as_deref() {
+                        Some("fill") => element.fill = color.map(Fill::Solid),
+                        Some("stroke") => element.stroke = color,
+                        _ => {}
+                    }
+                }
+            }
+            "imagebrush" => {
+                let viewport = attr(&attrs, "viewport")
+                    .and_then(parse_numbers::<4>)
+                    .unwrap_or([0.0, 0.0, 1.0, 1.0]);
+                let in_fill = self.property() == Some("path.fill");
+                match (&mut self.pending, attr(&attrs, "imagesource")) {
+                    (Some(element), Some(source)) if in_fill => {
+                        element.fill = Some(Fill::Image {
+                            source: source.to_string(),
+                            viewport,
+                        });
+                    }
+                    _ => tracing::warn!("Skipping XPS image brush outside a path fill"),
+                }
+            }
+            name if name.contains('.') && !self_closing => {
+                self.properties.push(name.to_string());
+            }
+            "lineargradientbrush"
+            | "radialgradientbrush"
+            | "visualbrush"
+            | "pathgeometry"
+            | "resourcedictionary" => {
+                tracing::warn!("Skipping unsupported XPS element <{name}>");
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn close_element(&mut self, name: &str) -> PdfResult<()> {
+        match name {
+            "canvas" => {
+                self.content.restore_state();
+            }
+            "path" | "glyphs" => {
+                if let Some(element) = self.pending.take() {
+                    self.draw_element(&element)?;
+                }
+            }
+            name if self.property() == Some(name) => {
+                self.properties.pop();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Write the page's markup, returning its size in XPS units.
+    fn run(&mut self, markup: &str) -> PdfResult<(f32, f32)> {
+        for token in tokenize(markup) {
+            match token {
+                Token::Open {
+                    name,
+                    attrs,
+                    self_closing,
+                } => self.open_element(&name, attrs, self_closing)?,
+                Token::Close(name) => self.close_element(&name)?,
+                Token::Text(_) | Token::Style(_) => {}
+            }
+        }
+        self.size
+            .ok_or_else(|| PdfError::from("XPS page has no size"))
+    }
+}
+
+fn read_text(archive: &ZipArchive, name: &str) -> PdfResult<String> {
+    Ok(String::from_utf8_lossy(&archive.read(name)?).into_owned())
+}
+
+fn dir_of(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// The `Source` of each `child` element in the part at `path`, resolved to
+/// entry names.
+fn sources(archive: &ZipArchive, path: &str, child: &str) -> PdfResult<Vec<String>> {
+    Ok(tokenize(&read_text(archive, path)?)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Open { name, attrs, .. } if name == child => {
+                attr(&attrs, "source").map(|s| resolve_path(dir_of(path), s))
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// The entry names of every fixed page, in reading order.
+fn page_parts(archive: &ZipArchive) -> PdfResult<Vec<String>> {
+    let sequence = archive
+        .read("_rels/.rels")
+        .ok()
+        .and_then(|rels| {
+            tokenize(&String::from_utf8_lossy(&rels))
+                .into_iter()
+                .find_map(|token| match token {
+                    Token::Open { name, attrs, .. }
+                        if name == "relationship"
+                            && matches!(
+                                attr(&attrs, "type"),
+                                Some(FIXED_REPRESENTATION | OXPS_FIXED_REPRESENTATION)
+                            ) =>
+                    {
+                        attr(&attrs, "target").map(|t| resolve_path("", t))
+                    }
+                    _ => None,
+                })
+        })
+        .or_else(|| {
+            archive
+                .entries()
+                .iter()
+                .find(|e| e.name.to_ascii_lowercase().ends_with(".fdseq"))
+                .map(|e| e.name.clone())
+        })
+        .ok_or_else(|| PdfError::from("XPS package has no fixed document sequence"))?;
+    let mut pages = Vec::new();
+    for document in sources(archive, &sequence, "documentreference")? {
+        pages.extend(sources(archive, &document, "pagecontent")?);
+    }
+    Ok(pages)
+}
+
+/// Build a PDF with the pages of every fixed document in the package.
+pub fn xps_to_pdf(data: Vec<u8>) -> PdfResult<Document> {
+    let archive = ZipArchive::new(data)?;
+    let parts = page_parts(&archive)?;
+    if parts.is_empty() {
+        return Err(PdfError::from("XPS document has no pages"));
+    }
+
+    let mut doc = Document::with_version("1.7");
+    let font_id = helvetica_font(&mut doc);
+    let tree_id = doc.new_object_id();
+    let mut images = HashMap::new();
+    let mut kids = Vec::with_capacity(parts.len());
+    for part in &parts {
+        let markup = read_text(&archive, part)?;
+        let mut writer = PageWriter {
+            archive: &archive,
+            doc: &mut doc,
+            base: dir_of(part).to_string(),
+            content: Content::new(),
+            images: &mut images,
+            used: Vec::new(),
+            size: None,
+            pending: None,
+            properties: Vec::new(),
+        };
+        // Run the markup first to learn the page size, then prefix the
+        // flip from XPS's y-down units into PDF points.
+        let (width, height) = writer.run(&markup)?;
+        let (width, height) = (width * UNIT, height * UNIT);
+        let used = std::mem::take(&mut writer.used);
+        let body = std::mem::replace(&mut writer.content, Content::new()).finish();
+        let mut stream = format!("q {UNIT} 0 0 -{UNIT} 0 {height} cm\n").into_bytes();
+        stream.extend_from_slice(&body);
+        stream.extend_from_slice(b"\nQ");
+
+        let content_id = doc.add_object(lopdf::Stream::new(lopdf::Dictionary::new(), stream));
+        let mut resources = lopdf::Dictionary::from_iter(vec![(
+            "Font",
+            Object::Dictionary(lopdf::Dictionary::from_iter(vec![(
+                "F0",
+                Object::Reference(font_id),
+            )])),
+        )]);
+        if !used.is_empty() {
+            resources.set(
+                "XObject",
+                Object::Dictionary(lopdf::Dictionary::from_iter(
+                    used.into_iter()
+                        .map(|(name, id)| (name.into_bytes(), Object::Reference(id))),
+                )),
+            );
+        }
+        let page_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(tree_id)),
+            (
+                "MediaBox",
+                Object::Array(vec![
+                    Object::Real(0.0),
+                    Object::Real(0.0),
+                    Object::Real(width),
+                    Object::Real(height),
+                ]),
+            ),
+            ("Contents", Object::Reference(content_id)),
+            ("Resources", Object::Dictionary(resources)),
+        ])));
+        kids.push(Object::Reference(page_id));
+    }
+    doc.objects.insert(
+        tree_id,
+        Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Count", Object::Integer(kids.len() as i64)),
+            ("Kids", Object::Array(kids)),
+        ])),
+    );
+    let catalog_id = doc.add_object(Object::Dictionary(lopdf::Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Catalog".to_vec())),
+        ("Pages", Object::Reference(tree_id)),
+    ])));
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+    Ok(doc)
+}
+
+/// A one-page package for tests: a red square inside a translated canvas,
+/// a line of text and a gradient-filled path, which is skipped.
+#[cfg(test)]
+pub(crate) fn sample_xps() -> Vec<u8> {
+    let rels = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+          <Relationship Id="R0" Type="{FIXED_REPRESENTATION}" Target="/FixedDocSeq.fdseq"/>
+        </Relationships>"#
+    );
+    let sequence = r#"<FixedDocumentSequence xmlns="http://schemas.microsoft.com/xps/2005/06">
+          <DocumentReference Source="Documents/1/FixedDoc.fdoc"/>
+        </FixedDocumentSequence>"#;
+    let document = r#"<FixedDocument xmlns="http://schemas.microsoft.com/xps/2005/06">
+          <PageContent Source="Pages/1.fpage"/>
+        </FixedDocument>"#;
+    let page = r##"<FixedPage Width="816" Height="1056"
+          xmlns="http://schemas.microsoft.com/xps/2005/06" xml:lang="en-US">
+          <Canvas RenderTransform="1,0,0,1,96,96">
+            <Path Data="M 0,0 H 200 V 200 H 0 Z" Fill="#FFFF0000"/>
+          </Canvas>
+          <Glyphs OriginX="96" OriginY="400" FontRenderingEmSize="24"
+            FontUri="/Resources/font.odttf" UnicodeString="Hello XPS" Fill="#FF000000"/>
+          <Path Data="M 400,400 L 600,400 600,600 Z">
+            <Path.Fill>
+              <LinearGradientBrush StartPoint="0,0" EndPoint="1,1"/>
+            </Path.Fill>
+          </Path>
+        </FixedPage>"##;
+    crate::archive::write_stored_zip(&[
+        ("_rels/.rels", rels.as_bytes()),
+        ("FixedDocSeq.fdseq", sequence.as_bytes()),
+        ("Documents/1/FixedDoc.fdoc", document.as_bytes()),
+        ("Documents/1/Pages/1.fpage", page.as_bytes()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path_data() {
+        let (ops, even_odd) = parse_path_data("F1 M10,10 l 20,0 h-5 V 30 Q 0,30 0,20 z").unwrap();
+        assert!(!even_odd);
+        assert_eq!(
+            ops,
+            [
+                PathOp::Move(10.0, 10.0),
+                PathOp::Line(30.0, 10.0),
+                PathOp::Line(25.0, 10.0),
+                PathOp::Line(25.0, 30.0),
+                PathOp::Cubic(
+                    25.0 + (0.0 - 25.0) * 2.0 / 3.0,
+                    30.0,
+                    0.0,
+                    20.0 + (30.0 - 20.0) * 2.0 / 3.0,
+                    0.0,
+                    20.0
+                ),
+                PathOp::Close,
+            ]
+        );
+        // Pairs after a move continue as lines; exponents are numbers.
+        let (ops, even_odd) = parse_path_data("M 0 0 1e1,0 10 -1E1").unwrap();
+        assert!(even_odd);
+        assert_eq!(
+            ops[1..],
+            [PathOp::Line(10.0, 0.0), PathOp::Line(10.0, -10.0)]
+        );
+        assert!(parse_path_data("M 0,0 A 5,5 0 0 1 10,10").is_none());
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#FF0000"), Some([1.0, 0.0, 0.0]));
+        assert_eq!(parse_color("#8000FF00"), Some([0.0, 1.0, 0.0]));
+        assert_eq!(parse_color("#00FFFFFF"), None);
+        assert_eq!(parse_color("sc#1,0,0,1"), Some([0.0, 0.0, 1.0]));
+        assert_eq!(parse_color("red"), None);
+    }
+
+    #[test]
+    fn test_pages_and_sizes() {
+        let doc = xps_to_pdf(sample_xps()).unwrap();
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 1);
+        let page = doc.get_dictionary(pages[&1]).unwrap();
+        let media_box: Vec<f32> = page
+            .get(b"MediaBox")
+            .and_then(Object::as_array)
+            .unwrap()
+            .iter()
+            .map(|v| v.as_float().unwrap())
+            .collect();
+        assert_eq!(media_box, [0.0, 0.0, 612.0, 792.0]);
+    }
+}